@@ -147,6 +147,14 @@ impl PostingDomain {
     pub fn after_amount(&self) -> Amount {
         Amount(self.0.after_amount.clone())
     }
+    #[getter]
+    pub fn reconcile_state(&self) -> String {
+        self.0.reconcile_state.as_ref().to_string()
+    }
+    #[getter]
+    pub fn statement_date(&self) -> Option<String> {
+        self.0.statement_date.map(|it| it.to_string())
+    }
 }
 
 #[pyclass]
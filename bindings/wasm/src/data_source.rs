@@ -1,6 +1,6 @@
 use zhang_core::data_source::{DataSource, LoadResult};
 use zhang_core::data_type::DataType;
-use zhang_core::ZhangResult;
+use zhang_core::{ZhangError, ZhangResult};
 
 pub struct InMemoryDataSource {
     pub data_type: Box<dyn DataType<Carrier = String> + 'static + Send + Sync>,
@@ -10,8 +10,13 @@ impl DataSource for InMemoryDataSource {
     fn load(&self, _entry: String, _endpoint: String) -> ZhangResult<LoadResult> {
         let directive = self.data_type.transform(_entry, None)?;
         Ok(LoadResult {
-            directives: directive,
+            runs: vec![directive],
             visited_files: vec![],
         })
     }
+
+    /// the ledger in memory has no file, e.g. the documents referenced by it
+    fn get(&self, path: String) -> ZhangResult<Vec<u8>> {
+        Err(ZhangError::CustomError(format!("file {} is not available in memory", path)))
+    }
 }
@@ -83,7 +83,7 @@ pub fn parse(content: &str) -> PlayGroundParse {
     let (is_zhang_pass, zhang_store, zhang_error_msg) = match zhang_parse_result {
         Ok(data) => {
             let result = Ledger::process(LedgerProcessContext {
-                directives: data,
+                runs: vec![data],
                 entry: (PathBuf::from("/"), "".to_owned()),
                 visited_files: vec![],
                 data_source: source.clone(),
@@ -99,7 +99,7 @@ pub fn parse(content: &str) -> PlayGroundParse {
     let (is_beancount_pass, beancount_store, beancount_error_msg) = match beancount_parse_result {
         Ok(data) => {
             let result = Ledger::process(LedgerProcessContext {
-                directives: data,
+                runs: vec![data],
                 entry: (PathBuf::from("/"), "".to_owned()),
                 visited_files: vec![],
                 data_source: source.clone(),
@@ -0,0 +1,533 @@
+//! fidelity mode of beancount exporter, which converts the whole directive stream of ledger into the syntax accepted by beancount itself,
+//! so that the exported file can be checked by `bean-check` or used to migrate out of zhang.
+//!
+//! compared with [Beancount](crate::Beancount)'s export, which keeps the zhang flavored syntax for writing back into the ledger files,
+//! this mode:
+//! - exports transaction metas before postings, and always exports the flag of transaction
+//! - quotes meta values which are not valid beancount literals
+//! - exports budget, split, depreciation, loan, accrue and open-balance directives as quoted `custom` directives, which are converted back into zhang directives on parsing
+//! - comments out zhang only options, plugins and renames, and skips includes since the stream is flattened already
+
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use itertools::Itertools;
+use zhang_ast::amount::Amount;
+use zhang_ast::*;
+use zhang_core::data_type::text::exporter::{export_posting_account, ZhangDataTypeExportable};
+use zhang_core::data_type::DataType;
+use zhang_core::ledger::Ledger;
+use zhang_core::utils::string_::escape_with_quote;
+
+use crate::{convert_datetime_to_date, Beancount};
+
+/// options supported by beancount, other options are zhang only
+const BEANCOUNT_OPTIONS: [&str; 24] = [
+    "title",
+    "name_assets",
+    "name_liabilities",
+    "name_equity",
+    "name_income",
+    "name_expenses",
+    "account_previous_balances",
+    "account_previous_earnings",
+    "account_previous_conversions",
+    "account_current_earnings",
+    "account_current_conversions",
+    "account_unrealized_gains",
+    "account_rounding",
+    "conversion_currency",
+    "inferred_tolerance_default",
+    "inferred_tolerance_multiplier",
+    "infer_tolerance_from_cost",
+    "documents",
+    "operating_currency",
+    "render_commas",
+    "plugin_processing_mode",
+    "long_string_maxlines",
+    "booking_method",
+    "insert_pythonpath",
+];
+
+/// export the options and dated directives of ledger in fidelity mode
+pub fn export_ledger(ledger: &Ledger) -> String {
+    export_directives(ledger.metas.iter().chain(ledger.directives.iter()).cloned())
+}
+
+/// export the directives in fidelity mode, directives are separated by an empty line
+pub fn export_directives(directives: impl IntoIterator<Item = Spanned<Directive>>) -> String {
+    let mut content = directives.into_iter().filter_map(export_directive).join("\n\n");
+    content.push('\n');
+    content
+}
+
+fn export_directive(directive: Spanned<Directive>) -> Option<String> {
+    let Spanned { data, span } = convert_datetime_to_date(directive);
+    let exported = match data {
+        // beancount has no void flag, the voided transaction is kept as comment so that it is not posted
+        Directive::Transaction(trx) if trx.is_voided() => transaction(trx).lines().map(|it| format!("; {}", it)).join("\n"),
+        Directive::Transaction(trx) => transaction(trx),
+        Directive::Option(options) => {
+            let line = options.export();
+            if BEANCOUNT_OPTIONS.contains(&options_key(&line)) {
+                line
+            } else {
+                format!("; {}", line)
+            }
+        }
+        Directive::Plugin(plugin) => format!("; {}", plugin.export()),
+        Directive::Rename(rename) => format!("; {}", rename.export()),
+        Directive::Template(template) => template.export().lines().map(|it| format!("; {}", it)).join("\n"),
+        Directive::Include(_) => return None,
+        Directive::Comment(comment) => {
+            let content = comment.content.trim_start();
+            if content.starts_with(';') {
+                content.to_owned()
+            } else {
+                format!("; {}", content.trim_start_matches(['*', '#', '/']).trim_start())
+            }
+        }
+        Directive::Budget(budget) => custom(
+            budget.date,
+            "budget",
+            vec![ZhangString::quote(budget.name), ZhangString::quote(budget.commodity)],
+            budget.meta,
+        ),
+        Directive::BudgetAdd(budget) => {
+            let mut values = vec![ZhangString::quote(budget.name)];
+            values.extend(amount_values(budget.amount));
+            custom(budget.date, "budget-add", values, budget.meta)
+        }
+        Directive::BudgetTransfer(budget) => {
+            let mut values = vec![ZhangString::quote(budget.from), ZhangString::quote(budget.to)];
+            values.extend(amount_values(budget.amount));
+            custom(budget.date, "budget-transfer", values, budget.meta)
+        }
+        Directive::BudgetClose(budget) => custom(budget.date, "budget-close", vec![ZhangString::quote(budget.name)], budget.meta),
+        Directive::Split(split) => custom(
+            split.date,
+            "split",
+            vec![
+                ZhangString::quote(split.currency),
+                ZhangString::quote(format!("{}:{}", split.numerator, split.denominator)),
+            ],
+            split.meta,
+        ),
+        Directive::Depreciation(depreciation) => {
+            let mut values = vec![
+                ZhangString::unquote(depreciation.account.content),
+                ZhangString::unquote(depreciation.expense_account.content),
+            ];
+            values.extend(amount_values(depreciation.amount));
+            values.push(ZhangString::quote(depreciation.method.to_string()));
+            values.push(ZhangString::unquote(depreciation.lifetime.to_string()));
+            custom(depreciation.date, "depreciation", values, depreciation.meta)
+        }
+        Directive::Loan(loan) => {
+            let mut values = vec![ZhangString::unquote(loan.account.content)];
+            values.extend(amount_values(loan.principal));
+            values.push(ZhangString::unquote(loan.rate.to_string()));
+            values.push(ZhangString::unquote(loan.term.to_string()));
+            values.push(ZhangString::unquote(loan.payment_account.content));
+            values.push(ZhangString::unquote(loan.interest_account.content));
+            custom(loan.date, "loan", values, loan.meta)
+        }
+        Directive::Accrue(accrue) => {
+            let mut values = vec![
+                ZhangString::unquote(accrue.deferral_account.content),
+                ZhangString::unquote(accrue.account.content),
+            ];
+            values.extend(amount_values(accrue.amount));
+            values.push(ZhangString::unquote(accrue.periods.to_string()));
+            values.push(ZhangString::quote(accrue.interval.to_string()));
+            custom(accrue.date, "accrue", values, accrue.meta)
+        }
+        Directive::OpenBalance(open_balance) => {
+            let mut values = vec![ZhangString::unquote(open_balance.account.content)];
+            values.extend(amount_values(open_balance.amount));
+            values.extend(open_balance.equity_account.map(|it| ZhangString::unquote(it.content)));
+            custom(open_balance.date, "open-balance", values, open_balance.meta)
+        }
+        Directive::Custom(mut custom) => {
+            custom.custom_type = ZhangString::quote(custom.custom_type.to_plain_string());
+            custom.meta = quote_meta(custom.meta);
+            Beancount {}.export(Spanned::new(Directive::Custom(custom), span))
+        }
+        other => {
+            let meta = quote_meta(other.get_meta().cloned().unwrap_or_default());
+            Beancount {}.export(Spanned::new(other.set_meta(meta), span))
+        }
+    };
+    Some(exported)
+}
+
+fn options_key(line: &str) -> &str {
+    line.trim_start_matches("option").trim().split('"').nth(1).unwrap_or_default()
+}
+
+fn quote(content: &str) -> String {
+    escape_with_quote(content).to_string()
+}
+
+fn amount_values(amount: Amount) -> Vec<ZhangString> {
+    vec![ZhangString::unquote(amount.number.to_string()), ZhangString::unquote(amount.currency)]
+}
+
+fn custom(date: Date, custom_type: &str, values: Vec<ZhangString>, meta: Meta) -> String {
+    let custom = Custom {
+        date,
+        custom_type: ZhangString::quote(custom_type),
+        values: values.into_iter().map(StringOrAccount::String).collect_vec(),
+        meta: quote_meta(meta),
+    };
+    Beancount {}.export(Spanned::new(Directive::Custom(custom), SpanInfo::default()))
+}
+
+/// whether the unquoted value can be written as is in beancount, e.g. number, date, bool, account or currency
+fn is_beancount_literal(value: &str) -> bool {
+    let is_currency = value.chars().next().map(|it| it.is_ascii_uppercase()).unwrap_or(false)
+        && value.chars().all(|it| it.is_ascii_uppercase() || it.is_ascii_digit() || "'._-".contains(it));
+    BigDecimal::from_str(value).is_ok()
+        || NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok()
+        || value.eq("TRUE")
+        || value.eq("FALSE")
+        || (value.contains(':') && Account::from_str(value).is_ok())
+        || is_currency
+}
+
+fn quote_meta(meta: Meta) -> Meta {
+    let mut ret = Meta::default();
+    for (key, value) in meta.get_flatten() {
+        let value = match value {
+            ZhangString::UnquoteString(value) if !is_beancount_literal(&value) => ZhangString::QuoteString(value),
+            value => value,
+        };
+        ret.insert(key, value);
+    }
+    ret
+}
+
+fn meta_lines(meta: Meta, indent: &str) -> Vec<String> {
+    quote_meta(meta).export().into_iter().map(|it| format!("{}{}", indent, it)).collect_vec()
+}
+
+fn transaction(trx: Transaction) -> String {
+    let mut header = vec![trx.date.export(), trx.flag.unwrap_or(Flag::Okay).export()];
+    match (trx.payee, trx.narration) {
+        (Some(payee), narration) => {
+            header.push(quote(payee.as_str()));
+            header.push(quote(narration.as_ref().map(|it| it.as_str()).unwrap_or_default()));
+        }
+        (None, narration) => header.push(quote(narration.as_ref().map(|it| it.as_str()).unwrap_or_default())),
+    }
+    header.extend(trx.tags.into_iter().map(|it| format!("#{}", it)));
+    header.extend(trx.links.into_iter().map(|it| format!("^{}", it)));
+
+    let mut lines = vec![header.join(" ")];
+    lines.extend(meta_lines(trx.meta, "  "));
+    for posting in trx.postings {
+        let cost = match (posting.cost, posting.cost_date, posting.cost_label) {
+            (None, None, None) => None,
+            (cost, date, label) => Some(format!(
+                "{{{}}}",
+                [cost.map(|it| it.export()), date.map(|it| it.export()), label.map(|it| quote(&it))]
+                    .into_iter()
+                    .flatten()
+                    .join(", ")
+            )),
+        };
+        let line = [
+            posting.flag.map(|it| it.export()),
+            Some(export_posting_account(posting.posting_type, posting.account)),
+            posting.units.map(|it| it.export()),
+            cost,
+            posting.price.map(|it| it.export()),
+            posting.comment.map(|it| format!("; {}", it)),
+        ]
+        .into_iter()
+        .flatten()
+        .join(" ");
+        lines.push(format!("  {}", line));
+        lines.extend(meta_lines(posting.meta, "    "));
+    }
+    lines.join("\n")
+}
+
+/// convert the budget, split, depreciation, loan, accrue and open-balance `custom` directives exported in fidelity mode back into zhang directives
+pub(crate) fn directive_from_custom(custom: Custom) -> Directive {
+    let values = custom
+        .values
+        .iter()
+        .map(|value| match value {
+            StringOrAccount::String(value) => value.as_str(),
+            StringOrAccount::Account(account) => account.content.as_str(),
+        })
+        .collect_vec();
+    let amount = |number: &str, currency: &str| BigDecimal::from_str(number).ok().map(|number| Amount::new(number, currency));
+    let date = custom.date.clone();
+    let meta = custom.meta.clone();
+    let directive = match (custom.custom_type.as_str(), values.as_slice()) {
+        ("budget", [name, commodity]) => Some(Directive::Budget(Budget {
+            date,
+            name: name.to_string(),
+            commodity: commodity.to_string(),
+            meta,
+        })),
+        ("budget-add", [name, number, currency]) => amount(number, currency).map(|amount| {
+            Directive::BudgetAdd(BudgetAdd {
+                date,
+                name: name.to_string(),
+                amount,
+                meta,
+            })
+        }),
+        ("budget-transfer", [from, to, number, currency]) => amount(number, currency).map(|amount| {
+            Directive::BudgetTransfer(BudgetTransfer {
+                date,
+                from: from.to_string(),
+                to: to.to_string(),
+                amount,
+                meta,
+            })
+        }),
+        ("budget-close", [name]) => Some(Directive::BudgetClose(BudgetClose {
+            date,
+            name: name.to_string(),
+            meta,
+        })),
+        ("split", [currency, ratio]) => ratio.split_once(':').and_then(|(numerator, denominator)| {
+            let numerator = BigDecimal::from_str(numerator).ok()?;
+            let denominator = BigDecimal::from_str(denominator).ok()?;
+            Some(Directive::Split(Split {
+                date,
+                currency: currency.to_string(),
+                numerator,
+                denominator,
+                meta,
+            }))
+        }),
+        ("depreciation", [account, expense_account, number, currency, method, lifetime]) => match (
+            Account::from_str(account),
+            Account::from_str(expense_account),
+            amount(number, currency),
+            DepreciationMethod::from_str(method),
+            lifetime.parse::<u32>(),
+        ) {
+            (Ok(account), Ok(expense_account), Some(amount), Ok(method), Ok(lifetime)) => Some(Directive::Depreciation(Depreciation {
+                date,
+                account,
+                expense_account,
+                amount,
+                method,
+                lifetime,
+                meta,
+            })),
+            _ => None,
+        },
+        ("loan", [account, number, currency, rate, term, payment_account, interest_account]) => match (
+            Account::from_str(account),
+            amount(number, currency),
+            BigDecimal::from_str(rate),
+            term.parse::<u32>(),
+            Account::from_str(payment_account),
+            Account::from_str(interest_account),
+        ) {
+            (Ok(account), Some(principal), Ok(rate), Ok(term), Ok(payment_account), Ok(interest_account)) => Some(Directive::Loan(Loan {
+                date,
+                account,
+                principal,
+                rate,
+                term,
+                payment_account,
+                interest_account,
+                meta,
+            })),
+            _ => None,
+        },
+        ("accrue", [deferral_account, account, number, currency, periods, interval]) => match (
+            Account::from_str(deferral_account),
+            Account::from_str(account),
+            amount(number, currency),
+            periods.parse::<u32>(),
+            AccrualInterval::from_str(interval),
+        ) {
+            (Ok(deferral_account), Ok(account), Some(amount), Ok(periods), Ok(interval)) => Some(Directive::Accrue(Accrue {
+                date,
+                deferral_account,
+                account,
+                amount,
+                periods,
+                interval,
+                meta,
+            })),
+            _ => None,
+        },
+        ("open-balance", [account, number, currency, equity_account @ ..]) if equity_account.len() <= 1 => {
+            let equity_account = equity_account.first().map(|it| Account::from_str(it)).transpose();
+            match (Account::from_str(account), amount(number, currency), equity_account) {
+                (Ok(account), Some(amount), Ok(equity_account)) => Some(Directive::OpenBalance(OpenBalance {
+                    date,
+                    account,
+                    amount,
+                    equity_account,
+                    meta,
+                })),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    directive.unwrap_or(Directive::Custom(custom))
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+    use itertools::Itertools;
+    use zhang_ast::{Directive, Flag, Spanned};
+    use zhang_core::data_type::text::ZhangDataType;
+    use zhang_core::data_type::DataType;
+
+    use crate::exporter::export_directives;
+    use crate::Beancount;
+
+    fn parse_zhang(content: &str) -> Vec<Spanned<Directive>> {
+        ZhangDataType {}.transform(content.to_owned(), None).unwrap()
+    }
+
+    #[test]
+    fn should_export_transaction_in_beancount_syntax() {
+        let directives = parse_zhang(indoc! {r#"
+            2024-01-01 10:11:12 "Broker" "Buy stock" #invest ^order-1
+              Assets:Stock 10 AAPL { 100 USD, 2024-01-01 }
+                lot: first
+              Assets:Bank -1000 USD
+              source: broker
+              amount: 1000
+        "#});
+        assert_eq!(
+            indoc! {r#"
+                2024-01-01 * "Broker" "Buy stock" #invest ^order-1
+                  amount: 1000
+                  source: "broker"
+                  time: "10:11:12"
+                  Assets:Stock 10 AAPL {100 USD, 2024-01-01}
+                    lot: "first"
+                  Assets:Bank -1000 USD
+            "#},
+            export_directives(directives)
+        );
+    }
+
+    #[test]
+    fn should_export_virtual_postings() {
+        let directives = parse_zhang(indoc! {r#"
+            2024-01-01 * "Salary"
+              Assets:Bank 100 USD
+              Income:Salary -100 USD
+              (Budget:Food) 30 USD
+              [Assets:Savings] 20 USD
+              [Equity:Savings] -20 USD
+        "#});
+        assert_eq!(
+            indoc! {r#"
+                2024-01-01 * "Salary"
+                  Assets:Bank 100 USD
+                  Income:Salary -100 USD
+                  (Budget:Food) 30 USD
+                  [Assets:Savings] 20 USD
+                  [Equity:Savings] -20 USD
+            "#},
+            export_directives(directives)
+        );
+    }
+
+    #[test]
+    fn should_comment_out_zhang_only_directives() {
+        let directives = parse_zhang(indoc! {r#"
+            option "operating_currency" "CNY"
+            option "timezone" "Asia/Hong_Kong"
+            include "other.zhang"
+            2024-01-01 budget Food CNY
+            2024-01-02 budget-add Food 100 CNY
+        "#});
+        assert_eq!(
+            indoc! {r#"
+                option "operating_currency" "CNY"
+
+                ; option "timezone" "Asia/Hong_Kong"
+
+                2024-01-01 custom "budget" "Food" "CNY"
+
+                2024-01-02 custom "budget-add" "Food" 100 CNY
+            "#},
+            export_directives(directives)
+        );
+    }
+
+    #[test]
+    fn should_keep_semantic_equivalence_after_round_trip() {
+        let original = parse_zhang(indoc! {r#"
+            option "operating_currency" "CNY"
+            1970-01-01 commodity AAPL
+              precision: 2
+            1970-01-01 open Assets:Stock AAPL
+            1970-01-01 open Assets:Bank CNY, USD
+              alias: "bank account"
+            1970-01-01 open Expenses:Food
+            1970-01-01 open Equity:Open
+            2024-01-01 budget Food CNY
+            2024-01-02 budget-add Food 100 CNY
+            2024-01-03 balance Assets:Bank 100 CNY with pad Equity:Open
+            2024-01-04 price AAPL 150 USD
+            2024-01-04 split AAPL 4:1
+            2024-01-04 depreciation Assets:Bank Expenses:Food 1200 CNY straight-line 12
+            2024-01-04 loan Liabilities:Mortgage 300000 CNY 4.9% 360 Assets:Bank Expenses:Food
+            2024-01-04 accrue Assets:Bank Expenses:Food 1200 CNY 12 monthly
+            2024-01-04 open-balance Assets:Cash 100 CNY
+            2024-01-04 open-balance Assets:Wallet 50 CNY Equity:Open
+            2024-01-05 ! "KFC" "Dinner" #eat ^dinner
+              Assets:Bank -50 CNY
+              Expenses:Food 50 CNY
+                category: dinner
+              place: "Town"
+            2024-01-06 "Buy stock"
+              Assets:Stock 10 AAPL { 150 USD } @ 151 USD
+              Assets:Bank -1500 USD
+            2024-01-07 custom "note" "value"
+        "#});
+        let exported = export_directives(original.clone());
+        let round_tripped = Beancount {}.transform(exported.clone(), None).unwrap();
+
+        let strip = |directives: Vec<Spanned<Directive>>| directives.into_iter().map(|it| it.data).collect_vec();
+        let round_tripped = strip(round_tripped);
+        assert_eq!(original.len(), round_tripped.len());
+        for (original, round_tripped) in strip(original).into_iter().zip(round_tripped) {
+            match (&original, &round_tripped) {
+                (Directive::Transaction(a), Directive::Transaction(b)) => {
+                    assert_eq!(a.date, b.date);
+                    assert_eq!(a.payee.as_ref().map(|it| it.as_str()), b.payee.as_ref().map(|it| it.as_str()));
+                    // the missing narration is exported as empty string since beancount requires narration along with payee
+                    assert_eq!(
+                        a.narration.as_ref().map(|it| it.as_str()).unwrap_or_default(),
+                        b.narration.as_ref().map(|it| it.as_str()).unwrap_or_default()
+                    );
+                    assert_eq!(a.flag.clone().unwrap_or(Flag::Okay), b.flag.clone().unwrap_or(Flag::Okay));
+                    assert_eq!(a.tags, b.tags);
+                    assert_eq!(a.links, b.links);
+                    assert_eq!(a.postings.len(), b.postings.len());
+                    for (a, b) in a.postings.iter().zip(&b.postings) {
+                        assert_eq!(a.account, b.account);
+                        assert_eq!(a.units, b.units);
+                        assert_eq!(a.cost, b.cost);
+                        assert_eq!(a.price, b.price);
+                        assert_eq!(a.meta.clone().get_flatten().len(), b.meta.clone().get_flatten().len());
+                    }
+                }
+                (a, b) => assert_eq!(a.directive_type(), b.directive_type()),
+            }
+        }
+        assert_eq!(exported, export_directives(Beancount {}.transform(exported.clone(), None).unwrap()));
+    }
+}
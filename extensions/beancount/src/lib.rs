@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::path::PathBuf;
 
-use chrono::NaiveDate;
+use chrono::{DateTime, NaiveDate};
 use itertools::{Either, Itertools};
 use latestmap::LatestMap;
 use zhang_ast::*;
@@ -11,6 +11,7 @@ use zhang_core::data_type::DataType;
 use zhang_core::{ZhangError, ZhangResult};
 
 use crate::directives::{BalanceDirective, BeancountDirective, BeancountOnlyDirective, PadDirective};
+use crate::exporter::directive_from_custom;
 use crate::parser::{parse, parse_time};
 
 #[allow(clippy::upper_case_acronyms)]
@@ -18,6 +19,7 @@ use crate::parser::{parse, parse_time};
 pub mod parser;
 
 pub mod directives;
+pub mod exporter;
 
 #[derive(Clone, Default)]
 pub struct Beancount {}
@@ -51,6 +53,10 @@ impl DataType for Beancount {
                             data: Directive::Transaction(trx),
                         });
                     }
+                    Directive::Custom(custom) => ret.push(Spanned {
+                        span,
+                        data: directive_from_custom(custom),
+                    }),
                     _ => ret.push(Spanned { span, data: zhang_directive }),
                 },
                 Either::Right(beancount_directive) => match beancount_directive {
@@ -216,13 +222,20 @@ impl BeancountOnlyExportable for PadDirective {
 
 macro_rules! convert_to_datetime {
     ($directive: expr) => {
-        if let Date::Datetime(datetime) = $directive.date {
+        if let Date::Datetime(datetime) | Date::DateHour(datetime) = $directive.date {
             let (date, time) = (datetime.date(), datetime.time());
             $directive.date = Date::Date(date);
             $directive
                 .meta
                 .insert("time".to_string(), ZhangString::QuoteString(time.format("%H:%M:%S").to_string()));
             $directive
+        } else if let Date::ZonedDatetime(datetime) = $directive.date {
+            // the offset is kept in the time meta, e.g. `time: "09:30:00+09:00"`
+            $directive.date = Date::Date(datetime.date_naive());
+            $directive
+                .meta
+                .insert("time".to_string(), ZhangString::QuoteString(datetime.format("%H:%M:%S%:z").to_string()));
+            $directive
         } else {
             $directive
         }
@@ -238,6 +251,7 @@ fn convert_datetime_to_date(directive: Spanned<Directive>) -> Spanned<Directive>
         Directive::Transaction(mut directive) => Directive::Transaction(convert_to_datetime!(directive)),
         Directive::BalanceCheck(mut directive) => Directive::BalanceCheck(convert_to_datetime!(directive)),
         Directive::BalancePad(mut directive) => Directive::BalancePad(convert_to_datetime!(directive)),
+        Directive::Pad(mut directive) => Directive::Pad(convert_to_datetime!(directive)),
         Directive::Note(mut directive) => Directive::Note(convert_to_datetime!(directive)),
         Directive::Document(mut directive) => Directive::Document(convert_to_datetime!(directive)),
         Directive::Price(mut directive) => Directive::Price(convert_to_datetime!(directive)),
@@ -248,11 +262,21 @@ fn convert_datetime_to_date(directive: Spanned<Directive>) -> Spanned<Directive>
     Spanned::new(data, span)
 }
 
+/// the datetime of date with time meta, which is zoned if the time has offset like `09:30:00+09:00`
+fn date_with_time(date: NaiveDate, time: &str) -> Option<Date> {
+    if let Ok(datetime) = DateTime::parse_from_str(&format!("{} {}", date.format("%Y-%m-%d"), time.trim()), "%Y-%m-%d %H:%M:%S%:z") {
+        return Some(Date::ZonedDatetime(datetime));
+    }
+    parse_time(time).ok().map(|time| Date::Datetime(date.and_time(time)))
+}
+
 macro_rules! extract_time {
     ($directive: tt) => {{
-        let time = $directive.meta.pop_one("time").and_then(|it| parse_time(it.as_str()).ok());
-        if let Some(time) = time {
-            $directive.date = Date::Datetime($directive.date.naive_date().and_time(time));
+        if let Some(time) = $directive.meta.pop_one("time") {
+            match date_with_time($directive.date.naive_date(), time.as_str()) {
+                Some(date) => $directive.date = date,
+                None => $directive.meta.insert("time".to_string(), time),
+            }
         }
     }};
 }
@@ -267,6 +291,7 @@ impl Beancount {
                 Directive::Transaction(directive) => extract_time!(directive),
                 Directive::BalanceCheck(balance_check) => extract_time!(balance_check),
                 Directive::BalancePad(balance_pad) => extract_time!(balance_pad),
+                Directive::Pad(pad) => extract_time!(pad),
                 Directive::Note(directive) => extract_time!(directive),
                 Directive::Document(directive) => extract_time!(directive),
                 Directive::Price(directive) => extract_time!(directive),
@@ -288,7 +313,7 @@ mod test {
     use std::str::FromStr;
 
     use bigdecimal::BigDecimal;
-    use chrono::NaiveDate;
+    use chrono::{DateTime, NaiveDate};
     use indoc::indoc;
     use zhang_ast::amount::Amount;
     use zhang_ast::{Account, BalanceCheck, BalancePad, Date, Directive, Meta, Open, SpanInfo, Spanned};
@@ -505,4 +530,25 @@ mod test {
             })
         );
     }
+
+    #[test]
+    fn should_keep_timezone_offset_in_time_meta() {
+        let mut directive = test_parse_zhang! {"1970-01-02 open Assets:BankAccount"};
+        match &mut directive {
+            Directive::Open(ref mut open) => open.date = Date::ZonedDatetime(DateTime::parse_from_rfc3339("1970-01-02T01:02:03+09:00").unwrap()),
+            _ => unreachable!("only open directive"),
+        }
+        let beancount_data_type = Beancount::default();
+        let exported = beancount_data_type.export(Spanned::new(directive.clone(), SpanInfo::default()));
+        assert_eq!(
+            indoc! {r#"
+                1970-01-02 open Assets:BankAccount
+                  time: "01:02:03+09:00"
+            "#}
+            .trim(),
+            exported
+        );
+        let mut directives = beancount_data_type.transform(exported, None).unwrap();
+        assert_eq!(directive, directives.pop().unwrap().data);
+    }
 }
@@ -22,6 +22,21 @@ type Node<'i> = pest_consume::Node<'i, Rule, ()>;
 #[grammar = "beancount.pest"]
 pub struct BeancountParser;
 
+/// the cost, cost date, lot label and price of posting
+#[derive(Default)]
+struct PostingMeta {
+    cost: Option<Amount>,
+    cost_date: Option<Date>,
+    cost_label: Option<String>,
+    price: Option<SingleTotalPrice>,
+}
+
+enum LotComponent {
+    Cost(Amount),
+    Date(Date),
+    Label(String),
+}
+
 /// Construct a global [PrattParser] to handle number expressions.
 fn pratt_number_parser() -> &'static PrattParser<Rule> {
     static PARSER: OnceCell<PrattParser<Rule>> = OnceCell::new();
@@ -203,8 +218,8 @@ impl BeancountParser {
         Ok(ret.into_iter().collect())
     }
 
-    fn posting_unit(input: Node) -> Result<(Option<Amount>, Option<(Option<Amount>, Option<Date>, Option<SingleTotalPrice>)>)> {
-        let ret: (Option<Amount>, Option<(Option<Amount>, Option<Date>, Option<SingleTotalPrice>)>) = match_nodes!(input.into_children();
+    fn posting_unit(input: Node) -> Result<(Option<Amount>, Option<PostingMeta>)> {
+        let ret: (Option<Amount>, Option<PostingMeta>) = match_nodes!(input.into_children();
             [posting_amount(amount)] => (Some(amount), None),
             [posting_meta(meta)] => (None, Some(meta)),
             [posting_amount(amount), posting_meta(meta)] => (Some(amount), Some(meta)),
@@ -249,24 +264,34 @@ impl BeancountParser {
         );
         Ok(ret)
     }
-    fn posting_meta(input: Node) -> Result<(Option<Amount>, Option<Date>, Option<SingleTotalPrice>)> {
-        let ret: (Option<Amount>, Option<Date>, Option<SingleTotalPrice>) = match_nodes!(input.into_children();
-            [] => (None, None, None),
-            [posting_cost(cost)] => (Some(cost), None, None),
-            [posting_price(p)] => (None, None, Some(p)),
-            [posting_cost(cost), date(d)] => (Some(cost), Some(d), None),
-            [posting_cost(cost), posting_price(p)] => (Some(cost), None, Some(p)),
-            [posting_cost(cost), date(d), posting_price(p)] => (Some(cost), Some(d), Some(p)),
+    fn lot_component(input: Node) -> Result<LotComponent> {
+        let ret: LotComponent = match_nodes!(input.into_children();
+            [date(date)] => LotComponent::Date(date),
+            [posting_cost(cost)] => LotComponent::Cost(cost),
+            [quote_string(label)] => LotComponent::Label(label.to_plain_string()),
         );
         Ok(ret)
     }
+    fn posting_meta(input: Node) -> Result<PostingMeta> {
+        let (components, price): (Vec<LotComponent>, Option<SingleTotalPrice>) = match_nodes!(input.into_children();
+            [lot_component(components).., posting_price(p)] => (components.collect(), Some(p)),
+            [lot_component(components)..] => (components.collect(), None),
+        );
+        let mut meta = PostingMeta {
+            price,
+            ..PostingMeta::default()
+        };
+        for component in components {
+            match component {
+                LotComponent::Cost(cost) => meta.cost = Some(cost),
+                LotComponent::Date(date) => meta.cost_date = Some(date),
+                LotComponent::Label(label) => meta.cost_label = Some(label),
+            }
+        }
+        Ok(meta)
+    }
     fn transaction_posting(input: Node) -> Result<Posting> {
-        let ret: (
-            Option<Flag>,
-            Account,
-            Option<(Option<Amount>, Option<(Option<Amount>, Option<Date>, Option<SingleTotalPrice>)>)>,
-            Meta,
-        ) = match_nodes!(input.into_children();
+        let ret: (Option<Flag>, Account, Option<(Option<Amount>, Option<PostingMeta>)>, Meta) = match_nodes!(input.into_children();
             [account_name(account_name)] => (None, account_name, None, Meta::default()),
             [account_name(account_name), posting_unit(unit)] => (None, account_name, Some(unit), Meta::default()),
             [transaction_flag(flag), account_name(account_name)] => (flag, account_name, None, Meta::default()),
@@ -282,10 +307,12 @@ impl BeancountParser {
 
         let mut line = Posting {
             flag,
+            posting_type: PostingType::Real,
             account,
             units: None,
             cost: None,
             cost_date: None,
+            cost_label: None,
             price: None,
             comment: None,
             meta,
@@ -295,9 +322,10 @@ impl BeancountParser {
             line.units = amount;
 
             if let Some(meta) = meta {
-                line.cost = meta.0;
-                line.cost_date = meta.1;
-                line.price = meta.2;
+                line.cost = meta.cost;
+                line.cost_date = meta.cost_date;
+                line.cost_label = meta.cost_label;
+                line.price = meta.price;
             }
         }
         Ok(line)
@@ -363,6 +391,7 @@ impl BeancountParser {
         );
         let mut transaction = Transaction {
             date: ret.0,
+            auxiliary_date: None,
             flag: ret.1,
             payee: ret.2,
             narration: ret.3,
@@ -423,7 +452,7 @@ impl BeancountParser {
         let ret: ZhangString = match_nodes!(input.into_children();
             [quote_string(path)] => path,
         );
-        let include = Include { file: ret };
+        let include = Include { file: ret, profile: None };
         Ok(Directive::Include(include))
     }
 
@@ -608,6 +637,7 @@ impl BeancountParser {
             end: span.end_pos().pos(),
             content: span.as_str().to_string(),
             filename: None,
+            trivia: Default::default(),
         };
         let ret: Option<BeancountDirective> = match_nodes!(input.into_children();
             [option(item)]          => Some(Either::Left(item)),
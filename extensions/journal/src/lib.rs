@@ -0,0 +1,907 @@
+//! the data type of [hledger](https://hledger.org) and [ledger-cli](https://ledger-cli.org) journal files.
+//!
+//! the journal concepts which have no equivalent in zhang are mapped as following, and are restored when exporting back:
+//! - the code of transaction is kept in `code` meta
+//! - virtual postings `(Account)` and balanced virtual postings `[Account]` are mapped into the postings of same types,
+//!   but the virtual postings whose account or amount is not supported by zhang are kept in `virtual-posting` metas of
+//!   transaction as they are written, since they do not take part in the balance of transaction
+//! - monthly periodic transactions `~ monthly` are mapped into the budgets of their expenses postings, which get the amounts
+//!   assigned in every month of period. the other periodic transactions, automated transactions and unknown directives are kept as comments
+//! - balance assertions are converted into balance checks of the next day, balance assignments into balance pads
+
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{Datelike, Months, NaiveDate, NaiveTime};
+use indexmap::{IndexMap, IndexSet};
+use itertools::Itertools;
+use zhang_ast::amount::Amount;
+use zhang_ast::*;
+use zhang_core::data_type::text::ZhangDataType;
+use zhang_core::data_type::DataType;
+use zhang_core::ledger::Ledger;
+use zhang_core::{ZhangError, ZhangResult};
+
+use crate::parser::{parse, JournalDirective, JournalPeriodicTransaction, JournalPosting, JournalTransaction, PostingKind};
+
+pub mod parser;
+
+const CODE: &str = "code";
+const TIME: &str = "time";
+const COMMENT: &str = "comment";
+const LINKS: &str = "links";
+const VIRTUAL_POSTING: &str = "virtual-posting";
+const PERIODIC: &str = "periodic";
+const POSTING_INDENT: &str = "    ";
+
+#[derive(Clone, Default)]
+pub struct Journal {}
+
+/// convert the account of journal into zhang account, the top level account is matched case-insensitively,
+/// and spaces are replaced by `-` since they are not allowed in zhang account.
+pub fn normalize_account(account: &str) -> Result<Account, String> {
+    let (root, rest) = account.split_once(':').unwrap_or((account, ""));
+    let account_type = match root.to_lowercase().as_str() {
+        "asset" | "assets" => "Assets",
+        "liability" | "liabilities" => "Liabilities",
+        "equity" => "Equity",
+        "income" | "revenue" | "revenues" => "Income",
+        "expense" | "expenses" => "Expenses",
+        _ => {
+            return Err(format!(
+                "account {} is not supported, the top level account should be one of Assets, Liabilities, Equity, Income and Expenses",
+                account
+            ))
+        }
+    };
+    let name = if rest.is_empty() {
+        account_type.to_owned()
+    } else {
+        format!("{}:{}", account_type, rest.replace(' ', "-"))
+    };
+    Account::from_str(&name).map_err(|_| format!("account {} is not supported", account))
+}
+
+/// the content of comment, `:tag1:tag2:`, `key: value` and `tag:` are supported
+enum CommentContent {
+    Tags(Vec<String>),
+    Meta(String, String),
+    Text(String),
+}
+
+impl CommentContent {
+    fn parse(comment: &str) -> CommentContent {
+        let comment = comment.trim();
+        if comment.len() > 1 && comment.starts_with(':') && comment.ends_with(':') && !comment.contains(char::is_whitespace) {
+            return CommentContent::Tags(comment.split(':').filter(|it| !it.is_empty()).map(|it| it.to_owned()).collect_vec());
+        }
+        match comment.split_once(':') {
+            Some((key, value)) if !key.is_empty() && !key.contains(char::is_whitespace) => {
+                let value = value.trim();
+                if value.is_empty() {
+                    CommentContent::Tags(vec![key.to_owned()])
+                } else {
+                    CommentContent::Meta(key.to_owned(), value.to_owned())
+                }
+            }
+            _ => CommentContent::Text(comment.to_owned()),
+        }
+    }
+}
+
+/// accounts in order of appearance, keyed by name
+type Accounts = IndexMap<String, Account>;
+
+fn add_account(accounts: &mut Accounts, account: &Account) {
+    accounts.insert(account.content.clone(), account.clone());
+}
+
+/// the budgets of accounts which are generated from periodic transactions, keyed by account name
+type AccountBudgets = IndexMap<String, Vec<String>>;
+
+/// the budget name of account, e.g. `Expenses_food-and-drink` for `Expenses:food-and-drink`, since `:` is not allowed in budget name.
+/// the account which is budgeted by several periodic transactions gets the budgets with suffix, e.g. `Expenses_Rent_2`
+fn budget_name(budgets: &AccountBudgets, account: &Account) -> String {
+    let name = account.name().replace(':', "_");
+    let is_used = |candidate: &String| budgets.values().flatten().any(|it| it.eq(candidate));
+    std::iter::once(name.clone())
+        .chain((2..).map(|index| format!("{}_{}", name, index)))
+        .find(|candidate| !is_used(candidate))
+        .expect("budget name should be found")
+}
+
+/// the first and the day after the last day of date which omits day or month, e.g. `2024`, `2024/01` and `2024-01-15`
+fn partial_date_range(date: &str) -> Option<(NaiveDate, NaiveDate)> {
+    let parts = date.split(['-', '/', '.']).map(|it| it.parse::<u32>().ok()).collect::<Option<Vec<_>>>()?;
+    match parts.as_slice() {
+        [year] => {
+            let start = NaiveDate::from_ymd_opt(*year as i32, 1, 1)?;
+            Some((start, start.checked_add_months(Months::new(12))?))
+        }
+        [year, month] => {
+            let start = NaiveDate::from_ymd_opt(*year as i32, *month, 1)?;
+            Some((start, start.checked_add_months(Months::new(1))?))
+        }
+        [year, month, day] => {
+            let start = NaiveDate::from_ymd_opt(*year as i32, *month, *day)?;
+            Some((start, start.succ_opt()?))
+        }
+        _ => None,
+    }
+}
+
+/// the first days of months covered by the period expression, e.g. `monthly`, `every month from 2024/01 to 2024/07` and `monthly in 2024`,
+/// `to` is exclusive as hledger does. the bounds which are not written default to the dates of transactions in the same file.
+/// `None` is returned if the period is not monthly, since the budgets of zhang are assigned by month
+fn period_months(period: &str, transaction_dates: Option<(NaiveDate, NaiveDate)>) -> Option<Vec<NaiveDate>> {
+    let words = period.split_whitespace().map(|it| it.to_lowercase()).collect_vec();
+    let words = words.iter().map(String::as_str).collect_vec();
+    let bounds = match words.as_slice() {
+        ["monthly", bounds @ ..] | ["every", "month", bounds @ ..] => bounds,
+        _ => return None,
+    };
+    let (mut from, mut to) = (None, None);
+    for bound in bounds.chunks(2) {
+        let [keyword, date] = bound else {
+            return None;
+        };
+        let (start, end) = partial_date_range(date)?;
+        match *keyword {
+            "from" | "since" => from = Some(start),
+            "to" | "until" => to = Some(start),
+            "in" => (from, to) = (Some(start), Some(end)),
+            _ => return None,
+        }
+    }
+    let from = from.or(transaction_dates.map(|(first, _)| first))?.with_day(1)?;
+    let to = to.or(transaction_dates.and_then(|(_, last)| last.succ_opt()))?;
+    let months = std::iter::successors(Some(from), |month| month.checked_add_months(Months::new(1)))
+        .take_while(|month| month.lt(&to))
+        .collect_vec();
+    (!months.is_empty()).then_some(months)
+}
+
+fn day_after(date: NaiveDate) -> Date {
+    Date::Date(date.succ_opt().unwrap_or(date))
+}
+
+impl Journal {
+    fn transform_transaction(&self, trx: JournalTransaction, accounts: &mut Accounts) -> Result<Vec<Directive>, String> {
+        // transactions which only contains balance assertions or a balance assignment
+        let all_assertions = !trx.postings.is_empty()
+            && trx
+                .postings
+                .iter()
+                .all(|it| it.kind == PostingKind::Real && it.assertion.is_some() && it.amount.as_ref().map(|amount| amount.number.is_zero()).unwrap_or(false));
+        if all_assertions {
+            return trx
+                .postings
+                .into_iter()
+                .map(|posting| {
+                    let account = normalize_account(&posting.account)?;
+                    add_account(accounts, &account);
+                    Ok(Directive::BalanceCheck(BalanceCheck {
+                        date: day_after(trx.date),
+                        account,
+                        amount: posting.assertion.expect("posting should have assertion"),
+                        meta: Default::default(),
+                    }))
+                })
+                .collect();
+        }
+        if let [target, pad] = trx.postings.as_slice() {
+            if target.kind == PostingKind::Real && target.amount.is_none() && target.assertion.is_some() && pad.amount.is_none() && pad.assertion.is_none() {
+                let account = normalize_account(&target.account)?;
+                let pad = normalize_account(&pad.account)?;
+                add_account(accounts, &account);
+                add_account(accounts, &pad);
+                return Ok(vec![Directive::BalancePad(BalancePad {
+                    date: day_after(trx.date),
+                    account,
+                    amount: target.assertion.clone().expect("posting should have assertion"),
+                    pad,
+                    meta: Default::default(),
+                })]);
+            }
+        }
+
+        let mut ret = vec![];
+        let mut meta = Meta::default();
+        let mut tags = IndexSet::new();
+        let mut links = IndexSet::new();
+        let mut time = None;
+        if let Some(code) = trx.code {
+            meta.insert(CODE.to_owned(), ZhangString::quote(code));
+        }
+        for comment in trx.comments {
+            match CommentContent::parse(&comment) {
+                CommentContent::Tags(values) => tags.extend(values),
+                CommentContent::Meta(key, value) if key.eq(LINKS) => links.extend(value.split(',').map(|it| it.trim().to_owned()).filter(|it| !it.is_empty())),
+                CommentContent::Meta(key, value) if key.eq(TIME) && NaiveTime::from_str(&value).is_ok() => time = NaiveTime::from_str(&value).ok(),
+                CommentContent::Meta(key, value) => meta.insert(key, ZhangString::quote(value)),
+                CommentContent::Text(text) => meta.insert(COMMENT.to_owned(), ZhangString::quote(text)),
+            }
+        }
+
+        let mut postings = vec![];
+        for posting in trx.postings {
+            let JournalPosting {
+                kind,
+                status,
+                account,
+                amount,
+                cost,
+                cost_date,
+                price,
+                assertion,
+                comments,
+                raw,
+            } = posting;
+            let posting_type = match kind {
+                PostingKind::Real => PostingType::Real,
+                PostingKind::Virtual => PostingType::Virtual,
+                PostingKind::BalancedVirtual => PostingType::BalancedVirtual,
+            };
+            if kind == PostingKind::Virtual && (amount.is_none() || normalize_account(&account).is_err()) {
+                meta.insert(VIRTUAL_POSTING.to_owned(), ZhangString::quote(raw));
+                continue;
+            }
+            let account = normalize_account(&account)?;
+            add_account(accounts, &account);
+            let mut posting_meta = Meta::default();
+            let mut posting_comment = None;
+            for comment in comments {
+                match CommentContent::parse(&comment) {
+                    CommentContent::Tags(values) => tags.extend(values),
+                    CommentContent::Meta(key, value) => posting_meta.insert(key, ZhangString::quote(value)),
+                    CommentContent::Text(text) => posting_comment = Some(text),
+                }
+            }
+            if let Some(assertion) = assertion {
+                ret.push(Directive::BalanceCheck(BalanceCheck {
+                    date: day_after(trx.date),
+                    account: account.clone(),
+                    amount: assertion,
+                    meta: Default::default(),
+                }));
+            }
+            postings.push(Posting {
+                flag: status,
+                posting_type,
+                account,
+                units: amount,
+                cost,
+                cost_date: cost_date.map(Date::Date),
+                cost_label: None,
+                price,
+                comment: posting_comment,
+                meta: posting_meta,
+            });
+        }
+
+        let (payee, narration) = match trx.description {
+            Some(description) => match description.split_once('|') {
+                Some((payee, narration)) => (Some(payee.trim().to_owned()), Some(narration.trim().to_owned())),
+                None => (None, Some(description)),
+            },
+            None => (None, None),
+        };
+        let transaction = Directive::Transaction(Transaction {
+            date: match time {
+                Some(time) => Date::Datetime(trx.date.and_time(time)),
+                None => Date::Date(trx.date),
+            },
+            auxiliary_date: trx.auxiliary_date.map(Date::Date),
+            flag: trx.status,
+            payee: payee.map(ZhangString::quote),
+            narration: narration.map(ZhangString::quote),
+            tags,
+            links,
+            postings,
+            meta,
+        });
+        ret.insert(0, transaction);
+        Ok(ret)
+    }
+
+    /// the periodic transaction is mapped into a budget of each expenses posting with amount, which is linked to the account by `budget` meta
+    /// and gets the amount of posting assigned in every month of period. the budget does not carry the unused amount into the next month,
+    /// since the amount is the goal of each month. the first budget is marked by `periodic` meta of the period, and is exported back
+    /// as the periodic transaction is written. `None` is returned if the period is not supported or there is no expenses posting with amount
+    fn transform_periodic_transaction(
+        &self, trx: JournalPeriodicTransaction, transaction_dates: Option<(NaiveDate, NaiveDate)>, accounts: &mut Accounts, budgets: &mut AccountBudgets,
+    ) -> Result<Option<Vec<Directive>>, String> {
+        let Some(months) = period_months(&trx.period, transaction_dates) else {
+            return Ok(None);
+        };
+        let mut goals = vec![];
+        for posting in trx.postings {
+            let Some(amount) = posting.amount else {
+                continue;
+            };
+            let account = normalize_account(&posting.account)?;
+            if account.account_type == AccountType::Expenses {
+                goals.push((account, amount));
+            }
+        }
+
+        let mut ret = vec![];
+        for (account, amount) in goals {
+            let name = budget_name(budgets, &account);
+            let mut meta = Meta::default();
+            meta.insert("alias".to_owned(), ZhangString::quote(account.name()));
+            meta.insert("rollover".to_owned(), ZhangString::quote("reset"));
+            if ret.is_empty() {
+                meta.insert(PERIODIC.to_owned(), ZhangString::quote(&trx.period));
+            }
+            ret.push(Directive::Budget(Budget {
+                date: Date::Date(months[0]),
+                name: name.clone(),
+                commodity: amount.currency.clone(),
+                meta,
+            }));
+            ret.extend(months.iter().map(|month| {
+                Directive::BudgetAdd(BudgetAdd {
+                    date: Date::Date(*month),
+                    name: name.clone(),
+                    amount: amount.clone(),
+                    meta: Default::default(),
+                })
+            }));
+            add_account(accounts, &account);
+            budgets.entry(account.content.clone()).or_default().push(name);
+        }
+        Ok((!ret.is_empty()).then_some(ret))
+    }
+
+    fn export_transaction(&self, trx: Transaction) -> String {
+        let date = trx.date.naive_date();
+        let mut meta = trx.meta;
+        let mut header = date.format("%Y-%m-%d").to_string();
+        if let Some(auxiliary_date) = trx.auxiliary_date {
+            header.push_str(&format!("={}", auxiliary_date.naive_date().format("%Y-%m-%d")));
+        }
+        match trx.flag {
+            Some(Flag::Okay) => header.push_str(" *"),
+            Some(Flag::Warning) => header.push_str(" !"),
+            _ => {}
+        }
+        if let Some(code) = meta.pop_one(CODE) {
+            header.push_str(&format!(" ({})", code.as_str()));
+        }
+        match (trx.payee, trx.narration) {
+            (Some(payee), Some(narration)) => header.push_str(&format!(" {} | {}", payee.as_str(), narration.as_str())),
+            (Some(payee), None) => header.push_str(&format!(" {} |", payee.as_str())),
+            (None, Some(narration)) => header.push_str(&format!(" {}", narration.as_str())),
+            (None, None) => {}
+        }
+
+        let mut lines = vec![header];
+        if let Date::Datetime(datetime) = trx.date {
+            lines.push(format!("{}; {}: {}", POSTING_INDENT, TIME, datetime.time()));
+        }
+        if !trx.tags.is_empty() {
+            lines.push(format!("{}; :{}:", POSTING_INDENT, trx.tags.iter().join(":")));
+        }
+        if !trx.links.is_empty() {
+            lines.push(format!("{}; {}: {}", POSTING_INDENT, LINKS, trx.links.iter().join(",")));
+        }
+        let virtual_postings = std::iter::from_fn(|| meta.pop_one(VIRTUAL_POSTING)).collect_vec();
+        lines.extend(export_meta(meta, POSTING_INDENT));
+
+        for posting in trx.postings {
+            let meta = posting.meta;
+            let account = match posting.posting_type {
+                PostingType::Real => posting.account.name().to_owned(),
+                PostingType::Virtual => format!("({})", posting.account.name()),
+                PostingType::BalancedVirtual => format!("[{}]", posting.account.name()),
+            };
+            let mut line = match posting.flag {
+                Some(Flag::Okay) => format!("{}* {}", POSTING_INDENT, account),
+                Some(Flag::Warning) => format!("{}! {}", POSTING_INDENT, account),
+                _ => format!("{}{}", POSTING_INDENT, account),
+            };
+            if let Some(units) = posting.units {
+                line.push_str(&format!("  {}", export_amount(units)));
+            }
+            if let Some(cost) = posting.cost {
+                line.push_str(&format!(" {{{}}}", export_amount(cost)));
+            }
+            if let Some(cost_date) = posting.cost_date {
+                line.push_str(&format!(" [{}]", cost_date.naive_date().format("%Y-%m-%d")));
+            }
+            match posting.price {
+                Some(SingleTotalPrice::Single(price)) => line.push_str(&format!(" @ {}", export_amount(price))),
+                Some(SingleTotalPrice::Total(price)) => line.push_str(&format!(" @@ {}", export_amount(price))),
+                None => {}
+            }
+            if let Some(comment) = posting.comment {
+                line.push_str(&format!("  ; {}", comment));
+            }
+            lines.push(line);
+            lines.extend(export_meta(meta, &POSTING_INDENT.repeat(2)));
+        }
+        lines.extend(virtual_postings.into_iter().map(|it| format!("{}{}", POSTING_INDENT, it.as_str())));
+        lines.join("\n")
+    }
+}
+
+fn export_amount(amount: Amount) -> String {
+    format!("{} {}", amount.number, amount.currency)
+}
+
+fn export_meta(meta: Meta, indent: &str) -> Vec<String> {
+    meta.get_flatten()
+        .into_iter()
+        .sorted_by(|a, b| a.0.cmp(&b.0))
+        .map(|(key, value)| {
+            if key.eq(COMMENT) {
+                format!("{}; {}", indent, value.as_str())
+            } else {
+                format!("{}; {}: {}", indent, key, value.as_str())
+            }
+        })
+        .collect_vec()
+}
+
+/// the directives which have no equivalent in journal are exported as comments of zhang syntax
+fn export_as_comment(directive: Spanned<Directive>) -> String {
+    ZhangDataType {}.export(directive).lines().map(|it| format!("; {}", it)).join("\n")
+}
+
+/// export the whole ledger as one journal file, includes are skipped since the directives of included files are exported as well
+pub fn export_ledger(ledger: &Ledger) -> String {
+    let journal = Journal {};
+    let mut content = ledger
+        .metas
+        .iter()
+        .chain(ledger.directives.iter())
+        .filter(|it| !matches!(it.data, Directive::Include(_)))
+        .cloned()
+        .map(|it| journal.export(it))
+        .filter(|it| !it.is_empty())
+        .join("\n\n");
+    content.push('\n');
+    content
+}
+
+impl DataType for Journal {
+    type Carrier = String;
+
+    fn transform(&self, raw_data: Self::Carrier, source: Option<String>) -> ZhangResult<Vec<Spanned<Directive>>> {
+        let path = source.clone().map(PathBuf::from);
+        let directives = parse(&raw_data, path).map_err(|it| ZhangError::PestError {
+            path: source.clone().unwrap_or_default(),
+            msg: it.to_string(),
+        })?;
+        let to_error = |msg: String| ZhangError::PestError {
+            path: source.clone().unwrap_or_default(),
+            msg,
+        };
+
+        let transaction_dates = directives
+            .iter()
+            .filter_map(|it| match &it.data {
+                JournalDirective::Transaction(trx) => Some(trx.date),
+                _ => None,
+            })
+            .minmax()
+            .into_option();
+
+        let mut ret = vec![];
+        let mut accounts = Accounts::default();
+        let mut budgets = AccountBudgets::default();
+        for directive in directives {
+            let Spanned { span, data } = directive;
+            match data {
+                JournalDirective::Transaction(trx) => {
+                    for directive in self.transform_transaction(trx, &mut accounts).map_err(to_error)? {
+                        ret.push(Spanned::new(directive, span.clone()));
+                    }
+                }
+                JournalDirective::Price { date, time, commodity, amount } => ret.push(Spanned::new(
+                    Directive::Price(Price {
+                        date: match time {
+                            Some(time) => Date::Datetime(date.and_time(time)),
+                            None => Date::Date(date),
+                        },
+                        currency: commodity,
+                        amount,
+                        meta: Default::default(),
+                    }),
+                    span,
+                )),
+                JournalDirective::Account(account) => add_account(&mut accounts, &normalize_account(&account).map_err(to_error)?),
+                JournalDirective::Commodity { commodity, prefix, precision } => {
+                    let mut meta = Meta::default();
+                    if let Some(prefix) = prefix {
+                        meta.insert("prefix".to_owned(), ZhangString::quote(prefix));
+                    }
+                    if let Some(precision) = precision {
+                        meta.insert("precision".to_owned(), ZhangString::quote(precision.to_string()));
+                    }
+                    ret.push(Spanned::new(
+                        Directive::Commodity(Commodity {
+                            date: Date::Date(NaiveDate::default()),
+                            currency: commodity,
+                            meta,
+                        }),
+                        span,
+                    ));
+                }
+                JournalDirective::Include(file) => ret.push(Spanned::new(
+                    Directive::Include(Include {
+                        file: ZhangString::quote(file),
+                        profile: None,
+                    }),
+                    span,
+                )),
+                JournalDirective::PeriodicTransaction(trx) => {
+                    let raw = trx.raw.clone();
+                    match self
+                        .transform_periodic_transaction(trx, transaction_dates, &mut accounts, &mut budgets)
+                        .map_err(to_error)?
+                    {
+                        Some(directives) => ret.extend(directives.into_iter().map(|directive| Spanned::new(directive, span.clone()))),
+                        None => ret.push(Spanned::new(Directive::Comment(Comment { content: raw }), span)),
+                    }
+                }
+                JournalDirective::Raw(content) => ret.push(Spanned::new(Directive::Comment(Comment { content }), span)),
+            }
+        }
+
+        // journal does not require accounts to be opened, so all declared and used accounts are opened at the very beginning
+        let opens = accounts.into_values().map(|account| {
+            let mut meta = Meta::default();
+            for budget in budgets.shift_remove(&account.content).unwrap_or_default() {
+                meta.insert("budget".to_owned(), ZhangString::quote(budget));
+            }
+            Spanned::new(
+                Directive::Open(Open {
+                    date: Date::Date(NaiveDate::default()),
+                    account,
+                    commodities: vec![],
+                    meta,
+                }),
+                SpanInfo {
+                    filename: source.clone().map(PathBuf::from),
+                    ..Default::default()
+                },
+            )
+        });
+        Ok(opens.chain(ret).collect_vec())
+    }
+
+    fn export(&self, directive: Spanned<Directive>) -> Self::Carrier {
+        let Spanned { data, span } = directive;
+        match data {
+            // journal has no void flag, the voided transaction is kept as comment so that it is not posted
+            Directive::Transaction(trx) if trx.is_voided() => export_as_comment(Spanned::new(Directive::Transaction(trx), span)),
+            Directive::Transaction(trx) => self.export_transaction(trx),
+            // the budgets generated from periodic transaction are restored as the periodic transaction is written
+            Directive::Budget(budget) if span.content.starts_with('~') => match budget.meta.get_one(PERIODIC) {
+                Some(_) => span.content,
+                None => String::new(),
+            },
+            Directive::BudgetAdd(_) if span.content.starts_with('~') => String::new(),
+            Directive::Open(open) => format!("account {}", open.account.name()),
+            Directive::Commodity(commodity) => {
+                let precision = commodity.meta.get_one("precision").and_then(|it| it.as_str().parse::<usize>().ok());
+                let prefix = commodity.meta.get_one("prefix").map(|it| it.as_str().to_owned());
+                let number = match precision {
+                    Some(0) => Some("1".to_owned()),
+                    Some(precision) => Some(format!("1.{}", "0".repeat(precision))),
+                    None => None,
+                };
+                match (prefix, number) {
+                    (Some(prefix), Some(number)) => format!("commodity {}{}", prefix, number),
+                    (Some(prefix), None) => format!("commodity {}", prefix),
+                    (None, Some(number)) => format!("commodity {} {}", number, commodity.currency),
+                    (None, None) => format!("commodity {}", commodity.currency),
+                }
+            }
+            Directive::Price(price) => {
+                let date = match price.date {
+                    Date::Date(date) => date.format("%Y-%m-%d").to_string(),
+                    Date::DateHour(datetime) | Date::Datetime(datetime) => datetime.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    Date::ZonedDatetime(datetime) => datetime.naive_local().format("%Y-%m-%d %H:%M:%S").to_string(),
+                };
+                format!("P {} {} {}", date, price.currency, export_amount(price.amount))
+            }
+            Directive::BalanceCheck(check) => {
+                let date = check.date.naive_date();
+                let assertion_date = date.pred_opt().unwrap_or(date);
+                format!(
+                    "{} * balance assertion\n{}{}  {} = {}",
+                    assertion_date.format("%Y-%m-%d"),
+                    POSTING_INDENT,
+                    check.account.name(),
+                    export_amount(Amount::new(BigDecimal::zero(), check.amount.currency.clone())),
+                    export_amount(check.amount)
+                )
+            }
+            Directive::BalancePad(pad) => {
+                let date = pad.date.naive_date();
+                let assignment_date = date.pred_opt().unwrap_or(date);
+                format!(
+                    "{} * balance assignment\n{}{}  = {}\n{}{}",
+                    assignment_date.format("%Y-%m-%d"),
+                    POSTING_INDENT,
+                    pad.account.name(),
+                    export_amount(pad.amount),
+                    POSTING_INDENT,
+                    pad.pad.name()
+                )
+            }
+            Directive::Include(include) => format!("include {}", include.file.as_str()),
+            Directive::Comment(comment) => match comment.content.strip_prefix("//") {
+                Some(content) => format!(";{}", content),
+                None => comment.content,
+            },
+            _ => export_as_comment(Spanned::new(data, span)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use bigdecimal::BigDecimal;
+    use indoc::indoc;
+    use itertools::Itertools;
+    use tempfile::tempdir;
+    use zhang_ast::{Comment, Directive, PostingType, ZhangString};
+    use zhang_core::data_source::LocalFileSystemDataSource;
+    use zhang_core::data_type::DataType;
+    use zhang_core::ledger::Ledger;
+
+    use crate::{export_ledger, Journal};
+
+    fn transform(content: &str) -> Vec<Directive> {
+        Journal {}
+            .transform(content.to_owned(), None)
+            .unwrap()
+            .into_iter()
+            .map(|it| it.data)
+            .collect_vec()
+    }
+
+    #[test]
+    fn should_map_virtual_postings() {
+        let directives = transform(indoc! {r#"
+            2024/01/05 * Shop | Groceries  ; :food:
+                expenses:food and drink    $20.00
+                (budget:food)              $-20.00
+                (assets:envelope:food)     $-20.00
+                [assets:savings]           $-5
+                [equity:savings]           $5
+                assets:checking
+        "#});
+        let opens = directives
+            .iter()
+            .filter_map(|it| match it {
+                Directive::Open(open) => Some(open.account.name().to_owned()),
+                _ => None,
+            })
+            .collect_vec();
+        assert_eq!(
+            vec![
+                "Expenses:food-and-drink",
+                "Assets:envelope:food",
+                "Assets:savings",
+                "Equity:savings",
+                "Assets:checking"
+            ],
+            opens
+        );
+        let Directive::Transaction(trx) = directives.last().unwrap() else {
+            panic!("should be transaction")
+        };
+        assert_eq!(Some(ZhangString::quote("Shop")), trx.payee);
+        assert_eq!(Some(ZhangString::quote("Groceries")), trx.narration);
+        assert!(trx.tags.contains("food"));
+        assert_eq!(5, trx.postings.len());
+        assert_eq!(
+            Some(&ZhangString::quote("(budget:food)              $-20.00")),
+            trx.meta.get_one("virtual-posting")
+        );
+        assert_eq!(
+            vec![
+                PostingType::Real,
+                PostingType::Virtual,
+                PostingType::BalancedVirtual,
+                PostingType::BalancedVirtual,
+                PostingType::Real
+            ],
+            trx.postings.iter().map(|it| it.posting_type).collect_vec()
+        );
+    }
+
+    #[test]
+    fn should_convert_assertions_into_balance_directives() {
+        let directives = transform(indoc! {r#"
+            2024-01-01 opening
+                assets:bank  10 CNY = 10 CNY
+                equity:opening
+
+            2024-01-31 * balance assertion
+                assets:bank  0 CNY = 10 CNY
+
+            2024-02-29 * balance assignment
+                assets:bank  = 100 CNY
+                income:unknown
+        "#});
+        let kinds = directives
+            .iter()
+            .map(|it| match it {
+                Directive::Open(_) => "open",
+                Directive::Transaction(_) => "transaction",
+                Directive::BalanceCheck(_) => "balance check",
+                Directive::BalancePad(_) => "balance pad",
+                _ => "others",
+            })
+            .collect_vec();
+        assert_eq!(
+            vec!["open", "open", "open", "transaction", "balance check", "balance check", "balance pad"],
+            kinds
+        );
+        let Directive::BalancePad(pad) = directives.last().unwrap() else {
+            panic!("should be balance pad")
+        };
+        assert_eq!("2024-03-01", pad.date.naive_date().to_string());
+        assert_eq!("Income:unknown", pad.pad.name());
+    }
+
+    #[test]
+    fn should_map_monthly_periodic_transaction_into_budgets() {
+        let directives = transform(indoc! {r#"
+            ~ monthly from 2024/01 to 2024/03  household
+                expenses:rent          $500
+                expenses:food          $300
+                assets:bank
+
+            ~ monthly
+                expenses:rent          $100
+                assets:bank
+
+            ~ every 2 weeks
+                expenses:food          $50
+                assets:bank
+
+            2024/01/05 rent
+                expenses:rent          $500
+                assets:bank
+            2024/04/05 rent
+                expenses:rent          $500
+                assets:bank
+        "#});
+        let Directive::Open(rent) = &directives[0] else { panic!("should be open") };
+        assert_eq!("Expenses:rent", rent.account.name());
+        assert_eq!(
+            vec!["Expenses_rent", "Expenses_rent_2"],
+            rent.meta
+                .clone()
+                .get_flatten()
+                .into_iter()
+                .map(|(_, value)| value.to_plain_string())
+                .collect_vec()
+        );
+
+        let budgets = directives
+            .iter()
+            .filter_map(|it| match it {
+                Directive::Budget(budget) => Some((budget.name.as_str(), budget.date.naive_date().to_string(), budget.commodity.as_str())),
+                _ => None,
+            })
+            .collect_vec();
+        assert_eq!(
+            vec![
+                ("Expenses_rent", "2024-01-01".to_owned(), "USD"),
+                ("Expenses_food", "2024-01-01".to_owned(), "USD"),
+                ("Expenses_rent_2", "2024-01-01".to_owned(), "USD")
+            ],
+            budgets
+        );
+        let additions = directives
+            .iter()
+            .filter_map(|it| match it {
+                Directive::BudgetAdd(add) => Some(format!("{} {} {}", add.date.naive_date(), add.name, add.amount.number)),
+                _ => None,
+            })
+            .collect_vec();
+        assert_eq!(
+            vec![
+                "2024-01-01 Expenses_rent 500",
+                "2024-02-01 Expenses_rent 500",
+                "2024-01-01 Expenses_food 300",
+                "2024-02-01 Expenses_food 300",
+                "2024-01-01 Expenses_rent_2 100",
+                "2024-02-01 Expenses_rent_2 100",
+                "2024-03-01 Expenses_rent_2 100",
+                "2024-04-01 Expenses_rent_2 100",
+            ],
+            additions
+        );
+        assert!(directives.contains(&Directive::Comment(Comment {
+            content: "~ every 2 weeks\n    expenses:food          $50\n    assets:bank".to_owned()
+        })));
+    }
+
+    #[test]
+    fn should_process_budgets_of_periodic_transaction() {
+        let temp_dir = tempdir().unwrap().into_path();
+        std::fs::write(
+            temp_dir.join("main.journal"),
+            indoc! {r#"
+                ~ monthly in 2024/01
+                    expenses:rent          500 CNY
+                    assets:bank
+
+                2024/01/05 rent
+                    expenses:rent          400 CNY
+                    assets:bank
+            "#},
+        )
+        .unwrap();
+        let ledger = Ledger::load_with_data_source(temp_dir, "main.journal".to_owned(), Arc::new(LocalFileSystemDataSource::new(Journal {}))).unwrap();
+        let mut operations = ledger.operations();
+        assert_eq!(0, operations.errors().unwrap().len());
+        let detail = operations.budget_month_detail("Expenses_rent", 202401).unwrap().unwrap();
+        assert_eq!(BigDecimal::from(500), detail.assigned_amount.number);
+        assert_eq!(BigDecimal::from(400), detail.activity_amount.number);
+        assert!(export_ledger(&ledger).ends_with(indoc! {r#"
+            account Expenses:rent
+
+            account Assets:bank
+
+            ~ monthly in 2024/01
+                expenses:rent          500 CNY
+                assets:bank
+
+            2024-01-05 rent
+                Expenses:rent  400 CNY
+                Assets:bank
+        "#}));
+    }
+
+    #[test]
+    fn should_export_back_to_equivalent_journal() {
+        let content = indoc! {r#"
+            account assets:bank
+            commodity $1,000.00
+            P 2024/01/01 AAPL $150
+
+            ~ monthly
+                expenses:rent  $500
+                assets:bank
+
+            2024/01/05=2024/01/07 * (#102) Broker | Buy stocks
+                ; trip: japan
+                ; :stock:
+                assets:broker    10 AAPL {$150} @ $160  ; first lot
+                (budget:stock)  $-1500
+                [assets:savings]  $10
+                [equity:savings]  $-10
+                assets:bank
+
+            2024/01/31 * balance assertion
+                assets:bank  0 USD = 100 USD
+        "#};
+        let journal = Journal {};
+        let directives = journal.transform(content.to_owned(), None).unwrap();
+        let exported = directives.clone().into_iter().map(|it| journal.export(it)).join("\n");
+        let round_trip = journal.transform(exported.clone(), None).unwrap();
+        assert_eq!(
+            directives.into_iter().map(|it| it.data).collect_vec(),
+            round_trip.into_iter().map(|it| it.data).collect_vec(),
+            "exported journal:\n{}",
+            exported
+        );
+        assert!(exported.contains("~ monthly\n    expenses:rent  $500\n    assets:bank"));
+        assert!(exported.contains("2024-01-05=2024-01-07 * (#102) Broker | Buy stocks"));
+        assert!(exported.contains("    (budget:stock)  $-1500"));
+        assert!(exported.contains("    [Assets:savings]  10 USD"));
+    }
+}
@@ -0,0 +1,636 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{NaiveDate, NaiveTime};
+use itertools::Itertools;
+use pest_consume::{match_nodes, Error, Parser};
+use zhang_ast::amount::Amount;
+use zhang_ast::{Flag, SingleTotalPrice, SpanInfo, Spanned};
+
+type Result<T> = std::result::Result<T, Error<Rule>>;
+type Node<'i> = pest_consume::Node<'i, Rule, ()>;
+
+#[derive(Parser)]
+#[grammar = "journal.pest"]
+pub struct JournalParser;
+
+/// directives of journal, the accounts and commodities are kept as they are written and normalized in transforming
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum JournalDirective {
+    Transaction(JournalTransaction),
+    Price {
+        date: NaiveDate,
+        time: Option<NaiveTime>,
+        commodity: String,
+        amount: Amount,
+    },
+    Account(String),
+    Commodity {
+        commodity: String,
+        prefix: Option<String>,
+        precision: Option<usize>,
+    },
+    Include(String),
+    PeriodicTransaction(JournalPeriodicTransaction),
+    /// lines which have no equivalent in zhang, e.g. comments, automated transactions and `alias` directives
+    Raw(String),
+}
+
+/// the periodic transaction `~ PERIOD`, whose postings are the budget goals of each period
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct JournalPeriodicTransaction {
+    /// the period expression as it is written, e.g. `monthly from 2024/01`
+    pub period: String,
+    pub postings: Vec<JournalPosting>,
+    /// the periodic transaction as it is written, including comments
+    pub raw: String,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct JournalTransaction {
+    pub date: NaiveDate,
+    pub auxiliary_date: Option<NaiveDate>,
+    pub status: Option<Flag>,
+    pub code: Option<String>,
+    pub description: Option<String>,
+    pub comments: Vec<String>,
+    pub postings: Vec<JournalPosting>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PostingKind {
+    Real,
+    /// `(Account)`, the posting does not need to balance
+    Virtual,
+    /// `[Account]`, the posting must balance with other balanced virtual postings
+    BalancedVirtual,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct JournalPosting {
+    pub kind: PostingKind,
+    pub status: Option<Flag>,
+    pub account: String,
+    pub amount: Option<Amount>,
+    pub cost: Option<Amount>,
+    pub cost_date: Option<NaiveDate>,
+    pub price: Option<SingleTotalPrice>,
+    pub assertion: Option<Amount>,
+    pub comments: Vec<String>,
+    /// the posting line as it is written, without comment
+    pub raw: String,
+}
+
+enum PostingPart {
+    Amount(Amount),
+    Cost(Amount),
+    TotalCost(Amount),
+    CostDate(NaiveDate),
+    Price(SingleTotalPrice),
+    Assertion(Amount),
+    Comment(String),
+}
+
+enum TransactionLine {
+    Comment(String),
+    Posting(JournalPosting),
+}
+
+enum TransactionHeader {
+    AuxiliaryDate(NaiveDate),
+    Status(Flag),
+    Code(String),
+    Description(String),
+}
+
+/// convert the commodity symbol into zhang commodity, e.g. `$` into `USD`, `"AAPL 2024"` into `AAPL-2024`
+pub fn normalize_commodity(symbol: &str) -> Option<String> {
+    let symbol = symbol.trim().trim_matches('"');
+    let commodity = match symbol {
+        "$" => "USD".to_owned(),
+        "€" => "EUR".to_owned(),
+        "£" => "GBP".to_owned(),
+        "¥" => "JPY".to_owned(),
+        "₹" => "INR".to_owned(),
+        "₩" => "KRW".to_owned(),
+        "₽" => "RUB".to_owned(),
+        _ => symbol
+            .chars()
+            .map(|it| {
+                if it.is_alphanumeric() || it == '_' || it == '.' {
+                    it.to_ascii_uppercase()
+                } else {
+                    '-'
+                }
+            })
+            .collect(),
+    };
+    let valid = commodity.chars().next().map(|it| it.is_ascii_alphabetic()).unwrap_or(false) && commodity.is_ascii();
+    valid.then_some(commodity)
+}
+
+/// parse the amount which commodity can be placed before or after the number, e.g. `$-1,000.00`, `-$10`, `10 USD` and `EUR 10`.
+/// the symbol which is placed before the number is returned as well.
+pub fn parse_amount(content: &str) -> std::result::Result<(Amount, Option<String>), String> {
+    let content = content.trim();
+    let (negative, content) = match content.strip_prefix('-') {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, content.strip_prefix('+').unwrap_or(content).trim_start()),
+    };
+    let is_number_char = |it: char| it.is_ascii_digit() || it == '.' || it == ',' || it == '-' || it == '+';
+    let (number, symbol, prefix) = if content.starts_with(|it: char| it.is_ascii_digit() || it == '.') {
+        let position = content.find(|it: char| !is_number_char(it)).unwrap_or(content.len());
+        (&content[..position], content[position..].trim(), false)
+    } else {
+        let position = if let Some(quoted) = content.strip_prefix('"') {
+            quoted.find('"').map(|it| it + 2).unwrap_or(content.len())
+        } else {
+            content.find(|it: char| is_number_char(it) || it.is_whitespace()).unwrap_or(content.len())
+        };
+        (content[position..].trim(), content[..position].trim(), true)
+    };
+    if symbol.is_empty() {
+        return Err(format!("amount {} has no commodity", content));
+    }
+    let commodity = normalize_commodity(symbol).ok_or_else(|| format!("commodity {} is not supported", symbol))?;
+    let number = BigDecimal::from_str(&number.replace(',', "")).map_err(|_| format!("{} is not a valid number", number))?;
+    let number = if negative { -number } else { number };
+    Ok((Amount::new(number, commodity), prefix.then(|| symbol.to_owned())))
+}
+
+#[pest_consume::parser]
+impl JournalParser {
+    #[allow(dead_code)]
+    fn EOI(_input: Node) -> Result<()> {
+        Ok(())
+    }
+
+    fn year(input: Node) -> Result<i32> {
+        Ok(input.as_str().parse().expect("year must be digits"))
+    }
+    fn month(input: Node) -> Result<u32> {
+        Ok(input.as_str().parse().expect("month must be digits"))
+    }
+    fn day(input: Node) -> Result<u32> {
+        Ok(input.as_str().parse().expect("day must be digits"))
+    }
+    fn date(input: Node) -> Result<NaiveDate> {
+        let (year, month, day) = match_nodes!(input.clone().into_children();
+            [year(year), month(month), day(day)] => (year, month, day),
+        );
+        NaiveDate::from_ymd_opt(year, month, day).ok_or_else(|| input.error("not a valid date"))
+    }
+    fn time(input: Node) -> Result<NaiveTime> {
+        let content = input.as_str();
+        NaiveTime::parse_from_str(content, "%H:%M:%S")
+            .or_else(|_| NaiveTime::parse_from_str(content, "%H:%M"))
+            .map_err(|_| input.error("not a valid time"))
+    }
+
+    fn amount(input: Node) -> Result<Amount> {
+        parse_amount(input.as_str()).map(|(amount, _)| amount).map_err(|msg| input.error(msg))
+    }
+    fn account(input: Node) -> Result<String> {
+        Ok(input.as_str().to_owned())
+    }
+    fn commodity_symbol(input: Node) -> Result<String> {
+        normalize_commodity(input.as_str()).ok_or_else(|| input.error(format!("commodity {} is not supported", input.as_str())))
+    }
+    fn status(input: Node) -> Result<Flag> {
+        Ok(match input.as_str() {
+            "*" => Flag::Okay,
+            _ => Flag::Warning,
+        })
+    }
+    fn comment_content(input: Node) -> Result<String> {
+        Ok(input.as_str().trim().to_owned())
+    }
+    fn comment(input: Node) -> Result<String> {
+        Ok(match_nodes!(input.into_children();
+            [comment_content(content)] => content,
+        ))
+    }
+
+    fn virtual_account(input: Node) -> Result<String> {
+        Ok(match_nodes!(input.into_children();
+            [account(account)] => account,
+        ))
+    }
+    fn balanced_virtual_account(input: Node) -> Result<String> {
+        Ok(match_nodes!(input.into_children();
+            [account(account)] => account,
+        ))
+    }
+    fn lot_cost(input: Node) -> Result<Amount> {
+        Ok(match_nodes!(input.into_children();
+            [amount(amount)] => amount,
+        ))
+    }
+    fn total_lot_cost(input: Node) -> Result<Amount> {
+        Ok(match_nodes!(input.into_children();
+            [amount(amount)] => amount,
+        ))
+    }
+    fn lot_date(input: Node) -> Result<NaiveDate> {
+        Ok(match_nodes!(input.into_children();
+            [date(date)] => date,
+        ))
+    }
+    fn unit_price(input: Node) -> Result<SingleTotalPrice> {
+        Ok(match_nodes!(input.into_children();
+            [amount(amount)] => SingleTotalPrice::Single(amount),
+        ))
+    }
+    fn total_price(input: Node) -> Result<SingleTotalPrice> {
+        Ok(match_nodes!(input.into_children();
+            [amount(amount)] => SingleTotalPrice::Total(amount),
+        ))
+    }
+    fn assertion(input: Node) -> Result<Amount> {
+        Ok(match_nodes!(input.into_children();
+            [amount(amount)] => amount,
+        ))
+    }
+
+    fn posting(input: Node) -> Result<JournalPosting> {
+        let raw = input.as_str().split(';').next().unwrap_or_default().trim().to_owned();
+        let error = input.error("total cost requires the amount of posting");
+        let mut status = None;
+        let mut kind = PostingKind::Real;
+        let mut account = String::new();
+        let mut parts = vec![];
+        for child in input.into_children() {
+            match child.as_rule() {
+                Rule::status => status = Some(Self::status(child)?),
+                Rule::account => account = Self::account(child)?,
+                Rule::virtual_account => {
+                    kind = PostingKind::Virtual;
+                    account = Self::virtual_account(child)?;
+                }
+                Rule::balanced_virtual_account => {
+                    kind = PostingKind::BalancedVirtual;
+                    account = Self::balanced_virtual_account(child)?;
+                }
+                Rule::amount => parts.push(PostingPart::Amount(Self::amount(child)?)),
+                Rule::lot_cost => parts.push(PostingPart::Cost(Self::lot_cost(child)?)),
+                Rule::total_lot_cost => parts.push(PostingPart::TotalCost(Self::total_lot_cost(child)?)),
+                Rule::lot_date => parts.push(PostingPart::CostDate(Self::lot_date(child)?)),
+                Rule::unit_price => parts.push(PostingPart::Price(Self::unit_price(child)?)),
+                Rule::total_price => parts.push(PostingPart::Price(Self::total_price(child)?)),
+                Rule::assertion => parts.push(PostingPart::Assertion(Self::assertion(child)?)),
+                Rule::comment => parts.push(PostingPart::Comment(Self::comment(child)?)),
+                rule => unreachable!("Unexpected posting part {:?}", rule),
+            }
+        }
+        let mut posting = JournalPosting {
+            kind,
+            status,
+            account,
+            amount: None,
+            cost: None,
+            cost_date: None,
+            price: None,
+            assertion: None,
+            comments: vec![],
+            raw,
+        };
+        let mut total_cost = None;
+        for part in parts {
+            match part {
+                PostingPart::Amount(amount) => posting.amount = Some(amount),
+                PostingPart::Cost(cost) => posting.cost = Some(cost),
+                PostingPart::TotalCost(cost) => total_cost = Some(cost),
+                PostingPart::CostDate(date) => posting.cost_date = Some(date),
+                PostingPart::Price(price) => posting.price = Some(price),
+                PostingPart::Assertion(amount) => posting.assertion = Some(amount),
+                PostingPart::Comment(comment) => posting.comments.push(comment),
+            }
+        }
+        // zhang only supports the cost per unit
+        if let Some(total_cost) = total_cost {
+            let units = posting.amount.as_ref().map(|it| it.number.abs()).filter(|it| !it.is_zero()).ok_or(error)?;
+            posting.cost = Some(Amount::new(total_cost.number / units, total_cost.currency));
+        }
+        Ok(posting)
+    }
+
+    fn auxiliary_date(input: Node) -> Result<NaiveDate> {
+        Ok(match_nodes!(input.into_children();
+            [date(date)] => date,
+        ))
+    }
+    fn code(input: Node) -> Result<String> {
+        Ok(input.as_str().trim_start_matches('(').trim_end_matches(')').to_owned())
+    }
+    fn description(input: Node) -> Result<String> {
+        Ok(input.as_str().trim().to_owned())
+    }
+
+    fn transaction(input: Node) -> Result<JournalDirective> {
+        let mut children = input.into_children();
+        let date = Self::date(children.next().expect("transaction must start with date"))?;
+        let mut headers = vec![];
+        let mut lines = vec![];
+        for child in children {
+            match child.as_rule() {
+                Rule::auxiliary_date => headers.push(TransactionHeader::AuxiliaryDate(Self::auxiliary_date(child)?)),
+                Rule::status => headers.push(TransactionHeader::Status(Self::status(child)?)),
+                Rule::code => headers.push(TransactionHeader::Code(Self::code(child)?)),
+                Rule::description => headers.push(TransactionHeader::Description(Self::description(child)?)),
+                Rule::comment => lines.push(TransactionLine::Comment(Self::comment(child)?)),
+                Rule::posting => lines.push(TransactionLine::Posting(Self::posting(child)?)),
+                rule => unreachable!("Unexpected transaction part {:?}", rule),
+            }
+        }
+        let mut trx = JournalTransaction {
+            date,
+            auxiliary_date: None,
+            status: None,
+            code: None,
+            description: None,
+            comments: vec![],
+            postings: vec![],
+        };
+        for header in headers {
+            match header {
+                TransactionHeader::AuxiliaryDate(date) => trx.auxiliary_date = Some(date),
+                TransactionHeader::Status(status) => trx.status = Some(status),
+                TransactionHeader::Code(code) => trx.code = Some(code),
+                TransactionHeader::Description(description) => trx.description = Some(description),
+            }
+        }
+        // the comment lines belong to the transaction until the first posting, and then belong to the posting above them
+        for line in lines {
+            match line {
+                TransactionLine::Comment(comment) => match trx.postings.last_mut() {
+                    Some(posting) => posting.comments.push(comment),
+                    None => trx.comments.push(comment),
+                },
+                TransactionLine::Posting(posting) => trx.postings.push(posting),
+            }
+        }
+        Ok(JournalDirective::Transaction(trx))
+    }
+
+    fn period(input: Node) -> Result<String> {
+        Ok(input.as_str().trim().to_owned())
+    }
+    fn periodic_transaction(input: Node) -> Result<JournalDirective> {
+        let raw = input.as_str().to_owned();
+        let mut period = String::new();
+        let mut postings = vec![];
+        for child in input.into_children() {
+            match child.as_rule() {
+                Rule::period => period = Self::period(child)?,
+                Rule::posting => postings.push(Self::posting(child)?),
+                Rule::description | Rule::comment => {}
+                rule => unreachable!("Unexpected periodic transaction part {:?}", rule),
+            }
+        }
+        Ok(JournalDirective::PeriodicTransaction(JournalPeriodicTransaction { period, postings, raw }))
+    }
+    fn raw_periodic_transaction(input: Node) -> Result<JournalDirective> {
+        Ok(JournalDirective::Raw(input.as_str().to_owned()))
+    }
+    fn automated_transaction(input: Node) -> Result<JournalDirective> {
+        Ok(JournalDirective::Raw(input.as_str().to_owned()))
+    }
+    fn comment_block(input: Node) -> Result<JournalDirective> {
+        Ok(JournalDirective::Raw(input.as_str().to_owned()))
+    }
+    fn line_comment(input: Node) -> Result<JournalDirective> {
+        Ok(JournalDirective::Raw(input.as_str().to_owned()))
+    }
+    fn other_directive(input: Node) -> Result<JournalDirective> {
+        Ok(JournalDirective::Raw(input.as_str().to_owned()))
+    }
+
+    fn price(input: Node) -> Result<JournalDirective> {
+        Ok(match_nodes!(input.into_children();
+            [date(date), commodity_symbol(commodity), amount(amount), comment(_)..] => JournalDirective::Price { date, time: None, commodity, amount },
+            [date(date), time(time), commodity_symbol(commodity), amount(amount), comment(_)..] => JournalDirective::Price { date, time: Some(time), commodity, amount },
+        ))
+    }
+    fn account_directive(input: Node) -> Result<JournalDirective> {
+        Ok(match_nodes!(input.into_children();
+            [account(account), comment(_)..] => JournalDirective::Account(account),
+        ))
+    }
+    fn commodity_directive(input: Node) -> Result<JournalDirective> {
+        let amount = input.children().next().expect("commodity directive must have commodity");
+        let content = amount.as_str().trim();
+        if content.contains(|it: char| it.is_ascii_digit()) {
+            let (amount, prefix) = parse_amount(content).map_err(|msg| amount.error(msg))?;
+            let precision = content
+                .rsplit_once('.')
+                .map(|(_, decimal)| decimal.chars().take_while(|it| it.is_ascii_digit()).count());
+            Ok(JournalDirective::Commodity {
+                commodity: amount.currency,
+                prefix,
+                precision: precision.or(Some(0)),
+            })
+        } else {
+            let commodity = normalize_commodity(content).ok_or_else(|| amount.error(format!("commodity {} is not supported", content)))?;
+            let prefix = (commodity != content).then(|| content.to_owned());
+            Ok(JournalDirective::Commodity {
+                commodity,
+                prefix,
+                precision: None,
+            })
+        }
+    }
+    fn include_path(input: Node) -> Result<String> {
+        Ok(input.as_str().trim().to_owned())
+    }
+    fn include(input: Node) -> Result<JournalDirective> {
+        Ok(match_nodes!(input.into_children();
+            [include_path(path)] => JournalDirective::Include(path),
+        ))
+    }
+
+    fn empty_space_line(_input: Node) -> Result<()> {
+        Ok(())
+    }
+
+    fn item(input: Node) -> Result<Option<(JournalDirective, SpanInfo)>> {
+        let span = input.as_span();
+        let span_info = SpanInfo {
+            start: span.start_pos().pos(),
+            end: span.end_pos().pos(),
+            content: span.as_str().to_string(),
+            filename: None,
+            trivia: Default::default(),
+        };
+        let ret = match_nodes!(input.into_children();
+            [transaction(item)]              => Some(item),
+            [periodic_transaction(item)]     => Some(item),
+            [raw_periodic_transaction(item)] => Some(item),
+            [automated_transaction(item)]    => Some(item),
+            [price(item)]                    => Some(item),
+            [account_directive(item)]        => Some(item),
+            [commodity_directive(item)]      => Some(item),
+            [include(item)]                  => Some(item),
+            [comment_block(item)]            => Some(item),
+            [line_comment(item)]             => Some(item),
+            [other_directive(item)]          => Some(item),
+            [empty_space_line(_)]            => None,
+        );
+        Ok(ret.map(|it| (it, span_info)))
+    }
+
+    fn entry(input: Node) -> Result<Vec<Spanned<JournalDirective>>> {
+        let ret: Vec<(JournalDirective, SpanInfo)> = match_nodes!(input.into_children();
+            [item(items).., _] => items.flatten().collect(),
+        );
+        Ok(ret.into_iter().map(|(data, span)| Spanned { data, span }).collect_vec())
+    }
+}
+
+pub fn parse(input_str: &str, file: impl Into<Option<PathBuf>>) -> Result<Vec<Spanned<JournalDirective>>> {
+    let file = file.into();
+    let inputs = JournalParser::parse(Rule::entry, input_str)?;
+    let input = inputs.single()?;
+    JournalParser::entry(input).map(|mut directives| {
+        directives.iter_mut().for_each(|directive| directive.span.filename.clone_from(&file));
+        directives
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use chrono::NaiveDate;
+    use indoc::indoc;
+    use zhang_ast::amount::Amount;
+    use zhang_ast::{Flag, SingleTotalPrice};
+
+    use crate::parser::{parse, parse_amount, JournalDirective, PostingKind};
+
+    fn amount(number: i32, commodity: &str) -> Amount {
+        Amount::new(BigDecimal::from(number), commodity)
+    }
+
+    #[test]
+    fn should_parse_amount_with_commodity_in_both_sides() {
+        assert_eq!((amount(-1000, "USD"), Some("$".to_owned())), parse_amount("$-1,000.00").unwrap());
+        assert_eq!((amount(-10, "USD"), Some("$".to_owned())), parse_amount("-$10").unwrap());
+        assert_eq!((amount(10, "CNY"), None), parse_amount("10 CNY").unwrap());
+        assert_eq!((amount(10, "EUR"), Some("EUR".to_owned())), parse_amount("EUR 10").unwrap());
+        assert_eq!(
+            (amount(3, "AAPL-2024"), Some("\"AAPL 2024\"".to_owned())),
+            parse_amount("\"AAPL 2024\" 3").unwrap()
+        );
+        assert!(parse_amount("10").is_err());
+    }
+
+    #[test]
+    fn should_parse_transaction() {
+        let directives = parse(
+            indoc! {r#"
+                2024/01/05=2024/01/07 * (#102) Shop | Buy stocks  ; :stock:
+                    ; trip: japan
+                    Assets:Broker:Stock    10 AAPL {{$1,500.00}} [2024/01/01] @ $160
+                    ; a comment of posting
+                    (Budget:Stock)  $-1500
+                    [Assets:Savings]  $10
+                    Assets:Bank Account    = $200
+            "#},
+            None,
+        )
+        .unwrap();
+        assert_eq!(1, directives.len());
+        let JournalDirective::Transaction(trx) = &directives[0].data else {
+            panic!("should be transaction")
+        };
+        assert_eq!(NaiveDate::from_ymd_opt(2024, 1, 5).unwrap(), trx.date);
+        assert_eq!(Some(NaiveDate::from_ymd_opt(2024, 1, 7).unwrap()), trx.auxiliary_date);
+        assert_eq!(Some(Flag::Okay), trx.status);
+        assert_eq!(Some("#102".to_owned()), trx.code);
+        assert_eq!(Some("Shop | Buy stocks".to_owned()), trx.description);
+        assert_eq!(vec![":stock:".to_owned(), "trip: japan".to_owned()], trx.comments);
+        assert_eq!(4, trx.postings.len());
+
+        let stock = &trx.postings[0];
+        assert_eq!("Assets:Broker:Stock", stock.account);
+        assert_eq!(Some(amount(10, "AAPL")), stock.amount);
+        assert_eq!(Some(amount(150, "USD")), stock.cost);
+        assert_eq!(Some(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()), stock.cost_date);
+        assert_eq!(Some(SingleTotalPrice::Single(amount(160, "USD"))), stock.price);
+        assert_eq!(vec!["a comment of posting".to_owned()], stock.comments);
+
+        assert_eq!(PostingKind::Virtual, trx.postings[1].kind);
+        assert_eq!("(Budget:Stock)  $-1500", trx.postings[1].raw);
+        assert_eq!(PostingKind::BalancedVirtual, trx.postings[2].kind);
+        assert_eq!("Assets:Savings", trx.postings[2].account);
+        assert_eq!("Assets:Bank Account", trx.postings[3].account);
+        assert_eq!(None, trx.postings[3].amount);
+        assert_eq!(Some(amount(200, "USD")), trx.postings[3].assertion);
+    }
+
+    #[test]
+    fn should_keep_unsupported_directives_as_raw() {
+        let directives = parse(
+            indoc! {r#"
+                ; comment
+                = expenses:food
+                    (Budget:Food)  -1
+
+                alias checking = Assets:Bank
+                commodity $1,000.00
+                P 2024/01/01 EUR $1.10
+            "#},
+            None,
+        )
+        .unwrap();
+        let directives = directives.into_iter().map(|it| it.data).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                JournalDirective::Raw("; comment".to_owned()),
+                JournalDirective::Raw("= expenses:food\n    (Budget:Food)  -1".to_owned()),
+                JournalDirective::Raw("alias checking = Assets:Bank".to_owned()),
+                JournalDirective::Commodity {
+                    commodity: "USD".to_owned(),
+                    prefix: Some("$".to_owned()),
+                    precision: Some(2)
+                },
+                JournalDirective::Price {
+                    date: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+                    time: None,
+                    commodity: "EUR".to_owned(),
+                    amount: Amount::new("1.10".parse().unwrap(), "USD")
+                },
+            ],
+            directives
+        );
+    }
+
+    #[test]
+    fn should_parse_periodic_transaction() {
+        let content = indoc! {r#"
+            ~ monthly from 2024/01  household budget  ; goals
+                ; a comment
+                Expenses:Rent  $500
+                Assets:Bank
+            ~ every 2 weeks
+                Expenses:Food  $100
+                Assets:Bank
+        "#};
+        let directives = parse(content, None).unwrap();
+        assert_eq!(2, directives.len());
+        let JournalDirective::PeriodicTransaction(trx) = &directives[0].data else {
+            panic!("should be periodic transaction")
+        };
+        assert_eq!("monthly from 2024/01", trx.period);
+        assert_eq!(2, trx.postings.len());
+        assert_eq!("Expenses:Rent", trx.postings[0].account);
+        assert_eq!(Some(amount(500, "USD")), trx.postings[0].amount);
+        assert_eq!(None, trx.postings[1].amount);
+        assert_eq!(content.lines().take(4).collect::<Vec<_>>().join("\n"), trx.raw);
+
+        let JournalDirective::PeriodicTransaction(trx) = &directives[1].data else {
+            panic!("should be periodic transaction")
+        };
+        assert_eq!("every 2 weeks", trx.period);
+    }
+}
@@ -234,12 +234,15 @@ export default function App() {
             loading: false,
             autoClose: 3000,
           });
-          mutate('/api/for-new-transaction');
           dispatch(fetchBasicInfo());
           dispatch(fetchError(1));
-          dispatch(fetchCommodities());
-          dispatch(accountsSlice.actions.clear());
-          dispatch(journalsSlice.actions.clear());
+          // the views of directives are kept if the reload changes nothing but whitespaces or comments
+          if (data.changes?.date_range || data.changes?.metas_changed) {
+            mutate('/api/for-new-transaction');
+            dispatch(fetchCommodities());
+            dispatch(accountsSlice.actions.clear());
+            dispatch(journalsSlice.actions.clear());
+          }
           break;
         case 'Connected':
           notifications.show({
@@ -249,6 +252,16 @@ export default function App() {
           });
           dispatch(fetchBasicInfo());
           break;
+        case 'ReloadFailed':
+          notifications.update({
+            id: 'leger-reload',
+            title: '[Ledger Reload] failed',
+            message: data.error,
+            color: 'red',
+            loading: false,
+            autoClose: 5000,
+          });
+          break;
         case 'NewVersionFound':
           dispatch(basicInfoSlice.actions.setUpdatableVersion({ newVersion: data.version }));
           break;
@@ -55,7 +55,7 @@ export default function ErrorBox() {
         onClose={() => setIsOpen(false)}
         title={`${selectError?.span.filename}:${selectError?.span.start}:${selectError?.span.end}`}
       >
-        <Text>{t(`ERROR.${selectError?.error_type || ''}`)}</Text>
+        <Text>{t(`ERROR.${selectError?.error_type || ''}`, selectError?.metas)}</Text>
         <Textarea
           value={selectErrorContent}
           onChange={(event) => {
@@ -74,7 +74,7 @@ export default function ErrorBox() {
       <Stack>
         {items.map((error, idx) => (
           <Text key={idx} onClick={() => toggleError(error)}>
-            {t(`ERROR.${error.error_type}`)}
+            {t(`ERROR.${error.error_type}`, error.metas)}
           </Text>
         ))}
 
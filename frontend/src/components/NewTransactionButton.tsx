@@ -28,7 +28,7 @@ export default function NewTransactionButton() {
         showNotification({
           title: 'Fail to create new Transaction',
           color: 'red',
-          message: error?.response?.data ?? '',
+          message: error?.response?.data?.message ?? '',
           autoClose: false,
         });
         console.log(error);
@@ -27,6 +27,10 @@ const useStyles = createStyles((theme, _, u) => ({
   warning: {
     borderLeft: `3px solid ${theme.colors.orange[7]}`,
   },
+  voided: {
+    opacity: 0.5,
+    textDecoration: 'line-through',
+  },
   actionHider: {
     '&:hover': {
       [`& .${getStylesRef('actions')}`]: {
@@ -77,11 +81,11 @@ export default function TableViewTransactionLine({ data }: Props) {
   const summary = calculate(data);
   const hasDocuments = data.metas.some((meta) => meta.key === 'document');
   return (
-    <Table.Tr className={`${classes.actionHider} ${!data.is_balanced ? classes.notBalance : ''} ${data.flag === '!' ? classes.warning : ''}`}>
+    <Table.Tr className={`${classes.actionHider} ${!data.is_balanced ? classes.notBalance : ''} ${data.flag === '!' ? classes.warning : ''} ${data.flag === 'void' ? classes.voided : ''}`}>
       <Table.Td>{time}</Table.Td>
       <Table.Td>
         <Badge color="gray" size="xs" variant="outline">
-          TRX
+          {data.flag === 'void' ? 'VOID' : 'TRX'}
         </Badge>
       </Table.Td>
       <Table.Td>
@@ -30,10 +30,19 @@ if (development) {
   console.log(`active backend is ${backendUri}`);
 }
 export const serverBaseUrl = development ? backendUri : '';
+
+// the ledger of multi-ledger server is selected by the page query `?ledger=NAME`, and kept for the session
+const ledgerFromQuery = new URLSearchParams(window.location.search).get('ledger');
+if (ledgerFromQuery) {
+  sessionStorage.setItem('ledger', ledgerFromQuery);
+}
+const selectedLedger = sessionStorage.getItem('ledger');
+
 export const axiosInstance = axios.create({
   baseURL: serverBaseUrl,
   headers: {
     'Content-type': 'application/json',
+    ...(selectedLedger ? { 'X-Zhang-Ledger': selectedLedger } : {}),
   },
 });
 
@@ -43,7 +43,7 @@ export default function SingleCommodity() {
                 <Table.Tr key={idx}>
                   <Table.Td>{it.account}</Table.Td>
                   <Table.Td>
-                    {it.price_amount} {it.price_commodity}
+                    {it.price_amount} {it.price_commodity} {it.label && `"${it.label}"`}
                   </Table.Td>
                   <Table.Td>{it.amount}</Table.Td>
                 </Table.Tr>
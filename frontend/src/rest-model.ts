@@ -55,6 +55,7 @@ export interface Document {
   extension?: string;
   account?: string;
   trx_id: string;
+  hash?: string;
 }
 
 export interface InfoForNewTransaction {
@@ -99,6 +100,7 @@ export interface CommodityLot {
   amount: string;
   price_amount?: string;
   price_commodity?: string;
+  label?: string;
   account: string;
 }
 
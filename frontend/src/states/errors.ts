@@ -3,18 +3,43 @@ import { fetcher } from '..';
 import { LoadingState, SpanInfo } from '../rest-model';
 
 export enum LedgerErrorType {
+  ParseError = 'ParseError',
   AccountBalanceCheckError = 'AccountBalanceCheckError',
+  AccountBalanceCheckWithinTolerance = 'AccountBalanceCheckWithinTolerance',
+  AccountReconciledBalanceCheckError = 'AccountReconciledBalanceCheckError',
   AccountDoesNotExist = 'AccountDoesNotExist',
   AccountClosed = 'AccountClosed',
   TransactionDoesNotBalance = 'TransactionDoesNotBalance',
   CommodityDoesNotDefine = 'CommodityDoesNotDefine',
   TransactionHasMultipleImplicitPosting = 'TransactionHasMultipleImplicitPosting',
+  CommodityNotAllowedInAccount = 'CommodityNotAllowedInAccount',
+  PostingAmountExceedsLimit = 'PostingAmountExceedsLimit',
+  TransactionMissingRequiredTag = 'TransactionMissingRequiredTag',
+  MissingRequiredMeta = 'MissingRequiredMeta',
+  InvalidMetaType = 'InvalidMetaType',
+  DuplicatedTransaction = 'DuplicatedTransaction',
+  PluginValidationError = 'PluginValidationError',
+  PluginExecutionError = 'PluginExecutionError',
+  DuplicatedAccountRename = 'DuplicatedAccountRename',
+  AccountRenameCycle = 'AccountRenameCycle',
+  LotNotFound = 'LotNotFound',
+  InvalidSplitRatio = 'InvalidSplitRatio',
+  InvalidDepreciationLifetime = 'InvalidDepreciationLifetime',
+  InvalidLoanTerm = 'InvalidLoanTerm',
+  InvalidAccrualPeriods = 'InvalidAccrualPeriods',
+  TaxAccountMissing = 'TaxAccountMissing',
+  InvalidInvoice = 'InvalidInvoice',
+  DuplicatedInvoice = 'DuplicatedInvoice',
+  InvalidBudgetRollover = 'InvalidBudgetRollover',
 }
 
+export type LedgerErrorSeverity = 'error' | 'warning' | 'info';
+
 export interface LedgerError {
   id: string;
   span: SpanInfo;
   error_type: LedgerErrorType;
+  severity: LedgerErrorSeverity;
   metas: { [key: string]: string };
 }
 
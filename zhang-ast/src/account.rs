@@ -5,6 +5,7 @@ use serde::{Deserialize, Serialize};
 use strum::{Display, EnumString};
 
 #[derive(Debug, EnumString, PartialEq, Eq, Display, Deserialize, Serialize, Copy, Clone, Hash)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum AccountType {
     Assets,
     Liabilities,
@@ -5,8 +5,10 @@ use bigdecimal::{BigDecimal, Zero};
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct CalculatedAmount {
     pub calculated: Amount,
+    #[cfg_attr(feature = "openapi", schema(value_type = HashMap<String, String>))]
     pub detail: HashMap<String, BigDecimal>,
 }
 
@@ -22,7 +24,9 @@ impl CalculatedAmount {
 }
 
 #[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Amount {
+    #[cfg_attr(feature = "openapi", schema(value_type = String, format = "decimal", example = "100.00"))]
     pub number: BigDecimal,
     pub currency: String,
 }
@@ -1,8 +1,9 @@
 use std::collections::HashSet;
-use std::ops::{Div, Mul, Neg};
+use std::ops::{Div, Mul, Neg, Sub};
+use std::str::FromStr;
 
-use bigdecimal::{BigDecimal, One};
-use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use bigdecimal::{BigDecimal, One, Signed, Zero};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
 use chrono_tz::Tz;
 use indexmap::IndexSet;
 use itertools::Itertools;
@@ -11,17 +12,22 @@ use serde::{Deserialize, Serialize};
 use crate::amount::Amount;
 use crate::error::ErrorKind;
 use crate::models::*;
-use crate::utils::inventory::{AmountLotPair, Inventory, LotInfo};
+use crate::utils::inventory::{Inventory, LotInfo};
 use crate::utils::multi_value_map::MultiValueMap;
-use crate::Account;
+use crate::{Account, Currency};
 
 pub type Meta = MultiValueMap<String, ZhangString>;
 
+const SPLIT_WEIGHT: &str = "split-weight";
+const VOID: &str = "void";
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub enum Date {
     Date(NaiveDate),
     DateHour(NaiveDateTime),
     Datetime(NaiveDateTime),
+    /// the datetime with explicit offset, e.g. `2024-05-01 09:30:00+09:00`, which is converted into the timezone of ledger
+    ZonedDatetime(DateTime<FixedOffset>),
 }
 
 impl Date {
@@ -29,13 +35,18 @@ impl Date {
         Date::Datetime(Utc::now().with_timezone(timezone).naive_local())
     }
     pub fn to_timezone_datetime(&self, timezone: &Tz) -> DateTime<Tz> {
-        timezone.from_local_datetime(&self.naive_datetime()).unwrap()
+        match self {
+            Date::ZonedDatetime(datetime) => datetime.with_timezone(timezone),
+            _ => timezone.from_local_datetime(&self.naive_datetime()).unwrap(),
+        }
     }
-    pub(crate) fn naive_datetime(&self) -> NaiveDateTime {
+    /// the datetime as it is written, the offset of zoned datetime is ignored
+    pub fn naive_datetime(&self) -> NaiveDateTime {
         match self {
             Date::Date(date) => date.and_hms_opt(0, 0, 0).expect("cannot construct naive datetime from naive date"),
             Date::DateHour(date_hour) => *date_hour,
             Date::Datetime(datetime) => *datetime,
+            Date::ZonedDatetime(datetime) => datetime.naive_local(),
         }
     }
     pub fn naive_date(&self) -> NaiveDate {
@@ -43,6 +54,7 @@ impl Date {
             Date::Date(date) => *date,
             Date::DateHour(date_hour) => date_hour.date(),
             Date::Datetime(datetime) => datetime.date(),
+            Date::ZonedDatetime(datetime) => datetime.date_naive(),
         }
     }
     pub fn as_budget_interval(&self) -> u32 {
@@ -92,13 +104,38 @@ pub struct BalancePad {
     pub meta: Meta,
 }
 
+/// pad the account from `pad` account, the amount is decided by the next balance assertion of the account
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Pad {
+    pub date: Date,
+    pub account: Account,
+    pub pad: Account,
+    pub meta: Meta,
+}
+
+/// how the posting takes part in the balance of transaction
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum PostingType {
+    #[default]
+    Real,
+    /// written as `(Account)`, it is excluded from the balance of transaction, so that it must have explicit amount
+    Virtual,
+    /// written as `[Account]`, the balanced virtual postings balance among themselves instead of with the real ones
+    BalancedVirtual,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct Posting {
     pub flag: Option<Flag>,
+    #[serde(default)]
+    pub posting_type: PostingType,
     pub account: Account,
     pub units: Option<Amount>,
     pub cost: Option<Amount>,
     pub cost_date: Option<Date>,
+    /// the label of lot, e.g. `"lot-A"` of `{ 12.3 USD, "lot-A" }`, which is used to reduce the lot by label
+    #[serde(default)]
+    pub cost_label: Option<String>,
     pub price: Option<SingleTotalPrice>,
     pub comment: Option<String>,
     pub meta: Meta,
@@ -113,6 +150,9 @@ impl Posting {
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct Transaction {
     pub date: Date,
+    /// the auxiliary date written as `2024-05-01=2024-05-03`, e.g. the effective date of transaction
+    #[serde(default)]
+    pub auxiliary_date: Option<Date>,
     pub flag: Option<Flag>,
     pub payee: Option<ZhangString>,
     pub narration: Option<ZhangString>,
@@ -123,11 +163,22 @@ pub struct Transaction {
 }
 
 impl Transaction {
+    /// whether the transaction is voided by meta `void: "true"`, the voided transaction is kept in journal but excluded from balances and reports
+    pub fn is_voided(&self) -> bool {
+        self.meta.get_one(VOID).is_some_and(|it| it.as_str().trim().eq_ignore_ascii_case("true"))
+    }
+
+    /// the inventory of real postings, which should be balanced
     pub fn get_postings_inventory(&self) -> Result<Inventory, ErrorKind> {
+        self.get_postings_inventory_of(PostingType::Real)
+    }
+
+    /// the inventory of postings of the type, the virtual postings are never balanced while the others are balanced per type
+    pub fn get_postings_inventory_of(&self, posting_type: PostingType) -> Result<Inventory, ErrorKind> {
         let mut inventory = Inventory {
             currencies: Default::default(),
         };
-        for posting in self.txn_postings() {
+        for posting in self.txn_postings().into_iter().filter(|it| it.posting.posting_type == posting_type) {
             let amount = posting.infer_trade_amount()?;
             let lot_info = posting.lots().unwrap_or(LotInfo::Fifo);
             inventory.add_lot(amount, lot_info);
@@ -159,22 +210,23 @@ impl<'a> TxnPosting<'a> {
     /// `Assets:Card 1 CNY @ 10 AAA` then cost `10 AAA` can be indicated from single price`@ 10 AAA`
     pub fn costs(&self) -> Option<Amount> {
         self.posting.cost.clone().or_else(|| {
-            self.posting.price.as_ref().map(|price| match price {
-                SingleTotalPrice::Single(single_price) => single_price.clone(),
-                SingleTotalPrice::Total(total_price) => Amount::new(
-                    (&total_price.number).div(self.posting.units.as_ref().map(|it| &it.number).unwrap_or(&BigDecimal::one())),
-                    total_price.currency.clone(),
-                ),
+            self.posting.price.as_ref().and_then(|price| match price {
+                SingleTotalPrice::Single(single_price) => Some(single_price.clone()),
+                SingleTotalPrice::Total(total_price) => {
+                    let units = self.posting.units.as_ref().map(|it| it.number.clone()).unwrap_or_else(BigDecimal::one);
+                    // the cost of each unit cannot be indicated from the total price of zero units
+                    (!units.is_zero()).then(|| Amount::new((&total_price.number).div(units), total_price.currency.clone()))
+                }
             })
         })
     }
     /// trade amount means the amount used for other postings to calculate balance
     /// 1. if `unit` is null, return null
     /// 2. if `unit` is present,
-    /// 2.1 return `unit * cost`, if cost is present
-    /// 2.2 return `unit * single_price`, if single price is present
-    /// 2.3 return `total_price`, if total price is present
-    /// 2.4 return `unit`, if both cost and price are not present.
+    ///    2.1 return `unit * cost`, if cost is present
+    ///    2.2 return `unit * single_price`, if single price is present
+    ///    2.3 return `total_price`, if total price is present
+    ///    2.4 return `unit`, if both cost and price are not present.
     pub fn trade_amount(&self) -> Option<Amount> {
         self.posting
             .units
@@ -189,44 +241,86 @@ impl<'a> TxnPosting<'a> {
             })
     }
 
-    /// infer the trade amount based on other postings, if it's trade amount is null
+    /// infer the trade amount based on other postings of the same type, if it's trade amount is null
+    /// if there are multiple postings without trade amount, the residual is split across them by the `split-weight` meta,
+    /// or equally if none of them has the meta. The remainder of rounding goes to the last one.
+    /// the virtual posting cannot be inferred since it does not take part in the balance
     pub fn infer_trade_amount(&self) -> Result<Amount, ErrorKind> {
         self.trade_amount().map(Ok).unwrap_or_else(|| {
+            if self.posting.posting_type == PostingType::Virtual {
+                return Err(ErrorKind::TransactionCannotInferTradeAmount);
+            }
             // get other postings' trade amount
-            let (trade_amount_postings, non_trade_amount_postings): (Vec<AmountLotPair>, Vec<AmountLotPair>) = self
+            let txn_postings = self
                 .txn
                 .txn_postings()
-                .iter()
-                .map(|it| (it.trade_amount(), it.lots()))
-                .partition(|it| it.0.is_some());
-            match non_trade_amount_postings.len() {
-                0 => unreachable!("txn should not have zero posting"),
-                1 => {
-                    let mut inventory = Inventory {
-                        currencies: Default::default(),
-                    };
-                    for (trade_amount, lot) in trade_amount_postings {
-                        if let Some(trade_amount) = trade_amount {
-                            let info = lot.unwrap_or(LotInfo::Fifo);
-                            inventory.add_lot(trade_amount, info);
-                        }
-                    }
-                    match inventory.size() {
-                        0 => Err(ErrorKind::TransactionCannotInferTradeAmount),
-                        1 => {
-                            if let Some(inventory_balance) = inventory.pop() {
-                                Ok(inventory_balance.neg())
-                            } else {
-                                Err(ErrorKind::TransactionCannotInferTradeAmount)
-                            }
-                        }
-                        _ => Err(ErrorKind::TransactionExplicitPostingHaveMultipleCommodity),
-                    }
+                .into_iter()
+                .filter(|it| it.posting.posting_type == self.posting.posting_type)
+                .collect_vec();
+            let (trade_amount_postings, non_trade_amount_postings): (Vec<&TxnPosting>, Vec<&TxnPosting>) =
+                txn_postings.iter().partition(|it| it.trade_amount().is_some());
+            if non_trade_amount_postings.is_empty() {
+                unreachable!("txn should not have zero posting")
+            }
+
+            let mut inventory = Inventory {
+                currencies: Default::default(),
+            };
+            for txn_posting in trade_amount_postings {
+                if let Some(trade_amount) = txn_posting.trade_amount() {
+                    let info = txn_posting.lots().unwrap_or(LotInfo::Fifo);
+                    inventory.add_lot(trade_amount, info);
                 }
-                _ => Err(ErrorKind::TransactionHasMultipleImplicitPosting),
             }
+            let residual = match (inventory.size(), non_trade_amount_postings.len()) {
+                (0, 1) => return Err(ErrorKind::TransactionCannotInferTradeAmount),
+                (0, _) => return Err(ErrorKind::TransactionHasMultipleImplicitPosting),
+                (1, _) => inventory.pop().ok_or(ErrorKind::TransactionCannotInferTradeAmount)?.neg(),
+                _ => return Err(ErrorKind::TransactionExplicitPostingHaveMultipleCommodity),
+            };
+            if non_trade_amount_postings.len() == 1 {
+                return Ok(residual);
+            }
+
+            let weights = non_trade_amount_postings.iter().map(|it| it.split_weight()).collect_vec();
+            let weights: Vec<BigDecimal> = if weights.iter().all(|it| it.is_none()) {
+                vec![BigDecimal::one(); weights.len()]
+            } else {
+                // all implicit postings should have valid weight, otherwise the split is ambiguous
+                weights
+                    .into_iter()
+                    .map(|it| it.filter(|weight| weight.is_positive()))
+                    .collect::<Option<Vec<BigDecimal>>>()
+                    .ok_or(ErrorKind::TransactionAmbiguousSplit)?
+            };
+            let total_weight: BigDecimal = weights.iter().sum();
+            let scale = residual.number.as_bigint_and_exponent().1.max(0);
+            let shares = weights
+                .iter()
+                .map(|weight| (&residual.number).mul(weight).div(&total_weight).with_scale(scale))
+                .collect_vec();
+
+            let idx = non_trade_amount_postings
+                .iter()
+                .position(|it| std::ptr::eq(it.posting, self.posting))
+                .expect("posting should be in its transaction");
+            let share = if idx == shares.len() - 1 {
+                (&residual.number).sub(shares.iter().take(idx).sum::<BigDecimal>())
+            } else {
+                shares[idx].clone()
+            };
+            Ok(Amount::new(share, residual.currency))
         })
     }
+
+    /// weight of splitting the residual, given by posting meta `split-weight`
+    fn split_weight(&self) -> Option<BigDecimal> {
+        self.posting
+            .meta
+            .get_one(SPLIT_WEIGHT)
+            .map(|weight| BigDecimal::from_str(weight.as_str().trim()).unwrap_or_else(|_| BigDecimal::zero()))
+    }
+
     pub fn lots(&self) -> Option<LotInfo> {
         if let Some(unit) = &self.posting.units {
             if let Some(cost) = &self.posting.cost {
@@ -234,6 +328,7 @@ impl<'a> TxnPosting<'a> {
             } else if let Some(price) = &self.posting.price {
                 match price {
                     SingleTotalPrice::Single(amount) => Some(LotInfo::Lot(amount.currency.clone(), amount.number.clone())),
+                    SingleTotalPrice::Total(_) if unit.number.is_zero() => None,
                     SingleTotalPrice::Total(amount) => Some(LotInfo::Lot(amount.currency.clone(), (&amount.number).div(&unit.number))),
                 }
             } else {
@@ -291,6 +386,134 @@ pub struct Price {
     pub meta: Meta,
 }
 
+/// the stock split of commodity, every `denominator` units held become `numerator` units and the cost per unit is
+/// adjusted inversely, e.g. `4:1` split turns 10 shares at 100 USD into 40 shares at 25 USD
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Split {
+    pub date: Date,
+
+    pub currency: String,
+    pub numerator: BigDecimal,
+    pub denominator: BigDecimal,
+
+    pub meta: Meta,
+}
+
+/// the depreciation schedule of asset, the purchase amount is written off from `account` into `expense_account`
+/// month by month within the lifetime
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Depreciation {
+    pub date: Date,
+
+    pub account: Account,
+    pub expense_account: Account,
+    pub amount: Amount,
+    pub method: DepreciationMethod,
+    /// the lifetime of asset in months
+    pub lifetime: u32,
+
+    pub meta: Meta,
+}
+
+/// the loan repaid by equal monthly payments within the term, each payment repays the interest of remaining principal
+/// from `payment_account` into `interest_account`, and the rest of payment repays the principal of `account`
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Loan {
+    pub date: Date,
+
+    pub account: Account,
+    pub principal: Amount,
+    /// the annual interest rate in percentage
+    pub rate: BigDecimal,
+    /// the term of loan in months
+    pub term: u32,
+    pub payment_account: Account,
+    pub interest_account: Account,
+
+    pub meta: Meta,
+}
+
+/// the amount deferred in `deferral_account` is recognized into `account` evenly over the periods, starting from the date,
+/// e.g. an annual insurance bill paid into prepaid expenses is recognized as expense monthly
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Accrue {
+    pub date: Date,
+
+    pub deferral_account: Account,
+    pub account: Account,
+    /// the total amount recognized into `account`, which is negative for income
+    pub amount: Amount,
+    pub periods: u32,
+    pub interval: AccrualInterval,
+
+    pub meta: Meta,
+}
+
+/// the reusable skeleton of transaction, e.g. the rent shared with roommates, whose amounts are expressions of placeholders
+/// like `{total} / 2`. it is instantiated into a transaction by filling the placeholders with concrete amounts
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Template {
+    pub date: Date,
+    pub name: String,
+    pub payee: Option<ZhangString>,
+    pub narration: Option<ZhangString>,
+    pub tags: IndexSet<String>,
+    pub links: IndexSet<String>,
+    pub postings: Vec<TemplatePosting>,
+    pub meta: Meta,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct TemplatePosting {
+    pub posting_type: PostingType,
+    pub account: Account,
+    /// the posting without amount is inferred as the one of transaction
+    pub amount: Option<TemplateAmount>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct TemplateAmount {
+    /// the number expression as it is written, e.g. `-{total}` or `({total} - {deposit}) / 2`
+    pub expression: String,
+    pub currency: Currency,
+}
+
+impl TemplateAmount {
+    /// the names of placeholders in the order of appearance
+    pub fn placeholders(&self) -> Vec<String> {
+        self.expression
+            .split('{')
+            .skip(1)
+            .filter_map(|it| it.split_once('}').map(|(name, _)| name.trim().to_owned()))
+            .collect_vec()
+    }
+}
+
+impl Template {
+    /// the unique names of placeholders among all postings in the order of appearance
+    pub fn placeholders(&self) -> Vec<String> {
+        self.postings
+            .iter()
+            .filter_map(|posting| posting.amount.as_ref())
+            .flat_map(|amount| amount.placeholders())
+            .unique()
+            .collect_vec()
+    }
+}
+
+/// the account is opened with its initial balance against `equity_account`, which is `Equity:Opening-Balances` if absent,
+/// e.g. the balances migrated from other tools
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct OpenBalance {
+    pub date: Date,
+
+    pub account: Account,
+    pub amount: Amount,
+    pub equity_account: Option<Account>,
+
+    pub meta: Meta,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct Document {
     pub date: Date,
@@ -327,6 +550,15 @@ pub struct Plugin {
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct Include {
     pub file: ZhangString,
+    /// the file is only included under the profile if present, which is written as `include-if "{PROFILE}" "{FILE}"`
+    pub profile: Option<ZhangString>,
+}
+
+/// rename the account and its sub-accounts in all directives, used to restructure accounts without editing the history
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Rename {
+    pub from: Account,
+    pub to: Account,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
@@ -334,6 +566,14 @@ pub struct Comment {
     pub content: String,
 }
 
+/// the part of file which cannot be parsed, it is kept as it is so that the rest of file keeps working
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Malformed {
+    pub content: String,
+    /// the reason why it cannot be parsed
+    pub message: String,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct Budget {
     pub date: Date,
@@ -1,23 +1,86 @@
-use serde::Serialize;
-use strum::Display;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
 
-#[derive(Debug, Display, Clone, PartialEq, Eq, Serialize)]
+/// how loud an error is, ordered from the quietest to the loudest
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, EnumString)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum ErrorSeverity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Display, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, EnumString)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum ErrorKind {
+    /// the directive cannot be parsed, it is skipped and the rest of file is still loaded
+    ParseError,
+
     UnbalancedTransaction,
     TransactionCannotInferTradeAmount,
     TransactionHasMultipleImplicitPosting,
     TransactionExplicitPostingHaveMultipleCommodity,
+    TransactionAmbiguousSplit,
     InvalidFlag,
 
     AccountBalanceCheckError,
+    /// the balance assertion is within the tolerance, which is a warning rather than an error
+    AccountBalanceCheckWithinTolerance,
+    /// the balance of reconciled postings does not match the balance assertion with `reconciled: true` meta
+    AccountReconciledBalanceCheckError,
     AccountDoesNotExist,
     AccountClosed,
     TransactionDoesNotBalance,
     CommodityDoesNotDefine,
     CloseNonZeroAccount,
+    LotBecomesNegative,
+    /// the reduction targets a lot label which is not held by the account
+    LotNotFound,
+    /// the ratio of split directive has zero on either side
+    InvalidSplitRatio,
+    /// the lifetime of depreciation directive is zero month
+    InvalidDepreciationLifetime,
+    /// the term of loan directive is zero month
+    InvalidLoanTerm,
+    /// the periods of accrue directive is zero, or too many to be scheduled
+    InvalidAccrualPeriods,
+    /// the posting to be split by `tax-rate` meta has no `tax-account` to post the tax into
+    TaxAccountMissing,
+    /// the invoice custom directive misses its id, counterparty, receivable or payable account, due date or items in one commodity
+    InvalidInvoice,
+    DuplicatedInvoice,
+    CommodityNotAllowedInAccount,
+    PostingAmountExceedsLimit,
+    TransactionMissingRequiredTag,
+    /// the directive or posting misses the meta required by option `meta_schema`
+    MissingRequiredMeta,
+    /// the value of meta does not match the type declared by option `meta_schema`
+    InvalidMetaType,
+    /// the transaction is a probable duplicate of an earlier one, which is a warning rather than an error
+    DuplicatedTransaction,
 
     BudgetDoesNotExist,
     DefineDuplicatedBudget,
+    /// the `rollover` meta of budget is none of `carry`, `carry_positive`, `carry_negative` and `reset`, the budget carries as `carry`
+    InvalidBudgetRollover,
+
+    DuplicatedAccountRename,
+    AccountRenameCycle,
 
     MultipleOperatingCurrencyDetect,
+
+    PluginValidationError,
+    PluginExecutionError,
+}
+
+impl ErrorKind {
+    /// the default severity of error kind, it can be overridden by option `error_severity`
+    pub fn severity(&self) -> ErrorSeverity {
+        match self {
+            ErrorKind::AccountBalanceCheckWithinTolerance | ErrorKind::DuplicatedTransaction => ErrorSeverity::Warning,
+            _ => ErrorSeverity::Error,
+        }
+    }
 }
@@ -7,7 +7,10 @@ use strum::{Display, EnumString};
 
 use crate::account::Account;
 use crate::amount::Amount;
-use crate::data::{Close, Comment, Commodity, Custom, Document, Event, Include, Note, Open, Options, Plugin, Price, Transaction};
+use crate::data::{
+    Accrue, Close, Comment, Commodity, Custom, Date, Depreciation, Document, Event, Include, Loan, Malformed, Note, Open, OpenBalance, Options, Pad, Plugin,
+    Price, Rename, Split, Template, Transaction,
+};
 use crate::error::ErrorKind;
 use crate::{BalanceCheck, BalancePad, Budget, BudgetAdd, BudgetClose, BudgetTransfer, Meta};
 
@@ -17,17 +20,26 @@ pub enum DirectiveType {
     Close,
     Commodity,
     Transaction,
+    Template,
     BalancePad,
     BalanceCheck,
+    Pad,
     Note,
     Document,
     Price,
+    Split,
+    Depreciation,
+    Loan,
+    Accrue,
+    OpenBalance,
     Event,
     Custom,
     Option,
     Plugin,
     Include,
     Comment,
+    Malformed,
+    Rename,
 
     Budget,
     BudgetAdd,
@@ -41,17 +53,26 @@ pub enum Directive {
     Close(Close),
     Commodity(Commodity),
     Transaction(Transaction),
+    Template(Template),
     BalancePad(BalancePad),
     BalanceCheck(BalanceCheck),
+    Pad(Pad),
     Note(Note),
     Document(Document),
     Price(Price),
+    Split(Split),
+    Depreciation(Depreciation),
+    Loan(Loan),
+    Accrue(Accrue),
+    OpenBalance(OpenBalance),
     Event(Event),
     Custom(Custom),
     Option(Options),
     Plugin(Plugin),
     Include(Include),
     Comment(Comment),
+    Malformed(Malformed),
+    Rename(Rename),
 
     Budget(Budget),
     BudgetAdd(BudgetAdd),
@@ -61,27 +82,39 @@ pub enum Directive {
 
 impl Directive {
     pub fn datetime(&self) -> Option<NaiveDateTime> {
+        self.date().map(|it| it.naive_datetime())
+    }
+    pub fn date(&self) -> Option<&Date> {
         match self {
-            Directive::Open(open) => Some(open.date.naive_datetime()),
-            Directive::Close(close) => Some(close.date.naive_datetime()),
-            Directive::Commodity(commodity) => Some(commodity.date.naive_datetime()),
-            Directive::Transaction(txn) => Some(txn.date.naive_datetime()),
-            Directive::BalanceCheck(check) => Some(check.date.naive_datetime()),
-            Directive::BalancePad(pad) => Some(pad.date.naive_datetime()),
-            Directive::Note(note) => Some(note.date.naive_datetime()),
-            Directive::Document(document) => Some(document.date.naive_datetime()),
-            Directive::Price(price) => Some(price.date.naive_datetime()),
-            Directive::Event(event) => Some(event.date.naive_datetime()),
-            Directive::Custom(custom) => Some(custom.date.naive_datetime()),
+            Directive::Open(open) => Some(&open.date),
+            Directive::Close(close) => Some(&close.date),
+            Directive::Commodity(commodity) => Some(&commodity.date),
+            Directive::Transaction(txn) => Some(&txn.date),
+            Directive::Template(template) => Some(&template.date),
+            Directive::BalanceCheck(check) => Some(&check.date),
+            Directive::BalancePad(pad) => Some(&pad.date),
+            Directive::Pad(pad) => Some(&pad.date),
+            Directive::Note(note) => Some(&note.date),
+            Directive::Document(document) => Some(&document.date),
+            Directive::Price(price) => Some(&price.date),
+            Directive::Split(split) => Some(&split.date),
+            Directive::Depreciation(depreciation) => Some(&depreciation.date),
+            Directive::Loan(loan) => Some(&loan.date),
+            Directive::Accrue(accrue) => Some(&accrue.date),
+            Directive::OpenBalance(open_balance) => Some(&open_balance.date),
+            Directive::Event(event) => Some(&event.date),
+            Directive::Custom(custom) => Some(&custom.date),
             Directive::Option(_) => None,
             Directive::Plugin(_) => None,
             Directive::Include(_) => None,
             Directive::Comment(_) => None,
+            Directive::Malformed(_) => None,
+            Directive::Rename(_) => None,
 
-            Directive::Budget(budget) => Some(budget.date.naive_datetime()),
-            Directive::BudgetAdd(budget_add) => Some(budget_add.date.naive_datetime()),
-            Directive::BudgetTransfer(budget_transfer) => Some(budget_transfer.date.naive_datetime()),
-            Directive::BudgetClose(budget_close) => Some(budget_close.date.naive_datetime()),
+            Directive::Budget(budget) => Some(&budget.date),
+            Directive::BudgetAdd(budget_add) => Some(&budget_add.date),
+            Directive::BudgetTransfer(budget_transfer) => Some(&budget_transfer.date),
+            Directive::BudgetClose(budget_close) => Some(&budget_close.date),
         }
     }
     pub fn directive_type(&self) -> DirectiveType {
@@ -90,17 +123,26 @@ impl Directive {
             Directive::Close(_) => DirectiveType::Close,
             Directive::Commodity(_) => DirectiveType::Commodity,
             Directive::Transaction(_) => DirectiveType::Transaction,
+            Directive::Template(_) => DirectiveType::Template,
             Directive::Note(_) => DirectiveType::Note,
             Directive::Document(_) => DirectiveType::Document,
             Directive::Price(_) => DirectiveType::Price,
+            Directive::Split(_) => DirectiveType::Split,
+            Directive::Depreciation(_) => DirectiveType::Depreciation,
+            Directive::Loan(_) => DirectiveType::Loan,
+            Directive::Accrue(_) => DirectiveType::Accrue,
+            Directive::OpenBalance(_) => DirectiveType::OpenBalance,
             Directive::Event(_) => DirectiveType::Event,
             Directive::Custom(_) => DirectiveType::Custom,
             Directive::Option(_) => DirectiveType::Option,
             Directive::Plugin(_) => DirectiveType::Plugin,
             Directive::Include(_) => DirectiveType::Include,
             Directive::Comment(_) => DirectiveType::Comment,
+            Directive::Malformed(_) => DirectiveType::Malformed,
+            Directive::Rename(_) => DirectiveType::Rename,
             Directive::BalancePad(_) => DirectiveType::BalancePad,
             Directive::BalanceCheck(_) => DirectiveType::BalanceCheck,
+            Directive::Pad(_) => DirectiveType::Pad,
             Directive::Budget(_) => DirectiveType::Budget,
             Directive::BudgetAdd(_) => DirectiveType::BudgetAdd,
             Directive::BudgetTransfer(_) => DirectiveType::BudgetTransfer,
@@ -108,17 +150,82 @@ impl Directive {
         }
     }
 
+    pub fn get_meta(&self) -> Option<&Meta> {
+        match self {
+            Directive::Open(directive) => Some(&directive.meta),
+            Directive::Close(directive) => Some(&directive.meta),
+            Directive::Commodity(directive) => Some(&directive.meta),
+            Directive::Transaction(directive) => Some(&directive.meta),
+            Directive::Template(directive) => Some(&directive.meta),
+            Directive::BalancePad(directive) => Some(&directive.meta),
+            Directive::BalanceCheck(directive) => Some(&directive.meta),
+            Directive::Pad(directive) => Some(&directive.meta),
+            Directive::Note(directive) => Some(&directive.meta),
+            Directive::Document(directive) => Some(&directive.meta),
+            Directive::Price(directive) => Some(&directive.meta),
+            Directive::Split(directive) => Some(&directive.meta),
+            Directive::Depreciation(directive) => Some(&directive.meta),
+            Directive::Loan(directive) => Some(&directive.meta),
+            Directive::Accrue(directive) => Some(&directive.meta),
+            Directive::OpenBalance(directive) => Some(&directive.meta),
+            Directive::Event(directive) => Some(&directive.meta),
+            Directive::Custom(directive) => Some(&directive.meta),
+            Directive::Budget(directive) => Some(&directive.meta),
+            Directive::BudgetAdd(directive) => Some(&directive.meta),
+            Directive::BudgetTransfer(directive) => Some(&directive.meta),
+            Directive::BudgetClose(directive) => Some(&directive.meta),
+            Directive::Plugin(directive) => Some(&directive.meta),
+            Directive::Option(_) | Directive::Include(_) | Directive::Comment(_) | Directive::Malformed(_) | Directive::Rename(_) => None,
+        }
+    }
+
+    pub fn get_meta_mut(&mut self) -> Option<&mut Meta> {
+        match self {
+            Directive::Open(directive) => Some(&mut directive.meta),
+            Directive::Close(directive) => Some(&mut directive.meta),
+            Directive::Commodity(directive) => Some(&mut directive.meta),
+            Directive::Transaction(directive) => Some(&mut directive.meta),
+            Directive::Template(directive) => Some(&mut directive.meta),
+            Directive::BalancePad(directive) => Some(&mut directive.meta),
+            Directive::BalanceCheck(directive) => Some(&mut directive.meta),
+            Directive::Pad(directive) => Some(&mut directive.meta),
+            Directive::Note(directive) => Some(&mut directive.meta),
+            Directive::Document(directive) => Some(&mut directive.meta),
+            Directive::Price(directive) => Some(&mut directive.meta),
+            Directive::Split(directive) => Some(&mut directive.meta),
+            Directive::Depreciation(directive) => Some(&mut directive.meta),
+            Directive::Loan(directive) => Some(&mut directive.meta),
+            Directive::Accrue(directive) => Some(&mut directive.meta),
+            Directive::OpenBalance(directive) => Some(&mut directive.meta),
+            Directive::Event(directive) => Some(&mut directive.meta),
+            Directive::Custom(directive) => Some(&mut directive.meta),
+            Directive::Budget(directive) => Some(&mut directive.meta),
+            Directive::BudgetAdd(directive) => Some(&mut directive.meta),
+            Directive::BudgetTransfer(directive) => Some(&mut directive.meta),
+            Directive::BudgetClose(directive) => Some(&mut directive.meta),
+            Directive::Plugin(directive) => Some(&mut directive.meta),
+            Directive::Option(_) | Directive::Include(_) | Directive::Comment(_) | Directive::Malformed(_) | Directive::Rename(_) => None,
+        }
+    }
+
     pub fn set_meta(mut self, meta: Meta) -> Self {
         match &mut self {
             Directive::Open(ref mut directive) => directive.meta = meta,
             Directive::Close(ref mut directive) => directive.meta = meta,
             Directive::Commodity(ref mut directive) => directive.meta = meta,
             Directive::Transaction(ref mut directive) => directive.meta = meta,
+            Directive::Template(ref mut directive) => directive.meta = meta,
             Directive::BalancePad(ref mut directive) => directive.meta = meta,
             Directive::BalanceCheck(ref mut directive) => directive.meta = meta,
+            Directive::Pad(ref mut directive) => directive.meta = meta,
             Directive::Note(ref mut directive) => directive.meta = meta,
             Directive::Document(ref mut directive) => directive.meta = meta,
             Directive::Price(ref mut directive) => directive.meta = meta,
+            Directive::Split(ref mut directive) => directive.meta = meta,
+            Directive::Depreciation(ref mut directive) => directive.meta = meta,
+            Directive::Loan(ref mut directive) => directive.meta = meta,
+            Directive::Accrue(ref mut directive) => directive.meta = meta,
+            Directive::OpenBalance(ref mut directive) => directive.meta = meta,
             Directive::Event(ref mut directive) => directive.meta = meta,
             Directive::Custom(ref mut directive) => directive.meta = meta,
             Directive::Budget(ref mut directive) => directive.meta = meta,
@@ -129,9 +236,52 @@ impl Directive {
             Directive::Plugin(ref mut directive) => directive.meta = meta,
             Directive::Include(_) => {}
             Directive::Comment(_) => {}
+            Directive::Malformed(_) => {}
+            Directive::Rename(_) => {}
         }
         self
     }
+
+    /// all accounts referenced by the directive
+    pub fn accounts_mut(&mut self) -> Vec<&mut Account> {
+        match self {
+            Directive::Open(open) => vec![&mut open.account],
+            Directive::Close(close) => vec![&mut close.account],
+            Directive::Transaction(trx) => trx.postings.iter_mut().map(|posting| &mut posting.account).collect(),
+            Directive::Template(template) => template.postings.iter_mut().map(|posting| &mut posting.account).collect(),
+            Directive::BalancePad(pad) => vec![&mut pad.account, &mut pad.pad],
+            Directive::BalanceCheck(check) => vec![&mut check.account],
+            Directive::Pad(pad) => vec![&mut pad.account, &mut pad.pad],
+            Directive::Note(note) => vec![&mut note.account],
+            Directive::Document(document) => vec![&mut document.account],
+            Directive::Custom(custom) => custom
+                .values
+                .iter_mut()
+                .filter_map(|value| match value {
+                    StringOrAccount::Account(account) => Some(account),
+                    StringOrAccount::String(_) => None,
+                })
+                .collect(),
+            Directive::Rename(rename) => vec![&mut rename.from, &mut rename.to],
+            Directive::Depreciation(depreciation) => vec![&mut depreciation.account, &mut depreciation.expense_account],
+            Directive::Loan(loan) => vec![&mut loan.account, &mut loan.payment_account, &mut loan.interest_account],
+            Directive::Accrue(accrue) => vec![&mut accrue.deferral_account, &mut accrue.account],
+            Directive::OpenBalance(open_balance) => std::iter::once(&mut open_balance.account).chain(open_balance.equity_account.as_mut()).collect(),
+            Directive::Commodity(_)
+            | Directive::Price(_)
+            | Directive::Split(_)
+            | Directive::Event(_)
+            | Directive::Option(_)
+            | Directive::Plugin(_)
+            | Directive::Include(_)
+            | Directive::Comment(_)
+            | Directive::Malformed(_)
+            | Directive::Budget(_)
+            | Directive::BudgetAdd(_)
+            | Directive::BudgetTransfer(_)
+            | Directive::BudgetClose(_) => vec![],
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
@@ -182,6 +332,10 @@ pub enum Flag {
 
     BalanceCheck,
 
+    /// the flag of voided transaction in store, which is never parsed from flag since the transaction is voided by meta `void`,
+    /// see [crate::Transaction::is_voided]
+    Void,
+
     Custom(String),
 }
 
@@ -205,6 +359,7 @@ impl Display for Flag {
             Flag::Warning => "!".to_owned(),
             Flag::BalancePad => "P".to_owned(),
             Flag::BalanceCheck => "C".to_owned(),
+            Flag::Void => "void".to_owned(),
             Flag::Custom(s) => s.to_owned(),
         };
         write!(f, "{}", str)
@@ -228,6 +383,37 @@ impl Rounding {
     }
 }
 
+#[derive(EnumString, Debug, PartialEq, Eq, Deserialize, Serialize, Clone, Copy, Display)]
+pub enum DepreciationMethod {
+    /// the same amount is written off every month
+    #[strum(serialize = "straight-line")]
+    StraightLine,
+    /// the double of straight-line rate is applied to the remaining book value every month,
+    /// and the remaining book value is written off in the last month
+    #[strum(serialize = "declining-balance")]
+    DecliningBalance,
+}
+
+#[derive(EnumString, Debug, PartialEq, Eq, Deserialize, Serialize, Clone, Copy, Display)]
+pub enum AccrualInterval {
+    #[strum(serialize = "monthly")]
+    Monthly,
+    #[strum(serialize = "quarterly")]
+    Quarterly,
+    #[strum(serialize = "yearly")]
+    Yearly,
+}
+
+impl AccrualInterval {
+    pub fn months(&self) -> u32 {
+        match self {
+            AccrualInterval::Monthly => 1,
+            AccrualInterval::Quarterly => 3,
+            AccrualInterval::Yearly => 12,
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::str::FromStr;
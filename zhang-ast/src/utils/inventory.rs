@@ -14,6 +14,7 @@ pub enum LotInfo {
     Lot(Currency, BigDecimal),
     Fifo,
     Filo,
+    Average,
 }
 
 #[derive(Clone, Debug)]
@@ -43,7 +44,8 @@ impl CommodityInventory {
 
                 self.total.add_assign(number);
             }
-            LotInfo::Fifo => {
+            // lots here are only used to calculate the total, so average booking goes the same way as fifo
+            LotInfo::Fifo | LotInfo::Average => {
                 let mut number = number.clone();
                 self.total.add_assign(&number);
                 for (_, amount) in self.lots.iter_mut() {
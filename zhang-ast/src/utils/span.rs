@@ -5,11 +5,28 @@ use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct SpanInfo {
     pub start: usize,
     pub end: usize,
     pub content: String,
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<String>))]
     pub filename: Option<PathBuf>,
+    #[serde(default)]
+    pub trivia: Trivia,
+}
+
+/// the comments and blank lines around the directive, which make no difference to the meaning of directive
+/// but are kept to re-serialize the directive as it is written
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Trivia {
+    /// the count of blank lines before the directive
+    pub blank_lines: usize,
+    /// the comment at the end of the first line of directive, including the prefix, e.g. `; weekly groceries`
+    pub comment: Option<String>,
+    /// the comments at the end of meta lines in order, given by the meta key and the comment including the prefix
+    pub meta_comments: Vec<(String, String)>,
 }
 
 impl SpanInfo {
@@ -19,6 +36,7 @@ impl SpanInfo {
             end,
             content: "".to_string(),
             filename: None,
+            trivia: Trivia::default(),
         }
     }
 }
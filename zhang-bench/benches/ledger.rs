@@ -0,0 +1,74 @@
+use chrono::{Duration, NaiveTime};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use zhang_bench::LedgerGenerator;
+use zhang_core::data_type::text::ZhangDataType;
+use zhang_core::data_type::DataType;
+use zhang_core::domains::schemas::BalanceInterval;
+
+/// the number of transactions of the large ledger, which can be overridden by `ZHANG_BENCH_TRANSACTIONS`
+fn large_transactions() -> usize {
+    std::env::var("ZHANG_BENCH_TRANSACTIONS").ok().and_then(|it| it.parse().ok()).unwrap_or(100_000)
+}
+
+fn generators() -> Vec<LedgerGenerator> {
+    vec![LedgerGenerator::new(20, 1_000, 2), LedgerGenerator::new(200, large_transactions(), 5)]
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    group.sample_size(10);
+    for generator in generators() {
+        let content = generator.generate();
+        group.throughput(Throughput::Bytes(content.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(generator.transactions), &content, |b, content| {
+            b.iter(|| ZhangDataType {}.transform(black_box(content.clone()), None).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn process(c: &mut Criterion) {
+    let mut group = c.benchmark_group("process");
+    group.sample_size(10);
+    for generator in generators() {
+        let (_folder, entry) = generator.write().unwrap();
+        group.throughput(Throughput::Elements(generator.transactions as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(generator.transactions), &entry, |b, entry| {
+            b.iter(|| LedgerGenerator::load(entry.clone()).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn queries(c: &mut Criterion) {
+    let mut group = c.benchmark_group("queries");
+    for generator in generators() {
+        let (_folder, entry) = generator.write().unwrap();
+        let ledger = LedgerGenerator::load(entry).unwrap();
+        let account = generator.assets_account(0);
+        let middle = LedgerGenerator::start_date() + Duration::days(generator.days as i64 / 2);
+        let from = middle.and_time(NaiveTime::default()).and_utc();
+        let to = (middle + Duration::days(30)).and_time(NaiveTime::default()).and_utc();
+        let size = generator.transactions;
+
+        group.bench_function(BenchmarkId::new("account_journal", size), |b| {
+            b.iter(|| ledger.operations().account_journal(black_box(&account), from..to).unwrap())
+        });
+        group.bench_function(BenchmarkId::new("account_target_date_balance", size), |b| {
+            b.iter(|| ledger.operations().account_target_date_balance(black_box(&account), to, None).unwrap())
+        });
+        group.bench_function(BenchmarkId::new("dated_journals", size), |b| {
+            b.iter(|| ledger.operations().dated_journals(black_box(from), to).unwrap())
+        });
+        group.bench_function(BenchmarkId::new("trial_balance", size), |b| {
+            b.iter(|| ledger.operations().trial_balance(black_box(to)).unwrap())
+        });
+        group.bench_function(BenchmarkId::new("account_balances_over_time", size), |b| {
+            b.iter(|| ledger.operations().account_balances_over_time(black_box(BalanceInterval::Month)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse, process, queries);
+criterion_main!(benches);
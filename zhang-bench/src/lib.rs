@@ -0,0 +1,141 @@
+//! synthetic ledgers for benchmarking, whose sizes are configurable by the number of accounts, transactions and commodities.
+//!
+//! the generated ledgers are deterministic, so that the results of benchmarks are comparable between runs.
+//! the benchmarks are run by `cargo bench -p zhang-bench`, and criterion reports the changes against the previous run.
+
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use chrono::{Duration, NaiveDate};
+use tempfile::TempDir;
+use zhang_core::data_source::LocalFileSystemDataSource;
+use zhang_core::data_type::text::ZhangDataType;
+use zhang_core::ledger::Ledger;
+use zhang_core::ZhangResult;
+
+pub const ENTRY_FILE: &str = "main.zhang";
+
+#[derive(Debug, Clone, Copy)]
+pub struct LedgerGenerator {
+    /// the number of assets accounts, which is also the number of expenses accounts
+    pub accounts: usize,
+    pub transactions: usize,
+    /// the first commodity is the operating currency, the others are priced against it monthly
+    pub commodities: usize,
+    /// the number of days the transactions are spread across
+    pub days: usize,
+}
+
+impl Default for LedgerGenerator {
+    fn default() -> Self {
+        LedgerGenerator {
+            accounts: 20,
+            transactions: 1000,
+            commodities: 2,
+            days: 3650,
+        }
+    }
+}
+
+impl LedgerGenerator {
+    pub fn new(accounts: usize, transactions: usize, commodities: usize) -> Self {
+        LedgerGenerator {
+            accounts: accounts.max(1),
+            transactions,
+            commodities: commodities.max(1),
+            ..Default::default()
+        }
+    }
+
+    pub fn start_date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2000, 1, 1).expect("invalid date")
+    }
+
+    pub fn end_date(&self) -> NaiveDate {
+        LedgerGenerator::start_date() + Duration::days(self.days as i64)
+    }
+
+    pub fn commodity(&self, idx: usize) -> String {
+        format!("C{}", idx % self.commodities)
+    }
+
+    pub fn assets_account(&self, idx: usize) -> String {
+        format!("Assets:Account{}", idx % self.accounts)
+    }
+
+    pub fn expenses_account(&self, idx: usize) -> String {
+        format!("Expenses:Category{}", idx % self.accounts)
+    }
+
+    /// the content of ledger in zhang format, the transactions are in date order
+    pub fn generate(&self) -> String {
+        let start = LedgerGenerator::start_date();
+        let mut content = String::new();
+        writeln!(content, "option \"operating_currency\" \"{}\"", self.commodity(0)).unwrap();
+        for idx in 0..self.commodities {
+            writeln!(content, "1970-01-01 commodity {}", self.commodity(idx)).unwrap();
+        }
+        for idx in 0..self.accounts {
+            writeln!(content, "1970-01-01 open {}", self.assets_account(idx)).unwrap();
+            writeln!(content, "1970-01-01 open {}", self.expenses_account(idx)).unwrap();
+        }
+        content.push('\n');
+
+        for month in 0..=(self.days / 30) {
+            let date = start + Duration::days(month as i64 * 30);
+            for idx in 1..self.commodities {
+                writeln!(
+                    content,
+                    "{} price {} {} {}",
+                    date,
+                    self.commodity(idx),
+                    1 + (month + idx) % 10,
+                    self.commodity(0)
+                )
+                .unwrap();
+            }
+        }
+        content.push('\n');
+
+        for idx in 0..self.transactions {
+            let date = start + Duration::days((idx * self.days / self.transactions.max(1)) as i64);
+            // the strides spread the transactions across accounts and commodities without repeating patterns of them
+            let commodity = self.commodity(idx * 7);
+            let amount = format!("{}.{:02}", 1 + idx * 13 % 500, idx % 100);
+            writeln!(content, "{} \"Payee{}\" \"Transaction {}\" #tag{}", date, idx % 50, idx, idx % 10).unwrap();
+            writeln!(content, "  {} -{} {}", self.assets_account(idx * 3), amount, commodity).unwrap();
+            writeln!(content, "  {} {} {}", self.expenses_account(idx * 5), amount, commodity).unwrap();
+            content.push('\n');
+        }
+        content
+    }
+
+    /// write the ledger into a temporary folder, which is removed once the returned folder is dropped
+    pub fn write(&self) -> ZhangResult<(TempDir, PathBuf)> {
+        let folder = tempfile::tempdir()?;
+        std::fs::write(folder.path().join(ENTRY_FILE), self.generate())?;
+        let entry = folder.path().to_path_buf();
+        Ok((folder, entry))
+    }
+
+    /// load the ledger written in the folder
+    pub fn load(entry: PathBuf) -> ZhangResult<Ledger> {
+        Ledger::load_with_data_source(entry, ENTRY_FILE.to_owned(), Arc::new(LocalFileSystemDataSource::new(ZhangDataType {})))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::LedgerGenerator;
+
+    #[test]
+    fn should_generate_ledger_without_errors() {
+        let generator = LedgerGenerator::new(5, 200, 3);
+        let (_folder, entry) = generator.write().unwrap();
+        let ledger = LedgerGenerator::load(entry).unwrap();
+        let mut operations = ledger.operations();
+        assert_eq!(0, operations.errors().unwrap().len());
+        assert_eq!(200, ledger.store.read().unwrap().transactions.len());
+    }
+}
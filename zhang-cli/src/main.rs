@@ -1,17 +1,44 @@
 use std::fmt::Debug;
+use std::io::IsTerminal;
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
 
+use beancount::Beancount;
+use bigdecimal::BigDecimal;
+use chrono::{NaiveDate, NaiveTime};
 use clap::{Args, Parser};
 use env_logger::Env;
-use log::{error, info, LevelFilter};
+use journal::Journal;
+use log::{error, info, warn, LevelFilter};
 use self_update::Status;
 use tokio::task::spawn_blocking;
-use zhang_server::ServeConfig;
+use zhang_core::ast::error::{ErrorKind, ErrorSeverity};
+use zhang_core::ast::{Account, Date, Directive};
+use zhang_core::closing::{self, PeriodClosing};
+use zhang_core::constants::DEFAULT_RETAINED_EARNINGS_ACCOUNT;
+use zhang_core::data_source::{DataSource, LocalFileSystemDataSource};
+use zhang_core::data_type::text::formatter::format;
+use zhang_core::data_type::text::sorter::{check_order, sort_directives};
+use zhang_core::data_type::text::ZhangDataType;
+use zhang_core::encryption::Encryption;
+use zhang_core::error::IoErrorIntoZhangError;
+use zhang_core::export::sqlite::export_sqlite;
+use zhang_core::export::{export_anonymized, export_report, export_text, ExportFormat, ExportOptions, ExportReport};
+use zhang_core::git::GitDataSource;
+use zhang_core::importer::Importer;
+use zhang_core::ledger::Ledger;
+use zhang_core::template;
+use zhang_core::{ZhangError, ZhangResult};
+use zhang_server::auth::Authenticator;
+use zhang_server::{LedgerConfig, ServeConfig};
 
 use crate::opendal::OpendalDataSource;
+use crate::wizard::{review_import, Prompt};
 
 pub mod opendal;
+pub mod repl;
+pub mod wizard;
 
 #[derive(Parser, Debug)]
 #[clap(about, version, author)]
@@ -22,6 +49,33 @@ pub enum Opts {
     /// export to target file
     Export(ExportOpts),
 
+    /// import bank statement as transactions
+    Import(ImportOpts),
+
+    /// check the ledger and report its errors
+    Check(CheckOpts),
+
+    /// add a transaction interactively, with completion of payees and accounts
+    Add(AddOpts),
+
+    /// print the report of ledger as table, csv or json
+    Report(ReportOpts),
+
+    /// execute the query against ledger, or start the interactive query shell if the query is absent
+    Query(QueryOpts),
+
+    /// close income and expenses into retained earnings at the end of period, optionally emitting the opening balances of next period
+    ClosePeriod(ClosePeriodOpts),
+
+    /// append the transaction instantiated from template with the values of its placeholders
+    Template(TemplateOpts),
+
+    /// format the zhang files of ledger
+    Fmt(FmtOpts),
+
+    /// reorder the directives of zhang files of ledger by date
+    Sort(FmtOpts),
+
     /// start an internal server with frontend ui
     Serve(ServerOpts),
 
@@ -54,20 +108,346 @@ pub struct ExportOpts {
     #[clap(short, long, default_value = "main.zhang")]
     pub endpoint: String,
 
-    /// the endpoint of main zhang file.
-    #[clap(short, long, default_value = "Text")]
+    /// the format of exported file
+    #[clap(short = 'f', long = "format", alias = "exporter", default_value = "text")]
     pub exporter: Exporter,
+
+    /// the path of exported file, default to `zhang.zhang` for text, `zhang.bean` for beancount, `zhang.journal` for journal, `zhang.sqlite` for sqlite,
+    /// `zhang.anonymized.zhang` for anonymized or `{report}.{format}` for csv and json
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// the report exported as csv or json
+    #[clap(long, default_value = "journal")]
+    pub report: Report,
+
+    /// the columns of report separated by comma, all columns are exported if absent
+    #[clap(long, value_delimiter = ',')]
+    pub columns: Option<Vec<String>>,
+
+    /// the start date of report(inclusive)
+    #[clap(long)]
+    pub from: Option<NaiveDate>,
+
+    /// the end date of report(inclusive)
+    #[clap(long)]
+    pub to: Option<NaiveDate>,
+
+    /// display the numbers of report by the conventions of their commodities, like `¥1,234.00`
+    #[clap(long)]
+    pub formatted: bool,
+
+    /// the seed deciding the scale factor of anonymized amounts, a random one is used if absent
+    #[clap(long)]
+    pub seed: Option<u64>,
+
+    /// the profile under which `include-if` directives are loaded, overriding `option "profile"` of ledger
+    #[clap(long)]
+    pub profile: Option<String>,
 }
 
 #[derive(Debug, Clone, clap::ValueEnum)]
 pub enum Exporter {
+    /// the ledger as one zhang file in which the included files are merged
     Text,
+    /// the ledger as beancount file which can be checked by `bean-check`
     Beancount,
+    /// the ledger as hledger/ledger-cli journal file
+    Journal,
+    /// the processed ledger as SQLite database
+    Sqlite,
+    /// the ledger as one zhang file whose payees, narrations and amounts are scrubbed, for sharing the reproduction of bugs
+    Anonymized,
+    Csv,
+    Json,
+}
+
+#[derive(Args, Debug)]
+pub struct ImportOpts {
+    /// base path of zhang project
+    pub path: PathBuf,
+
+    /// the statement file to import
+    pub file: PathBuf,
+
+    /// the endpoint of main zhang file.
+    #[clap(short, long, default_value = "main.zhang")]
+    pub endpoint: String,
+
+    /// the format of statement
+    #[clap(short, long, default_value = "csv")]
+    pub format: ImportFormat,
+
+    /// the importer config file, default to `importer.toml` under base path
+    #[clap(short, long)]
+    pub config: Option<PathBuf>,
+
+    /// the name of mapping in config, can be omitted if only one mapping is configured
+    #[clap(short, long)]
+    pub mapping: Option<String>,
+
+    /// the file which transactions are appended to, relative to base path. default to the main file
+    #[clap(short, long)]
+    pub target: Option<PathBuf>,
+
+    /// preview the generated transactions without appending them
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// choose the counter account of each transaction interactively, and confirm before appending
+    #[clap(short, long)]
+    pub interactive: bool,
+
+    /// import the probable duplicates of transactions in ledger as well
+    #[clap(long)]
+    pub allow_duplicates: bool,
+
+    /// the profile under which `include-if` directives are loaded, overriding `option "profile"` of ledger
+    #[clap(long)]
+    pub profile: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct CheckOpts {
+    /// base path of zhang project
+    pub path: PathBuf,
+
+    /// the endpoint of main zhang file.
+    #[clap(short, long, default_value = "main.zhang")]
+    pub endpoint: String,
+
+    /// the format of reported errors
+    #[clap(long, value_enum, default_value = "text")]
+    pub format: DiagnosticFormat,
+
+    /// the lowest severity of errors failing the check, which is one of `error`, `warning` and `info`
+    #[clap(long, default_value = "error")]
+    pub severity: ErrorSeverity,
+
+    /// the kind of errors to be neither reported nor counted, e.g. `--ignore DuplicatedTransaction`
+    #[clap(long = "ignore", value_name = "KIND")]
+    pub ignores: Vec<ErrorKind>,
+
+    /// the profile under which `include-if` directives are loaded, overriding `option "profile"` of ledger
+    #[clap(long)]
+    pub profile: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ReportOpts {
+    /// base path of zhang project
+    pub path: PathBuf,
+
+    /// the report to be printed
+    pub report: Report,
+
+    /// the endpoint of main zhang file.
+    #[clap(short, long, default_value = "main.zhang")]
+    pub endpoint: String,
+
+    /// the format of printed report
+    #[clap(short = 'f', long, value_enum, default_value = "table")]
+    pub format: ReportFormat,
+
+    /// the columns of report separated by comma, all columns are printed if absent
+    #[clap(long, value_delimiter = ',')]
+    pub columns: Option<Vec<String>>,
+
+    /// the start date of report(inclusive)
+    #[clap(long)]
+    pub from: Option<NaiveDate>,
+
+    /// the end date of report(inclusive), which is the date of balance sheet and holdings
+    #[clap(long)]
+    pub to: Option<NaiveDate>,
+
+    /// the max depth of account tree of balance sheet and income statement, the account type is depth 1
+    #[clap(long)]
+    pub depth: Option<usize>,
+
+    /// report the accounts of the entity only in balance sheet and income statement
+    #[clap(long)]
+    pub entity: Option<String>,
+
+    /// report all entities as a whole in balance sheet and income statement, excluding the accounts of elimination rules
+    #[clap(long, conflicts_with = "entity")]
+    pub consolidated: bool,
+
+    /// display the numbers of report by the conventions of their commodities, like `¥1,234.00`
+    #[clap(long)]
+    pub formatted: bool,
+
+    /// the profile under which `include-if` directives are loaded, overriding `option "profile"` of ledger
+    #[clap(long)]
+    pub profile: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ClosePeriodOpts {
+    /// base path of zhang project
+    pub path: PathBuf,
+
+    /// the last date of period(inclusive), e.g. `2024-12-31`
+    pub date: NaiveDate,
+
+    /// the endpoint of main zhang file.
+    #[clap(short, long, default_value = "main.zhang")]
+    pub endpoint: String,
+
+    /// the equity account which income and expenses are closed into
+    #[clap(long, default_value = DEFAULT_RETAINED_EARNINGS_ACCOUNT)]
+    pub retained_earnings: String,
+
+    /// the file which the opening balances of next period are written into, relative to the base path, which must not exist yet
+    #[clap(long)]
+    pub opening: Option<PathBuf>,
+
+    /// print the closing transaction and opening balances without writing them
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// the profile under which `include-if` directives are loaded, overriding `option "profile"` of ledger
+    #[clap(long)]
+    pub profile: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct TemplateOpts {
+    /// base path of zhang project
+    pub path: PathBuf,
+
+    /// the name of template
+    pub name: String,
+
+    /// the values of placeholders in the format of `NAME=NUMBER`, e.g. `total=3000`
+    #[clap(value_parser = parse_placeholder_value)]
+    pub values: Vec<(String, BigDecimal)>,
+
+    /// the endpoint of main zhang file.
+    #[clap(short, long, default_value = "main.zhang")]
+    pub endpoint: String,
+
+    /// the date of transaction, today if absent
+    #[clap(long)]
+    pub date: Option<NaiveDate>,
+
+    /// print the instantiated transaction without writing it
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// the profile under which `include-if` directives are loaded, overriding `option "profile"` of ledger
+    #[clap(long)]
+    pub profile: Option<String>,
+}
+
+fn parse_placeholder_value(value: &str) -> Result<(String, BigDecimal), String> {
+    let invalid = || format!("value '{value}' is invalid, it should be in the format of 'NAME=NUMBER'");
+    let (name, number) = value.split_once('=').ok_or_else(invalid)?;
+    let number = BigDecimal::from_str(number.trim()).map_err(|_| invalid())?;
+    Ok((name.trim().to_owned(), number))
+}
+
+#[derive(Args, Debug)]
+pub struct QueryOpts {
+    /// base path of zhang project
+    pub path: PathBuf,
+
+    /// the query, e.g. `SELECT account, sum(amount) GROUP BY account`
+    pub query: Option<String>,
+
+    /// the endpoint of main zhang file.
+    #[clap(short, long, default_value = "main.zhang")]
+    pub endpoint: String,
+
+    /// the format of query result, the interactive shell always prints tables
+    #[clap(short = 'f', long, value_enum, default_value = "table")]
+    pub format: ReportFormat,
+
+    /// the profile under which `include-if` directives are loaded, overriding `option "profile"` of ledger
+    #[clap(long)]
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum ReportFormat {
+    /// the columns aligned for reading in terminal
+    Table,
+    Csv,
+    Json,
+}
+
+#[derive(Args, Debug)]
+pub struct AddOpts {
+    /// base path of zhang project
+    pub path: PathBuf,
+
+    /// the endpoint of main zhang file.
+    #[clap(short, long, default_value = "main.zhang")]
+    pub endpoint: String,
+
+    /// the profile under which `include-if` directives are loaded, overriding `option "profile"` of ledger
+    #[clap(long)]
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum DiagnosticFormat {
+    Text,
+    /// the JSON array of errors with file path, byte offsets and line/column positions
+    Json,
+}
+
+#[derive(Args, Debug)]
+pub struct FmtOpts {
+    /// base path of zhang project
+    pub path: PathBuf,
+
+    /// the endpoint of main zhang file.
+    #[clap(short, long, default_value = "main.zhang")]
+    pub endpoint: String,
+
+    /// report the files which are not formatted or sorted instead of changing them
+    #[clap(long)]
+    pub check: bool,
+}
+
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum ImportFormat {
+    Csv,
+    /// OFX and QFX
+    Ofx,
+    /// camt.053 XML
+    Camt,
+    Mt940,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Report {
+    Journal,
+    Balances,
+    Budgets,
+    BalanceSheet,
+    IncomeStatement,
+    Holdings,
+}
+
+impl From<Report> for ExportReport {
+    fn from(value: Report) -> Self {
+        match value {
+            Report::Journal => ExportReport::Journal,
+            Report::Balances => ExportReport::Balances,
+            Report::Budgets => ExportReport::Budgets,
+            Report::BalanceSheet => ExportReport::BalanceSheet,
+            Report::IncomeStatement => ExportReport::IncomeStatement,
+            Report::Holdings => ExportReport::Holdings,
+        }
+    }
 }
 #[derive(Debug, Clone, PartialEq, clap::ValueEnum)]
 pub enum FileSystem {
     Fs,
-    // S3,
+    /// the S3-compatible object storage, e.g. AWS S3, MinIO and Cloudflare R2
+    S3,
     WebDav,
     Github,
 }
@@ -76,6 +456,7 @@ impl FileSystem {
     fn from_env() -> Option<FileSystem> {
         match std::env::var("ZHANG_DATA_SOURCE").as_deref() {
             Ok("fs") => Some(FileSystem::Fs),
+            Ok("s3") => Some(FileSystem::S3),
             Ok("web-dav") => Some(FileSystem::WebDav),
             Ok("github") => Some(FileSystem::Github),
             _ => None,
@@ -83,7 +464,7 @@ impl FileSystem {
     }
 }
 
-#[derive(Args, Debug)]
+#[derive(Args, Debug, Clone)]
 pub struct ServerOpts {
     /// base path of zhang project
     pub path: PathBuf,
@@ -100,10 +481,16 @@ pub struct ServerOpts {
     #[clap(short, long, default_value_t = 8000)]
     pub port: u16,
 
-    /// web basic auth credential to enable basic auth. or enable it via env ZHANG_AUTH
+    /// web basic auth credential in the format of `NAME:PASSWORD` to enable basic auth, whose user is admin.
+    /// or enable it via env ZHANG_AUTH
     #[clap(long)]
     pub auth: Option<String>,
 
+    /// the static token of user in the format of `NAME:ROLE:TOKEN`, the role is one of `read-only`, `write` and `admin`.
+    /// or give them via env ZHANG_AUTH_TOKENS separated by comma
+    #[clap(long = "auth-token")]
+    pub auth_tokens: Vec<String>,
+
     /// data source type, default is fs, or enable it via env ZHANG_AUTH
     #[clap(long)]
     pub source: Option<FileSystem>,
@@ -111,6 +498,30 @@ pub struct ServerOpts {
     /// whether the server report version info for anonymous statistics
     #[clap(long)]
     pub no_report: bool,
+
+    /// disable the cache of parsed directives and the snapshot of processed ledger, which are stored in `.zhang-cache` of the ledger folder
+    #[clap(long)]
+    pub no_cache: bool,
+
+    /// the profile under which `include-if` directives are loaded, overriding `option "profile"` of ledger
+    #[clap(long)]
+    pub profile: Option<String>,
+
+    /// commit every write-back of ledger into the git repository of ledger folder, only for local file system
+    #[clap(long)]
+    pub git: bool,
+
+    /// the ledger served along with the main one in the format of `NAME=PATH`, which is loaded from local file system.
+    /// requests select it by header `X-Zhang-Ledger` or query parameter `ledger`
+    #[clap(long = "ledger", value_parser = parse_named_ledger)]
+    pub ledgers: Vec<(String, PathBuf)>,
+}
+
+fn parse_named_ledger(value: &str) -> Result<(String, PathBuf), String> {
+    match value.split_once('=') {
+        Some((name, path)) if !name.trim().is_empty() && !path.trim().is_empty() => Ok((name.trim().to_owned(), PathBuf::from(path.trim()))),
+        _ => Err(format!("ledger '{value}' is invalid, it should be in the format of 'NAME=PATH'")),
+    }
 }
 
 impl Opts {
@@ -121,20 +532,129 @@ impl Opts {
                 // todo: fix parse
                 // Ledger::load_with_database(parse_opts.path, parse_opts.endpoint, format.transformer()).expect("Cannot load ledger");
             }
-            Opts::Export(_) => todo!(),
+            Opts::Export(opts) => match export(opts) {
+                Ok(output) => info!("ledger is exported to {}", output.display()),
+                Err(e) => error!("fail to export ledger: {}", e),
+            },
+            Opts::Import(opts) => match import(opts).await {
+                Ok(count) => info!("{} directives are imported", count),
+                Err(e) => {
+                    error!("fail to import statement: {}", e);
+                    std::process::exit(1);
+                }
+            },
+            Opts::Report(opts) => match report(opts) {
+                Ok(content) => println!("{}", content.trim_end()),
+                Err(e) => {
+                    error!("fail to build report: {}", e);
+                    std::process::exit(1);
+                }
+            },
+            Opts::ClosePeriod(opts) => {
+                if let Err(e) = close_period(opts) {
+                    error!("fail to close period: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            Opts::Template(opts) => {
+                if let Err(e) = instantiate_template(opts) {
+                    error!("fail to instantiate template: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            Opts::Query(opts) => {
+                if let Err(e) = query(opts) {
+                    error!("fail to query ledger: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            Opts::Add(opts) => match add(opts).await {
+                Ok(Some(file)) => info!("transaction is appended to {}", file),
+                Ok(None) => info!("transaction is discarded"),
+                Err(e) => {
+                    error!("fail to add transaction: {}", e);
+                    std::process::exit(1);
+                }
+            },
+            // the exit code is 1 if the check fails, and 2 if the ledger cannot be checked at all
+            Opts::Check(opts) => match check(opts) {
+                Ok(0) => {}
+                Ok(_) => std::process::exit(1),
+                Err(e) => {
+                    error!("fail to check ledger: {}", e);
+                    std::process::exit(2);
+                }
+            },
+            Opts::Fmt(opts) => {
+                let check = opts.check;
+                match fmt(opts) {
+                    Ok(files) if check && !files.is_empty() => {
+                        for file in &files {
+                            error!("{} is not formatted", file.display());
+                        }
+                        std::process::exit(1);
+                    }
+                    Ok(files) => info!("{} files are formatted", files.len()),
+                    Err(e) => error!("fail to format ledger: {}", e),
+                }
+            }
+            Opts::Sort(opts) => match sort(opts) {
+                Ok(0) => info!("all directives are in date order"),
+                Ok(count) => {
+                    error!("{} directives are out of date order", count);
+                    std::process::exit(1);
+                }
+                Err(e) => error!("fail to sort ledger: {}", e),
+            },
             Opts::Serve(mut opts) => {
                 let file_system = opts.source.clone().or(FileSystem::from_env()).unwrap_or(FileSystem::Fs);
-                let data_source = OpendalDataSource::from_env(file_system.clone(), &mut opts).await;
-                let auth_credential = opts.auth.or(std::env::var("ZHANG_AUTH").ok()).filter(|it| it.contains(':'));
+                let data_source: Arc<dyn DataSource> = Arc::new(OpendalDataSource::from_env(file_system.clone(), &mut opts).await);
+                let data_source: Arc<dyn DataSource> = match (opts.git, &file_system) {
+                    (true, FileSystem::Fs) => match GitDataSource::new(data_source.clone(), &opts.path) {
+                        Ok(git_data_source) => Arc::new(git_data_source),
+                        Err(e) => {
+                            error!("cannot open git repository of ledger, write-backs will not be committed: {}", e);
+                            data_source
+                        }
+                    },
+                    (true, _) => {
+                        error!("git data source is only supported on local file system, write-backs will not be committed");
+                        data_source
+                    }
+                    (false, _) => data_source,
+                };
+                let mut ledgers = vec![];
+                for (name, path) in opts.ledgers.clone() {
+                    let mut ledger_opts = ServerOpts {
+                        path: path.clone(),
+                        ledgers: vec![],
+                        ..opts.clone()
+                    };
+                    let data_source = OpendalDataSource::from_env(FileSystem::Fs, &mut ledger_opts).await;
+                    ledgers.push(LedgerConfig {
+                        name,
+                        path,
+                        endpoint: opts.endpoint.clone(),
+                        data_source: Arc::new(data_source),
+                    });
+                }
+                let authenticator = match server_authenticator(&opts) {
+                    Ok(authenticator) => authenticator,
+                    Err(e) => {
+                        error!("{}", e);
+                        std::process::exit(1);
+                    }
+                };
                 let result = zhang_server::serve(ServeConfig {
                     path: opts.path,
                     endpoint: opts.endpoint,
                     addr: opts.addr,
                     port: opts.port,
-                    auth_credential,
+                    authenticator,
                     is_local_fs: file_system == FileSystem::Fs,
                     no_report: opts.no_report,
-                    data_source: Arc::new(data_source),
+                    data_source,
+                    ledgers,
                 })
                 .await;
                 match result {
@@ -171,6 +691,343 @@ impl Opts {
     }
 }
 
+/// the authenticator of server from the basic credential and the static tokens, which are given by options or env
+fn server_authenticator(opts: &ServerOpts) -> Result<Authenticator, String> {
+    let mut authenticator = Authenticator::default();
+    if let Some(credential) = opts.auth.clone().or(std::env::var("ZHANG_AUTH").ok()) {
+        authenticator = authenticator.with_basic_credential(&credential)?;
+    }
+    let env_tokens = std::env::var("ZHANG_AUTH_TOKENS").unwrap_or_default();
+    let env_tokens = env_tokens.split(',').map(str::trim).filter(|it| !it.is_empty());
+    for token in opts.auth_tokens.iter().map(String::as_str).chain(env_tokens) {
+        authenticator = authenticator.with_token(token)?;
+    }
+    Ok(authenticator)
+}
+
+fn load_local_ledger(path: PathBuf, endpoint: String, profile: Option<String>) -> ZhangResult<Ledger> {
+    let data_source: Arc<dyn DataSource> = match PathBuf::from(&endpoint).extension().and_then(|it| it.to_str()) {
+        Some("bc" | "bean") => Arc::new(LocalFileSystemDataSource::new(Beancount {}).with_profile(profile)),
+        Some("journal" | "ledger" | "hledger") => Arc::new(LocalFileSystemDataSource::new(Journal {}).with_profile(profile)),
+        _ => Arc::new(LocalFileSystemDataSource::new(ZhangDataType {}).with_profile(profile)),
+    };
+    Ledger::load_with_data_source(path, endpoint, data_source)
+}
+
+fn export(opts: ExportOpts) -> ZhangResult<PathBuf> {
+    let format = match opts.exporter {
+        Exporter::Text => {
+            let output = opts.output.unwrap_or_else(|| PathBuf::from("zhang.zhang"));
+            let ledger = load_local_ledger(opts.path, opts.endpoint, opts.profile.clone())?;
+            std::fs::write(&output, export_text(&ledger))?;
+            return Ok(output);
+        }
+        Exporter::Beancount => {
+            let output = opts.output.unwrap_or_else(|| PathBuf::from("zhang.bean"));
+            let ledger = load_local_ledger(opts.path, opts.endpoint, opts.profile.clone())?;
+            std::fs::write(&output, beancount::exporter::export_ledger(&ledger))?;
+            return Ok(output);
+        }
+        Exporter::Journal => {
+            let output = opts.output.unwrap_or_else(|| PathBuf::from("zhang.journal"));
+            let ledger = load_local_ledger(opts.path, opts.endpoint, opts.profile.clone())?;
+            std::fs::write(&output, journal::export_ledger(&ledger))?;
+            return Ok(output);
+        }
+        Exporter::Sqlite => {
+            let output = opts.output.unwrap_or_else(|| PathBuf::from("zhang.sqlite"));
+            let ledger = load_local_ledger(opts.path, opts.endpoint, opts.profile.clone())?;
+            export_sqlite(&ledger.operations(), &output)?;
+            return Ok(output);
+        }
+        Exporter::Anonymized => {
+            let output = opts.output.unwrap_or_else(|| PathBuf::from("zhang.anonymized.zhang"));
+            let ledger = load_local_ledger(opts.path, opts.endpoint, opts.profile.clone())?;
+            let seed = opts.seed.unwrap_or_else(|| {
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|it| it.as_nanos() as u64)
+                    .unwrap_or_default()
+            });
+            std::fs::write(&output, export_anonymized(&ledger, seed))?;
+            return Ok(output);
+        }
+        Exporter::Csv => ExportFormat::Csv,
+        Exporter::Json => ExportFormat::Json,
+    };
+    let report = ExportReport::from(opts.report);
+    let output = opts
+        .output
+        .unwrap_or_else(|| PathBuf::from(format!("{}.{}", report.as_ref(), format.extension())));
+    let ledger = load_local_ledger(opts.path, opts.endpoint, opts.profile.clone())?;
+    let options = ExportOptions {
+        columns: opts.columns,
+        formatted: opts.formatted,
+        ..date_range_options(&ledger, opts.from, opts.to)
+    };
+    let content = export_report(&ledger.operations(), report, format, &options)?;
+    std::fs::write(&output, content)?;
+    Ok(output)
+}
+
+/// the options of dates in the timezone of ledger, from the start of `from` to the end of `to`
+fn date_range_options(ledger: &Ledger, from: Option<NaiveDate>, to: Option<NaiveDate>) -> ExportOptions {
+    let timezone = &ledger.options.timezone;
+    ExportOptions {
+        from: from
+            .and_then(|date| date.and_time(NaiveTime::MIN).and_local_timezone(*timezone).earliest())
+            .map(|it| it.to_utc()),
+        to: to
+            .and_then(|date| date.and_hms_opt(23, 59, 59))
+            .and_then(|datetime| datetime.and_local_timezone(*timezone).latest())
+            .map(|it| it.to_utc()),
+        ..Default::default()
+    }
+}
+
+fn report(opts: ReportOpts) -> ZhangResult<String> {
+    let format = match opts.format {
+        ReportFormat::Table => ExportFormat::Table,
+        ReportFormat::Csv => ExportFormat::Csv,
+        ReportFormat::Json => ExportFormat::Json,
+    };
+    let ledger = load_local_ledger(opts.path, opts.endpoint, opts.profile.clone())?;
+    let options = ExportOptions {
+        columns: opts.columns,
+        formatted: opts.formatted,
+        depth: opts.depth,
+        entity: opts.entity,
+        consolidated: opts.consolidated,
+        ..date_range_options(&ledger, opts.from, opts.to)
+    };
+    export_report(&ledger.operations(), ExportReport::from(opts.report), format, &options)
+}
+
+async fn import(opts: ImportOpts) -> ZhangResult<usize> {
+    let config_path = match opts.config {
+        Some(config) => config,
+        None => opts.path.join("importer.toml"),
+    };
+    let importer = Importer::from_toml(&std::fs::read_to_string(&config_path).with_path(&config_path)?)?;
+    let content = std::fs::read_to_string(&opts.file).with_path(&opts.file)?;
+    let statements = match opts.format {
+        ImportFormat::Csv => vec![importer.csv_statement(opts.mapping.as_deref(), &content)?],
+        ImportFormat::Ofx => importer.ofx_statements(opts.mapping.as_deref(), &content)?,
+        ImportFormat::Camt => importer.camt_statements(opts.mapping.as_deref(), &content)?,
+        ImportFormat::Mt940 => importer.mt940_statements(opts.mapping.as_deref(), &content)?,
+    };
+    let directives = statements.into_iter().flat_map(|it| importer.directives(it)).collect::<Vec<_>>();
+    let target = match opts.target {
+        Some(target) => target,
+        None => PathBuf::from(&opts.endpoint),
+    };
+    let ledger = load_local_ledger(opts.path, opts.endpoint, opts.profile.clone())?;
+    let total = directives.len();
+    let directives = Importer::exclude_imported(&ledger, directives);
+    if directives.len() < total {
+        info!("{} directives are skipped since they have been imported", total - directives.len());
+    }
+    let (_, duplicates) = importer.exclude_duplicates(&ledger, directives.clone());
+
+    let stdin = std::io::stdin();
+    let mut prompt = Prompt::new(stdin.lock(), std::io::stdout()).colored(std::io::stdout().is_terminal());
+    let directives = review_import(&ledger, &mut prompt, directives, &duplicates, opts.allow_duplicates, opts.interactive)?;
+    if !opts.allow_duplicates && !duplicates.is_empty() {
+        warn!("{} probable duplicates are skipped, use --allow-duplicates to import them", duplicates.len());
+    }
+    let count = directives.len();
+    if opts.dry_run || count == 0 {
+        return Ok(0);
+    }
+    if opts.interactive && !prompt.confirm(&format!("append {} directives to {}?", count, target.display()))? {
+        return Ok(0);
+    }
+    Importer::append(&ledger, &target, directives).await?;
+    Ok(count)
+}
+
+fn close_period(opts: ClosePeriodOpts) -> ZhangResult<()> {
+    let ledger = load_local_ledger(opts.path, opts.endpoint, opts.profile)?;
+    let retained_earnings = Account::from_str(&opts.retained_earnings).map_err(|_| ZhangError::InvalidAccount)?;
+    let PeriodClosing { closing, opening } = closing::close_period(&ledger, opts.date, &retained_earnings)?;
+    let export = |directives: &[Directive]| -> ZhangResult<String> {
+        let mut content = String::new();
+        for directive in directives {
+            content.push_str(&String::from_utf8(ledger.data_source.export(directive.clone())?)?);
+            content.push('\n');
+        }
+        Ok(content)
+    };
+    if opts.dry_run {
+        println!("{}", export(&closing)?);
+        if opts.opening.is_some() {
+            println!("{}", export(&opening)?);
+        }
+        return Ok(());
+    }
+    // the opening balances are never written over an existing file, which may be a ledger file of the next period already
+    if let Some(file) = opts
+        .opening
+        .as_ref()
+        .filter(|file| ledger.data_source.get(file.to_string_lossy().to_string()).is_ok())
+    {
+        return Err(ZhangError::CustomError(format!("file {} of opening balances exists already", file.display())));
+    }
+    if closing.is_empty() {
+        info!("income and expenses are closed already at {}", opts.date);
+    } else {
+        for span in ledger.data_source.append(&ledger, closing)? {
+            info!("closing is appended to {}", span.filename.unwrap_or_default().display());
+        }
+    }
+    if let Some(file) = opts.opening {
+        ledger
+            .data_source
+            .save(&ledger, file.to_string_lossy().to_string(), export(&opening)?.as_bytes())?;
+        info!("opening balances are written to {}", file.display());
+    }
+    Ok(())
+}
+
+fn instantiate_template(opts: TemplateOpts) -> ZhangResult<()> {
+    let ledger = load_local_ledger(opts.path, opts.endpoint, opts.profile)?;
+    let name = opts.name;
+    let template = template::find_template(&ledger, &name).ok_or_else(|| ZhangError::CustomError(format!("template {} is not found", name)))?;
+    let date = opts.date.map(Date::Date).unwrap_or_else(|| Date::now(&ledger.options.timezone));
+    let trx = template::instantiate(template, date, &opts.values.into_iter().collect())?;
+    let issues = ledger.operations().validate_transaction(&trx)?;
+    if !issues.is_empty() {
+        return Err(ZhangError::CustomError(format!(
+            "invalid transaction: {}",
+            issues.iter().map(|it| it.to_string()).collect::<Vec<_>>().join(", ")
+        )));
+    }
+    let directive = Directive::Transaction(trx);
+    if opts.dry_run {
+        println!("{}", String::from_utf8(ledger.data_source.export(directive)?)?);
+        return Ok(());
+    }
+    for span in ledger.data_source.append(&ledger, vec![directive])? {
+        info!("transaction is appended to {}", span.filename.unwrap_or_default().display());
+    }
+    Ok(())
+}
+
+fn query(opts: QueryOpts) -> ZhangResult<()> {
+    let ledger = load_local_ledger(opts.path, opts.endpoint, opts.profile)?;
+    if let Some(query) = opts.query {
+        let table = repl::query_table(&ledger.operations(), &query)?;
+        let content = match opts.format {
+            ReportFormat::Table => table.to_text(),
+            ReportFormat::Csv => table.to_csv(),
+            ReportFormat::Json => table.to_json()?,
+        };
+        println!("{}", content.trim_end());
+        return Ok(());
+    }
+    let mut stdout = std::io::stdout();
+    if std::io::stdin().is_terminal() && std::io::stdout().is_terminal() {
+        let history_file = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".zhang_query_history"));
+        repl::run(&ledger, &mut repl::TerminalEditor::new(history_file)?, &mut stdout)
+    } else {
+        repl::run(&ledger, &mut repl::PlainEditor(std::io::stdin().lock()), &mut stdout)
+    }
+}
+
+async fn add(opts: AddOpts) -> ZhangResult<Option<String>> {
+    let ledger = load_local_ledger(opts.path, opts.endpoint, opts.profile)?;
+    let stdin = std::io::stdin();
+    let mut prompt = Prompt::new(stdin.lock(), std::io::stdout());
+    wizard::add(&ledger, &mut prompt).await
+}
+
+/// report the errors of ledger except the ignored kinds, return the count of errors whose severity reaches the threshold
+fn check(opts: CheckOpts) -> ZhangResult<usize> {
+    let ledger = load_local_ledger(opts.path, opts.endpoint, opts.profile.clone())?;
+    let ignores = &opts.ignores;
+    let diagnostics = ledger.diagnostics()?.into_iter().filter(|it| !ignores.contains(&it.kind)).collect::<Vec<_>>();
+    match opts.format {
+        DiagnosticFormat::Json => {
+            let json = serde_json::to_string_pretty(&diagnostics).map_err(|e| ZhangError::CustomError(e.to_string()))?;
+            println!("{}", json);
+        }
+        DiagnosticFormat::Text => {
+            for diagnostic in &diagnostics {
+                let location = match (&diagnostic.file, diagnostic.start_position) {
+                    (Some(file), Some(position)) => format!("{}:{}:{}", file.display(), position.line, position.column),
+                    (Some(file), None) => file.display().to_string(),
+                    _ => "<unknown>".to_owned(),
+                };
+                let mut metas = diagnostic.metas.iter().map(|(key, value)| format!("{key}={value}")).collect::<Vec<_>>();
+                metas.sort();
+                let metas = metas.join(", ");
+                match diagnostic.severity {
+                    ErrorSeverity::Error => error!("{}: {} {}", location, diagnostic.kind, metas),
+                    ErrorSeverity::Warning => warn!("{}: {} {}", location, diagnostic.kind, metas),
+                    ErrorSeverity::Info => info!("{}: {} {}", location, diagnostic.kind, metas),
+                }
+            }
+        }
+    }
+    let threshold = opts.severity;
+    Ok(diagnostics.iter().filter(|it| it.severity >= threshold).count())
+}
+
+/// the plain files included by ledger, the encrypted ones are skipped since they are written back by the ledger only
+fn ledger_files(opts: &FmtOpts) -> ZhangResult<Vec<PathBuf>> {
+    let data_source = LocalFileSystemDataSource::new(ZhangDataType {});
+    let load_result = data_source.load(opts.path.to_string_lossy().to_string(), opts.endpoint.clone())?;
+    let (encrypted, plain): (Vec<_>, Vec<_>) = load_result.visited_files.into_iter().partition(|file| Encryption::of(file).is_some());
+    for file in encrypted {
+        info!("encrypted file {} is skipped", file.display());
+    }
+    Ok(plain)
+}
+
+/// format the zhang files included by ledger, return the files whose content is changed
+fn fmt(opts: FmtOpts) -> ZhangResult<Vec<PathBuf>> {
+    let mut changed = vec![];
+    for file in ledger_files(&opts)? {
+        let content = std::fs::read_to_string(&file).with_path(&file)?;
+        let formatted = format(&content)?;
+        if formatted != content {
+            if !opts.check {
+                std::fs::write(&file, formatted).with_path(&file)?;
+            }
+            changed.push(file);
+        }
+    }
+    Ok(changed)
+}
+
+/// sort the zhang files included by ledger, return the count of misordered directives left in files
+fn sort(opts: FmtOpts) -> ZhangResult<usize> {
+    let mut count = 0;
+    for file in ledger_files(&opts)? {
+        let content = std::fs::read_to_string(&file).with_path(&file)?;
+        let misordered = check_order(&content)?;
+        if misordered.is_empty() {
+            continue;
+        }
+        if opts.check {
+            for directive in &misordered {
+                warn!(
+                    "{}:{}: directive of {} appears after {}",
+                    file.display(),
+                    directive.line,
+                    directive.date,
+                    directive.after
+                );
+            }
+            count += misordered.len();
+        } else {
+            std::fs::write(&file, sort_directives(&content)?).with_path(&file)?;
+            info!("{} directives of {} are reordered", misordered.len(), file.display());
+        }
+    }
+    Ok(count)
+}
+
 #[tokio::main]
 async fn main() {
     // console_subscriber::init();
@@ -196,21 +1053,31 @@ async fn main() {
 mod test {
     use std::io::{stdout, Write};
     use std::sync::Arc;
+    use std::time::Duration;
 
     use axum::body::Body;
     use axum::extract::Request;
+    use clap::Parser;
     use http::StatusCode;
     use http_body_util::BodyExt;
+    use indexmap::IndexMap;
     use jsonpath_rust::JsonPathQuery;
     use serde::Deserialize;
     use serde_json::Value;
-    use tokio::sync::{mpsc, RwLock};
+    use tokio::sync::RwLock;
     use tower::util::ServiceExt;
+    use uuid::Uuid;
+    use zhang_core::ast::Directive;
+    use zhang_core::importer::Importer;
     use zhang_core::ledger::Ledger;
-    use zhang_server::broadcast::Broadcaster;
-    use zhang_server::{create_server_app, ReloadSender};
+    use zhang_core::utils::id::FromSpan;
+    use zhang_server::auth::Authenticator;
+    use zhang_server::broadcast::{BroadcastEvent, Broadcaster};
+    use zhang_server::{create_server_app, swap_reloaded_ledger, ReloadSender, DEFAULT_LEDGER_NAME};
 
     use crate::opendal::OpendalDataSource;
+    use crate::repl::{Completions, PlainEditor};
+    use crate::wizard::{review_import, Prompt};
     use crate::{FileSystem, ServerOpts};
 
     macro_rules! pprintln {
@@ -256,8 +1123,13 @@ mod test {
                         addr: "".to_string(),
                         port: 0,
                         auth: None,
+                        auth_tokens: vec![],
                         source: None,
                         no_report: false,
+                        no_cache: true,
+                        profile: None,
+                        git: false,
+                        ledgers: vec![],
                     },
                 )
                 .await;
@@ -265,10 +1137,9 @@ mod test {
                 let ledger = Ledger::async_load(pathbuf.clone(), "main.zhang".to_owned(), data_source.clone())
                     .await
                     .expect("cannot load ledger");
-                let ledger_data = Arc::new(RwLock::new(ledger));
+                let ledger_data = Arc::new(IndexMap::from([(DEFAULT_LEDGER_NAME.to_owned(), Arc::new(RwLock::new(ledger)))]));
                 let broadcaster = Broadcaster::create();
-                let (tx, _) = mpsc::channel(1);
-                let reload_sender = Arc::new(ReloadSender(tx));
+                let reload_sender = Arc::new(ReloadSender::default());
                 let app = create_server_app(ledger_data, broadcaster, reload_sender, None);
 
                 let response = app
@@ -304,4 +1175,1208 @@ mod test {
             }
         }
     }
+
+    #[tokio::test]
+    async fn should_merge_reload_requests_not_handled_yet() {
+        let sender = ReloadSender::default();
+        sender.send(vec!["b.zhang".into()]);
+        sender.send(vec!["a.zhang".into(), "b.zhang".into()]);
+        assert_eq!(vec![std::path::PathBuf::from("a.zhang"), "b.zhang".into()], sender.recv().await);
+
+        sender.send(vec!["a.zhang".into()]);
+        sender.reload();
+        sender.send(vec!["b.zhang".into()]);
+        assert!(sender.recv().await.is_empty(), "all ledgers are reloaded once any request reloads all");
+    }
+
+    #[tokio::test]
+    async fn should_compute_changes_of_reloading_against_the_ledger_it_replaces() {
+        let folder = tempfile::tempdir().unwrap();
+        let load = |version: usize| {
+            let content = ["2024-01-01 open Assets:A", "2024-02-01 open Assets:B", "2024-03-01 open Assets:C"][..=version].join("\n");
+            let path = folder.path().join(version.to_string());
+            std::fs::create_dir_all(&path).unwrap();
+            std::fs::write(path.join("main.zhang"), content).unwrap();
+            crate::load_local_ledger(path, "main.zhang".to_owned(), None).unwrap()
+        };
+        let state = RwLock::new(load(0));
+        let date = |month| chrono::NaiveDate::from_ymd_opt(2024, month, 1).unwrap();
+        let changed_range = |event: zhang_core::ZhangResult<BroadcastEvent>| match event.unwrap() {
+            BroadcastEvent::Reload { changes, .. } => changes.date_range.map(|range| (range.from, range.to)),
+            _ => unreachable!(),
+        };
+
+        // both reloading wait for the ledger being held, the later one should see the ledger swapped in by the earlier one
+        let guard = state.write().await;
+        let (first, second, _) = tokio::join!(
+            swap_reloaded_ledger(DEFAULT_LEDGER_NAME, &state, load(1), &[], Duration::ZERO),
+            swap_reloaded_ledger(DEFAULT_LEDGER_NAME, &state, load(2), &[], Duration::ZERO),
+            async {
+                tokio::task::yield_now().await;
+                drop(guard);
+            }
+        );
+
+        assert_eq!(Some((date(2), date(2))), changed_range(first));
+        assert_eq!(Some((date(3), date(3))), changed_range(second));
+        assert_eq!(3, state.read().await.directives.len());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn should_select_ledger_per_request() {
+        let mut ledgers = IndexMap::new();
+        for (name, folder) in [(DEFAULT_LEDGER_NAME, "simple-parser"), ("business", "fava-demo-ledger")] {
+            let pathbuf = std::path::PathBuf::from("../integration-tests").join(folder);
+            let ledger = crate::load_local_ledger(pathbuf, "main.zhang".to_owned(), None).unwrap();
+            ledgers.insert(name.to_owned(), Arc::new(RwLock::new(ledger)));
+        }
+        let app = create_server_app(Arc::new(ledgers), Broadcaster::create(), Arc::new(ReloadSender::default()), None);
+
+        let get = |uri: &str, ledger: Option<&str>| {
+            let builder = Request::builder().method(http::Method::GET).uri(uri);
+            let builder = match ledger {
+                Some(ledger) => builder.header(zhang_server::state::LEDGER_HEADER, ledger),
+                None => builder,
+            };
+            app.clone().oneshot(builder.body(Body::empty()).unwrap())
+        };
+        async fn json(response: axum::response::Response) -> Value {
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            serde_json::from_slice(&body).unwrap()
+        }
+
+        assert_eq!("My Accounting", json(get("/api/info", None).await.unwrap()).await["data"]["title"]);
+        assert_eq!(
+            "Example Beancount file",
+            json(get("/api/info", Some("business")).await.unwrap()).await["data"]["title"]
+        );
+        assert_eq!(
+            "Example Beancount file",
+            json(get("/api/info?ledger=business", None).await.unwrap()).await["data"]["title"]
+        );
+        assert_eq!(StatusCode::NOT_FOUND, get("/api/info", Some("unknown")).await.unwrap().status());
+
+        let ledgers = json(get("/api/ledgers", None).await.unwrap()).await;
+        assert_eq!(
+            serde_json::json!([DEFAULT_LEDGER_NAME, "business"]),
+            ledgers.clone().path("$.data[*].name").unwrap()
+        );
+        assert_eq!(serde_json::json!([true, false]), ledgers.path("$.data[*].is_default").unwrap());
+
+        let net_worth = json(get("/api/ledgers/net-worth?currency=USD", None).await.unwrap()).await;
+        assert_eq!("USD", net_worth["data"]["total"]["currency"]);
+        assert!(net_worth["data"]["ledgers"]["business"].is_object());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn should_edit_and_delete_directive_by_id() {
+        let folder = tempfile::tempdir().unwrap();
+        let main = folder.path().join("main.zhang");
+        std::fs::write(&main, "1970-01-01 open Assets:Bank\n1970-01-01 open Expenses:Food\n").unwrap();
+        let ledger = crate::load_local_ledger(folder.path().to_path_buf(), "main.zhang".to_owned(), None).unwrap();
+        let id = Uuid::from_span(&ledger.directives[1].span);
+        let ledgers = Arc::new(IndexMap::from([(DEFAULT_LEDGER_NAME.to_owned(), Arc::new(RwLock::new(ledger)))]));
+        let app = create_server_app(ledgers, Broadcaster::create(), Arc::new(ReloadSender::default()), None);
+
+        let request = |method: http::Method, body: Option<Value>| {
+            let builder = Request::builder()
+                .method(method)
+                .uri(format!("/api/directives/{}", id))
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref());
+            let body = body.map(|it| Body::from(it.to_string())).unwrap_or_else(Body::empty);
+            app.clone().oneshot(builder.body(body).unwrap())
+        };
+
+        let response = request(http::Method::GET, None).await.unwrap();
+        let body: Value = serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!("1970-01-01 open Expenses:Food", body["data"]["content"]);
+
+        // the malformed directive is reported by the errors of reloaded ledger
+        let response = request(http::Method::PUT, Some(serde_json::json!({"content": "1970-01-01 open"})))
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!("ParseError", body["data"][0]["error_type"]);
+        assert_eq!("1970-01-01 open Assets:Bank\n1970-01-01 open\n", std::fs::read_to_string(&main).unwrap());
+
+        let response = request(http::Method::PUT, Some(serde_json::json!({"content": "1970-01-01 open Expenses:Dining"})))
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(serde_json::json!([]), body["data"]);
+        assert_eq!(
+            "1970-01-01 open Assets:Bank\n1970-01-01 open Expenses:Dining\n",
+            std::fs::read_to_string(&main).unwrap()
+        );
+
+        let response = request(http::Method::DELETE, None).await.unwrap();
+        let body: Value = serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(serde_json::json!([]), body["data"]);
+        assert_eq!("1970-01-01 open Assets:Bank\n", std::fs::read_to_string(&main).unwrap());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn should_serve_data_written_by_plugin() {
+        let folder = tempfile::tempdir().unwrap();
+        std::fs::write(folder.path().join("main.zhang"), "1970-01-01 open Assets:Bank\n").unwrap();
+        let ledger = crate::load_local_ledger(folder.path().to_path_buf(), "main.zhang".to_owned(), None).unwrap();
+        {
+            let mut operations = ledger.operations();
+            operations
+                .insert_plugin_data("trips", "tokyo".to_owned(), serde_json::json!({"budget": 1000}))
+                .unwrap();
+            operations.insert_plugin_data("trips", "osaka".to_owned(), serde_json::json!(true)).unwrap();
+        }
+        let ledgers = Arc::new(IndexMap::from([(DEFAULT_LEDGER_NAME.to_owned(), Arc::new(RwLock::new(ledger)))]));
+        let app = create_server_app(ledgers, Broadcaster::create(), Arc::new(ReloadSender::default()), None);
+
+        let get = |uri: &str| {
+            app.clone()
+                .oneshot(Request::builder().method(http::Method::GET).uri(uri).body(Body::empty()).unwrap())
+        };
+
+        let response = get("/api/plugins/trips/data").await.unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        let body: Value = serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(serde_json::json!({"tokyo": {"budget": 1000}, "osaka": true}), body["data"]);
+
+        let response = get("/api/plugins/unknown/data").await.unwrap();
+        let body: Value = serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(serde_json::json!({}), body["data"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn should_create_budget_and_report_actual_vs_budgeted() {
+        let folder = tempfile::tempdir().unwrap();
+        let main = folder.path().join("main.zhang");
+        std::fs::write(
+            &main,
+            concat!(
+                "1970-01-01 commodity CNY\n",
+                "1970-01-01 open Assets:Bank\n",
+                "1970-01-01 open Expenses:Food\n",
+                "  budget: \"Diet\"\n",
+                "2023-11-01 budget Diet CNY\n",
+                "2023-11-01 budget-add Diet 100 CNY\n",
+                "2023-11-05 \"KFC\"\n",
+                "  Assets:Bank -30 CNY\n",
+                "  Expenses:Food 30 CNY\n",
+            ),
+        )
+        .unwrap();
+        let ledger = crate::load_local_ledger(folder.path().to_path_buf(), "main.zhang".to_owned(), None).unwrap();
+        let ledgers = Arc::new(IndexMap::from([(DEFAULT_LEDGER_NAME.to_owned(), Arc::new(RwLock::new(ledger)))]));
+        let app = create_server_app(ledgers, Broadcaster::create(), Arc::new(ReloadSender::default()), None);
+
+        let request = |method: http::Method, uri: &str, body: Option<Value>| {
+            let builder = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref());
+            let body = body.map(|it| Body::from(it.to_string())).unwrap_or_else(Body::empty);
+            app.clone().oneshot(builder.body(body).unwrap())
+        };
+
+        let response = request(http::Method::GET, "/api/budget-report?interval=month", None).await.unwrap();
+        let body: Value = serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!("2023-11", body["data"][0]["period"]);
+        assert_eq!("100", body["data"][0]["budgeted_amount"]["number"]);
+        assert_eq!("30", body["data"][0]["actual_amount"]["number"]);
+        assert_eq!("70", body["data"][0]["variance"]["number"]);
+
+        let response = request(http::Method::GET, "/api/budget-report?interval=year", None).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+        let payload = serde_json::json!({"name": "Diet", "commodity": "CNY"});
+        let response = request(http::Method::POST, "/api/budgets", Some(payload)).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+        let payload = serde_json::json!({"name": "Travel", "commodity": "CNY", "alias": "Trips", "date": "2023-12-01", "assigned_amount": 500});
+        let response = request(http::Method::POST, "/api/budgets", Some(payload)).await.unwrap();
+        let body: Value = serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        let content = std::fs::read_to_string(body["data"]["path"].as_str().unwrap()).unwrap();
+        let span_start = body["data"]["span_start"].as_u64().unwrap() as usize;
+        assert!(content[span_start..].starts_with("2023-12-01 budget Travel CNY\n  alias: \"Trips\"\n"));
+        assert!(content.contains("2023-12-01 budget-add Travel 500 CNY"));
+        // the new file is included once by the directives appended together
+        assert_eq!(1, std::fs::read_to_string(&main).unwrap().matches("include \"data/2023/12.zhang\"").count());
+
+        let payload = serde_json::json!({"date": "2023-11-15", "amount": 20});
+        let response = request(http::Method::POST, "/api/budgets/Diet/amounts", Some(payload)).await.unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        let body: Value = serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        let content = std::fs::read_to_string(body["data"]["path"].as_str().unwrap()).unwrap();
+        assert!(content.contains("2023-11-15 budget-add Diet 20 CNY"));
+
+        let payload = serde_json::json!({"amount": 20});
+        let response = request(http::Method::POST, "/api/budgets/Unknown/amounts", Some(payload)).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn should_query_ledger_by_graphql() {
+        let folder = tempfile::tempdir().unwrap();
+        std::fs::write(
+            folder.path().join("main.zhang"),
+            concat!(
+                "1970-01-01 commodity CNY\n",
+                "1970-01-01 commodity USD\n",
+                "1970-01-01 open Assets:Bank\n",
+                "1970-01-01 open Assets:Cash\n",
+                "1970-01-01 open Expenses:Food\n",
+                "  budget: \"Diet\"\n",
+                "2023-11-01 budget Diet CNY\n",
+                "2023-11-01 budget-add Diet 100 CNY\n",
+                "2023-11-02 price USD 7 CNY\n",
+                "2023-11-05 \"KFC\" \"Lunch\" #food\n",
+                "  receipt: \"001\"\n",
+                "  Assets:Bank -30 CNY\n",
+                "  Expenses:Food 30 CNY\n",
+                "2023-12-05 \"Market\" \"Fruits\"\n",
+                "  Assets:Cash -10 CNY\n",
+                "  Expenses:Food 10 CNY\n",
+            ),
+        )
+        .unwrap();
+        let ledger = crate::load_local_ledger(folder.path().to_path_buf(), "main.zhang".to_owned(), None).unwrap();
+        let ledgers = Arc::new(IndexMap::from([(DEFAULT_LEDGER_NAME.to_owned(), Arc::new(RwLock::new(ledger)))]));
+        let app = create_server_app(ledgers, Broadcaster::create(), Arc::new(ReloadSender::default()), None);
+
+        let query = |query: &str, variables: Value| {
+            let request = Request::builder()
+                .method(http::Method::POST)
+                .uri("/api/graphql")
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(serde_json::json!({"query": query, "variables": variables}).to_string()))
+                .unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                assert_eq!(StatusCode::OK, response.status());
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                serde_json::from_slice::<Value>(&body).unwrap()
+            }
+        };
+
+        let body = query(
+            "query Food($account: String!) { food: account(name: $account) { name status balances { number commodity } } \
+             accounts(prefix: \"Assets\") { name } }",
+            serde_json::json!({"account": "Expenses:Food"}),
+        )
+        .await;
+        assert_eq!(
+            serde_json::json!({
+                "food": {"name": "Expenses:Food", "status": "Open", "balances": [{"number": "40", "commodity": "CNY"}]},
+                "accounts": [{"name": "Assets:Bank"}, {"name": "Assets:Cash"}],
+            }),
+            body["data"]
+        );
+        assert!(body.get("errors").is_none());
+
+        let body = query(
+            "{ journals(tag: \"food\") { payee metas { key value } postings { account inferred_unit_number } } \
+             account(name: \"Assets:Cash\") { journals(from: \"2023-12-01\") { payee account_after_number } } }",
+            Value::Null,
+        )
+        .await;
+        assert_eq!(
+            serde_json::json!([{
+                "payee": "KFC",
+                "metas": [{"key": "receipt", "value": "001"}],
+                "postings": [{"account": "Assets:Bank", "inferred_unit_number": "-30"}, {"account": "Expenses:Food", "inferred_unit_number": "30"}],
+            }]),
+            body["data"]["journals"]
+        );
+        assert_eq!(
+            serde_json::json!([{"payee": "Market", "account_after_number": "-10"}]),
+            body["data"]["account"]["journals"]
+        );
+
+        let body = query(
+            "{ budgets { name report(interval: \"month\") { period actual_amount { number } } } \
+             prices(commodity: \"USD\") { amount target_commodity } balances(account: \"Assets:Bank\") { number } }",
+            Value::Null,
+        )
+        .await;
+        assert_eq!("Diet", body["data"]["budgets"][0]["name"]);
+        assert_eq!("2023-11", body["data"]["budgets"][0]["report"][0]["period"]);
+        assert_eq!("30", body["data"]["budgets"][0]["report"][0]["actual_amount"]["number"]);
+        assert_eq!(serde_json::json!([{"amount": "7", "target_commodity": "CNY"}]), body["data"]["prices"]);
+        assert_eq!(serde_json::json!([{"number": "-30"}]), body["data"]["balances"]);
+
+        let body = query(
+            "query { accounts(prefix: \"Assets\") { ...names } journals(tag: \"food\") { ... on Journal { payee } } } \
+             fragment names on Account { name __typename }",
+            Value::Null,
+        )
+        .await;
+        assert_eq!(
+            serde_json::json!({
+                "accounts": [{"name": "Assets:Bank", "__typename": "Account"}, {"name": "Assets:Cash", "__typename": "Account"}],
+                "journals": [{"payee": "KFC"}],
+            }),
+            body["data"]
+        );
+
+        let body = query(
+            "{ __schema { queryType { name } mutationType { name } } __type(name: \"Account\") { fields { name } } }",
+            Value::Null,
+        )
+        .await;
+        assert_eq!("Query", body["data"]["__schema"]["queryType"]["name"]);
+        assert_eq!(Value::Null, body["data"]["__schema"]["mutationType"]);
+        let account_fields = body["data"]["__type"]["fields"].as_array().unwrap();
+        for field in ["name", "type", "status", "balances", "journals", "documents"] {
+            assert!(account_fields.iter().any(|it| it["name"] == field), "Account should have field {}", field);
+        }
+
+        for invalid in [
+            "query($account: String!) { account(name: $account) { name } }",
+            "{ journals(limit: $limit) { payee } }",
+            "{ accounts { unknown } }",
+            "{ accounts }",
+            "{ accounts { name { first } } }",
+            "{ accounts(owner: \"me\") { name } }",
+            "{ accounts { ...fields } }",
+            "mutation { accounts { name } }",
+        ] {
+            let body = query(invalid, Value::Null).await;
+            assert_eq!(Value::Null, body["data"], "{} should fail", invalid);
+            assert!(body["errors"][0]["message"].is_string(), "{} should fail", invalid);
+        }
+        let body = query(
+            "query($account: String!) { account(name: $account) { name } }",
+            serde_json::json!({"account": 1}),
+        )
+        .await;
+        assert_eq!(Value::Null, body["data"]);
+        assert!(body["errors"][0]["message"].is_string());
+
+        // the queries too deep or too complex are rejected before execution
+        let nested_types = (0..16).fold("name".to_owned(), |fields, _| format!("ofType {{ {} }}", fields));
+        let body = query(&format!("{{ __type(name: \"Account\") {{ {} }} }}", nested_types), Value::Null).await;
+        assert_eq!(Value::Null, body["data"]);
+        assert!(body["errors"][0]["message"].as_str().unwrap().contains("nested too deep"), "{}", body);
+        let aliased_accounts = (0..300).map(|idx| format!("a{}: accounts {{ name }}", idx)).collect::<Vec<_>>().join(" ");
+        let body = query(&format!("{{ {} }}", aliased_accounts), Value::Null).await;
+        assert_eq!(Value::Null, body["data"]);
+        assert!(body["errors"][0]["message"].as_str().unwrap().contains("too complex"), "{}", body);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn should_serve_openapi_document_with_resolved_references() {
+        let folder = tempfile::tempdir().unwrap();
+        std::fs::write(folder.path().join("main.zhang"), "1970-01-01 open Assets:Bank\n").unwrap();
+        let ledger = crate::load_local_ledger(folder.path().to_path_buf(), "main.zhang".to_owned(), None).unwrap();
+        let ledgers = Arc::new(IndexMap::from([(DEFAULT_LEDGER_NAME.to_owned(), Arc::new(RwLock::new(ledger)))]));
+        let app = create_server_app(ledgers, Broadcaster::create(), Arc::new(ReloadSender::default()), None);
+
+        let response = app.oneshot(Request::builder().uri("/openapi.json").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        let document: Value = serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+
+        assert_eq!("3.1.0", document["openapi"]);
+        let get_account_info = &document["paths"]["/api/accounts/{account_name}"]["get"];
+        assert_eq!("get_account_info", get_account_info["operationId"]);
+        assert_eq!("account_name", get_account_info["parameters"][0]["name"]);
+        let response = &get_account_info["responses"]["200"]["content"]["application/json"]["schema"]["$ref"];
+        let response = document.pointer(response.as_str().unwrap().strip_prefix('#').unwrap()).unwrap();
+        assert_eq!(
+            "#/components/schemas/AccountStatus",
+            response["properties"]["data"]["properties"]["status"]["$ref"]
+        );
+        assert_eq!("#/components/responses/Error", get_account_info["responses"]["default"]["$ref"]);
+        let journal_parameters = document["paths"]["/api/journals"]["get"]["parameters"].as_array().unwrap();
+        assert!(journal_parameters
+            .iter()
+            .any(|it| it["name"] == "keyword" && it["in"] == "query" && it["required"] == false));
+
+        let transaction = &document["components"]["schemas"]["CreateTransactionRequest"];
+        assert!(transaction["required"].as_array().unwrap().contains(&Value::from("postings")));
+        assert!(!transaction["required"].as_array().unwrap().contains(&Value::from("narration")));
+        assert_eq!(serde_json::json!(["Open", "Close"]), document["components"]["schemas"]["AccountStatus"]["enum"]);
+
+        fn references(value: &Value, found: &mut Vec<String>) {
+            match value {
+                Value::Object(object) => {
+                    if let Some(Value::String(reference)) = object.get("$ref") {
+                        found.push(reference.clone());
+                    }
+                    object.values().for_each(|it| references(it, found));
+                }
+                Value::Array(items) => items.iter().for_each(|it| references(it, found)),
+                _ => {}
+            }
+        }
+        let mut found = vec![];
+        references(&document, &mut found);
+        assert!(!found.is_empty());
+        for reference in found {
+            let pointer = reference.strip_prefix('#').unwrap();
+            assert!(document.pointer(pointer).is_some_and(|it| it.is_object()), "{} should be resolved", reference);
+        }
+    }
+
+    #[test]
+    fn should_describe_every_api_route_in_openapi_document() {
+        let document = zhang_server::openapi::document();
+        let paths = document["paths"].as_object().unwrap();
+        let routes = zhang_server::api_routes()
+            .into_iter()
+            .map(|(method, path)| {
+                let path = path
+                    .split('/')
+                    .map(|segment| match segment.strip_prefix(':') {
+                        Some(parameter) => format!("{{{}}}", parameter),
+                        None => segment.to_owned(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("/");
+                (method.to_owned(), path)
+            })
+            .collect::<Vec<_>>();
+        for (method, path) in &routes {
+            assert!(
+                paths.get(path).and_then(|it| it.get(method)).is_some(),
+                "{} {} should be described",
+                method,
+                path
+            );
+        }
+        let described = paths
+            .iter()
+            .flat_map(|(path, item)| item.as_object().unwrap().keys().map(move |method| (method.clone(), path.clone())))
+            .collect::<Vec<_>>();
+        for (method, path) in &described {
+            assert!(routes.contains(&(method.clone(), path.clone())), "{} {} should be served", method, path);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn should_expose_metrics_of_ledger_and_requests() {
+        let folder = tempfile::tempdir().unwrap();
+        std::fs::write(
+            folder.path().join("main.zhang"),
+            concat!(
+                "1970-01-01 open Assets:Bank\n",
+                "1970-01-01 open Expenses:Food\n",
+                "1970-01-02 \"Payee\" \"Narration\"\n",
+                "  Assets:Bank -1 CNY\n",
+                "  Expenses:Food 2 CNY\n",
+            ),
+        )
+        .unwrap();
+        let ledger = crate::load_local_ledger(folder.path().to_path_buf(), "main.zhang".to_owned(), None).unwrap();
+        let ledgers = Arc::new(IndexMap::from([(DEFAULT_LEDGER_NAME.to_owned(), Arc::new(RwLock::new(ledger)))]));
+        let app = create_server_app(ledgers, Broadcaster::create(), Arc::new(ReloadSender::default()), None);
+
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri("/api/accounts/Assets:Bank").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+
+        let response = app.oneshot(Request::builder().uri("/metrics").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        assert!(response.headers()[http::header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain; version=0.0.4"));
+        let text = String::from_utf8(response.into_body().collect().await.unwrap().to_bytes().to_vec()).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+
+        assert!(lines.contains(&"zhang_directives{ledger=\"default\",type=\"Open\"} 2"));
+        assert!(lines.contains(&"zhang_directives{ledger=\"default\",type=\"Transaction\"} 1"));
+        assert!(lines.contains(&"zhang_errors{ledger=\"default\",severity=\"error\"} 1"));
+        assert!(lines.contains(&"zhang_errors{ledger=\"default\",severity=\"info\"} 0"));
+        assert!(lines.contains(&"# TYPE zhang_http_request_duration_seconds histogram"));
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with("zhang_http_request_duration_seconds_bucket{method=\"GET\",path=\"/api/accounts/:account_name\",le=\"+Inf\"}")));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn should_upload_documents_to_account_and_transaction() {
+        let folder = tempfile::tempdir().unwrap();
+        let main = folder.path().join("main.zhang");
+        std::fs::write(
+            &main,
+            concat!(
+                "option \"insert_strategy\" \"single\"\n",
+                "option \"document_directory\" \"documents\"\n",
+                "option \"document_naming\" \"dated\"\n",
+                "1970-01-01 open Assets:Bank\n",
+                "1970-01-01 open Expenses:Food\n",
+                "2024-01-15 \"KFC\"\n",
+                "  Assets:Bank -30 CNY\n",
+                "  Expenses:Food 30 CNY\n",
+            ),
+        )
+        .unwrap();
+        let ledger = crate::load_local_ledger(folder.path().to_path_buf(), "main.zhang".to_owned(), None).unwrap();
+        let transaction_id = ledger.operations().read().transactions.keys().next().cloned().unwrap();
+        let ledgers = Arc::new(IndexMap::from([(DEFAULT_LEDGER_NAME.to_owned(), Arc::new(RwLock::new(ledger)))]));
+        let app = create_server_app(ledgers, Broadcaster::create(), Arc::new(ReloadSender::default()), None);
+
+        let upload = |fields: Vec<(&str, &str)>| {
+            let body = fields
+                .into_iter()
+                .map(|(name, value)| match name.strip_prefix("file:") {
+                    Some(file_name) => format!(
+                        "--boundary\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: text/plain\r\n\r\n{}\r\n",
+                        file_name, value
+                    ),
+                    None => format!("--boundary\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", name, value),
+                })
+                .collect::<String>();
+            let request = Request::builder()
+                .method(http::Method::POST)
+                .uri("/api/documents")
+                .header(http::header::CONTENT_TYPE, "multipart/form-data; boundary=boundary")
+                .body(Body::from(format!("{}--boundary--\r\n", body)))
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+
+        let response = upload(vec![("account", "Assets:Bank"), ("date", "2024-01-20"), ("file:../statement.txt", "statement")])
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(
+            "statement",
+            std::fs::read_to_string(folder.path().join("documents/2024/01/20-statement.txt")).unwrap()
+        );
+        let content = std::fs::read_to_string(&main).unwrap();
+        let span_start = body["data"][0]["span_start"].as_u64().unwrap() as usize;
+        assert!(content[span_start..].starts_with("2024-01-20 document Assets:Bank \"documents/2024/01/20-statement.txt\""));
+
+        // the document of same name on the same day is kept, the new one is suffixed
+        let response = upload(vec![
+            ("account", "Assets:Bank"),
+            ("date", "2024-01-20"),
+            ("file:statement.txt", "another statement"),
+        ])
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!(
+            "statement",
+            std::fs::read_to_string(folder.path().join("documents/2024/01/20-statement.txt")).unwrap()
+        );
+        assert_eq!(
+            "another statement",
+            std::fs::read_to_string(folder.path().join("documents/2024/01/20-statement-1.txt")).unwrap()
+        );
+
+        let response = upload(vec![("transaction", &transaction_id.to_string()), ("file:receipt.txt", "receipt")])
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(transaction_id.to_string(), body["data"][0]["id"]);
+        let content = std::fs::read_to_string(&main).unwrap();
+        let span_end = body["data"][0]["span_end"].as_u64().unwrap() as usize;
+        assert!(content[..span_end].ends_with("  Expenses:Food 30 CNY\n  document: \"documents/2024/01/15-receipt.txt\""));
+
+        let response = upload(vec![("account", "Assets:Unknown"), ("file:receipt.txt", "receipt")]).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        let response = upload(vec![("file:receipt.txt", "receipt")]).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn should_authorize_requests_by_role_of_token() {
+        let folder = tempfile::tempdir().unwrap();
+        let main = folder.path().join("main.zhang");
+        std::fs::write(
+            &main,
+            "option \"insert_strategy\" \"single\"\n1970-01-01 open Assets:Bank\n1970-01-01 open Expenses:Food\n",
+        )
+        .unwrap();
+        let ledger = crate::load_local_ledger(folder.path().to_path_buf(), "main.zhang".to_owned(), None).unwrap();
+        let ledgers = Arc::new(IndexMap::from([(DEFAULT_LEDGER_NAME.to_owned(), Arc::new(RwLock::new(ledger)))]));
+        let authenticator = Authenticator::default()
+            .with_token("alice:read-only:reader-token")
+            .and_then(|it| it.with_token("bob:write:writer-token"))
+            .unwrap();
+        let app = create_server_app(ledgers, Broadcaster::create(), Arc::new(ReloadSender::default()), Some(authenticator));
+
+        let request = |method: http::Method, uri: &str, authorization: Option<&str>, body: Option<Value>| {
+            let mut builder = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref());
+            if let Some(authorization) = authorization {
+                builder = builder.header(http::header::AUTHORIZATION, authorization);
+            }
+            let body = body.map(|it| Body::from(it.to_string())).unwrap_or_else(Body::empty);
+            app.clone().oneshot(builder.body(body).unwrap())
+        };
+        let transaction = serde_json::json!({
+            "datetime": "2024-01-15T12:00:00Z",
+            "payee": "KFC",
+            "postings": [
+                {"account": "Assets:Bank", "unit": {"number": "-30", "commodity": "CNY"}},
+                {"account": "Expenses:Food"}
+            ],
+            "metas": [],
+            "tags": [],
+            "links": []
+        });
+
+        let response = request(http::Method::GET, "/api/accounts", None, None).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+        assert!(response.headers().contains_key(http::header::WWW_AUTHENTICATE));
+        let response = request(http::Method::GET, "/api/accounts", Some("Bearer unknown-token"), None).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+        let response = request(http::Method::GET, "/api/auth/user", Some("Bearer reader-token"), None).await.unwrap();
+        let body: Value = serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(serde_json::json!({"name": "alice", "role": "read-only"}), body["data"]);
+        let response = request(http::Method::POST, "/api/transactions", Some("Bearer reader-token"), Some(transaction.clone()))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+        // the name and token of user are accepted as the basic credential, so that the browser can prompt for them
+        let response = request(
+            http::Method::POST,
+            "/api/transactions",
+            Some("Basic Ym9iOndyaXRlci10b2tlbg=="),
+            Some(transaction),
+        )
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        assert!(std::fs::read_to_string(&main).unwrap().contains("  created-by: \"bob\""));
+
+        let response = request(
+            http::Method::PUT,
+            "/api/files/bWFpbi56aGFuZw==",
+            Some("Bearer writer-token"),
+            Some(serde_json::json!({"content": ""})),
+        )
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::FORBIDDEN, response.status());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn should_keep_other_date_of_transaction_when_updating() {
+        let folder = tempfile::tempdir().unwrap();
+        let main = folder.path().join("main.zhang");
+        std::fs::write(
+            &main,
+            concat!(
+                "option \"timezone\" \"UTC\"\n",
+                "option \"transaction_date\" \"auxiliary\"\n",
+                "1970-01-01 open Assets:Bank\n",
+                "1970-01-01 open Expenses:Food\n",
+                "2024-01-10=2024-01-12 \"KFC\"\n",
+                "  Assets:Bank -30 CNY\n",
+                "  Expenses:Food 30 CNY\n",
+            ),
+        )
+        .unwrap();
+        let ledger = crate::load_local_ledger(folder.path().to_path_buf(), "main.zhang".to_owned(), None).unwrap();
+        let transaction_id = ledger.operations().read().transactions.keys().next().cloned().unwrap();
+        let ledgers = Arc::new(IndexMap::from([(DEFAULT_LEDGER_NAME.to_owned(), Arc::new(RwLock::new(ledger)))]));
+        let app = create_server_app(ledgers, Broadcaster::create(), Arc::new(ReloadSender::default()), None);
+
+        let transaction = serde_json::json!({
+            "datetime": "2024-01-20T12:00:00Z",
+            "payee": "KFC",
+            "postings": [
+                {"account": "Assets:Bank", "unit": {"number": "-30", "commodity": "CNY"}},
+                {"account": "Expenses:Food", "unit": {"number": "30", "commodity": "CNY"}}
+            ],
+            "metas": [],
+            "tags": [],
+            "links": []
+        });
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::PUT)
+                    .uri(format!("/api/transactions/{}", transaction_id))
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(transaction.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        assert!(std::fs::read_to_string(&main).unwrap().contains("2024-01-10=2024-01-20 12:00:00 * \"KFC\""));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn should_serve_tax_report_per_period() {
+        let folder = tempfile::tempdir().unwrap();
+        std::fs::write(
+            folder.path().join("main.zhang"),
+            concat!(
+                "option \"timezone\" \"UTC\"\n",
+                "1970-01-01 open Assets:Bank\n",
+                "1970-01-01 open Liabilities:Tax:VAT\n",
+                "1970-01-01 open Expenses:Office\n",
+                "  tax-rate: \"13%\"\n",
+                "  tax-account: \"Liabilities:Tax:VAT\"\n",
+                "2024-01-05 \"Paper\"\n",
+                "  Expenses:Office 113 CNY\n",
+                "  Assets:Bank\n",
+                "2024-02-10 \"Printer\"\n",
+                "  Expenses:Office 226 CNY\n",
+                "  Assets:Bank\n",
+            ),
+        )
+        .unwrap();
+        let ledger = crate::load_local_ledger(folder.path().to_path_buf(), "main.zhang".to_owned(), None).unwrap();
+        let ledgers = Arc::new(IndexMap::from([(DEFAULT_LEDGER_NAME.to_owned(), Arc::new(RwLock::new(ledger)))]));
+        let app = create_server_app(ledgers, Broadcaster::create(), Arc::new(ReloadSender::default()), None);
+        let request = |uri: &str| app.clone().oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap());
+
+        let response = request("/api/statistic/tax?from=2024-01-01T00:00:00Z&to=2024-12-31T23:59:59Z&interval=quarter")
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        let body: Value = serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(
+            serde_json::json!([{
+                "period": "2024-01-01",
+                "tax_account": "Liabilities:Tax:VAT",
+                "commodity": "CNY",
+                "collected": "0",
+                "paid": "39.00",
+                "payable": "-39.00",
+            }]),
+            body["data"]
+        );
+
+        let response = request("/api/statistic/tax?from=2024-01-01T00:00:00Z&to=2024-12-31T23:59:59Z").await.unwrap();
+        let body: Value = serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(2, body["data"].as_array().unwrap().len());
+
+        let response = request("/api/statistic/tax?from=2024-01-01T00:00:00Z&to=2024-12-31T23:59:59Z&interval=decade")
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+
+    #[test]
+    fn should_check_ledger_with_severity_threshold_and_ignored_kinds() {
+        let folder = tempfile::tempdir().unwrap();
+        std::fs::write(
+            folder.path().join("main.zhang"),
+            concat!(
+                "1970-01-01 open Assets:Bank\n",
+                "1970-01-01 open Expenses:Food\n",
+                "1970-01-02 \"Payee\" \"Narration\"\n",
+                "  Assets:Bank -1 CNY\n",
+                "  Expenses:Food 2 CNY\n",
+            ),
+        )
+        .unwrap();
+        let path = folder.path().to_str().unwrap();
+        let check = |args: &[&str]| match crate::Opts::parse_from(["zhang", "check", path].iter().chain(args)) {
+            crate::Opts::Check(opts) => crate::check(opts).unwrap(),
+            _ => unreachable!(),
+        };
+
+        assert_eq!(1, check(&[]));
+        assert_eq!(1, check(&["--severity", "warning", "--format", "json"]));
+        assert_eq!(0, check(&["--ignore", "UnbalancedTransaction"]));
+        assert_eq!(0, check(&["--ignore", "DuplicatedTransaction", "--ignore", "UnbalancedTransaction"]));
+        assert!(crate::Opts::try_parse_from(["zhang", "check", path, "--ignore", "NoSuchKind"]).is_err());
+        assert!(crate::Opts::try_parse_from(["zhang", "check", path, "--severity", "fatal"]).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn should_add_transaction_by_wizard_with_completion() {
+        let folder = tempfile::tempdir().unwrap();
+        let main = folder.path().join("main.zhang");
+        std::fs::write(
+            &main,
+            concat!(
+                "option \"insert_strategy\" \"single\"\n",
+                "1970-01-01 open Assets:Bank\n",
+                "1970-01-01 open Expenses:Coffee\n",
+                "1970-01-01 open Expenses:Food\n",
+                "2024-05-01 \"Starbucks\" \"Americano\"\n",
+                "  Expenses:Coffee 20 CNY\n",
+                "  Assets:Bank -20 CNY\n",
+            ),
+        )
+        .unwrap();
+        let ledger = crate::load_local_ledger(folder.path().to_path_buf(), "main.zhang".to_owned(), None).unwrap();
+
+        let answers = ["2024-05-02", "star", "1", "Latte", "coff", "25", "", "bank", "", "", "y"].join("\n");
+        let mut output = vec![];
+        let mut prompt = Prompt::new(answers.as_bytes(), &mut output);
+        let file = crate::wizard::add(&ledger, &mut prompt).await.unwrap();
+        assert_eq!(Some(main.display().to_string()), file);
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("  1) Starbucks"));
+        assert!(output.contains("  -> Expenses:Coffee"));
+        assert!(output.contains("  unbalanced by 25 CNY"));
+        assert!(output.contains("  a transaction needs two postings at least"));
+        assert!(output.contains("amount [-25 CNY]: "));
+
+        let reloaded = crate::load_local_ledger(folder.path().to_path_buf(), "main.zhang".to_owned(), None).unwrap();
+        let mut operations = reloaded.operations();
+        assert!(operations.errors().unwrap().is_empty());
+        let content = std::fs::read_to_string(&main).unwrap();
+        assert!(content.contains("2024-05-02 * \"Starbucks\" \"Latte\""), "{}", content);
+        assert!(content.contains("Assets:Bank -25 CNY"), "{}", content);
+        assert!(crate::wizard::add(&reloaded, &mut Prompt::new("2024-05-03\n".as_bytes(), vec![]))
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn should_review_imported_transactions_with_duplicates_and_chosen_accounts() {
+        let folder = tempfile::tempdir().unwrap();
+        std::fs::write(
+            folder.path().join("main.zhang"),
+            concat!(
+                "1970-01-01 open Assets:Bank\n",
+                "1970-01-01 open Expenses:Coffee\n",
+                "1970-01-01 open Expenses:Food\n",
+                "1970-01-01 open Expenses:Uncategorized\n",
+                "2024-01-02 \"Starbucks\" \"latte\"\n",
+                "  Assets:Bank -35 CNY\n",
+                "  Expenses:Coffee 35 CNY\n",
+            ),
+        )
+        .unwrap();
+        let ledger = crate::load_local_ledger(folder.path().to_path_buf(), "main.zhang".to_owned(), None).unwrap();
+        let importer = Importer::from_toml(concat!(
+            "[csv.bank]\n",
+            "account = \"Assets:Bank\"\n",
+            "commodity = \"CNY\"\n",
+            "date = \"Date\"\n",
+            "payee = \"Payee\"\n",
+            "amount = \"Amount\"\n",
+        ))
+        .unwrap();
+        let statement = importer
+            .csv_statement(
+                None,
+                "Date,Payee,Amount\n2024-01-02,Starbucks,-35.00\n2024-01-06,KFC,-50\n2024-01-07,Unknown,-10\n",
+            )
+            .unwrap();
+        let directives = importer.directives(statement);
+        let (_, duplicates) = importer.exclude_duplicates(&ledger, directives.clone());
+        assert_eq!(1, duplicates.len());
+
+        let mut output = vec![];
+        let mut prompt = Prompt::new("food\n-\n".as_bytes(), &mut output);
+        let accepted = review_import(&ledger, &mut prompt, directives.clone(), &duplicates, false, true).unwrap();
+        let accounts = accepted
+            .iter()
+            .filter_map(|it| match it {
+                Directive::Transaction(trx) => Some(trx.postings[1].account.name().to_owned()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["Expenses:Food"], accounts);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("~ 2024-01-02 ! \"Starbucks\""), "{}", output);
+        assert!(output.contains("  probable duplicate of a transaction in ledger, skipped"));
+        assert!(output.contains("? 2024-01-06 ! \"KFC\""));
+        assert!(output.contains("account [Expenses:Uncategorized] (- to skip): "));
+        assert!(output.contains("  skipped"));
+
+        let mut output = vec![];
+        let accepted = review_import(&ledger, &mut Prompt::new("".as_bytes(), &mut output), directives, &duplicates, true, false).unwrap();
+        assert_eq!(3, accepted.len());
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("~ 2024-01-02 ! \"Starbucks\""));
+        assert!(output.contains("+ 2024-01-07 ! \"Unknown\""));
+    }
+
+    #[test]
+    fn should_run_queries_in_shell_with_completion() {
+        let folder = tempfile::tempdir().unwrap();
+        std::fs::write(
+            folder.path().join("main.zhang"),
+            concat!(
+                "1970-01-01 open Assets:Bank\n",
+                "1970-01-01 open Expenses:Coffee\n",
+                "2024-01-02 \"Starbucks\" \"latte\"\n",
+                "  Assets:Bank -35 CNY\n",
+                "  Expenses:Coffee 35 CNY\n",
+            ),
+        )
+        .unwrap();
+        let ledger = crate::load_local_ledger(folder.path().to_path_buf(), "main.zhang".to_owned(), None).unwrap();
+
+        let mut output = vec![];
+        let input = "SELECT account, sum(amount) AS total GROUP BY account ORDER BY account;\n\nSELECT nothing\nexit\nSELECT payee\n";
+        crate::repl::run(&ledger, &mut PlainEditor(input.as_bytes()), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Assets:Bank"), "{}", output);
+        assert!(output.contains("Expenses:Coffee"));
+        assert!(output.contains("(2 rows)"));
+        assert_eq!(1, output.lines().filter(|it| it.starts_with("error: ")).count(), "{}", output);
+        assert!(!output.contains("Starbucks"));
+
+        let completions = Completions::new(vec!["Assets:Bank".to_owned(), "Expenses:Coffee".to_owned()]);
+        assert_eq!("SELECT", completions.complete("sel").0);
+        assert_eq!(
+            "SELECT account WHERE account = \"Expenses:Coffee",
+            completions.complete("SELECT account WHERE account = \"Exp").0
+        );
+        let (completed, candidates) = completions.complete("SELECT a");
+        assert_eq!("SELECT a", completed);
+        assert!(candidates.contains(&"account".to_owned()));
+        assert!(candidates.contains(&"AND".to_owned()));
+    }
+
+    #[test]
+    fn should_append_closing_and_write_opening_balances() {
+        let folder = tempfile::tempdir().unwrap();
+        std::fs::write(
+            folder.path().join("main.zhang"),
+            concat!(
+                "option \"insert_strategy\" \"single\"\n",
+                "1970-01-01 open Assets:Bank\n",
+                "1970-01-01 open Income:Salary\n",
+                "2024-03-01 \"Company\" \"salary\"\n",
+                "  Income:Salary -1000 CNY\n",
+                "  Assets:Bank 1000 CNY\n",
+            ),
+        )
+        .unwrap();
+        let path = folder.path().to_str().unwrap();
+        match crate::Opts::parse_from(["zhang", "close-period", path, "2024-12-31", "--opening", "2025.zhang"]) {
+            crate::Opts::ClosePeriod(opts) => crate::close_period(opts).unwrap(),
+            _ => unreachable!(),
+        }
+
+        let main = std::fs::read_to_string(folder.path().join("main.zhang")).unwrap();
+        assert!(main.contains("2024-12-31 open Equity:Retained-Earnings"), "{}", main);
+        assert!(main.contains("  Income:Salary 1000 CNY\n  Equity:Retained-Earnings -1000 CNY"));
+        let opening = std::fs::read_to_string(folder.path().join("2025.zhang")).unwrap();
+        assert!(opening.contains("2025-01-01 open Assets:Bank\n"), "{}", opening);
+        assert!(opening.contains("  Assets:Bank 1000 CNY\n  Equity:Retained-Earnings -1000 CNY\n"));
+
+        let ledger = crate::load_local_ledger(folder.path().to_path_buf(), "main.zhang".to_owned(), None).unwrap();
+        assert!(ledger.operations().errors().unwrap().is_empty());
+
+        // the existing file of opening balances is kept, and nothing is appended
+        match crate::Opts::parse_from(["zhang", "close-period", path, "2025-12-31", "--opening", "2025.zhang"]) {
+            crate::Opts::ClosePeriod(opts) => assert!(crate::close_period(opts).is_err()),
+            _ => unreachable!(),
+        }
+        assert_eq!(opening, std::fs::read_to_string(folder.path().join("2025.zhang")).unwrap());
+        assert_eq!(main, std::fs::read_to_string(folder.path().join("main.zhang")).unwrap());
+    }
+
+    #[test]
+    fn should_append_transaction_instantiated_from_template() {
+        let folder = tempfile::tempdir().unwrap();
+        std::fs::write(
+            folder.path().join("main.zhang"),
+            concat!(
+                "option \"insert_strategy\" \"single\"\n",
+                "1970-01-01 open Assets:Bank\n",
+                "1970-01-01 open Assets:Receivable:Roommate\n",
+                "1970-01-01 open Expenses:Rent\n",
+                "2024-01-01 template rent \"Landlord\" \"Monthly rent\"\n",
+                "  Assets:Bank -{total} CNY\n",
+                "  Assets:Receivable:Roommate {total} / 2 CNY\n",
+                "  Expenses:Rent\n",
+            ),
+        )
+        .unwrap();
+        let path = folder.path().to_str().unwrap();
+        let run = |args: &[&str]| match crate::Opts::parse_from(["zhang", "template", path].iter().chain(args)) {
+            crate::Opts::Template(opts) => crate::instantiate_template(opts),
+            _ => unreachable!(),
+        };
+        assert!(run(&["rent", "--date", "2024-02-01"]).unwrap_err().to_string().contains("{total} is missing"));
+        run(&["rent", "total=3000", "--date", "2024-02-01"]).unwrap();
+
+        let main = std::fs::read_to_string(folder.path().join("main.zhang")).unwrap();
+        assert!(
+            main.contains("2024-02-01 * \"Landlord\" \"Monthly rent\"\n  Assets:Bank -3000 CNY\n  Assets:Receivable:Roommate 1500 CNY\n  Expenses:Rent"),
+            "{}",
+            main
+        );
+        let ledger = crate::load_local_ledger(folder.path().to_path_buf(), "main.zhang".to_owned(), None).unwrap();
+        assert!(ledger.operations().errors().unwrap().is_empty());
+    }
+
+    #[test]
+    fn should_export_text_merging_included_files_by_default() {
+        let folder = tempfile::tempdir().unwrap();
+        std::fs::write(
+            folder.path().join("main.zhang"),
+            concat!(
+                "include \"accounts.zhang\"\n",
+                "2024-01-01 \"KFC\" \"lunch\"\n",
+                "  Assets:Bank -20 CNY\n",
+                "  Expenses:Food\n",
+            ),
+        )
+        .unwrap();
+        std::fs::write(
+            folder.path().join("accounts.zhang"),
+            "1970-01-01 open Assets:Bank\n1970-01-01 open Expenses:Food\n",
+        )
+        .unwrap();
+        let path = folder.path().to_str().unwrap();
+        let output = folder.path().join("merged.zhang");
+        match crate::Opts::parse_from(["zhang", "export", path, "-o", output.to_str().unwrap()]) {
+            crate::Opts::Export(opts) => assert_eq!(output, crate::export(opts).unwrap()),
+            _ => unreachable!(),
+        }
+
+        let merged = std::fs::read_to_string(&output).unwrap();
+        assert!(!merged.contains("include"), "{}", merged);
+        let ledger = crate::load_local_ledger(folder.path().to_path_buf(), "merged.zhang".to_owned(), None).unwrap();
+        assert!(ledger.operations().errors().unwrap().is_empty());
+        assert_eq!(1, ledger.operations().read().transactions.len());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn should_reject_include_outside_of_ledger_folder_by_opendal() {
+        let folder = tempfile::tempdir().unwrap();
+        std::fs::write(folder.path().join("main.zhang"), "include \"/tmp/**/*.zhang\"\n").unwrap();
+        let data_source = OpendalDataSource::from_env(
+            FileSystem::Fs,
+            &mut ServerOpts {
+                path: folder.path().to_path_buf(),
+                endpoint: "main.zhang".to_owned(),
+                addr: "".to_string(),
+                port: 0,
+                auth: None,
+                auth_tokens: vec![],
+                source: None,
+                no_report: false,
+                no_cache: true,
+                profile: None,
+                git: false,
+                ledgers: vec![],
+            },
+        )
+        .await;
+        let result = Ledger::async_load(folder.path().to_path_buf(), "main.zhang".to_owned(), Arc::new(data_source)).await;
+        assert!(matches!(result, Err(zhang_core::ZhangError::CustomError(msg)) if msg.contains("outside of the ledger folder")));
+    }
+
+    #[test]
+    fn should_key_remote_cache_folder_by_location_and_entry() {
+        use crate::opendal::remote_cache_folder;
+
+        let folder = remote_cache_folder("webdav:https://a.example.com:/ledger", "main.zhang");
+        assert!(folder.starts_with(".zhang-cache/remote"));
+        assert_eq!(folder, remote_cache_folder("webdav:https://a.example.com:/ledger", "main.zhang"));
+        assert_ne!(folder, remote_cache_folder("webdav:https://b.example.com:/ledger", "main.zhang"));
+        assert_ne!(folder, remote_cache_folder("webdav:https://a.example.com:/ledger", "other.zhang"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn should_serve_and_write_back_gpg_encrypted_file_by_opendal() {
+        use zhang_core::ast::ZhangString;
+        use zhang_core::data_source::DataSource;
+        use zhang_core::encryption::{Encryption, EncryptionKeys};
+
+        // the test is skipped if gpg is not installed
+        if std::process::Command::new("gpg").arg("--version").output().is_err() {
+            return;
+        }
+        let gpg_home = tempfile::tempdir().unwrap();
+        let status = std::process::Command::new("gpg")
+            .arg("--homedir")
+            .arg(gpg_home.path())
+            .args([
+                "--batch",
+                "--quiet",
+                "--passphrase",
+                "",
+                "--quick-gen-key",
+                "zhang <zhang@localhost>",
+                "default",
+                "default",
+                "never",
+            ])
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let folder = tempfile::tempdir().unwrap();
+        std::fs::write(
+            folder.path().join("main.zhang"),
+            format!(
+                "option \"encryption_gpg_home\" \"{}\"\noption \"encryption_recipient\" \"zhang@localhost\"\ninclude \"secret.zhang.gpg\"\n",
+                gpg_home.path().display()
+            ),
+        )
+        .unwrap();
+        let keys = EncryptionKeys {
+            identity: None,
+            recipients: vec!["zhang@localhost".to_owned()],
+            gpg_home: Some(gpg_home.path().to_path_buf()),
+        };
+        let encrypted = keys.encrypt(Encryption::Gpg { armor: false }, b"option \"title\" \"secret ledger\"\n").unwrap();
+        std::fs::write(folder.path().join("secret.zhang.gpg"), encrypted).unwrap();
+
+        let data_source = OpendalDataSource::from_env(
+            FileSystem::Fs,
+            &mut ServerOpts {
+                path: folder.path().to_path_buf(),
+                endpoint: "main.zhang".to_owned(),
+                addr: "".to_string(),
+                port: 0,
+                auth: None,
+                auth_tokens: vec![],
+                source: None,
+                no_report: false,
+                no_cache: false,
+                profile: None,
+                git: false,
+                ledgers: vec![],
+            },
+        )
+        .await;
+        let data_source = Arc::new(data_source);
+        let ledger = Ledger::async_load(folder.path().to_path_buf(), "main.zhang".to_owned(), data_source.clone())
+            .await
+            .unwrap();
+        let title = ledger
+            .metas
+            .iter()
+            .find(|it| matches!(&it.data, Directive::Option(option) if option.key.as_str() == "title"))
+            .unwrap();
+        let Directive::Option(mut option) = title.data.clone() else { unreachable!() };
+        assert_eq!("secret ledger", option.value.as_str());
+        option.value = ZhangString::QuoteString("new title".to_owned());
+        data_source
+            .async_update_directive(&ledger, &title.span, Directive::Option(option))
+            .await
+            .unwrap();
+
+        let written = std::fs::read(folder.path().join("secret.zhang.gpg")).unwrap();
+        assert!(!String::from_utf8_lossy(&written).contains("new title"));
+        let decrypted = keys.decrypt(Encryption::Gpg { armor: false }, &folder.path().join("secret.zhang.gpg")).unwrap();
+        assert_eq!("option \"title\" \"new title\"\n", String::from_utf8(decrypted).unwrap());
+        // the plain directives of encrypted file are not cached
+        let cache_folder = folder.path().join(zhang_core::directive_cache::DEFAULT_DIRECTIVE_CACHE_FOLDER);
+        for version in std::fs::read_dir(cache_folder).unwrap() {
+            for cached in std::fs::read_dir(version.unwrap().path()).unwrap() {
+                assert!(!String::from_utf8_lossy(&std::fs::read(cached.unwrap().path()).unwrap()).contains("secret ledger"));
+            }
+        }
+
+        // the encrypted file is left untouched by formatting
+        let opts = crate::FmtOpts {
+            path: folder.path().to_path_buf(),
+            endpoint: "main.zhang".to_owned(),
+            check: false,
+        };
+        assert!(crate::fmt(opts).unwrap().is_empty());
+        assert_eq!(written, std::fs::read(folder.path().join("secret.zhang.gpg")).unwrap());
+    }
 }
@@ -1,27 +1,46 @@
-use std::collections::VecDeque;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::RwLock;
 
 use async_recursion::async_recursion;
 use beancount::Beancount;
-use log::{debug, info};
-use opendal::services::{Fs, Github, Webdav};
+use futures::future::try_join_all;
+use journal::Journal;
+use log::{debug, error, info};
+use opendal::services::{Fs, Github, Webdav, S3};
 use opendal::{ErrorKind, Operator};
+use rayon::prelude::*;
 use zhang_ast::{Directive, Include, SpanInfo, Spanned, ZhangString};
-use zhang_core::data_source::{DataSource, LoadResult};
-use zhang_core::data_type::text::parser::parse as zhang_parse;
+use zhang_core::data_source::{DataSource, LoadResult, ProfileSelector};
+use zhang_core::data_type::text::parser::parse_recovering as zhang_parse;
 use zhang_core::data_type::text::ZhangDataType;
 use zhang_core::data_type::DataType;
+use zhang_core::directive_cache::{DirectiveCache, DEFAULT_DIRECTIVE_CACHE_FOLDER};
+use zhang_core::encryption::{Encryption, EncryptionKeys};
 use zhang_core::ledger::Ledger;
+use zhang_core::store_snapshot::{StoreSnapshot, DEFAULT_STORE_SNAPSHOT_FILE};
 use zhang_core::utils::has_path_visited;
 use zhang_core::{utils, ZhangError, ZhangResult};
 
 use crate::{FileSystem, ServerOpts};
 
+/// the folder of caches of remote ledgers in the working directory
+const REMOTE_CACHE_FOLDER: &str = ".zhang-cache/remote";
+
+/// the cache folder of remote ledger, which is keyed by the hash of its location and entry
+pub(crate) fn remote_cache_folder(location: &str, endpoint: &str) -> PathBuf {
+    PathBuf::from(REMOTE_CACHE_FOLDER).join(sha256::digest(format!("{}#{}", location, endpoint)))
+}
+
 pub struct OpendalDataSource {
     operator: Operator,
     data_type: Box<dyn DataType<Carrier = String> + 'static + Send + Sync>,
-    is_beancount: bool,
+    is_zhang: bool,
+    directive_cache: Option<DirectiveCache>,
+    store_snapshot: Option<StoreSnapshot>,
+    profile: Option<String>,
+    /// the keys declared by the files loaded, which decrypt and encrypt the files of `.age`, `.gpg` and `.asc`
+    encryption_keys: RwLock<EncryptionKeys>,
 }
 
 #[async_trait::async_trait]
@@ -30,55 +49,91 @@ impl DataSource for OpendalDataSource {
         Ok(self.data_type.export(Spanned::new(directive, SpanInfo::default())).into_bytes())
     }
 
+    fn store_snapshot(&self) -> Option<&StoreSnapshot> {
+        self.store_snapshot.as_ref()
+    }
+
     fn get(&self, path: String) -> ZhangResult<Vec<u8>> {
-        self.operator
+        let content = self
+            .operator
             .blocking()
             .read(path.as_str())
             .map(|data| data.to_vec())
-            .map_err(|e| ZhangError::CustomError(format!("fail to get file content [{}] : {}", path, e)))
+            .map_err(|e| ZhangError::CustomError(format!("fail to get file content [{}] : {}", path, e)))?;
+        self.decrypt(&path, content)
     }
 
     async fn async_load(&self, entry: String, endpoint: String) -> ZhangResult<LoadResult> {
         let entry = PathBuf::from(entry);
         let main_endpoint = entry.join(endpoint);
 
-        let mut load_queue: VecDeque<PathBuf> = VecDeque::new();
-        load_queue.push_back(main_endpoint);
+        // files are loaded level by level of the include tree, the files of the same level are fetched concurrently and parsed in parallel,
+        // the directives are merged in the same order as loading them one by one
+        let mut load_level: Vec<PathBuf> = vec![main_endpoint];
 
         let mut visited: Vec<PathBuf> = Vec::new();
-        let mut directives = vec![];
-        while let Some(pathbuf) = load_queue.pop_front() {
-            let striped_pathbuf = &pathbuf.strip_prefix(&entry).expect("Cannot strip entry").to_path_buf();
-            debug!("visited entry file: {:?}", striped_pathbuf.display());
-
-            if utils::has_path_visited(&visited, &pathbuf) {
-                continue;
+        let mut runs = vec![];
+        let mut profile = ProfileSelector::new(self.profile.clone());
+        *self.encryption_keys.write().unwrap() = EncryptionKeys::default();
+        while !load_level.is_empty() {
+            let mut files: Vec<PathBuf> = Vec::with_capacity(load_level.len());
+            for pathbuf in load_level {
+                if !utils::has_path_visited(&visited, &pathbuf) && !utils::has_path_visited(&files, &pathbuf) {
+                    files.push(pathbuf);
+                }
             }
-            let file_content = self.get_file_content(striped_pathbuf.clone()).await?;
-            let entity_directives = self.parse(&file_content, striped_pathbuf.clone())?;
+            let striped_files = files
+                .iter()
+                .map(|pathbuf| {
+                    pathbuf
+                        .strip_prefix(&entry)
+                        .map(|it| it.to_path_buf())
+                        .map_err(|_| ZhangError::CustomError(format!("included file [{}] is outside of the ledger folder", pathbuf.display())))
+                })
+                .collect::<ZhangResult<Vec<_>>>()?;
 
-            entity_directives.iter().filter_map(|directive| self.go_next(directive)).for_each(|buf| {
-                let fullpath = if buf.starts_with('/') {
-                    PathBuf::from_str(&buf).unwrap()
-                } else {
-                    pathbuf.parent().map(|it| it.join(buf)).unwrap()
-                };
-                load_queue.push_back(fullpath);
-            });
-            directives.extend(entity_directives);
-            visited.push(pathbuf);
+            let file_contents = try_join_all(striped_files.iter().map(|striped_pathbuf| {
+                debug!("visited entry file: {:?}", striped_pathbuf.display());
+                self.get_file_content(striped_pathbuf.clone())
+            }))
+            .await?;
+            let parsed_files = striped_files
+                .into_par_iter()
+                .zip(file_contents)
+                .map(|(striped_pathbuf, file_content)| self.parse(&file_content, striped_pathbuf))
+                .collect::<ZhangResult<Vec<_>>>()?;
+
+            load_level = vec![];
+            for (pathbuf, entity_directives) in files.into_iter().zip(parsed_files) {
+                let folder = pathbuf.parent().expect("file should have parent folder");
+                {
+                    let mut encryption_keys = self.encryption_keys.write().unwrap();
+                    entity_directives.iter().for_each(|directive| encryption_keys.handle_option(directive));
+                }
+                for buf in entity_directives.iter().filter_map(|directive| profile.next_include(directive)) {
+                    if utils::is_glob_pattern(&buf) {
+                        load_level.extend(self.expand_include_pattern(&entry, folder, &buf).await?);
+                    } else if buf.starts_with('/') {
+                        load_level.push(PathBuf::from_str(&buf).unwrap());
+                    } else {
+                        load_level.push(folder.join(buf));
+                    }
+                }
+                runs.push(self.transform(entity_directives)?);
+                visited.push(pathbuf);
+            }
         }
-        Ok(LoadResult {
-            directives: self.transform(directives)?,
-            visited_files: visited,
-        })
+        if let Some(directive_cache) = &self.directive_cache {
+            directive_cache.prune_unused();
+        }
+        Ok(LoadResult { runs, visited_files: visited })
     }
 
     async fn async_get(&self, path: String) -> ZhangResult<Vec<u8>> {
         let path_for_read = path.to_owned();
         let result = self.operator.read(&path_for_read).await;
         match result {
-            Ok(data) => Ok(data.to_vec()),
+            Ok(data) => self.async_decrypt(&path, data.to_vec()).await,
             Err(err) => {
                 if err.kind() == ErrorKind::NotFound {
                     Ok(Vec::new())
@@ -89,16 +144,29 @@ impl DataSource for OpendalDataSource {
         }
     }
 
-    async fn async_append(&self, ledger: &Ledger, directives: Vec<Directive>) -> ZhangResult<()> {
+    async fn async_append(&self, ledger: &Ledger, directives: Vec<Directive>) -> ZhangResult<Vec<SpanInfo>> {
+        let mut spans = Vec::with_capacity(directives.len());
+        let mut visited_files = ledger.visited_files.clone();
         for directive in directives {
-            self.append_directive(ledger, directive, None, true).await?;
+            spans.push(self.append_directive(ledger, directive, None, Some(&mut visited_files)).await?);
         }
-        Ok(())
+        Ok(spans)
     }
 
+    /// the content of encrypted file is encrypted again by the keys declared
     async fn async_save(&self, _ledger: &Ledger, path: String, content: &[u8]) -> ZhangResult<()> {
         info!("[opendal] save content path={}", &path);
-        let vec = content.to_vec();
+        let vec = match Encryption::of(Path::new(&path)) {
+            Some(encryption) => {
+                let keys = self.encryption_keys.read().unwrap().clone();
+                let content = content.to_vec();
+                // the command may wait for the passphrase, so it is kept away from the async workers
+                tokio::task::spawn_blocking(move || keys.encrypt(encryption, &content))
+                    .await
+                    .map_err(|e| ZhangError::CustomError(format!("cannot encrypt {}: {}", path, e)))??
+            }
+            None => content.to_vec(),
+        };
 
         self.operator.write(&path, vec).await.expect("cannot write");
         Ok(())
@@ -107,24 +175,24 @@ impl DataSource for OpendalDataSource {
 
 impl OpendalDataSource {
     #[async_recursion]
-    async fn append_directive(&self, ledger: &Ledger, directive: Directive, file: Option<PathBuf>, check_file_visit: bool) -> ZhangResult<()> {
+    async fn append_directive(
+        &self, ledger: &Ledger, directive: Directive, file: Option<PathBuf>, visited_files: Option<&mut Vec<PathBuf>>,
+    ) -> ZhangResult<SpanInfo> {
         let (entry, main_file_endpoint) = &ledger.entry;
 
         let endpoint = if let Some(file) = file {
             file
-        } else if let Some(datetime) = directive.datetime() {
-            let folder = datetime.format("data/%Y/").to_string();
-
-            self.operator.create_dir(&folder).await.expect("cannot create dir");
-
-            let path = format!("data/{}.zhang", datetime.format("%Y/%m"));
-            entry.join(PathBuf::from(path))
+        } else if let Some(path) = ledger.options.insert_strategy.endpoint(&directive) {
+            if let Some(folder) = path.parent().filter(|it| !it.as_os_str().is_empty()) {
+                self.operator.create_dir(&format!("{}/", folder.display())).await.expect("cannot create dir");
+            }
+            entry.join(path)
         } else {
             entry.join(main_file_endpoint)
         };
         let striped_endpoint = endpoint.strip_prefix(entry).expect("cannot strip entry prefix");
 
-        if !has_path_visited(&ledger.visited_files, &endpoint) && check_file_visit {
+        if let Some(visited_files) = visited_files.filter(|visited_files| !has_path_visited(visited_files.iter(), &endpoint)) {
             let path = match endpoint.strip_prefix(entry) {
                 Ok(relative_path) => relative_path.to_str().unwrap(),
                 Err(_) => endpoint.to_str().unwrap(),
@@ -133,97 +201,191 @@ impl OpendalDataSource {
                 ledger,
                 Directive::Include(Include {
                     file: ZhangString::QuoteString(path.to_string()),
+                    profile: None,
                 }),
                 None,
-                false,
+                None,
             )
             .await?;
+            visited_files.push(endpoint.clone());
         }
 
-        let content_buf = ledger.data_source.async_get(striped_endpoint.to_string_lossy().to_string()).await?;
+        let content_buf = self.async_get(striped_endpoint.to_string_lossy().to_string()).await?;
         let content = String::from_utf8(content_buf)?;
 
-        let appended_content = format!("{}\n{}\n", content, self.data_type.export(Spanned::new(directive, SpanInfo::default())));
+        let exported = self.data_type.export(Spanned::new(directive, SpanInfo::default()));
+        let appended_content = format!("{}\n{}\n", content, exported);
 
-        ledger
-            .data_source
-            .async_save(ledger, striped_endpoint.to_string_lossy().to_string(), appended_content.as_bytes())
+        self.async_save(ledger, striped_endpoint.to_string_lossy().to_string(), appended_content.as_bytes())
             .await?;
-        Ok(())
+        Ok(SpanInfo {
+            start: content.len() + 1,
+            end: content.len() + 1 + exported.len(),
+            content: exported,
+            filename: Some(striped_endpoint.to_path_buf()),
+            trivia: Default::default(),
+        })
     }
     pub async fn from_env(source: FileSystem, server_opts: &mut ServerOpts) -> OpendalDataSource {
-        let operator = match source {
+        // the location of remote ledger, which keys its cache
+        let (operator, location) = match source {
             FileSystem::Fs => {
                 let mut builder = Fs::default();
                 builder.root(server_opts.path.to_string_lossy().to_string().as_str());
                 // Operator::new(builder).unwrap().finish()
-                Operator::new(builder).unwrap().finish()
+                (Operator::new(builder).unwrap().finish(), None)
+            }
+            FileSystem::S3 => {
+                let mut builder = S3::default();
+                let bucket = std::env::var("ZHANG_S3_BUCKET").expect("ZHANG_S3_BUCKET must be set");
+                builder.bucket(&bucket);
+                let s3_endpoint = std::env::var("ZHANG_S3_ENDPOINT").ok();
+                if let Some(endpoint) = &s3_endpoint {
+                    builder.endpoint(endpoint);
+                }
+                if let Ok(region) = std::env::var("ZHANG_S3_REGION") {
+                    builder.region(&region);
+                }
+                builder.access_key_id(&std::env::var("ZHANG_S3_ACCESS_KEY_ID").expect("ZHANG_S3_ACCESS_KEY_ID must be set"));
+                builder.secret_access_key(&std::env::var("ZHANG_S3_SECRET_ACCESS_KEY").expect("ZHANG_S3_SECRET_ACCESS_KEY must be set"));
+                // the ledger path of command line is the root in bucket if it is not given by env
+                let s3_root = std::env::var("ZHANG_S3_ROOT").unwrap_or_else(|_| server_opts.path.to_string_lossy().to_string());
+                builder.root(&s3_root);
+                server_opts.path = PathBuf::from(&s3_root);
+                let location = format!("s3:{}:{}:{}", s3_endpoint.unwrap_or_default(), bucket, s3_root);
+                (Operator::new(builder).unwrap().finish(), Some(location))
             }
             FileSystem::WebDav => {
                 let mut webdav_builder = Webdav::default();
-                webdav_builder.endpoint(&std::env::var("ZHANG_WEBDAV_ENDPOINT").expect("ZHANG_WEBDAV_ENDPOINT must be set"));
+                let webdav_endpoint = std::env::var("ZHANG_WEBDAV_ENDPOINT").expect("ZHANG_WEBDAV_ENDPOINT must be set");
+                webdav_builder.endpoint(&webdav_endpoint);
                 let webdav_root = std::env::var("ZHANG_WEBDAV_ROOT").expect("ZHANG_WEBDAV_ROOT must be set");
                 webdav_builder.root(&webdav_root);
                 webdav_builder.username(std::env::var("ZHANG_WEBDAV_USERNAME").ok().as_deref().unwrap_or_default());
                 webdav_builder.password(std::env::var("ZHANG_WEBDAV_PASSWORD").ok().as_deref().unwrap_or_default());
                 server_opts.path = PathBuf::from(&webdav_root);
-                Operator::new(webdav_builder).unwrap().finish()
+                let location = format!("webdav:{}:{}", webdav_endpoint, webdav_root);
+                (Operator::new(webdav_builder).unwrap().finish(), Some(location))
             }
             FileSystem::Github => {
                 let mut builder = Github::default();
                 builder.root("/");
                 builder.token(&std::env::var("ZHANG_GITHUB_TOKEN").expect("ZHANG_GITHUB_TOKEN must be set"));
-                builder.owner(&std::env::var("ZHANG_GITHUB_USER").expect("ZHANG_GITHUB_USER must be set"));
-                builder.repo(&std::env::var("ZHANG_GITHUB_REPO").expect("ZHANG_GITHUB_REPO must be set"));
+                let owner = std::env::var("ZHANG_GITHUB_USER").expect("ZHANG_GITHUB_USER must be set");
+                let repo = std::env::var("ZHANG_GITHUB_REPO").expect("ZHANG_GITHUB_REPO must be set");
+                builder.owner(&owner);
+                builder.repo(&repo);
 
-                Operator::new(builder).unwrap().finish()
+                (Operator::new(builder).unwrap().finish(), Some(format!("github:{}/{}", owner, repo)))
+            }
+        };
+        // the data type of encrypted main file is the one of its plain name, e.g. `main.zhang.age`
+        let plain_endpoint = Encryption::plain_path(Path::new(&server_opts.endpoint));
+        let new_data_type: Box<dyn DataType<Carrier = String> + Send + Sync> =
+            match plain_endpoint.extension().unwrap_or_default().to_string_lossy().to_string().as_str() {
+                "bc" | "bean" => Box::new(Beancount {}),
+                "journal" | "ledger" | "hledger" => Box::new(Journal {}),
+                "zhang" => Box::new(ZhangDataType {}),
+                _ => unreachable!("not supported data format"),
+            };
+        let is_zhang = plain_endpoint.extension().map(|it| it == "zhang").unwrap_or(false);
+        // the cache of local ledger is kept in the ledger folder, the one of remote ledger is kept in the working directory,
+        // under the folder keyed by its location and entry, so that the remote ledgers never share their caches
+        let (cache_folder, snapshot_file) = match location {
+            None => (
+                server_opts.path.join(DEFAULT_DIRECTIVE_CACHE_FOLDER),
+                server_opts.path.join(DEFAULT_STORE_SNAPSHOT_FILE),
+            ),
+            Some(location) => {
+                let folder = remote_cache_folder(&location, &server_opts.endpoint);
+                (folder.join("directives"), folder.join("store.snapshot"))
             }
         };
-        let is_beancount = match PathBuf::from(&server_opts.endpoint)
-            .extension()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_string()
-            .as_str()
-        {
-            "bc" | "bean" => true,
-            "zhang" => false,
-            _ => unreachable!("not supported data format"),
+        let directive_cache = if server_opts.no_cache {
+            None
+        } else {
+            DirectiveCache::new(cache_folder)
+                .map_err(|e| error!("cannot create directive cache, directives will be parsed without cache: {}", e))
+                .ok()
         };
-        let new_data_type: Box<dyn DataType<Carrier = String> + Send + Sync> = if is_beancount { Box::new(Beancount {}) } else { Box::new(ZhangDataType {}) };
+        let store_snapshot = (!server_opts.no_cache).then(|| StoreSnapshot::new(snapshot_file));
         Self {
             operator,
             data_type: new_data_type,
-            is_beancount,
+            is_zhang,
+            directive_cache,
+            store_snapshot,
+            profile: server_opts.profile.clone(),
+            encryption_keys: RwLock::default(),
+        }
+    }
+
+    /// the content of encrypted file is decrypted by the keys declared, the missing file read as empty is kept as it is
+    fn decrypt(&self, path: &str, content: Vec<u8>) -> ZhangResult<Vec<u8>> {
+        match Encryption::of(Path::new(path)) {
+            Some(encryption) if !content.is_empty() => self.encryption_keys.read().unwrap().decrypt_content(encryption, &content),
+            _ => Ok(content),
         }
     }
 
+    /// the same as [OpendalDataSource::decrypt], but the command runs on the blocking threads since it may wait for the passphrase
+    async fn async_decrypt(&self, path: &str, content: Vec<u8>) -> ZhangResult<Vec<u8>> {
+        let Some(encryption) = Encryption::of(Path::new(path)).filter(|_| !content.is_empty()) else {
+            return Ok(content);
+        };
+        let keys = self.encryption_keys.read().unwrap().clone();
+        tokio::task::spawn_blocking(move || keys.decrypt_content(encryption, &content))
+            .await
+            .map_err(|e| ZhangError::CustomError(format!("cannot decrypt {}: {}", path, e)))?
+    }
+
     fn parse(&self, content: &str, path: PathBuf) -> ZhangResult<Vec<Spanned<Directive>>> {
         let path_string = path.to_string_lossy().to_string();
-        if self.is_beancount {
-            let beancount_parser = beancount::Beancount {};
-            beancount_parser
+        // the plain directives of encrypted file are never cached on disk
+        let directive_cache = self.directive_cache.as_ref().filter(|_| Encryption::of(&path).is_none());
+        DirectiveCache::parse_with_cache(directive_cache, &path_string, content, || self.parse_content(content, path))
+    }
+
+    fn parse_content(&self, content: &str, path: PathBuf) -> ZhangResult<Vec<Spanned<Directive>>> {
+        let path_string = path.to_string_lossy().to_string();
+        if self.is_zhang {
+            Ok(zhang_parse(content, path))
+        } else {
+            self.data_type
                 .transform(content.to_string(), Some(path_string.clone()))
                 .map_err(|it| ZhangError::PestError {
                     path: path_string,
                     msg: it.to_string(),
                 })
-        } else {
-            zhang_parse(content, path).map_err(|it| ZhangError::PestError {
-                path: path_string,
-                msg: it.to_string(),
-            })
-        }
-    }
-    fn go_next(&self, directive: &Spanned<Directive>) -> Option<String> {
-        match &directive.data {
-            Directive::Include(include) => Some(include.file.clone().to_plain_string()),
-            _ => None,
         }
     }
     fn transform(&self, directives: Vec<Spanned<Directive>>) -> ZhangResult<Vec<Spanned<Directive>>> {
         Ok(directives)
     }
+    /// the files matching include pattern, which are found by listing the folder of pattern recursively
+    async fn expand_include_pattern(&self, entry: &Path, folder: &Path, pattern: &str) -> ZhangResult<Vec<PathBuf>> {
+        let base = utils::include_pattern_base(folder, pattern);
+        let striped_base = base
+            .strip_prefix(entry)
+            .map_err(|_| ZhangError::CustomError(format!("include pattern [{}] is outside of the ledger folder", pattern)))?;
+        let list_path = if striped_base.as_os_str().is_empty() {
+            "/".to_owned()
+        } else {
+            format!("{}/", striped_base.display())
+        };
+        let entries = self
+            .operator
+            .list_with(&list_path)
+            .recursive(true)
+            .await
+            .map_err(|e| ZhangError::CustomError(format!("fail to list folder [{}] : {}", list_path, e)))?;
+        let files = entries
+            .into_iter()
+            .filter(|it| it.metadata().is_file())
+            .map(|it| entry.join(it.path().trim_start_matches('/')));
+        utils::match_include_pattern(folder, pattern, files)
+    }
+
     async fn get_file_content(&self, path: PathBuf) -> ZhangResult<String> {
         let path = path.to_str().expect("cannot convert path to string");
 
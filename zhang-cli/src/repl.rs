@@ -0,0 +1,213 @@
+//! the interactive shell of query language, which prints the results as tables.
+//!
+//! the line is edited in the terminal with the history of previous queries and the tab completion of keywords, columns
+//! and account names. the queries are read line by line if the input is not a terminal, e.g. piped from a file.
+
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::FileHistory;
+use rustyline::validate::Validator;
+use rustyline::{CompletionType, Config, Context, Editor, Helper};
+use strum::IntoEnumIterator;
+use zhang_core::export::Table;
+use zhang_core::ledger::Ledger;
+use zhang_core::query::{execute, Aggregation, Column};
+use zhang_core::{ZhangError, ZhangResult};
+
+const KEYWORDS: [&str; 10] = ["SELECT", "WHERE", "GROUP BY", "ORDER BY", "AND", "OR", "ASC", "DESC", "LIMIT", "NOT"];
+
+/// the max count of queries kept in the history file
+const MAX_HISTORY: usize = 1000;
+
+pub trait LineEditor {
+    /// read the next line after printing the prompt, `None` means the input is closed
+    fn read_line(&mut self, prompt: &str, completions: &Completions) -> ZhangResult<Option<String>>;
+}
+
+/// the lines of plain input, e.g. the queries piped into command
+pub struct PlainEditor<R>(pub R);
+
+impl<R: BufRead> LineEditor for PlainEditor<R> {
+    fn read_line(&mut self, _prompt: &str, _completions: &Completions) -> ZhangResult<Option<String>> {
+        let mut line = String::new();
+        match self.0.read_line(&mut line)? {
+            0 => Ok(None),
+            _ => Ok(Some(line.trim_end().to_owned())),
+        }
+    }
+}
+
+/// the line editor of terminal with history and tab completion
+pub struct TerminalEditor {
+    editor: Editor<Completions, FileHistory>,
+    history_file: Option<PathBuf>,
+}
+
+impl TerminalEditor {
+    /// the editor with the history loaded from the file, which is appended by the queries read
+    pub fn new(history_file: Option<PathBuf>) -> ZhangResult<Self> {
+        let config = Config::builder()
+            .max_history_size(MAX_HISTORY)
+            .and_then(|it| it.history_ignore_dups(true))
+            .map_err(|e| ZhangError::CustomError(e.to_string()))?
+            .history_ignore_space(true)
+            .completion_type(CompletionType::List)
+            .build();
+        let mut editor = Editor::with_config(config).map_err(|e| ZhangError::CustomError(e.to_string()))?;
+        if let Some(file) = history_file.as_ref().filter(|it| it.exists()) {
+            if let Err(e) = editor.load_history(file) {
+                log::warn!("cannot read query history from {}: {}", file.display(), e);
+            }
+        }
+        Ok(Self { editor, history_file })
+    }
+
+    fn remember(&mut self, line: &str) {
+        if line.trim().is_empty() || !self.editor.add_history_entry(line).unwrap_or(false) {
+            return;
+        }
+        if let Some(file) = &self.history_file {
+            if let Err(e) = self.editor.save_history(file) {
+                log::warn!("cannot write query history into {}: {}", file.display(), e);
+            }
+        }
+    }
+}
+
+impl LineEditor for TerminalEditor {
+    fn read_line(&mut self, prompt: &str, completions: &Completions) -> ZhangResult<Option<String>> {
+        if self.editor.helper().is_none() {
+            self.editor.set_helper(Some(completions.clone()));
+        }
+        loop {
+            match self.editor.readline(prompt) {
+                Ok(line) => {
+                    self.remember(&line);
+                    return Ok(Some(line));
+                }
+                // the editing line is discarded on ctrl-c, and the shell is closed on ctrl-d
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => return Ok(None),
+                Err(e) => return Err(ZhangError::CustomError(e.to_string())),
+            }
+        }
+    }
+}
+
+/// the words completed on tab, which are the keywords, columns, aggregations and account names
+#[derive(Clone)]
+pub struct Completions {
+    words: Vec<String>,
+}
+
+impl Completions {
+    pub fn new(accounts: Vec<String>) -> Self {
+        let mut words = KEYWORDS.iter().map(|it| it.to_string()).collect::<Vec<_>>();
+        words.extend(Column::iter().map(|it| it.as_ref().to_owned()));
+        words.extend(Aggregation::iter().map(|it| format!("{}(", it.as_ref())));
+        words.extend(accounts);
+        Self { words }
+    }
+
+    /// the start of last word in line, and the words it may be completed to
+    fn candidates(&self, line: &str) -> (usize, Vec<String>) {
+        let start = line
+            .rfind(|c: char| c.is_whitespace() || c == '"' || c == '(' || c == ',')
+            .map(|idx| idx + 1)
+            .unwrap_or(0);
+        let word = &line[start..];
+        if word.is_empty() {
+            return (start, vec![]);
+        }
+        let candidates = self
+            .words
+            .iter()
+            .filter(|it| it.len() >= word.len() && it.is_char_boundary(word.len()) && it[..word.len()].eq_ignore_ascii_case(word))
+            .cloned()
+            .collect();
+        (start, candidates)
+    }
+
+    /// complete the last word of line, which starts after the last whitespace or quote. the line is completed to the
+    /// longest common prefix of candidates, and the candidates are returned for listing
+    pub fn complete(&self, line: &str) -> (String, Vec<String>) {
+        let (start, candidates) = self.candidates(line);
+        let Some(first) = candidates.first() else {
+            return (line.to_owned(), vec![]);
+        };
+        let common = candidates.iter().skip(1).fold(first.as_str(), |common, candidate| {
+            let length = common
+                .char_indices()
+                .zip(candidate.chars())
+                .take_while(|((_, a), b)| a.eq_ignore_ascii_case(b))
+                .last()
+                .map(|((idx, c), _)| idx + c.len_utf8())
+                .unwrap_or(0);
+            &common[..length]
+        });
+        let completed = match common.len() > line.len() - start {
+            true => format!("{}{}", &line[..start], common),
+            false => line.to_owned(),
+        };
+        (completed, candidates)
+    }
+}
+
+impl Completer for Completions {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(&line[..pos]))
+    }
+}
+
+impl Hinter for Completions {
+    type Hint = String;
+}
+
+impl Highlighter for Completions {}
+
+impl Validator for Completions {}
+
+impl Helper for Completions {}
+
+/// execute the queries read from editor until the input is closed or `exit` is entered. the error of query is printed
+/// rather than stopping the shell
+pub fn run<E: LineEditor, W: Write>(ledger: &Ledger, editor: &mut E, output: &mut W) -> ZhangResult<()> {
+    let operations = ledger.operations();
+    let mut accounts = ledger.operations().all_accounts()?;
+    accounts.sort();
+    let completions = Completions::new(accounts);
+    while let Some(line) = editor.read_line("zhang> ", &completions)? {
+        let query = line.trim().trim_end_matches(';');
+        match query {
+            "" => continue,
+            "exit" | "quit" => break,
+            _ => {}
+        }
+        let content = match query_table(&operations, query) {
+            Ok(table) => format!("{}({} rows)\n", table.to_text(), table.rows.len()),
+            Err(e) => format!("error: {}\n", e),
+        };
+        write!(output, "{}", content).map_err(|e| ZhangError::CustomError(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// the result of query as table, the null cell is absent
+pub fn query_table(operations: &zhang_core::domains::Operations, query: &str) -> ZhangResult<Table> {
+    let result = execute(operations, query)?;
+    Ok(Table {
+        columns: result.columns,
+        rows: result
+            .rows
+            .into_iter()
+            .map(|row| row.into_iter().map(|value| Some(value.to_string()).filter(|it| !it.is_empty())).collect())
+            .collect(),
+    })
+}
@@ -0,0 +1,379 @@
+//! the interactive prompts of command line, which read the answers line by line so that they work in any terminal.
+//!
+//! the completion is made on the answer: the candidates matching the typed text fuzzily are listed to be chosen by
+//! their numbers, and the only matched candidate is taken directly.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{NaiveDate, Utc};
+use zhang_core::ast::amount::Amount;
+use zhang_core::ast::{Account, Date, Directive, Flag, Meta, Posting, PostingType, Transaction};
+use zhang_core::ledger::Ledger;
+use zhang_core::utils::string_::StringExt;
+use zhang_core::{ZhangError, ZhangResult};
+
+/// the max count of candidates listed for choosing
+const MAX_CANDIDATES: usize = 9;
+
+pub struct Prompt<R, W> {
+    input: R,
+    output: W,
+    colored: bool,
+}
+
+impl<R: BufRead, W: Write> Prompt<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self { input, output, colored: false }
+    }
+
+    /// print the previews in colors, e.g. when the output is a terminal
+    pub fn colored(self, colored: bool) -> Self {
+        Self { colored, ..self }
+    }
+
+    pub fn say(&mut self, message: impl AsRef<str>) -> ZhangResult<()> {
+        writeln!(self.output, "{}", message.as_ref()).map_err(io_error)
+    }
+
+    /// ask for an answer, the default is taken if the answer is empty. the closed input is treated as cancellation
+    pub fn ask(&mut self, question: &str, default: Option<&str>) -> ZhangResult<String> {
+        match default {
+            Some(default) => write!(self.output, "{} [{}]: ", question, default),
+            None => write!(self.output, "{}: ", question),
+        }
+        .and_then(|_| self.output.flush())
+        .map_err(io_error)?;
+        let mut line = String::new();
+        if self.input.read_line(&mut line).map_err(io_error)? == 0 {
+            return Err(ZhangError::CustomError("input is closed, nothing is written".to_owned()));
+        }
+        let answer = line.trim();
+        Ok(match default {
+            Some(default) if answer.is_empty() => default.to_owned(),
+            _ => answer.to_owned(),
+        })
+    }
+
+    /// ask for yes or no, the default is yes
+    pub fn confirm(&mut self, question: &str) -> ZhangResult<bool> {
+        let answer = self.ask(&format!("{} [Y/n]", question), None)?;
+        Ok(!matches!(answer.to_lowercase().as_str(), "n" | "no"))
+    }
+
+    /// ask for one of candidates with completion, the empty answer is returned as `None`. the typed text is returned as
+    /// it is if no candidate matches and `allow_new` is set, otherwise the question is asked again
+    pub fn complete(&mut self, question: &str, candidates: &[String], allow_new: bool) -> ZhangResult<Option<String>> {
+        loop {
+            let answer = self.ask(question, None)?;
+            if answer.is_empty() {
+                return Ok(None);
+            }
+            if let Some(chosen) = self.choose(&answer, candidates, allow_new)? {
+                return Ok(Some(chosen));
+            }
+        }
+    }
+
+    /// choose the candidate completed from the answer, `None` is returned if nothing is chosen so that the question
+    /// should be asked again
+    pub fn choose(&mut self, answer: &str, candidates: &[String], allow_new: bool) -> ZhangResult<Option<String>> {
+        if let Some(exact) = candidates.iter().find(|it| it.eq_ignore_ascii_case(answer)) {
+            return Ok(Some(exact.clone()));
+        }
+        let matched = fuzzy_match(answer, candidates);
+        match matched.as_slice() {
+            [] if allow_new => Ok(Some(answer.to_owned())),
+            [] => {
+                self.say(format!("  nothing matches '{}'", answer))?;
+                Ok(None)
+            }
+            [only] if !allow_new => {
+                self.say(format!("  -> {}", only))?;
+                Ok(Some(only.to_string()))
+            }
+            matched => {
+                for (idx, candidate) in matched.iter().enumerate() {
+                    self.say(format!("  {}) {}", idx + 1, candidate))?;
+                }
+                let choice = match allow_new {
+                    true => self.ask(&format!("choose 1-{}, or enter to keep '{}'", matched.len(), answer), None)?,
+                    false => self.ask(&format!("choose 1-{}, or enter to retype", matched.len()), None)?,
+                };
+                Ok(
+                    match choice.parse::<usize>().ok().and_then(|idx| idx.checked_sub(1)).and_then(|idx| matched.get(idx)) {
+                        Some(chosen) => Some(chosen.to_string()),
+                        None if allow_new && choice.is_empty() => Some(answer.to_owned()),
+                        None => None,
+                    },
+                )
+            }
+        }
+    }
+
+    /// print the lines of content prefixed by the marker, in the color of marker if the output is colored
+    pub fn preview(&mut self, marker: Marker, content: &str) -> ZhangResult<()> {
+        for line in content.trim_end().lines() {
+            match (self.colored, marker.color()) {
+                (true, Some(color)) => self.say(format!("\x1b[{}m{} {}\x1b[0m", color, marker.symbol(), line))?,
+                _ => self.say(format!("{} {}", marker.symbol(), line))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// the marker of previewed directive, like the ones of diff
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Marker {
+    /// the directive to be appended
+    Added,
+    /// the probable duplicate of transaction in ledger
+    Duplicate,
+    /// the directive to be decided
+    Pending,
+}
+
+impl Marker {
+    fn symbol(&self) -> char {
+        match self {
+            Marker::Added => '+',
+            Marker::Duplicate => '~',
+            Marker::Pending => '?',
+        }
+    }
+
+    /// the ANSI color code
+    fn color(&self) -> Option<u8> {
+        match self {
+            Marker::Added => Some(32),
+            Marker::Duplicate => Some(33),
+            Marker::Pending => None,
+        }
+    }
+}
+
+fn io_error(e: std::io::Error) -> ZhangError {
+    ZhangError::CustomError(format!("cannot prompt: {}", e))
+}
+
+/// the candidates containing the characters of query in order, ignoring case. the candidates starting with the query
+/// come first, then the ones containing it, then the rest, each in the order of given candidates
+pub fn fuzzy_match<'a>(query: &str, candidates: &'a [String]) -> Vec<&'a str> {
+    let query = query.to_lowercase();
+    let mut matched = candidates
+        .iter()
+        .filter_map(|candidate| {
+            let lowercase = candidate.to_lowercase();
+            let rank = if lowercase.starts_with(&query) {
+                0
+            } else if lowercase.contains(&query) {
+                1
+            } else {
+                let mut chars = lowercase.chars();
+                if !query.chars().all(|c| chars.any(|it| it == c)) {
+                    return None;
+                }
+                2
+            };
+            Some((rank, candidate.as_str()))
+        })
+        .collect::<Vec<_>>();
+    matched.sort_by_key(|(rank, _)| *rank);
+    matched.into_iter().take(MAX_CANDIDATES).map(|(_, candidate)| candidate).collect()
+}
+
+/// prompt the transaction to be appended into ledger, with the payees and the open accounts of ledger as completion.
+/// the typical accounts of chosen payee are completed first, and the amount of next posting defaults to the residual
+pub fn transaction_wizard<R: BufRead, W: Write>(ledger: &Ledger, prompt: &mut Prompt<R, W>) -> ZhangResult<Transaction> {
+    let mut operations = ledger.operations();
+    let payees = operations.payees()?;
+    let mut accounts = operations.all_open_accounts()?.into_iter().map(|it| it.name).collect::<Vec<_>>();
+    accounts.sort();
+
+    let today = Utc::now().with_timezone(&ledger.options.timezone).date_naive().to_string();
+    let date = loop {
+        let answer = prompt.ask("date", Some(&today))?;
+        match NaiveDate::parse_from_str(&answer, "%Y-%m-%d") {
+            Ok(date) => break date,
+            Err(_) => prompt.say(format!("  '{}' is not a date like {}", answer, today))?,
+        }
+    };
+
+    let payee_names = payees.iter().map(|it| it.name.clone()).collect::<Vec<_>>();
+    let payee = prompt.complete("payee", &payee_names, true)?;
+    if let Some(typical_accounts) = payees.iter().find(|it| Some(&it.name) == payee.as_ref()).map(|it| &it.typical_accounts) {
+        accounts.sort_by_key(|account| typical_accounts.iter().position(|it| it == account).unwrap_or(usize::MAX));
+    }
+    let narration = Some(prompt.ask("narration (optional)", None)?).filter(|it| !it.is_empty());
+
+    let mut postings = vec![];
+    let mut residual: BTreeMap<String, BigDecimal> = BTreeMap::new();
+    loop {
+        let question = match postings.len() {
+            0 | 1 => format!("account #{}", postings.len() + 1),
+            _ => format!("account #{} (enter to finish)", postings.len() + 1),
+        };
+        let Some(account) = prompt.complete(&question, &accounts, false)? else {
+            if postings.len() < 2 {
+                prompt.say("  a transaction needs two postings at least")?;
+            } else if !residual.is_empty() {
+                prompt.say(format!("  the transaction is unbalanced by {}", display_residual(&residual)))?;
+            } else {
+                break;
+            }
+            continue;
+        };
+
+        let default_commodity = match residual.keys().collect::<Vec<_>>().as_slice() {
+            [commodity] => commodity.to_string(),
+            _ => ledger.options.operating_currency.clone(),
+        };
+        let default_amount = match residual.iter().collect::<Vec<_>>().as_slice() {
+            [(commodity, number)] => format!("{} {}", -(*number).clone(), commodity),
+            _ => default_commodity.clone(),
+        };
+        let amount = loop {
+            let answer = prompt.ask("amount", Some(&default_amount))?;
+            match parse_amount(&answer, &default_commodity) {
+                Some(amount) => break amount,
+                None => prompt.say(format!("  '{}' is not an amount like 12.50 {}", answer, default_commodity))?,
+            }
+        };
+
+        let number = residual.entry(amount.currency.clone()).or_insert_with(BigDecimal::zero);
+        *number += &amount.number;
+        residual.retain(|_, number| !number.is_zero());
+        match residual.is_empty() {
+            true => prompt.say("  balanced")?,
+            false => prompt.say(format!("  unbalanced by {}", display_residual(&residual)))?,
+        }
+        postings.push(Posting {
+            flag: None,
+            posting_type: PostingType::Real,
+            account: Account::from_str(&account).map_err(|_| ZhangError::InvalidAccount)?,
+            units: Some(amount),
+            cost: None,
+            cost_date: None,
+            cost_label: None,
+            price: None,
+            comment: None,
+            meta: Default::default(),
+        });
+    }
+
+    Ok(Transaction {
+        date: Date::Date(date),
+        auxiliary_date: None,
+        flag: Some(Flag::Okay),
+        payee: payee.map(|it| it.into_quote()),
+        narration: narration.map(|it| it.into_quote()),
+        tags: Default::default(),
+        links: Default::default(),
+        postings,
+        meta: Meta::default(),
+    })
+}
+
+/// prompt the transaction, then append it by the insert strategy of ledger once it is valid and confirmed.
+/// the file it is appended to is returned, or `None` if it is discarded
+pub async fn add<R: BufRead, W: Write>(ledger: &Ledger, prompt: &mut Prompt<R, W>) -> ZhangResult<Option<String>> {
+    let trx = transaction_wizard(ledger, prompt)?;
+    let issues = ledger.operations().validate_transaction(&trx)?;
+    if !issues.is_empty() {
+        let issues = issues.iter().map(|it| it.to_string()).collect::<Vec<_>>().join(", ");
+        return Err(ZhangError::CustomError(format!("invalid transaction: {}", issues)));
+    }
+    let directive = Directive::Transaction(trx);
+    let content = String::from_utf8_lossy(&ledger.data_source.export(directive.clone())?).to_string();
+    prompt.say(format!("\n{}\n", content.trim_end()))?;
+    if !prompt.confirm("append to ledger?")? {
+        return Ok(None);
+    }
+    let spans = ledger.data_source.async_append(ledger, vec![directive]).await?;
+    Ok(Some(
+        spans
+            .first()
+            .and_then(|span| span.filename.as_ref())
+            .map(|file| file.display().to_string())
+            .unwrap_or_default(),
+    ))
+}
+
+/// the amount like `-12.50 CNY`, whose commodity can be omitted
+fn parse_amount(text: &str, default_commodity: &str) -> Option<Amount> {
+    let mut parts = text.split_whitespace();
+    let number = BigDecimal::from_str(parts.next()?).ok()?;
+    let commodity = parts.next().unwrap_or(default_commodity);
+    match parts.next() {
+        None => Some(Amount::new(number, commodity)),
+        Some(_) => None,
+    }
+}
+
+fn display_residual(residual: &BTreeMap<String, BigDecimal>) -> String {
+    residual
+        .iter()
+        .map(|(commodity, number)| format!("{} {}", number, commodity))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// preview the directives to be imported and return the accepted ones. the probable duplicates are skipped unless
+/// `allow_duplicates` is set. if `interactive` is set, the counter account of each transaction is asked with
+/// completion, which defaults to the one classified by importer rules, and `-` skips the transaction
+pub fn review_import<R: BufRead, W: Write>(
+    ledger: &Ledger, prompt: &mut Prompt<R, W>, directives: Vec<Directive>, duplicates: &[Transaction], allow_duplicates: bool, interactive: bool,
+) -> ZhangResult<Vec<Directive>> {
+    let mut accounts = ledger.operations().all_open_accounts()?.into_iter().map(|it| it.name).collect::<Vec<_>>();
+    accounts.sort();
+    let export = |directive: &Directive| ledger.data_source.export(directive.clone()).map(|it| String::from_utf8_lossy(&it).to_string());
+
+    let mut accepted = vec![];
+    for mut directive in directives {
+        let duplicate = matches!(&directive, Directive::Transaction(trx) if duplicates.contains(trx));
+        if duplicate {
+            prompt.preview(Marker::Duplicate, &export(&directive)?)?;
+            if !allow_duplicates {
+                prompt.say("  probable duplicate of a transaction in ledger, skipped")?;
+                continue;
+            }
+        }
+        match &mut directive {
+            Directive::Transaction(trx) if interactive => {
+                if !duplicate {
+                    prompt.preview(Marker::Pending, &export(&Directive::Transaction(trx.clone()))?)?;
+                }
+                let Some(counter_posting) = trx.postings.last_mut() else {
+                    continue;
+                };
+                let question = format!("account [{}] (- to skip)", counter_posting.account.name());
+                let account = loop {
+                    let answer = prompt.ask(&question, None)?;
+                    match answer.as_str() {
+                        "" => break Some(counter_posting.account.name().to_owned()),
+                        "-" => break None,
+                        answer => {
+                            if let Some(chosen) = prompt.choose(answer, &accounts, false)? {
+                                break Some(chosen);
+                            }
+                        }
+                    }
+                };
+                match account {
+                    Some(account) => counter_posting.account = Account::from_str(&account).map_err(|_| ZhangError::InvalidAccount)?,
+                    None => {
+                        prompt.say("  skipped")?;
+                        continue;
+                    }
+                }
+            }
+            _ if duplicate => {}
+            _ => prompt.preview(Marker::Added, &export(&directive)?)?,
+        }
+        accepted.push(directive);
+    }
+    Ok(accepted)
+}
@@ -0,0 +1,242 @@
+//! closing the books at the end of period, e.g. the fiscal year, by the standard year-end workflow of accounting:
+//! the balances of income and expenses are transferred into retained earnings, so that the income statement of next period
+//! starts from zero, then the balances of assets, liabilities and equity can be carried into the ledger of next period.
+
+use std::collections::BTreeMap;
+use std::ops::AddAssign;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{Days, NaiveDate, NaiveTime};
+use zhang_ast::amount::Amount;
+use zhang_ast::{Account, AccountType, Currency, Date, Directive, Flag, Meta, Open, Posting, PostingType, Transaction, ZhangString};
+
+use crate::constants::DEFAULT_OPENING_BALANCE_ACCOUNT;
+use crate::domains::schemas::AccountStatus;
+use crate::ledger::Ledger;
+use crate::{ZhangError, ZhangResult};
+
+#[derive(Debug, Clone, Default)]
+pub struct PeriodClosing {
+    /// the directives closing income and expenses into retained earnings at the end of period, which are appended into ledger.
+    /// it is empty if income and expenses are closed already
+    pub closing: Vec<Directive>,
+    /// the directives opening the accounts of assets, liabilities and equity with their balances after closing on the next
+    /// day of period, which start the ledger of next period
+    pub opening: Vec<Directive>,
+}
+
+/// the balances of accounts at the end of date, the zero balances are skipped
+fn balances_at(ledger: &Ledger, date: NaiveDate) -> ZhangResult<BTreeMap<(String, Currency), BigDecimal>> {
+    let end = date
+        .checked_add_days(Days::new(1))
+        .and_then(|next_day| next_day.and_time(NaiveTime::MIN).and_local_timezone(ledger.options.timezone).earliest())
+        .ok_or_else(|| ZhangError::CustomError(format!("the end of {} is invalid", date)))?;
+    let operations = ledger.operations();
+    let store = operations.read();
+    let mut balances: BTreeMap<(String, Currency), BigDecimal> = BTreeMap::new();
+    for posting in store.postings.iter().filter(|posting| posting.datetime < end) {
+        balances
+            .entry((posting.account.name().to_owned(), posting.inferred_amount.currency.clone()))
+            .or_insert_with(BigDecimal::zero)
+            .add_assign(&posting.inferred_amount.number);
+    }
+    balances.retain(|_, number| !number.is_zero());
+    Ok(balances)
+}
+
+fn posting(account: &str, number: BigDecimal, currency: &str) -> ZhangResult<Posting> {
+    Ok(Posting {
+        flag: None,
+        posting_type: PostingType::Real,
+        account: Account::from_str(account).map_err(|_| ZhangError::InvalidAccount)?,
+        units: Some(Amount::new(number, currency)),
+        cost: None,
+        cost_date: None,
+        cost_label: None,
+        price: None,
+        comment: None,
+        meta: Meta::default(),
+    })
+}
+
+fn transaction(date: NaiveDate, narration: String, postings: Vec<Posting>) -> Directive {
+    Directive::Transaction(Transaction {
+        date: Date::Date(date),
+        auxiliary_date: None,
+        flag: Some(Flag::Okay),
+        payee: None,
+        narration: Some(ZhangString::QuoteString(narration)),
+        tags: Default::default(),
+        links: Default::default(),
+        postings,
+        meta: Meta::default(),
+    })
+}
+
+fn open(date: NaiveDate, account: Account) -> Directive {
+    Directive::Open(Open {
+        date: Date::Date(date),
+        account,
+        commodities: vec![],
+        meta: Meta::default(),
+    })
+}
+
+/// close the period ending at `date`(inclusive) into the retained earnings account, which is opened at `date` if it is not
+/// opened yet. the opening balances are posted against `Equity:Opening-Balances`, which takes the residuals of commodities
+/// traded at cost or price, since the balances are carried in units
+pub fn close_period(ledger: &Ledger, date: NaiveDate, retained_earnings: &Account) -> ZhangResult<PeriodClosing> {
+    let balances = balances_at(ledger, date)?;
+    let accounts = ledger.operations().read().accounts.clone();
+    let is_type_of =
+        |account: &str, account_types: &[AccountType]| Account::from_str(account).is_ok_and(|account| account_types.contains(&account.account_type));
+
+    let mut closing = vec![];
+    if !accounts.contains_key(retained_earnings.name()) {
+        closing.push(open(date, retained_earnings.clone()));
+    }
+    let mut postings = vec![];
+    let mut retained: BTreeMap<Currency, BigDecimal> = BTreeMap::new();
+    for ((account, currency), number) in balances
+        .iter()
+        .filter(|((account, _), _)| is_type_of(account, &[AccountType::Income, AccountType::Expenses]))
+    {
+        postings.push(posting(account, -number, currency)?);
+        retained.entry(currency.clone()).or_insert_with(BigDecimal::zero).add_assign(number);
+    }
+    for (currency, number) in retained.iter().filter(|(_, number)| !number.is_zero()) {
+        postings.push(posting(retained_earnings.name(), number.clone(), currency)?);
+    }
+    if !postings.is_empty() {
+        let narration = format!("close income and expenses into {}", retained_earnings.name());
+        closing.push(transaction(date, narration, postings));
+    }
+
+    let next_day = date
+        .checked_add_days(Days::new(1))
+        .ok_or_else(|| ZhangError::CustomError(format!("the next day of {} is invalid", date)))?;
+    let mut opened = accounts
+        .values()
+        .filter(|account| account.status == AccountStatus::Open && account.date.date() <= date)
+        .map(|account| account.name.clone())
+        .filter(|account| is_type_of(account, &[AccountType::Assets, AccountType::Liabilities, AccountType::Equity]))
+        .chain([retained_earnings.name().to_owned(), DEFAULT_OPENING_BALANCE_ACCOUNT.to_owned()])
+        .collect::<Vec<_>>();
+    opened.sort();
+    opened.dedup();
+    let mut opening = opened
+        .iter()
+        .map(|account| {
+            Account::from_str(account)
+                .map(|account| open(next_day, account))
+                .map_err(|_| ZhangError::InvalidAccount)
+        })
+        .collect::<ZhangResult<Vec<_>>>()?;
+
+    let mut carried = balances
+        .into_iter()
+        .filter(|((account, _), _)| is_type_of(account, &[AccountType::Assets, AccountType::Liabilities, AccountType::Equity]))
+        .collect::<BTreeMap<_, _>>();
+    for (currency, number) in retained {
+        carried
+            .entry((retained_earnings.name().to_owned(), currency))
+            .or_insert_with(BigDecimal::zero)
+            .add_assign(number);
+    }
+    let mut postings = vec![];
+    let mut residuals: BTreeMap<Currency, BigDecimal> = BTreeMap::new();
+    for ((account, currency), number) in carried
+        .iter()
+        .filter(|((account, _), number)| account != DEFAULT_OPENING_BALANCE_ACCOUNT && !number.is_zero())
+    {
+        postings.push(posting(account, number.clone(), currency)?);
+        residuals.entry(currency.clone()).or_insert_with(BigDecimal::zero).add_assign(number);
+    }
+    for (currency, number) in residuals.into_iter().filter(|(_, number)| !number.is_zero()) {
+        postings.push(posting(DEFAULT_OPENING_BALANCE_ACCOUNT, -number, &currency)?);
+    }
+    if !postings.is_empty() {
+        opening.push(transaction(next_day, format!("opening balances after closing {}", date), postings));
+    }
+    Ok(PeriodClosing { closing, opening })
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use chrono::NaiveDate;
+    use indoc::indoc;
+    use itertools::Itertools;
+    use zhang_ast::{Account, Directive, SpanInfo, Spanned};
+
+    use crate::closing::close_period;
+    use crate::data_type::text::ZhangDataType;
+    use crate::data_type::DataType;
+    use crate::test::load_from_text;
+
+    fn export(directives: Vec<Directive>) -> String {
+        directives
+            .into_iter()
+            .map(|it| ZhangDataType {}.export(Spanned::new(it, SpanInfo::default())))
+            .join("\n")
+    }
+
+    const LEDGER: &str = indoc! {r#"
+        1970-01-01 commodity CNY
+        1970-01-01 open Assets:Bank
+        1970-01-01 open Liabilities:CreditCard
+        1970-01-01 open Equity:Opening-Balances
+        1970-01-01 open Income:Salary
+        1970-01-01 open Expenses:Food
+        2024-01-01 "opening"
+          Assets:Bank 500 CNY
+          Equity:Opening-Balances
+        2024-03-01 "Company" "salary"
+          Income:Salary -1000 CNY
+          Assets:Bank
+        2024-06-01 "KFC" "dinner"
+          Expenses:Food 300 CNY
+          Liabilities:CreditCard
+        2025-01-02 "KFC" "lunch"
+          Expenses:Food 50 CNY
+          Assets:Bank
+    "#};
+
+    #[test]
+    fn should_close_income_and_expenses_into_retained_earnings() {
+        let ledger = load_from_text(LEDGER);
+        let date = NaiveDate::from_ymd_opt(2024, 12, 31).unwrap();
+        let retained_earnings = Account::from_str("Equity:Retained-Earnings").unwrap();
+
+        let closing = close_period(&ledger, date, &retained_earnings).unwrap();
+        assert_eq!(
+            indoc! {r#"
+                2024-12-31 open Equity:Retained-Earnings
+                2024-12-31 * "close income and expenses into Equity:Retained-Earnings"
+                  Expenses:Food -300 CNY
+                  Income:Salary 1000 CNY
+                  Equity:Retained-Earnings -700 CNY"#},
+            export(closing.closing.clone())
+        );
+        assert_eq!(
+            indoc! {r#"
+                2025-01-01 open Assets:Bank
+                2025-01-01 open Equity:Opening-Balances
+                2025-01-01 open Equity:Retained-Earnings
+                2025-01-01 open Liabilities:CreditCard
+                2025-01-01 * "opening balances after closing 2024-12-31"
+                  Assets:Bank 1500 CNY
+                  Equity:Retained-Earnings -700 CNY
+                  Liabilities:CreditCard -300 CNY
+                  Equity:Opening-Balances -500 CNY"#},
+            export(closing.opening)
+        );
+
+        // the period is closed already once the closing transaction is in ledger
+        let closed = load_from_text(&format!("{}\n{}\n", LEDGER, export(closing.closing)));
+        assert!(closed.operations().errors().unwrap().is_empty());
+        assert!(close_period(&closed, date, &retained_earnings).unwrap().closing.is_empty());
+    }
+}
@@ -5,21 +5,81 @@ pub const KEY_DEFAULT_ROUNDING: &str = "default_rounding";
 pub const KEY_DEFAULT_BALANCE_TOLERANCE_PRECISION: &str = "default_balance_tolerance_precision";
 pub const KEY_DEFAULT_COMMODITY_PRECISION: &str = "default_commodity_precision";
 pub const KEY_TIMEZONE: &str = "timezone";
+pub const KEY_BUDGET_CONVERT_TO_OPERATING_CURRENCY: &str = "budget_convert_to_operating_currency";
+pub const KEY_BALANCE_TOLERANCE: &str = "balance_tolerance";
+pub const KEY_ROUNDING_ACCOUNT: &str = "rounding_account";
+pub const KEY_PAYEE_ALIAS: &str = "payee-alias";
+/// the profile under which the `include-if` directives are loaded
+pub const KEY_PROFILE: &str = "profile";
+/// the override of error severity in the format of `[{FILE}:]{KIND}={SEVERITY}`
+pub const KEY_ERROR_SEVERITY: &str = "error_severity";
+/// the schema of meta in the format of `{TARGET}[:{ACCOUNT}] {KEY}={TYPE}[?]`, it can be declared repeatedly
+pub const KEY_META_SCHEMA: &str = "meta_schema";
+/// the max days between probable duplicate transactions, duplicate transactions are not detected if absent
+pub const KEY_DUPLICATE_TRANSACTION_WINDOW: &str = "duplicate_transaction_window";
+/// the age identity file decrypting the encrypted files, relative to the file declaring it
+pub const KEY_ENCRYPTION_IDENTITY: &str = "encryption_identity";
+/// the age recipient or GPG key id which the encrypted files are encrypted to on write-back, it can be declared repeatedly
+pub const KEY_ENCRYPTION_RECIPIENT: &str = "encryption_recipient";
+/// the GPG home directory holding the keyring, relative to the file declaring it, the default one of GPG is used if absent
+pub const KEY_ENCRYPTION_GPG_HOME: &str = "encryption_gpg_home";
+
+pub const KEY_PLUGIN_TIMEOUT: &str = "plugin_timeout";
+pub const KEY_PLUGIN_MEMORY_LIMIT: &str = "plugin_memory_limit";
 
 pub const KEY_FEATURES_PLUGIN: &str = "features.plugin";
+pub const KEY_FEATURES_DOCUMENT_INDEX: &str = "features.document_index";
 
 pub const DEFAULT_COMMODITY_PRECISION: i32 = 2;
 pub const DEFAULT_OPERATING_CURRENCY: &str = "CNY";
 pub const DEFAULT_ROUNDING: Rounding = Rounding::RoundDown;
 pub const DEFAULT_BALANCE_TOLERANCE_PRECISION: i32 = 2;
 pub const DEFAULT_TIMEZONE: &str = "Asia/Hong_Kong";
+pub const DEFAULT_OPENING_BALANCE_ACCOUNT: &str = "Equity:Opening-Balances";
+/// the equity account which income and expenses are closed into at the end of period
+pub const DEFAULT_RETAINED_EARNINGS_ACCOUNT: &str = "Equity:Retained-Earnings";
+/// max execution time of each plugin call in milliseconds
+pub const DEFAULT_PLUGIN_TIMEOUT: u64 = 10_000;
+/// max memory of plugin instance in MiB
+pub const DEFAULT_PLUGIN_MEMORY_LIMIT: u32 = 512;
 
 pub const DEFAULT_ROUNDING_PLAIN: &str = "RoundDown";
 pub const DEFAULT_COMMODITY_PRECISION_PLAIN: &str = "2";
 pub const DEFAULT_BALANCE_TOLERANCE_PRECISION_PLAIN: &str = "2";
+pub const DEFAULT_BUDGET_CONVERT_TO_OPERATING_CURRENCY_PLAIN: &str = "false";
+pub const DEFAULT_BALANCE_TOLERANCE_PLAIN: &str = "0";
+pub const DEFAULT_DOCUMENT_DIRECTORY: &str = "attachments";
 
 pub const TRUE: &str = "true";
 
 pub const TXN_ID: &str = "txn_id";
+/// the id of statement entry which the transaction is imported from, e.g. the `FITID` of OFX
+pub const TXN_IMPORT_ID: &str = "import-id";
+/// the transaction with this meta is never treated as a duplicate of others
+pub const TXN_DEDUP_IGNORE: &str = "dedup-ignore";
 
 pub const COMMODITY_GROUP: &str = "group";
+pub const COMMODITY_PRICE_SOURCE: &str = "price-source";
+pub const COMMODITY_BALANCE_TOLERANCE: &str = "balance_tolerance";
+
+pub const BOOKING_METHOD: &str = "booking_method";
+pub const ACCOUNT_ALLOWED_COMMODITIES: &str = "allowed-commodities";
+pub const ACCOUNT_MAX_POSTING_AMOUNT: &str = "max-posting-amount";
+pub const ACCOUNT_REQUIRE_TAG: &str = "require-tag";
+pub const ACCOUNT_CASHFLOW_CATEGORY: &str = "cashflow-category";
+/// the entity which the account belongs to, for the users tracking several entities in one ledger
+pub const ACCOUNT_ENTITY: &str = "entity";
+/// the gross amount of posting is split into net amount and tax, the meta can be given on account, transaction or posting
+pub const TAX_RATE: &str = "tax-rate";
+pub const TAX_ACCOUNT: &str = "tax-account";
+
+pub const BALANCE_RECURSIVE: &str = "recursive";
+/// the balance check with `reconciled: true` meta asserts the balance of reconciled postings only
+pub const BALANCE_RECONCILED: &str = "reconciled";
+
+pub const POSTING_AUTO_ROUNDING: &str = "auto-rounding";
+/// the generated tax posting is flagged by the meta, whose value is the account of the taxed posting
+pub const POSTING_AUTO_TAX: &str = "auto-tax";
+pub const POSTING_RECONCILE: &str = "reconcile";
+pub const POSTING_STATEMENT_DATE: &str = "statement-date";
+pub const POSTING_DATE: &str = "date";
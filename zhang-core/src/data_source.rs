@@ -1,15 +1,20 @@
-use std::collections::VecDeque;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
 
-use chrono::Datelike;
 use log::debug;
+use rayon::prelude::*;
 use zhang_ast::{Directive, Include, SpanInfo, Spanned, ZhangString};
 
+use crate::constants::KEY_PROFILE;
 use crate::data_type::DataType;
+use crate::directive_cache::DirectiveCache;
+use crate::encryption::{Encryption, EncryptionKeys};
 use crate::error::IoErrorIntoZhangError;
 use crate::ledger::Ledger;
-use crate::utils::has_path_visited;
-use crate::ZhangResult;
+use crate::store_snapshot::StoreSnapshot;
+use crate::utils::{expand_include_pattern, has_path_visited, is_glob_pattern};
+use crate::{ZhangError, ZhangResult};
 
 /// `DataSource` is the protocol to describe how the `DataType` be stored and be transformed into standard directives.
 /// The Data Source have two capabilities:
@@ -24,6 +29,10 @@ where
     fn export(&self, _directive: Directive) -> ZhangResult<Vec<u8>> {
         unimplemented!()
     }
+    /// the snapshot of processed store, from which ledger is restored if no directive is changed
+    fn store_snapshot(&self) -> Option<&StoreSnapshot> {
+        None
+    }
     fn get(&self, _path: String) -> ZhangResult<Vec<u8>> {
         unimplemented!()
     }
@@ -36,7 +45,8 @@ where
         unimplemented!()
     }
 
-    fn append(&self, _ledger: &Ledger, _directives: Vec<Directive>) -> ZhangResult<()> {
+    /// append the directives into the files picked by the insert strategy of ledger, return the spans where they are written
+    fn append(&self, _ledger: &Ledger, _directives: Vec<Directive>) -> ZhangResult<Vec<SpanInfo>> {
         unimplemented!()
     }
 
@@ -47,50 +57,212 @@ where
     async fn async_get(&self, path: String) -> ZhangResult<Vec<u8>> {
         self.get(path)
     }
-    async fn async_append(&self, ledger: &Ledger, directives: Vec<Directive>) -> ZhangResult<()> {
+    async fn async_append(&self, ledger: &Ledger, directives: Vec<Directive>) -> ZhangResult<Vec<SpanInfo>> {
         self.append(ledger, directives)
     }
 
     async fn async_save(&self, ledger: &Ledger, path: String, content: &[u8]) -> ZhangResult<()> {
         self.save(ledger, path, content)
     }
+
+    /// insert the directive before the directive of given span, or at the position if the span is empty
+    fn insert_directive(&self, ledger: &Ledger, span: &SpanInfo, directive: Directive) -> ZhangResult<()> {
+        let content = self.export(directive)?;
+        self.edit_span(ledger, span, SpanEdit::Insert(&String::from_utf8(content)?))
+    }
+
+    /// replace the directive of given span, the content around it such as comments and blank lines is kept
+    fn update_directive(&self, ledger: &Ledger, span: &SpanInfo, directive: Directive) -> ZhangResult<()> {
+        let content = self.export(directive)?;
+        self.edit_span(ledger, span, SpanEdit::Update(&String::from_utf8(content)?))
+    }
+
+    fn delete_directive(&self, ledger: &Ledger, span: &SpanInfo) -> ZhangResult<()> {
+        self.edit_span(ledger, span, SpanEdit::Delete)
+    }
+
+    fn edit_span(&self, ledger: &Ledger, span: &SpanInfo, edit: SpanEdit<'_>) -> ZhangResult<()> {
+        let path = span_path(span)?;
+        let content = String::from_utf8(self.get(path.clone())?)?;
+        let content = edit.apply(content, span)?;
+        self.save(ledger, path, content.as_bytes())
+    }
+
+    async fn async_insert_directive(&self, ledger: &Ledger, span: &SpanInfo, directive: Directive) -> ZhangResult<()> {
+        let content = self.export(directive)?;
+        self.async_edit_span(ledger, span, SpanEdit::Insert(&String::from_utf8(content)?)).await
+    }
+
+    async fn async_update_directive(&self, ledger: &Ledger, span: &SpanInfo, directive: Directive) -> ZhangResult<()> {
+        let content = self.export(directive)?;
+        self.async_edit_span(ledger, span, SpanEdit::Update(&String::from_utf8(content)?)).await
+    }
+
+    async fn async_delete_directive(&self, ledger: &Ledger, span: &SpanInfo) -> ZhangResult<()> {
+        self.async_edit_span(ledger, span, SpanEdit::Delete).await
+    }
+
+    async fn async_edit_span(&self, ledger: &Ledger, span: &SpanInfo, edit: SpanEdit<'_>) -> ZhangResult<()> {
+        let path = span_path(span)?;
+        let content = String::from_utf8(self.async_get(path.clone()).await?)?;
+        let content = edit.apply(content, span)?;
+        self.async_save(ledger, path, content.as_bytes()).await
+    }
+}
+
+fn span_path(span: &SpanInfo) -> ZhangResult<String> {
+    span.filename
+        .as_ref()
+        .map(|it| it.to_string_lossy().to_string())
+        .ok_or_else(|| ZhangError::CustomError("the span of directive does not belong to any file".to_owned()))
+}
+
+/// the modification of source file at the span of directive
+pub enum SpanEdit<'a> {
+    Insert(&'a str),
+    Update(&'a str),
+    Delete,
+}
+
+impl<'a> SpanEdit<'a> {
+    /// apply the edit to the content of file, the span must still point to the same directive as the one loaded,
+    /// otherwise the file is changed since last loading and the edit is rejected
+    pub fn apply(self, mut content: String, span: &SpanInfo) -> ZhangResult<String> {
+        if content.get(span.start..span.end) != Some(span.content.as_str()) {
+            return Err(ZhangError::DirectiveModified(span.filename.clone().unwrap_or_default()));
+        }
+        // the trailing spaces and newlines of span are kept, so that the layout of following content is not changed
+        let end = span.start + span.content.trim_end().len();
+        match self {
+            SpanEdit::Insert(directive) => {
+                let directive = directive.trim_end();
+                if span.start == content.len() && !content.is_empty() && !content.ends_with('\n') {
+                    content.push('\n');
+                    content.push_str(directive);
+                    content.push('\n');
+                } else {
+                    content.insert_str(span.start, &format!("{}\n", directive));
+                }
+            }
+            SpanEdit::Update(directive) => content.replace_range(span.start..end, directive.trim_end()),
+            SpanEdit::Delete => {
+                // remove the line break of directive as well
+                let end = match &content[end..] {
+                    rest if rest.starts_with("\r\n") => end + 2,
+                    rest if rest.starts_with('\n') => end + 1,
+                    _ => end,
+                };
+                content.replace_range(span.start..end, "");
+            }
+        }
+        Ok(content)
+    }
+}
+
+/// the profile deciding which `include-if` directives are followed while loading, the profile given by the caller
+/// takes precedence over the one declared by `option "profile"`
+#[derive(Debug, Clone, Default)]
+pub struct ProfileSelector {
+    given: Option<String>,
+    declared: Option<String>,
+}
+
+impl ProfileSelector {
+    pub fn new(profile: Option<String>) -> Self {
+        ProfileSelector {
+            given: profile,
+            declared: None,
+        }
+    }
+
+    pub fn active(&self) -> Option<&str> {
+        self.given.as_deref().or(self.declared.as_deref())
+    }
+
+    /// the file to be loaded next if directive is an include one, `option "profile"` is recorded along the way
+    /// so that it must be declared before the `include-if` directives
+    pub fn next_include(&mut self, directive: &Spanned<Directive>) -> Option<String> {
+        match &directive.data {
+            Directive::Option(option) if option.key.as_str() == KEY_PROFILE => {
+                self.declared = Some(option.value.as_str().to_owned());
+                None
+            }
+            Directive::Include(include) => match &include.profile {
+                Some(profile) if self.active() != Some(profile.as_str()) => None,
+                _ => Some(include.file.clone().to_plain_string()),
+            },
+            _ => None,
+        }
+    }
 }
 
+/// the contents of files which take precedence over the ones on disk, e.g. the unsaved buffers of editor
+pub type FileOverlay = Arc<RwLock<HashMap<PathBuf, String>>>;
+
 pub struct LocalFileSystemDataSource {
     data_type: Box<dyn DataType<Carrier = String> + 'static + Send + Sync>,
+    directive_cache: Option<DirectiveCache>,
+    store_snapshot: Option<StoreSnapshot>,
+    overlay: Option<FileOverlay>,
+    profile: Option<String>,
+    /// the keys of encrypted files declared by the files loaded last time
+    encryption_keys: RwLock<EncryptionKeys>,
+    /// the entry loaded last time, from which the relative path is resolved
+    entry: RwLock<Option<PathBuf>>,
 }
 
 impl LocalFileSystemDataSource {
     pub fn new<DT: DataType<Carrier = String> + Send + Sync + 'static>(data_type: DT) -> Self {
         LocalFileSystemDataSource {
             data_type: Box::new(data_type),
+            directive_cache: None,
+            store_snapshot: None,
+            overlay: None,
+            profile: None,
+            encryption_keys: RwLock::default(),
+            entry: RwLock::default(),
         }
     }
-    fn go_next(&self, directive: &Spanned<Directive>) -> Option<String> {
-        match &directive.data {
-            Directive::Include(include) => Some(include.file.clone().to_plain_string()),
-            _ => None,
-        }
+
+    /// cache the parsed directives of files, so that unchanged files skip parsing on next loading
+    pub fn with_directive_cache(mut self, directive_cache: DirectiveCache) -> Self {
+        self.directive_cache = Some(directive_cache);
+        self
+    }
+
+    /// snapshot the processed store, so that ledger is restored from it on next loading if no file is changed
+    pub fn with_store_snapshot(mut self, store_snapshot: StoreSnapshot) -> Self {
+        self.store_snapshot = Some(store_snapshot);
+        self
+    }
+
+    /// read the files from overlay if they are present in it
+    pub fn with_overlay(mut self, overlay: FileOverlay) -> Self {
+        self.overlay = Some(overlay);
+        self
+    }
+    /// load the `include-if` directives of profile, instead of the one declared by `option "profile"`
+    pub fn with_profile(mut self, profile: Option<String>) -> Self {
+        self.profile = profile;
+        self
     }
 
     pub(crate) fn create_folder_if_not_exist(filename: &std::path::Path) {
         std::fs::create_dir_all(filename.parent().unwrap()).expect("cannot create folder recursive");
     }
 
-    fn append_directive(&self, ledger: &Ledger, directive: Directive, file: Option<PathBuf>, check_file_visit: bool) -> ZhangResult<()> {
+    /// the file not visited yet is included into the main file, `visited_files` is updated by the included ones so that
+    /// the file is included once by the directives appended together. the file is not checked if `visited_files` is absent
+    fn append_directive(
+        &self, ledger: &Ledger, directive: Directive, file: Option<PathBuf>, visited_files: Option<&mut Vec<PathBuf>>,
+    ) -> ZhangResult<SpanInfo> {
         let (entry, main_file_endpoint) = &ledger.entry;
 
-        let endpoint = file.unwrap_or_else(|| {
-            if let Some(datetime) = directive.datetime() {
-                entry.join(PathBuf::from(format!("data/{}/{}.zhang", datetime.year(), datetime.month())))
-            } else {
-                entry.join(main_file_endpoint)
-            }
-        });
+        let endpoint = file.unwrap_or_else(|| entry.join(ledger.options.insert_strategy.endpoint(&directive).unwrap_or_else(|| main_file_endpoint.into())));
 
         LocalFileSystemDataSource::create_folder_if_not_exist(&endpoint);
 
-        if !has_path_visited(&ledger.visited_files, &endpoint) && check_file_visit {
+        if let Some(visited_files) = visited_files.filter(|visited_files| !has_path_visited(visited_files.iter(), &endpoint)) {
             let path = match endpoint.strip_prefix(entry) {
                 Ok(relative_path) => relative_path.to_str().unwrap(),
                 Err(_) => endpoint.to_str().unwrap(),
@@ -99,21 +271,32 @@ impl LocalFileSystemDataSource {
                 ledger,
                 Directive::Include(Include {
                     file: ZhangString::QuoteString(path.to_string()),
+                    profile: None,
                 }),
                 None,
-                false,
+                None,
             )?;
+            visited_files.push(endpoint.clone());
         }
 
-        let content_buf = ledger.data_source.get(endpoint.to_string_lossy().to_string())?;
+        // the file of new year or month is not created yet
+        let content_buf = match self.get(endpoint.to_string_lossy().to_string()) {
+            Err(ZhangError::IoError(e)) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+            result => result?,
+        };
         let content = String::from_utf8(content_buf)?;
 
-        let appended_content = format!("{}\n{}\n", content, self.data_type.export(Spanned::new(directive, SpanInfo::default())));
+        let exported = self.data_type.export(Spanned::new(directive, SpanInfo::default()));
+        let appended_content = format!("{}\n{}\n", content, exported);
 
-        ledger
-            .data_source
-            .save(ledger, endpoint.to_string_lossy().to_string(), appended_content.as_bytes())?;
-        Ok(())
+        self.save(ledger, endpoint.to_string_lossy().to_string(), appended_content.as_bytes())?;
+        Ok(SpanInfo {
+            start: content.len() + 1,
+            end: content.len() + 1 + exported.len(),
+            content: exported,
+            filename: Some(endpoint),
+            trivia: Default::default(),
+        })
     }
 }
 
@@ -123,62 +306,470 @@ impl DataSource for LocalFileSystemDataSource {
         Ok(self.data_type.export(Spanned::new(directive, SpanInfo::default())).into_bytes())
     }
 
+    fn store_snapshot(&self) -> Option<&StoreSnapshot> {
+        self.store_snapshot.as_ref()
+    }
+
+    /// the relative path is resolved from the ledger entry, e.g. the one of document
     fn get(&self, path: String) -> ZhangResult<Vec<u8>> {
-        Ok(std::fs::read(PathBuf::from(path))?)
+        let path = match self.entry.read().unwrap().as_ref() {
+            Some(entry) => entry.join(path),
+            None => PathBuf::from(path),
+        };
+        // the files of overlay are keyed by canonical path, the file only present in overlay is looked up as it is
+        let path = path.canonicalize().unwrap_or(path);
+        if let Some(content) = self.overlay.as_ref().and_then(|overlay| overlay.read().ok()?.get(&path).cloned()) {
+            return Ok(content.into_bytes());
+        }
+        if let Some(encryption) = Encryption::of(&path) {
+            return self.encryption_keys.read().unwrap().decrypt(encryption, &path);
+        }
+        Ok(std::fs::read(path)?)
     }
 
     fn load(&self, entry: String, endpoint: String) -> ZhangResult<LoadResult> {
         let entry = PathBuf::from(entry);
         let entry = entry.canonicalize().with_path(&entry)?;
+        *self.entry.write().unwrap() = Some(entry.clone());
         let main_endpoint = entry.join(endpoint);
         let main_endpoint = main_endpoint.canonicalize().with_path(&main_endpoint)?;
 
-        let mut load_queue: VecDeque<PathBuf> = VecDeque::new();
-        load_queue.push_back(main_endpoint);
+        // files are loaded level by level of the include tree, the files of the same level are independent
+        // so that they are parsed in parallel, the directives are merged in the same order as loading them one by one
+        let mut load_level: Vec<PathBuf> = vec![main_endpoint];
 
         let mut visited: Vec<PathBuf> = Vec::new();
-        let mut directives = vec![];
-        while let Some(pathbuf) = load_queue.pop_front() {
-            debug!("visited entry file: {:?}", pathbuf.display());
+        let mut runs = vec![];
+        let mut profile = ProfileSelector::new(self.profile.clone());
+        *self.encryption_keys.write().unwrap() = EncryptionKeys::default();
+        while !load_level.is_empty() {
+            let mut files: Vec<PathBuf> = Vec::with_capacity(load_level.len());
+            for pathbuf in load_level {
+                if !has_path_visited(&visited, &pathbuf) && !has_path_visited(&files, &pathbuf) {
+                    files.push(pathbuf);
+                }
+            }
 
-            if has_path_visited(&visited, &pathbuf) {
-                continue;
+            let parsed_files = files
+                .par_iter()
+                .map(|pathbuf| {
+                    debug!("visited entry file: {:?}", pathbuf.display());
+                    let source = pathbuf.to_string_lossy().to_string();
+                    let file_content = self.get(source.clone())?;
+                    let file_content = String::from_utf8_lossy(&file_content).to_string();
+                    // the plain directives of encrypted file are never cached on disk
+                    let directive_cache = self.directive_cache.as_ref().filter(|_| Encryption::of(pathbuf).is_none());
+                    DirectiveCache::parse_with_cache(directive_cache, &source, &file_content, || {
+                        self.data_type.transform(file_content.clone(), Some(source.clone()))
+                    })
+                })
+                .collect::<ZhangResult<Vec<_>>>()?;
+
+            load_level = vec![];
+            for (pathbuf, entity_directives) in files.into_iter().zip(parsed_files) {
+                let folder = pathbuf.parent().expect("file should have parent folder");
+                let mut encryption_keys = self.encryption_keys.write().unwrap();
+                entity_directives.iter().for_each(|directive| encryption_keys.handle_option(directive));
+                drop(encryption_keys);
+                for buf in entity_directives.iter().filter_map(|directive| profile.next_include(directive)) {
+                    if is_glob_pattern(&buf) {
+                        load_level.extend(expand_include_pattern(folder, &buf)?);
+                    } else if buf.starts_with('/') {
+                        load_level.push(PathBuf::from(&buf));
+                    } else {
+                        load_level.push(folder.join(buf));
+                    }
+                }
+                runs.push(entity_directives);
+                visited.push(pathbuf);
             }
-            let file_content = self.get(pathbuf.to_string_lossy().to_string())?;
-            let entity_directives = self
-                .data_type
-                .transform(String::from_utf8_lossy(&file_content).to_string(), Some(pathbuf.to_string_lossy().to_string()))?;
-
-            entity_directives.iter().filter_map(|directive| self.go_next(directive)).for_each(|buf| {
-                let fullpath = if buf.starts_with('/') {
-                    PathBuf::from(&buf)
-                } else {
-                    pathbuf.parent().map(|it| it.join(buf)).unwrap()
-                };
-                load_queue.push_back(fullpath);
-            });
-            directives.extend(entity_directives);
-            visited.push(pathbuf);
-        }
-        Ok(LoadResult {
-            directives,
-            visited_files: visited,
-        })
+        }
+        if let Some(directive_cache) = &self.directive_cache {
+            directive_cache.prune_unused();
+        }
+        Ok(LoadResult { runs, visited_files: visited })
     }
 
-    fn save(&self, _ledger: &Ledger, path: String, content: &[u8]) -> ZhangResult<()> {
-        std::fs::write(&path, content).with_path(PathBuf::from(path).as_path())
+    /// the relative path is resolved from the ledger entry, e.g. the one of uploaded document.
+    /// the content of encrypted file is encrypted again by the keys declared
+    fn save(&self, ledger: &Ledger, path: String, content: &[u8]) -> ZhangResult<()> {
+        let path = ledger.entry.0.join(path);
+        LocalFileSystemDataSource::create_folder_if_not_exist(&path);
+        match Encryption::of(&path) {
+            Some(encryption) => {
+                let encrypted = self.encryption_keys.read().unwrap().encrypt(encryption, content)?;
+                std::fs::write(&path, encrypted).with_path(path.as_path())
+            }
+            None => std::fs::write(&path, content).with_path(path.as_path()),
+        }
     }
 
-    fn append(&self, ledger: &Ledger, directives: Vec<Directive>) -> ZhangResult<()> {
-        for directive in directives {
-            self.append_directive(ledger, directive, None, true)?;
-        }
-        Ok(())
+    fn append(&self, ledger: &Ledger, directives: Vec<Directive>) -> ZhangResult<Vec<SpanInfo>> {
+        let mut visited_files = ledger.visited_files.clone();
+        directives
+            .into_iter()
+            .map(|directive| self.append_directive(ledger, directive, None, Some(&mut visited_files)))
+            .collect()
     }
 }
 
 pub struct LoadResult {
-    pub directives: Vec<Spanned<Directive>>,
+    /// the directives of each loaded file in the order of loading, which are kept apart so that they are merged by datetime lazily
+    pub runs: Vec<Vec<Spanned<Directive>>>,
     pub visited_files: Vec<PathBuf>,
 }
+
+impl LoadResult {
+    pub fn directives(&self) -> impl Iterator<Item = &Spanned<Directive>> {
+        self.runs.iter().flatten()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use indoc::indoc;
+    use itertools::Itertools;
+    use tempfile::tempdir;
+    use zhang_ast::{Directive, SpanInfo, ZhangString};
+
+    use crate::data_source::{DataSource, FileOverlay, LocalFileSystemDataSource};
+    use crate::data_type::text::ZhangDataType;
+    use crate::data_type::DataType;
+    use crate::directive_cache::DirectiveCache;
+    use crate::error::ZhangError;
+    use crate::ledger::Ledger;
+
+    fn span_of(content: &str, directive: &str) -> SpanInfo {
+        let start = content.find(directive).unwrap();
+        SpanInfo {
+            start,
+            end: start + directive.len(),
+            content: directive.to_owned(),
+            filename: None,
+            trivia: Default::default(),
+        }
+    }
+
+    #[test]
+    fn should_load_included_files_in_include_order() {
+        let temp_dir = tempdir().unwrap().into_path();
+        std::fs::write(
+            temp_dir.join("main.zhang"),
+            indoc! {r#"
+                include "a.zhang"
+                include "b.zhang"
+                option "title" "main"
+            "#},
+        )
+        .unwrap();
+        std::fs::write(
+            temp_dir.join("a.zhang"),
+            indoc! {r#"
+                include "c.zhang"
+                include "b.zhang"
+                option "title" "a"
+            "#},
+        )
+        .unwrap();
+        std::fs::write(temp_dir.join("b.zhang"), "option \"title\" \"b\"\n").unwrap();
+        std::fs::write(temp_dir.join("c.zhang"), "option \"title\" \"c\"\n").unwrap();
+
+        let source = LocalFileSystemDataSource::new(ZhangDataType {});
+        let result = source.load(temp_dir.to_string_lossy().to_string(), "main.zhang".to_owned()).unwrap();
+
+        let visited_files = result
+            .visited_files
+            .iter()
+            .map(|it| it.file_name().unwrap().to_string_lossy().to_string())
+            .collect_vec();
+        assert_eq!(vec!["main.zhang", "a.zhang", "b.zhang", "c.zhang"], visited_files);
+
+        let titles = result
+            .directives()
+            .filter_map(|it| match &it.data {
+                Directive::Option(option) => Some(option.value.as_str().to_owned()),
+                _ => None,
+            })
+            .collect_vec();
+        assert_eq!(vec!["main", "a", "b", "c"], titles);
+    }
+
+    #[test]
+    fn should_load_included_files_matching_glob_in_sorted_order() {
+        let temp_dir = tempdir().unwrap().into_path();
+        std::fs::create_dir_all(temp_dir.join("accounts/nested")).unwrap();
+        std::fs::write(
+            temp_dir.join("main.zhang"),
+            indoc! {r#"
+                include "accounts/*.zhang"
+                include "accounts/b.zhang"
+                option "title" "main"
+            "#},
+        )
+        .unwrap();
+        std::fs::write(temp_dir.join("accounts/c.zhang"), "option \"title\" \"c\"\n").unwrap();
+        std::fs::write(temp_dir.join("accounts/a.zhang"), "option \"title\" \"a\"\n").unwrap();
+        std::fs::write(temp_dir.join("accounts/b.zhang"), "option \"title\" \"b\"\n").unwrap();
+        std::fs::write(temp_dir.join("accounts/notes.txt"), "not a ledger").unwrap();
+        std::fs::write(temp_dir.join("accounts/nested/d.zhang"), "option \"title\" \"d\"\n").unwrap();
+
+        let source = LocalFileSystemDataSource::new(ZhangDataType {});
+        let result = source.load(temp_dir.to_string_lossy().to_string(), "main.zhang".to_owned()).unwrap();
+
+        let visited_files = result
+            .visited_files
+            .iter()
+            .map(|it| it.file_name().unwrap().to_string_lossy().to_string())
+            .collect_vec();
+        assert_eq!(vec!["main.zhang", "a.zhang", "b.zhang", "c.zhang"], visited_files);
+
+        std::fs::write(temp_dir.join("main.zhang"), "include \"accounts/**/*.zhang\"\n").unwrap();
+        let result = source.load(temp_dir.to_string_lossy().to_string(), "main.zhang".to_owned()).unwrap();
+        let visited_files = result
+            .visited_files
+            .iter()
+            .map(|it| it.strip_prefix(temp_dir.canonicalize().unwrap()).unwrap().to_string_lossy().to_string())
+            .collect_vec();
+        assert_eq!(
+            vec![
+                "main.zhang",
+                "accounts/a.zhang",
+                "accounts/b.zhang",
+                "accounts/c.zhang",
+                "accounts/nested/d.zhang"
+            ],
+            visited_files
+        );
+    }
+
+    #[test]
+    fn should_load_conditional_includes_of_active_profile() {
+        let temp_dir = tempdir().unwrap().into_path();
+        std::fs::write(
+            temp_dir.join("main.zhang"),
+            indoc! {r#"
+                option "profile" "personal"
+                include "common.zhang"
+                include-if "personal" "personal.zhang"
+                include-if "work" "work.zhang"
+            "#},
+        )
+        .unwrap();
+        for name in ["common", "personal", "work"] {
+            std::fs::write(temp_dir.join(format!("{name}.zhang")), format!("option \"title\" \"{name}\"\n")).unwrap();
+        }
+        let visited_files = |profile: Option<&str>| {
+            let source = LocalFileSystemDataSource::new(ZhangDataType {}).with_profile(profile.map(|it| it.to_owned()));
+            let result = source.load(temp_dir.to_string_lossy().to_string(), "main.zhang".to_owned()).unwrap();
+            result
+                .visited_files
+                .iter()
+                .map(|it| it.file_name().unwrap().to_string_lossy().to_string())
+                .collect_vec()
+        };
+
+        assert_eq!(vec!["main.zhang", "common.zhang", "personal.zhang"], visited_files(None));
+        assert_eq!(vec!["main.zhang", "common.zhang", "work.zhang"], visited_files(Some("work")));
+        assert_eq!(vec!["main.zhang", "common.zhang"], visited_files(Some("test")));
+    }
+
+    #[test]
+    fn should_load_files_from_overlay_first() {
+        let temp_dir = tempdir().unwrap().into_path().canonicalize().unwrap();
+        std::fs::write(temp_dir.join("main.zhang"), "option \"title\" \"disk\"\n").unwrap();
+
+        let overlay = FileOverlay::default();
+        let source = LocalFileSystemDataSource::new(ZhangDataType {})
+            .with_directive_cache(DirectiveCache::in_memory())
+            .with_overlay(overlay.clone());
+        overlay
+            .write()
+            .unwrap()
+            .insert(temp_dir.join("main.zhang"), "option \"title\" \"overlay\"\n".to_owned());
+
+        let result = source.load(temp_dir.to_string_lossy().to_string(), "main.zhang".to_owned()).unwrap();
+        let Directive::Option(option) = &result.directives().next().unwrap().data else {
+            panic!("should be option directive");
+        };
+        assert_eq!("overlay", option.value.as_str());
+
+        // the path is canonicalized before looking up the overlay
+        std::fs::create_dir(temp_dir.join("data")).unwrap();
+        assert_eq!(b"option \"title\" \"overlay\"\n".to_vec(), source.get("data/../main.zhang".to_owned()).unwrap());
+    }
+
+    mod span_edit {
+        use indoc::indoc;
+        use zhang_ast::SpanInfo;
+
+        use crate::data_source::test::span_of;
+        use crate::data_source::SpanEdit;
+        use crate::error::ZhangError;
+
+        const CONTENT: &str = indoc! {r#"
+            ; groceries
+            2023-01-01 "KFC"
+              Assets:Bank -10 CNY
+              Expenses:Food
+
+            2023-01-02 "Bakery" ; comment of bakery
+              Assets:Bank -5 CNY
+              Expenses:Food
+        "#};
+
+        #[test]
+        fn should_update_directive_and_keep_comments() {
+            let span = span_of(CONTENT, "2023-01-01 \"KFC\"\n  Assets:Bank -10 CNY\n  Expenses:Food");
+            let content = SpanEdit::Update("2023-01-01 \"McDonald\"\n  Assets:Bank -10 CNY\n  Expenses:Food\n")
+                .apply(CONTENT.to_owned(), &span)
+                .unwrap();
+            assert_eq!(
+                indoc! {r#"
+                    ; groceries
+                    2023-01-01 "McDonald"
+                      Assets:Bank -10 CNY
+                      Expenses:Food
+
+                    2023-01-02 "Bakery" ; comment of bakery
+                      Assets:Bank -5 CNY
+                      Expenses:Food
+                "#},
+                content
+            );
+        }
+
+        #[test]
+        fn should_delete_directive_with_its_line_break() {
+            let span = span_of(CONTENT, "2023-01-02 \"Bakery\" ; comment of bakery\n  Assets:Bank -5 CNY\n  Expenses:Food");
+            let content = SpanEdit::Delete.apply(CONTENT.to_owned(), &span).unwrap();
+            assert_eq!(
+                indoc! {r#"
+                    ; groceries
+                    2023-01-01 "KFC"
+                      Assets:Bank -10 CNY
+                      Expenses:Food
+
+                "#},
+                content
+            );
+        }
+
+        #[test]
+        fn should_insert_directive_before_span() {
+            let span = span_of(CONTENT, "2023-01-02 \"Bakery\"");
+            let content = SpanEdit::Insert("2023-01-02 balance Assets:Bank -10 CNY")
+                .apply(CONTENT.to_owned(), &span)
+                .unwrap();
+            assert!(content.contains("Expenses:Food\n\n2023-01-02 balance Assets:Bank -10 CNY\n2023-01-02 \"Bakery\""));
+        }
+
+        #[test]
+        fn should_insert_directive_at_end_of_file_without_line_break() {
+            let content = "option \"title\" \"main\"";
+            let span = SpanInfo::simple(content.len(), content.len());
+            let content = SpanEdit::Insert("option \"timezone\" \"Asia/Hong_Kong\"\n")
+                .apply(content.to_owned(), &span)
+                .unwrap();
+            assert_eq!("option \"title\" \"main\"\noption \"timezone\" \"Asia/Hong_Kong\"\n", content);
+        }
+
+        #[test]
+        fn should_reject_edit_if_file_is_modified() {
+            let span = span_of(CONTENT, "2023-01-01 \"KFC\"");
+            let modified = CONTENT.replace("; groceries", "; food");
+            let result = SpanEdit::Delete.apply(modified, &span);
+            assert!(matches!(result, Err(ZhangError::DirectiveModified(_))));
+        }
+    }
+
+    #[test]
+    fn should_update_directive_in_source_file() {
+        let temp_dir = tempdir().unwrap().into_path();
+        let file = temp_dir.join("main.zhang");
+        std::fs::write(
+            &file,
+            indoc! {r#"
+                option "title" "main"
+                ; the timezone
+                option "timezone" "Asia/Hong_Kong"
+            "#},
+        )
+        .unwrap();
+
+        let source = Arc::new(LocalFileSystemDataSource::new(ZhangDataType {}));
+        let ledger = Ledger::load_with_data_source(temp_dir.clone(), "main.zhang".to_owned(), source.clone()).unwrap();
+        let result = source.load(temp_dir.to_string_lossy().to_string(), "main.zhang".to_owned()).unwrap();
+        let title = result
+            .directives()
+            .find(|it| matches!(&it.data, Directive::Option(option) if option.key.as_str() == "title"))
+            .unwrap();
+        let Directive::Option(mut option) = title.data.clone() else { unreachable!() };
+        option.value = ZhangString::QuoteString("new title".to_owned());
+
+        source.update_directive(&ledger, &title.span, Directive::Option(option)).unwrap();
+        assert_eq!(
+            indoc! {r#"
+                option "title" "new title"
+                ; the timezone
+                option "timezone" "Asia/Hong_Kong"
+            "#},
+            std::fs::read_to_string(&file).unwrap()
+        );
+
+        // the span is outdated after updating
+        let result = source.delete_directive(&ledger, &title.span);
+        assert!(matches!(result, Err(ZhangError::DirectiveModified(_))));
+    }
+
+    #[test]
+    fn should_append_directives_to_unpadded_month_file_by_default() {
+        let temp_dir = tempdir().unwrap().into_path();
+        std::fs::write(temp_dir.join("main.zhang"), "option \"title\" \"main\"\n").unwrap();
+
+        let source = Arc::new(LocalFileSystemDataSource::new(ZhangDataType {}));
+        let ledger = Ledger::load_with_data_source(temp_dir.clone(), "main.zhang".to_owned(), source.clone()).unwrap();
+        let directives = ZhangDataType {}.transform("2024-01-05 open Assets:Bank\n".to_owned(), None).unwrap();
+        source.append(&ledger, directives.into_iter().map(|it| it.data).collect_vec()).unwrap();
+
+        let main = std::fs::read_to_string(temp_dir.join("main.zhang")).unwrap();
+        assert!(main.contains("include \"data/2024/1.zhang\""));
+        let monthly = std::fs::read_to_string(temp_dir.join("data/2024/1.zhang")).unwrap();
+        assert!(monthly.contains("2024-01-05 open Assets:Bank"));
+    }
+
+    #[test]
+    fn should_append_directives_to_file_of_insert_strategy() {
+        let temp_dir = tempdir().unwrap().into_path();
+        std::fs::write(temp_dir.join("main.zhang"), "option \"insert_strategy\" \"yearly\"\n").unwrap();
+
+        let source = Arc::new(LocalFileSystemDataSource::new(ZhangDataType {}));
+        let ledger = Ledger::load_with_data_source(temp_dir.clone(), "main.zhang".to_owned(), source.clone()).unwrap();
+        let directives = ZhangDataType {}
+            .transform(
+                indoc! {r#"
+                    2024-01-01 open Assets:Bank
+                    option "title" "main"
+                "#}
+                .to_owned(),
+                None,
+            )
+            .unwrap();
+        let spans = source.append(&ledger, directives.into_iter().map(|it| it.data).collect_vec()).unwrap();
+
+        let main = std::fs::read_to_string(temp_dir.join("main.zhang")).unwrap();
+        assert!(main.contains("include \"data/2024.zhang\""));
+        assert!(main.contains("option \"title\" \"main\""));
+        let yearly = std::fs::read_to_string(temp_dir.join("data/2024.zhang")).unwrap();
+        assert!(yearly.contains("2024-01-01 open Assets:Bank"));
+
+        // the returned span is the one of directive once the ledger is reloaded
+        assert_eq!(2, spans.len());
+        let reloaded = Ledger::load_with_data_source(temp_dir, "main.zhang".to_owned(), source).unwrap();
+        let open = reloaded.directives.iter().find(|it| matches!(it.data, Directive::Open(_))).unwrap();
+        assert_eq!(open.span.filename, spans[0].filename);
+        assert_eq!(open.span.start, spans[0].start);
+        assert_eq!(open.span.end, spans[0].end);
+    }
+}
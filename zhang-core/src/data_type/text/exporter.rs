@@ -23,6 +23,7 @@ impl ZhangDataTypeExportable for Date {
             Date::Date(date) => date.format("%Y-%m-%d").to_string(),
             Date::Datetime(datetime) => datetime.format("%Y-%m-%d %H:%M:%S").to_string(),
             Date::DateHour(datehour) => datehour.format("%Y-%m-%d %H:%M").to_string(),
+            Date::ZonedDatetime(datetime) => datetime.format("%Y-%m-%d %H:%M:%S%:z").to_string(),
         }
     }
 }
@@ -81,8 +82,12 @@ impl ZhangDataTypeExportable for StringOrAccount {
 impl ZhangDataTypeExportable for Transaction {
     type Output = String;
     fn export(self) -> String {
+        let date = match self.auxiliary_date {
+            Some(auxiliary_date) => format!("{}={}", self.date.export(), auxiliary_date.export()),
+            None => self.date.export(),
+        };
         let mut header = vec![
-            Some(self.date.export()),
+            Some(date),
             self.flag.map(|it| it.export()),
             self.payee.map(|it| it.export()),
             self.narration.map(|it| it.export()),
@@ -106,25 +111,72 @@ impl ZhangDataTypeExportable for Transaction {
     }
 }
 
+impl ZhangDataTypeExportable for Template {
+    type Output = String;
+    fn export(self) -> String {
+        let mut header = vec![self.date.export(), "template".to_owned(), self.name];
+        header.extend(self.payee.map(|it| it.export()));
+        header.extend(self.narration.map(|it| it.export()));
+        header.extend(self.tags.into_iter().map(|it| format!("#{}", it)));
+        header.extend(self.links.into_iter().map(|it| format!("^{}", it)));
+
+        let mut lines = vec![header.join(" ")];
+        for posting in self.postings {
+            let account = export_posting_account(posting.posting_type, posting.account);
+            lines.push(match posting.amount {
+                Some(amount) => format!("  {} {} {}", account, amount.expression, amount.currency),
+                None => format!("  {}", account),
+            });
+        }
+        lines.extend(self.meta.export().into_iter().map(|it| format!("  {}", it)));
+        lines.join("\n")
+    }
+}
+
+/// the account of posting, which is wrapped by `()` for virtual posting and `[]` for balanced virtual posting
+pub fn export_posting_account(posting_type: PostingType, account: Account) -> String {
+    match posting_type {
+        PostingType::Real => account.export(),
+        PostingType::Virtual => format!("({})", account.export()),
+        PostingType::BalancedVirtual => format!("[{}]", account.export()),
+    }
+}
+
+/// the lot of posting in the form of `{ 100 USD, 2024-01-01, "lot-A" }`, `None` if the posting has no lot component
+pub(crate) fn export_posting_lot(posting: &Posting) -> Option<String> {
+    let components = [
+        posting.cost.clone().map(|it| it.export()),
+        posting.cost_date.clone().map(|it| it.export()),
+        posting.cost_label.clone().map(|it| ZhangString::quote(it).export()),
+    ]
+    .into_iter()
+    .flatten()
+    .collect_vec();
+    if components.is_empty() {
+        None
+    } else {
+        Some(format!("{{ {} }}", components.join(", ")))
+    }
+}
+
 impl ZhangDataTypeExportable for Posting {
     type Output = Vec<String>;
     fn export(self) -> Vec<String> {
         // todo cost and price
-        let cost_string = if self.cost.is_some() || self.cost_date.is_some() {
-            let vec2 = vec![self.cost.map(|it| it.export()), self.cost_date.map(|it| it.export())];
-            Some(format!("{{ {} }}", vec2.into_iter().flatten().join(", ")))
-        } else {
-            None
-        };
+        let cost_string = export_posting_lot(&self);
         let vec1 = vec![
             self.flag.map(|it| format!(" {}", it.export())),
-            Some(self.account.export()),
+            Some(export_posting_account(self.posting_type, self.account)),
             self.units.map(|it| it.export()),
             cost_string,
             self.price.map(|it| it.export()),
         ];
+        let mut line = vec1.into_iter().flatten().join(" ");
+        if let Some(comment) = self.comment {
+            line = format!("{} ; {}", line, comment).trim_end().to_owned();
+        }
         let mut ret = self.meta.export().into_iter().map(|it| format!("  {}", it)).collect_vec();
-        ret.insert(0, vec1.into_iter().flatten().join(" "));
+        ret.insert(0, line);
 
         ret
     }
@@ -195,6 +247,14 @@ impl ZhangDataTypeExportable for BalanceCheck {
     }
 }
 
+impl ZhangDataTypeExportable for Pad {
+    type Output = String;
+    fn export(self) -> String {
+        let line = [self.date.export(), "pad".to_string(), self.account.export(), self.pad.export()];
+        append_meta(self.meta, line.join(" "))
+    }
+}
+
 impl ZhangDataTypeExportable for Note {
     type Output = String;
     fn export(self) -> String {
@@ -219,6 +279,79 @@ impl ZhangDataTypeExportable for Price {
     }
 }
 
+impl ZhangDataTypeExportable for Split {
+    type Output = String;
+    fn export(self) -> String {
+        let line = [
+            self.date.export(),
+            "split".to_string(),
+            self.currency,
+            format!("{}:{}", self.numerator, self.denominator),
+        ];
+        append_meta(self.meta, line.join(" "))
+    }
+}
+
+impl ZhangDataTypeExportable for Depreciation {
+    type Output = String;
+    fn export(self) -> String {
+        let line = [
+            self.date.export(),
+            "depreciation".to_string(),
+            self.account.export(),
+            self.expense_account.export(),
+            self.amount.export(),
+            self.method.to_string(),
+            self.lifetime.to_string(),
+        ];
+        append_meta(self.meta, line.join(" "))
+    }
+}
+
+impl ZhangDataTypeExportable for Loan {
+    type Output = String;
+    fn export(self) -> String {
+        let line = [
+            self.date.export(),
+            "loan".to_string(),
+            self.account.export(),
+            self.principal.export(),
+            format!("{}%", self.rate),
+            self.term.to_string(),
+            self.payment_account.export(),
+            self.interest_account.export(),
+        ];
+        append_meta(self.meta, line.join(" "))
+    }
+}
+
+impl ZhangDataTypeExportable for OpenBalance {
+    type Output = String;
+    fn export(self) -> String {
+        let mut line = vec![self.date.export(), "open-balance".to_string(), self.account.export(), self.amount.export()];
+        if let Some(equity_account) = self.equity_account {
+            line.push(equity_account.export());
+        }
+        append_meta(self.meta, line.join(" "))
+    }
+}
+
+impl ZhangDataTypeExportable for Accrue {
+    type Output = String;
+    fn export(self) -> String {
+        let line = [
+            self.date.export(),
+            "accrue".to_string(),
+            self.deferral_account.export(),
+            self.account.export(),
+            self.amount.export(),
+            self.periods.to_string(),
+            self.interval.to_string(),
+        ];
+        append_meta(self.meta, line.join(" "))
+    }
+}
+
 impl ZhangDataTypeExportable for Event {
     type Output = String;
     fn export(self) -> String {
@@ -257,7 +390,17 @@ impl ZhangDataTypeExportable for Plugin {
 impl ZhangDataTypeExportable for Include {
     type Output = String;
     fn export(self) -> String {
-        let line = ["include".to_string(), self.file.export()];
+        match self.profile {
+            Some(profile) => ["include-if".to_string(), profile.export(), self.file.export()].join(" "),
+            None => ["include".to_string(), self.file.export()].join(" "),
+        }
+    }
+}
+
+impl ZhangDataTypeExportable for Rename {
+    type Output = String;
+    fn export(self) -> String {
+        let line = ["rename".to_string(), self.from.export(), self.to.export()];
         line.join(" ")
     }
 }
@@ -312,17 +455,26 @@ impl ZhangDataTypeExportable for Directive {
             Directive::Close(close) => close.export(),
             Directive::Commodity(commodity) => commodity.export(),
             Directive::Transaction(txn) => txn.export(),
+            Directive::Template(template) => template.export(),
             Directive::BalancePad(pad) => pad.export(),
             Directive::BalanceCheck(check) => check.export(),
+            Directive::Pad(pad) => pad.export(),
             Directive::Note(note) => note.export(),
             Directive::Document(document) => document.export(),
             Directive::Price(price) => price.export(),
+            Directive::Split(split) => split.export(),
+            Directive::Depreciation(depreciation) => depreciation.export(),
+            Directive::Loan(loan) => loan.export(),
+            Directive::Accrue(accrue) => accrue.export(),
+            Directive::OpenBalance(open_balance) => open_balance.export(),
             Directive::Event(event) => event.export(),
             Directive::Custom(custom) => custom.export(),
             Directive::Option(options) => options.export(),
             Directive::Plugin(plugin) => plugin.export(),
             Directive::Include(include) => include.export(),
             Directive::Comment(comment) => comment.export(),
+            Directive::Malformed(malformed) => malformed.content,
+            Directive::Rename(rename) => rename.export(),
             Directive::Budget(budget) => budget.export(),
             Directive::BudgetAdd(budget_add) => budget_add.export(),
             Directive::BudgetTransfer(budget_transfer) => budget_transfer.export(),
@@ -331,6 +483,42 @@ impl ZhangDataTypeExportable for Directive {
     }
 }
 
+/// attach the comments of trivia to the exported lines of directive
+pub fn attach_trivia(mut lines: Vec<String>, trivia: &Trivia) -> Vec<String> {
+    if let (Some(comment), Some(header)) = (&trivia.comment, lines.first_mut()) {
+        header.push(' ');
+        header.push_str(comment);
+    }
+    let mut commented = vec![false; lines.len()];
+    for (key, comment) in &trivia.meta_comments {
+        let prefix = format!("{}: ", key);
+        let position = (1..lines.len()).find(|idx| !commented[*idx] && lines[*idx].trim_start().starts_with(&prefix));
+        if let Some(idx) = position {
+            commented[idx] = true;
+            lines[idx].push(' ');
+            lines[idx].push_str(comment);
+        }
+    }
+    lines
+}
+
+/// export the directive with its comments
+pub fn export_with_trivia(directive: Spanned<Directive>) -> String {
+    let lines = directive.data.export().lines().map(|it| it.to_owned()).collect_vec();
+    attach_trivia(lines, &directive.span.trivia).join("\n")
+}
+
+/// export the directives of file with their comments and the blank lines between them
+pub fn export_directives(directives: Vec<Spanned<Directive>>) -> String {
+    let mut content = String::new();
+    for directive in directives {
+        content.push_str(&"\n".repeat(directive.span.trivia.blank_lines));
+        content.push_str(&export_with_trivia(directive));
+        content.push('\n');
+    }
+    content
+}
+
 impl ZhangDataTypeExportable for Ledger {
     type Output = String;
     fn export(self) -> String {
@@ -344,6 +532,7 @@ mod test {
 
     use indoc::indoc;
 
+    use crate::data_type::text::exporter::export_directives;
     use crate::data_type::text::ZhangDataType;
     use crate::data_type::DataType;
 
@@ -408,6 +597,26 @@ mod test {
         );
     }
 
+    #[test]
+    fn rename() {
+        assert_parse!(
+            "rename directive",
+            indoc! {r#"
+            rename Assets:OldBank Assets:NewBank
+        "#}
+        );
+    }
+
+    #[test]
+    fn pad() {
+        assert_parse!(
+            "pad directive",
+            indoc! {r#"
+            1970-01-01 pad Assets:hello Equity:Opening-Balances
+        "#}
+        );
+    }
+
     #[test]
     fn close() {
         assert_parse!(
@@ -536,6 +745,45 @@ mod test {
               Expenses:TestCategory:One 1 CCC @@ 1 CNY
         "#}
         );
+
+        assert_parse!(
+            "transaction posting with lot label",
+            indoc! {r#"
+            1970-01-01 * "Payee" "Narration"
+              Assets:Broker 10 AAPL { 12.3 USD, 2024-01-05, "lot-A" }
+              Assets:Broker -5 AAPL { "lot-A" } @ 15 USD
+        "#}
+        );
+
+        assert_parse!(
+            "transaction directive with timezone offset",
+            indoc! {r#"
+            1970-01-01 10:00:00+09:00 * "Payee" "Narration"
+              Assets:123 -1 CNY
+              Expenses:TestCategory:One 1 CNY
+        "#}
+        );
+
+        assert_parse!(
+            "transaction directive with auxiliary date",
+            indoc! {r#"
+            1970-01-01=1970-01-03 * "Payee" "Narration"
+              Assets:123 -1 CNY
+              Expenses:TestCategory:One 1 CNY
+        "#}
+        );
+
+        assert_parse!(
+            "transaction directive with virtual postings",
+            indoc! {r#"
+            1970-01-01 * "Payee" "Narration"
+              Assets:123 -1 CNY
+              Expenses:TestCategory:One 1 CNY
+              (Assets:Budget:Food) -1 CNY
+              [Equity:Budget:Food] 1 CNY
+              [Equity:Budget:Saving:储蓄]
+        "#}
+        );
     }
 
     #[test]
@@ -568,6 +816,104 @@ mod test {
         );
     }
 
+    #[test]
+    fn split() {
+        assert_parse!(
+            "split directive ",
+            indoc! {r#"
+            1970-01-01 split AAPL 4:1
+        "#}
+        );
+        assert_parse!(
+            "reverse split directive ",
+            indoc! {r#"
+            1970-01-01 split AAPL 1:10
+        "#}
+        );
+    }
+
+    #[test]
+    fn depreciation() {
+        assert_parse!(
+            "straight-line depreciation directive",
+            indoc! {r#"
+            1970-01-01 depreciation Assets:Equipment:Laptop Expenses:Depreciation 3600 CNY straight-line 36
+        "#}
+        );
+        assert_parse!(
+            "declining-balance depreciation directive with meta",
+            indoc! {r#"
+            1970-01-01 depreciation Assets:Equipment:Car Expenses:Depreciation 120000 CNY declining-balance 60
+              note: "company car"
+        "#}
+        );
+    }
+
+    #[test]
+    fn loan() {
+        assert_parse!(
+            "loan directive",
+            indoc! {r#"
+            1970-01-01 loan Liabilities:Mortgage 300000 CNY 4.9% 360 Assets:Bank Expenses:Interest
+        "#}
+        );
+    }
+
+    #[test]
+    fn accrue() {
+        assert_parse!(
+            "accrue directive",
+            indoc! {r#"
+            1970-01-01 accrue Assets:Prepaid:Insurance Expenses:Insurance 1200 CNY 12 monthly
+        "#}
+        );
+        assert_parse!(
+            "accrue directive of income",
+            indoc! {r#"
+            1970-01-01 accrue Liabilities:Unearned Income:Consulting -4000 CNY 4 quarterly
+        "#}
+        );
+    }
+
+    #[test]
+    fn template() {
+        assert_parse!(
+            "template directive",
+            indoc! {r#"
+            1970-01-01 template rent "Landlord" "Monthly rent" #home
+              Assets:Bank -{total} CNY
+              (Assets:Receivable:Roommate) ({total} - {deposit}) / 2 CNY
+              Expenses:Rent
+              note: "shared with roommate"
+        "#}
+        );
+        assert_parse!(
+            "template directive without payee",
+            indoc! {r#"
+            1970-01-01 template utility
+              Assets:Bank -{amount} CNY
+              Expenses:Utility {amount} CNY
+        "#}
+        );
+    }
+
+    #[test]
+    fn open_balance() {
+        assert_parse!(
+            "open-balance directive",
+            indoc! {r#"
+            1970-01-01 open-balance Assets:Bank 1000 CNY
+        "#}
+        );
+        assert_parse!(
+            "open-balance directive with equity account and meta",
+            indoc! {r#"
+            1970-01-01 open-balance Liabilities:Card -200 CNY Equity:Migration
+              alias: "credit card"
+        "#}
+        );
+    }
+
     #[test]
     fn event() {
         assert_parse!(
@@ -620,6 +966,12 @@ mod test {
             include "file path"
         "#}
         );
+        assert_parse!(
+            "include-if directive ",
+            indoc! {r#"
+            include-if "work" "file path"
+        "#}
+        );
     }
 
     #[test]
@@ -650,4 +1002,22 @@ mod test {
             "#}
         );
     }
+
+    #[test]
+    fn should_export_directives_with_comments_and_blank_lines() {
+        let content = indoc! {r#"
+            ; groceries
+
+            2024-01-05 * "KFC" ; weekly lunch
+              Assets:Bank -25.5 CNY
+                card: "visa" // the old card
+              Expenses:Food ; paid by card
+              location: "Shanghai" # near office
+
+
+            1970-01-01 open Assets:Bank ; the salary card
+        "#};
+        let directives = ZhangDataType {}.transform(content.to_owned(), None).unwrap();
+        assert_eq!(content, export_directives(directives));
+    }
 }
@@ -0,0 +1,238 @@
+//! the formatter of zhang file.
+//!
+//! the directives are re-emitted with canonical dates, sorted metas and two spaces indentation, the amounts of postings
+//! are aligned across the whole file. the blank lines and comments kept in the trivia of directives are written back.
+
+use itertools::Itertools;
+use zhang_ast::{Directive, Meta, Posting, Spanned, Transaction, Trivia};
+
+use crate::data_type::text::exporter::{attach_trivia, export_posting_account, export_posting_lot, ZhangDataTypeExportable};
+use crate::data_type::text::parser::{parse, Rule};
+use crate::{ZhangError, ZhangResult};
+
+/// the width of account and number columns of postings
+#[derive(Default)]
+struct Alignment {
+    account: usize,
+    number: usize,
+}
+
+impl Alignment {
+    fn of(directives: &[Spanned<Directive>]) -> Self {
+        let mut alignment = Alignment::default();
+        for directive in directives {
+            if let Directive::Transaction(trx) = &directive.data {
+                for posting in trx.postings.iter().filter(|it| it.units.is_some()) {
+                    alignment.account = alignment.account.max(posting_account(posting).chars().count());
+                    alignment.number = alignment.number.max(posting.units.as_ref().unwrap().number.to_string().len());
+                }
+            }
+        }
+        alignment
+    }
+}
+
+fn posting_account(posting: &Posting) -> String {
+    let account = export_posting_account(posting.posting_type, posting.account.clone());
+    match &posting.flag {
+        Some(flag) => format!("{} {}", flag.clone().export(), account),
+        None => account,
+    }
+}
+
+fn posting_lines(posting: &Posting, alignment: &Alignment) -> Vec<String> {
+    let account = posting_account(posting);
+    let mut line = match &posting.units {
+        Some(units) => format!(
+            "  {:<account_width$}  {:>number_width$} {}",
+            account,
+            units.number.to_string(),
+            units.currency,
+            account_width = alignment.account,
+            number_width = alignment.number
+        ),
+        None => format!("  {}", account),
+    };
+    if let Some(lot) = export_posting_lot(posting) {
+        line.push_str(&format!(" {}", lot));
+    }
+    if let Some(price) = &posting.price {
+        line.push_str(&format!(" {}", price.clone().export()));
+    }
+    if let Some(comment) = &posting.comment {
+        line.push_str(&format!(" ; {}", comment));
+    }
+    let mut lines = vec![line];
+    lines.extend(posting.meta.clone().export().into_iter().map(|it| format!("    {}", it)));
+    lines
+}
+
+fn transaction_lines(trx: &Transaction, alignment: &Alignment) -> Vec<String> {
+    let header = Transaction {
+        postings: vec![],
+        meta: Meta::default(),
+        ..trx.clone()
+    };
+    let mut lines = vec![header.export()];
+    lines.extend(trx.postings.iter().flat_map(|posting| posting_lines(posting, alignment)));
+    lines.extend(trx.meta.clone().export().into_iter().map(|it| format!("  {}", it)));
+    lines
+}
+
+fn same_comments(a: &Trivia, b: &Trivia) -> bool {
+    a.comment == b.comment && a.meta_comments.iter().sorted().eq(b.meta_comments.iter().sorted())
+}
+
+fn format_directive(directive: &Spanned<Directive>, alignment: &Alignment) -> String {
+    let rendered = match &directive.data {
+        Directive::Transaction(trx) => transaction_lines(trx, alignment),
+        other => other.clone().export().lines().map(|it| it.to_owned()).collect_vec(),
+    };
+    let formatted = attach_trivia(rendered, &directive.span.trivia)
+        .into_iter()
+        .map(|it| it.trim_end().to_owned())
+        .join("\n");
+    // the directive is kept as it is if some comments cannot be placed
+    let equivalent = parse(&formatted, None)
+        .map(|it| it.len() == 1 && it[0].data == directive.data && same_comments(&it[0].span.trivia, &directive.span.trivia))
+        .unwrap_or(false);
+    if equivalent {
+        formatted
+    } else {
+        directive.span.content.lines().map(|it| it.trim_end()).join("\n")
+    }
+}
+
+fn parse_error(e: pest_consume::Error<Rule>) -> ZhangError {
+    ZhangError::PestError {
+        path: String::new(),
+        msg: e.to_string(),
+    }
+}
+
+/// format the content of zhang file
+pub fn format(content: &str) -> ZhangResult<String> {
+    let directives = parse(content, None).map_err(parse_error)?;
+    let alignment = Alignment::of(&directives);
+
+    let mut formatted = String::with_capacity(content.len());
+    for directive in directives.iter().sorted_by_key(|it| it.span.start) {
+        formatted.push_str(&"\n".repeat(directive.span.trivia.blank_lines));
+        formatted.push_str(&format_directive(directive, &alignment));
+        formatted.push('\n');
+    }
+    let mut formatted = formatted.trim_end().to_owned();
+    if !formatted.is_empty() {
+        formatted.push('\n');
+    }
+
+    // the formatting must not change the meaning of file
+    let reformatted = parse(&formatted, None).map_err(parse_error)?;
+    let equivalent = reformatted.len() == directives.len() && reformatted.iter().zip(directives.iter()).all(|(a, b)| a.data == b.data);
+    if !equivalent {
+        return Err(ZhangError::CustomError(
+            "the formatted content is not equivalent to the original one".to_owned(),
+        ));
+    }
+    Ok(formatted)
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use crate::data_type::text::formatter::format;
+
+    #[test]
+    fn should_align_amounts_and_normalize_layout() {
+        let formatted = format(indoc! {r#"
+            option "title" "Example"
+            2024-1-5 * "KFC" "Lunch"
+                Assets:Bank -25.5 CNY
+                    note: "card"
+                Expenses:Food 25.5 CNY
+                location: "Shanghai"
+                category: "food"
+
+
+
+            2024-01-06 open Assets:Cash
+        "#})
+        .unwrap();
+        assert_eq!(
+            indoc! {r#"
+                option "title" "Example"
+                2024-01-05 * "KFC" "Lunch"
+                  Assets:Bank    -25.5 CNY
+                    note: "card"
+                  Expenses:Food   25.5 CNY
+                  category: "food"
+                  location: "Shanghai"
+
+
+
+                2024-01-06 open Assets:Cash
+            "#},
+            formatted
+        );
+    }
+
+    #[test]
+    fn should_preserve_comments() {
+        let formatted = format(indoc! {r#"
+            ; the groceries
+            2024-01-05 "KFC" ; weekly lunch
+              Assets:Bank  -25.5 CNY ; paid by card
+              Expenses:Food
+              location: "Shanghai" ; near office
+            2024-01-06 open Assets:Cash // opened at bank
+        "#})
+        .unwrap();
+        assert_eq!(
+            indoc! {r#"
+                ; the groceries
+                2024-01-05 "KFC" ; weekly lunch
+                  Assets:Bank  -25.5 CNY ; paid by card
+                  Expenses:Food
+                  location: "Shanghai" ; near office
+                2024-01-06 open Assets:Cash // opened at bank
+            "#},
+            formatted
+        );
+    }
+
+    #[test]
+    fn should_keep_virtual_postings() {
+        let formatted = format(indoc! {r#"
+            2024-01-05 "KFC"
+              Assets:Bank -25.5 CNY
+              Expenses:Food
+              (Assets:Budget:Food) -25.5 CNY
+              [Equity:Budget:Food] 25.5 CNY
+              [Equity:Budget:Available]
+        "#})
+        .unwrap();
+        assert_eq!(
+            indoc! {r#"
+                2024-01-05 "KFC"
+                  Assets:Bank           -25.5 CNY
+                  Expenses:Food
+                  (Assets:Budget:Food)  -25.5 CNY
+                  [Equity:Budget:Food]   25.5 CNY
+                  [Equity:Budget:Available]
+            "#},
+            formatted
+        );
+    }
+
+    #[test]
+    fn should_be_idempotent() {
+        let content = indoc! {r#"
+            2024-01-05 "KFC"
+              Assets:Bank -1000 CNY
+              Expenses:Food 1000 CNY { 1 USD } @ 7 CNY
+        "#};
+        let formatted = format(content).unwrap();
+        assert_eq!(formatted, format(&formatted).unwrap());
+    }
+}
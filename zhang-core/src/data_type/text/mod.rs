@@ -2,16 +2,18 @@ use std::path::PathBuf;
 
 use zhang_ast::{Directive, Spanned};
 
-use crate::data_type::text::exporter::ZhangDataTypeExportable;
-use crate::data_type::text::parser::parse;
+use crate::data_type::text::exporter::export_with_trivia;
+use crate::data_type::text::parser::parse_recovering;
 use crate::data_type::DataType;
-use crate::{ZhangError, ZhangResult};
+use crate::ZhangResult;
 
 #[allow(clippy::upper_case_acronyms)]
 #[allow(clippy::type_complexity)]
 pub mod parser;
 
 pub mod exporter;
+pub mod formatter;
+pub mod sorter;
 
 #[derive(Default)]
 pub struct ZhangDataType {}
@@ -20,14 +22,11 @@ impl DataType for ZhangDataType {
     type Carrier = String;
 
     fn transform(&self, raw_data: Self::Carrier, source: Option<String>) -> ZhangResult<Vec<Spanned<Directive>>> {
-        let file = source.clone().map(PathBuf::from);
-        parse(&raw_data, file).map_err(|it| ZhangError::PestError {
-            path: source.unwrap_or_default(),
-            msg: it.to_string(),
-        })
+        let file = source.map(PathBuf::from);
+        Ok(parse_recovering(&raw_data, file))
     }
 
     fn export(&self, directive: Spanned<Directive>) -> Self::Carrier {
-        directive.data.export()
+        export_with_trivia(directive)
     }
 }
@@ -1,11 +1,12 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::str::FromStr;
 
-use bigdecimal::BigDecimal;
-use chrono::{NaiveDate, NaiveDateTime};
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
 use itertools::Itertools;
 use once_cell::sync::OnceCell;
-use pest::iterators::Pairs;
+use pest::iterators::{Pair, Pairs};
 use pest::pratt_parser::PrattParser;
 use pest_consume::{match_nodes, Error, Parser};
 use snailquote::unescape;
@@ -20,6 +21,21 @@ type Node<'i> = pest_consume::Node<'i, Rule, ()>;
 #[grammar = "data_type/text/zhang.pest"]
 pub struct ZhangParser;
 
+/// the cost, cost date, lot label and price of posting
+#[derive(Default)]
+struct PostingMeta {
+    cost: Option<Amount>,
+    cost_date: Option<Date>,
+    cost_label: Option<String>,
+    price: Option<SingleTotalPrice>,
+}
+
+enum LotComponent {
+    Cost(Amount),
+    Date(Date),
+    Label(String),
+}
+
 /// Construct a global [PrattParser] to handle number expressions.
 fn pratt_number_parser() -> &'static PrattParser<Rule> {
     static PARSER: OnceCell<PrattParser<Rule>> = OnceCell::new();
@@ -57,6 +73,54 @@ fn parse_number_expr(pairs: Pairs<Rule>) -> Result<BigDecimal> {
         .parse(pairs)
 }
 
+/// the template expression is calculated in the same way as [number_expr], the placeholders are replaced by given values
+fn evaluate_template_expr_pairs(pairs: Pairs<Rule>, values: &HashMap<String, BigDecimal>) -> std::result::Result<BigDecimal, String> {
+    pratt_number_parser()
+        .map_primary(|primary| match primary.as_rule() {
+            Rule::number => ZhangParser::number(Node::new(primary)).map_err(|e| e.to_string()),
+            Rule::placeholder => {
+                let name = primary.as_str().trim_start_matches('{').trim_end_matches('}');
+                values
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| format!("the value of placeholder {{{}}} is missing", name))
+            }
+            Rule::template_expr => evaluate_template_expr_pairs(primary.into_inner(), values),
+            rule => unreachable!("Unexpected template expr {:?}", rule),
+        })
+        .map_infix(|lhs, op, rhs| match op.as_rule() {
+            Rule::add => Ok(lhs? + rhs?),
+            Rule::subtract => Ok(lhs? - rhs?),
+            Rule::multiply => Ok(lhs? * rhs?),
+            Rule::divide => {
+                let (lhs, rhs) = (lhs?, rhs?);
+                if rhs.is_zero() {
+                    return Err("division by zero".to_owned());
+                }
+                Ok(lhs / rhs)
+            }
+            rule => unreachable!("Unexpected infix operation {:?}", rule),
+        })
+        .map_prefix(|op, rhs| match op.as_rule() {
+            Rule::unary_minus => Ok(-rhs?),
+            rule => unreachable!("Unexpected prefix operation {:?}", rule),
+        })
+        .parse(pairs)
+}
+
+/// evaluate the amount expression of template, e.g. `({total} - {deposit}) / 2`, with the values of placeholders
+pub fn evaluate_template_expr(expression: &str, values: &HashMap<String, BigDecimal>) -> std::result::Result<BigDecimal, String> {
+    let entry = ZhangParser::parse(Rule::template_expr_entry, expression).map_err(|e| e.to_string())?;
+    let expr = entry
+        .single()
+        .map_err(|e| e.to_string())?
+        .into_pair()
+        .into_inner()
+        .next()
+        .ok_or_else(|| format!("invalid template expression: {}", expression))?;
+    evaluate_template_expr_pairs(expr.into_inner(), values)
+}
+
 #[pest_consume::parser]
 impl ZhangParser {
     #[allow(dead_code)]
@@ -108,9 +172,41 @@ impl ZhangParser {
             components: r.1,
         })
     }
+    fn bracketed_account_part(input: Node) -> Result<String> {
+        Ok(input.as_str().to_owned())
+    }
+    fn bracketed_account_name(input: Node) -> Result<Account> {
+        let (account_type, components): (String, Vec<String>) = match_nodes!(input.into_children();
+            [account_type(a), bracketed_account_part(i)..] => (a, i.collect()),
+        );
+        Ok(Account {
+            account_type: AccountType::from_str(&account_type).expect("invalid account type"),
+            content: format!("{}:{}", &account_type, components.join(":")),
+            components,
+        })
+    }
+    fn posting_account(input: Node) -> Result<(PostingType, Account)> {
+        let ret = match_nodes!(input.into_children();
+            [account_name(account)] => (PostingType::Real, account),
+            [virtual_account(account)] => (PostingType::Virtual, account),
+            [balanced_virtual_account(account)] => (PostingType::BalancedVirtual, account),
+        );
+        Ok(ret)
+    }
+    fn virtual_account(input: Node) -> Result<Account> {
+        Ok(match_nodes!(input.into_children();
+            [account_name(account)] => account,
+        ))
+    }
+    fn balanced_virtual_account(input: Node) -> Result<Account> {
+        Ok(match_nodes!(input.into_children();
+            [bracketed_account_name(account)] => account,
+        ))
+    }
     fn date(input: Node) -> Result<Date> {
         let datetime: Date = match_nodes!(input.into_children();
             [date_only(d)] => d,
+            [zoned_datetime(d)] => d,
             [datetime(d)] => d,
             [date_hour(d)] => d
         );
@@ -126,6 +222,15 @@ impl ZhangParser {
             NaiveDateTime::parse_from_str(input.as_str(), "%Y-%m-%d %H:%M:%S").expect("cannot construct naive datetime"),
         ))
     }
+    fn zoned_datetime(input: Node) -> Result<Date> {
+        let content = match input.as_str().strip_suffix('Z') {
+            Some(datetime) => format!("{}+00:00", datetime),
+            None => input.as_str().to_owned(),
+        };
+        Ok(Date::ZonedDatetime(
+            DateTime::parse_from_str(&content, "%Y-%m-%d %H:%M:%S%:z").expect("cannot construct zoned datetime"),
+        ))
+    }
     fn date_hour(input: Node) -> Result<Date> {
         Ok(Date::DateHour(
             NaiveDateTime::parse_from_str(input.as_str(), "%Y-%m-%d %H:%M").expect("cannot construct naive date hour"),
@@ -206,15 +311,26 @@ impl ZhangParser {
         Ok(ret)
     }
 
+    fn trailing_comment(input: Node) -> Result<Option<String>> {
+        Ok(Some(input.as_str().trim().to_owned()).filter(|it| !it.is_empty()))
+    }
+
+    fn key_value_line_with_comment(input: Node) -> Result<(String, ZhangString)> {
+        let ret: (String, ZhangString) = match_nodes!(input.into_children();
+            [key_value_line(line), trailing_comment(_)] => line,
+        );
+        Ok(ret)
+    }
+
     fn metas(input: Node) -> Result<Meta> {
         let ret: Vec<(String, ZhangString)> = match_nodes!(input.into_children();
-            [key_value_line(lines)..] => lines.collect(),
+            [key_value_line_with_comment(lines)..] => lines.collect(),
         );
         Ok(ret.into_iter().collect())
     }
 
-    fn posting_unit(input: Node) -> Result<(Option<Amount>, Option<(Option<Amount>, Option<Date>, Option<SingleTotalPrice>)>)> {
-        let ret: (Option<Amount>, Option<(Option<Amount>, Option<Date>, Option<SingleTotalPrice>)>) = match_nodes!(input.into_children();
+    fn posting_unit(input: Node) -> Result<(Option<Amount>, Option<PostingMeta>)> {
+        let ret: (Option<Amount>, Option<PostingMeta>) = match_nodes!(input.into_children();
             [posting_amount(amount)] => (Some(amount), None),
             [posting_meta(meta)] => (None, Some(meta)),
             [posting_amount(amount), posting_meta(meta)] => (Some(amount), Some(meta)),
@@ -259,43 +375,56 @@ impl ZhangParser {
         );
         Ok(ret)
     }
-    fn posting_meta(input: Node) -> Result<(Option<Amount>, Option<Date>, Option<SingleTotalPrice>)> {
-        let ret: (Option<Amount>, Option<Date>, Option<SingleTotalPrice>) = match_nodes!(input.into_children();
-            [] => (None, None, None),
-            [posting_cost(cost)] => (Some(cost), None, None),
-            [posting_price(p)] => (None, None, Some(p)),
-            [posting_cost(cost), date(d)] => (Some(cost), Some(d), None),
-            [posting_cost(cost), posting_price(p)] => (Some(cost), None, Some(p)),
-            [posting_cost(cost), date(d), posting_price(p)] => (Some(cost), Some(d), Some(p)),
+    fn lot_component(input: Node) -> Result<LotComponent> {
+        let ret: LotComponent = match_nodes!(input.into_children();
+            [date(date)] => LotComponent::Date(date),
+            [posting_cost(cost)] => LotComponent::Cost(cost),
+            [quote_string(label)] => LotComponent::Label(label.to_plain_string()),
         );
         Ok(ret)
     }
+    fn posting_meta(input: Node) -> Result<PostingMeta> {
+        let (components, price): (Vec<LotComponent>, Option<SingleTotalPrice>) = match_nodes!(input.into_children();
+            [lot_component(components).., posting_price(p)] => (components.collect(), Some(p)),
+            [lot_component(components)..] => (components.collect(), None),
+        );
+        let mut meta = PostingMeta {
+            price,
+            ..PostingMeta::default()
+        };
+        // the components of lot can be written in any order, like `{ 2024-01-05, "lot-A", 12.3 USD }`
+        for component in components {
+            match component {
+                LotComponent::Cost(cost) => meta.cost = Some(cost),
+                LotComponent::Date(date) => meta.cost_date = Some(date),
+                LotComponent::Label(label) => meta.cost_label = Some(label),
+            }
+        }
+        Ok(meta)
+    }
     fn transaction_posting(input: Node) -> Result<Posting> {
-        let ret: (
-            Option<Flag>,
-            Account,
-            Option<(Option<Amount>, Option<(Option<Amount>, Option<Date>, Option<SingleTotalPrice>)>)>,
-            Meta,
-        ) = match_nodes!(input.into_children();
-            [account_name(account_name)] => (None, account_name, None, Meta::default()),
-            [account_name(account_name), posting_unit(unit)] => (None, account_name, Some(unit), Meta::default()),
-            [transaction_flag(flag), account_name(account_name)] => (flag, account_name, None, Meta::default()),
-            [transaction_flag(flag), account_name(account_name), posting_unit(unit)] => (flag, account_name, Some(unit), Meta::default()),
-
-            [account_name(account_name), metas(meta)] => (None, account_name, None, meta),
-            [account_name(account_name), posting_unit(unit), metas(meta)] => (None, account_name, Some(unit), meta),
-            [transaction_flag(flag), account_name(account_name), metas(meta)] => (flag, account_name, None, meta),
-            [transaction_flag(flag), account_name(account_name), posting_unit(unit), metas(meta)] => (flag, account_name, Some(unit), meta),
+        let ret: (Option<Flag>, (PostingType, Account), Option<(Option<Amount>, Option<PostingMeta>)>, Meta) = match_nodes!(input.into_children();
+            [posting_account(account)] => (None, account, None, Meta::default()),
+            [posting_account(account), posting_unit(unit)] => (None, account, Some(unit), Meta::default()),
+            [transaction_flag(flag), posting_account(account)] => (flag, account, None, Meta::default()),
+            [transaction_flag(flag), posting_account(account), posting_unit(unit)] => (flag, account, Some(unit), Meta::default()),
+
+            [posting_account(account), metas(meta)] => (None, account, None, meta),
+            [posting_account(account), posting_unit(unit), metas(meta)] => (None, account, Some(unit), meta),
+            [transaction_flag(flag), posting_account(account), metas(meta)] => (flag, account, None, meta),
+            [transaction_flag(flag), posting_account(account), posting_unit(unit), metas(meta)] => (flag, account, Some(unit), meta),
         );
 
-        let (flag, account, unit, meta) = ret;
+        let (flag, (posting_type, account), unit, meta) = ret;
 
         let mut line = Posting {
             flag,
+            posting_type,
             account,
             units: None,
             cost: None,
             cost_date: None,
+            cost_label: None,
             price: None,
             comment: None,
             meta,
@@ -305,9 +434,10 @@ impl ZhangParser {
             line.units = amount;
 
             if let Some(meta) = meta {
-                line.cost = meta.0;
-                line.cost_date = meta.1;
-                line.price = meta.2;
+                line.cost = meta.cost;
+                line.cost_date = meta.cost_date;
+                line.cost_label = meta.cost_label;
+                line.price = meta.price;
             }
         }
         Ok(line)
@@ -355,9 +485,17 @@ impl ZhangParser {
         Ok(ret)
     }
 
+    fn transaction_date(input: Node) -> Result<(Date, Option<Date>)> {
+        let ret = match_nodes!(input.into_children();
+            [date(date)] => (date, None),
+            [date(date), date(auxiliary_date)] => (date, Some(auxiliary_date)),
+        );
+        Ok(ret)
+    }
+
     fn transaction(input: Node) -> Result<Directive> {
         let ret: (
-            Date,
+            (Date, Option<Date>),
             Option<Flag>,
             Option<ZhangString>,
             Option<ZhangString>,
@@ -365,14 +503,15 @@ impl ZhangParser {
             Vec<String>,
             Vec<(Option<Posting>, Option<(String, ZhangString)>)>,
         ) = match_nodes!(input.into_children();
-            [date(date), quote_string(payee), tags(tags), links(links), transaction_lines(lines)] => (date, None, Some(payee), None, tags, links,lines),
-            [date(date), quote_string(payee), quote_string(narration), tags(tags), links(links), transaction_lines(lines)] => (date, None, Some(payee), Some(narration), tags, links,lines),
-            [date(date), transaction_flag(flag), tags(tags), links(links), transaction_lines(lines)] => (date, flag, None, None, tags, links, lines),
-            [date(date), transaction_flag(flag), quote_string(narration), tags(tags), links(links), transaction_lines(lines)] => (date, flag, None, Some(narration), tags, links, lines),
-            [date(date), transaction_flag(flag), quote_string(payee), quote_string(narration), tags(tags), links(links), transaction_lines(lines)] => (date, flag, Some(payee), Some(narration), tags, links,lines),
+            [transaction_date(date), quote_string(payee), tags(tags), links(links), trailing_comment(_), transaction_lines(lines)] => (date, None, Some(payee), None, tags, links,lines),
+            [transaction_date(date), quote_string(payee), quote_string(narration), tags(tags), links(links), trailing_comment(_), transaction_lines(lines)] => (date, None, Some(payee), Some(narration), tags, links,lines),
+            [transaction_date(date), transaction_flag(flag), tags(tags), links(links), trailing_comment(_), transaction_lines(lines)] => (date, flag, None, None, tags, links, lines),
+            [transaction_date(date), transaction_flag(flag), quote_string(narration), tags(tags), links(links), trailing_comment(_), transaction_lines(lines)] => (date, flag, None, Some(narration), tags, links, lines),
+            [transaction_date(date), transaction_flag(flag), quote_string(payee), quote_string(narration), tags(tags), links(links), trailing_comment(_), transaction_lines(lines)] => (date, flag, Some(payee), Some(narration), tags, links,lines),
         );
         let mut transaction = Transaction {
-            date: ret.0,
+            date: ret.0 .0,
+            auxiliary_date: ret.0 .1,
             flag: ret.1,
             payee: ret.2,
             narration: ret.3,
@@ -397,6 +536,79 @@ impl ZhangParser {
         Ok(Directive::Transaction(transaction))
     }
 
+    fn template_expr(input: Node) -> Result<String> {
+        Ok(input.as_str().to_owned())
+    }
+
+    fn template_amount(input: Node) -> Result<TemplateAmount> {
+        let ret: TemplateAmount = match_nodes!(input.into_children();
+            [template_expr(expression), commodity_name(currency)] => TemplateAmount { expression, currency },
+        );
+        Ok(ret)
+    }
+
+    fn template_posting(input: Node) -> Result<TemplatePosting> {
+        let ret: ((PostingType, Account), Option<TemplateAmount>) = match_nodes!(input.into_children();
+            [posting_account(account)] => (account, None),
+            [posting_account(account), template_amount(amount)] => (account, Some(amount)),
+        );
+        let ((posting_type, account), amount) = ret;
+        Ok(TemplatePosting { posting_type, account, amount })
+    }
+
+    fn template_line(input: Node) -> Result<(Option<TemplatePosting>, Option<(String, ZhangString)>)> {
+        let ret: (Option<TemplatePosting>, Option<(String, ZhangString)>) = match_nodes!(input.into_children();
+            [template_posting(posting)] => (Some(posting), None),
+            [template_posting(posting), valuable_comment(_)] => (Some(posting), None),
+            [key_value_line(meta)] => (None, Some(meta)),
+            [key_value_line(meta), valuable_comment(_)] => (None, Some(meta)),
+        );
+        Ok(ret)
+    }
+
+    fn template_lines(input: Node) -> Result<Vec<(Option<TemplatePosting>, Option<(String, ZhangString)>)>> {
+        let ret = match_nodes!(input.into_children();
+            [template_line(lines)..] => lines.collect(),
+        );
+        Ok(ret)
+    }
+
+    fn template(input: Node) -> Result<Directive> {
+        let ret: (
+            Date,
+            ZhangString,
+            Option<ZhangString>,
+            Option<ZhangString>,
+            Vec<String>,
+            Vec<String>,
+            Vec<(Option<TemplatePosting>, Option<(String, ZhangString)>)>,
+        ) = match_nodes!(input.into_children();
+            [date(date), unquote_string(name), tags(tags), links(links), trailing_comment(_), template_lines(lines)] => (date, name, None, None, tags, links, lines),
+            [date(date), unquote_string(name), quote_string(narration), tags(tags), links(links), trailing_comment(_), template_lines(lines)] => (date, name, None, Some(narration), tags, links, lines),
+            [date(date), unquote_string(name), quote_string(payee), quote_string(narration), tags(tags), links(links), trailing_comment(_), template_lines(lines)] => (date, name, Some(payee), Some(narration), tags, links, lines),
+        );
+        let mut template = Template {
+            date: ret.0,
+            name: ret.1.to_plain_string(),
+            payee: ret.2,
+            narration: ret.3,
+            tags: ret.4.into_iter().collect(),
+            links: ret.5.into_iter().collect(),
+            postings: vec![],
+            meta: Meta::default(),
+        };
+        for line in ret.6 {
+            match line {
+                (Some(posting), None) => template.postings.push(posting),
+                (None, Some(meta)) => {
+                    template.meta.insert(meta.0, meta.1);
+                }
+                _ => {}
+            }
+        }
+        Ok(Directive::Template(template))
+    }
+
     fn commodity(input: Node) -> Result<Directive> {
         let ret = match_nodes!(input.into_children();
             [date(date), commodity_name(name)] => (date, name, Meta::default()),
@@ -434,10 +646,24 @@ impl ZhangParser {
         let ret: ZhangString = match_nodes!(input.into_children();
             [quote_string(path)] => path,
         );
-        let include = Include { file: ret };
+        let include = Include { file: ret, profile: None };
         Ok(Directive::Include(include))
     }
 
+    fn include_if(input: Node) -> Result<Directive> {
+        let (profile, file): (ZhangString, ZhangString) = match_nodes!(input.into_children();
+            [quote_string(profile), quote_string(file)] => (profile, file),
+        );
+        Ok(Directive::Include(Include { file, profile: Some(profile) }))
+    }
+
+    fn rename(input: Node) -> Result<Directive> {
+        let (from, to): (Account, Account) = match_nodes!(input.into_children();
+            [account_name(from), account_name(to)] => (from, to),
+        );
+        Ok(Directive::Rename(Rename { from, to }))
+    }
+
     fn note(input: Node) -> Result<Directive> {
         let ret: (Date, Account, ZhangString) = match_nodes!(input.into_children();
             [date(date), account_name(a), string(path)] => (date, a, path),
@@ -487,6 +713,18 @@ impl ZhangParser {
         }
     }
 
+    fn pad(input: Node) -> Result<Directive> {
+        let ret: (Date, Account, Account) = match_nodes!(input.into_children();
+            [date(date), account_name(name), account_name(pad)] => (date, name, pad),
+        );
+        Ok(Directive::Pad(Pad {
+            date: ret.0,
+            account: ret.1,
+            pad: ret.2,
+            meta: Default::default(),
+        }))
+    }
+
     fn document(input: Node) -> Result<Directive> {
         let ret: (Date, Account, ZhangString) = match_nodes!(input.into_children();
             [date(date), account_name(name), string(path)] => (date, name, path),
@@ -513,6 +751,104 @@ impl ZhangParser {
         }))
     }
 
+    fn split(input: Node) -> Result<Directive> {
+        let ret: (Date, String, BigDecimal, BigDecimal) = match_nodes!(input.into_children();
+            [date(date), commodity_name(commodity), number(numerator), number(denominator)] => (date, commodity, numerator, denominator)
+        );
+        Ok(Directive::Split(Split {
+            date: ret.0,
+            currency: ret.1,
+            numerator: ret.2,
+            denominator: ret.3,
+            meta: Default::default(),
+        }))
+    }
+
+    fn depreciation_method(input: Node) -> Result<DepreciationMethod> {
+        Ok(DepreciationMethod::from_str(input.as_str()).expect("invalid depreciation method"))
+    }
+
+    fn unsigned_integer(input: Node) -> Result<u32> {
+        input.as_str().parse::<u32>().map_err(|e| input.error(e))
+    }
+
+    fn percentage(input: Node) -> Result<BigDecimal> {
+        let ret: BigDecimal = match_nodes!(input.into_children();
+            [number(number)] => number
+        );
+        Ok(ret)
+    }
+
+    fn depreciation(input: Node) -> Result<Directive> {
+        let ret: (Date, Account, Account, Amount, DepreciationMethod, u32) = match_nodes!(input.into_children();
+            [date(date), account_name(account), account_name(expense_account), posting_amount(amount), depreciation_method(method), unsigned_integer(lifetime)] => {
+                (date, account, expense_account, amount, method, lifetime)
+            }
+        );
+        Ok(Directive::Depreciation(Depreciation {
+            date: ret.0,
+            account: ret.1,
+            expense_account: ret.2,
+            amount: ret.3,
+            method: ret.4,
+            lifetime: ret.5,
+            meta: Default::default(),
+        }))
+    }
+
+    fn loan(input: Node) -> Result<Directive> {
+        let ret: (Date, Account, Amount, BigDecimal, u32, Account, Account) = match_nodes!(input.into_children();
+            [date(date), account_name(account), posting_amount(principal), percentage(rate), unsigned_integer(term), account_name(payment_account), account_name(interest_account)] => {
+                (date, account, principal, rate, term, payment_account, interest_account)
+            }
+        );
+        Ok(Directive::Loan(Loan {
+            date: ret.0,
+            account: ret.1,
+            principal: ret.2,
+            rate: ret.3,
+            term: ret.4,
+            payment_account: ret.5,
+            interest_account: ret.6,
+            meta: Default::default(),
+        }))
+    }
+
+    fn accrual_interval(input: Node) -> Result<AccrualInterval> {
+        Ok(AccrualInterval::from_str(input.as_str()).expect("invalid accrual interval"))
+    }
+
+    fn open_balance(input: Node) -> Result<Directive> {
+        let ret: (Date, Account, Amount, Option<Account>) = match_nodes!(input.into_children();
+            [date(date), account_name(account), posting_amount(amount)] => (date, account, amount, None),
+            [date(date), account_name(account), posting_amount(amount), account_name(equity_account)] => (date, account, amount, Some(equity_account)),
+        );
+        Ok(Directive::OpenBalance(OpenBalance {
+            date: ret.0,
+            account: ret.1,
+            amount: ret.2,
+            equity_account: ret.3,
+            meta: Default::default(),
+        }))
+    }
+
+    fn accrue(input: Node) -> Result<Directive> {
+        let ret: (Date, Account, Account, Amount, u32, AccrualInterval) = match_nodes!(input.into_children();
+            [date(date), account_name(deferral_account), account_name(account), posting_amount(amount), unsigned_integer(periods), accrual_interval(interval)] => {
+                (date, deferral_account, account, amount, periods, interval)
+            }
+        );
+        Ok(Directive::Accrue(Accrue {
+            date: ret.0,
+            deferral_account: ret.1,
+            account: ret.2,
+            amount: ret.3,
+            periods: ret.4,
+            interval: ret.5,
+            meta: Default::default(),
+        }))
+    }
+
     fn budget(input: Node) -> Result<Directive> {
         let ret: (Date, ZhangString, String, Meta) = match_nodes!(input.into_children();
             [date(date), unquote_string(name), commodity_name(commodity)] => (date, name, commodity, Meta::default()),
@@ -566,21 +902,27 @@ impl ZhangParser {
 
     fn metable_head(input: Node) -> Result<Directive> {
         let ret: Directive = match_nodes!(input.into_children();
-            [open(item)] => item,
-            [close(item)] => item,
-            [note(item)] => item,
-            [event(item)] => item,
-            [document(item)] => item,
-            [balance(item)] => item,
-            [price(item)] => item,
-            [commodity(item)] => item,
-            [custom(item)] => item,
-            [comment(item)] => item,
-            [budget(item)] => item,
-            [budget_close(item)] => item,
-            [budget_add(item)] => item,
-            [budget_transfer(item)] => item,
-            [plugin(item)] => item,
+            [open(item), trailing_comment(_)] => item,
+            [close(item), trailing_comment(_)] => item,
+            [note(item), trailing_comment(_)] => item,
+            [event(item), trailing_comment(_)] => item,
+            [document(item), trailing_comment(_)] => item,
+            [balance(item), trailing_comment(_)] => item,
+            [pad(item), trailing_comment(_)] => item,
+            [price(item), trailing_comment(_)] => item,
+            [split(item), trailing_comment(_)] => item,
+            [depreciation(item), trailing_comment(_)] => item,
+            [loan(item), trailing_comment(_)] => item,
+            [accrue(item), trailing_comment(_)] => item,
+            [open_balance(item), trailing_comment(_)] => item,
+            [commodity(item), trailing_comment(_)] => item,
+            [custom(item), trailing_comment(_)] => item,
+            [comment(item), trailing_comment(_)] => item,
+            [budget(item), trailing_comment(_)] => item,
+            [budget_close(item), trailing_comment(_)] => item,
+            [budget_add(item), trailing_comment(_)] => item,
+            [budget_transfer(item), trailing_comment(_)] => item,
+            [plugin(item), trailing_comment(_)] => item,
         );
         Ok(ret)
     }
@@ -589,18 +931,24 @@ impl ZhangParser {
     }
     fn item(input: Node) -> Result<Option<(Directive, SpanInfo)>> {
         let span = input.as_span();
-        let span_info = SpanInfo {
+        let mut span_info = SpanInfo {
             start: span.start_pos().pos(),
             end: span.end_pos().pos(),
             content: span.as_str().to_string(),
             filename: None,
+            trivia: Default::default(),
         };
+        collect_trivia(input.as_pair().clone(), &mut span_info.trivia);
         let ret: Option<Directive> = match_nodes!(input.into_children();
-            [option(item)] => Some(item),
-            [include(item)] => Some(item),
-            [valuable_comment(item)] => Some(Directive::Comment(Comment { content:item })),
+            [option(item), trailing_comment(_)] => Some(item),
+            [include(item), trailing_comment(_)] => Some(item),
+            [include_if(item), trailing_comment(_)] => Some(item),
+            [rename(item), trailing_comment(_)] => Some(item),
+            // the prefix of comment is kept so that it can be exported as it is
+            [valuable_comment(_), trailing_comment(_)] => Some(Directive::Comment(Comment { content: span_info.content.trim().to_owned() })),
 
             [transaction(item)] => Some(item),
+            [template(item)] => Some(item),
             [empty_space_line(_)] => None,
             [metable_head(head)] => Some(head),
             [metable_head(head), metas(meta)] => {
@@ -623,16 +971,120 @@ impl ZhangParser {
     }
 }
 
+/// the key of meta line, the quote of key is removed
+fn meta_key(key_value_line: Pair<Rule>) -> String {
+    let key = key_value_line.into_inner().next().map(|it| it.as_str()).unwrap_or_default();
+    key.strip_prefix('"').and_then(|it| it.strip_suffix('"')).unwrap_or(key).to_owned()
+}
+
+/// collect the comments of directive header and meta lines, the comments of postings are kept in postings
+fn collect_trivia(pair: Pair<Rule>, trivia: &mut Trivia) {
+    match pair.as_rule() {
+        Rule::trailing_comment => {
+            let comment = pair.as_str().trim();
+            if !comment.is_empty() && trivia.comment.is_none() {
+                trivia.comment = Some(comment.to_owned());
+            }
+        }
+        Rule::key_value_line_with_comment => {
+            let mut children = pair.into_inner();
+            if let (Some(line), Some(comment)) = (children.next(), children.next()) {
+                let comment = comment.as_str().trim();
+                if !comment.is_empty() {
+                    trivia.meta_comments.push((meta_key(line), comment.to_owned()));
+                }
+            }
+        }
+        Rule::transaction_line | Rule::template_line => {
+            let mut children = pair.into_inner();
+            match (children.next(), children.next()) {
+                (Some(line), Some(comment)) if line.as_rule() == Rule::key_value_line => {
+                    trivia.meta_comments.push((meta_key(line), comment.as_str().trim().to_owned()));
+                }
+                (Some(posting), _) => collect_trivia(posting, trivia),
+                _ => {}
+            }
+        }
+        _ => pair.into_inner().for_each(|it| collect_trivia(it, trivia)),
+    }
+}
+
+fn fill_spans(input_str: &str, file: Option<PathBuf>, directives: &mut [Spanned<Directive>]) {
+    let mut cursor = 0;
+    for (idx, directive) in directives.iter_mut().enumerate() {
+        directive.span.filename.clone_from(&file);
+        // the line breaks between directives, except the one ending the previous directive
+        let line_breaks = input_str[cursor..directive.span.start].matches('\n').count();
+        directive.span.trivia.blank_lines = if idx == 0 { line_breaks } else { line_breaks.saturating_sub(1) };
+        cursor = directive.span.end;
+    }
+}
+
 pub fn parse(input_str: &str, file: impl Into<Option<PathBuf>>) -> Result<Vec<Spanned<Directive>>> {
-    let file = file.into();
     let inputs = ZhangParser::parse(Rule::entry, input_str)?;
     let input = inputs.single()?;
     ZhangParser::entry(input).map(|mut directives| {
-        directives.iter_mut().for_each(|directive| directive.span.filename.clone_from(&file));
+        fill_spans(input_str, file.into(), &mut directives);
         directives
     })
 }
 
+/// the byte ranges of top level blocks, a block starts from the line which is not indented,
+/// and the following indented and blank lines belong to it
+fn blocks(input_str: &str) -> Vec<(usize, usize)> {
+    let mut blocks: Vec<(usize, usize)> = vec![];
+    let mut line_start = 0;
+    for line in input_str.split_inclusive('\n') {
+        let line_end = line_start + line.len();
+        let is_block_start = !line.starts_with([' ', '\t']) && !line.trim().is_empty();
+        match blocks.last_mut() {
+            Some(block) if !is_block_start => block.1 = line_end,
+            _ if is_block_start => blocks.push((line_start, line_end)),
+            _ => {}
+        }
+        line_start = line_end;
+    }
+    blocks
+}
+
+/// parse the content and skip the malformed directives instead of failing the whole file.
+/// each top level block which cannot be parsed is kept as `Malformed` directive
+pub fn parse_recovering(input_str: &str, file: impl Into<Option<PathBuf>>) -> Vec<Spanned<Directive>> {
+    let file = file.into();
+    if let Ok(directives) = parse(input_str, file.clone()) {
+        return directives;
+    }
+    let mut directives = vec![];
+    for (start, end) in blocks(input_str) {
+        let block = &input_str[start..end];
+        match parse(block, None) {
+            Ok(block_directives) => directives.extend(block_directives.into_iter().map(|mut directive| {
+                directive.span.start += start;
+                directive.span.end += start;
+                directive
+            })),
+            Err(e) => {
+                let content = block.trim_end();
+                directives.push(Spanned::new(
+                    Directive::Malformed(Malformed {
+                        content: content.to_owned(),
+                        message: e.variant.message().to_string(),
+                    }),
+                    SpanInfo {
+                        start,
+                        end: start + content.len(),
+                        content: content.to_owned(),
+                        filename: None,
+                        trivia: Default::default(),
+                    },
+                ))
+            }
+        }
+    }
+    fill_spans(input_str, file, &mut directives);
+    directives
+}
+
 #[cfg(test)]
 mod test {
     use zhang_ast::{Directive, Transaction};
@@ -737,6 +1189,191 @@ mod test {
                 balance.data
             )
         }
+
+        #[test]
+        fn should_parse_pad() {
+            let pad = parse("2101-10-10 pad Assets:Hello Equity:Opening-Balances", None).unwrap().remove(0);
+            assert_eq!(
+                Directive::Pad(Pad {
+                    date: Date::Date(NaiveDate::from_ymd_opt(2101, 10, 10).unwrap()),
+                    account: Account::from_str("Assets:Hello").unwrap(),
+                    pad: Account::from_str("Equity:Opening-Balances").unwrap(),
+                    meta: Default::default()
+                }),
+                pad.data
+            )
+        }
+    }
+    mod rename {
+        use std::str::FromStr;
+
+        use zhang_ast::*;
+
+        use crate::data_type::text::parser::parse;
+
+        #[test]
+        fn should_parse() {
+            let rename = parse("rename Assets:OldBank Assets:NewBank", None).unwrap().remove(0);
+            assert_eq!(
+                Directive::Rename(Rename {
+                    from: Account::from_str("Assets:OldBank").unwrap(),
+                    to: Account::from_str("Assets:NewBank").unwrap(),
+                }),
+                rename.data
+            )
+        }
+    }
+    mod split {
+        use bigdecimal::BigDecimal;
+        use chrono::NaiveDate;
+        use zhang_ast::*;
+
+        use crate::data_type::text::parser::parse;
+
+        #[test]
+        fn should_parse() {
+            let split = parse("2024-06-10 split AAPL 4:1", None).unwrap().remove(0);
+            assert_eq!(
+                Directive::Split(Split {
+                    date: Date::Date(NaiveDate::from_ymd_opt(2024, 6, 10).unwrap()),
+                    currency: "AAPL".to_owned(),
+                    numerator: BigDecimal::from(4i32),
+                    denominator: BigDecimal::from(1i32),
+                    meta: Default::default()
+                }),
+                split.data
+            )
+        }
+    }
+    mod depreciation {
+        use std::str::FromStr;
+
+        use bigdecimal::BigDecimal;
+        use chrono::NaiveDate;
+        use zhang_ast::amount::Amount;
+        use zhang_ast::*;
+
+        use crate::data_type::text::parser::parse;
+
+        #[test]
+        fn should_parse() {
+            let depreciation = parse(
+                "2024-01-01 depreciation Assets:Equipment Expenses:Depreciation 3600 CNY declining-balance 36",
+                None,
+            )
+            .unwrap()
+            .remove(0);
+            assert_eq!(
+                Directive::Depreciation(Depreciation {
+                    date: Date::Date(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()),
+                    account: Account::from_str("Assets:Equipment").unwrap(),
+                    expense_account: Account::from_str("Expenses:Depreciation").unwrap(),
+                    amount: Amount::new(BigDecimal::from(3600i32), "CNY"),
+                    method: DepreciationMethod::DecliningBalance,
+                    lifetime: 36,
+                    meta: Default::default()
+                }),
+                depreciation.data
+            )
+        }
+    }
+    mod loan {
+        use std::str::FromStr;
+
+        use bigdecimal::BigDecimal;
+        use chrono::NaiveDate;
+        use zhang_ast::amount::Amount;
+        use zhang_ast::*;
+
+        use crate::data_type::text::parser::parse;
+
+        #[test]
+        fn should_parse() {
+            let loan = parse("2024-01-01 loan Liabilities:Mortgage 300000 CNY 4.9% 360 Assets:Bank Expenses:Interest", None)
+                .unwrap()
+                .remove(0);
+            assert_eq!(
+                Directive::Loan(Loan {
+                    date: Date::Date(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()),
+                    account: Account::from_str("Liabilities:Mortgage").unwrap(),
+                    principal: Amount::new(BigDecimal::from(300000i32), "CNY"),
+                    rate: BigDecimal::from_str("4.9").unwrap(),
+                    term: 360,
+                    payment_account: Account::from_str("Assets:Bank").unwrap(),
+                    interest_account: Account::from_str("Expenses:Interest").unwrap(),
+                    meta: Default::default()
+                }),
+                loan.data
+            )
+        }
+    }
+    mod accrue {
+        use std::str::FromStr;
+
+        use bigdecimal::BigDecimal;
+        use chrono::NaiveDate;
+        use zhang_ast::amount::Amount;
+        use zhang_ast::*;
+
+        use crate::data_type::text::parser::parse;
+
+        #[test]
+        fn should_parse() {
+            let accrue = parse("2024-01-01 accrue Assets:Prepaid Expenses:Insurance 1200 CNY 12 monthly", None)
+                .unwrap()
+                .remove(0);
+            assert_eq!(
+                Directive::Accrue(Accrue {
+                    date: Date::Date(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()),
+                    deferral_account: Account::from_str("Assets:Prepaid").unwrap(),
+                    account: Account::from_str("Expenses:Insurance").unwrap(),
+                    amount: Amount::new(BigDecimal::from(1200i32), "CNY"),
+                    periods: 12,
+                    interval: AccrualInterval::Monthly,
+                    meta: Default::default()
+                }),
+                accrue.data
+            )
+        }
+    }
+    mod open_balance {
+        use std::str::FromStr;
+
+        use bigdecimal::BigDecimal;
+        use chrono::NaiveDate;
+        use zhang_ast::amount::Amount;
+        use zhang_ast::*;
+
+        use crate::data_type::text::parser::parse;
+
+        #[test]
+        fn should_parse_with_or_without_equity_account() {
+            let open_balance = parse("2024-01-01 open-balance Assets:Bank 1000 CNY", None).unwrap().remove(0);
+            assert_eq!(
+                Directive::OpenBalance(OpenBalance {
+                    date: Date::Date(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()),
+                    account: Account::from_str("Assets:Bank").unwrap(),
+                    amount: Amount::new(BigDecimal::from(1000i32), "CNY"),
+                    equity_account: None,
+                    meta: Default::default()
+                }),
+                open_balance.data
+            );
+
+            let open_balance = parse("2024-01-01 open-balance Liabilities:Card -200 CNY Equity:Migration", None)
+                .unwrap()
+                .remove(0);
+            assert_eq!(
+                Directive::OpenBalance(OpenBalance {
+                    date: Date::Date(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()),
+                    account: Account::from_str("Liabilities:Card").unwrap(),
+                    amount: Amount::new(BigDecimal::from(-200i32), "CNY"),
+                    equity_account: Some(Account::from_str("Equity:Migration").unwrap()),
+                    meta: Default::default()
+                }),
+                open_balance.data
+            )
+        }
     }
     mod options {
 
@@ -1081,6 +1718,26 @@ mod test {
                 assert_eq!(None, posting.price);
             }
             #[test]
+            fn should_return_lot_label_given_components_in_any_order() {
+                let mut trx = get_first_posting(indoc! {r#"
+                2022-06-02 "balanced transaction"
+                  Assets:Card 10 AAPL { 2024-01-05, "lot-A", 12.3 USD }
+                "#});
+                let posting = trx.postings.pop().unwrap();
+                assert_eq!(Some(Amount::new(BigDecimal::from_str("12.3").unwrap(), "USD")), posting.cost);
+                assert_eq!(Some(Date::Date(NaiveDate::from_ymd_opt(2024, 1, 5).unwrap())), posting.cost_date);
+                assert_eq!(Some("lot-A".to_owned()), posting.cost_label);
+
+                let mut trx = get_first_posting(indoc! {r#"
+                2022-06-02 "balanced transaction"
+                  Assets:Card -10 AAPL {"lot-A"} @ 15 USD
+                "#});
+                let posting = trx.postings.pop().unwrap();
+                assert_eq!(None, posting.cost);
+                assert_eq!(Some("lot-A".to_owned()), posting.cost_label);
+                assert_eq!(Some(SingleTotalPrice::Single(Amount::new(BigDecimal::from(15i32), "USD"))), posting.price);
+            }
+            #[test]
             fn should_return_unit_and_single_price() {
                 let mut trx = get_first_posting(indoc! {r#"
                 2022-06-02 "balanced transaction"
@@ -1273,4 +1930,87 @@ mod test {
             }
         }
     }
+
+    mod trivia {
+        use indoc::indoc;
+        use zhang_ast::Directive;
+
+        use crate::data_type::text::parser::parse;
+
+        #[test]
+        fn should_parse_comments_and_blank_lines_of_directives() {
+            let directives = parse(
+                indoc! {r#"
+                    # groceries
+
+
+                    2024-01-05 * "KFC" ; weekly lunch
+                      location: "Shanghai" // near office
+                      Assets:Bank -25.5 CNY
+                        card: "visa" ; the old card
+                      Expenses:Food ; for lunch
+
+                    1970-01-01 open Assets:Bank # the salary card
+                      bank: "HSBC" * main bank
+                "#},
+                None,
+            )
+            .unwrap();
+            assert_eq!(3, directives.len());
+
+            assert!(matches!(&directives[0].data, Directive::Comment(comment) if comment.content == "# groceries"));
+            assert_eq!(0, directives[0].span.trivia.blank_lines);
+
+            let trx = &directives[1].span.trivia;
+            assert_eq!(2, trx.blank_lines);
+            assert_eq!(Some("; weekly lunch".to_owned()), trx.comment);
+            assert_eq!(
+                vec![
+                    ("location".to_owned(), "// near office".to_owned()),
+                    ("card".to_owned(), "; the old card".to_owned())
+                ],
+                trx.meta_comments
+            );
+
+            let open = &directives[2].span.trivia;
+            assert_eq!(1, open.blank_lines);
+            assert_eq!(Some("# the salary card".to_owned()), open.comment);
+            assert_eq!(vec![("bank".to_owned(), "* main bank".to_owned())], open.meta_comments);
+        }
+    }
+
+    mod recovering {
+        use indoc::indoc;
+        use zhang_ast::Directive;
+
+        use crate::data_type::text::parser::parse_recovering;
+
+        #[test]
+        fn should_skip_malformed_directives() {
+            let content = indoc! {r#"
+                1970-01-01 open Assets:Bank
+
+                1970-01-02 "KFC"
+                  Assets:Bank -50 CNY CNY
+                  Expenses:Food
+                1970-01-03 opne Assets:Cash
+                1970-01-04 open Assets:Cash
+            "#};
+            let directives = parse_recovering(content, None);
+            assert_eq!(4, directives.len());
+            assert!(matches!(directives[0].data, Directive::Open(_)));
+            assert!(matches!(directives[3].data, Directive::Open(_)));
+            assert_eq!("1970-01-04 open Assets:Cash", directives[3].span.content);
+            assert_eq!(content.find("1970-01-04").unwrap(), directives[3].span.start);
+
+            let Directive::Malformed(malformed) = &directives[1].data else {
+                panic!("should be malformed");
+            };
+            assert!(malformed.content.starts_with("1970-01-02 \"KFC\""));
+            assert!(malformed.content.ends_with("Expenses:Food"));
+            assert_eq!(1, directives[1].span.trivia.blank_lines);
+            assert_eq!(content.find("1970-01-02").unwrap(), directives[1].span.start);
+            assert!(matches!(&directives[2].data, Directive::Malformed(it) if it.content == "1970-01-03 opne Assets:Cash"));
+        }
+    }
 }
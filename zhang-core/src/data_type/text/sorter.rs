@@ -0,0 +1,183 @@
+//! the checker and fixer of the date order of directives in zhang file.
+//!
+//! the dated directives are sorted by date, the directives of the same day keep their order. the directives without date
+//! and the comments separated by blank lines are treated as section boundaries, directives are never moved across them.
+//! the comments right above a directive are moved along with it, and the blank lines between directives stay in place.
+
+use chrono::NaiveDate;
+use itertools::Itertools;
+use zhang_ast::{Directive, SpanInfo, Spanned};
+
+use crate::data_type::text::parser::parse;
+use crate::{ZhangError, ZhangResult};
+
+/// the directive whose date is earlier than the directives before it
+#[derive(Debug, Clone, PartialEq)]
+pub struct MisorderedDirective {
+    pub span: SpanInfo,
+    /// the line of directive, starts from 1
+    pub line: usize,
+    pub date: NaiveDate,
+    /// the latest date of directives before it
+    pub after: NaiveDate,
+}
+
+/// the movable part of file, which is a dated directive and the comments right above it
+struct Unit<'a> {
+    start: usize,
+    end: usize,
+    date: NaiveDate,
+    directive: &'a Spanned<Directive>,
+}
+
+/// split the directives into sections of units
+fn sections(directives: &[Spanned<Directive>]) -> Vec<Vec<Unit<'_>>> {
+    let mut sections = vec![];
+    let mut section = vec![];
+    let mut comment_start = None;
+    for directive in directives.iter().sorted_by_key(|it| it.span.start) {
+        // the comments before are not attached to current directive if they are separated by blank lines
+        if directive.span.trivia.blank_lines > 0 && comment_start.take().is_some() {
+            sections.push(std::mem::take(&mut section));
+        }
+        if let Directive::Comment(_) = &directive.data {
+            comment_start.get_or_insert(directive.span.start);
+            continue;
+        }
+        match directive.data.datetime() {
+            Some(datetime) => section.push(Unit {
+                start: comment_start.take().unwrap_or(directive.span.start),
+                end: directive.span.end,
+                date: datetime.date(),
+                directive,
+            }),
+            None => {
+                comment_start = None;
+                sections.push(std::mem::take(&mut section));
+            }
+        }
+    }
+    sections.push(section);
+    sections.into_iter().filter(|it| it.len() > 1).collect_vec()
+}
+
+fn parse_content(content: &str) -> ZhangResult<Vec<Spanned<Directive>>> {
+    parse(content, None).map_err(|e| ZhangError::PestError {
+        path: String::new(),
+        msg: e.to_string(),
+    })
+}
+
+/// report the directives which appear out of date order in the content of zhang file
+pub fn check_order(content: &str) -> ZhangResult<Vec<MisorderedDirective>> {
+    let directives = parse_content(content)?;
+    let mut ret = vec![];
+    for section in sections(&directives) {
+        let mut latest = section[0].date;
+        for unit in section.iter().skip(1) {
+            if unit.date < latest {
+                ret.push(MisorderedDirective {
+                    span: unit.directive.span.clone(),
+                    line: content[..unit.directive.span.start].matches('\n').count() + 1,
+                    date: unit.date,
+                    after: latest,
+                });
+            }
+            latest = latest.max(unit.date);
+        }
+    }
+    Ok(ret)
+}
+
+/// reorder the directives of the content of zhang file by date
+pub fn sort_directives(content: &str) -> ZhangResult<String> {
+    let directives = parse_content(content)?;
+    let mut replacements = vec![];
+    for section in sections(&directives) {
+        let sorted = section.iter().sorted_by_key(|it| it.date).collect_vec();
+        for (slot, unit) in section.iter().zip(sorted) {
+            replacements.push((slot.start, slot.end, &content[unit.start..unit.end]));
+        }
+    }
+    let mut sorted = String::with_capacity(content.len());
+    let mut cursor = 0;
+    for (start, end, text) in replacements.into_iter().sorted_by_key(|it| it.0) {
+        sorted.push_str(&content[cursor..start]);
+        sorted.push_str(text);
+        cursor = end;
+    }
+    sorted.push_str(&content[cursor..]);
+    Ok(sorted)
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use crate::data_type::text::sorter::{check_order, sort_directives};
+
+    const CONTENT: &str = indoc! {r#"
+        option "title" "Example"
+
+        2024-01-03 open Assets:Cash
+        ; lunch
+        2024-01-02 "KFC"
+          Assets:Cash -10 CNY
+          Expenses:Food
+
+        2024-01-03 "Bakery"
+          Assets:Cash -5 CNY
+          Expenses:Food
+        2024-01-01 open Assets:Bank
+
+        ; === February ===
+
+        2024-02-02 open Expenses:Food
+        2024-02-01 open Expenses:Transport
+    "#};
+
+    #[test]
+    fn should_report_misordered_directives() {
+        let misordered = check_order(CONTENT).unwrap();
+        let lines = misordered
+            .iter()
+            .map(|it| (it.line, it.date.to_string(), it.after.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (5, "2024-01-02".to_owned(), "2024-01-03".to_owned()),
+                (12, "2024-01-01".to_owned(), "2024-01-03".to_owned()),
+                (17, "2024-02-01".to_owned(), "2024-02-02".to_owned()),
+            ],
+            lines
+        );
+    }
+
+    #[test]
+    fn should_sort_directives_and_keep_same_day_order() {
+        let sorted = sort_directives(CONTENT).unwrap();
+        assert_eq!(
+            indoc! {r#"
+                option "title" "Example"
+
+                2024-01-01 open Assets:Bank
+                ; lunch
+                2024-01-02 "KFC"
+                  Assets:Cash -10 CNY
+                  Expenses:Food
+
+                2024-01-03 open Assets:Cash
+                2024-01-03 "Bakery"
+                  Assets:Cash -5 CNY
+                  Expenses:Food
+
+                ; === February ===
+
+                2024-02-01 open Expenses:Transport
+                2024-02-02 open Expenses:Food
+            "#},
+            sorted
+        );
+        assert!(check_order(&sorted).unwrap().is_empty());
+    }
+}
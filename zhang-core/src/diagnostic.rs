@@ -0,0 +1,134 @@
+//! the machine-readable form of ledger errors, used by editor integrations and CI checks.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use itertools::Itertools;
+use serde::Serialize;
+use zhang_ast::error::{ErrorKind, ErrorSeverity};
+
+use crate::domains::schemas::ErrorDomain;
+use crate::ledger::Ledger;
+use crate::ZhangResult;
+
+/// the position in file, both line and column start from 1, and the column is counted in characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    /// the position of byte offset in content, `None` if the offset is out of content or not at a character boundary
+    pub fn of(content: &str, offset: usize) -> Option<Position> {
+        let before = content.get(..offset)?;
+        let line_start = before.rfind('\n').map(|it| it + 1).unwrap_or(0);
+        Some(Position {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub id: String,
+    pub kind: ErrorKind,
+    pub severity: ErrorSeverity,
+    pub file: Option<PathBuf>,
+    /// the byte offsets of the directive in file
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+    /// the positions of the directive, absent if the file cannot be read
+    pub start_position: Option<Position>,
+    pub end_position: Option<Position>,
+    pub metas: HashMap<String, String>,
+}
+
+impl Diagnostic {
+    /// the diagnostic of error, the positions are calculated from the content of file where the error is
+    pub fn new(error: &ErrorDomain, content: Option<&str>) -> Self {
+        let span = error.span.as_ref();
+        let position = |offset: usize| content.and_then(|content| Position::of(content, offset));
+        Diagnostic {
+            id: error.id.clone(),
+            kind: error.error_type.clone(),
+            severity: error.severity,
+            file: span.and_then(|it| it.filename.clone()),
+            start: span.map(|it| it.start),
+            end: span.map(|it| it.end),
+            start_position: span.and_then(|it| position(it.start)),
+            end_position: span.and_then(|it| position(it.end)),
+            metas: error.metas.clone(),
+        }
+    }
+}
+
+impl Ledger {
+    /// the diagnostics of ledger errors ordered by file and offset
+    pub fn diagnostics(&self) -> ZhangResult<Vec<Diagnostic>> {
+        let errors = self.operations().errors()?;
+        let mut contents: HashMap<PathBuf, Option<String>> = HashMap::new();
+        let diagnostics = errors
+            .iter()
+            .map(|error| {
+                let filename = error.span.as_ref().and_then(|it| it.filename.clone());
+                let content = filename.and_then(|filename| {
+                    contents
+                        .entry(filename.clone())
+                        .or_insert_with(|| {
+                            self.data_source
+                                .get(filename.to_string_lossy().to_string())
+                                .ok()
+                                .and_then(|it| String::from_utf8(it).ok())
+                        })
+                        .clone()
+                });
+                Diagnostic::new(error, content.as_deref())
+            })
+            .sorted_by(|a, b| a.file.cmp(&b.file).then(a.start.cmp(&b.start)))
+            .collect_vec();
+        Ok(diagnostics)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+    use zhang_ast::error::ErrorKind;
+
+    use crate::diagnostic::Position;
+    use crate::test::load_from_text;
+
+    #[test]
+    fn should_get_position_of_offset() {
+        let content = "first\n价格 line\n";
+        assert_eq!(Some(Position { line: 1, column: 1 }), Position::of(content, 0));
+        assert_eq!(Some(Position { line: 2, column: 1 }), Position::of(content, 6));
+        assert_eq!(Some(Position { line: 2, column: 3 }), Position::of(content, 12));
+        assert_eq!(None, Position::of(content, 7));
+        assert_eq!(None, Position::of(content, 100));
+    }
+
+    #[test]
+    fn should_serialize_diagnostics_with_positions() {
+        let ledger = load_from_text(indoc! {r#"
+            1970-01-01 open Assets:MyCard
+            1970-01-02 close Expenses:Food
+        "#});
+
+        let diagnostics = ledger.diagnostics().unwrap();
+        assert_eq!(1, diagnostics.len());
+        assert_eq!(ErrorKind::AccountDoesNotExist, diagnostics[0].kind);
+
+        let json = serde_json::to_value(&diagnostics[0]).unwrap();
+        assert_eq!("AccountDoesNotExist", json["kind"]);
+        assert_eq!("error", json["severity"]);
+        assert!(json["file"].as_str().unwrap().ends_with("example.zhang"));
+        assert_eq!(30, json["start"]);
+        assert_eq!(60, json["end"]);
+        assert_eq!(serde_json::json!({"line": 2, "column": 1}), json["start_position"]);
+        assert_eq!(serde_json::json!({"line": 2, "column": 31}), json["end_position"]);
+        assert_eq!("Expenses:Food", json["metas"]["account_name"]);
+    }
+}
@@ -0,0 +1,258 @@
+//! directive cache is the persistent cache of parsed directives, keyed by the content hash of file,
+//! so that unchanged files skip parsing when ledger is loaded again.
+//!
+//! cached directives are stored in the folder of current zhang version under the ledger folder, e.g.
+//! `{LEDGER}/.zhang-cache/directives/{VERSION}/{HASH}.json`, caches of other versions are removed once the cache is created,
+//! since the format of directives may change between versions. the caches of file contents which are not hit during a load,
+//! e.g. the ones of old contents, are pruned after the load.
+//!
+//! the in-memory cache keeps the directives of the latest content of each file, which is used by long-running processes
+//! like language server to reparse only the changed files when ledger is reloaded.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+use log::{debug, warn};
+use sha256::digest;
+use zhang_ast::{Directive, Spanned};
+
+use crate::error::IoErrorIntoZhangError;
+use crate::{ZhangError, ZhangResult};
+
+/// the folder of directive cache, relative to the ledger folder
+pub const DEFAULT_DIRECTIVE_CACHE_FOLDER: &str = ".zhang-cache/directives";
+
+const ZHANG_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Clone)]
+pub struct DirectiveCache {
+    storage: Storage,
+    /// the hashes of caches which are hit or written since last pruning
+    used: Arc<Mutex<HashSet<String>>>,
+}
+
+/// the content hash and directives of file, by source
+type MemoryStorage = Arc<RwLock<HashMap<String, (String, Vec<Spanned<Directive>>)>>>;
+
+#[derive(Debug, Clone)]
+enum Storage {
+    Folder(PathBuf),
+    Memory(MemoryStorage),
+}
+
+impl DirectiveCache {
+    /// create the cache under `root`, the caches of other zhang versions in `root` are removed
+    pub fn new(root: impl AsRef<Path>) -> ZhangResult<Self> {
+        let root = root.as_ref();
+        let folder = root.join(ZHANG_VERSION);
+        std::fs::create_dir_all(&folder).with_path(&folder)?;
+
+        for entry in std::fs::read_dir(root).with_path(root)? {
+            let path = entry.with_path(root)?.path();
+            if path.is_dir() && path.ne(&folder) {
+                debug!("removing stale directive cache: {}", path.display());
+                std::fs::remove_dir_all(&path).with_path(&path)?;
+            }
+        }
+        Ok(DirectiveCache {
+            storage: Storage::Folder(folder),
+            used: Default::default(),
+        })
+    }
+
+    /// create the cache kept in memory, only the directives of the latest content of each file are kept
+    pub fn in_memory() -> Self {
+        DirectiveCache {
+            storage: Storage::Memory(Default::default()),
+            used: Default::default(),
+        }
+    }
+
+    fn hash(source: &str, content: &str) -> String {
+        // source is a part of the key since the span of directive contains its filename
+        digest(format!("{}\n{}", source, content))
+    }
+
+    /// retrieve the cached directives of file, `None` is returned if the cache is missing or broken
+    pub fn get(&self, source: &str, content: &str) -> Option<Vec<Spanned<Directive>>> {
+        let hash = DirectiveCache::hash(source, content);
+        let folder = match &self.storage {
+            Storage::Folder(folder) => folder,
+            Storage::Memory(memory) => {
+                let memory = memory.read().ok()?;
+                return memory
+                    .get(source)
+                    .filter(|(cached_hash, _)| cached_hash.eq(&hash))
+                    .map(|(_, directives)| directives.clone());
+            }
+        };
+        let cache_file = folder.join(format!("{}.json", hash));
+        let cached = std::fs::read(&cache_file).ok()?;
+        match serde_json::from_slice(&cached) {
+            Ok(directives) => {
+                self.mark_used(hash);
+                Some(directives)
+            }
+            Err(e) => {
+                warn!("directive cache {} is broken: {}", cache_file.display(), e);
+                None
+            }
+        }
+    }
+
+    /// store the parsed directives of file
+    pub fn set(&self, source: &str, content: &str, directives: &[Spanned<Directive>]) -> ZhangResult<()> {
+        let hash = DirectiveCache::hash(source, content);
+        let folder = match &self.storage {
+            Storage::Folder(folder) => folder,
+            Storage::Memory(memory) => {
+                let mut memory = memory.write().map_err(|_| ZhangError::CustomError("directive cache is poisoned".to_owned()))?;
+                memory.insert(source.to_owned(), (hash, directives.to_vec()));
+                return Ok(());
+            }
+        };
+        let cache_file = folder.join(format!("{}.json", hash));
+        let cached = serde_json::to_vec(directives).map_err(|e| ZhangError::CustomError(format!("cannot serialize directives: {}", e)))?;
+        std::fs::write(&cache_file, cached).with_path(&cache_file)?;
+        self.mark_used(hash);
+        Ok(())
+    }
+
+    fn mark_used(&self, hash: String) {
+        if let Ok(mut used) = self.used.lock() {
+            used.insert(hash);
+        }
+    }
+
+    /// remove the caches which are not hit or written since last pruning, it should be called once the ledger is loaded.
+    /// the in-memory cache keeps the latest content of each file only, so there is nothing to prune
+    pub fn prune_unused(&self) {
+        let Storage::Folder(folder) = &self.storage else {
+            return;
+        };
+        let Ok(mut used) = self.used.lock() else {
+            return;
+        };
+        let entries = match std::fs::read_dir(folder) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("cannot list directive cache {}: {}", folder.display(), e);
+                return;
+            }
+        };
+        for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+            let is_unused =
+                path.extension().is_some_and(|it| it == "json") && path.file_stem().and_then(|it| it.to_str()).is_some_and(|hash| !used.contains(hash));
+            if is_unused {
+                debug!("removing unused directive cache: {}", path.display());
+                if let Err(e) = std::fs::remove_file(&path) {
+                    warn!("cannot remove directive cache {}: {}", path.display(), e);
+                }
+            }
+        }
+        used.clear();
+    }
+
+    /// get the directives of file from cache if the cache is given, otherwise parse the file and cache the result.
+    /// failing to write the cache does not fail the parsing
+    pub fn parse_with_cache(
+        cache: Option<&DirectiveCache>, source: &str, content: &str, parse: impl FnOnce() -> ZhangResult<Vec<Spanned<Directive>>>,
+    ) -> ZhangResult<Vec<Spanned<Directive>>> {
+        let Some(cache) = cache else {
+            return parse();
+        };
+        if let Some(directives) = cache.get(source, content) {
+            debug!("directive cache hit: {}", source);
+            return Ok(directives);
+        }
+        let directives = parse()?;
+        if let Err(e) = cache.set(source, content, &directives) {
+            warn!("cannot write directive cache of {}: {}", source, e);
+        }
+        Ok(directives)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use tempfile::tempdir;
+
+    use crate::data_type::text::ZhangDataType;
+    use crate::data_type::DataType;
+    use crate::directive_cache::{DirectiveCache, ZHANG_VERSION};
+
+    #[test]
+    fn should_parse_once_given_unchanged_content() {
+        let root = tempdir().unwrap().into_path();
+        let cache = DirectiveCache::new(&root).unwrap();
+        let content = "1970-01-01 open Assets:Bank\n";
+        let parsed_times = Cell::new(0);
+        let parse = || {
+            parsed_times.set(parsed_times.get() + 1);
+            ZhangDataType {}.transform(content.to_owned(), Some("main.zhang".to_owned()))
+        };
+
+        let first = DirectiveCache::parse_with_cache(Some(&cache), "main.zhang", content, parse).unwrap();
+        let second = DirectiveCache::parse_with_cache(Some(&cache), "main.zhang", content, parse).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(1, parsed_times.get());
+
+        DirectiveCache::parse_with_cache(Some(&cache), "main.zhang", "1970-01-01 open Assets:Cash\n", parse).unwrap();
+        assert_eq!(2, parsed_times.get(), "changed content should be parsed again");
+    }
+
+    #[test]
+    fn should_keep_latest_content_in_memory() {
+        let cache = DirectiveCache::in_memory();
+        let parsed_times = Cell::new(0);
+        let parse = |content: &str| {
+            parsed_times.set(parsed_times.get() + 1);
+            ZhangDataType {}.transform(content.to_owned(), Some("main.zhang".to_owned()))
+        };
+        let first = "1970-01-01 open Assets:Bank\n";
+        let second = "1970-01-01 open Assets:Cash\n";
+
+        DirectiveCache::parse_with_cache(Some(&cache), "main.zhang", first, || parse(first)).unwrap();
+        DirectiveCache::parse_with_cache(Some(&cache), "main.zhang", first, || parse(first)).unwrap();
+        assert_eq!(1, parsed_times.get());
+
+        DirectiveCache::parse_with_cache(Some(&cache), "main.zhang", second, || parse(second)).unwrap();
+        assert_eq!(2, parsed_times.get());
+        assert!(cache.get("main.zhang", first).is_none(), "only the latest content should be kept");
+        assert!(cache.get("main.zhang", second).is_some());
+    }
+
+    #[test]
+    fn should_prune_cache_not_hit_during_load() {
+        let root = tempdir().unwrap().into_path();
+        let cache = DirectiveCache::new(&root).unwrap();
+        let parse = |content: &str| ZhangDataType {}.transform(content.to_owned(), Some("main.zhang".to_owned()));
+        let first = "1970-01-01 open Assets:Bank\n";
+        let second = "1970-01-01 open Assets:Cash\n";
+        let cached_files = || std::fs::read_dir(root.join(ZHANG_VERSION)).unwrap().count();
+
+        DirectiveCache::parse_with_cache(Some(&cache), "main.zhang", first, || parse(first)).unwrap();
+        cache.prune_unused();
+        assert_eq!(1, cached_files());
+
+        DirectiveCache::parse_with_cache(Some(&cache), "main.zhang", second, || parse(second)).unwrap();
+        assert_eq!(2, cached_files());
+        cache.prune_unused();
+        assert_eq!(1, cached_files(), "the cache of old content should be pruned");
+        assert!(cache.get("main.zhang", first).is_none());
+        assert!(cache.get("main.zhang", second).is_some());
+    }
+
+    #[test]
+    fn should_remove_cache_of_other_versions() {
+        let root = tempdir().unwrap().into_path();
+        std::fs::create_dir_all(root.join("0.0.0-stale")).unwrap();
+
+        DirectiveCache::new(&root).unwrap();
+        assert!(!root.join("0.0.0-stale").exists());
+        assert!(root.join(ZHANG_VERSION).exists());
+    }
+}
@@ -1,9 +1,9 @@
 use std::collections::{BTreeMap, HashMap, HashSet};
-use std::ops::{Add, AddAssign, Sub};
+use std::ops::{Add, AddAssign, Bound, Div, Mul, RangeBounds, Sub, SubAssign};
 use std::str::FromStr;
 use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
-use bigdecimal::{BigDecimal, Zero};
+use bigdecimal::{BigDecimal, Signed, Zero};
 use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use chrono_tz::Tz;
 use indexmap::IndexMap;
@@ -11,20 +11,25 @@ use itertools::Itertools;
 use serde::Deserialize;
 use uuid::Uuid;
 use zhang_ast::amount::Amount;
-use zhang_ast::error::ErrorKind;
-use zhang_ast::{Account, AccountType, Currency, Date, Flag, Meta, Rounding, SpanInfo, Transaction};
+use zhang_ast::error::{ErrorKind, ErrorSeverity};
+use zhang_ast::{Account, AccountType, Currency, Date, Flag, Meta, PostingType, SpanInfo, Transaction};
 
+use crate::constants::{ACCOUNT_ENTITY, KEY_OPERATING_CURRENCY};
+use crate::domains::price_graph::PriceGraph;
 use crate::domains::schemas::{
-    AccountBalanceDomain, AccountDailyBalanceDomain, AccountDomain, AccountJournalDomain, AccountStatus, CommodityDomain, ErrorDomain, MetaDomain, MetaType,
-    OptionDomain, PriceDomain, TransactionInfoDomain,
+    AccountBalanceDomain, AccountBalanceSeriesDomain, AccountDailyBalanceDomain, AccountDomain, AccountJournalDomain, AccountStatus, AgingBucket,
+    BalanceInterval, BudgetReportDomain, BudgetReportInterval, CommodityDomain, EliminationDomain, ErrorDomain, HoldingDomain, HoldingLotDomain, InvoiceDomain,
+    InvoiceKind, LoanPaymentDomain, MetaDomain, MetaType, NetWorthDomain, OpenInvoiceDomain, OptionDomain, PayeeDomain, PriceDomain, RealizedGainDomain,
+    TaxDomain, TaxReportDomain, TransactionInfoDomain, TrialBalanceAccountDomain, TrialBalanceDomain, TrialBalanceTotalDomain,
 };
 use crate::store::{
-    BudgetDomain, BudgetEvent, BudgetEventType, BudgetIntervalDetail, CommodityLotRecord, DocumentDomain, DocumentType, PostingDomain, Store, TransactionDomain,
+    BudgetDomain, BudgetEvent, BudgetEventType, BudgetIntervalDetail, BudgetRollover, CommodityLotRecord, CommodityLotSnapshot, DocumentDomain, DocumentType,
+    PadDomain, PostingDomain, ReconcileState, Store, TransactionDomain,
 };
-use crate::utils::bigdecimal_ext::BigDecimalExt;
 use crate::utils::id::FromSpan;
 use crate::{ZhangError, ZhangResult};
 
+pub mod price_graph;
 pub mod schemas;
 
 #[derive(Debug, Deserialize)]
@@ -45,6 +50,71 @@ pub struct StaticRow {
     pub account_type: String,
     pub amount: BigDecimal,
     pub commodity: String,
+    /// the amount valued in the currency of conversion, present if conversion is requested and the exchange rate is resolved
+    pub converted: Option<Amount>,
+}
+
+/// value the holdings in `currency`, e.g. the operating currency, by the prices at `date`
+#[derive(Debug, Clone)]
+pub struct Conversion {
+    pub currency: Currency,
+    pub date: NaiveDateTime,
+}
+
+impl Conversion {
+    pub fn new(currency: impl Into<Currency>, date: NaiveDateTime) -> Self {
+        Conversion {
+            currency: currency.into(),
+            date,
+        }
+    }
+
+    pub(crate) fn convert(&self, price_graph: &PriceGraph, number: &BigDecimal, commodity: &str) -> Option<Amount> {
+        price_graph
+            .rate(commodity, &self.currency, self.date)
+            .map(|rate| Amount::new(number.mul(rate), self.currency.clone()))
+    }
+}
+
+/// the filters of journal, the transactions matching all of the given filters are returned
+#[derive(Debug, Clone, Default)]
+pub struct JournalFilter {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// the transactions having postings of the account or its sub-accounts
+    pub account: Option<String>,
+    /// the transactions whose payee contains it, case-insensitively
+    pub payee: Option<String>,
+    pub tag: Option<String>,
+    pub link: Option<String>,
+    /// the free text matched against payee, narration, tags, links and accounts of transaction
+    pub keyword: Option<String>,
+}
+
+impl JournalFilter {
+    pub fn matches(&self, trx: &TransactionDomain) -> bool {
+        let sub_account_prefix = self.account.as_ref().map(|account| format!("{}:", account));
+        self.from.map(|from| trx.datetime.ge(&from)).unwrap_or(true)
+            && self.to.map(|to| trx.datetime.le(&to)).unwrap_or(true)
+            && self
+                .account
+                .as_ref()
+                .zip(sub_account_prefix.as_ref())
+                .map(|(account, prefix)| {
+                    trx.postings
+                        .iter()
+                        .any(|it| it.account.name().eq(account) || it.account.name().starts_with(prefix))
+                })
+                .unwrap_or(true)
+            && self
+                .payee
+                .as_ref()
+                .map(|payee| trx.payee.as_ref().map(|it| it.to_lowercase().contains(&payee.to_lowercase())).unwrap_or(false))
+                .unwrap_or(true)
+            && self.tag.as_ref().map(|tag| trx.tags.contains(tag)).unwrap_or(true)
+            && self.link.as_ref().map(|link| trx.links.contains(link)).unwrap_or(true)
+            && self.keyword.as_ref().map(|keyword| trx.contains_keyword(keyword)).unwrap_or(true)
+    }
 }
 
 pub struct AccountCommodityLot {
@@ -52,6 +122,7 @@ pub struct AccountCommodityLot {
     pub datetime: Option<DateTime<Tz>>,
     pub amount: BigDecimal,
     pub price: Option<Amount>,
+    pub label: Option<String>,
 }
 
 pub struct Operations {
@@ -83,6 +154,7 @@ impl Operations {
                         datetime: lot.datetime,
                         amount: lot.amount,
                         price: lot.price,
+                        label: lot.label,
                     })
                 }
             }
@@ -127,6 +199,12 @@ impl Operations {
     ) -> ZhangResult<()> {
         let mut store = self.write();
 
+        for tag in &tags {
+            store.tag_index.entry(tag.clone()).or_default().push(*id);
+        }
+        for link in &links {
+            store.link_index.entry(link.clone()).or_default().push(*id);
+        }
         store.transactions.insert(
             *id,
             TransactionDomain {
@@ -151,31 +229,62 @@ impl Operations {
         if txn.flag == Some(Flag::BalanceCheck) {
             return Ok(None);
         }
-        match txn.get_postings_inventory() {
-            Ok(inventory) => {
-                for (currency, amount) in inventory.currencies.iter() {
-                    let commodity = self.commodity(currency)?;
-                    let Some(commodity) = commodity else {
-                        return Ok(Some(ErrorKind::CommodityDoesNotDefine));
-                    };
-                    let precision = commodity.precision;
-                    let rounding = commodity.rounding;
-                    let decimal = amount.total.round_with(precision as i64, rounding.is_up());
-                    if !decimal.is_zero() {
-                        return Ok(Some(ErrorKind::UnbalancedTransaction));
-                    }
+        // the virtual postings are not balanced, so that their amounts cannot be inferred
+        if txn
+            .txn_postings()
+            .iter()
+            .any(|it| it.posting.posting_type == PostingType::Virtual && it.trade_amount().is_none())
+        {
+            return Ok(Some(ErrorKind::TransactionCannotInferTradeAmount));
+        }
+        // the real postings and the balanced virtual postings are balanced separately
+        for posting_type in [PostingType::Real, PostingType::BalancedVirtual] {
+            let inventory = match txn.get_postings_inventory_of(posting_type) {
+                Ok(inventory) => inventory,
+                Err(e) => return Ok(Some(e)),
+            };
+            for (currency, amount) in inventory.currencies.iter() {
+                let commodity = self.commodity(currency)?;
+                let Some(commodity) = commodity else {
+                    return Ok(Some(ErrorKind::CommodityDoesNotDefine));
+                };
+                // the residual is tolerated if it is within half a unit of the smallest decimal place of commodity's precision
+                let tolerance = BigDecimal::new(5.into(), commodity.precision as i64 + 1);
+                if amount.total.abs() > tolerance {
+                    return Ok(Some(ErrorKind::UnbalancedTransaction));
                 }
-                Ok(None)
             }
-            Err(e) => Ok(Some(e)),
         }
+        Ok(None)
     }
 
-    /// insert transaction postings
+    /// the issues of transaction against the ledger before it is written, which are the accounts not opened or closed,
+    /// and the postings which are not balanced. the commodities not defined are not issues, since they are defined implicitly
+    pub fn validate_transaction(&self, txn: &Transaction) -> ZhangResult<Vec<ErrorKind>> {
+        let mut issues = vec![];
+        {
+            let store = self.read();
+            for posting in txn.postings.iter() {
+                match store.accounts.get(posting.account.name()) {
+                    None => issues.push(ErrorKind::AccountDoesNotExist),
+                    Some(account) if account.status == AccountStatus::Close => issues.push(ErrorKind::AccountClosed),
+                    Some(_) => {}
+                }
+            }
+        }
+        match self.check_transaction(txn)? {
+            None | Some(ErrorKind::CommodityDoesNotDefine) => {}
+            Some(issue) => issues.push(issue),
+        }
+        Ok(issues.into_iter().unique().collect_vec())
+    }
+
+    /// insert transaction postings, the postings of voided transaction are only kept in the transaction, so that they are
+    /// shown in journal but excluded from balances and reports
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn insert_transaction_posting(
-        &mut self, trx_id: &Uuid, posting_idx: usize, account_name: &str, unit: Option<Amount>, cost: Option<Amount>, inferred_amount: Amount,
-        previous_amount: Amount, after_amount: Amount,
+        &mut self, trx_id: &Uuid, posting_idx: usize, datetime: DateTime<Tz>, account_name: &str, unit: Option<Amount>, cost: Option<Amount>,
+        inferred_amount: Amount, previous_amount: Amount, after_amount: Amount, reconcile_state: ReconcileState, statement_date: Option<NaiveDate>,
     ) -> ZhangResult<()> {
         let mut store = self.write();
 
@@ -189,14 +298,21 @@ impl Operations {
             trx_id: *trx_id,
             trx_sequence: trx.sequence,
             trx_datetime: trx.datetime,
+            datetime,
             account: Account::from_str(account_name).map_err(|_| ZhangError::InvalidAccount)?,
             unit,
             cost,
             inferred_amount,
             previous_amount,
             after_amount,
+            reconcile_state,
+            statement_date,
         };
-        store.postings.push(posting.clone());
+        if trx.flag != Flag::Void {
+            let position = store.postings.len();
+            store.posting_index.insert(position, &posting);
+            store.postings.push(posting.clone());
+        }
         let txn_header = store
             .transactions
             .get_mut(trx_id)
@@ -209,7 +325,9 @@ impl Operations {
     /// datetime means:
     ///  - for transaction document: transaction datetime
     ///  - for account document: document linking datetime
-    pub(crate) fn insert_document(&mut self, datetime: DateTime<Tz>, filename: Option<&str>, path: String, document_type: DocumentType) -> ZhangResult<()> {
+    pub(crate) fn insert_document(
+        &mut self, datetime: DateTime<Tz>, filename: Option<&str>, path: String, document_type: DocumentType, hash: Option<String>,
+    ) -> ZhangResult<()> {
         let mut store = self.write();
 
         store.documents.push(DocumentDomain {
@@ -217,11 +335,26 @@ impl Operations {
             document_type,
             filename: filename.map(|it| it.to_owned()),
             path,
+            hash,
         });
 
         Ok(())
     }
 
+    /// write key/value data into the area of plugin, the value of existing key is replaced
+    pub fn insert_plugin_data(&mut self, plugin_name: &str, key: String, value: serde_json::Value) -> ZhangResult<()> {
+        let mut store = self.write();
+        store.plugin_data.entry(plugin_name.to_owned()).or_default().insert(key, value);
+        Ok(())
+    }
+
+    /// insert the extracted text of document content
+    pub(crate) fn insert_document_text(&mut self, hash: &str, text: String) -> ZhangResult<()> {
+        let mut store = self.write();
+        store.document_texts.insert(hash.to_owned(), text);
+        Ok(())
+    }
+
     /// insert single price
     pub(crate) fn insert_price(&mut self, datetime: DateTime<Tz>, commodity: &str, amount: &BigDecimal, target_commodity: &str) -> ZhangResult<()> {
         let mut store = self.write();
@@ -231,6 +364,7 @@ impl Operations {
             amount: amount.clone(),
             target_commodity: target_commodity.to_owned(),
         });
+        store.price_graph.take();
         Ok(())
     }
 
@@ -239,14 +373,10 @@ impl Operations {
 
         let account = Account::from_str(account_name).map_err(|_| ZhangError::InvalidAccount)?;
 
-        let posting: Option<&PostingDomain> = store
-            .postings
-            .iter()
-            .filter(|posting| posting.account.eq(&account))
-            .filter(|posting| posting.after_amount.currency.eq(&currency))
-            .filter(|posting| posting.trx_datetime.le(&datetime))
-            .sorted_by_key(|posting| posting.trx_datetime)
-            .next_back();
+        let posting = store
+            .posting_index
+            .balance(account.name(), currency, datetime.with_timezone(&Utc))
+            .map(|position| &store.postings[position]);
 
         Ok(posting.map(|it| AccountAmount {
             number: it.after_amount.number.clone(),
@@ -254,15 +384,86 @@ impl Operations {
         }))
     }
 
+    /// the sum of reconciled postings of account at the given datetime, the postings of sub-accounts are included if `recursive` is true
+    pub(crate) fn account_reconciled_balance(&self, account_name: &str, recursive: bool, datetime: DateTime<Tz>, currency: &str) -> ZhangResult<BigDecimal> {
+        let store = self.read();
+        let sub_account_prefix = format!("{}:", account_name);
+        Ok(store
+            .postings
+            .iter()
+            .filter(|posting| posting.account.name().eq(account_name) || (recursive && posting.account.name().starts_with(&sub_account_prefix)))
+            .filter(|posting| posting.reconcile_state == ReconcileState::Reconciled)
+            .filter(|posting| posting.inferred_amount.currency.eq(currency))
+            .filter(|posting| posting.datetime.le(&datetime))
+            .map(|posting| &posting.inferred_amount.number)
+            .sum())
+    }
+
+    /// the aggregated balance of account and all its sub-accounts at the given datetime
+    pub(crate) fn account_tree_target_day_balance(&mut self, account_name: &str, datetime: DateTime<Tz>, currency: &str) -> ZhangResult<BigDecimal> {
+        let store = self.read();
+        let sub_account_prefix = format!("{}:", account_name);
+
+        let latest_postings: HashMap<&str, &PostingDomain> = store
+            .postings
+            .iter()
+            .filter(|posting| posting.account.name().eq(account_name) || posting.account.name().starts_with(&sub_account_prefix))
+            .filter(|posting| posting.after_amount.currency.eq(&currency))
+            .filter(|posting| posting.datetime.le(&datetime))
+            .sorted_by_key(|posting| posting.datetime)
+            .map(|posting| (posting.account.name(), posting))
+            .collect();
+
+        Ok(latest_postings
+            .values()
+            .fold(BigDecimal::zero(), |total, posting| total.add(&posting.after_amount.number)))
+    }
+
+    /// check if the account or any of its sub-accounts exists
+    pub fn exist_account_tree(&mut self, account_name: &str) -> ZhangResult<bool> {
+        let store = self.read();
+        let sub_account_prefix = format!("{}:", account_name);
+        Ok(store.accounts.keys().any(|it| it.eq(account_name) || it.starts_with(&sub_account_prefix)))
+    }
+
+    /// the lot without label of target commodity in account at given price
     pub(crate) fn account_lot(&mut self, account_name: &str, currency: &str, price: Option<Amount>) -> ZhangResult<Option<CommodityLotRecord>> {
         let mut store = self.write();
         let entry = store.commodity_lots.entry(account_name.to_owned()).or_default();
 
-        let option = entry.iter().filter(|lot| lot.commodity.eq(currency)).find(|lot| lot.price.eq(&price)).cloned();
+        let option = entry
+            .iter()
+            .filter(|lot| lot.commodity.eq(currency))
+            .find(|lot| lot.price.eq(&price) && lot.label.is_none())
+            .cloned();
 
         Ok(option)
     }
 
+    /// the lot of target commodity in account with given label
+    pub(crate) fn account_labeled_lot(&self, account_name: &str, currency: &str, label: &str) -> ZhangResult<Option<CommodityLotRecord>> {
+        let store = self.read();
+        Ok(store
+            .commodity_lots
+            .get(account_name)
+            .and_then(|lots| {
+                lots.iter()
+                    .filter(|lot| lot.commodity.eq(currency))
+                    .find(|lot| lot.label.as_deref() == Some(label))
+            })
+            .cloned())
+    }
+
+    /// all lots of target commodity in account, ordered by acquiring sequence
+    pub fn account_lots(&self, account_name: &str, currency: &str) -> ZhangResult<Vec<CommodityLotRecord>> {
+        let store = self.read();
+        Ok(store
+            .commodity_lots
+            .get(account_name)
+            .map(|lots| lots.iter().filter(|lot| lot.commodity.eq(currency)).cloned().collect_vec())
+            .unwrap_or_default())
+    }
+
     pub fn account_lot_fifo(&mut self, account_name: &str, currency: &str, price_commodity: &str) -> ZhangResult<Option<CommodityLotRecord>> {
         let mut store = self.write();
         let entry = store.commodity_lots.entry(account_name.to_owned()).or_default();
@@ -275,11 +476,16 @@ impl Operations {
 
         Ok(option)
     }
-    pub(crate) fn update_account_lot(&mut self, account_name: &str, currency: &str, price: Option<Amount>, amount: &BigDecimal) -> ZhangResult<()> {
+    pub(crate) fn update_account_lot(
+        &mut self, account_name: &str, currency: &str, price: Option<Amount>, label: Option<String>, amount: &BigDecimal,
+    ) -> ZhangResult<()> {
         let mut store = self.write();
         let entry = store.commodity_lots.entry(account_name.to_owned()).or_default();
 
-        let option = entry.iter_mut().find(|lot| lot.price.eq(&price));
+        let option = entry
+            .iter_mut()
+            .filter(|lot| lot.commodity.eq(currency))
+            .find(|lot| lot.price.eq(&price) && lot.label.eq(&label));
         if let Some(lot) = option {
             lot.amount = amount.clone();
         } else {
@@ -288,12 +494,23 @@ impl Operations {
                 datetime: None,
                 amount: amount.clone(),
                 price,
+                label,
             })
         }
         Ok(())
     }
 
-    pub(crate) fn insert_account_lot(&mut self, account_name: &str, currency: &str, price: Option<Amount>, amount: &BigDecimal) -> ZhangResult<()> {
+    /// remove all lots of target commodity in account which are priced in `price_commodity`
+    pub(crate) fn remove_account_lots(&mut self, account_name: &str, currency: &str, price_commodity: &str) -> ZhangResult<()> {
+        let mut store = self.write();
+        let entry = store.commodity_lots.entry(account_name.to_owned()).or_default();
+        entry.retain(|lot| !(lot.commodity.eq(currency) && lot.price.as_ref().map(|it| it.currency.as_str()).eq(&Some(price_commodity))));
+        Ok(())
+    }
+
+    pub(crate) fn insert_account_lot(
+        &mut self, account_name: &str, currency: &str, price: Option<Amount>, label: Option<String>, amount: &BigDecimal,
+    ) -> ZhangResult<()> {
         let mut store = self.write();
         let lot_records = store.commodity_lots.entry(account_name.to_owned()).or_default();
 
@@ -302,10 +519,225 @@ impl Operations {
             datetime: None,
             amount: amount.clone(),
             price,
+            label,
         });
         Ok(())
     }
 
+    /// keep the lots of account after they are changed at the datetime, so that the holdings in the past can be rebuilt
+    pub(crate) fn record_account_lots(&mut self, account_name: &str, datetime: DateTime<Tz>) -> ZhangResult<()> {
+        let mut store = self.write();
+        let lots = store.commodity_lots.get(account_name).cloned().unwrap_or_default();
+        store.commodity_lot_history.push(CommodityLotSnapshot {
+            datetime,
+            account: account_name.to_owned(),
+            lots,
+        });
+        Ok(())
+    }
+
+    /// split the lots of commodity in all accounts, the amounts of lots are multiplied by `ratio` and their costs are divided by it.
+    /// return the changed amount of the lots without cost by account, since the lots held at cost are balanced in their cost commodity
+    pub(crate) fn split_commodity_lots(&mut self, currency: &str, ratio: &BigDecimal, datetime: DateTime<Tz>) -> ZhangResult<Vec<(String, BigDecimal)>> {
+        let mut guard = self.write();
+        let store = &mut *guard;
+        let mut changes = vec![];
+        for (account_name, lots) in store.commodity_lots.iter_mut().sorted_by_key(|(account_name, _)| account_name.to_owned()) {
+            if !lots.iter().any(|lot| lot.commodity.eq(currency)) {
+                continue;
+            }
+            let mut change = BigDecimal::zero();
+            for lot in lots.iter_mut().filter(|lot| lot.commodity.eq(currency)) {
+                let amount = (&lot.amount).mul(ratio);
+                match lot.price.as_mut() {
+                    Some(price) => price.number = (&price.number).div(ratio),
+                    None => change.add_assign((&amount).sub(&lot.amount)),
+                }
+                lot.amount = amount;
+            }
+            store.commodity_lot_history.push(CommodityLotSnapshot {
+                datetime,
+                account: account_name.clone(),
+                lots: lots.clone(),
+            });
+            if !change.is_zero() {
+                changes.push((account_name.clone(), change));
+            }
+        }
+        Ok(changes)
+    }
+
+    /// record the pad directive of account, it will replace the previous unused one
+    pub(crate) fn insert_pad(&mut self, account_name: &str, pad: PadDomain) -> ZhangResult<()> {
+        let mut store = self.write();
+        store.pads.insert(account_name.to_owned(), pad);
+        Ok(())
+    }
+
+    /// take the most recent unused pad directive of account
+    pub(crate) fn take_pad(&mut self, account_name: &str) -> ZhangResult<Option<PadDomain>> {
+        let mut store = self.write();
+        Ok(store.pads.remove(account_name))
+    }
+
+    /// shift the balance of postings after the given datetime, used when a posting is inserted before processed postings
+    pub(crate) fn shift_account_balances(&mut self, account_name: &str, after: DateTime<Tz>, amount: &Amount) -> ZhangResult<()> {
+        let mut store = self.write();
+        let account = Account::from_str(account_name).map_err(|_| ZhangError::InvalidAccount)?;
+
+        let shift = |posting: &mut PostingDomain| {
+            if posting.after_amount.currency.eq(&amount.currency) {
+                posting.previous_amount.number.add_assign(&amount.number);
+                posting.after_amount.number.add_assign(&amount.number);
+            }
+        };
+        let positions = store
+            .posting_index
+            .journal(account.name(), (Bound::Excluded(after.with_timezone(&Utc)), Bound::Unbounded))
+            .flatten()
+            .copied()
+            .collect_vec();
+        for position in positions {
+            let posting = &mut store.postings[position];
+            shift(posting);
+            let (trx_id, posting_id) = (posting.trx_id, posting.id);
+            if let Some(posting) = store
+                .transactions
+                .get_mut(&trx_id)
+                .and_then(|trx| trx.postings.iter_mut().find(|posting| posting.id.eq(&posting_id)))
+            {
+                shift(posting);
+            }
+        }
+        Ok(())
+    }
+
+    /// insert realized gain of disposing lot
+    pub(crate) fn insert_realized_gain(&mut self, realized_gain: RealizedGainDomain) -> ZhangResult<()> {
+        let mut store = self.write();
+        store.realized_gains.push(realized_gain);
+        Ok(())
+    }
+
+    /// realized gains of target account within the datetime range
+    pub fn realized_gains(&self, account_name: impl AsRef<str>, from: DateTime<Tz>, to: DateTime<Tz>) -> ZhangResult<Vec<RealizedGainDomain>> {
+        let store = self.read();
+        Ok(store
+            .realized_gains
+            .iter()
+            .filter(|gain| gain.account.eq(account_name.as_ref()))
+            .filter(|gain| gain.datetime.ge(&from))
+            .filter(|gain| gain.datetime.le(&to))
+            .cloned()
+            .collect_vec())
+    }
+
+    pub(crate) fn insert_tax(&mut self, tax: TaxDomain) -> ZhangResult<()> {
+        let mut store = self.write();
+        store.taxes.push(tax);
+        Ok(())
+    }
+
+    /// the collected and paid tax of each tax account per period within the datetime range, for tax filing
+    pub fn tax_report(&self, interval: BalanceInterval, from: DateTime<Tz>, to: DateTime<Tz>) -> ZhangResult<Vec<TaxReportDomain>> {
+        let store = self.read();
+        let mut periods: BTreeMap<(NaiveDate, String, Currency), (BigDecimal, BigDecimal)> = BTreeMap::new();
+        for tax in store.taxes.iter().filter(|tax| tax.datetime.ge(&from) && tax.datetime.le(&to)) {
+            let period = interval.period_start(tax.datetime.naive_local().date());
+            let (collected, paid) = periods
+                .entry((period, tax.tax_account.clone(), tax.tax.currency.clone()))
+                .or_insert_with(|| (BigDecimal::zero(), BigDecimal::zero()));
+            // the tax of income is credited, so that it is collected in negative
+            match Account::from_str(&tax.account).map(|it| it.account_type) {
+                Ok(AccountType::Income) => collected.sub_assign(&tax.tax.number),
+                _ => paid.add_assign(&tax.tax.number),
+            }
+        }
+        Ok(periods
+            .into_iter()
+            .map(|((period, tax_account, commodity), (collected, paid))| TaxReportDomain {
+                period,
+                tax_account,
+                commodity,
+                payable: (&collected).sub(&paid),
+                collected,
+                paid,
+            })
+            .collect_vec())
+    }
+
+    pub(crate) fn insert_elimination(&mut self, elimination: EliminationDomain) -> ZhangResult<()> {
+        let mut store = self.write();
+        store.eliminations.push(elimination);
+        Ok(())
+    }
+
+    pub(crate) fn insert_invoice(&mut self, invoice: InvoiceDomain) -> ZhangResult<()> {
+        let mut store = self.write();
+        store.invoices.insert(invoice.id.clone(), invoice);
+        Ok(())
+    }
+
+    pub fn invoice(&self, id: &str) -> ZhangResult<Option<InvoiceDomain>> {
+        let store = self.read();
+        Ok(store.invoices.get(id).cloned())
+    }
+
+    /// the outstanding receivables and payables at the given date in the order of due date.
+    /// the payments are the postings of transactions linked to the invoice id, which decrease the balance of invoice account
+    pub fn open_invoices(&self, date: NaiveDate) -> ZhangResult<Vec<OpenInvoiceDomain>> {
+        let store = self.read();
+        let mut ret = vec![];
+        for invoice in store.invoices.values().filter(|invoice| invoice.date.le(&date)) {
+            let mut paid = BigDecimal::zero();
+            for trx in Operations::indexed_transactions(&store, store.link_index.get(&invoice.id)) {
+                for posting in trx.postings.iter() {
+                    if posting.account.name().ne(&invoice.account)
+                        || posting.inferred_amount.currency.ne(&invoice.total.currency)
+                        || posting.datetime.naive_local().date().gt(&date)
+                    {
+                        continue;
+                    }
+                    let payment = match invoice.kind {
+                        InvoiceKind::Receivable => -&posting.inferred_amount.number,
+                        InvoiceKind::Payable => posting.inferred_amount.number.clone(),
+                    };
+                    if payment.is_positive() {
+                        paid.add_assign(payment);
+                    }
+                }
+            }
+            let outstanding = (&invoice.total.number).sub(&paid);
+            if !outstanding.is_positive() {
+                continue;
+            }
+            let days_overdue = (date - invoice.due).num_days().max(0);
+            ret.push(OpenInvoiceDomain {
+                paid: Amount::new(paid, invoice.total.currency.clone()),
+                outstanding: Amount::new(outstanding, invoice.total.currency.clone()),
+                days_overdue,
+                aging: AgingBucket::of(days_overdue),
+                invoice: invoice.clone(),
+            });
+        }
+        ret.sort_by(|a, b| a.invoice.due.cmp(&b.invoice.due).then_with(|| a.invoice.id.cmp(&b.invoice.id)));
+        Ok(ret)
+    }
+
+    pub(crate) fn insert_loan_payment(&mut self, account_name: &str, payment: LoanPaymentDomain) -> ZhangResult<()> {
+        let mut store = self.write();
+        store.loan_payments.entry(account_name.to_owned()).or_default().push(payment);
+        Ok(())
+    }
+
+    /// the amortization schedule of loan account in date order, which tells the remaining principal over time
+    pub fn loan_payments(&self, account_name: impl AsRef<str>) -> ZhangResult<Vec<LoanPaymentDomain>> {
+        let store = self.read();
+        let mut payments = store.loan_payments.get(account_name.as_ref()).cloned().unwrap_or_default();
+        payments.sort_by_key(|payment| payment.datetime);
+        Ok(payments)
+    }
+
     pub fn get_latest_price(&self, from: impl AsRef<str>, to: impl AsRef<str>) -> ZhangResult<Option<PriceDomain>> {
         let store = self.read();
         let option = store
@@ -360,9 +792,9 @@ impl Operations {
 
         let mut ret: HashMap<Account, IndexMap<Currency, BTreeMap<NaiveDate, Amount>>> = HashMap::new();
 
-        for posting in store.postings.iter().cloned().sorted_by_key(|posting| posting.trx_datetime) {
+        for posting in store.postings.iter().cloned().sorted_by_key(|posting| posting.datetime) {
             let posting: PostingDomain = posting;
-            let date = posting.trx_datetime.naive_local().date();
+            let date = posting.datetime.naive_local().date();
 
             let account_inventory = ret.entry(posting.account).or_default();
             let dated_amount = account_inventory.entry(posting.after_amount.currency.clone()).or_default();
@@ -396,12 +828,56 @@ impl Operations {
             .filter(|price| price.commodity.eq(from.as_ref()))
             .filter(|price| price.target_commodity.eq(to.as_ref()))
             .filter(|price| price.datetime.le(&date))
-            .sorted_by(|a, b| a.datetime.cmp(&b.datetime))
-            .next()
+            .max_by_key(|price| price.datetime)
             .cloned();
         Ok(x)
     }
 
+    /// get the price of commodity in target currency at the given date, the nearest prior price is returned.
+    /// when `interpolate` is enabled, the price is linearly interpolated between the nearest prior and next price points
+    pub fn price_at(
+        &self, commodity: impl AsRef<str>, target_currency: impl AsRef<str>, date: NaiveDateTime, interpolate: bool,
+    ) -> ZhangResult<Option<PriceDomain>> {
+        let store = self.read();
+        let prices = store
+            .prices
+            .iter()
+            .filter(|price| price.commodity.eq(commodity.as_ref()))
+            .filter(|price| price.target_commodity.eq(target_currency.as_ref()))
+            .collect_vec();
+
+        let Some(prior) = prices.iter().filter(|price| price.datetime.le(&date)).max_by_key(|price| price.datetime) else {
+            return Ok(None);
+        };
+        if !interpolate || prior.datetime.eq(&date) {
+            return Ok(Some((*prior).clone()));
+        }
+        let Some(next) = prices.iter().filter(|price| price.datetime.gt(&date)).min_by_key(|price| price.datetime) else {
+            return Ok(Some((*prior).clone()));
+        };
+
+        let elapsed = BigDecimal::from((date - prior.datetime).num_seconds());
+        let duration = BigDecimal::from((next.datetime - prior.datetime).num_seconds());
+        let amount = (&prior.amount).add((&next.amount).sub(&prior.amount).mul(elapsed).div(duration));
+        Ok(Some(PriceDomain {
+            datetime: date,
+            amount,
+            ..(*prior).clone()
+        }))
+    }
+
+    /// the price graph of all prices, which is built once and reused until a price is inserted
+    pub fn price_graph(&self) -> ZhangResult<Arc<PriceGraph>> {
+        let store = self.read();
+        Ok(store.price_graph.get_or_init(|| Arc::new(PriceGraph::new(store.prices.iter()))).clone())
+    }
+
+    /// get the rate converting one unit of `from` into `to` at the given date,
+    /// the rate is derived from inverse prices or triangulated via other commodities if there is no direct price
+    pub fn exchange_rate(&self, from: impl AsRef<str>, to: impl AsRef<str>, date: NaiveDateTime) -> ZhangResult<Option<BigDecimal>> {
+        Ok(self.price_graph()?.rate(from.as_ref(), to.as_ref(), date))
+    }
+
     pub fn metas(&self, type_: MetaType, type_identifier: impl AsRef<str>) -> ZhangResult<Vec<MetaDomain>> {
         let store = self.read();
         Ok(store
@@ -438,6 +914,22 @@ impl Operations {
         Ok(tags)
     }
 
+    /// transactions with the tag, in processing order
+    pub fn transactions_by_tag(&self, tag: impl AsRef<str>) -> ZhangResult<Vec<TransactionDomain>> {
+        let store = self.read();
+        Ok(Operations::indexed_transactions(&store, store.tag_index.get(tag.as_ref())))
+    }
+
+    /// transactions with the link, in processing order
+    pub fn transactions_by_link(&self, link: impl AsRef<str>) -> ZhangResult<Vec<TransactionDomain>> {
+        let store = self.read();
+        Ok(Operations::indexed_transactions(&store, store.link_index.get(link.as_ref())))
+    }
+
+    fn indexed_transactions(store: &Store, ids: Option<&Vec<Uuid>>) -> Vec<TransactionDomain> {
+        ids.into_iter().flatten().filter_map(|id| store.transactions.get(id)).cloned().collect_vec()
+    }
+
     pub fn commodity(&self, name: &str) -> ZhangResult<Option<CommodityDomain>> {
         let store = self.read();
         Ok(store.commodities.get(name).cloned())
@@ -453,7 +945,22 @@ impl Operations {
 
     pub fn transaction_counts(&mut self) -> ZhangResult<i64> {
         let store = self.read();
-        Ok(store.transactions.len() as i64)
+        Ok(store.transactions.values().filter(|trx| trx.flag != Flag::Void).count() as i64)
+    }
+
+    /// the page of transactions matching the filter, the latest first, along with the total count of matched transactions
+    pub fn journals(&self, filter: &JournalFilter, offset: usize, limit: usize) -> ZhangResult<(usize, Vec<TransactionDomain>)> {
+        let store = self.read();
+
+        // tag and link filters are resolved by the index instead of scanning all transactions
+        let candidates: Box<dyn Iterator<Item = &TransactionDomain>> = match (&filter.tag, &filter.link) {
+            (Some(tag), _) => Box::new(store.tag_index.get(tag).into_iter().flatten().filter_map(|id| store.transactions.get(id))),
+            (None, Some(link)) => Box::new(store.link_index.get(link).into_iter().flatten().filter_map(|id| store.transactions.get(id))),
+            (None, None) => Box::new(store.transactions.values()),
+        };
+        let matched = candidates.filter(|trx| filter.matches(trx)).sorted_by_key(|trx| -trx.sequence).collect_vec();
+        let total_count = matched.len();
+        Ok((total_count, matched.into_iter().skip(offset).take(limit).cloned().collect_vec()))
     }
 
     pub fn single_transaction(&mut self, id: &Uuid) -> ZhangResult<Option<TransactionDomain>> {
@@ -471,6 +978,12 @@ impl Operations {
         }))
     }
 
+    /// the previous versions of directive of span in the git history of its file, from the latest to the earliest
+    #[cfg(feature = "git")]
+    pub fn directive_history(&self, span: &SpanInfo) -> ZhangResult<Vec<schemas::DirectiveVersionDomain>> {
+        crate::git::directive_history(span, &self.timezone)
+    }
+
     /// get target account's latest balance
     /// because the account can have multiple commodities, so the result is the array.
     pub fn single_account_latest_balances(&self, account_name: &str) -> ZhangResult<Vec<AccountBalanceDomain>> {
@@ -485,10 +998,10 @@ impl Operations {
             .iter()
             .filter(|posting| posting.account.eq(&account))
             .cloned()
-            .sorted_by_key(|posting| posting.trx_datetime)
+            .sorted_by_key(|posting| posting.datetime)
         {
             let posting: PostingDomain = posting;
-            let date = posting.trx_datetime.naive_local().date();
+            let date = posting.datetime.naive_local().date();
 
             let dated_amount = ret.entry(posting.after_amount.currency.clone()).or_default();
             dated_amount.insert(date, posting.after_amount);
@@ -504,6 +1017,7 @@ impl Operations {
                     account_status: AccountStatus::Open,
                     balance_number: amount.number,
                     balance_commodity: amount.currency,
+                    converted: None,
                 }
             })
             .collect_vec())
@@ -523,10 +1037,10 @@ impl Operations {
             .iter()
             .filter(|posting| posting.account.eq(&account))
             .cloned()
-            .sorted_by_key(|posting| posting.trx_datetime)
+            .sorted_by_key(|posting| posting.datetime)
         {
             let posting: PostingDomain = posting;
-            let date = posting.trx_datetime.naive_local().date();
+            let date = posting.datetime.naive_local().date();
 
             let dated_amount = ret.entry(posting.after_amount.currency.clone()).or_default();
             dated_amount.insert(date, posting.after_amount);
@@ -536,29 +1050,33 @@ impl Operations {
     }
 
     pub fn account_journals(&mut self, account: &str) -> ZhangResult<Vec<AccountJournalDomain>> {
+        self.account_journal(account, ..)
+    }
+
+    /// journal of account within the datetime range, the latest first, which is found by the posting index of account
+    pub fn account_journal(&self, account: &str, range: impl RangeBounds<DateTime<Utc>>) -> ZhangResult<Vec<AccountJournalDomain>> {
         let store = self.read();
         let account = Account::from_str(account).map_err(|_| ZhangError::InvalidAccount)?;
 
         let mut ret = vec![];
-        for posting in store.postings.iter().filter(|posting| posting.account.eq(&account)).cloned().sorted_by(|a, b| {
-            a.trx_datetime
-                .cmp(&b.trx_datetime)
-                .reverse()
-                .then(a.trx_sequence.cmp(&b.trx_sequence).reverse())
-        }) {
-            let posting: PostingDomain = posting;
+        for posting in store
+            .posting_index
+            .journal(account.name(), range)
+            .rev()
+            .flat_map(|positions| positions.iter().map(|position| &store.postings[*position]))
+        {
             let trx_header = store.transactions.get(&posting.trx_id);
             ret.push(AccountJournalDomain {
-                datetime: posting.trx_datetime.naive_local(),
-                timestamp: posting.trx_datetime.timestamp(),
+                datetime: posting.datetime.naive_local(),
+                timestamp: posting.datetime.timestamp(),
                 account: posting.account.name().to_owned(),
                 trx_id: posting.id.to_string(),
                 payee: trx_header.and_then(|it| it.payee.clone()),
                 narration: trx_header.and_then(|it| it.narration.clone()),
-                inferred_unit_number: posting.inferred_amount.number,
-                inferred_unit_commodity: posting.inferred_amount.currency,
-                account_after_number: posting.after_amount.number,
-                account_after_commodity: posting.after_amount.currency,
+                inferred_unit_number: posting.inferred_amount.number.clone(),
+                inferred_unit_commodity: posting.inferred_amount.currency.clone(),
+                account_after_number: posting.after_amount.number.clone(),
+                account_after_commodity: posting.after_amount.currency.clone(),
             })
         }
         Ok(ret)
@@ -569,8 +1087,8 @@ impl Operations {
         Ok(store
             .postings
             .iter()
-            .filter(|posting| posting.trx_datetime.ge(&from))
-            .filter(|posting| posting.trx_datetime.le(&to))
+            .filter(|posting| posting.datetime.ge(&from))
+            .filter(|posting| posting.datetime.le(&to))
             .cloned()
             .collect_vec())
     }
@@ -581,16 +1099,16 @@ impl Operations {
         for posting in store
             .postings
             .iter()
-            .filter(|posting| posting.trx_datetime.ge(&from))
-            .filter(|posting| posting.trx_datetime.le(&to))
+            .filter(|posting| posting.datetime.ge(&from))
+            .filter(|posting| posting.datetime.le(&to))
             .filter(|posting| posting.account.account_type == account_type)
             .cloned()
         {
             let trx = store.transactions.get(&posting.trx_id).cloned().expect("cannot find trx");
 
             ret.push(AccountJournalDomain {
-                datetime: posting.trx_datetime.naive_local(),
-                timestamp: posting.trx_datetime.timestamp(),
+                datetime: posting.datetime.naive_local(),
+                timestamp: posting.datetime.timestamp(),
                 account: posting.account.name().to_owned(),
                 trx_id: posting.trx_id.to_string(),
                 payee: trx.payee,
@@ -610,16 +1128,16 @@ impl Operations {
         for posting in store
             .postings
             .iter()
-            .filter(|posting| posting.trx_datetime.ge(&from))
-            .filter(|posting| posting.trx_datetime.le(&to))
+            .filter(|posting| posting.datetime.ge(&from))
+            .filter(|posting| posting.datetime.le(&to))
             .filter(|posting| accounts.contains(&posting.account.content))
             .cloned()
         {
             let trx = store.transactions.get(&posting.trx_id).cloned().expect("cannot find trx");
 
             ret.push(AccountJournalDomain {
-                datetime: posting.trx_datetime.naive_local(),
-                timestamp: posting.trx_datetime.timestamp(),
+                datetime: posting.datetime.naive_local(),
+                timestamp: posting.datetime.timestamp(),
                 account: posting.account.name().to_owned(),
                 trx_id: posting.trx_id.to_string(),
                 payee: trx.payee,
@@ -637,6 +1155,11 @@ impl Operations {
         let store = self.read();
         Ok(store.errors.iter().cloned().collect_vec())
     }
+    /// the errors whose severity is at least the given one
+    pub fn errors_by_severity(&mut self, severity: ErrorSeverity) -> ZhangResult<Vec<ErrorDomain>> {
+        let store = self.read();
+        Ok(store.errors.iter().filter(|error| error.severity >= severity).cloned().collect_vec())
+    }
     pub fn errors_by_meta(&mut self, key: &str, value: &str) -> ZhangResult<Vec<ErrorDomain>> {
         let store = self.read();
         Ok(store
@@ -679,18 +1202,55 @@ impl Operations {
         Ok(payees.into_iter().collect_vec())
     }
 
-    pub fn static_duration(&mut self, from: DateTime<Utc>, to: DateTime<Utc>) -> ZhangResult<Vec<StaticRow>> {
+    /// registry of all payees, sorted by transaction count in descending order
+    pub fn payees(&self) -> ZhangResult<Vec<PayeeDomain>> {
+        const TYPICAL_ACCOUNTS_SIZE: usize = 3;
+        let store = self.read();
+        let payee_transactions = store
+            .transactions
+            .values()
+            .filter(|it| it.payee.as_ref().map(|payee| !payee.is_empty()).unwrap_or(false))
+            .into_group_map_by(|it| it.payee.clone().unwrap_or_default());
+
+        Ok(payee_transactions
+            .into_iter()
+            .map(|(name, transactions)| {
+                let dates = transactions.iter().map(|it| it.datetime.naive_local().date()).collect_vec();
+                let typical_accounts = transactions
+                    .iter()
+                    .flat_map(|it| it.postings.iter().map(|posting| posting.account.name().to_owned()))
+                    .counts()
+                    .into_iter()
+                    .sorted_by(|(a_account, a_count), (b_account, b_count)| b_count.cmp(a_count).then(a_account.cmp(b_account)))
+                    .take(TYPICAL_ACCOUNTS_SIZE)
+                    .map(|(account, _)| account)
+                    .collect_vec();
+                PayeeDomain {
+                    name,
+                    transaction_count: transactions.len(),
+                    first_seen: dates.iter().min().copied().expect("payee should have transactions"),
+                    last_seen: dates.iter().max().copied().expect("payee should have transactions"),
+                    typical_accounts,
+                }
+            })
+            .sorted_by(|a, b| b.transaction_count.cmp(&a.transaction_count).then(a.name.cmp(&b.name)))
+            .collect_vec())
+    }
+
+    /// the balance of each account type and commodity by date, the balance is valued in the currency of `convert` if it is given
+    pub fn static_duration(&mut self, from: DateTime<Utc>, to: DateTime<Utc>, convert: Option<&Conversion>) -> ZhangResult<Vec<StaticRow>> {
+        let price_graph = convert.map(|_| self.price_graph()).transpose()?;
         let store = self.read();
         let mut cal: HashMap<NaiveDate, HashMap<AccountType, HashMap<Currency, BigDecimal>>> = HashMap::new();
 
         for posting in store
             .postings
             .iter()
-            .filter(|posting| posting.trx_datetime.ge(&from))
-            .filter(|posting| posting.trx_datetime.le(&to))
+            .filter(|posting| posting.datetime.ge(&from))
+            .filter(|posting| posting.datetime.le(&to))
             .cloned()
         {
-            let date = posting.trx_datetime.naive_local().date();
+            let date = posting.datetime.naive_local().date();
             let date_store = cal.entry(date).or_default();
             let account_type_store = date_store.entry(posting.account.account_type).or_default();
             let balance = account_type_store.entry(posting.after_amount.currency).or_insert_with(BigDecimal::zero);
@@ -701,11 +1261,15 @@ impl Operations {
         for (date, type_store) in cal {
             for (account_type, currency_store) in type_store {
                 for (currency, balance) in currency_store {
+                    let converted = convert
+                        .zip(price_graph.as_ref())
+                        .and_then(|(conversion, graph)| conversion.convert(graph, &balance, &currency));
                     ret.push(StaticRow {
                         date,
                         account_type: account_type.to_string(),
                         amount: balance,
                         commodity: currency,
+                        converted,
                     })
                 }
             }
@@ -713,40 +1277,342 @@ impl Operations {
         Ok(ret)
     }
 
-    pub fn account_target_date_balance(&self, account_name: impl AsRef<str>, date: DateTime<Utc>) -> ZhangResult<Vec<AccountBalanceDomain>> {
+    /// get target account's balance at the given date, the balance is valued in the currency of `convert` if it is given
+    pub fn account_target_date_balance(
+        &self, account_name: impl AsRef<str>, date: DateTime<Utc>, convert: Option<&Conversion>,
+    ) -> ZhangResult<Vec<AccountBalanceDomain>> {
+        let price_graph = convert.map(|_| self.price_graph()).transpose()?;
         let store = self.read();
 
         let account = Account::from_str(account_name.as_ref()).map_err(|_| ZhangError::InvalidAccount)?;
 
-        let mut ret: IndexMap<Currency, BTreeMap<NaiveDate, Amount>> = IndexMap::new();
+        Ok(store
+            .posting_index
+            .balances(account.name(), date)
+            .map(|position| {
+                let posting = &store.postings[position];
+                let date = posting.datetime.naive_local().date();
+                let amount = posting.after_amount.clone();
+                let converted = convert
+                    .zip(price_graph.as_ref())
+                    .and_then(|(conversion, graph)| conversion.convert(graph, &amount.number, &amount.currency));
+                AccountBalanceDomain {
+                    datetime: date.and_time(NaiveTime::default()),
+                    account: account.name().to_owned(),
+                    account_status: AccountStatus::Open,
+                    balance_number: amount.number,
+                    balance_commodity: amount.currency,
+                    converted,
+                }
+            })
+            .collect_vec())
+    }
+    /// time series of balances per account and commodity, bucketed by the interval.
+    /// balances are computed in a single pass over postings, the periods without postings carry the balance of previous period
+    /// until the period of the latest posting
+    pub fn account_balances_over_time(&self, interval: BalanceInterval) -> ZhangResult<Vec<AccountBalanceSeriesDomain>> {
+        let store = self.read();
+        let mut running: BTreeMap<(String, Currency), BigDecimal> = BTreeMap::new();
+        let mut series: BTreeMap<(String, Currency), BTreeMap<NaiveDate, BigDecimal>> = BTreeMap::new();
+        let mut last_period = None;
+        for posting in store.postings.iter().sorted_by_key(|posting| (posting.datetime, posting.trx_sequence)) {
+            let period = interval.period_start(posting.datetime.naive_local().date());
+            let key = (posting.account.name().to_owned(), posting.inferred_amount.currency.clone());
+            let balance = running.entry(key.clone()).or_insert_with(BigDecimal::zero);
+            balance.add_assign(&posting.inferred_amount.number);
+            series.entry(key).or_default().insert(period, balance.clone());
+            last_period = Some(period);
+        }
+        let Some(last_period) = last_period else {
+            return Ok(vec![]);
+        };
 
-        for posting in store
+        Ok(series
+            .into_iter()
+            .map(|((account, commodity), mut balances)| {
+                let mut period = *balances.keys().next().expect("series should not be empty");
+                let mut balance = BigDecimal::zero();
+                while period <= last_period {
+                    balance = balances.entry(period).or_insert(balance).clone();
+                    period = interval.next_period_start(period);
+                }
+                AccountBalanceSeriesDomain { account, commodity, balances }
+            })
+            .collect_vec())
+    }
+
+    /// net worth in the currency at the end of each period, from the period of the earliest posting to the one of the latest posting.
+    /// the market value values the units of holdings, while the cost value values the posting weights, e.g. the cost of lots.
+    /// commodities without exchange rate into the currency are skipped
+    pub fn net_worth_series(&self, interval: BalanceInterval, currency: impl AsRef<str>) -> ZhangResult<Vec<NetWorthDomain>> {
+        let currency = currency.as_ref();
+        let postings = self
+            .read()
             .postings
             .iter()
-            .filter(|posting| posting.account.eq(&account))
-            .filter(|positing| positing.trx_datetime.le(&date))
+            .filter(|posting| matches!(posting.account.account_type, AccountType::Assets | AccountType::Liabilities))
             .cloned()
-            .sorted_by_key(|posting| posting.trx_datetime)
+            .sorted_by_key(|posting| (posting.datetime, posting.trx_sequence))
+            .collect_vec();
+        let (Some(first), Some(last)) = (postings.first(), postings.last()) else {
+            return Ok(vec![]);
+        };
+        let last_period = interval.period_start(last.datetime.naive_local().date());
+        let mut period = interval.period_start(first.datetime.naive_local().date());
+
+        let mut units: HashMap<Currency, BigDecimal> = HashMap::new();
+        let mut weights: HashMap<Currency, BigDecimal> = HashMap::new();
+        let price_graph = self.price_graph()?;
+        let mut postings = postings.into_iter().peekable();
+        let mut ret = vec![];
+        while period <= last_period {
+            let next_period = interval.next_period_start(period);
+            while let Some(posting) = postings.next_if(|posting| posting.datetime.naive_local().date() < next_period) {
+                let unit = posting.unit.unwrap_or_else(|| posting.inferred_amount.clone());
+                units.entry(unit.currency).or_insert_with(BigDecimal::zero).add_assign(&unit.number);
+                weights
+                    .entry(posting.inferred_amount.currency)
+                    .or_insert_with(BigDecimal::zero)
+                    .add_assign(&posting.inferred_amount.number);
+            }
+
+            let period_end = next_period
+                .pred_opt()
+                .and_then(|last_date| last_date.and_hms_opt(23, 59, 59))
+                .expect("end of period should be valid");
+            let value = |holdings: &HashMap<Currency, BigDecimal>| {
+                let total = holdings
+                    .iter()
+                    .filter_map(|(commodity, number)| price_graph.rate(commodity, currency, period_end).map(|rate| number.mul(rate)))
+                    .fold(BigDecimal::zero(), |total, value| total + value);
+                Amount::new(total, currency)
+            };
+            ret.push(NetWorthDomain {
+                date: period,
+                market_value: value(&units),
+                cost_value: value(&weights),
+            });
+            period = next_period;
+        }
+        Ok(ret)
+    }
+
+    /// net worth in the currency at the date valued by the latest prices at that time, commodities without exchange rate
+    /// into the currency are skipped
+    pub fn net_worth(&self, date: NaiveDateTime, currency: impl AsRef<str>) -> ZhangResult<Amount> {
+        let currency = currency.as_ref();
+        let mut units: HashMap<Currency, BigDecimal> = HashMap::new();
+        for posting in self
+            .read()
+            .postings
+            .iter()
+            .filter(|posting| matches!(posting.account.account_type, AccountType::Assets | AccountType::Liabilities))
+            .filter(|posting| posting.datetime.naive_local() <= date)
         {
-            let posting: PostingDomain = posting;
-            let date = posting.trx_datetime.naive_local().date();
+            let unit = posting.unit.clone().unwrap_or_else(|| posting.inferred_amount.clone());
+            units.entry(unit.currency).or_insert_with(BigDecimal::zero).add_assign(&unit.number);
+        }
+        let price_graph = self.price_graph()?;
+        let total = units
+            .iter()
+            .filter_map(|(commodity, number)| price_graph.rate(commodity, currency, date).map(|rate| number.mul(rate)))
+            .fold(BigDecimal::zero(), |total, value| total + value);
+        Ok(Amount::new(total, currency))
+    }
 
-            let dated_amount = ret.entry(posting.after_amount.currency.clone()).or_default();
-            dated_amount.insert(date, posting.after_amount);
+    /// the lots held by accounts at the given date grouped per account and commodity, valued by the latest prices at the given date.
+    /// the lots of account are the ones after its last change not later than the date
+    pub fn holdings(&self, date: NaiveDateTime) -> ZhangResult<Vec<HoldingDomain>> {
+        let operating_currency = self.option::<String>(KEY_OPERATING_CURRENCY)?;
+        let price_graph = self.price_graph()?;
+        let store = self.read();
+
+        let mut account_lots: HashMap<&String, &Vec<CommodityLotRecord>> = HashMap::new();
+        for snapshot in store
+            .commodity_lot_history
+            .iter()
+            .filter(|snapshot| snapshot.datetime.naive_local() <= date)
+            .sorted_by_key(|snapshot| snapshot.datetime)
+        {
+            account_lots.insert(&snapshot.account, &snapshot.lots);
         }
 
-        Ok(ret
+        let mut holdings: BTreeMap<(String, Currency), Vec<HoldingLotDomain>> = BTreeMap::new();
+        for (account, lots) in account_lots {
+            for lot in lots.iter().filter(|lot| !lot.amount.is_zero()) {
+                let valuation_currency = lot.price.as_ref().map(|cost| cost.currency.clone()).or_else(|| operating_currency.clone());
+                let price = valuation_currency.and_then(|currency| price_graph.rate(&lot.commodity, &currency, date).map(|rate| Amount::new(rate, currency)));
+                let cost_basis = lot
+                    .price
+                    .as_ref()
+                    .map(|cost| Amount::new((&cost.number).mul(&lot.amount), cost.currency.clone()));
+                let market_value = price
+                    .as_ref()
+                    .map(|price| Amount::new((&price.number).mul(&lot.amount), price.currency.clone()));
+                let unrealized_gain = match (&cost_basis, &market_value) {
+                    (Some(cost_basis), Some(market_value)) => Some(Amount::new((&market_value.number).sub(&cost_basis.number), cost_basis.currency.clone())),
+                    _ => None,
+                };
+                holdings.entry((account.clone(), lot.commodity.clone())).or_default().push(HoldingLotDomain {
+                    datetime: lot.datetime,
+                    label: lot.label.clone(),
+                    amount: lot.amount.clone(),
+                    cost: lot.price.clone(),
+                    cost_basis,
+                    price,
+                    market_value,
+                    unrealized_gain,
+                });
+            }
+        }
+
+        Ok(holdings
             .into_iter()
-            .map(|(_, mut balance)| {
-                let (date, amount) = balance.pop_last().expect("");
-                AccountBalanceDomain {
-                    datetime: date.and_time(NaiveTime::default()),
-                    account: account.name().to_owned(),
-                    account_status: AccountStatus::Open,
-                    balance_number: amount.number,
-                    balance_commodity: amount.currency,
-                }
+            .map(|((account, commodity), lots)| HoldingDomain {
+                account,
+                commodity,
+                amount: lots.iter().fold(BigDecimal::zero(), |total, lot| total.add(&lot.amount)),
+                cost_basis: sum_amounts(lots.iter().map(|lot| lot.cost_basis.as_ref())),
+                market_value: sum_amounts(lots.iter().map(|lot| lot.market_value.as_ref())),
+                unrealized_gain: sum_amounts(lots.iter().map(|lot| lot.unrealized_gain.as_ref())),
+                lots,
+            })
+            .collect_vec())
+    }
+
+    /// trial balance of all accounts at the given date, the positive balance is the debit and the negative one is the credit
+    pub fn trial_balance(&self, date: DateTime<Utc>) -> ZhangResult<TrialBalanceDomain> {
+        self.filtered_trial_balance(date, |_| true)
+    }
+
+    /// trial balance of the accounts belonging to the entity by their `entity` meta
+    pub fn entity_trial_balance(&self, date: DateTime<Utc>, entity: impl AsRef<str>) -> ZhangResult<TrialBalanceDomain> {
+        self.filtered_trial_balance(date, self.posting_scope(Some(entity.as_ref()), false))
+    }
+
+    /// trial balance of all entities as a whole, the postings into the accounts of elimination rules in effect are excluded
+    pub fn consolidated_trial_balance(&self, date: DateTime<Utc>) -> ZhangResult<TrialBalanceDomain> {
+        self.filtered_trial_balance(date, self.posting_scope(None, true))
+    }
+
+    /// whether the posting is in the scope of report: the postings of accounts belonging to the entity if it is given,
+    /// or the postings of all entities as a whole except the ones into the accounts of elimination rules in effect if consolidated
+    pub fn posting_scope(&self, entity: Option<&str>, consolidated: bool) -> impl Fn(&PostingDomain) -> bool {
+        let entity = entity.map(|entity| (entity.to_owned(), self.account_entities()));
+        let eliminations = if entity.is_none() && consolidated {
+            self.read().eliminations.clone()
+        } else {
+            vec![]
+        };
+        move |posting| match &entity {
+            Some((entity, entities)) => entities.get(posting.account.name()).is_some_and(|it| it.eq(entity)),
+            None => !eliminations
+                .iter()
+                .any(|elimination| elimination.date.le(&posting.datetime.naive_local().date()) && elimination.eliminates(posting.account.name())),
+        }
+    }
+
+    /// the entity of accounts which have `entity` meta
+    fn account_entities(&self) -> HashMap<String, String> {
+        let store = self.read();
+        store
+            .metas
+            .iter()
+            .filter(|meta| meta.meta_type.eq(MetaType::AccountMeta.as_ref()) && meta.key.eq(ACCOUNT_ENTITY))
+            .map(|meta| (meta.type_identifier.clone(), meta.value.trim().to_owned()))
+            .collect()
+    }
+
+    fn filtered_trial_balance(&self, date: DateTime<Utc>, filter: impl Fn(&PostingDomain) -> bool) -> ZhangResult<TrialBalanceDomain> {
+        let store = self.read();
+        let mut balances: BTreeMap<(String, Currency), BigDecimal> = BTreeMap::new();
+        for posting in store
+            .postings
+            .iter()
+            .filter(|posting| posting.datetime.le(&date))
+            .filter(|posting| filter(posting))
+        {
+            let balance = balances
+                .entry((posting.account.name().to_owned(), posting.inferred_amount.currency.clone()))
+                .or_insert_with(BigDecimal::zero);
+            balance.add_assign(&posting.inferred_amount.number);
+        }
+
+        let mut accounts = vec![];
+        let mut totals: BTreeMap<Currency, (BigDecimal, BigDecimal)> = BTreeMap::new();
+        for ((account, commodity), balance) in balances {
+            let (debit, credit) = if balance.is_negative() {
+                (BigDecimal::zero(), balance.abs())
+            } else {
+                (balance, BigDecimal::zero())
+            };
+            let (total_debit, total_credit) = totals.entry(commodity.clone()).or_default();
+            total_debit.add_assign(&debit);
+            total_credit.add_assign(&credit);
+            accounts.push(TrialBalanceAccountDomain {
+                account,
+                commodity,
+                debit,
+                credit,
+            });
+        }
+        let totals = totals
+            .into_iter()
+            .map(|(commodity, (debit, credit))| TrialBalanceTotalDomain { commodity, debit, credit })
+            .collect_vec();
+        let balanced = totals.iter().all(|total| total.debit.eq(&total.credit));
+        Ok(TrialBalanceDomain { accounts, totals, balanced })
+    }
+
+    /// key/value data written by plugin handling custom directives
+    pub fn plugin_data(&self, plugin_name: impl AsRef<str>) -> ZhangResult<IndexMap<String, serde_json::Value>> {
+        let store = self.read();
+        Ok(store.plugin_data.get(plugin_name.as_ref()).cloned().unwrap_or_default())
+    }
+
+    /// documents whose content has the given hash
+    pub fn documents_by_hash(&self, hash: impl AsRef<str>) -> ZhangResult<Vec<DocumentDomain>> {
+        let store = self.read();
+        Ok(store
+            .documents
+            .iter()
+            .filter(|document| document.hash.as_deref() == Some(hash.as_ref()))
+            .cloned()
+            .collect_vec())
+    }
+
+    /// search documents whose filename or extracted text contains all the words of query, case-insensitively
+    pub fn search_documents(&self, query: impl AsRef<str>) -> ZhangResult<Vec<DocumentDomain>> {
+        let store = self.read();
+        let words = query.as_ref().to_lowercase().split_whitespace().map(|it| it.to_owned()).collect_vec();
+        if words.is_empty() {
+            return Ok(vec![]);
+        }
+        Ok(store
+            .documents
+            .iter()
+            .filter(|document| {
+                let filename = document.filename.as_deref().unwrap_or_default().to_lowercase();
+                let text = document.hash.as_ref().and_then(|hash| store.document_texts.get(hash));
+                words
+                    .iter()
+                    .all(|word| filename.contains(word) || text.map(|text| text.contains(word)).unwrap_or(false))
             })
+            .cloned()
+            .collect_vec())
+    }
+
+    /// documents with the same content attached more than once, grouped by the content hash
+    pub fn duplicated_documents(&self) -> ZhangResult<Vec<(String, Vec<DocumentDomain>)>> {
+        let store = self.read();
+        Ok(store
+            .documents
+            .iter()
+            .filter_map(|document| document.hash.clone().map(|hash| (hash, document.clone())))
+            .into_group_map()
+            .into_iter()
+            .filter(|(_, documents)| documents.len() > 1)
+            .sorted_by(|a, b| a.0.cmp(&b.0))
             .collect_vec())
     }
 }
@@ -757,6 +1623,7 @@ impl Operations {
         let mut store = self.write();
         store.errors.push(ErrorDomain {
             id: Uuid::from_span(span).to_string(),
+            severity: error_kind.severity(),
             error_type: error_kind,
             span: Some(span.clone()),
             metas,
@@ -764,6 +1631,52 @@ impl Operations {
         Ok(())
     }
 
+    /// reconcile the account against the statement balance on `statement_date`: the sum of cleared and reconciled postings
+    /// until the end of statement date must equal to the statement balance, then the cleared postings are marked reconciled
+    /// with the statement date. pending postings are not touched. return the ids of postings which become reconciled
+    pub fn reconcile(&mut self, account_name: &str, statement_date: NaiveDate, statement_balance: &Amount) -> ZhangResult<Vec<Uuid>> {
+        let mut store = self.write();
+        let account = Account::from_str(account_name).map_err(|_| ZhangError::InvalidAccount)?;
+        let is_reconcilable = |posting: &PostingDomain| {
+            posting.account.eq(&account)
+                && posting.inferred_amount.currency.eq(&statement_balance.currency)
+                && posting.datetime.date_naive().le(&statement_date)
+                && posting.reconcile_state != ReconcileState::Pending
+        };
+        let cleared_balance: BigDecimal = store
+            .postings
+            .iter()
+            .filter(|posting| is_reconcilable(posting))
+            .map(|posting| &posting.inferred_amount.number)
+            .sum();
+        if cleared_balance.ne(&statement_balance.number) {
+            return Err(ZhangError::ReconcileError(format!(
+                "the cleared balance of account {} is {} {} on {}, but the statement balance is {}",
+                account_name, cleared_balance, statement_balance.currency, statement_date, statement_balance
+            )));
+        }
+
+        let mut reconciled = vec![];
+        for posting in store.postings.iter_mut() {
+            if is_reconcilable(posting) && posting.reconcile_state == ReconcileState::Cleared {
+                posting.reconcile_state = ReconcileState::Reconciled;
+                posting.statement_date = Some(statement_date);
+                reconciled.push((posting.trx_id, posting.id));
+            }
+        }
+        for (trx_id, posting_id) in reconciled.iter() {
+            if let Some(posting) = store
+                .transactions
+                .get_mut(trx_id)
+                .and_then(|trx| trx.postings.iter_mut().find(|posting| posting.id.eq(posting_id)))
+            {
+                posting.reconcile_state = ReconcileState::Reconciled;
+                posting.statement_date = Some(statement_date);
+            }
+        }
+        Ok(reconciled.into_iter().map(|(_, posting_id)| posting_id).collect_vec())
+    }
+
     pub fn insert_or_update_options(&mut self, key: &str, value: &str) -> ZhangResult<()> {
         let mut store = self.write();
 
@@ -807,18 +1720,9 @@ impl Operations {
         Ok(())
     }
 
-    pub fn insert_commodity(&mut self, name: &String, precision: i32, prefix: Option<String>, suffix: Option<String>, rounding: Rounding) -> ZhangResult<()> {
+    pub fn insert_commodity(&mut self, commodity: CommodityDomain) -> ZhangResult<()> {
         let mut store = self.write();
-        store.commodities.insert(
-            name.to_owned(),
-            CommodityDomain {
-                name: name.to_owned(),
-                precision,
-                prefix,
-                suffix,
-                rounding,
-            },
-        );
+        store.commodities.insert(commodity.name.clone(), commodity);
         Ok(())
     }
 }
@@ -840,6 +1744,7 @@ impl Operations {
     /// init or create a new budget
     pub fn init_budget(
         &mut self, name: impl Into<String>, commodity: impl Into<String>, date: DateTime<Tz>, alias: Option<String>, category: Option<String>,
+        rollover: BudgetRollover,
     ) -> ZhangResult<()> {
         let mut store = self.write();
         let name = name.into();
@@ -853,6 +1758,7 @@ impl Operations {
             category,
             closed: false,
             detail: Default::default(),
+            rollover,
         });
         budget_domain.detail.entry(interval).or_insert(BudgetIntervalDetail {
             date: interval,
@@ -882,13 +1788,63 @@ impl Operations {
                     BudgetIntervalDetail {
                         date: interval,
                         events: vec![],
-                        assigned_amount: fetched_detail.assigned_amount.sub(fetched_detail.activity_amount.number),
+                        assigned_amount: target_budget
+                            .rollover
+                            .carry(fetched_detail.assigned_amount.sub(fetched_detail.activity_amount.number)),
                         activity_amount: Amount::zero(&target_budget.commodity),
                     }
                 }
             }))
     }
 
+    /// get target month's available amount, including the amount carried from previous months by the rollover policy
+    pub fn budget_available_amount(&self, name: impl Into<String>, interval: u32) -> ZhangResult<Option<Amount>> {
+        let detail = self.budget_month_detail(name, interval)?;
+        Ok(detail.map(|detail| detail.assigned_amount.sub(detail.activity_amount.number)))
+    }
+
+    /// report of budgeted amount, actual spend and variance for each budget in every month or quarter
+    pub fn budget_report(&self, interval: BudgetReportInterval) -> ZhangResult<Vec<BudgetReportDomain>> {
+        let store = self.read();
+        let mut ret = vec![];
+        for budget in store.budgets.values().sorted_by(|a, b| a.name.cmp(&b.name)) {
+            let mut periods: BTreeMap<u32, (BigDecimal, BigDecimal)> = BTreeMap::new();
+            for (month, detail) in budget.detail.iter() {
+                let period = match interval {
+                    BudgetReportInterval::Month => *month,
+                    BudgetReportInterval::Quarter => (month / 100) * 10 + (month % 100).div_ceil(3),
+                };
+                let budgeted: BigDecimal = detail.events.iter().map(|event| &event.amount.number).sum();
+                let (period_budgeted, period_actual) = periods.entry(period).or_insert_with(|| (BigDecimal::zero(), BigDecimal::zero()));
+                period_budgeted.add_assign(budgeted);
+                period_actual.add_assign(&detail.activity_amount.number);
+            }
+
+            for (period, (budgeted, actual)) in periods {
+                let period = match interval {
+                    BudgetReportInterval::Month => format!("{}-{:02}", period / 100, period % 100),
+                    BudgetReportInterval::Quarter => format!("{}-Q{}", period / 10, period % 10),
+                };
+                let percent_consumed = if budgeted.is_zero() {
+                    None
+                } else {
+                    Some((&actual).mul(BigDecimal::from(100)).div(&budgeted).round(2))
+                };
+                ret.push(BudgetReportDomain {
+                    name: budget.name.clone(),
+                    alias: budget.alias.clone(),
+                    category: budget.category.clone(),
+                    period,
+                    variance: Amount::new((&budgeted).sub(&actual), &budget.commodity),
+                    budgeted_amount: Amount::new(budgeted, &budget.commodity),
+                    actual_amount: Amount::new(actual, &budget.commodity),
+                    percent_consumed,
+                });
+            }
+        }
+        Ok(ret)
+    }
+
     /// add amount to target month's budget
     pub fn budget_add_assigned_amount(&mut self, name: impl Into<String>, date: DateTime<Tz>, event_type: BudgetEventType, amount: Amount) -> ZhangResult<()> {
         let name = name.into();
@@ -960,8 +1916,23 @@ impl Operations {
         Ok(())
     }
 
+    /// get the commodity of budget
+    pub fn budget_commodity(&self, name: impl AsRef<str>) -> ZhangResult<Option<String>> {
+        let store = self.read();
+        Ok(store.budgets.get(name.as_ref()).map(|budget| budget.commodity.clone()))
+    }
+
     pub fn get_account_budget(&self, account_name: impl AsRef<str>) -> ZhangResult<Vec<String>> {
         let metas = self.metas(MetaType::AccountMeta, account_name)?;
         Ok(metas.into_iter().filter(|meta| meta.key.eq("budget")).map(|meta| meta.value).collect_vec())
     }
 }
+
+/// the sum of amounts, `None` if any amount is missing or they are in different commodities
+fn sum_amounts<'a>(mut amounts: impl Iterator<Item = Option<&'a Amount>>) -> Option<Amount> {
+    let first = amounts.next()??.clone();
+    amounts.try_fold(first, |total, amount| {
+        let amount = amount.filter(|amount| amount.currency.eq(&total.currency))?;
+        Some(Amount::new(total.number.add(&amount.number), total.currency))
+    })
+}
@@ -0,0 +1,95 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::{Div, Mul};
+
+use bigdecimal::{BigDecimal, One, Zero};
+use chrono::NaiveDateTime;
+use itertools::Itertools;
+use zhang_ast::Currency;
+
+use crate::domains::schemas::PriceDomain;
+
+/// `PriceGraph` is the graph of commodities connected by prices, used to resolve the exchange rate between any two commodities.
+/// each price `A rate B` is an edge from `A` to `B` with the rate, and an inverse edge from `B` to `A` with `1 / rate`.
+/// the direct price is preferred over the derived inverse one if both of them exist.
+///
+/// the prices of each edge are kept by date, so that the graph is built once from all prices and resolves the rates at any date
+#[derive(Debug, Default)]
+pub struct PriceGraph {
+    // the prices from commodity to target commodity sorted by datetime, the target without price is the inverse edge
+    edges: HashMap<Currency, HashMap<Currency, Vec<(NaiveDateTime, BigDecimal)>>>,
+}
+
+impl PriceGraph {
+    /// build the graph from prices, the prices between the same commodities at the same time are kept in declaring order
+    pub fn new<'a>(prices: impl IntoIterator<Item = &'a PriceDomain>) -> Self {
+        let mut graph = PriceGraph::default();
+        for price in prices {
+            graph
+                .edges
+                .entry(price.commodity.clone())
+                .or_default()
+                .entry(price.target_commodity.clone())
+                .or_default()
+                .push((price.datetime, price.amount.clone()));
+            graph
+                .edges
+                .entry(price.target_commodity.clone())
+                .or_default()
+                .entry(price.commodity.clone())
+                .or_default();
+        }
+        graph
+            .edges
+            .values_mut()
+            .flat_map(|targets| targets.values_mut())
+            .for_each(|prices| prices.sort_by_key(|(datetime, _)| *datetime));
+        graph
+    }
+
+    /// the latest price from `from` to `to` at the date, the zero price is ignored
+    fn latest_price(&self, from: &str, to: &str, date: NaiveDateTime) -> Option<&BigDecimal> {
+        let prices = self.edges.get(from)?.get(to)?;
+        let position = prices.partition_point(|(datetime, _)| datetime.le(&date));
+        position.checked_sub(1).map(|idx| &prices[idx].1).filter(|price| !price.is_zero())
+    }
+
+    /// the rate of edge at the date, which is the direct price or the inverse of the reverse one
+    fn edge_rate(&self, from: &str, to: &str, date: NaiveDateTime) -> Option<BigDecimal> {
+        self.latest_price(from, to, date)
+            .cloned()
+            .or_else(|| self.latest_price(to, from, date).map(|price| BigDecimal::one().div(price)))
+    }
+
+    /// resolve the rate converting one unit of `from` into `to` at the date via the path with fewest hops,
+    /// only the prices defined at or before the date are used.
+    /// visited commodities are skipped so cycles in the graph are not walked twice.
+    pub fn rate(&self, from: &str, to: &str, date: NaiveDateTime) -> Option<BigDecimal> {
+        if from.eq(to) {
+            return Some(BigDecimal::one());
+        }
+        let mut visited: HashSet<&str> = HashSet::from([from]);
+        let mut queue: VecDeque<(&str, BigDecimal)> = VecDeque::from([(from, BigDecimal::one())]);
+
+        while let Some((commodity, rate)) = queue.pop_front() {
+            let Some(targets) = self.edges.get(commodity) else {
+                continue;
+            };
+            // targets are sorted so that the resolved path is deterministic
+            for target in targets.keys().sorted() {
+                if visited.contains(target.as_str()) {
+                    continue;
+                }
+                let Some(target_rate) = self.edge_rate(commodity, target, date) else {
+                    continue;
+                };
+                visited.insert(target.as_str());
+                let next_rate = (&rate).mul(target_rate);
+                if target.eq(to) {
+                    return Some(next_rate);
+                }
+                queue.push_back((target.as_str(), next_rate));
+            }
+        }
+        None
+    }
+}
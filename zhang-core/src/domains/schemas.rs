@@ -1,11 +1,15 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 
 use bigdecimal::BigDecimal;
-use chrono::{NaiveDate, NaiveDateTime};
-use serde::Serialize;
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveDateTime};
+use chrono_tz::Tz;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
 use strum::{AsRefStr, EnumString};
-use zhang_ast::error::ErrorKind;
+use uuid::Uuid;
+use zhang_ast::amount::Amount;
+use zhang_ast::error::{ErrorKind, ErrorSeverity};
 use zhang_ast::{Currency, Rounding, SpanInfo};
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, AsRefStr, EnumString)]
@@ -16,12 +20,13 @@ pub enum MetaType {
 }
 
 #[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct OptionDomain {
     pub key: String,
     pub value: String,
 }
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct AccountDomain {
     pub date: NaiveDateTime,
     pub r#type: String,
@@ -30,7 +35,8 @@ pub struct AccountDomain {
     pub alias: Option<String>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Copy, Serialize, AsRefStr, EnumString)]
+#[derive(Clone, Debug, PartialEq, Eq, Copy, Serialize, Deserialize, AsRefStr, EnumString)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum AccountStatus {
     Open,
     Close,
@@ -44,6 +50,8 @@ pub struct AccountBalanceDomain {
     // todo: combine number and commodity
     pub balance_number: BigDecimal,
     pub balance_commodity: String,
+    /// the balance valued in the currency of conversion, present if conversion is requested and the exchange rate is resolved
+    pub converted: Option<Amount>,
 }
 
 #[derive(Debug, Clone)]
@@ -54,7 +62,7 @@ pub struct AccountDailyBalanceDomain {
     pub balance_commodity: String,
 }
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct PriceDomain {
     pub datetime: NaiveDateTime,
     pub commodity: Currency,
@@ -62,7 +70,7 @@ pub struct PriceDomain {
     pub target_commodity: Currency,
 }
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct MetaDomain {
     pub meta_type: String,
     pub type_identifier: String,
@@ -70,13 +78,17 @@ pub struct MetaDomain {
     pub value: String,
 }
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct CommodityDomain {
     pub name: String,
     pub precision: i32,
     pub prefix: Option<String>,
     pub suffix: Option<String>,
     pub rounding: Rounding,
+    /// the separator between groups of thousands, the integer part is not grouped if absent
+    pub grouping_separator: Option<String>,
+    /// the symbol between integer part and decimal part, `.` if absent
+    pub decimal_symbol: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -88,6 +100,7 @@ pub struct TransactionInfoDomain {
 }
 
 #[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct AccountJournalDomain {
     pub datetime: NaiveDateTime,
     pub timestamp: i64,
@@ -95,16 +108,326 @@ pub struct AccountJournalDomain {
     pub trx_id: String,
     pub payee: Option<String>,
     pub narration: Option<String>,
+    #[cfg_attr(feature = "openapi", schema(value_type = String, format = "decimal"))]
     pub inferred_unit_number: BigDecimal,
     pub inferred_unit_commodity: String,
+    #[cfg_attr(feature = "openapi", schema(value_type = String, format = "decimal"))]
     pub account_after_number: BigDecimal,
     pub account_after_commodity: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct ErrorDomain {
     pub id: String,
     pub span: Option<SpanInfo>,
     pub error_type: ErrorKind,
+    pub severity: ErrorSeverity,
     pub metas: HashMap<String, String>,
 }
+
+/// realized gain or loss of disposing a lot acquired at cost
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RealizedGainDomain {
+    #[serde(with = "crate::utils::zoned_datetime")]
+    pub datetime: DateTime<Tz>,
+    pub trx_id: Uuid,
+    pub account: String,
+    pub commodity: Currency,
+    pub amount: BigDecimal,
+    pub cost: Amount,
+    pub price: Amount,
+    pub gain: Amount,
+}
+
+/// the accounts eliminated from consolidated reports since the date
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EliminationDomain {
+    pub date: NaiveDate,
+    pub accounts: Vec<String>,
+}
+
+impl EliminationDomain {
+    /// whether the account is one of the eliminated accounts or their sub accounts
+    pub fn eliminates(&self, account: &str) -> bool {
+        self.accounts
+            .iter()
+            .any(|eliminated| account.eq(eliminated) || account.strip_prefix(eliminated.as_str()).is_some_and(|rest| rest.starts_with(':')))
+    }
+}
+
+/// the tax split from the gross amount of posting by `tax-rate` meta
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaxDomain {
+    #[serde(with = "crate::utils::zoned_datetime")]
+    pub datetime: DateTime<Tz>,
+    pub trx_id: Uuid,
+    /// the account of the taxed posting
+    pub account: String,
+    pub tax_account: String,
+    pub tax: Amount,
+}
+
+/// the tax of one tax account in one commodity within one period, keyed by the first date of period.
+/// the tax split from income postings is collected, and the tax split from other postings is paid
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TaxReportDomain {
+    pub period: NaiveDate,
+    pub tax_account: String,
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub commodity: Currency,
+    #[cfg_attr(feature = "openapi", schema(value_type = String, format = "decimal"))]
+    pub collected: BigDecimal,
+    #[cfg_attr(feature = "openapi", schema(value_type = String, format = "decimal"))]
+    pub paid: BigDecimal,
+    /// collected minus paid, which is the tax to be filed
+    #[cfg_attr(feature = "openapi", schema(value_type = String, format = "decimal"))]
+    pub payable: BigDecimal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InvoiceKind {
+    Receivable,
+    Payable,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceItemDomain {
+    pub description: String,
+    pub amount: Amount,
+}
+
+/// the invoice declared by `invoice` custom directive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceDomain {
+    pub id: String,
+    pub date: NaiveDate,
+    pub counterparty: String,
+    pub account: String,
+    pub kind: InvoiceKind,
+    pub due: NaiveDate,
+    pub items: Vec<InvoiceItemDomain>,
+    pub total: Amount,
+}
+
+/// the aging bucket of invoice by the days past its due date
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgingBucket {
+    Current,
+    #[serde(rename = "days_1_30")]
+    Days1To30,
+    #[serde(rename = "days_31_60")]
+    Days31To60,
+    #[serde(rename = "days_61_90")]
+    Days61To90,
+    #[serde(rename = "over_90_days")]
+    Over90Days,
+}
+
+impl AgingBucket {
+    pub fn of(days_overdue: i64) -> Self {
+        match days_overdue {
+            i64::MIN..=0 => AgingBucket::Current,
+            1..=30 => AgingBucket::Days1To30,
+            31..=60 => AgingBucket::Days31To60,
+            61..=90 => AgingBucket::Days61To90,
+            _ => AgingBucket::Over90Days,
+        }
+    }
+}
+
+/// the invoice which is not fully paid yet
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenInvoiceDomain {
+    #[serde(flatten)]
+    pub invoice: InvoiceDomain,
+    pub paid: Amount,
+    pub outstanding: Amount,
+    pub days_overdue: i64,
+    pub aging: AgingBucket,
+}
+
+/// one version of directive in the git history of its file
+#[derive(Debug, Clone, Serialize)]
+pub struct DirectiveVersionDomain {
+    pub commit: String,
+    pub author: String,
+    pub datetime: DateTime<Tz>,
+    /// the subject of commit message
+    pub message: String,
+    pub content: String,
+}
+
+/// one monthly payment in the amortization schedule of loan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoanPaymentDomain {
+    #[serde(with = "crate::utils::zoned_datetime")]
+    pub datetime: DateTime<Tz>,
+    pub trx_id: Uuid,
+    pub interest: Amount,
+    pub principal: Amount,
+    /// the principal remaining after the payment
+    pub remaining_principal: Amount,
+}
+
+/// the holding of one commodity in account, the total values are present only if all lots are valued in the same commodity
+#[derive(Debug, Clone, Serialize)]
+pub struct HoldingDomain {
+    pub account: String,
+    pub commodity: Currency,
+    pub amount: BigDecimal,
+    pub cost_basis: Option<Amount>,
+    pub market_value: Option<Amount>,
+    pub unrealized_gain: Option<Amount>,
+    pub lots: Vec<HoldingLotDomain>,
+}
+
+/// the lot held by account, valued at the latest price in the cost commodity, or the operating currency if it is not held at cost
+#[derive(Debug, Clone, Serialize)]
+pub struct HoldingLotDomain {
+    pub datetime: Option<DateTime<Tz>>,
+    pub label: Option<String>,
+    pub amount: BigDecimal,
+    pub cost: Option<Amount>,
+    pub cost_basis: Option<Amount>,
+    pub price: Option<Amount>,
+    pub market_value: Option<Amount>,
+    pub unrealized_gain: Option<Amount>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, AsRefStr, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum BudgetReportInterval {
+    Month,
+    Quarter,
+}
+
+/// the bucket size of balance time series
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, AsRefStr, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum BalanceInterval {
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl BalanceInterval {
+    /// the first date of the period containing the date, week starts on monday
+    pub fn period_start(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            BalanceInterval::Day => date,
+            BalanceInterval::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            BalanceInterval::Month => date.with_day(1).expect("first day of month should be valid"),
+            BalanceInterval::Quarter => NaiveDate::from_ymd_opt(date.year(), (date.month0() / 3) * 3 + 1, 1).expect("first day of quarter should be valid"),
+            BalanceInterval::Year => NaiveDate::from_ymd_opt(date.year(), 1, 1).expect("first day of year should be valid"),
+        }
+    }
+
+    /// the first date of the next period, given the first date of a period
+    pub fn next_period_start(&self, period_start: NaiveDate) -> NaiveDate {
+        match self {
+            BalanceInterval::Day => period_start + Duration::days(1),
+            BalanceInterval::Week => period_start + Duration::weeks(1),
+            BalanceInterval::Month => period_start + Months::new(1),
+            BalanceInterval::Quarter => period_start + Months::new(3),
+            BalanceInterval::Year => period_start + Months::new(12),
+        }
+    }
+}
+
+/// balances of account in one commodity at the end of each period, keyed by the first date of period
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountBalanceSeriesDomain {
+    pub account: String,
+    pub commodity: Currency,
+    pub balances: BTreeMap<NaiveDate, BigDecimal>,
+}
+
+/// net worth, which is assets minus liabilities, at the end of one period
+#[derive(Debug, Clone, Serialize)]
+pub struct NetWorthDomain {
+    /// the first date of period
+    pub date: NaiveDate,
+    /// holdings valued by the latest prices at the end of period
+    pub market_value: Amount,
+    /// holdings valued by their cost, the cost in other commodities is converted by the latest prices at the end of period
+    pub cost_value: Amount,
+}
+
+/// the net worth summed up across ledgers, each ledger is valued by its own prices
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ConsolidatedNetWorthDomain {
+    pub date: NaiveDateTime,
+    pub total: Amount,
+    /// the net worth of each ledger by its name
+    pub ledgers: IndexMap<String, Amount>,
+}
+
+/// the payee with the statistics of its transactions
+#[derive(Debug, Clone, Serialize)]
+pub struct PayeeDomain {
+    pub name: String,
+    pub transaction_count: usize,
+    pub first_seen: NaiveDate,
+    pub last_seen: NaiveDate,
+    /// the most frequently posted accounts in the transactions of payee
+    pub typical_accounts: Vec<String>,
+}
+
+/// actual vs. budgeted of single budget in one period
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BudgetReportDomain {
+    pub name: String,
+    pub alias: Option<String>,
+    pub category: Option<String>,
+    /// `2023-11` for month, `2023-Q4` for quarter
+    pub period: String,
+    pub budgeted_amount: Amount,
+    pub actual_amount: Amount,
+    /// budgeted minus actual, negative means overspending
+    pub variance: Amount,
+    /// percent of budgeted amount consumed by actual spend, `None` if nothing is budgeted
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<String>, format = "decimal"))]
+    pub percent_consumed: Option<BigDecimal>,
+}
+
+/// debit and credit balance of single account in one commodity, the balance is measured by posting weights(the cost basis),
+/// so that the totals of each commodity net to zero for a balanced ledger
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TrialBalanceAccountDomain {
+    pub account: String,
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub commodity: Currency,
+    #[cfg_attr(feature = "openapi", schema(value_type = String, format = "decimal"))]
+    pub debit: BigDecimal,
+    #[cfg_attr(feature = "openapi", schema(value_type = String, format = "decimal"))]
+    pub credit: BigDecimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TrialBalanceTotalDomain {
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub commodity: Currency,
+    #[cfg_attr(feature = "openapi", schema(value_type = String, format = "decimal"))]
+    pub debit: BigDecimal,
+    #[cfg_attr(feature = "openapi", schema(value_type = String, format = "decimal"))]
+    pub credit: BigDecimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TrialBalanceDomain {
+    pub accounts: Vec<TrialBalanceAccountDomain>,
+    pub totals: Vec<TrialBalanceTotalDomain>,
+    /// whether the debit total equals the credit total in every commodity
+    pub balanced: bool,
+}
@@ -0,0 +1,348 @@
+//! the ledger files encrypted by age or GPG, e.g. `secret.zhang.age`, which are decrypted on loading and encrypted again on
+//! write-back, so that the ledger can be kept in synced folders without exposing the plain content.
+//!
+//! the `age` and `gpg` commands are used, the passphrase of file or key is prompted by them if it is needed. the keys are
+//! declared by options in a plain file, usually the main file, which includes the encrypted ones:
+//! ```zhang
+//! option "encryption_identity" "~/.config/zhang/identity.txt"
+//! option "encryption_recipient" "age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p"
+//! include "secret.zhang.age"
+//! ```
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use zhang_ast::{Directive, Spanned};
+
+use crate::constants::{KEY_ENCRYPTION_GPG_HOME, KEY_ENCRYPTION_IDENTITY, KEY_ENCRYPTION_RECIPIENT};
+use crate::{ZhangError, ZhangResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encryption {
+    Age,
+    /// the binary one of `.gpg` or the armored one of `.asc`
+    Gpg {
+        armor: bool,
+    },
+}
+
+impl Encryption {
+    /// the encryption of file by its extension, the file is plain if it is none of `.age`, `.gpg` and `.asc`
+    pub fn of(path: &Path) -> Option<Encryption> {
+        match path.extension()?.to_str()? {
+            "age" => Some(Encryption::Age),
+            "gpg" => Some(Encryption::Gpg { armor: false }),
+            "asc" => Some(Encryption::Gpg { armor: true }),
+            _ => None,
+        }
+    }
+
+    /// the path without the extension of encryption, e.g. `main.zhang` of `main.zhang.age`, which tells the data type of file
+    pub fn plain_path(path: &Path) -> PathBuf {
+        match Encryption::of(path) {
+            Some(_) => path.with_extension(""),
+            None => path.to_path_buf(),
+        }
+    }
+}
+
+/// the keys declared by options while loading
+#[derive(Debug, Clone, Default)]
+pub struct EncryptionKeys {
+    /// the age identity file, the passphrase is prompted by age if it is absent
+    pub identity: Option<PathBuf>,
+    /// the age recipients or GPG key ids, the GPG files are encrypted to the default key if they are absent
+    pub recipients: Vec<String>,
+    /// the home directory of GPG, the default one is used if it is absent
+    pub gpg_home: Option<PathBuf>,
+}
+
+impl EncryptionKeys {
+    /// record the key if directive is an encryption option, so that it must be declared before including the encrypted files
+    pub fn handle_option(&mut self, directive: &Spanned<Directive>) {
+        let Directive::Option(option) = &directive.data else {
+            return;
+        };
+        let value = option.value.as_str();
+        let resolve = |path: &str| {
+            let file = expand_home(path);
+            match directive.span.filename.as_ref().and_then(|filename| filename.parent()) {
+                Some(folder) if file.is_relative() => folder.join(file),
+                _ => file,
+            }
+        };
+        match option.key.as_str() {
+            KEY_ENCRYPTION_IDENTITY => self.identity = Some(resolve(value)),
+            KEY_ENCRYPTION_GPG_HOME => self.gpg_home = Some(resolve(value)),
+            KEY_ENCRYPTION_RECIPIENT if !self.recipients.iter().any(|it| it == value) => self.recipients.push(value.to_owned()),
+            _ => {}
+        }
+    }
+
+    pub fn decrypt(&self, encryption: Encryption, path: &Path) -> ZhangResult<Vec<u8>> {
+        run(self.decrypt_command(encryption, Some(path)), None)
+    }
+
+    /// decrypt the content which is not a local file, e.g. the one read from remote storage. it is piped into command,
+    /// so the passphrase is prompted from terminal by age, or by the pinentry of GPG agent
+    pub fn decrypt_content(&self, encryption: Encryption, content: &[u8]) -> ZhangResult<Vec<u8>> {
+        run(self.decrypt_command(encryption, None), Some(content))
+    }
+
+    pub fn encrypt(&self, encryption: Encryption, content: &[u8]) -> ZhangResult<Vec<u8>> {
+        run(self.encrypt_command(encryption), Some(content))
+    }
+
+    /// the file is read by command rather than piped if it is given, so that the stdin is left for prompting passphrase
+    fn decrypt_command(&self, encryption: Encryption, path: Option<&Path>) -> Command {
+        let mut command = match encryption {
+            Encryption::Age => {
+                let mut command = Command::new("age");
+                command.arg("--decrypt");
+                if let Some(identity) = &self.identity {
+                    command.arg("--identity").arg(identity);
+                }
+                command
+            }
+            Encryption::Gpg { .. } => {
+                let mut command = self.gpg_command();
+                command.args(["--quiet", "--decrypt"]);
+                command
+            }
+        };
+        command.args(path);
+        command
+    }
+
+    fn encrypt_command(&self, encryption: Encryption) -> Command {
+        match encryption {
+            Encryption::Age => {
+                let mut command = Command::new("age");
+                command.arg("--encrypt");
+                for recipient in &self.recipients {
+                    command.arg("--recipient").arg(recipient);
+                }
+                match (&self.identity, self.recipients.is_empty()) {
+                    (Some(identity), true) => command.arg("--identity").arg(identity),
+                    (None, true) => command.arg("--passphrase"),
+                    _ => &mut command,
+                };
+                command
+            }
+            Encryption::Gpg { armor } => {
+                let mut command = self.gpg_command();
+                command.args(["--quiet", "--yes", "--encrypt"]);
+                if armor {
+                    command.arg("--armor");
+                }
+                for recipient in &self.recipients {
+                    command.arg("--recipient").arg(recipient);
+                }
+                if self.recipients.is_empty() {
+                    command.arg("--default-recipient-self");
+                }
+                command
+            }
+        }
+    }
+
+    fn gpg_command(&self) -> Command {
+        let mut command = Command::new("gpg");
+        if let Some(gpg_home) = &self.gpg_home {
+            command.arg("--homedir").arg(gpg_home);
+        }
+        command
+    }
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(relative), Some(home)) => PathBuf::from(home).join(relative),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// run the command with the input piped into it, the stdout is returned if the command succeeds
+fn run(mut command: Command, input: Option<&[u8]>) -> ZhangResult<Vec<u8>> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    command
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::inherit() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = command.spawn().map_err(|e| ZhangError::CustomError(format!("cannot run {}: {}", program, e)))?;
+    // the input is written in another thread, otherwise the command may be blocked by the full stdout while reading it
+    let writer = match (input, child.stdin.take()) {
+        (Some(input), Some(mut stdin)) => {
+            let input = input.to_vec();
+            Some(std::thread::spawn(move || stdin.write_all(&input)))
+        }
+        _ => None,
+    };
+    let output = child.wait_with_output()?;
+    if let Some(writer) = writer {
+        writer.join().expect("writer thread panicked")?;
+    }
+    if !output.status.success() {
+        return Err(ZhangError::CustomError(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+    use std::sync::Arc;
+
+    use tempfile::{tempdir, TempDir};
+    use zhang_ast::{Directive, ZhangString};
+
+    use crate::data_source::{DataSource, LocalFileSystemDataSource};
+    use crate::data_type::text::ZhangDataType;
+    use crate::encryption::{Encryption, EncryptionKeys};
+    use crate::ledger::Ledger;
+
+    fn args_of(command: &Command) -> Vec<String> {
+        command.get_args().map(|it| it.to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn should_detect_encryption_by_extension() {
+        assert_eq!(Some(Encryption::Age), Encryption::of(Path::new("secret.zhang.age")));
+        assert_eq!(Some(Encryption::Gpg { armor: false }), Encryption::of(Path::new("secret.zhang.gpg")));
+        assert_eq!(Some(Encryption::Gpg { armor: true }), Encryption::of(Path::new("secret.zhang.asc")));
+        assert_eq!(None, Encryption::of(Path::new("main.zhang")));
+    }
+
+    #[test]
+    fn should_build_age_commands_from_keys() {
+        let keys = EncryptionKeys {
+            identity: Some(PathBuf::from("/keys/identity.txt")),
+            recipients: vec![],
+            gpg_home: None,
+        };
+        assert_eq!(
+            vec!["--decrypt", "--identity", "/keys/identity.txt", "secret.zhang.age"],
+            args_of(&keys.decrypt_command(Encryption::Age, Some(Path::new("secret.zhang.age"))))
+        );
+        assert_eq!(
+            vec!["--decrypt", "--identity", "/keys/identity.txt"],
+            args_of(&keys.decrypt_command(Encryption::Age, None))
+        );
+        assert_eq!(
+            vec!["--encrypt", "--identity", "/keys/identity.txt"],
+            args_of(&keys.encrypt_command(Encryption::Age))
+        );
+
+        let keys = EncryptionKeys {
+            identity: Some(PathBuf::from("/keys/identity.txt")),
+            recipients: vec!["age1a".to_owned(), "age1b".to_owned()],
+            gpg_home: None,
+        };
+        assert_eq!(
+            vec!["--encrypt", "--recipient", "age1a", "--recipient", "age1b"],
+            args_of(&keys.encrypt_command(Encryption::Age))
+        );
+        assert_eq!(
+            vec!["--encrypt", "--passphrase"],
+            args_of(&EncryptionKeys::default().encrypt_command(Encryption::Age))
+        );
+    }
+
+    /// the GPG home holding a key of `zhang@localhost`, `None` if gpg is not installed so that the test is skipped
+    fn gpg_home_with_key() -> Option<TempDir> {
+        if Command::new("gpg").arg("--version").output().is_err() {
+            return None;
+        }
+        let gpg_home = tempdir().unwrap();
+        let status = Command::new("gpg")
+            .arg("--homedir")
+            .arg(gpg_home.path())
+            .args([
+                "--batch",
+                "--quiet",
+                "--passphrase",
+                "",
+                "--quick-gen-key",
+                "zhang <zhang@localhost>",
+                "default",
+                "default",
+                "never",
+            ])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        Some(gpg_home)
+    }
+
+    #[test]
+    fn should_build_gpg_commands_with_home() {
+        let keys = EncryptionKeys {
+            identity: None,
+            recipients: vec![],
+            gpg_home: Some(PathBuf::from("/keys/gnupg")),
+        };
+        assert_eq!(
+            vec!["--homedir", "/keys/gnupg", "--quiet", "--decrypt"],
+            args_of(&keys.decrypt_command(Encryption::Gpg { armor: false }, None))
+        );
+        assert_eq!(
+            vec![
+                "--homedir",
+                "/keys/gnupg",
+                "--quiet",
+                "--yes",
+                "--encrypt",
+                "--armor",
+                "--default-recipient-self"
+            ],
+            args_of(&keys.encrypt_command(Encryption::Gpg { armor: true }))
+        );
+    }
+
+    #[test]
+    fn should_load_and_write_back_gpg_encrypted_file() {
+        let Some(gpg_home) = gpg_home_with_key() else {
+            return;
+        };
+
+        let temp_dir = tempdir().unwrap().into_path();
+        std::fs::write(
+            temp_dir.join("main.zhang"),
+            format!(
+                "option \"encryption_gpg_home\" \"{}\"\noption \"encryption_recipient\" \"zhang@localhost\"\ninclude \"secret.zhang.gpg\"\n",
+                gpg_home.path().display()
+            ),
+        )
+        .unwrap();
+        let keys = EncryptionKeys {
+            identity: None,
+            recipients: vec!["zhang@localhost".to_owned()],
+            gpg_home: Some(gpg_home.path().to_path_buf()),
+        };
+        let encrypted = keys.encrypt(Encryption::Gpg { armor: false }, b"option \"title\" \"secret ledger\"\n").unwrap();
+        std::fs::write(temp_dir.join("secret.zhang.gpg"), encrypted).unwrap();
+
+        let source = Arc::new(LocalFileSystemDataSource::new(ZhangDataType {}));
+        let ledger = Ledger::load_with_data_source(temp_dir.clone(), "main.zhang".to_owned(), source.clone()).unwrap();
+        let result = source.load(temp_dir.to_string_lossy().to_string(), "main.zhang".to_owned()).unwrap();
+        let title = result
+            .directives()
+            .find(|it| matches!(&it.data, Directive::Option(option) if option.key.as_str() == "title"))
+            .unwrap();
+        let Directive::Option(mut option) = title.data.clone() else { unreachable!() };
+        assert_eq!("secret ledger", option.value.as_str());
+
+        option.value = ZhangString::QuoteString("new title".to_owned());
+        source.update_directive(&ledger, &title.span, Directive::Option(option)).unwrap();
+        let written = std::fs::read(temp_dir.join("secret.zhang.gpg")).unwrap();
+        assert!(!String::from_utf8_lossy(&written).contains("new title"));
+        let decrypted = keys.decrypt(Encryption::Gpg { armor: false }, &temp_dir.join("secret.zhang.gpg")).unwrap();
+        assert_eq!("option \"title\" \"new title\"\n", String::from_utf8(decrypted).unwrap());
+    }
+}
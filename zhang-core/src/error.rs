@@ -36,6 +36,25 @@ pub enum ZhangError {
     #[error("file not found")]
     FileNotFound,
 
+    #[error("query error: {0}")]
+    QueryError(String),
+
+    #[error("unknown export column: {0}")]
+    UnknownExportColumn(String),
+
+    #[cfg(feature = "export_sqlite")]
+    #[error("sqlite error: {0}")]
+    SqliteError(#[from] rusqlite::Error),
+
+    #[error("import error: {0}")]
+    ImportError(String),
+
+    #[error("reconcile error: {0}")]
+    ReconcileError(String),
+
+    #[error("directive is modified since last loading: {0}")]
+    DirectiveModified(PathBuf),
+
     #[error("custom error: {0}")]
     CustomError(String),
 }
@@ -0,0 +1,284 @@
+//! the anonymized ledger is a shareable reproduction of ledger for bug reports, in which the private text and amounts
+//! are scrubbed while the structure is kept, e.g. accounts, commodities, dates and the balances of transactions.
+//!
+//! - payees, narrations, notes, the values of custom directives and the quoted meta values of all directives are replaced
+//!   by numbered pseudonyms, the same text is always replaced by the same pseudonym. the quoted meta values are replaced
+//!   whatever their keys are, so the options written in quoted meta, e.g. `alias` of account, are scrubbed too
+//! - tags and links are replaced by numbered pseudonyms, so that the tagged or linked directives are still grouped together
+//! - amounts are scaled by one random factor, so that transactions are still balanced and balance assertions still pass.
+//!   the prices and per-unit costs are kept since they are the ratios between commodities
+//! - comments and the comments of postings are dropped, includes are dropped since all directives are exported in one file
+
+use std::collections::HashMap;
+
+use bigdecimal::num_bigint::BigInt;
+use bigdecimal::BigDecimal;
+use itertools::Itertools;
+use zhang_ast::amount::Amount;
+use zhang_ast::{Directive, Meta, SingleTotalPrice, Spanned, StringOrAccount, ZhangString};
+
+use crate::data_type::text::ZhangDataType;
+use crate::data_type::DataType;
+use crate::ledger::Ledger;
+
+/// the title of anonymized ledger
+const ANONYMIZED_TITLE: &str = "Anonymized Ledger";
+
+struct Anonymizer {
+    factor: BigDecimal,
+    pseudonyms: HashMap<(&'static str, String), String>,
+    counters: HashMap<&'static str, usize>,
+}
+
+impl Anonymizer {
+    /// the factor is between 0.50 and 2.00, which is decided by the seed
+    fn new(seed: u64) -> Self {
+        Anonymizer {
+            factor: BigDecimal::new(BigInt::from(50 + seed % 151), 2),
+            pseudonyms: HashMap::new(),
+            counters: HashMap::new(),
+        }
+    }
+
+    fn pseudonym(&mut self, kind: &'static str, text: &str) -> String {
+        let counters = &mut self.counters;
+        self.pseudonyms
+            .entry((kind, text.to_owned()))
+            .or_insert_with(|| {
+                let counter = counters.entry(kind).or_default();
+                *counter += 1;
+                format!("{} {}", kind, counter)
+            })
+            .clone()
+    }
+
+    fn string(&mut self, kind: &'static str, text: &ZhangString) -> ZhangString {
+        ZhangString::QuoteString(self.pseudonym(kind, text.as_str()))
+    }
+
+    /// the pseudonym without space, which is used as tag, link or file name
+    fn name(&mut self, kind: &'static str, text: &str) -> String {
+        self.pseudonym(kind, text).replace(' ', "-")
+    }
+
+    /// the unordered tags or links should be given in sorted order, so that the pseudonyms are numbered in the same way on every export
+    fn names<T: FromIterator<String>>(&mut self, kind: &'static str, names: impl IntoIterator<Item = String>) -> T {
+        names.into_iter().map(|name| self.name(kind, &name)).collect()
+    }
+
+    /// the scaled number keeps at least the precision of the original one
+    fn scale(&self, amount: Amount) -> Amount {
+        let precision = amount.number.as_bigint_and_exponent().1.max(0);
+        let scaled = (amount.number * &self.factor).normalized();
+        let scaled_precision = scaled.as_bigint_and_exponent().1.max(precision);
+        Amount::new(scaled.with_scale(scaled_precision), amount.currency)
+    }
+
+    /// the values are visited in the order of keys, so that the pseudonyms are numbered in the same way on every export
+    fn meta(&mut self, meta: Meta) -> Meta {
+        let mut ret = Meta::default();
+        for (key, value) in meta.get_flatten().into_iter().sorted_by(|a, b| a.0.cmp(&b.0)) {
+            let value = match value {
+                ZhangString::QuoteString(text) => ZhangString::QuoteString(self.pseudonym("Value", &text)),
+                unquoted => unquoted,
+            };
+            ret.insert(key, value);
+        }
+        ret
+    }
+
+    fn anonymize(&mut self, directive: Directive) -> Option<Directive> {
+        let mut directive = match directive {
+            Directive::Comment(_) | Directive::Include(_) => return None,
+            Directive::Option(mut option) if option.key.as_str() == "title" => {
+                option.value = ZhangString::QuoteString(ANONYMIZED_TITLE.to_owned());
+                Directive::Option(option)
+            }
+            Directive::Transaction(mut trx) => {
+                trx.payee = trx.payee.map(|payee| self.string("Payee", &payee));
+                trx.narration = trx.narration.map(|narration| self.string("Narration", &narration));
+                trx.tags = self.names("tag", trx.tags);
+                trx.links = self.names("link", trx.links);
+                for posting in trx.postings.iter_mut() {
+                    posting.units = posting.units.take().map(|units| self.scale(units));
+                    posting.price = posting.price.take().map(|price| match price {
+                        SingleTotalPrice::Total(total) => SingleTotalPrice::Total(self.scale(total)),
+                        single => single,
+                    });
+                    posting.comment = None;
+                    posting.meta = self.meta(std::mem::take(&mut posting.meta));
+                }
+                Directive::Transaction(trx)
+            }
+            Directive::Template(mut template) => {
+                template.payee = template.payee.map(|payee| self.string("Payee", &payee));
+                template.narration = template.narration.map(|narration| self.string("Narration", &narration));
+                template.tags = self.names("tag", template.tags);
+                template.links = self.names("link", template.links);
+                Directive::Template(template)
+            }
+            Directive::BalanceCheck(mut check) => {
+                check.amount = self.scale(check.amount);
+                Directive::BalanceCheck(check)
+            }
+            Directive::BalancePad(mut pad) => {
+                pad.amount = self.scale(pad.amount);
+                Directive::BalancePad(pad)
+            }
+            Directive::OpenBalance(mut open_balance) => {
+                open_balance.amount = self.scale(open_balance.amount);
+                Directive::OpenBalance(open_balance)
+            }
+            Directive::Depreciation(mut depreciation) => {
+                depreciation.amount = self.scale(depreciation.amount);
+                Directive::Depreciation(depreciation)
+            }
+            Directive::Loan(mut loan) => {
+                loan.principal = self.scale(loan.principal);
+                Directive::Loan(loan)
+            }
+            Directive::Accrue(mut accrue) => {
+                accrue.amount = self.scale(accrue.amount);
+                Directive::Accrue(accrue)
+            }
+            Directive::BudgetAdd(mut budget_add) => {
+                budget_add.amount = self.scale(budget_add.amount);
+                Directive::BudgetAdd(budget_add)
+            }
+            Directive::BudgetTransfer(mut budget_transfer) => {
+                budget_transfer.amount = self.scale(budget_transfer.amount);
+                Directive::BudgetTransfer(budget_transfer)
+            }
+            Directive::Note(mut note) => {
+                note.comment = self.string("Note", &note.comment);
+                note.tags = note.tags.map(|tags| self.names("tag", tags.into_iter().sorted()));
+                note.links = note.links.map(|links| self.names("link", links.into_iter().sorted()));
+                Directive::Note(note)
+            }
+            Directive::Event(mut event) => {
+                event.description = self.string("Event", &event.description);
+                Directive::Event(event)
+            }
+            Directive::Document(mut document) => {
+                let filename = document.filename.as_str();
+                let extension = filename.rsplit_once('.').map(|(_, extension)| extension.to_owned());
+                let pseudonym = self.name("document", filename);
+                document.filename = ZhangString::QuoteString(match extension {
+                    Some(extension) => format!("{}.{}", pseudonym, extension),
+                    None => pseudonym,
+                });
+                document.tags = document.tags.map(|tags| self.names("tag", tags.into_iter().sorted()));
+                document.links = document.links.map(|links| self.names("link", links.into_iter().sorted()));
+                Directive::Document(document)
+            }
+            Directive::Custom(mut custom) => {
+                custom.values = custom
+                    .values
+                    .into_iter()
+                    .map(|value| match value {
+                        StringOrAccount::String(text) => StringOrAccount::String(self.string("Custom", &text)),
+                        account => account,
+                    })
+                    .collect();
+                Directive::Custom(custom)
+            }
+            directive => directive,
+        };
+        if let Some(meta) = directive.get_meta_mut() {
+            *meta = self.meta(std::mem::take(meta));
+        }
+        Some(directive)
+    }
+}
+
+/// export the whole ledger as one zhang file with the private data scrubbed, the same seed gives the same scale factor
+pub fn export_anonymized(ledger: &Ledger, seed: u64) -> String {
+    let mut anonymizer = Anonymizer::new(seed);
+    let data_type = ZhangDataType {};
+    let mut content = ledger
+        .metas
+        .iter()
+        .chain(ledger.directives.iter())
+        .filter_map(|it| anonymizer.anonymize(it.data.clone()).map(|directive| Spanned::new(directive, it.span.clone())))
+        .map(|it| data_type.export(it))
+        .join("\n\n");
+    content.push('\n');
+    content
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use crate::export::export_anonymized;
+    use crate::test::load_from_text;
+
+    #[test]
+    fn should_scrub_payees_narrations_and_scale_amounts() {
+        let ledger = load_from_text(indoc! {r#"
+            option "title" "Alice's Finances"
+            1970-01-01 open Assets:Bank
+            1970-01-01 open Expenses:Food
+            ; the salary is paid on friday
+            2024-01-01 "Starbucks" "latte with Bob"
+              receipt: "photo of receipt"
+              Assets:Bank -20 CNY
+              Expenses:Food
+            2024-01-02 "Starbucks" "dinner"
+              Assets:Bank -30.50 CNY ; cash back
+              Expenses:Food 30.50 CNY
+            2024-01-03 balance Assets:Bank -50.50 CNY
+        "#});
+
+        let content = export_anonymized(&ledger, 87);
+        for private in ["Alice", "Starbucks", "latte", "Bob", "photo", "salary", "cash back"] {
+            assert!(!content.contains(private), "{} is not scrubbed: {}", private, content);
+        }
+        assert!(content.contains(r#"option "title" "Anonymized Ledger""#));
+        assert_eq!(2, content.matches(r#""Payee 1""#).count());
+        assert!(content.contains(r#""Payee 1" "Narration 1""#));
+        assert!(content.contains(r#""Payee 1" "Narration 2""#));
+        assert!(content.contains(r#"receipt: "Value 1""#));
+        // the factor of seed 87 is 1.37
+        assert!(content.contains("Assets:Bank -27.4 CNY"), "{}", content);
+        assert!(content.contains("Assets:Bank -41.785 CNY"));
+        assert!(content.contains("2024-01-03 balance Assets:Bank -69.185 CNY"));
+
+        let anonymized = load_from_text(&content);
+        assert!(anonymized.operations().errors().unwrap().is_empty());
+        assert_eq!(content, export_anonymized(&ledger, 87));
+    }
+
+    #[test]
+    fn should_scrub_metas_of_all_directives_tags_links_and_custom_values() {
+        let ledger = load_from_text(indoc! {r#"
+            1970-01-01 commodity CNY
+              issuer: "Alice Bank"
+            1970-01-01 open Assets:Bank
+              alias: "Alice's salary card"
+            1970-01-01 open Expenses:Food
+            1970-01-01 open Assets:Cash
+            2024-01-01 "Starbucks" "latte" #trip-tokyo ^invoice-bob
+              Assets:Bank -20 CNY
+              Expenses:Food
+            2024-01-02 "Starbucks" "dinner" #trip-tokyo
+              Assets:Bank -30 CNY
+              Expenses:Food
+            2024-01-03 custom "contact" "Bob" "bob@example.com" Assets:Bank
+            2024-12-31 close Assets:Cash
+              reason: "moved to Alice's new card"
+        "#});
+
+        let content = export_anonymized(&ledger, 87);
+        for private in ["Alice", "Bob", "bob@", "trip-tokyo", "invoice-bob"] {
+            assert!(!content.contains(private), "{} is not scrubbed: {}", private, content);
+        }
+        assert_eq!(2, content.matches("#tag-1").count(), "{}", content);
+        assert!(content.contains("^link-1"));
+        assert!(content.contains(r#"custom "contact" "Custom 1" "Custom 2" Assets:Bank"#), "{}", content);
+
+        let anonymized = load_from_text(&content);
+        assert!(anonymized.operations().errors().unwrap().is_empty());
+        assert_eq!(content, export_anonymized(&ledger, 87));
+    }
+}
@@ -0,0 +1,12 @@
+//! export dumps the processed ledger into formats which can be consumed by other tools, unlike the data types,
+//! the exported data is the result of processing, e.g. postings with inferred amounts and the errors of ledger.
+
+mod anonymize;
+#[cfg(feature = "export_sqlite")]
+pub mod sqlite;
+mod tabular;
+mod text;
+
+pub use anonymize::export_anonymized;
+pub use tabular::{export_report, export_table, ExportFormat, ExportOptions, ExportReport, Table};
+pub use text::export_text;
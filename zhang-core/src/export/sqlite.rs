@@ -0,0 +1,259 @@
+//! export the processed store into a SQLite database, so that the ledger can be queried by SQL or BI tools.
+//! numbers are stored in `NUMERIC` columns, and datetimes are stored as RFC 3339 text in the timezone of ledger.
+//! the schema of database is defined in [SCHEMA].
+
+use std::path::Path;
+
+use itertools::Itertools;
+use rusqlite::{params, Connection};
+
+use crate::domains::Operations;
+use crate::ZhangResult;
+
+/// the schema of exported database
+pub const SCHEMA: &str = r#"
+CREATE TABLE accounts
+(
+    name   TEXT PRIMARY KEY,
+    type   TEXT NOT NULL, -- account type, e.g. Assets
+    status TEXT NOT NULL, -- Open or Close
+    alias  TEXT,
+    date   TEXT NOT NULL  -- the open date
+);
+
+CREATE TABLE commodities
+(
+    name      TEXT PRIMARY KEY,
+    precision INTEGER NOT NULL,
+    prefix    TEXT,
+    suffix    TEXT
+);
+
+CREATE TABLE transactions
+(
+    id          TEXT PRIMARY KEY,
+    sequence    INTEGER NOT NULL, -- the processing order of transaction
+    datetime    TEXT    NOT NULL,
+    flag        TEXT    NOT NULL,
+    payee       TEXT,
+    narration   TEXT,
+    tags        TEXT    NOT NULL, -- comma separated tags
+    links       TEXT    NOT NULL, -- comma separated links
+    source_file TEXT
+);
+
+CREATE TABLE postings
+(
+    id                TEXT PRIMARY KEY,
+    trx_id            TEXT    NOT NULL REFERENCES transactions (id),
+    datetime          TEXT    NOT NULL,
+    account           TEXT    NOT NULL REFERENCES accounts (name),
+    unit_number       NUMERIC,          -- the units written in ledger, null if the posting is inferred
+    unit_commodity    TEXT,
+    cost_number       NUMERIC,
+    cost_commodity    TEXT,
+    weight_number     NUMERIC NOT NULL, -- the weight of posting which balances the transaction
+    weight_commodity  TEXT    NOT NULL,
+    balance_number    NUMERIC NOT NULL, -- the balance of account in the weight commodity after the posting
+    balance_commodity TEXT    NOT NULL
+);
+
+CREATE TABLE prices
+(
+    datetime         TEXT    NOT NULL,
+    commodity        TEXT    NOT NULL,
+    amount           NUMERIC NOT NULL,
+    target_commodity TEXT    NOT NULL
+);
+
+CREATE TABLE budgets
+(
+    name      TEXT PRIMARY KEY,
+    alias     TEXT,
+    category  TEXT,
+    commodity TEXT    NOT NULL,
+    closed    INTEGER NOT NULL
+);
+
+CREATE TABLE budget_intervals
+(
+    budget   TEXT    NOT NULL REFERENCES budgets (name),
+    interval INTEGER NOT NULL, -- year and month, e.g. 202312
+    assigned NUMERIC NOT NULL,
+    activity NUMERIC NOT NULL
+);
+
+CREATE TABLE metas
+(
+    meta_type       TEXT NOT NULL, -- e.g. AccountMeta
+    type_identifier TEXT NOT NULL, -- e.g. the name of account
+    key             TEXT NOT NULL,
+    value           TEXT NOT NULL
+);
+
+CREATE TABLE errors
+(
+    id          TEXT PRIMARY KEY,
+    error_type  TEXT NOT NULL,
+    source_file TEXT,
+    span_start  INTEGER,
+    span_end    INTEGER,
+    metas       TEXT NOT NULL -- json object of error metas
+);
+"#;
+
+/// export the store into a new SQLite database at `path`, the existing file is replaced
+pub fn export_sqlite(operations: &Operations, path: &Path) -> ZhangResult<()> {
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    let mut connection = Connection::open(path)?;
+    let db = connection.transaction()?;
+    db.execute_batch(SCHEMA)?;
+
+    let store = operations.read();
+    for account in store.accounts.values() {
+        db.execute(
+            "INSERT INTO accounts (name, type, status, alias, date) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![account.name, account.r#type, account.status.as_ref(), account.alias, account.date.to_string()],
+        )?;
+    }
+    for commodity in store.commodities.values() {
+        db.execute(
+            "INSERT INTO commodities (name, precision, prefix, suffix) VALUES (?1, ?2, ?3, ?4)",
+            params![commodity.name, commodity.precision, commodity.prefix, commodity.suffix],
+        )?;
+    }
+    for trx in store.transactions.values().sorted_by_key(|trx| trx.sequence) {
+        db.execute(
+            "INSERT INTO transactions (id, sequence, datetime, flag, payee, narration, tags, links, source_file) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                trx.id.to_string(),
+                trx.sequence,
+                trx.datetime.to_rfc3339(),
+                trx.flag.to_string(),
+                trx.payee,
+                trx.narration,
+                trx.tags.join(","),
+                trx.links.join(","),
+                trx.span.filename.as_ref().map(|it| it.to_string_lossy().to_string()),
+            ],
+        )?;
+    }
+    for posting in &store.postings {
+        db.execute(
+            "INSERT INTO postings (id, trx_id, datetime, account, unit_number, unit_commodity, cost_number, cost_commodity, weight_number, weight_commodity, balance_number, balance_commodity) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                posting.id.to_string(),
+                posting.trx_id.to_string(),
+                posting.trx_datetime.to_rfc3339(),
+                posting.account.name(),
+                posting.unit.as_ref().map(|it| it.number.to_string()),
+                posting.unit.as_ref().map(|it| it.currency.clone()),
+                posting.cost.as_ref().map(|it| it.number.to_string()),
+                posting.cost.as_ref().map(|it| it.currency.clone()),
+                posting.inferred_amount.number.to_string(),
+                posting.inferred_amount.currency,
+                posting.after_amount.number.to_string(),
+                posting.after_amount.currency,
+            ],
+        )?;
+    }
+    for price in &store.prices {
+        db.execute(
+            "INSERT INTO prices (datetime, commodity, amount, target_commodity) VALUES (?1, ?2, ?3, ?4)",
+            params![price.datetime.to_string(), price.commodity, price.amount.to_string(), price.target_commodity],
+        )?;
+    }
+    for budget in store.budgets.values() {
+        db.execute(
+            "INSERT INTO budgets (name, alias, category, commodity, closed) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![budget.name, budget.alias, budget.category, budget.commodity, budget.closed],
+        )?;
+        for (interval, detail) in &budget.detail {
+            db.execute(
+                "INSERT INTO budget_intervals (budget, interval, assigned, activity) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    budget.name,
+                    interval,
+                    detail.assigned_amount.number.to_string(),
+                    detail.activity_amount.number.to_string()
+                ],
+            )?;
+        }
+    }
+    for meta in &store.metas {
+        db.execute(
+            "INSERT INTO metas (meta_type, type_identifier, key, value) VALUES (?1, ?2, ?3, ?4)",
+            params![meta.meta_type, meta.type_identifier, meta.key, meta.value],
+        )?;
+    }
+    for error in &store.errors {
+        db.execute(
+            "INSERT INTO errors (id, error_type, source_file, span_start, span_end, metas) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                error.id,
+                error.error_type.to_string(),
+                error
+                    .span
+                    .as_ref()
+                    .and_then(|span| span.filename.as_ref())
+                    .map(|it| it.to_string_lossy().to_string()),
+                error.span.as_ref().map(|span| span.start),
+                error.span.as_ref().map(|span| span.end),
+                serde_json::to_string(&error.metas).unwrap_or_default(),
+            ],
+        )?;
+    }
+    drop(store);
+    db.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+    use rusqlite::Connection;
+
+    use crate::export::sqlite::export_sqlite;
+    use crate::test::load_from_text;
+
+    #[test]
+    fn should_export_store_into_sqlite() {
+        let ledger = load_from_text(indoc! {r#"
+            1970-01-01 commodity CNY
+            1970-01-01 open Assets:Bank
+            1970-01-01 open Expenses:Food
+
+            2024-01-10 "KFC" "Dinner"
+              Assets:Bank -50 CNY
+              Expenses:Food
+
+            2024-01-11 "KFC" "Unbalanced"
+              Assets:Bank -50 CNY
+              Expenses:Food 10 CNY
+
+            2024-01-12 price USD 7 CNY
+        "#});
+        let operations = ledger.operations();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ledger.sqlite");
+        export_sqlite(&operations, &path).unwrap();
+
+        let connection = Connection::open(&path).unwrap();
+        let count = |sql: &str| connection.query_row(sql, [], |row| row.get::<_, i64>(0)).unwrap();
+        assert_eq!(2, count("SELECT count(*) FROM accounts"));
+        assert_eq!(2, count("SELECT count(*) FROM transactions"));
+        assert_eq!(4, count("SELECT count(*) FROM postings"));
+        assert_eq!(1, count("SELECT count(*) FROM prices"));
+        assert_eq!(1, count("SELECT count(*) FROM errors WHERE error_type = 'UnbalancedTransaction'"));
+        let spent: f64 = connection
+            .query_row(
+                "SELECT sum(weight_number) FROM postings p JOIN transactions t ON p.trx_id = t.id WHERE account = 'Expenses:Food' AND t.narration = 'Dinner'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(50.0, spent);
+    }
+}
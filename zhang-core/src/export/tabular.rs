@@ -0,0 +1,635 @@
+use std::collections::BTreeMap;
+use std::ops::AddAssign;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, Datelike, Utc};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use strum::{AsRefStr, EnumString};
+use unicode_width::UnicodeWidthStr;
+use zhang_ast::amount::Amount;
+use zhang_ast::Currency;
+
+use crate::domains::schemas::{BudgetReportInterval, CommodityDomain};
+use crate::domains::Operations;
+use crate::reports::{balance_sheet, income_statement, ReportNode, ReportOptions};
+use crate::utils::amount_format::format_amount;
+use crate::{ZhangError, ZhangResult};
+
+/// the report to be exported
+#[derive(Clone, Copy, Debug, PartialEq, Eq, AsRefStr, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum ExportReport {
+    /// one row per posting, columns: `date`, `time`, `flag`, `payee`, `narration`, `account`, `number`, `commodity`,
+    /// `cost_number`, `cost_commodity`, `weight_number`, `weight_commodity`, `tags`, `links` and `transaction_id`
+    Journal,
+    /// one row per account and commodity, columns: `account`, `commodity`, `opening`, `change` and `closing`.
+    /// `opening` is the balance before `from`, `closing` is the balance at `to`
+    Balances,
+    /// one row per budget and month, columns: `name`, `alias`, `category`, `period`, `commodity`, `budgeted`, `actual`,
+    /// `variance` and `percent_consumed`
+    Budgets,
+    /// the balance sheet at `to`, one row per account and commodity in the order of account tree, columns: `account`,
+    /// `depth`, `commodity` and `amount`. the account without amount is a row without commodity
+    BalanceSheet,
+    /// the income statement between `from` and `to`, in the same rows and columns as the balance sheet
+    IncomeStatement,
+    /// one row per account and commodity held at `to`, columns: `account`, `commodity`, `amount`, `cost_basis`,
+    /// `market_value`, `unrealized_gain` and `value_commodity`
+    Holdings,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, AsRefStr, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    /// array of objects keyed by column name
+    Json,
+    /// the columns aligned for reading in terminal
+    Table,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Table => "txt",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Json => "application/json",
+            ExportFormat::Table => "text/plain; charset=utf-8",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// the columns of exported report in order, all columns are exported if absent
+    pub columns: Option<Vec<String>>,
+    /// the start of date range(inclusive)
+    pub from: Option<DateTime<Utc>>,
+    /// the end of date range(inclusive)
+    pub to: Option<DateTime<Utc>>,
+    /// display the numbers by the conventions of their commodities like `¥1,234.00`, instead of the plain numbers
+    pub formatted: bool,
+    /// max depth of account tree of the financial statements, see [`ReportOptions::depth`]
+    pub depth: Option<usize>,
+    /// the entity of the financial statements, see [`ReportOptions::entity`]
+    pub entity: Option<String>,
+    /// report all entities as a whole in the financial statements, see [`ReportOptions::consolidated`]
+    pub consolidated: bool,
+}
+
+impl ExportOptions {
+    fn contains(&self, datetime: DateTime<Utc>) -> bool {
+        self.from.map(|from| datetime.ge(&from)).unwrap_or(true) && self.to.map(|to| datetime.le(&to)).unwrap_or(true)
+    }
+
+    fn number(&self, commodities: &IndexMap<String, CommodityDomain>, number: &BigDecimal, commodity: &str) -> String {
+        match commodities.get(commodity) {
+            Some(commodity) if self.formatted => format_amount(commodity, number),
+            _ => number.to_string(),
+        }
+    }
+
+    fn report_options(&self) -> ReportOptions {
+        ReportOptions {
+            depth: self.depth,
+            convert: None,
+            entity: self.entity.clone(),
+            consolidated: self.consolidated,
+        }
+    }
+}
+
+/// the rows of report, `None` cell means the value is absent
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Option<String>>>,
+}
+
+impl Table {
+    fn new(columns: &[&str]) -> Self {
+        Table {
+            columns: columns.iter().map(|it| it.to_string()).collect_vec(),
+            rows: vec![],
+        }
+    }
+
+    /// keep the given columns in the given order
+    fn select(self, columns: &[String]) -> ZhangResult<Table> {
+        let indexes = columns
+            .iter()
+            .map(|column| {
+                self.columns
+                    .iter()
+                    .position(|it| it.eq(column))
+                    .ok_or_else(|| ZhangError::UnknownExportColumn(column.clone()))
+            })
+            .collect::<ZhangResult<Vec<_>>>()?;
+        let rows = self
+            .rows
+            .into_iter()
+            .map(|row| indexes.iter().map(|index| row[*index].clone()).collect_vec())
+            .collect_vec();
+        Ok(Table {
+            columns: columns.to_vec(),
+            rows,
+        })
+    }
+
+    pub fn to_csv(&self) -> String {
+        let line = |cells: Vec<&str>| cells.into_iter().map(escape_csv).join(",");
+        let mut content = line(self.columns.iter().map(|it| it.as_str()).collect_vec());
+        content.push('\n');
+        for row in &self.rows {
+            content.push_str(&line(row.iter().map(|cell| cell.as_deref().unwrap_or_default()).collect_vec()));
+            content.push('\n');
+        }
+        content
+    }
+
+    pub fn to_json(&self) -> ZhangResult<String> {
+        let objects = self
+            .rows
+            .iter()
+            .map(|row| self.columns.iter().zip(row).collect::<IndexMap<_, _>>())
+            .collect_vec();
+        serde_json::to_string(&objects).map_err(|e| ZhangError::CustomError(e.to_string()))
+    }
+
+    /// the columns padded to the widest cell, the numbers are aligned to the right
+    pub fn to_text(&self) -> String {
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.as_deref().unwrap_or_default()).collect_vec())
+            .collect_vec();
+        let widths = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(idx, column)| rows.iter().map(|row| row[idx].width()).chain([column.width()]).max().unwrap_or_default())
+            .collect_vec();
+        let numeric = (0..self.columns.len())
+            .map(|idx| rows.iter().any(|row| !row[idx].is_empty()) && rows.iter().all(|row| row[idx].is_empty() || BigDecimal::from_str(row[idx]).is_ok()))
+            .collect_vec();
+        let line = |cells: Vec<&str>| {
+            cells
+                .into_iter()
+                .enumerate()
+                .map(|(idx, cell)| {
+                    let padding = " ".repeat(widths[idx] - cell.width());
+                    match numeric[idx] {
+                        true => format!("{}{}", padding, cell),
+                        false => format!("{}{}", cell, padding),
+                    }
+                })
+                .join("  ")
+                .trim_end()
+                .to_owned()
+        };
+        let mut content = line(self.columns.iter().map(|it| it.as_str()).collect_vec());
+        content.push('\n');
+        content.push_str(&widths.iter().map(|width| "-".repeat(*width)).join("  "));
+        content.push('\n');
+        for row in rows {
+            content.push_str(&line(row));
+            content.push('\n');
+        }
+        content
+    }
+}
+
+fn escape_csv(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_owned()
+    }
+}
+
+fn journal(operations: &Operations, options: &ExportOptions) -> Table {
+    let mut table = Table::new(&[
+        "date",
+        "time",
+        "flag",
+        "payee",
+        "narration",
+        "account",
+        "number",
+        "commodity",
+        "cost_number",
+        "cost_commodity",
+        "weight_number",
+        "weight_commodity",
+        "tags",
+        "links",
+        "transaction_id",
+    ]);
+    let store = operations.read();
+    for posting in store
+        .postings
+        .iter()
+        .filter(|posting| options.contains(posting.datetime.with_timezone(&Utc)))
+        .sorted_by_key(|posting| (posting.datetime, posting.trx_sequence))
+    {
+        let Some(trx) = store.transactions.get(&posting.trx_id) else {
+            continue;
+        };
+        let unit = posting.unit.as_ref().unwrap_or(&posting.inferred_amount);
+        table.rows.push(vec![
+            Some(posting.datetime.naive_local().date().to_string()),
+            Some(posting.datetime.naive_local().time().to_string()),
+            Some(trx.flag.to_string()),
+            trx.payee.clone(),
+            trx.narration.clone(),
+            Some(posting.account.name().to_owned()),
+            Some(options.number(&store.commodities, &unit.number, &unit.currency)),
+            Some(unit.currency.clone()),
+            posting.cost.as_ref().map(|it| options.number(&store.commodities, &it.number, &it.currency)),
+            posting.cost.as_ref().map(|it| it.currency.clone()),
+            Some(options.number(&store.commodities, &posting.inferred_amount.number, &posting.inferred_amount.currency)),
+            Some(posting.inferred_amount.currency.clone()),
+            Some(trx.tags.join(",")),
+            Some(trx.links.join(",")),
+            Some(trx.id.to_string()),
+        ]);
+    }
+    table
+}
+
+fn balances(operations: &Operations, options: &ExportOptions) -> Table {
+    let mut table = Table::new(&["account", "commodity", "opening", "change", "closing"]);
+    let mut balances: BTreeMap<(String, Currency), (BigDecimal, BigDecimal)> = BTreeMap::new();
+    let store = operations.read();
+    for posting in &store.postings {
+        let datetime = posting.datetime.with_timezone(&Utc);
+        if options.to.map(|to| datetime.gt(&to)).unwrap_or(false) {
+            continue;
+        }
+        let (opening, change) = balances
+            .entry((posting.account.name().to_owned(), posting.inferred_amount.currency.clone()))
+            .or_insert_with(|| (BigDecimal::zero(), BigDecimal::zero()));
+        if options.from.map(|from| datetime.lt(&from)).unwrap_or(false) {
+            opening.add_assign(&posting.inferred_amount.number);
+        } else {
+            change.add_assign(&posting.inferred_amount.number);
+        }
+    }
+    for ((account, commodity), (opening, change)) in balances {
+        let closing = &opening + &change;
+        let [opening, change, closing] = [opening, change, closing].map(|number| options.number(&store.commodities, &number, &commodity));
+        table
+            .rows
+            .push(vec![Some(account), Some(commodity), Some(opening), Some(change), Some(closing)]);
+    }
+    table
+}
+
+fn budgets(operations: &Operations, options: &ExportOptions) -> ZhangResult<Table> {
+    let mut table = Table::new(&[
+        "name",
+        "alias",
+        "category",
+        "period",
+        "commodity",
+        "budgeted",
+        "actual",
+        "variance",
+        "percent_consumed",
+    ]);
+    // the period of budget is the month in the timezone of ledger
+    let month = |datetime: DateTime<Utc>| {
+        let datetime = datetime.with_timezone(&operations.timezone);
+        format!("{}-{:02}", datetime.year(), datetime.month())
+    };
+    let from = options.from.map(month);
+    let to = options.to.map(month);
+    let reports = operations.budget_report(BudgetReportInterval::Month)?;
+    let store = operations.read();
+    for report in reports {
+        if from.as_ref().map(|from| report.period.lt(from)).unwrap_or(false) || to.as_ref().map(|to| report.period.gt(to)).unwrap_or(false) {
+            continue;
+        }
+        table.rows.push(vec![
+            Some(report.name),
+            report.alias,
+            report.category,
+            Some(report.period),
+            Some(report.budgeted_amount.currency.clone()),
+            Some(options.number(&store.commodities, &report.budgeted_amount.number, &report.budgeted_amount.currency)),
+            Some(options.number(&store.commodities, &report.actual_amount.number, &report.actual_amount.currency)),
+            Some(options.number(&store.commodities, &report.variance.number, &report.variance.currency)),
+            report.percent_consumed.map(|it| it.to_string()),
+        ]);
+    }
+    Ok(table)
+}
+
+fn statement(operations: &Operations, sections: Vec<ReportNode>, options: &ExportOptions) -> Table {
+    fn push_node(table: &mut Table, commodities: &IndexMap<String, CommodityDomain>, node: &ReportNode, options: &ExportOptions) {
+        let depth = node.name.split(':').count().to_string();
+        if node.amounts.is_empty() {
+            table.rows.push(vec![Some(node.name.clone()), Some(depth.clone()), None, None]);
+        }
+        for amount in &node.amounts {
+            table.rows.push(vec![
+                Some(node.name.clone()),
+                Some(depth.clone()),
+                Some(amount.currency.clone()),
+                Some(options.number(commodities, &amount.number, &amount.currency)),
+            ]);
+        }
+        for child in &node.children {
+            push_node(table, commodities, child, options);
+        }
+    }
+    let mut table = Table::new(&["account", "depth", "commodity", "amount"]);
+    let store = operations.read();
+    for section in &sections {
+        push_node(&mut table, &store.commodities, section, options);
+    }
+    table
+}
+
+fn holdings(operations: &Operations, options: &ExportOptions) -> ZhangResult<Table> {
+    let mut table = Table::new(&[
+        "account",
+        "commodity",
+        "amount",
+        "cost_basis",
+        "market_value",
+        "unrealized_gain",
+        "value_commodity",
+    ]);
+    let date = options.to.unwrap_or_else(Utc::now).with_timezone(&operations.timezone).naive_local();
+    let holdings = operations.holdings(date)?;
+    let store = operations.read();
+    for holding in holdings {
+        let value_commodity = holding.market_value.as_ref().or(holding.cost_basis.as_ref()).map(|it| it.currency.clone());
+        let value = |amount: &Option<Amount>| amount.as_ref().map(|it| options.number(&store.commodities, &it.number, &it.currency));
+        table.rows.push(vec![
+            Some(holding.account.clone()),
+            Some(holding.commodity.clone()),
+            Some(options.number(&store.commodities, &holding.amount, &holding.commodity)),
+            value(&holding.cost_basis),
+            value(&holding.market_value),
+            value(&holding.unrealized_gain),
+            value_commodity,
+        ]);
+    }
+    Ok(table)
+}
+
+/// build the table of report, with the columns and date range of options
+pub fn export_table(operations: &Operations, report: ExportReport, options: &ExportOptions) -> ZhangResult<Table> {
+    let table = match report {
+        ExportReport::Journal => journal(operations, options),
+        ExportReport::Balances => balances(operations, options),
+        ExportReport::Budgets => budgets(operations, options)?,
+        ExportReport::BalanceSheet => {
+            let sheet = balance_sheet(operations, options.to.unwrap_or_else(Utc::now), &options.report_options())?;
+            statement(operations, vec![sheet.assets, sheet.liabilities, sheet.equity, sheet.net_income], options)
+        }
+        ExportReport::IncomeStatement => {
+            let from = options.from.unwrap_or(DateTime::<Utc>::MIN_UTC);
+            let income = income_statement(operations, from, options.to.unwrap_or_else(Utc::now), &options.report_options())?;
+            statement(operations, vec![income.income, income.expenses, income.net_income], options)
+        }
+        ExportReport::Holdings => holdings(operations, options)?,
+    };
+    match &options.columns {
+        Some(columns) => table.select(columns),
+        None => Ok(table),
+    }
+}
+
+/// export the report into the content of given format
+pub fn export_report(operations: &Operations, report: ExportReport, format: ExportFormat, options: &ExportOptions) -> ZhangResult<String> {
+    let table = export_table(operations, report, options)?;
+    match format {
+        ExportFormat::Csv => Ok(table.to_csv()),
+        ExportFormat::Json => table.to_json(),
+        ExportFormat::Table => Ok(table.to_text()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, NaiveDate, Utc};
+    use indoc::indoc;
+
+    use crate::export::{export_report, export_table, ExportFormat, ExportOptions, ExportReport};
+    use crate::test::load_from_text;
+
+    fn date(month: u32, day: u32) -> DateTime<Utc> {
+        NaiveDate::from_ymd_opt(2024, month, day).unwrap().and_hms_opt(23, 59, 59).unwrap().and_utc()
+    }
+
+    const LEDGER: &str = indoc! {r#"
+        1970-01-01 commodity CNY
+        1970-01-01 open Assets:Bank
+        1970-01-01 open Expenses:Food
+
+        2024-01-10 "KFC" "Dinner, with friends"
+          Assets:Bank -50 CNY
+          Expenses:Food
+
+        2024-02-10 "Subway" "Lunch"
+          Assets:Bank -30 CNY
+          Expenses:Food
+
+        2024-03-10 "KFC" "Lunch"
+          Assets:Bank -20 CNY
+          Expenses:Food
+    "#};
+
+    #[test]
+    fn should_export_journal_as_csv_with_given_columns() {
+        let ledger = load_from_text(LEDGER);
+        let operations = ledger.operations();
+        let options = ExportOptions {
+            columns: Some(vec!["date".to_owned(), "narration".to_owned(), "account".to_owned(), "number".to_owned()]),
+            from: Some(date(1, 1)),
+            to: Some(date(1, 31)),
+            formatted: false,
+            ..Default::default()
+        };
+        let content = export_report(&operations, ExportReport::Journal, ExportFormat::Csv, &options).unwrap();
+        assert_eq!(
+            indoc! {r#"
+                date,narration,account,number
+                2024-01-10,"Dinner, with friends",Assets:Bank,-50
+                2024-01-10,"Dinner, with friends",Expenses:Food,50
+            "#},
+            content
+        );
+    }
+
+    #[test]
+    fn should_export_balances_of_date_range_as_json() {
+        let ledger = load_from_text(LEDGER);
+        let operations = ledger.operations();
+        let options = ExportOptions {
+            columns: None,
+            from: Some(date(2, 1)),
+            to: Some(date(2, 29)),
+            formatted: false,
+            ..Default::default()
+        };
+        let content = export_report(&operations, ExportReport::Balances, ExportFormat::Json, &options).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(
+            serde_json::json!([
+                {"account": "Assets:Bank", "commodity": "CNY", "opening": "-50", "change": "-30", "closing": "-80"},
+                {"account": "Expenses:Food", "commodity": "CNY", "opening": "50", "change": "30", "closing": "80"},
+            ]),
+            json
+        );
+    }
+
+    #[test]
+    fn should_export_formatted_numbers_given_commodity_conventions() {
+        let ledger = load_from_text(indoc! {r#"
+            1970-01-01 commodity EUR
+              suffix: "€"
+              grouping-separator: " "
+              decimal-symbol: ","
+            1970-01-01 open Assets:Bank
+            1970-01-01 open Expenses:Rent
+
+            2024-01-10 "Rent"
+              Assets:Bank -1234.5 EUR
+              Expenses:Rent
+        "#});
+        let operations = ledger.operations();
+        let options = ExportOptions {
+            columns: Some(vec!["account".to_owned(), "number".to_owned()]),
+            formatted: true,
+            ..Default::default()
+        };
+        let content = export_report(&operations, ExportReport::Journal, ExportFormat::Csv, &options).unwrap();
+        assert_eq!(
+            indoc! {r#"
+                account,number
+                Assets:Bank,"-1 234,50 €"
+                Expenses:Rent,"1 234,50 €"
+            "#},
+            content
+        );
+    }
+
+    #[test]
+    fn should_reject_unknown_column() {
+        let ledger = load_from_text(LEDGER);
+        let operations = ledger.operations();
+        let options = ExportOptions {
+            columns: Some(vec!["balance".to_owned()]),
+            ..Default::default()
+        };
+        assert!(export_table(&operations, ExportReport::Journal, &options).is_err());
+    }
+
+    #[test]
+    fn should_print_balance_sheet_as_aligned_table() {
+        let ledger = load_from_text(LEDGER);
+        let operations = ledger.operations();
+        let options = ExportOptions {
+            to: Some(date(2, 29)),
+            ..Default::default()
+        };
+        let content = export_report(&operations, ExportReport::BalanceSheet, ExportFormat::Table, &options).unwrap();
+        assert_eq!(
+            indoc! {r#"
+                account      depth  commodity  amount
+                -----------  -----  ---------  ------
+                Assets           1  CNY           -80
+                Assets:Bank      2  CNY           -80
+                Liabilities      1
+                Equity           1
+                NetIncome        1  CNY           -80
+            "#},
+            content
+        );
+    }
+
+    #[test]
+    fn should_export_income_statement_of_date_range_with_depth() {
+        let ledger = load_from_text(indoc! {r#"
+            1970-01-01 open Assets:Bank
+            1970-01-01 open Expenses:Food:Dinner
+            1970-01-01 open Expenses:Food:Lunch
+            1970-01-01 open Income:Salary
+
+            2024-01-01 "Salary"
+              Assets:Bank 1000 CNY
+              Income:Salary
+            2024-01-10 "KFC" "Dinner"
+              Assets:Bank -50 CNY
+              Expenses:Food:Dinner
+            2024-02-10 "Subway" "Lunch"
+              Assets:Bank -30 CNY
+              Expenses:Food:Lunch
+        "#});
+        let operations = ledger.operations();
+        let options = ExportOptions {
+            columns: Some(vec!["account".to_owned(), "amount".to_owned()]),
+            from: Some(date(1, 1) - chrono::Duration::days(1)),
+            to: Some(date(1, 31)),
+            depth: Some(2),
+            ..Default::default()
+        };
+        let content = export_report(&operations, ExportReport::IncomeStatement, ExportFormat::Csv, &options).unwrap();
+        assert_eq!(
+            indoc! {r#"
+                account,amount
+                Income,-1000
+                Income:Salary,-1000
+                Expenses,50
+                Expenses:Food,50
+                NetIncome,950
+            "#},
+            content
+        );
+    }
+
+    #[test]
+    fn should_filter_budget_periods_in_timezone_of_ledger() {
+        let ledger = load_from_text(indoc! {r#"
+            option "timezone" "Asia/Shanghai"
+            1970-01-01 commodity CNY
+            1970-01-01 open Assets:Bank
+            1970-01-01 open Expenses:Food
+              budget: food
+
+            2024-01-01 budget food CNY
+            2024-01-01 budget-add food 100 CNY
+            2024-02-01 budget-add food 100 CNY
+        "#});
+        let operations = ledger.operations();
+        // 2024-02-01 00:30 in Asia/Shanghai, which is still January in UTC
+        let from = NaiveDate::from_ymd_opt(2024, 1, 31).unwrap().and_hms_opt(16, 30, 0).unwrap().and_utc();
+        let options = ExportOptions {
+            columns: Some(vec!["name".to_owned(), "period".to_owned(), "budgeted".to_owned()]),
+            from: Some(from),
+            formatted: false,
+            ..Default::default()
+        };
+        let content = export_report(&operations, ExportReport::Budgets, ExportFormat::Csv, &options).unwrap();
+        assert_eq!(
+            indoc! {r#"
+                name,period,budgeted
+                food,2024-02,100
+            "#},
+            content
+        );
+    }
+}
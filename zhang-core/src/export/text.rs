@@ -0,0 +1,54 @@
+//! the text export dumps the whole ledger into one zhang file by round-tripping the directives, e.g. for merging the
+//! included files into one. includes are dropped since all directives of the included files are exported.
+
+use itertools::Itertools;
+use zhang_ast::Directive;
+
+use crate::data_type::text::ZhangDataType;
+use crate::data_type::DataType;
+use crate::ledger::Ledger;
+
+pub fn export_text(ledger: &Ledger) -> String {
+    let data_type = ZhangDataType {};
+    // the metas are kept in the reversed order of declaration, along with the built-in options which are not declared in files
+    let mut content = ledger
+        .metas
+        .iter()
+        .rev()
+        .filter(|it| it.span.filename.is_some())
+        .chain(ledger.directives.iter())
+        .filter(|it| !matches!(it.data, Directive::Include(_)))
+        .map(|it| data_type.export(it.clone()))
+        .join("\n\n");
+    content.push('\n');
+    content
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use crate::export::export_text;
+    use crate::test::load_from_text;
+
+    #[test]
+    fn should_export_ledger_which_loads_into_same_directives() {
+        let ledger = load_from_text(indoc! {r#"
+            option "title" "My Ledger"
+            1970-01-01 open Assets:Bank
+            1970-01-01 open Expenses:Food
+            ; lunch of monday
+            2024-01-01 "KFC" "lunch"
+              Assets:Bank -20 CNY
+              Expenses:Food
+            2024-01-02 balance Assets:Bank -20 CNY
+        "#});
+
+        let content = export_text(&ledger);
+        let exported = load_from_text(&content);
+        assert!(exported.operations().errors().unwrap().is_empty());
+        assert_eq!(content, export_text(&exported));
+        assert!(content.contains("; lunch of monday"), "{}", content);
+        assert_eq!(ledger.directives.len(), exported.directives.len());
+    }
+}
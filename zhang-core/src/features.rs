@@ -1,4 +1,4 @@
-use crate::constants::{KEY_FEATURES_PLUGIN, TRUE};
+use crate::constants::{KEY_FEATURES_DOCUMENT_INDEX, KEY_FEATURES_PLUGIN, TRUE};
 
 /// [Features] indicates features are not stable, users need to use options to enable the feature
 /// the option directive will be like
@@ -8,12 +8,14 @@ use crate::constants::{KEY_FEATURES_PLUGIN, TRUE};
 #[derive(Default, Debug)]
 pub struct Features {
     pub plugins: bool,
+    pub document_index: bool,
 }
 
 impl Features {
     pub fn handle_options(&mut self, key: &str, value: &str) {
         match key {
             s if s == KEY_FEATURES_PLUGIN => self.plugins = value.to_lowercase().eq(TRUE),
+            s if s == KEY_FEATURES_DOCUMENT_INDEX => self.document_index = value.to_lowercase().eq(TRUE),
             _ => {}
         }
     }
@@ -0,0 +1,393 @@
+//! git data source commits every write-back of ledger, like new transactions or edited directives,
+//! into the git repository of ledger folder, so that the previous versions of directives can be looked up from the history.
+//!
+//! the `git` command is used to access the repository, the files of ledger should be on local file system.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+use chrono::DateTime;
+use chrono_tz::Tz;
+use itertools::Itertools;
+use log::debug;
+use zhang_ast::{Directive, SpanInfo};
+
+use crate::data_source::{DataSource, LoadResult, SpanEdit};
+use crate::domains::schemas::DirectiveVersionDomain;
+use crate::ledger::Ledger;
+use crate::store_snapshot::StoreSnapshot;
+use crate::utils::has_path_visited;
+use crate::{ZhangError, ZhangResult};
+
+/// the identity of commits if the repository has no `user.name` or `user.email` configured
+const DEFAULT_COMMITTER_NAME: &str = "zhang";
+const DEFAULT_COMMITTER_EMAIL: &str = "zhang@localhost";
+
+/// run git command in the folder, the stdout is returned if the command succeeds
+pub fn git(folder: &Path, args: &[&str]) -> ZhangResult<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(folder)
+        .args(args)
+        .output()
+        .map_err(|e| ZhangError::CustomError(format!("cannot run git: {}", e)))?;
+    if !output.status.success() {
+        return Err(ZhangError::CustomError(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+pub struct GitDataSource {
+    inner: Arc<dyn DataSource>,
+    repository: PathBuf,
+    /// git commands of concurrent write-backs are run one by one, since they share the index of repository
+    commit_lock: Arc<Mutex<()>>,
+}
+
+/// commit the changes of files, nothing is committed if none of the files changes
+fn commit(repository: &Path, commit_lock: &Mutex<()>, paths: &[String], message: &str) -> ZhangResult<()> {
+    let paths = paths.iter().map(String::as_str).unique().collect_vec();
+    if paths.is_empty() {
+        return Ok(());
+    }
+    let _guard = commit_lock.lock().unwrap();
+    git(repository, &[&["add", "--"], paths.as_slice()].concat())?;
+    if git(repository, &[&["diff", "--cached", "--quiet", "--"], paths.as_slice()].concat()).is_ok() {
+        return Ok(());
+    }
+    let name = git(repository, &["config", "user.name"]).unwrap_or_default();
+    let email = git(repository, &["config", "user.email"]).unwrap_or_default();
+    let name = format!("user.name={}", if name.trim().is_empty() { DEFAULT_COMMITTER_NAME } else { name.trim() });
+    let email = format!("user.email={}", if email.trim().is_empty() { DEFAULT_COMMITTER_EMAIL } else { email.trim() });
+    git(
+        repository,
+        &[&["-c", &name, "-c", &email, "commit", "--quiet", "-m", message, "--"], paths.as_slice()].concat(),
+    )?;
+    Ok(())
+}
+
+impl GitDataSource {
+    /// wrap the data source whose files are in `repository`, the repository is initialized if it does not exist
+    pub fn new(inner: Arc<dyn DataSource>, repository: impl AsRef<Path>) -> ZhangResult<Self> {
+        let repository = repository.as_ref().to_path_buf();
+        if git(&repository, &["rev-parse", "--is-inside-work-tree"]).is_err() {
+            debug!("initializing git repository: {}", repository.display());
+            git(&repository, &["init", "--quiet"])?;
+        }
+        Ok(GitDataSource {
+            inner,
+            repository,
+            commit_lock: Arc::default(),
+        })
+    }
+
+    fn commit(&self, paths: &[String], message: &str) -> ZhangResult<()> {
+        commit(&self.repository, &self.commit_lock, paths, message)
+    }
+
+    /// the git commands are blocking, they are run off the async workers
+    async fn async_commit(&self, paths: Vec<String>, message: String) -> ZhangResult<()> {
+        let repository = self.repository.clone();
+        let commit_lock = self.commit_lock.clone();
+        tokio::task::spawn_blocking(move || commit(&repository, &commit_lock, &paths, &message))
+            .await
+            .map_err(|e| ZhangError::CustomError(format!("cannot run git: {}", e)))?
+    }
+
+    /// the files written by appending directives, which are the files of their spans, and the main file once a new file is included into it
+    fn appended_paths(ledger: &Ledger, spans: &[SpanInfo]) -> Vec<String> {
+        let files = spans.iter().filter_map(|span| span.filename.clone()).unique().collect_vec();
+        let (entry, main_file_endpoint) = &ledger.entry;
+        let includes_new_file = files
+            .iter()
+            .any(|file| !has_path_visited(&ledger.visited_files, file) && !has_path_visited(&ledger.visited_files, &entry.join(file)));
+        let main_file = includes_new_file.then(|| entry.join(main_file_endpoint));
+        files.into_iter().chain(main_file).map(|it| it.to_string_lossy().to_string()).collect_vec()
+    }
+
+    fn edit_message(path: &str) -> String {
+        format!("edit {}\n\nAction: edit\nFile: {}", path, path)
+    }
+
+    fn append_message(ledger: &Ledger, directives: &[Directive], spans: &[SpanInfo]) -> (Vec<String>, String) {
+        let paths = GitDataSource::appended_paths(ledger, spans);
+        let message = GitDataSource::message("append", &directives.iter().collect_vec(), None, &paths);
+        (paths, message)
+    }
+
+    /// the file of span is the one edited by the edit of span
+    fn span_edit_message(action: &str, directive: Option<&Directive>, span: &SpanInfo) -> (Vec<String>, String) {
+        let paths = span.filename.iter().map(|it| it.to_string_lossy().to_string()).collect_vec();
+        let message = GitDataSource::message(action, &directive.into_iter().collect_vec(), Some(span), &paths);
+        (paths, message)
+    }
+
+    /// the message of write-back, whose subject is followed by the `key: value` lines of its details
+    fn message(action: &str, directives: &[&Directive], span: Option<&SpanInfo>, paths: &[String]) -> String {
+        let directive_types = directives
+            .iter()
+            .map(|directive| format!("{:?}", directive.directive_type()))
+            .unique()
+            .collect_vec();
+        let subject = match (directives.len(), directive_types.as_slice()) {
+            (0, _) => format!("{} directive", action),
+            (1, [directive_type]) => format!("{} {}", action, directive_type),
+            (count, _) => format!("{} {} directives", action, count),
+        };
+        let mut details = vec![format!("Action: {}", action)];
+        if !directive_types.is_empty() {
+            details.push(format!("Directive: {}", directive_types.join(", ")));
+        }
+        details.extend(paths.iter().unique().map(|path| format!("File: {}", path)));
+        if let Some(span) = span {
+            details.push(format!("Span: {}..{}", span.start, span.end));
+        }
+        format!("{}\n\n{}", subject, details.join("\n"))
+    }
+}
+
+/// the write-backs of inner data source write the files by the inner one itself, so that each write-back is committed
+/// with the files derived from its own result, e.g. the spans of appended directives
+#[async_trait::async_trait]
+impl DataSource for GitDataSource {
+    fn export(&self, directive: Directive) -> ZhangResult<Vec<u8>> {
+        self.inner.export(directive)
+    }
+
+    fn store_snapshot(&self) -> Option<&StoreSnapshot> {
+        self.inner.store_snapshot()
+    }
+
+    fn get(&self, path: String) -> ZhangResult<Vec<u8>> {
+        self.inner.get(path)
+    }
+
+    fn load(&self, entry: String, endpoint: String) -> ZhangResult<LoadResult> {
+        self.inner.load(entry, endpoint)
+    }
+
+    fn save(&self, ledger: &Ledger, path: String, content: &[u8]) -> ZhangResult<()> {
+        self.inner.save(ledger, path.clone(), content)?;
+        self.commit(std::slice::from_ref(&path), &GitDataSource::edit_message(&path))
+    }
+
+    fn append(&self, ledger: &Ledger, directives: Vec<Directive>) -> ZhangResult<Vec<SpanInfo>> {
+        let spans = self.inner.append(ledger, directives.clone())?;
+        let (paths, message) = GitDataSource::append_message(ledger, &directives, &spans);
+        self.commit(&paths, &message)?;
+        Ok(spans)
+    }
+
+    async fn async_load(&self, entry: String, endpoint: String) -> ZhangResult<LoadResult> {
+        self.inner.async_load(entry, endpoint).await
+    }
+
+    async fn async_get(&self, path: String) -> ZhangResult<Vec<u8>> {
+        self.inner.async_get(path).await
+    }
+
+    async fn async_append(&self, ledger: &Ledger, directives: Vec<Directive>) -> ZhangResult<Vec<SpanInfo>> {
+        let spans = self.inner.async_append(ledger, directives.clone()).await?;
+        let (paths, message) = GitDataSource::append_message(ledger, &directives, &spans);
+        self.async_commit(paths, message).await?;
+        Ok(spans)
+    }
+
+    async fn async_save(&self, ledger: &Ledger, path: String, content: &[u8]) -> ZhangResult<()> {
+        self.inner.async_save(ledger, path.clone(), content).await?;
+        let message = GitDataSource::edit_message(&path);
+        self.async_commit(vec![path], message).await
+    }
+
+    fn insert_directive(&self, ledger: &Ledger, span: &SpanInfo, directive: Directive) -> ZhangResult<()> {
+        self.inner.insert_directive(ledger, span, directive.clone())?;
+        let (paths, message) = GitDataSource::span_edit_message("insert", Some(&directive), span);
+        self.commit(&paths, &message)
+    }
+
+    fn update_directive(&self, ledger: &Ledger, span: &SpanInfo, directive: Directive) -> ZhangResult<()> {
+        self.inner.update_directive(ledger, span, directive.clone())?;
+        let (paths, message) = GitDataSource::span_edit_message("update", Some(&directive), span);
+        self.commit(&paths, &message)
+    }
+
+    fn delete_directive(&self, ledger: &Ledger, span: &SpanInfo) -> ZhangResult<()> {
+        self.inner.delete_directive(ledger, span)?;
+        let (paths, message) = GitDataSource::span_edit_message("delete", None, span);
+        self.commit(&paths, &message)
+    }
+
+    fn edit_span(&self, ledger: &Ledger, span: &SpanInfo, edit: SpanEdit<'_>) -> ZhangResult<()> {
+        self.inner.edit_span(ledger, span, edit)?;
+        let (paths, message) = GitDataSource::span_edit_message("edit", None, span);
+        self.commit(&paths, &message)
+    }
+
+    async fn async_insert_directive(&self, ledger: &Ledger, span: &SpanInfo, directive: Directive) -> ZhangResult<()> {
+        self.inner.async_insert_directive(ledger, span, directive.clone()).await?;
+        let (paths, message) = GitDataSource::span_edit_message("insert", Some(&directive), span);
+        self.async_commit(paths, message).await
+    }
+
+    async fn async_update_directive(&self, ledger: &Ledger, span: &SpanInfo, directive: Directive) -> ZhangResult<()> {
+        self.inner.async_update_directive(ledger, span, directive.clone()).await?;
+        let (paths, message) = GitDataSource::span_edit_message("update", Some(&directive), span);
+        self.async_commit(paths, message).await
+    }
+
+    async fn async_delete_directive(&self, ledger: &Ledger, span: &SpanInfo) -> ZhangResult<()> {
+        self.inner.async_delete_directive(ledger, span).await?;
+        let (paths, message) = GitDataSource::span_edit_message("delete", None, span);
+        self.async_commit(paths, message).await
+    }
+
+    async fn async_edit_span(&self, ledger: &Ledger, span: &SpanInfo, edit: SpanEdit<'_>) -> ZhangResult<()> {
+        self.inner.async_edit_span(ledger, span, edit).await?;
+        let (paths, message) = GitDataSource::span_edit_message("edit", None, span);
+        self.async_commit(paths, message).await
+    }
+}
+
+/// the versions of the lines of directive in the history of its file, from the latest to the earliest.
+/// the lines are traced by `git log -L`, so that the directive is followed even if the lines around it change
+pub fn directive_history(span: &SpanInfo, timezone: &Tz) -> ZhangResult<Vec<DirectiveVersionDomain>> {
+    let file = span
+        .filename
+        .as_ref()
+        .ok_or_else(|| ZhangError::CustomError("the span of directive does not belong to any file".to_owned()))?;
+    let content = std::fs::read_to_string(file)?;
+    if content.get(span.start..span.end) != Some(span.content.as_str()) {
+        return Err(ZhangError::DirectiveModified(file.clone()));
+    }
+    let start_line = content[..span.start].matches('\n').count() + 1;
+    let end_line = start_line + span.content.trim_end().matches('\n').count();
+
+    let folder = file.parent().unwrap_or(Path::new("."));
+    let file_name = file.file_name().map(|it| it.to_string_lossy().to_string()).unwrap_or_default();
+    let log = git(
+        folder,
+        &[
+            "log",
+            "--no-color",
+            "--format=%x1e%H%x1f%an%x1f%aI%x1f%s",
+            &format!("-L{},{}:{}", start_line, end_line, file_name),
+        ],
+    )?;
+
+    log.split('\x1e')
+        .filter(|it| !it.trim().is_empty())
+        .map(|entry| {
+            let (header, diff) = entry.split_once('\n').unwrap_or((entry, ""));
+            let [commit, author, datetime, message]: [&str; 4] = header
+                .splitn(4, '\x1f')
+                .collect_vec()
+                .try_into()
+                .map_err(|_| ZhangError::CustomError(format!("unexpected git log: {}", header)))?;
+            let datetime = DateTime::parse_from_rfc3339(datetime)
+                .map_err(|e| ZhangError::CustomError(format!("invalid commit datetime {}: {}", datetime, e)))?
+                .with_timezone(timezone);
+            // the lines of directive after the commit are the context and added lines of the hunk
+            let content = diff
+                .lines()
+                .skip_while(|line| !line.starts_with("@@"))
+                .filter(|line| !line.starts_with("@@"))
+                .filter_map(|line| line.strip_prefix(' ').or_else(|| line.strip_prefix('+')))
+                .join("\n");
+            Ok(DirectiveVersionDomain {
+                commit: commit.to_owned(),
+                author: author.to_owned(),
+                datetime,
+                message: message.to_owned(),
+                content,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use indoc::indoc;
+    use tempfile::tempdir;
+    use zhang_ast::{Directive, SpanInfo, ZhangString};
+
+    use crate::data_source::LocalFileSystemDataSource;
+    use crate::data_type::text::ZhangDataType;
+    use crate::data_type::DataType;
+    use crate::git::{git, GitDataSource};
+    use crate::ledger::Ledger;
+
+    fn load(root: &Path) -> Ledger {
+        let data_source = GitDataSource::new(Arc::new(LocalFileSystemDataSource::new(ZhangDataType {})), root).unwrap();
+        Ledger::load_with_data_source(root.to_path_buf(), "main.zhang".to_owned(), Arc::new(data_source)).unwrap()
+    }
+
+    fn transaction_span(ledger: &Ledger) -> (SpanInfo, Directive) {
+        let directive = ledger.directives.iter().find(|it| matches!(it.data, Directive::Transaction(_))).unwrap();
+        (directive.span.clone(), directive.data.clone())
+    }
+
+    #[test]
+    fn should_commit_write_backs_and_trace_directive_history() {
+        let root = tempdir().unwrap().into_path().canonicalize().unwrap();
+        std::fs::write(
+            root.join("main.zhang"),
+            indoc! {r#"
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Expenses:Food
+
+                2023-12-02 "KFC" "Crazy Thursday"
+                  Assets:Bank -50 CNY
+                  Expenses:Food
+            "#},
+        )
+        .unwrap();
+
+        let ledger = load(&root);
+        let (span, directive) = transaction_span(&ledger);
+        ledger.data_source.update_directive(&ledger, &span, directive).unwrap();
+
+        let ledger = load(&root);
+        let (span, mut directive) = transaction_span(&ledger);
+        if let Directive::Transaction(trx) = &mut directive {
+            trx.narration = Some(ZhangString::quote("Crazy Friday"));
+        }
+        ledger.data_source.update_directive(&ledger, &span, directive).unwrap();
+
+        let log = git(&root, &["log", "--format=%B%x00"]).unwrap();
+        let messages = log.split('\0').map(str::trim).filter(|it| !it.is_empty()).collect::<Vec<_>>();
+        assert_eq!(2, messages.len());
+        assert!(messages[0].starts_with("update Transaction\n\nAction: update\nDirective: Transaction\nFile: "));
+
+        let ledger = load(&root);
+        let (span, _) = transaction_span(&ledger);
+        let history = ledger.operations().directive_history(&span).unwrap();
+        assert_eq!(2, history.len());
+        assert_eq!("update Transaction", history[0].message);
+        assert!(history[0].content.contains("\"Crazy Friday\""));
+        assert!(history[1].content.contains("\"Crazy Thursday\""));
+    }
+
+    #[test]
+    fn should_commit_appended_directives_together() {
+        let root = tempdir().unwrap().into_path().canonicalize().unwrap();
+        std::fs::write(root.join("main.zhang"), "1970-01-01 open Assets:Bank\n").unwrap();
+
+        let ledger = load(&root);
+        let directives = ZhangDataType {}
+            .transform("1970-01-02 open Assets:Cash\n1970-01-02 open Assets:Card\n".to_owned(), None)
+            .unwrap();
+        ledger.data_source.append(&ledger, directives.into_iter().map(|it| it.data).collect()).unwrap();
+
+        let subjects = git(&root, &["log", "--format=%s"]).unwrap();
+        assert_eq!("append 2 directives\n", subjects);
+        assert_eq!("", git(&root, &["status", "--porcelain"]).unwrap(), "every appended file is committed");
+    }
+}
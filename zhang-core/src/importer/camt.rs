@@ -0,0 +1,241 @@
+//! camt.053 (ISO 20022 bank to customer statement) statements.
+//! only the booked entries are imported, and the payee is the counterparty of entry, i.e. the creditor of debit entries
+//! and the debtor of credit entries.
+
+use chrono::NaiveDate;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+use crate::importer::{BankBalance, BankEntry, BankStatement};
+use crate::{ZhangError, ZhangResult};
+
+#[derive(Default)]
+struct CamtBalance {
+    code: Option<String>,
+    amount: Option<String>,
+    debit: bool,
+    date: Option<NaiveDate>,
+}
+
+#[derive(Default)]
+struct CamtEntry {
+    entry: BankEntry,
+    debit: bool,
+    status: Option<String>,
+    reference: Option<String>,
+    creditor: Option<String>,
+    debtor: Option<String>,
+    remittances: Vec<String>,
+    additional_info: Option<String>,
+}
+
+impl CamtEntry {
+    fn into_entry(self) -> Option<BankEntry> {
+        if self.status.as_deref().map(|it| it != "BOOK").unwrap_or(false) {
+            return None;
+        }
+        let mut entry = self.entry;
+        if self.debit {
+            entry.amount = entry.amount.map(|it| format!("-{}", it));
+        }
+        entry.id = self.reference.or(entry.id);
+        entry.payee = if self.debit { self.creditor } else { self.debtor };
+        entry.narration = if self.remittances.is_empty() {
+            self.additional_info
+        } else {
+            Some(self.remittances.join(" "))
+        };
+        Some(entry)
+    }
+}
+
+/// the date of camt is either `Dt` in format `YYYY-MM-DD` or `DtTm` in ISO 8601, only the date part is used
+fn parse_date(content: &str) -> Option<NaiveDate> {
+    content.get(..10).and_then(|it| NaiveDate::parse_from_str(it, "%Y-%m-%d").ok())
+}
+
+pub fn parse_camt(content: &str) -> ZhangResult<Vec<BankStatement>> {
+    let mut reader = Reader::from_str(content);
+    reader.trim_text(true);
+
+    let mut path: Vec<String> = vec![];
+    let mut statements = vec![];
+    let mut statement: Option<BankStatement> = None;
+    let mut balance: Option<CamtBalance> = None;
+    let mut entry: Option<CamtEntry> = None;
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| ZhangError::ImportError(format!("invalid camt.053 file: {}", e)))?;
+        match event {
+            Event::Start(start) => {
+                let name = String::from_utf8_lossy(start.local_name().as_ref()).to_string();
+                match name.as_str() {
+                    "Stmt" => statement = Some(BankStatement::default()),
+                    "Bal" => balance = Some(CamtBalance::default()),
+                    "Ntry" => entry = Some(CamtEntry::default()),
+                    "Amt" => {
+                        let currency = start
+                            .try_get_attribute("Ccy")
+                            .ok()
+                            .flatten()
+                            .and_then(|it| it.unescape_value().ok().map(|it| it.to_string()));
+                        if let (Some(statement), Some(currency)) = (statement.as_mut(), currency) {
+                            statement.currency.get_or_insert(currency);
+                        }
+                    }
+                    _ => {}
+                }
+                path.push(name);
+            }
+            Event::End(_) => {
+                match path.pop().as_deref() {
+                    Some("Stmt") => statements.extend(statement.take()),
+                    Some("Bal") => {
+                        if let Some(CamtBalance {
+                            code: Some(code),
+                            amount: Some(amount),
+                            debit,
+                            date: Some(date),
+                        }) = balance.take()
+                        {
+                            // closing booked balance
+                            if code == "CLBD" {
+                                if let Some(statement) = statement.as_mut() {
+                                    let amount = if debit { format!("-{}", amount) } else { amount };
+                                    statement.balances.push(BankBalance { date, amount });
+                                }
+                            }
+                        }
+                    }
+                    Some("Ntry") => {
+                        if let (Some(statement), Some(entry)) = (statement.as_mut(), entry.take().and_then(CamtEntry::into_entry)) {
+                            statement.entries.push(entry);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::Text(text) => {
+                let text = text
+                    .unescape()
+                    .map_err(|e| ZhangError::ImportError(format!("invalid camt.053 file: {}", e)))?
+                    .to_string();
+                let path = path.join("/");
+                if let Some(entry) = entry.as_mut() {
+                    if path.ends_with("Ntry/Amt") {
+                        entry.entry.amount = Some(text);
+                    } else if path.ends_with("Ntry/CdtDbtInd") {
+                        entry.debit = text == "DBIT";
+                    } else if path.ends_with("Ntry/Sts") || path.ends_with("Ntry/Sts/Cd") {
+                        entry.status = Some(text);
+                    } else if path.ends_with("Ntry/BookgDt/Dt") || path.ends_with("Ntry/BookgDt/DtTm") {
+                        entry.entry.date = parse_date(&text);
+                    } else if path.ends_with("Ntry/AcctSvcrRef") {
+                        entry.reference = Some(text);
+                    } else if path.ends_with("Ntry/NtryRef") {
+                        entry.entry.id = Some(text);
+                    } else if path.ends_with("RltdPties/Cdtr/Nm") || path.ends_with("RltdPties/Cdtr/Pty/Nm") {
+                        entry.creditor.get_or_insert(text);
+                    } else if path.ends_with("RltdPties/Dbtr/Nm") || path.ends_with("RltdPties/Dbtr/Pty/Nm") {
+                        entry.debtor.get_or_insert(text);
+                    } else if path.ends_with("RmtInf/Ustrd") {
+                        entry.remittances.push(text);
+                    } else if path.ends_with("Ntry/AddtlNtryInf") {
+                        entry.additional_info = Some(text);
+                    }
+                } else if let Some(balance) = balance.as_mut() {
+                    if path.ends_with("Bal/Tp/CdOrPrtry/Cd") {
+                        balance.code = Some(text);
+                    } else if path.ends_with("Bal/Amt") {
+                        balance.amount = Some(text);
+                    } else if path.ends_with("Bal/CdtDbtInd") {
+                        balance.debit = text == "DBIT";
+                    } else if path.ends_with("Bal/Dt/Dt") || path.ends_with("Bal/Dt/DtTm") {
+                        balance.date = parse_date(&text);
+                    }
+                } else if let Some(statement) = statement.as_mut() {
+                    if path.ends_with("Stmt/Acct/Id/IBAN") || path.ends_with("Stmt/Acct/Id/Othr/Id") {
+                        statement.acctid = Some(text);
+                    } else if path.ends_with("Stmt/Acct/Ccy") {
+                        statement.currency = Some(text);
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if statements.is_empty() {
+        return Err(ZhangError::ImportError("no statement is found in camt.053 file".to_owned()));
+    }
+    Ok(statements)
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use crate::importer::camt::parse_camt;
+
+    #[test]
+    fn should_parse_booked_entries_and_closing_balance() {
+        let statements = parse_camt(indoc! {r#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02">
+              <BkToCstmrStmt>
+                <Stmt>
+                  <Id>2024-01</Id>
+                  <Acct><Id><IBAN>DE89370400440532013000</IBAN></Id><Ccy>EUR</Ccy></Acct>
+                  <Bal>
+                    <Tp><CdOrPrtry><Cd>OPBD</Cd></CdOrPrtry></Tp>
+                    <Amt Ccy="EUR">100.00</Amt><CdtDbtInd>CRDT</CdtDbtInd>
+                    <Dt><Dt>2024-01-01</Dt></Dt>
+                  </Bal>
+                  <Bal>
+                    <Tp><CdOrPrtry><Cd>CLBD</Cd></CdOrPrtry></Tp>
+                    <Amt Ccy="EUR">2096.50</Amt><CdtDbtInd>CRDT</CdtDbtInd>
+                    <Dt><Dt>2024-01-31</Dt></Dt>
+                  </Bal>
+                  <Ntry>
+                    <Amt Ccy="EUR">3.50</Amt><CdtDbtInd>DBIT</CdtDbtInd><Sts>BOOK</Sts>
+                    <BookgDt><Dt>2024-01-02</Dt></BookgDt>
+                    <AcctSvcrRef>REF-1</AcctSvcrRef>
+                    <NtryDtls><TxDtls>
+                      <RltdPties><Dbtr><Nm>John Doe</Nm></Dbtr><Cdtr><Nm>B&amp;B Bakery</Nm></Cdtr></RltdPties>
+                      <RmtInf><Ustrd>Breakfast</Ustrd></RmtInf>
+                    </TxDtls></NtryDtls>
+                  </Ntry>
+                  <Ntry>
+                    <Amt Ccy="EUR">2000.00</Amt><CdtDbtInd>CRDT</CdtDbtInd><Sts>BOOK</Sts>
+                    <BookgDt><DtTm>2024-01-25T10:00:00</DtTm></BookgDt>
+                    <AcctSvcrRef>REF-2</AcctSvcrRef>
+                    <NtryDtls><TxDtls><RltdPties><Dbtr><Nm>ACME GmbH</Nm></Dbtr></RltdPties></TxDtls></NtryDtls>
+                    <AddtlNtryInf>Salary</AddtlNtryInf>
+                  </Ntry>
+                  <Ntry>
+                    <Amt Ccy="EUR">9.99</Amt><CdtDbtInd>DBIT</CdtDbtInd><Sts>PDNG</Sts>
+                    <BookgDt><Dt>2024-01-31</Dt></BookgDt>
+                  </Ntry>
+                </Stmt>
+              </BkToCstmrStmt>
+            </Document>
+        "#})
+        .unwrap();
+        assert_eq!(1, statements.len());
+        let statement = &statements[0];
+        assert_eq!(Some("DE89370400440532013000".to_owned()), statement.acctid);
+        assert_eq!(Some("EUR".to_owned()), statement.currency);
+        assert_eq!(2, statement.entries.len());
+        assert_eq!(Some("-3.50".to_owned()), statement.entries[0].amount);
+        assert_eq!(Some("B&B Bakery".to_owned()), statement.entries[0].payee);
+        assert_eq!(Some("Breakfast".to_owned()), statement.entries[0].narration);
+        assert_eq!(Some("REF-1".to_owned()), statement.entries[0].id);
+        assert_eq!(Some("ACME GmbH".to_owned()), statement.entries[1].payee);
+        assert_eq!(Some("Salary".to_owned()), statement.entries[1].narration);
+        assert_eq!("2024-01-25", statement.entries[1].date.unwrap().to_string());
+        assert_eq!(1, statement.balances.len());
+        assert_eq!("2024-01-31", statement.balances[0].date.to_string());
+        assert_eq!("2096.50", statement.balances[0].amount);
+    }
+}
@@ -0,0 +1,177 @@
+use chrono::NaiveDate;
+use serde::Deserialize;
+use zhang_ast::amount::Amount;
+
+use crate::importer::{parse_account, parse_number, Statement, StatementEntry};
+use crate::{ZhangError, ZhangResult};
+
+/// the column of csv, given by the header name or the index(starts from 0)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum CsvColumn {
+    Index(usize),
+    Name(String),
+}
+
+fn default_delimiter() -> char {
+    ','
+}
+
+fn default_header() -> bool {
+    true
+}
+
+fn default_date_format() -> String {
+    "%Y-%m-%d".to_owned()
+}
+
+/// the mapping from csv columns to statement entries.
+/// the amount is read from `amount` column, or `credit - debit` if the statement has separated columns of money in and out.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CsvMapping {
+    /// the statement account
+    pub account: String,
+    pub commodity: String,
+    #[serde(default = "default_delimiter")]
+    pub delimiter: char,
+    /// the lines to skip before the header, e.g. the account information of statement
+    #[serde(default)]
+    pub skip: usize,
+    #[serde(default = "default_header")]
+    pub header: bool,
+    pub date: CsvColumn,
+    #[serde(default = "default_date_format")]
+    pub date_format: String,
+    pub payee: Option<CsvColumn>,
+    pub narration: Option<CsvColumn>,
+    pub amount: Option<CsvColumn>,
+    /// the column of money out
+    pub debit: Option<CsvColumn>,
+    /// the column of money in
+    pub credit: Option<CsvColumn>,
+    /// the column of unique id of entry, used to skip the imported entries
+    pub id: Option<CsvColumn>,
+    /// negate the amount, e.g. the statement of credit card where the spending is positive
+    #[serde(default)]
+    pub negate: bool,
+}
+
+impl CsvMapping {
+    fn index(&self, column: &CsvColumn, headers: &[String]) -> ZhangResult<usize> {
+        match column {
+            CsvColumn::Index(index) => Ok(*index),
+            CsvColumn::Name(name) => headers
+                .iter()
+                .position(|it| it.trim().eq(name))
+                .ok_or_else(|| ZhangError::ImportError(format!("column {} is not found in csv header", name))),
+        }
+    }
+
+    pub fn parse(&self, content: &str) -> ZhangResult<Statement> {
+        let account = parse_account(&self.account)?;
+        let content = content.lines().skip(self.skip).collect::<Vec<_>>().join("\n");
+        let delimiter = u8::try_from(self.delimiter).map_err(|_| ZhangError::ImportError(format!("delimiter {} is not supported", self.delimiter)))?;
+        let mut reader = ::csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .has_headers(self.header)
+            .flexible(true)
+            .trim(::csv::Trim::All)
+            .from_reader(content.as_bytes());
+        let headers = if self.header {
+            let headers = reader.headers().map_err(|e| ZhangError::ImportError(e.to_string()))?;
+            headers.iter().map(|it| it.to_owned()).collect::<Vec<_>>()
+        } else {
+            vec![]
+        };
+        let index = |column: &Option<CsvColumn>| column.as_ref().map(|it| self.index(it, &headers)).transpose();
+        let date_index = self.index(&self.date, &headers)?;
+        let payee_index = index(&self.payee)?;
+        let narration_index = index(&self.narration)?;
+        let amount_index = index(&self.amount)?;
+        let debit_index = index(&self.debit)?;
+        let credit_index = index(&self.credit)?;
+        let id_index = index(&self.id)?;
+        if amount_index.is_none() && debit_index.is_none() && credit_index.is_none() {
+            return Err(ZhangError::ImportError("one of amount, debit and credit column must be given".to_owned()));
+        }
+
+        let mut entries = vec![];
+        for (line, record) in reader.records().enumerate() {
+            let record = record.map_err(|e| ZhangError::ImportError(e.to_string()))?;
+            let cell = |index: Option<usize>| index.and_then(|it| record.get(it)).filter(|it| !it.is_empty());
+            // rows without date are usually the summary of statement
+            let Some(date) = cell(Some(date_index)) else {
+                continue;
+            };
+            let date = NaiveDate::parse_from_str(date, &self.date_format)
+                .map_err(|_| ZhangError::ImportError(format!("date {} of row {} does not match format {}", date, line + 1, self.date_format)))?;
+            let number = |index: Option<usize>| {
+                cell(index)
+                    .map(|it| parse_number(it).ok_or_else(|| ZhangError::ImportError(format!("{} of row {} is not a valid number", it, line + 1))))
+                    .transpose()
+            };
+            let number = match amount_index {
+                Some(_) => number(amount_index)?,
+                None => match (number(credit_index)?, number(debit_index)?) {
+                    (None, None) => None,
+                    (credit, debit) => Some(credit.unwrap_or_default() - debit.unwrap_or_default()),
+                },
+            };
+            let Some(number) = number else {
+                continue;
+            };
+            entries.push(StatementEntry {
+                date,
+                payee: cell(payee_index).map(|it| it.to_owned()),
+                narration: cell(narration_index).map(|it| it.to_owned()),
+                amount: Amount::new(if self.negate { -number } else { number }, self.commodity.clone()),
+                id: cell(id_index).map(|it| it.to_owned()),
+            });
+        }
+        Ok(Statement {
+            account,
+            entries,
+            balances: vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use crate::importer::{CsvColumn, CsvMapping};
+
+    #[test]
+    fn should_parse_statement_with_debit_and_credit_columns() {
+        let mapping = CsvMapping {
+            account: "Assets:Bank".to_owned(),
+            commodity: "EUR".to_owned(),
+            delimiter: ';',
+            skip: 2,
+            header: false,
+            date: CsvColumn::Index(0),
+            date_format: "%d.%m.%Y".to_owned(),
+            payee: Some(CsvColumn::Index(1)),
+            narration: None,
+            amount: None,
+            debit: Some(CsvColumn::Index(2)),
+            credit: Some(CsvColumn::Index(3)),
+            id: None,
+            negate: false,
+        };
+        let statement = mapping
+            .parse(indoc! {r#"
+                Account;DE00 1234
+                Period;2024-01
+                02.01.2024;Bakery;3.50;
+                03.01.2024;Employer;;2000
+                ;Total;3.50;2000
+            "#})
+            .unwrap();
+        let amounts = statement.entries.iter().map(|it| it.amount.number.to_string()).collect::<Vec<_>>();
+        assert_eq!(vec!["-3.50", "2000"], amounts);
+        assert_eq!(Some("Bakery".to_owned()), statement.entries[0].payee);
+        assert_eq!("2024-01-03", statement.entries[1].date.to_string());
+    }
+}
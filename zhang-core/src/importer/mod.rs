@@ -0,0 +1,629 @@
+//! importer converts bank statements into zhang transactions, each statement entry becomes a transaction between the
+//! statement account and the counter account decided by classification rules.
+//!
+//! importers are configured in TOML:
+//! ```toml
+//! default_account = "Expenses:Uncategorized"
+//!
+//! [csv.cmb]
+//! account = "Assets:Bank:CMB"
+//! commodity = "CNY"
+//! date = "Date"
+//! date_format = "%Y-%m-%d"
+//! payee = "Payee"
+//! narration = "Description"
+//! amount = "Amount"
+//!
+//! [ofx.checking]
+//! account = "Assets:Bank:Checking"
+//! acctid = "1234567890"
+//!
+//! [camt.giro]
+//! account = "Assets:Bank:Giro"
+//! acctid = "DE89370400440532013000"
+//!
+//! [[rules]]
+//! payee = "(?i)starbucks"
+//! account = "Expenses:Coffee"
+//! tags = ["coffee"]
+//! ```
+//!
+//! the id of entry, e.g. the `FITID` of OFX, is recorded in the `import-id` meta of transaction,
+//! entries which have been imported are skipped by [Importer::exclude_imported], and the probable duplicates of
+//! transactions in ledger are split out by [Importer::exclude_duplicates].
+//! the closing balances of bank statements are imported as balance assertions.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use chrono::{Duration, NaiveDate};
+use indexmap::IndexSet;
+use itertools::Itertools;
+use regex::Regex;
+use serde::Deserialize;
+use zhang_ast::amount::Amount;
+use zhang_ast::{Account, BalanceCheck, Date, Directive, Flag, Meta, Posting, PostingType, Transaction, ZhangString};
+
+use crate::constants::TXN_IMPORT_ID;
+use crate::domains::schemas::MetaType;
+use crate::ledger::Ledger;
+use crate::process::duplicate::{ledger_fingerprints, TransactionFingerprint};
+use crate::utils::append_directives;
+use crate::{ZhangError, ZhangResult};
+
+mod camt;
+mod csv;
+mod mt940;
+mod ofx;
+
+pub use self::camt::parse_camt;
+pub use self::csv::{CsvColumn, CsvMapping};
+pub use self::mt940::parse_mt940;
+pub use self::ofx::parse_ofx;
+
+/// find the mapping of given name, the name can be omitted if only one mapping is configured
+fn find_mapping<'a, T>(mappings: &'a HashMap<String, T>, name: Option<&str>, format: &str) -> ZhangResult<&'a T> {
+    match name {
+        Some(name) => mappings
+            .get(name)
+            .ok_or_else(|| ZhangError::ImportError(format!("{} mapping {} is not configured", format, name))),
+        None => match mappings.values().collect_vec().as_slice() {
+            [mapping] => Ok(mapping),
+            _ => Err(ZhangError::ImportError(format!("the name of {} mapping must be given", format))),
+        },
+    }
+}
+
+fn default_account() -> String {
+    "Expenses:Uncategorized".to_owned()
+}
+
+fn default_flag() -> String {
+    "!".to_owned()
+}
+
+fn default_duplicate_window() -> i64 {
+    3
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImporterConfig {
+    /// the counter account of entries which match no rule
+    #[serde(default = "default_account")]
+    pub default_account: String,
+    /// the flag of imported transactions, `!` by default so that they can be reviewed
+    #[serde(default = "default_flag")]
+    pub flag: String,
+    /// the max days between the imported transaction and the probable duplicate one in ledger
+    #[serde(default = "default_duplicate_window")]
+    pub duplicate_window: i64,
+    /// csv mappings keyed by name, e.g. the name of bank
+    #[serde(default)]
+    pub csv: HashMap<String, CsvMapping>,
+    /// ofx mappings keyed by name
+    #[serde(default)]
+    pub ofx: HashMap<String, StatementMapping>,
+    /// camt.053 mappings keyed by name
+    #[serde(default)]
+    pub camt: HashMap<String, StatementMapping>,
+    /// mt940 mappings keyed by name
+    #[serde(default)]
+    pub mt940: HashMap<String, StatementMapping>,
+    /// classification rules, the first matched rule is applied
+    #[serde(default)]
+    pub rules: Vec<ClassificationRule>,
+}
+
+/// the mapping from bank statement(OFX, camt.053 and MT940) to zhang account
+#[derive(Debug, Clone, Deserialize)]
+pub struct StatementMapping {
+    /// the statement account
+    pub account: String,
+    /// the account id of statement, e.g. the `ACCTID` of OFX, the IBAN of camt.053 and the `:25:` field of MT940.
+    /// used to find the mapping of statement when the file contains multiple statements
+    pub acctid: Option<String>,
+    /// the commodity of amounts, default to the currency of statement
+    pub commodity: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClassificationRule {
+    /// regex of payee, the narration is used if the payee is absent
+    pub payee: Option<String>,
+    /// regex of narration
+    pub narration: Option<String>,
+    /// the counter account
+    pub account: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// the entry of statement, the amount is the change of statement account
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatementEntry {
+    pub date: NaiveDate,
+    pub payee: Option<String>,
+    pub narration: Option<String>,
+    pub amount: Amount,
+    /// the unique id of entry given by bank
+    pub id: Option<String>,
+}
+
+/// the balance of statement account at the end of date
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatementBalance {
+    pub date: NaiveDate,
+    pub amount: Amount,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    pub account: Account,
+    pub entries: Vec<StatementEntry>,
+    pub balances: Vec<StatementBalance>,
+}
+
+/// the statement read from bank statement file, whose account is not mapped to zhang account yet
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BankStatement {
+    pub acctid: Option<String>,
+    pub currency: Option<String>,
+    pub entries: Vec<BankEntry>,
+    /// the closing balances
+    pub balances: Vec<BankBalance>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BankEntry {
+    pub id: Option<String>,
+    pub date: Option<NaiveDate>,
+    pub amount: Option<String>,
+    pub payee: Option<String>,
+    pub narration: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BankBalance {
+    pub date: NaiveDate,
+    pub amount: String,
+}
+
+impl StatementMapping {
+    pub fn statement(&self, statement: BankStatement) -> ZhangResult<Statement> {
+        let account = parse_account(&self.account)?;
+        let commodity = self
+            .commodity
+            .clone()
+            .or(statement.currency)
+            .ok_or_else(|| ZhangError::ImportError("the commodity of statement is unknown".to_owned()))?;
+        let number = |amount: &str| parse_number(amount).ok_or_else(|| ZhangError::ImportError(format!("{} is not a valid number", amount)));
+        let entries = statement
+            .entries
+            .into_iter()
+            .map(|entry| {
+                let date = entry
+                    .date
+                    .ok_or_else(|| ZhangError::ImportError(format!("the date of entry {} is invalid", entry.id.clone().unwrap_or_default())))?;
+                Ok(StatementEntry {
+                    date,
+                    payee: entry.payee,
+                    narration: entry.narration,
+                    amount: Amount::new(number(entry.amount.as_deref().unwrap_or_default())?, commodity.clone()),
+                    id: entry.id,
+                })
+            })
+            .collect::<ZhangResult<Vec<_>>>()?;
+        let balances = statement
+            .balances
+            .into_iter()
+            .map(|balance| {
+                Ok(StatementBalance {
+                    date: balance.date,
+                    amount: Amount::new(number(&balance.amount)?, commodity.clone()),
+                })
+            })
+            .collect::<ZhangResult<Vec<_>>>()?;
+        Ok(Statement { account, entries, balances })
+    }
+}
+
+struct CompiledRule {
+    payee: Option<Regex>,
+    narration: Option<Regex>,
+    account: Account,
+    tags: Vec<String>,
+}
+
+impl CompiledRule {
+    fn matches(&self, entry: &StatementEntry) -> bool {
+        let payee = entry.payee.as_deref().or(entry.narration.as_deref()).unwrap_or_default();
+        let narration = entry.narration.as_deref().unwrap_or_default();
+        self.payee.as_ref().map(|it| it.is_match(payee)).unwrap_or(true) && self.narration.as_ref().map(|it| it.is_match(narration)).unwrap_or(true)
+    }
+}
+
+pub(crate) fn parse_account(account: &str) -> ZhangResult<Account> {
+    Account::from_str(account).map_err(|_| ZhangError::ImportError(format!("account {} is invalid", account)))
+}
+
+/// parse the number of statement, thousands separators and spaces are ignored, and `(10.00)` means `-10.00`
+pub(crate) fn parse_number(content: &str) -> Option<BigDecimal> {
+    let content = content.trim();
+    let (negative, content) = match content.strip_prefix('(').and_then(|it| it.strip_suffix(')')) {
+        Some(inner) => (true, inner),
+        None => (false, content),
+    };
+    let number = content.chars().filter(|it| !matches!(it, ',' | ' ' | '_')).collect::<String>();
+    let number = BigDecimal::from_str(number.trim_start_matches('+')).ok()?;
+    Some(if negative { -number } else { number })
+}
+
+pub struct Importer {
+    config: ImporterConfig,
+    default_account: Account,
+    rules: Vec<CompiledRule>,
+}
+
+impl Importer {
+    pub fn new(config: ImporterConfig) -> ZhangResult<Self> {
+        let regex = |pattern: &Option<String>| {
+            pattern
+                .as_ref()
+                .map(|it| Regex::new(it).map_err(|e| ZhangError::ImportError(format!("invalid rule pattern {}: {}", it, e))))
+                .transpose()
+        };
+        let rules = config
+            .rules
+            .iter()
+            .map(|rule| {
+                Ok(CompiledRule {
+                    payee: regex(&rule.payee)?,
+                    narration: regex(&rule.narration)?,
+                    account: parse_account(&rule.account)?,
+                    tags: rule.tags.clone(),
+                })
+            })
+            .collect::<ZhangResult<Vec<_>>>()?;
+        Ok(Importer {
+            default_account: parse_account(&config.default_account)?,
+            config,
+            rules,
+        })
+    }
+
+    pub fn from_toml(content: &str) -> ZhangResult<Self> {
+        let config: ImporterConfig = toml::from_str(content).map_err(|e| ZhangError::ImportError(e.to_string()))?;
+        Importer::new(config)
+    }
+
+    /// parse the csv statement by the mapping of given name, the mapping can be omitted if only one mapping is configured
+    pub fn csv_statement(&self, mapping: Option<&str>, content: &str) -> ZhangResult<Statement> {
+        find_mapping(&self.config.csv, mapping, "csv")?.parse(content)
+    }
+
+    /// parse the statements of OFX/QFX file, see [Importer::bank_statements]
+    pub fn ofx_statements(&self, mapping: Option<&str>, content: &str) -> ZhangResult<Vec<Statement>> {
+        Importer::bank_statements(&self.config.ofx, mapping, "ofx", parse_ofx(content)?)
+    }
+
+    /// parse the statements of camt.053 file, see [Importer::bank_statements]
+    pub fn camt_statements(&self, mapping: Option<&str>, content: &str) -> ZhangResult<Vec<Statement>> {
+        Importer::bank_statements(&self.config.camt, mapping, "camt", parse_camt(content)?)
+    }
+
+    /// parse the statements of MT940 file, see [Importer::bank_statements]
+    pub fn mt940_statements(&self, mapping: Option<&str>, content: &str) -> ZhangResult<Vec<Statement>> {
+        Importer::bank_statements(&self.config.mt940, mapping, "mt940", parse_mt940(content)?)
+    }
+
+    /// map the bank statements to zhang accounts, the mapping of each statement is the given one, or the one with the same `acctid`
+    fn bank_statements(
+        mappings: &HashMap<String, StatementMapping>, mapping: Option<&str>, format: &str, statements: Vec<BankStatement>,
+    ) -> ZhangResult<Vec<Statement>> {
+        statements
+            .into_iter()
+            .map(|statement| {
+                let by_acctid = statement
+                    .acctid
+                    .as_ref()
+                    .and_then(|acctid| mappings.values().find(|it| it.acctid.as_ref() == Some(acctid)));
+                let statement_mapping = match (mapping, by_acctid) {
+                    (None, Some(statement_mapping)) => statement_mapping,
+                    _ => find_mapping(mappings, mapping, format)?,
+                };
+                statement_mapping.statement(statement)
+            })
+            .collect()
+    }
+
+    /// convert statement into directives, which are the transactions of entries followed by balance assertions of closing balances
+    pub fn directives(&self, statement: Statement) -> Vec<Directive> {
+        let balance_checks = Importer::balance_checks(&statement);
+        self.transactions(statement)
+            .into_iter()
+            .map(Directive::Transaction)
+            .chain(balance_checks.into_iter().map(Directive::BalanceCheck))
+            .collect_vec()
+    }
+
+    /// the balance assertions of closing balances, which are checked at the beginning of next day
+    pub fn balance_checks(statement: &Statement) -> Vec<BalanceCheck> {
+        statement
+            .balances
+            .iter()
+            .map(|balance| BalanceCheck {
+                date: Date::Date(balance.date + Duration::days(1)),
+                account: statement.account.clone(),
+                amount: balance.amount.clone(),
+                meta: Default::default(),
+            })
+            .collect_vec()
+    }
+
+    /// convert statement entries into transactions, the counter account and tags are decided by the first matched rule
+    pub fn transactions(&self, statement: Statement) -> Vec<Transaction> {
+        let flag = Flag::from_str(&self.config.flag).unwrap_or(Flag::Warning);
+        statement
+            .entries
+            .into_iter()
+            .map(|entry| {
+                let rule = self.rules.iter().find(|rule| rule.matches(&entry));
+                let counter_account = rule.map(|it| it.account.clone()).unwrap_or_else(|| self.default_account.clone());
+                let tags = rule.map(|it| it.tags.iter().cloned().collect::<IndexSet<_>>()).unwrap_or_default();
+                let mut meta = Meta::default();
+                if let Some(id) = entry.id {
+                    meta.insert(TXN_IMPORT_ID.to_owned(), ZhangString::quote(id));
+                }
+                Transaction {
+                    date: Date::Date(entry.date),
+                    auxiliary_date: None,
+                    flag: Some(flag.clone()),
+                    payee: entry.payee.map(ZhangString::quote),
+                    narration: entry.narration.map(ZhangString::quote),
+                    tags,
+                    links: Default::default(),
+                    postings: vec![
+                        Posting {
+                            flag: None,
+                            posting_type: PostingType::Real,
+                            account: statement.account.clone(),
+                            units: Some(entry.amount),
+                            cost: None,
+                            cost_date: None,
+                            cost_label: None,
+                            price: None,
+                            comment: None,
+                            meta: Default::default(),
+                        },
+                        Posting {
+                            flag: None,
+                            posting_type: PostingType::Real,
+                            account: counter_account,
+                            units: None,
+                            cost: None,
+                            cost_date: None,
+                            cost_label: None,
+                            price: None,
+                            comment: None,
+                            meta: Default::default(),
+                        },
+                    ],
+                    meta,
+                }
+            })
+            .collect_vec()
+    }
+
+    /// remove the transactions whose `import-id` exists in ledger or appears more than once,
+    /// and the balance assertions whose account, date and commodity have been asserted
+    pub fn exclude_imported(ledger: &Ledger, directives: Vec<Directive>) -> Vec<Directive> {
+        let operations = ledger.operations();
+        let store = operations.read();
+        let mut imported = store
+            .metas
+            .iter()
+            .filter(|it| it.meta_type.eq(MetaType::TransactionMeta.as_ref()) && it.key.eq(TXN_IMPORT_ID))
+            .map(|it| it.value.clone())
+            .collect::<HashSet<_>>();
+        let balance_key = |date: &Date, account: &Account, amount: &Amount| (date.naive_date(), account.name().to_owned(), amount.currency.clone());
+        let mut checked = ledger
+            .directives
+            .iter()
+            .filter_map(|it| match &it.data {
+                Directive::BalanceCheck(check) => Some(balance_key(&check.date, &check.account, &check.amount)),
+                Directive::BalancePad(pad) => Some(balance_key(&pad.date, &pad.account, &pad.amount)),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        directives
+            .into_iter()
+            .filter(|directive| match directive {
+                Directive::Transaction(trx) => match trx.meta.get_one(TXN_IMPORT_ID) {
+                    Some(id) => imported.insert(id.as_str().to_owned()),
+                    None => true,
+                },
+                Directive::BalanceCheck(check) => checked.insert(balance_key(&check.date, &check.account, &check.amount)),
+                _ => true,
+            })
+            .collect_vec()
+    }
+
+    /// split out the transactions which are the probable duplicates of transactions in ledger, e.g. the ones entered by hand.
+    /// return the remaining directives and the duplicate transactions
+    pub fn exclude_duplicates(&self, ledger: &Ledger, directives: Vec<Directive>) -> (Vec<Directive>, Vec<Transaction>) {
+        let fingerprints = ledger_fingerprints(ledger);
+        let mut duplicates = vec![];
+        let directives = directives
+            .into_iter()
+            .filter_map(|directive| match directive {
+                Directive::Transaction(trx) => {
+                    let fingerprint = TransactionFingerprint::of_transaction(ledger, &trx);
+                    if fingerprints.iter().any(|it| fingerprint.is_duplicate_of(it, self.config.duplicate_window)) {
+                        duplicates.push(trx);
+                        None
+                    } else {
+                        Some(Directive::Transaction(trx))
+                    }
+                }
+                directive => Some(directive),
+            })
+            .collect_vec();
+        (directives, duplicates)
+    }
+
+    /// append the directives into `target` file, relative path is resolved from the ledger entry
+    pub async fn append(ledger: &Ledger, target: &Path, directives: Vec<Directive>) -> ZhangResult<()> {
+        append_directives(ledger, target, directives).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+    use zhang_ast::{Directive, ZhangString};
+
+    use crate::importer::Importer;
+    use crate::test::load_from_text;
+
+    #[test]
+    fn should_classify_entries_by_first_matched_rule() {
+        let importer = Importer::from_toml(indoc! {r#"
+            [csv.bank]
+            account = "Assets:Bank"
+            commodity = "CNY"
+            date = "Date"
+            payee = "Payee"
+            narration = "Memo"
+            amount = "Amount"
+
+            [[rules]]
+            payee = "(?i)starbucks"
+            account = "Expenses:Coffee"
+            tags = ["coffee"]
+
+            [[rules]]
+            narration = "salary"
+            account = "Income:Salary"
+        "#})
+        .unwrap();
+        let statement = importer
+            .csv_statement(
+                None,
+                indoc! {r#"
+                    Date,Payee,Memo,Amount
+                    2024-01-02,STARBUCKS #12,latte,-35.00
+                    2024-01-05,ACME,monthly salary,"10,000.00"
+                    2024-01-06,KFC,,-50
+                "#},
+            )
+            .unwrap();
+        let transactions = importer.transactions(statement);
+        let counter_accounts = transactions.iter().map(|it| it.postings[1].account.name().to_owned()).collect::<Vec<_>>();
+        assert_eq!(vec!["Expenses:Coffee", "Income:Salary", "Expenses:Uncategorized"], counter_accounts);
+        assert!(transactions[0].tags.contains("coffee"));
+        assert_eq!(None, transactions[2].narration);
+        assert_eq!("10000.00", transactions[1].postings[0].units.as_ref().unwrap().number.to_string());
+    }
+
+    #[test]
+    fn should_exclude_imported_entries_by_fitid() {
+        let ledger = load_from_text(indoc! {r#"
+            1970-01-01 open Assets:Bank
+            1970-01-01 open Expenses:Uncategorized
+
+            2024-01-05 ! "AT&T"
+              import-id: "2024010501"
+              Assets:Bank -42.10 USD
+              Expenses:Uncategorized
+        "#});
+        let importer = Importer::from_toml(indoc! {r#"
+            [ofx.checking]
+            account = "Assets:Bank"
+            acctid = "1234567890"
+        "#})
+        .unwrap();
+        let statements = importer
+            .ofx_statements(
+                None,
+                indoc! {r#"
+                    <OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS><CURDEF>USD
+                    <BANKACCTFROM><ACCTID>1234567890</BANKACCTFROM>
+                    <BANKTRANLIST>
+                    <STMTTRN><DTPOSTED>20240105<TRNAMT>-42.10<FITID>2024010501<NAME>AT&amp;T</STMTTRN>
+                    <STMTTRN><DTPOSTED>20240110<TRNAMT>1500<FITID>2024011001<NAME>ACME</STMTTRN>
+                    <STMTTRN><DTPOSTED>20240110<TRNAMT>1500<FITID>2024011001<NAME>ACME</STMTTRN>
+                    </BANKTRANLIST></STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>
+                "#},
+            )
+            .unwrap();
+        let directives = statements.into_iter().flat_map(|it| importer.directives(it)).collect::<Vec<_>>();
+        assert_eq!(3, directives.len());
+        let directives = Importer::exclude_imported(&ledger, directives);
+        assert_eq!(1, directives.len());
+        let Directive::Transaction(trx) = &directives[0] else { unreachable!() };
+        assert_eq!(Some(&ZhangString::quote("2024011001")), trx.meta.get_one("import-id"));
+    }
+
+    #[test]
+    fn should_exclude_probable_duplicates_of_ledger_transactions() {
+        let ledger = load_from_text(indoc! {r#"
+            1970-01-01 open Assets:Bank
+            1970-01-01 open Expenses:Coffee
+
+            2024-01-02 "Starbucks" "latte"
+              Assets:Bank -35.00 CNY
+              Expenses:Coffee
+
+            2024-01-03 "Starbucks" "latte"
+              dedup-ignore: "true"
+              Assets:Bank -30.00 CNY
+              Expenses:Coffee
+        "#});
+        let importer = Importer::from_toml(indoc! {r#"
+            duplicate_window = 2
+
+            [csv.bank]
+            account = "Assets:Bank"
+            commodity = "CNY"
+            date = "Date"
+            payee = "Payee"
+            amount = "Amount"
+        "#})
+        .unwrap();
+        let statement = importer
+            .csv_statement(
+                None,
+                indoc! {r#"
+                    Date,Payee,Amount
+                    2024-01-03,STARBUCKS STORE 12,-35.00
+                    2024-01-03,STARBUCKS STORE 12,-30.00
+                    2024-01-03,KFC,-35.00
+                    2024-01-08,STARBUCKS STORE 12,-35.00
+                "#},
+            )
+            .unwrap();
+        let (directives, duplicates) = importer.exclude_duplicates(&ledger, importer.directives(statement));
+        assert_eq!(3, directives.len());
+        assert_eq!(1, duplicates.len());
+        assert_eq!("STARBUCKS STORE 12", duplicates[0].payee.as_ref().unwrap().as_str());
+        assert_eq!("2024-01-03", duplicates[0].date.naive_date().to_string());
+    }
+
+    #[test]
+    fn should_reject_invalid_rule() {
+        assert!(Importer::from_toml(indoc! {r#"
+            [[rules]]
+            payee = "(unclosed"
+            account = "Expenses:Coffee"
+        "#})
+        .is_err());
+        assert!(Importer::from_toml(indoc! {r#"
+            [[rules]]
+            payee = "KFC"
+            account = "Food"
+        "#})
+        .is_err());
+    }
+}
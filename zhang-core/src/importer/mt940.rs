@@ -0,0 +1,210 @@
+//! MT940 (SWIFT customer statement) statements.
+//! the `:86:` information of entry is read as the structured format of German banks if it contains `?` subfields,
+//! where `?20` to `?29` are the purpose and `?32`, `?33` are the name of counterparty, otherwise it is the narration.
+
+use chrono::{Datelike, NaiveDate};
+
+use crate::importer::{BankBalance, BankEntry, BankStatement};
+use crate::{ZhangError, ZhangResult};
+
+/// split the message into `(tag, content)` fields, the continuation lines are joined with `\n`
+fn fields(content: &str) -> Vec<(&str, String)> {
+    let mut ret: Vec<(&str, String)> = vec![];
+    for line in content.lines() {
+        let line = line.trim_end();
+        let tag = line
+            .strip_prefix(':')
+            .and_then(|it| it.split_once(':'))
+            .filter(|(tag, _)| (2..=3).contains(&tag.len()) && tag.chars().all(|it| it.is_ascii_alphanumeric()));
+        match tag {
+            Some((tag, value)) => ret.push((tag, value.to_owned())),
+            None => {
+                // the end of message, or the header and trailer blocks of SWIFT
+                if line.starts_with('-') || line.starts_with('{') {
+                    continue;
+                }
+                if let Some((_, value)) = ret.last_mut() {
+                    value.push('\n');
+                    value.push_str(line);
+                }
+            }
+        }
+    }
+    ret
+}
+
+/// split the amount which uses `,` as decimal separator, e.g. `1000,00NMSC...`
+fn split_amount(content: &str) -> Option<(String, &str)> {
+    let end = content.find(|it: char| !it.is_ascii_digit() && it != ',').unwrap_or(content.len());
+    let amount = content[..end].replace(',', ".");
+    (!amount.is_empty()).then(|| (amount.trim_end_matches('.').to_owned(), &content[end..]))
+}
+
+/// the balance in format `[C|D]YYMMDD<currency><amount>`
+fn parse_balance(content: &str) -> Option<(BankBalance, String)> {
+    let debit = match content.get(..1)? {
+        "C" => false,
+        "D" => true,
+        _ => return None,
+    };
+    let date = NaiveDate::parse_from_str(content.get(1..7)?, "%y%m%d").ok()?;
+    let currency = content.get(7..10)?.to_owned();
+    let (amount, _) = split_amount(content.get(10..)?)?;
+    let amount = if debit { format!("-{}", amount) } else { amount };
+    Some((BankBalance { date, amount }, currency))
+}
+
+/// the entry in format `YYMMDD[MMDD][R]C|D[funds code]<amount><type><customer reference>[//<bank reference>]`
+fn parse_entry(content: &str) -> Option<BankEntry> {
+    let line = content.lines().next()?;
+    let value_date = NaiveDate::parse_from_str(line.get(..6)?, "%y%m%d").ok()?;
+    let mut rest = line.get(6..)?;
+    // the booking date without year, which may cross the year of value date
+    let mut date = value_date;
+    if let Some(booking) = rest.get(..4).filter(|it| it.chars().all(|c| c.is_ascii_digit())) {
+        let month: u32 = booking[..2].parse().ok()?;
+        let day: u32 = booking[2..].parse().ok()?;
+        let year = match (value_date.month(), month) {
+            (12, 1) => value_date.year() + 1,
+            (1, 12) => value_date.year() - 1,
+            _ => value_date.year(),
+        };
+        date = NaiveDate::from_ymd_opt(year, month, day)?;
+        rest = &rest[4..];
+    }
+    let (debit, mark_length) = if rest.starts_with("RC") {
+        (true, 2)
+    } else if rest.starts_with("RD") {
+        (false, 2)
+    } else if rest.starts_with('C') {
+        (false, 1)
+    } else if rest.starts_with('D') {
+        (true, 1)
+    } else {
+        return None;
+    };
+    rest = &rest[mark_length..];
+    if rest.starts_with(|it: char| it.is_ascii_alphabetic()) {
+        rest = &rest[1..];
+    }
+    let (amount, rest) = split_amount(rest)?;
+    // the transaction type is 4 characters, e.g. `NMSC`
+    let references = rest.get(4..).unwrap_or_default();
+    let (customer_reference, bank_reference) = references.split_once("//").unwrap_or((references, ""));
+    let id = [bank_reference, customer_reference]
+        .into_iter()
+        .map(|it| it.trim())
+        .find(|it| !it.is_empty() && *it != "NONREF")
+        .map(|it| it.to_owned());
+    Some(BankEntry {
+        id,
+        date: Some(date),
+        amount: Some(if debit { format!("-{}", amount) } else { amount }),
+        payee: None,
+        narration: None,
+    })
+}
+
+/// read the payee and narration from `:86:` information
+fn parse_information(content: &str) -> (Option<String>, Option<String>) {
+    let non_empty = |it: String| Some(it.trim().to_owned()).filter(|it| !it.is_empty());
+    if !content.contains('?') {
+        return (None, non_empty(content.lines().map(|it| it.trim()).collect::<Vec<_>>().join(" ")));
+    }
+    let content = content.replace('\n', "");
+    let mut purpose = vec![];
+    let mut name = vec![];
+    for subfield in content.split('?').skip(1) {
+        let (Some(code), Some(value)) = (subfield.get(..2), subfield.get(2..)) else {
+            continue;
+        };
+        match code {
+            "20" | "21" | "22" | "23" | "24" | "25" | "26" | "27" | "28" | "29" | "60" | "61" | "62" | "63" => purpose.push(value),
+            "32" | "33" => name.push(value),
+            _ => {}
+        }
+    }
+    (non_empty(name.join("")), non_empty(purpose.join("")))
+}
+
+pub fn parse_mt940(content: &str) -> ZhangResult<Vec<BankStatement>> {
+    let mut statements = vec![];
+    let mut statement: Option<BankStatement> = None;
+    for (tag, value) in fields(content) {
+        match tag {
+            "20" => statements.extend(statement.replace(BankStatement::default())),
+            "25" => {
+                if let Some(statement) = statement.as_mut() {
+                    statement.acctid = Some(value.trim().to_owned());
+                }
+            }
+            "60F" | "60M" | "62F" | "62M" => {
+                let (balance, currency) = parse_balance(value.trim()).ok_or_else(|| ZhangError::ImportError(format!("invalid balance :{}:{}", tag, value)))?;
+                if let Some(statement) = statement.as_mut() {
+                    statement.currency.get_or_insert(currency);
+                    if tag.starts_with("62") {
+                        statement.balances.push(balance);
+                    }
+                }
+            }
+            "61" => {
+                let entry = parse_entry(&value).ok_or_else(|| ZhangError::ImportError(format!("invalid entry :61:{}", value)))?;
+                if let Some(statement) = statement.as_mut() {
+                    statement.entries.push(entry);
+                }
+            }
+            "86" => {
+                if let Some(entry) = statement.as_mut().and_then(|it| it.entries.last_mut()) {
+                    (entry.payee, entry.narration) = parse_information(&value);
+                }
+            }
+            _ => {}
+        }
+    }
+    statements.extend(statement);
+    if statements.is_empty() {
+        return Err(ZhangError::ImportError("no statement is found in MT940 file".to_owned()));
+    }
+    Ok(statements)
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use crate::importer::mt940::parse_mt940;
+
+    #[test]
+    fn should_parse_entries_and_closing_balance() {
+        let statements = parse_mt940(indoc! {r#"
+            :20:STARTUMSE
+            :25:10020030/1234567
+            :28C:00001/001
+            :60F:C231229EUR1000,00
+            :61:2312291230D3,5NMSCNONREF//BANK-1
+            :86:106?00KARTENZAHLUNG?20Breakfast at the ?21bakery?32B&B Bakery
+            :61:2401020103C2000,00NTRFSALARY-2401
+            :86:Salary January
+            ACME GmbH
+            :62F:C240103EUR2996,50
+            -
+        "#})
+        .unwrap();
+        assert_eq!(1, statements.len());
+        let statement = &statements[0];
+        assert_eq!(Some("10020030/1234567".to_owned()), statement.acctid);
+        assert_eq!(Some("EUR".to_owned()), statement.currency);
+        assert_eq!(2, statement.entries.len());
+        assert_eq!("2023-12-30", statement.entries[0].date.unwrap().to_string());
+        assert_eq!(Some("-3.5".to_owned()), statement.entries[0].amount);
+        assert_eq!(Some("BANK-1".to_owned()), statement.entries[0].id);
+        assert_eq!(Some("B&B Bakery".to_owned()), statement.entries[0].payee);
+        assert_eq!(Some("Breakfast at the bakery".to_owned()), statement.entries[0].narration);
+        assert_eq!(Some("SALARY-2401".to_owned()), statement.entries[1].id);
+        assert_eq!(Some("2000.00".to_owned()), statement.entries[1].amount);
+        assert_eq!(Some("Salary January ACME GmbH".to_owned()), statement.entries[1].narration);
+        assert_eq!(1, statement.balances.len());
+        assert_eq!("2024-01-03", statement.balances[0].date.to_string());
+        assert_eq!("2996.50", statement.balances[0].amount);
+    }
+}
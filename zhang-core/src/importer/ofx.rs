@@ -0,0 +1,132 @@
+//! OFX/QFX statements, both the SGML(OFX 1.x) and XML(OFX 2.x) syntax are supported.
+//! leaf elements of SGML may omit the end tag, so the statement is read as a flat sequence of elements
+//! instead of a tree.
+
+use chrono::NaiveDate;
+
+use crate::importer::{BankEntry, BankStatement};
+use crate::{ZhangError, ZhangResult};
+
+enum Element<'a> {
+    Start(&'a str, Option<String>),
+    End(&'a str),
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+fn elements(content: &str) -> Vec<Element<'_>> {
+    let body = content.find("<OFX>").map(|it| &content[it..]).unwrap_or(content);
+    let mut ret = vec![];
+    for part in body.split('<').skip(1) {
+        let Some((tag, text)) = part.split_once('>') else {
+            continue;
+        };
+        let tag = tag.trim();
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        match tag.strip_prefix('/') {
+            Some(name) => ret.push(Element::End(name.trim())),
+            None => {
+                let text = text.trim();
+                ret.push(Element::Start(tag, (!text.is_empty()).then(|| unescape(text))));
+            }
+        }
+    }
+    ret
+}
+
+/// the date of OFX is in format `YYYYMMDD[HHMMSS[.XXX]][[gmt offset:tz name]]`, only the date part is used
+fn parse_date(content: &str) -> Option<NaiveDate> {
+    content.get(..8).and_then(|it| NaiveDate::parse_from_str(it, "%Y%m%d").ok())
+}
+
+pub fn parse_ofx(content: &str) -> ZhangResult<Vec<BankStatement>> {
+    let mut statements = vec![];
+    let mut statement: Option<BankStatement> = None;
+    let mut entry: Option<BankEntry> = None;
+    for element in elements(content) {
+        match element {
+            Element::Start("STMTRS" | "CCSTMTRS", _) => statement = Some(BankStatement::default()),
+            Element::End("STMTRS" | "CCSTMTRS") => statements.extend(statement.take()),
+            Element::Start("STMTTRN", _) => entry = Some(BankEntry::default()),
+            Element::End("STMTTRN") => {
+                if let (Some(statement), Some(entry)) = (statement.as_mut(), entry.take()) {
+                    statement.entries.push(entry);
+                }
+            }
+            Element::Start(tag, Some(text)) => match (entry.as_mut(), statement.as_mut()) {
+                (Some(entry), _) => match tag {
+                    "FITID" => entry.id = Some(text),
+                    "DTPOSTED" => entry.date = parse_date(&text),
+                    "TRNAMT" => entry.amount = Some(text),
+                    "NAME" => entry.payee = Some(text),
+                    "MEMO" => entry.narration = Some(text),
+                    _ => {}
+                },
+                (None, Some(statement)) => match tag {
+                    "CURDEF" => statement.currency = Some(text),
+                    "ACCTID" => statement.acctid = Some(text),
+                    _ => {}
+                },
+                (None, None) => {}
+            },
+            _ => {}
+        }
+    }
+    if statements.is_empty() {
+        return Err(ZhangError::ImportError("no statement is found in OFX file".to_owned()));
+    }
+    Ok(statements)
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use crate::importer::ofx::parse_ofx;
+
+    #[test]
+    fn should_parse_sgml_statement() {
+        let statements = parse_ofx(indoc! {r#"
+            OFXHEADER:100
+            DATA:OFXSGML
+            VERSION:102
+
+            <OFX>
+            <BANKMSGSRSV1><STMTTRNRS><STMTRS>
+            <CURDEF>USD
+            <BANKACCTFROM><BANKID>121000248<ACCTID>1234567890<ACCTTYPE>CHECKING</BANKACCTFROM>
+            <BANKTRANLIST>
+            <STMTTRN><TRNTYPE>DEBIT<DTPOSTED>20240105120000.000[-5:EST]<TRNAMT>-42.10<FITID>2024010501<NAME>AT&amp;T<MEMO>Phone bill</STMTTRN>
+            <STMTTRN>
+            <TRNTYPE>CREDIT
+            <DTPOSTED>20240110
+            <TRNAMT>1500.00
+            <FITID>2024011001
+            <NAME>ACME PAYROLL
+            </STMTTRN>
+            </BANKTRANLIST>
+            </STMTRS></STMTTRNRS></BANKMSGSRSV1>
+            </OFX>
+        "#})
+        .unwrap();
+        assert_eq!(1, statements.len());
+        let statement = &statements[0];
+        assert_eq!(Some("1234567890".to_owned()), statement.acctid);
+        assert_eq!(Some("USD".to_owned()), statement.currency);
+        assert_eq!(2, statement.entries.len());
+        assert_eq!(Some("AT&T".to_owned()), statement.entries[0].payee);
+        assert_eq!(Some("Phone bill".to_owned()), statement.entries[0].narration);
+        assert_eq!("2024-01-05", statement.entries[0].date.unwrap().to_string());
+        assert_eq!(Some("2024011001".to_owned()), statement.entries[1].id);
+        assert_eq!(Some("1500.00".to_owned()), statement.entries[1].amount);
+    }
+}
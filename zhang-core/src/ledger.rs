@@ -1,20 +1,32 @@
 use std::borrow::Cow;
 use std::cmp::Ordering;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::atomic::AtomicI32;
 use std::sync::{Arc, RwLock};
 
+use chrono::NaiveDate;
 use itertools::Itertools;
 use log::{error, info};
-use zhang_ast::{Directive, DirectiveType, Options, Plugin, SpanInfo, Spanned};
+use serde::Serialize;
+use uuid::Uuid;
+use zhang_ast::amount::Amount;
+use zhang_ast::error::{ErrorKind, ErrorSeverity};
+use zhang_ast::{Directive, DirectiveType, Options, Plugin, Rename, SpanInfo, Spanned, ZhangString};
 
+use crate::constants::{POSTING_RECONCILE, POSTING_STATEMENT_DATE};
 use crate::data_source::DataSource;
 use crate::domains::Operations;
+use crate::encryption::Encryption;
 use crate::error::IoErrorIntoZhangError;
-use crate::options::{BuiltinOption, InMemoryOptions};
-use crate::process::{DirectivePreProcess, DirectiveProcess};
+use crate::options::{BuiltinOption, DirectiveOrder, InMemoryOptions};
+use crate::process::document::ReadDocument;
+use crate::process::{self, DirectivePreProcess, DirectiveProcess};
 use crate::store::Store;
+use crate::store_snapshot::StoreSnapshot;
+use crate::utils::hashmap::HashMapOfExt;
+use crate::utils::id::FromSpan;
+use crate::utils::merge::merge_sorted_runs;
 use crate::{ZhangError, ZhangResult};
 
 pub struct Ledger {
@@ -33,12 +45,31 @@ pub struct Ledger {
 
     pub(crate) trx_counter: AtomicI32,
 
+    /// the documents read ahead of processing by async loading, see [process::document::prefetch_documents]
+    pub(crate) prefetched_documents: HashMap<String, Option<ReadDocument>>,
+
     #[cfg(feature = "plugin_runtime")]
     pub plugins: crate::plugin::store::PluginStore,
 }
 
+/// the changes between two versions of ledger, so that only the views affected are refreshed after reloading
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct LedgerChanges {
+    /// the dates of dated directives which are added, removed or edited, `None` if there is no such one
+    pub date_range: Option<DateRange>,
+    /// whether the directives without date, e.g. options, plugins and includes, are changed, which may affect every view
+    pub metas_changed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DateRange {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
 pub struct LedgerProcessContext {
-    pub directives: Vec<Spanned<Directive>>,
+    /// the directives of each loaded file, see [LoadResult::runs]
+    pub runs: Vec<Vec<Spanned<Directive>>>,
     pub entry: (PathBuf, String),
     pub visited_files: Vec<PathBuf>,
     pub data_source: Arc<dyn DataSource>,
@@ -46,22 +77,30 @@ pub struct LedgerProcessContext {
 
 struct SplitDirectives {
     meta_directives: Vec<Spanned<Directive>>,
-    dated_directives: Vec<Spanned<Directive>>,
+    /// the dated directives of each file sorted by datetime, which are merged one by one when they are processed
+    dated_runs: Vec<Vec<Spanned<Directive>>>,
+    order: DirectiveOrder,
 
     options_directives: Vec<(Options, SpanInfo)>,
     plugin_directives: Vec<(Plugin, SpanInfo)>,
-    other_directives: Vec<Spanned<Directive>>,
+    rename_directives: Vec<(Rename, SpanInfo)>,
 }
 
 impl SplitDirectives {
-    fn new(directives: Vec<Spanned<Directive>>) -> Self {
+    fn new(runs: Vec<Vec<Spanned<Directive>>>) -> Self {
         // split directive into two groups.
         // first is meta which is no date
-        // second is dated directives
-        let (meta_directives, dated_directive): (Vec<Spanned<Directive>>, Vec<Spanned<Directive>>) =
-            directives.into_iter().partition(|it| it.datetime().is_none());
+        // second is dated directives, which are kept in the run of their file
+        let mut meta_directives = vec![];
+        let mut dated_runs = Vec::with_capacity(runs.len());
+        for run in runs {
+            let (metas, dated): (Vec<Spanned<Directive>>, Vec<Spanned<Directive>>) = run.into_iter().partition(|it| it.datetime().is_none());
+            meta_directives.extend(metas);
+            dated_runs.push(dated);
+        }
 
-        let dated_directives = Ledger::sort_directives_datetime(dated_directive);
+        let order = DirectiveOrder::declared_by(&meta_directives);
+        let dated_runs = dated_runs.into_iter().map(|run| Ledger::sort_directives_datetime(run, order)).collect_vec();
 
         // find all options which are not defined by users
         let options_key: HashSet<Cow<str>> = meta_directives
@@ -78,29 +117,70 @@ impl SplitDirectives {
             .chain(meta_directives)
             .rev()
             .collect_vec();
-        let grouped_directives = merged_metas.iter().rev().chain(dated_directives.iter()).cloned().collect_vec();
 
         let mut options_directives = vec![];
         let mut plugin_directives = vec![];
-        let mut other_directives = Vec::with_capacity(grouped_directives.len());
+        let mut rename_directives = vec![];
 
         // extract plugins first before handling other directives
-        for directive in grouped_directives.into_iter() {
-            match directive.data {
-                Directive::Plugin(plugin) => plugin_directives.push((plugin, directive.span)),
-                Directive::Option(option) => options_directives.push((option, directive.span)),
-                _ => other_directives.push(directive),
+        for directive in merged_metas.iter().rev().chain(dated_runs.iter().flatten()) {
+            match &directive.data {
+                Directive::Plugin(plugin) => plugin_directives.push((plugin.clone(), directive.span.clone())),
+                Directive::Option(option) => options_directives.push((option.clone(), directive.span.clone())),
+                Directive::Rename(rename) => rename_directives.push((rename.clone(), directive.span.clone())),
+                _ => {}
             }
         }
 
         Self {
             meta_directives: merged_metas,
-            dated_directives,
+            dated_runs,
+            order,
             options_directives,
             plugin_directives,
-            other_directives,
+            rename_directives,
         }
     }
+
+    /// the directives to be processed in order, the dated ones are merged from the runs by a k-way merge one by one when they are consumed.
+    /// the merged directives are moved into `dated_directives`, which is kept by ledger, once they are processed, see [KeepProcessed]
+    fn other_directives<'a>(
+        meta_directives: &'a [Spanned<Directive>], dated_runs: Vec<Vec<Spanned<Directive>>>, order: DirectiveOrder,
+        dated_directives: &'a mut Vec<Spanned<Directive>>,
+    ) -> impl Iterator<Item = Spanned<Directive>> + 'a {
+        let merged = KeepProcessed {
+            directives: Ledger::merge_directives_datetime(dated_runs, order),
+            processing: None,
+            processed: dated_directives,
+        };
+        meta_directives
+            .iter()
+            .rev()
+            .cloned()
+            .chain(merged)
+            .filter(|directive| !matches!(directive.data, Directive::Plugin(_) | Directive::Option(_) | Directive::Rename(_)))
+    }
+}
+
+/// yields the copy of each directive to be processed, and moves the directive into `processed` after its copy is processed,
+/// i.e. when the next one is requested. the copy is needed since processing changes it, e.g. renaming accounts and adding tax postings,
+/// while ledger keeps the directives as they are written. only the copy of directive in processing is held at a time
+struct KeepProcessed<'a, I: Iterator<Item = Spanned<Directive>>> {
+    directives: I,
+    processing: Option<Spanned<Directive>>,
+    processed: &'a mut Vec<Spanned<Directive>>,
+}
+
+impl<I: Iterator<Item = Spanned<Directive>>> Iterator for KeepProcessed<'_, I> {
+    type Item = Spanned<Directive>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.processed.extend(self.processing.take());
+        let directive = self.directives.next()?;
+        let copy = directive.clone();
+        self.processing = Some(directive);
+        Some(copy)
+    }
 }
 
 impl Ledger {
@@ -114,7 +194,7 @@ impl Ledger {
 
         let load_result = data_source.load(entry.to_string_lossy().to_string(), endpoint.clone())?;
         Ledger::process(LedgerProcessContext {
-            directives: load_result.directives,
+            runs: load_result.runs,
             entry: (entry, endpoint),
             visited_files: load_result.visited_files,
             data_source,
@@ -124,7 +204,7 @@ impl Ledger {
         let load_result = data_source.async_load(entry.to_string_lossy().to_string(), endpoint.clone()).await?;
 
         Ledger::async_process(LedgerProcessContext {
-            directives: load_result.directives,
+            runs: load_result.runs,
             entry: (entry, endpoint),
             visited_files: load_result.visited_files,
             data_source,
@@ -142,30 +222,53 @@ impl Ledger {
             data_source: context.data_source,
             store: Default::default(),
             trx_counter: AtomicI32::new(1),
+            prefetched_documents: HashMap::new(),
             #[cfg(feature = "plugin_runtime")]
             plugins: crate::plugin::store::PluginStore::default(),
         };
         let SplitDirectives {
             meta_directives,
-            dated_directives,
+            dated_runs,
+            order,
             mut options_directives,
             mut plugin_directives,
-            other_directives,
-        } = SplitDirectives::new(context.directives);
+            rename_directives,
+        } = SplitDirectives::new(context.runs);
 
         ret_ledger.handle_options(&mut options_directives)?;
+        let snapshot_fingerprint = ret_ledger.snapshot_fingerprint(&meta_directives, &dated_runs, &plugin_directives);
+        if ret_ledger.restore_snapshot(snapshot_fingerprint.as_deref()) {
+            ret_ledger.metas = meta_directives;
+            ret_ledger.directives = Ledger::merge_directives_datetime(dated_runs, order).collect_vec();
+            info!("Ledger restored from snapshot");
+            return Ok(ret_ledger);
+        }
+        let account_renames = process::rename::account_renames(&mut ret_ledger, &rename_directives)?;
 
         ret_ledger.handle_plugins_pre_process(&mut plugin_directives)?;
         ret_ledger.handle_plugins(&mut plugin_directives)?;
 
-        let other_directives = ret_ledger.handle_plugin_execution(other_directives)?;
+        let mut dated_directives = vec![];
+        let other_directives = SplitDirectives::other_directives(&meta_directives, dated_runs, order, &mut dated_directives).map(|mut directive| {
+            account_renames.apply(&mut directive);
+            directive
+        });
+        // the directives are collected only if they are processed or mapped as a whole by plugins
+        if ret_ledger.has_directive_plugins() {
+            let other_directives = ret_ledger.handle_plugin_execution(other_directives.collect_vec())?;
+            ret_ledger.handle_other_directives(other_directives)?;
+        } else {
+            ret_ledger.handle_other_directives(other_directives)?;
+        }
+        process::duplicate::detect_duplicate_transactions(&ret_ledger)?;
+        process::severity::apply_error_severities(&ret_ledger)?;
 
-        ret_ledger.handle_other_directives(other_directives)?;
+        ret_ledger.save_snapshot(snapshot_fingerprint.as_deref());
 
         ret_ledger.metas = meta_directives;
         ret_ledger.directives = dated_directives;
         let mut operations = ret_ledger.operations();
-        let errors = operations.errors()?;
+        let errors = operations.errors_by_severity(ErrorSeverity::Error)?;
         if !errors.is_empty() {
             error!("Ledger loaded with {} error", errors.len());
         } else {
@@ -184,28 +287,54 @@ impl Ledger {
             data_source: context.data_source,
             store: Default::default(),
             trx_counter: AtomicI32::new(1),
+            prefetched_documents: HashMap::new(),
             #[cfg(feature = "plugin_runtime")]
             plugins: crate::plugin::store::PluginStore::default(),
         };
         let SplitDirectives {
             meta_directives,
-            dated_directives,
+            dated_runs,
+            order,
             mut options_directives,
             mut plugin_directives,
-            other_directives,
-        } = SplitDirectives::new(context.directives);
+            rename_directives,
+        } = SplitDirectives::new(context.runs);
         ret_ledger.handle_options(&mut options_directives)?;
+        let snapshot_fingerprint = ret_ledger.snapshot_fingerprint(&meta_directives, &dated_runs, &plugin_directives);
+        if ret_ledger.restore_snapshot(snapshot_fingerprint.as_deref()) {
+            ret_ledger.metas = meta_directives;
+            ret_ledger.directives = Ledger::merge_directives_datetime(dated_runs, order).collect_vec();
+            info!("Ledger restored from snapshot");
+            return Ok(ret_ledger);
+        }
+        let account_renames = process::rename::account_renames(&mut ret_ledger, &rename_directives)?;
+        let referenced_files = process::document::referenced_files(&ret_ledger, dated_runs.iter().flatten());
+        process::document::prefetch_documents(&mut ret_ledger, referenced_files).await;
         ret_ledger.async_handle_plugins_pre_process(&mut plugin_directives).await?;
         ret_ledger.handle_plugins(&mut plugin_directives)?;
 
-        let other_directives = ret_ledger.handle_plugin_execution(other_directives)?;
+        let mut dated_directives = vec![];
+        let other_directives = SplitDirectives::other_directives(&meta_directives, dated_runs, order, &mut dated_directives).map(|mut directive| {
+            account_renames.apply(&mut directive);
+            directive
+        });
+        // the directives are collected only if they are processed or mapped as a whole by plugins
+        if ret_ledger.has_directive_plugins() {
+            let other_directives = ret_ledger.handle_plugin_execution(other_directives.collect_vec())?;
+            ret_ledger.handle_other_directives(other_directives)?;
+        } else {
+            ret_ledger.handle_other_directives(other_directives)?;
+        }
+        process::duplicate::detect_duplicate_transactions(&ret_ledger)?;
+        process::severity::apply_error_severities(&ret_ledger)?;
+        ret_ledger.prefetched_documents = HashMap::new();
 
-        ret_ledger.handle_other_directives(other_directives)?;
+        ret_ledger.save_snapshot(snapshot_fingerprint.as_deref());
 
         ret_ledger.metas = meta_directives;
         ret_ledger.directives = dated_directives;
         let mut operations = ret_ledger.operations();
-        let errors = operations.errors()?;
+        let errors = operations.errors_by_severity(ErrorSeverity::Error)?;
         if !errors.is_empty() {
             error!("Ledger loaded with {} error", errors.len());
         } else {
@@ -218,7 +347,7 @@ impl Ledger {
         let (entry, endpoint) = &mut self.entry;
         let transform_result = self.data_source.load(entry.to_string_lossy().to_string(), endpoint.clone())?;
         let reload_ledger = Ledger::process(LedgerProcessContext {
-            directives: transform_result.directives,
+            runs: transform_result.runs,
             entry: (entry.clone(), endpoint.clone()),
             visited_files: transform_result.visited_files,
             data_source: self.data_source.clone(),
@@ -231,7 +360,7 @@ impl Ledger {
         let (entry, endpoint) = &mut self.entry;
         let transform_result = self.data_source.async_load(entry.to_string_lossy().to_string(), endpoint.clone()).await?;
         let reload_ledger = Ledger::async_process(LedgerProcessContext {
-            directives: transform_result.directives,
+            runs: transform_result.runs,
             entry: (entry.clone(), endpoint.clone()),
             visited_files: transform_result.visited_files,
             data_source: self.data_source.clone(),
@@ -241,6 +370,37 @@ impl Ledger {
         Ok(())
     }
 
+    /// the directive whose id is the hash of its span, which changes once the directive is moved in its file
+    pub fn directive(&self, id: &Uuid) -> Option<&Spanned<Directive>> {
+        self.directives.iter().chain(self.metas.iter()).find(|it| Uuid::from_span(&it.span).eq(id))
+    }
+
+    /// the changes of directives since the previous version of ledger, the directives are compared by their source
+    pub fn changes_since(&self, previous: &Ledger) -> LedgerChanges {
+        let date_range = Ledger::changed_directives(&self.directives, &previous.directives)
+            .filter_map(|directive| directive.datetime())
+            .map(|datetime| datetime.date())
+            .minmax()
+            .into_option()
+            .map(|(from, to)| DateRange { from, to });
+        LedgerChanges {
+            date_range,
+            metas_changed: Ledger::changed_directives(&self.metas, &previous.metas).next().is_some(),
+        }
+    }
+
+    /// the directives whose sources appear in one version more times than in the other one
+    fn changed_directives<'a>(current: &'a [Spanned<Directive>], previous: &'a [Spanned<Directive>]) -> impl Iterator<Item = &'a Spanned<Directive>> {
+        let mut counts: HashMap<&str, (isize, &Spanned<Directive>)> = HashMap::new();
+        for directive in current {
+            counts.entry(directive.span.content.trim_end()).or_insert((0, directive)).0 += 1;
+        }
+        for directive in previous {
+            counts.entry(directive.span.content.trim_end()).or_insert((0, directive)).0 -= 1;
+        }
+        counts.into_values().filter(|(count, _)| *count != 0).map(|(_, directive)| directive)
+    }
+
     pub fn operations(&self) -> Operations {
         let timezone = self.options.timezone;
         Operations {
@@ -248,11 +408,55 @@ impl Ledger {
             timezone,
         }
     }
+
+    /// reconcile the cleared postings of account against the statement by [`Operations::reconcile`],
+    /// and persist the reconciled postings by their `statement-date` meta so that they stay reconciled after reload
+    pub fn reconcile(&self, account_name: &str, statement_date: NaiveDate, statement_balance: &Amount) -> ZhangResult<Vec<Uuid>> {
+        let reconciled = self.operations().reconcile(account_name, statement_date, statement_balance)?;
+        let reconciled_ids: HashSet<&Uuid> = reconciled.iter().collect();
+        let mut updated_transactions = vec![];
+        for directive in self.directives.iter() {
+            let Directive::Transaction(trx) = &directive.data else {
+                continue;
+            };
+            let trx_id = Uuid::from_span(&directive.span);
+            let mut trx = trx.clone();
+            let mut is_updated = false;
+            for (posting_idx, posting) in trx.postings.iter_mut().enumerate() {
+                if reconciled_ids.contains(&Uuid::from_txn_posting(&trx_id, posting_idx)) {
+                    posting.meta.pop_one(POSTING_RECONCILE);
+                    posting.meta.pop_one(POSTING_STATEMENT_DATE);
+                    posting.meta.insert(
+                        POSTING_STATEMENT_DATE.to_owned(),
+                        ZhangString::quote(statement_date.format("%Y-%m-%d").to_string()),
+                    );
+                    is_updated = true;
+                }
+            }
+            if is_updated {
+                updated_transactions.push((&directive.span, trx));
+            }
+        }
+        // the transactions are updated from the end of each file, so that the spans of the others are not shifted
+        updated_transactions.sort_by(|(a, _), (b, _)| a.filename.cmp(&b.filename).then(b.start.cmp(&a.start)));
+        for (span, trx) in updated_transactions {
+            self.data_source.update_directive(self, span, Directive::Transaction(trx))?;
+        }
+        Ok(reconciled)
+    }
 }
 
 impl Ledger {
-    fn sort_directives_datetime(mut directives: Vec<Spanned<Directive>>) -> Vec<Spanned<Directive>> {
-        directives.sort_by(|a, b| match (a.datetime(), b.datetime()) {
+    /// merge the sorted directives of each file lazily, the directives are loaded file by file so that each file is one run
+    fn merge_directives_datetime(runs: Vec<Vec<Spanned<Directive>>>, order: DirectiveOrder) -> impl Iterator<Item = Spanned<Directive>> {
+        merge_sorted_runs(runs, move |directive| {
+            let is_balance = matches!(directive.directive_type(), DirectiveType::BalancePad | DirectiveType::BalanceCheck);
+            (order.datetime_of(&directive.data), !is_balance)
+        })
+    }
+
+    fn sort_directives_datetime(mut directives: Vec<Spanned<Directive>>, order: DirectiveOrder) -> Vec<Spanned<Directive>> {
+        directives.sort_by(|a, b| match (order.datetime_of(&a.data), order.datetime_of(&b.data)) {
             (Some(a_datetime), Some(b_datetime)) => match a_datetime.cmp(&b_datetime) {
                 Ordering::Equal => match (a.directive_type(), b.directive_type()) {
                     (DirectiveType::BalancePad | DirectiveType::BalanceCheck, DirectiveType::BalancePad | DirectiveType::BalanceCheck) => Ordering::Equal,
@@ -267,6 +471,50 @@ impl Ledger {
         directives
     }
 
+    /// the fingerprint of directives if the data source keeps store snapshot,
+    /// ledgers with plugins are always processed since plugins may produce different results from the same directives
+    fn snapshot_fingerprint(
+        &self, meta_directives: &[Spanned<Directive>], dated_runs: &[Vec<Spanned<Directive>>], plugin_directives: &[(Plugin, SpanInfo)],
+    ) -> Option<String> {
+        self.data_source.store_snapshot()?;
+        if !plugin_directives.is_empty() {
+            return None;
+        }
+        // the store of encrypted files is never written into snapshot in plain
+        if self.visited_files.iter().any(|file| Encryption::of(file).is_some()) {
+            return None;
+        }
+        let referenced_files = process::document::referenced_files(self, dated_runs.iter().flatten());
+        StoreSnapshot::fingerprint(meta_directives, dated_runs, &self.options.timezone, &self.entry.0, &referenced_files)
+            .map_err(|e| error!("cannot fingerprint directives, ledger will be processed without snapshot: {}", e))
+            .ok()
+    }
+
+    /// replace the store with the snapshot of fingerprint, the transaction counter continues from the restored transactions
+    fn restore_snapshot(&mut self, fingerprint: Option<&str>) -> bool {
+        let Some(store) = fingerprint.and_then(|fingerprint| self.data_source.store_snapshot()?.restore(fingerprint)) else {
+            return false;
+        };
+        let next_sequence = store.transactions.values().map(|trx| trx.sequence + 1).max().unwrap_or(1);
+        self.trx_counter = AtomicI32::new(next_sequence);
+        self.store = Arc::new(RwLock::new(store));
+        true
+    }
+
+    /// failing to write the snapshot does not fail the processing
+    fn save_snapshot(&self, fingerprint: Option<&str>) {
+        let (Some(fingerprint), Some(snapshot)) = (fingerprint, self.data_source.store_snapshot()) else {
+            return;
+        };
+        let result = match self.store.read() {
+            Ok(store) => snapshot.save(fingerprint, &store),
+            Err(_) => Err(ZhangError::CustomError("store is poisoned".to_owned())),
+        };
+        if let Err(e) = result {
+            error!("cannot write store snapshot: {}", e);
+        }
+    }
+
     fn handle_options(&mut self, options_directives: &mut [(Options, SpanInfo)]) -> ZhangResult<()> {
         // handle option
         for (option, span) in options_directives.iter_mut() {
@@ -294,34 +542,99 @@ impl Ledger {
         Ok(())
     }
 
-    fn handle_other_directives(&mut self, mut other_directives: Vec<Spanned<Directive>>) -> Result<(), ZhangError> {
+    fn handle_other_directives(&mut self, other_directives: impl IntoIterator<Item = Spanned<Directive>>) -> Result<(), ZhangError> {
+        #[cfg(feature = "plugin_runtime")]
+        let mut transaction_hooks = self.load_plugins(&crate::plugin::PluginType::TransactionHook)?;
+        #[cfg(feature = "plugin_runtime")]
+        let mut validators = self.load_plugins(&crate::plugin::PluginType::Validator)?;
+        #[cfg(feature = "plugin_runtime")]
+        let mut custom_directive_plugins = self.load_plugins(&crate::plugin::PluginType::CustomDirective)?;
+
         // handle other directives
-        for directive in other_directives.iter_mut() {
+        for mut directive in other_directives {
+            process::meta_schema::check_meta_schema(self, &mut directive.data, &directive.span)?;
             match &mut directive.data {
                 Directive::Option(_) => unreachable!("option directive should not be passed into the processor here"),
                 Directive::Open(open) => open.handler(self, &directive.span)?,
                 Directive::Close(close) => close.handler(self, &directive.span)?,
                 Directive::Commodity(commodity) => commodity.handler(self, &directive.span)?,
-                Directive::Transaction(trx) => trx.handler(self, &directive.span)?,
+                Directive::Transaction(trx) => {
+                    #[cfg(feature = "plugin_runtime")]
+                    crate::process::plugin::transaction_by_plugins(self, &mut transaction_hooks, trx, &directive.span)?;
+                    trx.handler(self, &directive.span)?
+                }
+                Directive::Template(template) => template.handler(self, &directive.span)?,
                 Directive::BalancePad(pad) => pad.handler(self, &directive.span)?,
                 Directive::BalanceCheck(check) => check.handler(self, &directive.span)?,
+                Directive::Pad(pad) => pad.handler(self, &directive.span)?,
                 Directive::Note(_) => {}
                 Directive::Document(document) => document.handler(self, &directive.span)?,
                 Directive::Price(price) => price.handler(self, &directive.span)?,
+                Directive::Split(split) => split.handler(self, &directive.span)?,
+                Directive::Depreciation(depreciation) => depreciation.handler(self, &directive.span)?,
+                Directive::Loan(loan) => loan.handler(self, &directive.span)?,
+                Directive::Accrue(accrue) => accrue.handler(self, &directive.span)?,
+                Directive::OpenBalance(open_balance) => open_balance.handler(self, &directive.span)?,
                 Directive::Event(_) => {}
-                Directive::Custom(_) => {}
+                Directive::Custom(custom) => {
+                    match custom.custom_type.as_str() {
+                        process::invoice::INVOICE_CUSTOM_TYPE => process::invoice::handle_invoice(self, custom, &directive.span)?,
+                        process::elimination::ELIMINATION_CUSTOM_TYPE => process::elimination::handle_elimination(self, custom, &directive.span)?,
+                        _ => {}
+                    }
+                    #[cfg(feature = "plugin_runtime")]
+                    crate::process::plugin::handle_custom_by_plugins(self, &mut custom_directive_plugins, custom, &directive.span)?;
+                }
                 Directive::Plugin(_) => unreachable!("plugin directive should not be passed into the processor here"),
                 Directive::Include(_) => {}
                 Directive::Comment(_) => {}
+                Directive::Malformed(malformed) => {
+                    let mut operations = self.operations();
+                    let metas = HashMap::of("message", malformed.message.clone());
+                    operations.new_error(ErrorKind::ParseError, &directive.span, metas)?;
+                }
+                Directive::Rename(_) => unreachable!("rename directive should not be passed into the processor here"),
                 Directive::Budget(budget) => budget.handler(self, &directive.span)?,
                 Directive::BudgetAdd(budget_add) => budget_add.handler(self, &directive.span)?,
                 Directive::BudgetTransfer(budget_transfer) => budget_transfer.handler(self, &directive.span)?,
                 Directive::BudgetClose(budget_close) => budget_close.handler(self, &directive.span)?,
             }
+
+            #[cfg(feature = "plugin_runtime")]
+            crate::process::plugin::validate_by_plugins(self, &mut validators, &directive)?;
         }
         Ok(())
     }
 
+    /// load the plugins of given type once, used for the plugins invoked for each directive.
+    /// the plugin failing to load is reported and skipped
+    #[cfg(feature = "plugin_runtime")]
+    fn load_plugins(&mut self, plugin_type: &crate::plugin::PluginType) -> ZhangResult<Vec<(crate::plugin::store::RegisteredPlugin, extism::Plugin)>> {
+        let plugins = self.plugins.plugins_of(plugin_type).to_vec();
+        if !self.options.features.plugins || plugins.is_empty() {
+            return Ok(vec![]);
+        }
+        let options = self.operations().options()?;
+        let mut loaded_plugins = vec![];
+        for plugin in plugins {
+            match plugin.load_as_plugin(&options) {
+                Ok(wasm_plugin) => loaded_plugins.push((plugin, wasm_plugin)),
+                Err(e) => crate::process::plugin::plugin_execution_failed(self, &plugin.name, &plugin.span, e)?,
+            }
+        }
+        Ok(loaded_plugins)
+    }
+
+    /// whether any plugin of processor or mapper type is loaded, which executes on all directives at once
+    fn has_directive_plugins(&self) -> bool {
+        #[cfg(feature = "plugin_runtime")]
+        {
+            self.options.features.plugins && (!self.plugins.processors.is_empty() || !self.plugins.mappers.is_empty())
+        }
+        #[cfg(not(feature = "plugin_runtime"))]
+        false
+    }
+
     fn handle_plugin_execution(&mut self, other_directives: Vec<Spanned<Directive>>) -> ZhangResult<Vec<Spanned<Directive>>> {
         let d = feature_enable!(
             self.options.features.plugins,
@@ -331,19 +644,26 @@ impl Ledger {
                     let mut directives = other_directives;
                     let options = self.operations().options()?;
                     // execute the plugins of processor type
-                    for plugin in self.plugins.processors.iter() {
-                        directives = plugin.execute_as_processor(directives, &options)?;
+                    // the directives are kept as they are if the plugin fails to execute
+                    for plugin in self.plugins.processors.clone() {
+                        match plugin.execute_as_processor(directives.clone(), &options) {
+                            Ok(ret) => directives = ret,
+                            Err(e) => crate::process::plugin::plugin_execution_failed(self, &plugin.name, &plugin.span, e)?,
+                        }
                     }
 
-                    directives = Ledger::sort_directives_datetime(directives);
+                    directives = Ledger::sort_directives_datetime(directives, self.options.directive_order());
 
                     // execute the plugins of mapper type
-                    for plugin in self.plugins.mappers.iter() {
+                    for plugin in self.plugins.mappers.clone() {
                         let plugin_ret: ZhangResult<Vec<Vec<Spanned<Directive>>>> =
-                            directives.into_iter().map(|d| plugin.execute_as_mapper(d, &options)).collect();
-                        directives = plugin_ret?.into_iter().flatten().collect_vec();
+                            directives.iter().map(|d| plugin.execute_as_mapper(d.clone(), &options)).collect();
+                        match plugin_ret {
+                            Ok(ret) => directives = ret.into_iter().flatten().collect_vec(),
+                            Err(e) => crate::process::plugin::plugin_execution_failed(self, &plugin.name, &plugin.span, e)?,
+                        }
                     }
-                    Ledger::sort_directives_datetime(directives)
+                    Ledger::sort_directives_datetime(directives, self.options.directive_order())
                 }
                 #[cfg(not(feature = "plugin_runtime"))]
                 other_directives
@@ -373,6 +693,7 @@ mod test {
             end: 0,
             content: "".to_string(),
             filename: None,
+            trivia: Default::default(),
         }
     }
     fn test_parse_zhang(content: &str) -> Vec<Spanned<Directive>> {
@@ -393,8 +714,11 @@ mod test {
         use itertools::Itertools;
         use zhang_ast::{Directive, Options, Spanned, ZhangString};
 
+        use crate::data_type::text::ZhangDataType;
+        use crate::data_type::DataType;
         use crate::ledger::test::{fake_span_info, test_parse_zhang};
         use crate::ledger::Ledger;
+        use crate::options::DirectiveOrder;
 
         #[test]
         fn should_keep_order_given_two_none_datetime() {
@@ -414,7 +738,7 @@ mod test {
                     fake_span_info(),
                 ),
             ];
-            let sorted = Ledger::sort_directives_datetime(original);
+            let sorted = Ledger::sort_directives_datetime(original, DirectiveOrder::default());
             assert_eq!(
                 vec![
                     Spanned::new(
@@ -442,7 +766,7 @@ mod test {
                 1970-01-01 open Assets:Hello
                 option "description" "Description"
             "#});
-            let sorted = Ledger::sort_directives_datetime(original);
+            let sorted = Ledger::sort_directives_datetime(original, DirectiveOrder::default());
             assert_eq!(
                 test_parse_zhang(indoc! {r#"
                     1970-01-01 open Assets:Hello
@@ -454,7 +778,7 @@ mod test {
                     option "description" "Description"
                     1970-01-01 open Assets:Hello
                 "#});
-            let sorted = Ledger::sort_directives_datetime(original);
+            let sorted = Ledger::sort_directives_datetime(original, DirectiveOrder::default());
             assert_eq!(
                 test_parse_zhang(indoc! {r#"
                     option "description" "Description"
@@ -471,7 +795,7 @@ mod test {
                     1970-02-01 open Assets:Hello
                 "#});
 
-            let sorted = Ledger::sort_directives_datetime(original);
+            let sorted = Ledger::sort_directives_datetime(original, DirectiveOrder::default());
             assert_eq!(
                 test_parse_zhang(indoc! {r#"
                     1970-01-01 open Assets:Hello
@@ -486,7 +810,7 @@ mod test {
                     1970-02-01 open Assets:Hello
                     1970-01-01 open Assets:Hello
                 "#});
-            let sorted = Ledger::sort_directives_datetime(original);
+            let sorted = Ledger::sort_directives_datetime(original, DirectiveOrder::default());
             assert_eq!(
                 test_parse_zhang(indoc! {r#"
                     1970-01-01 open Assets:Hello
@@ -508,7 +832,7 @@ mod test {
                     1970-01-01 open Assets:Hello
                 "#});
 
-            let sorted = Ledger::sort_directives_datetime(original);
+            let sorted = Ledger::sort_directives_datetime(original, DirectiveOrder::default());
             assert_eq!(
                 test_parse_zhang(indoc! {r#"
                     option "1" "1"
@@ -531,10 +855,13 @@ mod test {
                     1970-01-01 open Assets:Hello
                     1970-01-01 close Assets:Hello
                 "#}),
-                Ledger::sort_directives_datetime(test_parse_zhang(indoc! {r#"
+                Ledger::sort_directives_datetime(
+                    test_parse_zhang(indoc! {r#"
                     1970-01-01 open Assets:Hello
                     1970-01-01 close Assets:Hello
-                "#}))
+                "#}),
+                    DirectiveOrder::default()
+                )
             );
         }
 
@@ -548,15 +875,61 @@ mod test {
                 .into_iter()
                 .map(|it| it.data)
                 .collect_vec(),
-                Ledger::sort_directives_datetime(test_parse_zhang(indoc! {r#"
+                Ledger::sort_directives_datetime(
+                    test_parse_zhang(indoc! {r#"
                     1970-01-01 open Assets:Hello
                     1970-01-01 balance Assets:Hello 2 CNY
-                "#}))
+                "#}),
+                    DirectiveOrder::default()
+                )
                 .into_iter()
                 .map(|it| it.data)
                 .collect_vec()
             );
         }
+        #[test]
+        fn should_merge_directives_of_files_by_datetime() {
+            let data_type = ZhangDataType {};
+            let main_run = data_type
+                .transform(
+                    indoc! {r#"
+                    1970-03-01 open Assets:Hello
+                    1970-01-01 open Assets:Hello
+                "#}
+                    .to_owned(),
+                    Some("main.zhang".to_owned()),
+                )
+                .unwrap();
+            let include_run = data_type
+                .transform(
+                    indoc! {r#"
+                    1970-02-01 open Assets:Hello
+                    1970-01-01 balance Assets:Hello 2 CNY
+                "#}
+                    .to_owned(),
+                    Some("include.zhang".to_owned()),
+                )
+                .unwrap();
+            let runs = [main_run, include_run]
+                .into_iter()
+                .map(|run| Ledger::sort_directives_datetime(run, DirectiveOrder::default()))
+                .collect_vec();
+            assert_eq!(
+                test_parse_zhang(indoc! {r#"
+                    1970-01-01 balance Assets:Hello 2 CNY
+                    1970-01-01 open Assets:Hello
+                    1970-02-01 open Assets:Hello
+                    1970-03-01 open Assets:Hello
+                "#})
+                .into_iter()
+                .map(|it| it.data)
+                .collect_vec(),
+                Ledger::merge_directives_datetime(runs, DirectiveOrder::default())
+                    .map(|it| it.data)
+                    .collect_vec()
+            );
+        }
+
         #[test]
         fn should_keep_balance_order() {
             assert_eq!(
@@ -564,10 +937,13 @@ mod test {
                     1970-01-01 balance Assets:Hello 2 CNY
                     1970-01-01 balance Assets:Hello2 2 CNY
                 "#}),
-                Ledger::sort_directives_datetime(test_parse_zhang(indoc! {r#"
+                Ledger::sort_directives_datetime(
+                    test_parse_zhang(indoc! {r#"
                     1970-01-01 balance Assets:Hello 2 CNY
                     1970-01-01 balance Assets:Hello2 2 CNY
-                "#}))
+                "#}),
+                    DirectiveOrder::default()
+                )
             );
         }
     }
@@ -633,6 +1009,9 @@ mod test {
     }
 
     mod price {
+        use std::str::FromStr;
+        use std::sync::Arc;
+
         use bigdecimal::BigDecimal;
         use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
         use indoc::indoc;
@@ -659,6 +1038,115 @@ mod test {
                 .unwrap();
             assert_eq!(BigDecimal::from(7), option.amount)
         }
+
+        fn date(year: i32, month: u32, day: u32) -> NaiveDateTime {
+            NaiveDateTime::new(NaiveDate::from_ymd_opt(year, month, day).unwrap(), NaiveTime::from_hms_opt(0, 0, 0).unwrap())
+        }
+
+        #[test]
+        fn should_get_nearest_prior_price() {
+            let ledger = load_from_temp_str(indoc! {r#"
+                    1970-01-01 commodity CNY
+                    1970-01-01 commodity USD
+                    1970-02-01 price USD 7 CNY
+                    1970-02-11 price USD 8 CNY
+                "#});
+            let operations = ledger.operations();
+
+            assert!(operations.price_at("USD", "CNY", date(1970, 1, 31), false).unwrap().is_none());
+            assert_eq!(
+                BigDecimal::from(7),
+                operations.price_at("USD", "CNY", date(1970, 2, 6), false).unwrap().unwrap().amount
+            );
+            assert_eq!(
+                BigDecimal::from(8),
+                operations.price_at("USD", "CNY", date(1970, 3, 1), false).unwrap().unwrap().amount
+            );
+        }
+
+        #[test]
+        fn should_interpolate_between_price_points() {
+            let ledger = load_from_temp_str(indoc! {r#"
+                    1970-01-01 commodity CNY
+                    1970-01-01 commodity USD
+                    1970-02-01 price USD 7 CNY
+                    1970-02-11 price USD 8 CNY
+                "#});
+            let operations = ledger.operations();
+
+            let price = operations.price_at("USD", "CNY", date(1970, 2, 6), true).unwrap().unwrap();
+            assert_eq!(BigDecimal::from_str("7.5").unwrap(), price.amount);
+            assert_eq!(date(1970, 2, 6), price.datetime);
+            assert_eq!(
+                BigDecimal::from(7),
+                operations.price_at("USD", "CNY", date(1970, 2, 1), true).unwrap().unwrap().amount
+            );
+            assert_eq!(
+                BigDecimal::from(8),
+                operations.price_at("USD", "CNY", date(1970, 3, 1), true).unwrap().unwrap().amount
+            );
+        }
+
+        #[test]
+        fn should_derive_inverse_and_cross_rate() {
+            let ledger = load_from_temp_str(indoc! {r#"
+                    1970-01-01 commodity CNY
+                    1970-01-01 commodity USD
+                    1970-01-01 commodity EUR
+                    1970-02-01 price USD 8 CNY
+                    1970-02-01 price EUR 1.25 USD
+                "#});
+            let operations = ledger.operations();
+            let rate = |from: &str, to: &str| operations.exchange_rate(from, to, date(1970, 3, 1)).unwrap();
+
+            assert_eq!(Some(BigDecimal::from(8)), rate("USD", "CNY"));
+            assert_eq!(Some(BigDecimal::from_str("0.125").unwrap()), rate("CNY", "USD"));
+            assert_eq!(Some(BigDecimal::from(10)), rate("EUR", "CNY"));
+            assert_eq!(Some(BigDecimal::from_str("0.1").unwrap()), rate("CNY", "EUR"));
+            assert_eq!(None, operations.exchange_rate("CNY", "EUR", date(1970, 1, 1)).unwrap());
+        }
+
+        #[test]
+        fn should_prefer_direct_price_and_handle_cycles() {
+            let ledger = load_from_temp_str(indoc! {r#"
+                    1970-01-01 commodity CNY
+                    1970-01-01 commodity USD
+                    1970-01-01 commodity EUR
+                    1970-01-01 commodity JPY
+                    1970-02-01 price USD 8 CNY
+                    1970-02-01 price CNY 0.1 EUR
+                    1970-02-01 price EUR 1.25 USD
+                    1970-02-01 price CNY 0.124 USD
+                "#});
+            let operations = ledger.operations();
+            let rate = |from: &str, to: &str| operations.exchange_rate(from, to, date(1970, 3, 1)).unwrap();
+
+            assert_eq!(Some(BigDecimal::from_str("0.124").unwrap()), rate("CNY", "USD"));
+            assert_eq!(Some(BigDecimal::from(8)), rate("USD", "CNY"));
+            assert_eq!(None, rate("CNY", "JPY"));
+        }
+
+        #[test]
+        fn should_reuse_price_graph_until_price_is_inserted() {
+            let ledger = load_from_temp_str(indoc! {r#"
+                    1970-01-01 commodity CNY
+                    1970-01-01 commodity USD
+                    1970-02-01 price USD 7 CNY
+                    1970-03-01 price USD 8 CNY
+                "#});
+            let mut operations = ledger.operations();
+            let graph = operations.price_graph().unwrap();
+            assert!(Arc::ptr_eq(&graph, &operations.price_graph().unwrap()));
+            assert_eq!(Some(BigDecimal::from(7)), graph.rate("USD", "CNY", date(1970, 2, 15)));
+            assert_eq!(Some(BigDecimal::from(8)), graph.rate("USD", "CNY", date(1970, 3, 15)));
+            assert_eq!(None, graph.rate("USD", "CNY", date(1970, 1, 15)));
+
+            let datetime = date(1970, 4, 1).and_local_timezone(ledger.options.timezone).unwrap();
+            operations.insert_price(datetime, "USD", &BigDecimal::from(9), "CNY").unwrap();
+            let rebuilt = operations.price_graph().unwrap();
+            assert!(!Arc::ptr_eq(&graph, &rebuilt));
+            assert_eq!(Some(BigDecimal::from(9)), rebuilt.rate("USD", "CNY", date(1970, 4, 15)));
+        }
     }
 
     mod account {
@@ -678,4 +1166,140 @@ mod test {
             Ok(())
         }
     }
+
+    mod changes {
+        use chrono::NaiveDate;
+        use indoc::indoc;
+
+        use crate::ledger::test::load_from_temp_str;
+        use crate::ledger::{DateRange, LedgerChanges};
+
+        #[test]
+        fn should_get_date_range_of_changed_directives() {
+            let previous = load_from_temp_str(indoc! {r#"
+                1970-01-01 open Assets:Bank
+                2024-01-05 note Assets:Bank "first"
+                2024-02-10 note Assets:Bank "second"
+                2024-03-15 note Assets:Bank "third"
+            "#});
+            let current = load_from_temp_str(indoc! {r#"
+                1970-01-01 open Assets:Bank
+                2024-01-05 note Assets:Bank "first"
+                2024-02-11 note Assets:Bank "second"
+                2024-03-15 note Assets:Bank "third"
+                2024-04-01 note Assets:Bank "fourth"
+            "#});
+
+            let date = |year, month, day| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+            assert_eq!(
+                LedgerChanges {
+                    date_range: Some(DateRange {
+                        from: date(2024, 2, 10),
+                        to: date(2024, 4, 1)
+                    }),
+                    metas_changed: false
+                },
+                current.changes_since(&previous)
+            );
+            assert_eq!(LedgerChanges::default(), current.changes_since(&current));
+
+            let with_option = load_from_temp_str(indoc! {r#"
+                option "title" "Household"
+                1970-01-01 open Assets:Bank
+                2024-01-05 note Assets:Bank "first"
+                2024-02-10 note Assets:Bank "second"
+                2024-03-15 note Assets:Bank "third"
+            "#});
+            assert_eq!(
+                LedgerChanges {
+                    date_range: None,
+                    metas_changed: true
+                },
+                with_option.changes_since(&previous)
+            );
+        }
+    }
+
+    mod document {
+        use std::collections::HashMap;
+        use std::path::PathBuf;
+        use std::sync::Arc;
+
+        use indoc::indoc;
+        use zhang_ast::error::ErrorKind;
+
+        use crate::data_source::{DataSource, LoadResult};
+        use crate::data_type::text::ZhangDataType;
+        use crate::data_type::DataType;
+        use crate::ledger::Ledger;
+        use crate::ZhangResult;
+
+        /// the files are only readable asynchronously, like the ones of remote data sources
+        struct AsyncOnlyDataSource(HashMap<String, Vec<u8>>);
+
+        #[async_trait::async_trait]
+        impl DataSource for AsyncOnlyDataSource {
+            fn load(&self, _entry: String, endpoint: String) -> ZhangResult<LoadResult> {
+                let content = String::from_utf8(self.0[&endpoint].clone())?;
+                Ok(LoadResult {
+                    runs: vec![ZhangDataType {}.transform(content, Some(endpoint.clone()))?],
+                    visited_files: vec![PathBuf::from(endpoint)],
+                })
+            }
+
+            async fn async_get(&self, path: String) -> ZhangResult<Vec<u8>> {
+                Ok(self.0.get(&path).cloned().unwrap_or_default())
+            }
+        }
+
+        #[tokio::test]
+        async fn should_hash_documents_read_through_data_source() {
+            let main = indoc! {r#"
+                1970-01-01 open Assets:Bank
+                1970-01-02 document Assets:Bank "statements/january.txt"
+                1970-01-03 document Assets:Bank "statements/missing.txt"
+            "#};
+            let data_source = AsyncOnlyDataSource(HashMap::from([
+                ("main.zhang".to_owned(), main.as_bytes().to_vec()),
+                ("statements/january.txt".to_owned(), b"January".to_vec()),
+            ]));
+            let ledger = Ledger::async_load(PathBuf::from("/remote"), "main.zhang".to_owned(), Arc::new(data_source))
+                .await
+                .unwrap();
+
+            let store = ledger.store.read().unwrap();
+            assert_eq!(Some(sha256::digest("January")), store.documents[0].hash);
+            assert_eq!(None, store.documents[1].hash);
+            assert!(ledger.prefetched_documents.is_empty());
+        }
+
+        #[tokio::test]
+        async fn should_check_path_metas_through_data_source() {
+            let main = indoc! {r#"
+                option "meta_schema" "transaction receipt=path"
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Expenses:Food
+                1970-01-02 "KFC"
+                  receipt: "receipts/kfc.txt"
+                  Assets:Bank -10 CNY
+                  Expenses:Food
+                1970-01-03 "KFC"
+                  receipt: "receipts/missing.txt"
+                  Assets:Bank -10 CNY
+                  Expenses:Food
+            "#};
+            let data_source = AsyncOnlyDataSource(HashMap::from([
+                ("main.zhang".to_owned(), main.as_bytes().to_vec()),
+                ("receipts/kfc.txt".to_owned(), b"KFC".to_vec()),
+            ]));
+            let ledger = Ledger::async_load(PathBuf::from("/remote"), "main.zhang".to_owned(), Arc::new(data_source))
+                .await
+                .unwrap();
+
+            let errors = ledger.operations().errors().unwrap();
+            assert_eq!(1, errors.len());
+            assert_eq!(ErrorKind::InvalidMetaType, errors[0].error_type);
+            assert_eq!(Some(&"receipts/missing.txt".to_owned()), errors[0].metas.get("value"));
+        }
+    }
 }
@@ -3,17 +3,36 @@ pub use error::ZhangError;
 #[macro_use]
 pub mod utils;
 
+pub mod closing;
 pub mod constants;
 pub mod data_source;
 pub mod data_type;
+pub mod diagnostic;
+pub mod directive_cache;
 pub mod domains;
+pub mod encryption;
 pub mod error;
+pub mod export;
+#[cfg(feature = "git")]
+pub mod git;
+#[cfg(feature = "importer")]
+pub mod importer;
 pub mod ledger;
 pub mod options;
 #[cfg(feature = "plugin_runtime")]
 pub mod plugin;
+#[cfg(feature = "price_fetcher")]
+pub mod price_fetcher;
 pub(crate) mod process;
+pub mod query;
+pub mod reports;
+pub mod span_index;
 pub mod store;
+pub mod store_snapshot;
+pub mod template;
+#[cfg(feature = "watcher")]
+pub mod watcher;
+pub mod workspace;
 
 pub mod features;
 
@@ -33,7 +52,7 @@ mod test {
     use crate::data_type::text::ZhangDataType;
     use crate::ledger::Ledger;
 
-    fn load_from_text(content: &str) -> Ledger {
+    pub(crate) fn load_from_text(content: &str) -> Ledger {
         let temp_dir = tempdir().unwrap().into_path();
         let example = temp_dir.join("example.zhang");
         std::fs::write(example, content).unwrap();
@@ -71,7 +90,7 @@ mod test {
         use indoc::indoc;
         use strum::IntoEnumIterator;
 
-        use crate::options::BuiltinOption;
+        use crate::options::{BuiltinOption, DocumentNaming, InsertStrategy};
         use crate::test::{load_from_text, load_store};
 
         #[test]
@@ -102,7 +121,35 @@ mod test {
             .assert_string("$.options.title", "Example2", "")
             .assert_string("$.options.operating_currency", "CNY", "")
             .assert_string("$.options.default_rounding", "RoundDown", "")
-            .assert_string("$.options.default_balance_tolerance_precision", "2", "");
+            .assert_string("$.options.default_balance_tolerance_precision", "2", "")
+            .assert_string("$.options.insert_strategy", "monthly", "");
+            Ok(())
+        }
+
+        #[test]
+        fn should_fallback_to_monthly_given_invalid_insert_strategy() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                 option "insert_strategy" "daily"
+            "#});
+            assert_eq!(InsertStrategy::Monthly, ledger.options.insert_strategy);
+            Ok(())
+        }
+
+        #[test]
+        fn should_parse_document_options() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                 option "document_directory" "/documents/receipts/"
+                 option "document_naming" "dated"
+            "#});
+            assert_eq!("documents/receipts", ledger.options.document_directory);
+            assert_eq!(DocumentNaming::Dated, ledger.options.document_naming);
+
+            let ledger = load_from_text(indoc! {r#"
+                 option "document_directory" "/"
+                 option "document_naming" "random"
+            "#});
+            assert_eq!("attachments", ledger.options.document_directory);
+            assert_eq!(DocumentNaming::Uuid, ledger.options.document_naming);
             Ok(())
         }
 
@@ -213,10 +260,92 @@ mod test {
         }
     }
 
+    mod account_constraints {
+        use indoc::indoc;
+        use zhang_ast::error::ErrorKind;
+
+        use crate::test::load_from_text;
+
+        #[test]
+        fn should_raise_error_given_commodity_not_allowed() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity USD
+                1970-01-01 open Assets:MyCard
+                  allowed-commodities: "CNY, HKD"
+                1970-01-01 open Expenses:Lunch
+                1970-01-02 "KFC" "Crazy Thursday"
+                  Assets:MyCard -50 CNY
+                  Expenses:Lunch
+                1970-01-02 "KFC" "Crazy Thursday"
+                  Assets:MyCard -50 USD
+                  Expenses:Lunch
+            "#});
+
+            let mut operations = ledger.operations();
+            let errors = operations.errors()?;
+            assert_eq!(1, errors.len());
+            assert_eq!(ErrorKind::CommodityNotAllowedInAccount, errors[0].error_type);
+            assert_eq!("USD", errors[0].metas.get("commodity_name").unwrap());
+            Ok(())
+        }
+
+        #[test]
+        fn should_raise_error_given_posting_exceeds_max_amount() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity USD
+                1970-01-01 open Assets:MyCard
+                  max-posting-amount: "100 CNY"
+                1970-01-01 open Expenses:Lunch
+                  max-posting-amount: "200"
+                1970-01-02 "KFC" "Crazy Thursday"
+                  Assets:MyCard -150 CNY
+                  Expenses:Lunch
+                1970-01-02 "KFC" "Crazy Thursday"
+                  Assets:MyCard -150 USD
+                  Expenses:Lunch
+            "#});
+
+            let mut operations = ledger.operations();
+            let errors = operations.errors()?;
+            assert_eq!(1, errors.len(), "the limit with commodity only applies to the postings of same commodity");
+            assert_eq!(ErrorKind::PostingAmountExceedsLimit, errors[0].error_type);
+            assert_eq!("Assets:MyCard", errors[0].metas.get("account_name").unwrap());
+            assert_eq!("-150 CNY", errors[0].metas.get("amount").unwrap());
+            Ok(())
+        }
+
+        #[test]
+        fn should_raise_error_given_transaction_missing_required_tag() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 open Assets:MyCard
+                1970-01-01 open Expenses:Travel
+                  require-tag: "trip, reimbursable"
+                1970-01-02 "Hotel" "" #trip #reimbursable
+                  Assets:MyCard -50 CNY
+                  Expenses:Travel
+                1970-01-03 "Hotel" "" #trip
+                  Assets:MyCard -50 CNY
+                  Expenses:Travel
+            "#});
+
+            let mut operations = ledger.operations();
+            let errors = operations.errors()?;
+            assert_eq!(1, errors.len());
+            assert_eq!(ErrorKind::TransactionMissingRequiredTag, errors[0].error_type);
+            assert_eq!("reimbursable", errors[0].metas.get("tag").unwrap());
+            Ok(())
+        }
+    }
+
     mod account_balance {
         use bigdecimal::BigDecimal;
+        use chrono::{Duration, NaiveDate};
         use indoc::indoc;
+        use zhang_ast::amount::Amount;
+        use zhang_ast::error::ErrorKind;
 
+        use crate::domains::schemas::BalanceInterval;
+        use crate::domains::Conversion;
         use crate::test::load_from_text;
 
         #[test]
@@ -278,256 +407,2988 @@ mod test {
             assert_eq!(balance.balance_number, BigDecimal::from(2970i32));
             assert_eq!(balance.balance_commodity, "CNY");
         }
-    }
-    mod commodity {
-        use indoc::indoc;
 
-        use crate::test::load_from_text;
+        #[test]
+        fn should_check_combined_balance_of_sub_accounts_given_recursive_meta() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 open Assets:Bank:Checking
+                1970-01-01 open Assets:Bank:Saving
+                1970-01-01 open Assets:BankOfAmerica
+                1970-01-01 open Equity:Open-Balances
+
+                2023-01-01 "Opening" ""
+                    Assets:Bank:Checking 300 CNY
+                    Assets:Bank:Saving 700 CNY
+                    Assets:BankOfAmerica 50 CNY
+                    Equity:Open-Balances
+
+                2023-01-02 balance Assets:Bank 1000 CNY
+                  recursive: "true"
+                2023-01-02 balance Assets:Bank 1050 CNY
+                  recursive: "true"
+            "#});
+
+            let mut operations = ledger.operations();
+            let errors = operations.errors()?;
+            assert_eq!(
+                1,
+                errors.len(),
+                "parent account does not need to be opened, and sibling account with same prefix is excluded"
+            );
+            assert_eq!(ErrorKind::AccountBalanceCheckError, errors[0].error_type);
+            assert_eq!(
+                "2023-01-02 balance Assets:Bank 1050 CNY\n  recursive: \"true\"",
+                errors[0].span.as_ref().unwrap().content.trim()
+            );
+            Ok(())
+        }
 
         #[test]
-        fn should_get_commodity() -> Result<(), Box<dyn std::error::Error>> {
+        fn should_include_parent_account_balance_given_recursive_meta() -> Result<(), Box<dyn std::error::Error>> {
             let ledger = load_from_text(indoc! {r#"
-                1970-01-01 commodity CNY
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Assets:Bank:Checking
+                1970-01-01 open Equity:Open-Balances
+
+                2023-01-01 "Opening" ""
+                    Assets:Bank 100 CNY
+                    Assets:Bank:Checking 300 CNY
+                    Equity:Open-Balances
+
+                2023-01-02 balance Assets:Bank 400 CNY
+                  recursive: "true"
+                2023-01-02 balance Assets:Bank 100 CNY
             "#});
 
-            let operations = ledger.operations();
-            let commodity = operations.commodity("CNY")?.unwrap();
-            assert_eq!("CNY", commodity.name);
-            assert_eq!(2, commodity.precision);
-            assert_eq!(None, commodity.prefix);
-            assert_eq!(None, commodity.suffix);
+            let mut operations = ledger.operations();
+            assert_eq!(0, operations.errors()?.len());
             Ok(())
         }
 
         #[test]
-        fn should_not_get_non_exist_commodity() -> Result<(), Box<dyn std::error::Error>> {
+        fn should_value_balance_in_conversion_currency() -> Result<(), Box<dyn std::error::Error>> {
             let ledger = load_from_text(indoc! {r#"
-                1970-01-01 commodity CNY
+                1970-01-01 commodity USD
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Equity:Open
+
+                2024-01-01 "Deposit"
+                  Assets:Bank 10 USD
+                  Equity:Open
+
+                2024-01-01 price USD 7 CNY
+                2024-02-01 price USD 8 CNY
+            "#});
+
+            let mut operations = ledger.operations();
+            let date = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap().and_hms_opt(0, 0, 0).unwrap();
+            let conversion = Conversion::new("CNY", date);
+
+            let balances = operations.account_target_date_balance("Assets:Bank", date.and_utc(), Some(&conversion))?;
+            assert_eq!(BigDecimal::from(10), balances[0].balance_number);
+            assert_eq!(Some(Amount::new(BigDecimal::from(70), "CNY")), balances[0].converted);
+
+            let balances = operations.account_target_date_balance("Assets:Bank", date.and_utc(), None)?;
+            assert_eq!(None, balances[0].converted);
+
+            let rows = operations.static_duration(date.and_utc() - Duration::days(30), date.and_utc(), Some(&conversion))?;
+            let assets = rows.iter().find(|row| row.account_type.eq("Assets")).unwrap();
+            assert_eq!(Some(Amount::new(BigDecimal::from(70), "CNY")), assets.converted);
+
+            let conversion = Conversion::new("EUR", date);
+            let balances = operations.account_target_date_balance("Assets:Bank", date.and_utc(), Some(&conversion))?;
+            assert_eq!(None, balances[0].converted, "balance without exchange rate should not be converted");
+            Ok(())
+        }
+
+        #[test]
+        fn should_bucket_balances_over_time() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Income:Salary
+
+                2024-01-10 "Salary"
+                  Assets:Bank 100 CNY
+                  Income:Salary
+
+                2024-01-20 "Salary"
+                  Assets:Bank 100 CNY
+                  Income:Salary
+
+                2024-03-10 "Salary"
+                  Assets:Bank 100 CNY
+                  Income:Salary
             "#});
 
             let operations = ledger.operations();
-            let commodity = operations.commodity("USD")?;
-            assert!(commodity.is_none());
+            let series = operations.account_balances_over_time(BalanceInterval::Month)?;
+            let bank = series.iter().find(|it| it.account.eq("Assets:Bank")).unwrap();
+            let month = |month: u32| NaiveDate::from_ymd_opt(2024, month, 1).unwrap();
+            assert_eq!(3, bank.balances.len());
+            assert_eq!(BigDecimal::from(200), bank.balances[&month(1)]);
+            assert_eq!(
+                BigDecimal::from(200),
+                bank.balances[&month(2)],
+                "period without postings should carry the previous balance"
+            );
+            assert_eq!(BigDecimal::from(300), bank.balances[&month(3)]);
+
+            let series = operations.account_balances_over_time(BalanceInterval::Quarter)?;
+            let salary = series.iter().find(|it| it.account.eq("Income:Salary")).unwrap();
+            assert_eq!(1, salary.balances.len());
+            assert_eq!(BigDecimal::from(-300), salary.balances[&month(1)]);
             Ok(())
         }
 
         #[test]
-        fn should_get_correct_precision_given_override_default_precision() -> Result<(), Box<dyn std::error::Error>> {
+        fn should_value_net_worth_by_market_and_cost() -> Result<(), Box<dyn std::error::Error>> {
             let ledger = load_from_text(indoc! {r#"
-                option "default_commodity_precision" "3"
-                1970-01-01 commodity CNY
+                1970-01-01 commodity AAPL
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Assets:Stock
+                1970-01-01 open Liabilities:CreditCard
+                1970-01-01 open Equity:Open
+
+                2024-01-10 "Open"
+                  Assets:Bank 1000 CNY
+                  Equity:Open
+
+                2024-01-11 "Buy"
+                  Assets:Stock 2 AAPL {100 CNY}
+                  Assets:Bank
+
+                2024-02-10 "Dinner"
+                  Liabilities:CreditCard -50 CNY
+                  Equity:Open
+
+                2024-01-20 price AAPL 150 CNY
             "#});
 
             let operations = ledger.operations();
-            let commodity = operations.commodity("CNY")?.unwrap();
-            assert_eq!("CNY", commodity.name);
-            assert_eq!(3, commodity.precision);
-            assert_eq!(None, commodity.prefix);
-            assert_eq!(None, commodity.suffix);
+            let series = operations.net_worth_series(BalanceInterval::Month, "CNY")?;
+            assert_eq!(2, series.len());
+            assert_eq!(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(), series[0].date);
+            assert_eq!(BigDecimal::from(1100), series[0].market_value.number);
+            assert_eq!(BigDecimal::from(1000), series[0].cost_value.number);
+            assert_eq!(BigDecimal::from(1050), series[1].market_value.number);
+            assert_eq!(BigDecimal::from(950), series[1].cost_value.number);
             Ok(())
         }
 
         #[test]
-        fn should_get_info_from_meta() -> Result<(), Box<dyn std::error::Error>> {
+        fn should_query_journal_and_balance_by_posting_index() -> Result<(), Box<dyn std::error::Error>> {
             let ledger = load_from_text(indoc! {r#"
-                1970-01-01 commodity CNY
-                  precision: "3"
-                  prefix: "¥"
-                  suffix: "CNY"
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Expenses:Food
+
+                2024-01-03 "Lunch"
+                  Assets:Bank -10 CNY
+                  Expenses:Food
+
+                2024-01-01 "Salary"
+                  Assets:Bank 100 CNY
+                  Expenses:Food
+
+                2024-01-02 "Dinner"
+                  Assets:Bank -20 CNY
+                  Assets:Bank -1 USD
+                  Expenses:Food 20 CNY
+                  Expenses:Food 1 USD
             "#});
 
             let operations = ledger.operations();
-            let commodity = operations.commodity("CNY")?.unwrap();
-            assert_eq!("CNY", commodity.name);
-            assert_eq!(3, commodity.precision);
-            assert_eq!("¥", commodity.prefix.unwrap());
-            assert_eq!("CNY", commodity.suffix.unwrap());
+            let date = |day: u32| NaiveDate::from_ymd_opt(2024, 1, day).unwrap().and_hms_opt(0, 0, 0).unwrap().and_utc();
+
+            let journal = operations.account_journal("Assets:Bank", date(2)..date(3))?;
+            assert_eq!(2, journal.len());
+            assert!(journal.iter().all(|it| it.payee.as_deref() == Some("Dinner")));
+            assert_eq!("CNY", journal[0].inferred_unit_commodity);
+            assert_eq!("USD", journal[1].inferred_unit_commodity);
+
+            let journal = operations.account_journal("Assets:Bank", date(2)..)?;
+            assert_eq!(
+                vec![Some("Lunch"), Some("Dinner"), Some("Dinner")],
+                journal.iter().map(|it| it.payee.as_deref()).collect::<Vec<_>>()
+            );
+            assert_eq!(0, operations.account_journal("Assets:Bank", date(3)..date(2))?.len());
+
+            let balances = operations.account_target_date_balance("Assets:Bank", date(2), None)?;
+            assert_eq!(
+                vec![(BigDecimal::from(80), "CNY".to_owned()), (BigDecimal::from(-1), "USD".to_owned())],
+                balances.into_iter().map(|it| (it.balance_number, it.balance_commodity)).collect::<Vec<_>>()
+            );
             Ok(())
         }
+    }
+
+    mod trial_balance {
+        use bigdecimal::BigDecimal;
+        use chrono::NaiveDate;
+        use indoc::indoc;
+
+        use crate::reports::{balance_sheet, income_statement, ReportOptions};
+        use crate::test::load_from_text;
+
         #[test]
-        fn should_meta_precision_have_higher_priority() -> Result<(), Box<dyn std::error::Error>> {
+        fn should_net_debit_and_credit_to_zero() -> Result<(), Box<dyn std::error::Error>> {
             let ledger = load_from_text(indoc! {r#"
-                option "default_commodity_precision" "3"
-                1970-01-01 commodity CNY
-                  precision: "4"
+                1970-01-01 commodity USD
+                1970-01-01 commodity AAPL
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Assets:Stock
+                1970-01-01 open Income:Salary
+
+                2024-01-01 "Salary"
+                  Assets:Bank 1000 USD
+                  Income:Salary
+
+                2024-01-02 "Buy"
+                  Assets:Stock 2 AAPL {100 USD}
+                  Assets:Bank
+
+                2024-02-01 "Salary"
+                  Assets:Bank 1000 USD
+                  Income:Salary
             "#});
 
             let operations = ledger.operations();
-            let commodity = operations.commodity("CNY")?.unwrap();
-            assert_eq!("CNY", commodity.name);
-            assert_eq!(4, commodity.precision);
-            assert_eq!(None, commodity.prefix);
-            assert_eq!(None, commodity.suffix);
+            let date = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap().and_hms_opt(0, 0, 0).unwrap().and_utc();
+            let trial_balance = operations.trial_balance(date)?;
+
+            assert!(trial_balance.balanced);
+            let row = |account: &str| trial_balance.accounts.iter().find(|row| row.account.eq(account)).unwrap();
+            assert_eq!(BigDecimal::from(800), row("Assets:Bank").debit);
+            assert_eq!(BigDecimal::from(200), row("Assets:Stock").debit);
+            assert_eq!("USD", row("Assets:Stock").commodity, "balance should be measured by cost");
+            assert_eq!(BigDecimal::from(1000), row("Income:Salary").credit);
+            assert_eq!(BigDecimal::from(0), row("Income:Salary").debit);
+
+            assert_eq!(1, trial_balance.totals.len());
+            assert_eq!(BigDecimal::from(1000), trial_balance.totals[0].debit);
+            assert_eq!(BigDecimal::from(1000), trial_balance.totals[0].credit);
             Ok(())
         }
 
         #[test]
-        fn should_work_with_same_default_operating_currency_and_commodity() -> Result<(), Box<dyn std::error::Error>> {
+        fn should_eliminate_inter_entity_accounts_in_consolidation() -> Result<(), Box<dyn std::error::Error>> {
             let ledger = load_from_text(indoc! {r#"
-                option "operating_currency" "CNY"
-                1970-01-01 commodity CNY
-                  precision: "4"
+                1970-01-01 commodity USD
+                1970-01-01 open Assets:HoldCo:Bank
+                  entity: "HoldCo"
+                1970-01-01 open Assets:HoldCo:DueFrom:OpCo
+                  entity: "HoldCo"
+                1970-01-01 open Income:HoldCo:Management
+                  entity: "HoldCo"
+                1970-01-01 open Assets:OpCo:Bank
+                  entity: "OpCo"
+                1970-01-01 open Liabilities:OpCo:DueTo:HoldCo
+                  entity: "OpCo"
+                1970-01-01 open Expenses:OpCo:Management
+                  entity: "OpCo"
+                1970-01-01 open Income:OpCo:Sales
+                  entity: "OpCo"
+
+                1970-01-01 custom "elimination" Assets:HoldCo:DueFrom Liabilities:OpCo:DueTo Income:HoldCo:Management Expenses:OpCo:Management
+
+                2024-01-01 "Loan to OpCo"
+                  Assets:HoldCo:Bank -500 USD
+                  Assets:HoldCo:DueFrom:OpCo 500 USD
+                  Assets:OpCo:Bank 500 USD
+                  Liabilities:OpCo:DueTo:HoldCo -500 USD
+
+                2024-01-02 "Management fee"
+                  Expenses:OpCo:Management 100 USD
+                  Income:HoldCo:Management -100 USD
+
+                2024-01-03 "Sales"
+                  Assets:OpCo:Bank 300 USD
+                  Income:OpCo:Sales
             "#});
 
             let operations = ledger.operations();
-            let commodity = operations.commodity("CNY")?.unwrap();
-            assert_eq!("CNY", commodity.name);
-            assert_eq!(4, commodity.precision);
-            assert_eq!(None, commodity.prefix);
-            assert_eq!(None, commodity.suffix);
+            assert_eq!(0, operations.read().errors.len());
+            let date = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap().and_hms_opt(0, 0, 0).unwrap().and_utc();
+
+            let holdco = operations.entity_trial_balance(date, "HoldCo")?;
+            let accounts = holdco.accounts.iter().map(|row| row.account.as_str()).collect::<Vec<_>>();
+            assert_eq!(vec!["Assets:HoldCo:Bank", "Assets:HoldCo:DueFrom:OpCo", "Income:HoldCo:Management"], accounts);
+            assert!(!holdco.balanced, "the entity is not balanced by itself given inter-entity transactions");
+
+            let consolidated = operations.consolidated_trial_balance(date)?;
+            let accounts = consolidated.accounts.iter().map(|row| row.account.as_str()).collect::<Vec<_>>();
+            assert_eq!(vec!["Assets:HoldCo:Bank", "Assets:OpCo:Bank", "Income:OpCo:Sales"], accounts);
+            assert!(consolidated.balanced);
+            assert_eq!(BigDecimal::from(800), consolidated.totals[0].credit);
+
+            let from = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap().and_utc();
+            let options = ReportOptions {
+                entity: Some("HoldCo".to_owned()),
+                ..Default::default()
+            };
+            let statement = income_statement(&operations, from, date, &options)?;
+            assert_eq!(BigDecimal::from(-100), statement.income.amount("USD"));
+            assert_eq!(BigDecimal::from(0), statement.expenses.amount("USD"));
+
+            let options = ReportOptions {
+                consolidated: true,
+                ..Default::default()
+            };
+            let statement = income_statement(&operations, from, date, &options)?;
+            assert_eq!(BigDecimal::from(-300), statement.income.amount("USD"));
+            assert!(statement.expenses.find("Expenses:OpCo:Management").is_none());
+            let sheet = balance_sheet(&operations, date, &options)?;
+            assert_eq!(BigDecimal::from(300), sheet.assets.amount("USD"));
+            assert!(sheet.liabilities.amounts.is_empty());
+            assert_eq!(BigDecimal::from(300), sheet.net_income.amount("USD"));
             Ok(())
         }
     }
-    mod error {
+    mod pad {
+        use bigdecimal::BigDecimal;
         use indoc::indoc;
         use zhang_ast::error::ErrorKind;
 
         use crate::test::load_from_text;
 
-        mod close_non_zero_account {
-            use indoc::indoc;
-            use zhang_ast::error::ErrorKind;
+        #[test]
+        fn should_pad_at_pad_date_given_unmatched_balance() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity CNY
+                1970-01-01 open Assets:A
+                1970-01-01 open Expenses:B
+                1970-01-01 open Equity:Opening-Balances
 
-            use crate::test::load_from_text;
+                2023-01-01 pad Assets:A Equity:Opening-Balances
 
-            #[test]
-            fn should_not_raise_error() -> Result<(), Box<dyn std::error::Error>> {
-                let ledger = load_from_text(indoc! {r#"
-                    1970-01-01 open Assets:MyCard
-                    1970-01-03 close Assets:MyCard
-                "#});
+                2023-01-02 "Shopping" ""
+                  Assets:A -30 CNY
+                  Expenses:B
 
-                let mut operations = ledger.operations();
-                let errors = operations.errors()?;
-                assert_eq!(errors.len(), 0);
-                Ok(())
-            }
-            #[test]
-            fn should_raise_error() -> Result<(), Box<dyn std::error::Error>> {
-                let ledger = load_from_text(indoc! {r#"
-                    1970-01-01 open Assets:MyCard
-                    1970-01-01 open Expenses:Lunch
-                    1970-01-02 "KFC" "Crazy Thursday"
-                      Assets:MyCard -50 CNY
-                      Expenses:Lunch 50 CNY
+                2023-01-05 balance Assets:A 970 CNY
+            "#});
 
-                    1970-01-03 close Assets:MyCard
-                "#});
+            let mut operations = ledger.operations();
+            assert_eq!(0, operations.errors()?.len());
+
+            let balance = operations.single_account_latest_balances("Assets:A")?.pop().unwrap();
+            assert_eq!(balance.balance_number, BigDecimal::from(970));
+            let balance = operations.single_account_latest_balances("Equity:Opening-Balances")?.pop().unwrap();
+            assert_eq!(balance.balance_number, BigDecimal::from(-1000));
+
+            let journals = operations.account_journals("Assets:A")?;
+            let padding = journals.iter().find(|it| it.payee.as_deref() == Some("Balance Pad")).unwrap();
+            assert_eq!("2023-01-01", padding.datetime.date().to_string());
+            assert_eq!(padding.inferred_unit_number, BigDecimal::from(1000));
+            let shopping = journals.iter().find(|it| it.payee.as_deref() == Some("Shopping")).unwrap();
+            assert_eq!(shopping.account_after_number, BigDecimal::from(970));
+            Ok(())
+        }
 
-                let mut operations = ledger.operations();
-                let mut errors = operations.errors()?;
-                assert_eq!(errors.len(), 1);
-                let error = errors.pop().unwrap();
-                assert_eq!(error.error_type, ErrorKind::CloseNonZeroAccount);
-                Ok(())
-            }
+        #[test]
+        fn should_pad_with_balance_at_pad_date() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity CNY
+                1970-01-01 open Assets:A
+                1970-01-01 open Expenses:B
+                1970-01-01 open Equity:Opening-Balances
+
+                2022-12-01 "Salary" ""
+                  Assets:A 100 CNY
+                  Equity:Opening-Balances
+
+                2023-01-01 pad Assets:A Equity:Opening-Balances
+
+                2023-01-01 "Lunch" ""
+                  Assets:A -20 CNY
+                  Expenses:B
+
+                2023-01-02 "Shopping" ""
+                  Assets:A -30 CNY
+                  Expenses:B
+
+                2023-01-03 "Shopping" ""
+                  Assets:A -50 CNY
+                  Expenses:B
+
+                2023-01-05 balance Assets:A 970 CNY
+            "#});
+
+            let mut operations = ledger.operations();
+            assert_eq!(0, operations.errors()?.len());
+
+            let journals = operations.account_journals("Assets:A")?;
+            let padding = journals.iter().find(|it| it.payee.as_deref() == Some("Balance Pad")).unwrap();
+            assert_eq!(padding.inferred_unit_number, BigDecimal::from(970));
+            assert_eq!(padding.account_after_number, BigDecimal::from(1050));
+            let balance = operations.single_account_latest_balances("Assets:A")?.pop().unwrap();
+            assert_eq!(balance.balance_number, BigDecimal::from(970));
+            let journals = operations.account_journals("Equity:Opening-Balances")?;
+            let padding = journals.iter().find(|it| it.payee.as_deref() == Some("Balance Pad")).unwrap();
+            assert_eq!(padding.account_after_number, BigDecimal::from(-1070));
+            Ok(())
         }
 
         #[test]
-        fn should_raise_non_balance_error_only() -> Result<(), Box<dyn std::error::Error>> {
+        fn should_only_be_consumed_by_next_balance() -> Result<(), Box<dyn std::error::Error>> {
             let ledger = load_from_text(indoc! {r#"
-                    1970-01-01 open Assets:MyCard CNY
-                    1970-01-03 balance Assets:MyCard 10 CNY
-                "#});
+                1970-01-01 commodity CNY
+                1970-01-01 open Assets:A
+                1970-01-01 open Equity:Opening-Balances
+
+                2023-01-01 pad Assets:A Equity:Opening-Balances
+                2023-01-05 balance Assets:A 100 CNY
+                2023-01-06 balance Assets:A 200 CNY
+            "#});
 
             let mut operations = ledger.operations();
             let mut errors = operations.errors()?;
-            assert_eq!(errors.len(), 1);
-            let domain = errors.pop().unwrap();
-            assert_eq!(domain.error_type, ErrorKind::AccountBalanceCheckError);
-            assert_eq!(domain.metas.get("account_name").unwrap(), "Assets:MyCard");
+            assert_eq!(1, errors.len());
+            assert_eq!(ErrorKind::AccountBalanceCheckError, errors.pop().unwrap().error_type);
             Ok(())
         }
     }
-    mod timezone {
+    mod rename {
+        use bigdecimal::BigDecimal;
         use indoc::indoc;
+        use zhang_ast::error::ErrorKind;
 
         use crate::test::load_from_text;
 
         #[test]
-        fn should_get_system_timezone() -> Result<(), Box<dyn std::error::Error>> {
+        fn should_rename_historical_account_and_its_sub_accounts() -> Result<(), Box<dyn std::error::Error>> {
             let ledger = load_from_text(indoc! {r#"
-                    1970-01-01 open Assets:MyCard CNY
-                "#});
+                rename Assets:OldBank Assets:NewBank
 
-            let operations = ledger.operations();
-            let timezone: String = operations.option("timezone")?.unwrap();
-            assert_eq!(iana_time_zone::get_timezone().unwrap(), timezone);
+                1970-01-01 open Assets:NewBank:Checking
+                1970-01-01 open Assets:OldBankOfChina
+                1970-01-01 open Expenses:Food
+
+                2023-01-01 "KFC" ""
+                  Assets:OldBank:Checking -50 CNY
+                  Expenses:Food
+
+                2023-01-02 balance Assets:OldBank:Checking -50 CNY
+            "#});
+
+            let mut operations = ledger.operations();
+            assert_eq!(0, operations.errors()?.len());
+            assert!(!operations.exist_account("Assets:OldBank:Checking")?);
+            assert!(operations.exist_account("Assets:OldBankOfChina")?);
+
+            let balance = operations.single_account_latest_balances("Assets:NewBank:Checking")?.pop().unwrap();
+            assert_eq!(balance.balance_number, BigDecimal::from(-50));
             Ok(())
         }
 
         #[test]
-        fn should_fallback_to_use_system_timezone_given_invalid_timezone() -> Result<(), Box<dyn std::error::Error>> {
+        fn should_follow_chained_renames() -> Result<(), Box<dyn std::error::Error>> {
             let ledger = load_from_text(indoc! {r#"
-                    option "timezone" "MYZone"
-                "#});
+                rename Assets:A Assets:B
+                rename Assets:B Assets:C
 
-            let operations = ledger.operations();
-            let timezone: String = operations.option("timezone")?.unwrap();
-            assert_eq!(iana_time_zone::get_timezone().unwrap(), timezone);
+                1970-01-01 open Assets:A
+            "#});
+
+            let mut operations = ledger.operations();
+            assert_eq!(0, operations.errors()?.len());
+            assert!(operations.exist_account("Assets:C")?);
+            assert!(!operations.exist_account("Assets:B")?);
             Ok(())
         }
+
         #[test]
-        fn should_parse_user_timezone() -> Result<(), Box<dyn std::error::Error>> {
+        fn should_raise_error_given_rename_cycle_or_duplicated_rename() -> Result<(), Box<dyn std::error::Error>> {
             let ledger = load_from_text(indoc! {r#"
-                    option "timezone" "Antarctica/South_Pole"
-                "#});
+                rename Assets:A Assets:B
+                rename Assets:B Assets:A
+                rename Assets:A Assets:C
 
-            let operations = ledger.operations();
-            let timezone: String = operations.option("timezone")?.unwrap();
-            assert_eq!("Antarctica/South_Pole", timezone);
-            assert_eq!(ledger.options.timezone, "Antarctica/South_Pole".parse().unwrap());
+                1970-01-01 open Assets:A
+            "#});
+
+            let mut operations = ledger.operations();
+            let errors = operations.errors()?;
+            assert_eq!(2, errors.len());
+            assert_eq!(ErrorKind::AccountRenameCycle, errors[0].error_type);
+            assert_eq!(ErrorKind::DuplicatedAccountRename, errors[1].error_type);
+            assert!(operations.exist_account("Assets:B")?);
+            Ok(())
+        }
+    }
+
+    mod reconcile {
+        use std::str::FromStr;
+
+        use bigdecimal::BigDecimal;
+        use chrono::NaiveDate;
+        use indoc::indoc;
+        use zhang_ast::amount::Amount;
+        use zhang_ast::error::ErrorKind;
+
+        use crate::ledger::Ledger;
+        use crate::store::ReconcileState;
+        use crate::test::load_from_text;
+
+        #[test]
+        fn should_reconcile_cleared_postings_against_statement_balance() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Expenses:Food
+                2024-01-02 "A" ""
+                  Assets:Bank -10 CNY
+                  Expenses:Food
+                2024-01-03 ! "B" ""
+                  Assets:Bank -20 CNY
+                  Expenses:Food
+                2024-01-04 "C" ""
+                  Assets:Bank -30 CNY
+                    reconcile: "pending"
+                  Expenses:Food
+                2024-01-05 "D" ""
+                  Assets:Bank -5 CNY
+                    statement-date: "2023-12-31"
+                  Expenses:Food
+                2024-02-01 "E" ""
+                  Assets:Bank -40 CNY
+                  Expenses:Food
+                2024-02-02 "F" ""
+                  Assets:Bank -1 CNY
+                    reconcile: "cleared"
+                  Expenses:Food
+            "#});
+
+            let bank_state = |ledger: &Ledger, payee: &str| {
+                let store = ledger.store.read().unwrap();
+                let trx = store.transactions.values().find(|it| it.payee.as_deref() == Some(payee)).unwrap();
+                let posting = trx.postings.iter().find(|it| it.account.name() == "Assets:Bank").unwrap();
+                (posting.reconcile_state, posting.statement_date)
+            };
+            assert_eq!((ReconcileState::Cleared, None), bank_state(&ledger, "A"));
+            assert_eq!((ReconcileState::Pending, None), bank_state(&ledger, "B"));
+            assert_eq!((ReconcileState::Pending, None), bank_state(&ledger, "C"));
+            assert_eq!((ReconcileState::Reconciled, NaiveDate::from_ymd_opt(2023, 12, 31)), bank_state(&ledger, "D"));
+
+            let statement_date = NaiveDate::from_ymd_opt(2024, 1, 31).unwrap();
+            let mismatched = ledger.reconcile("Assets:Bank", statement_date, &Amount::new(BigDecimal::from(-20), "CNY"));
+            assert!(mismatched.is_err());
+            assert_eq!((ReconcileState::Cleared, None), bank_state(&ledger, "A"));
+
+            let reconciled = ledger.reconcile("Assets:Bank", statement_date, &Amount::new(BigDecimal::from_str("-15")?, "CNY"))?;
+            assert_eq!(1, reconciled.len());
+            assert_eq!((ReconcileState::Reconciled, Some(statement_date)), bank_state(&ledger, "A"));
+            assert_eq!((ReconcileState::Pending, None), bank_state(&ledger, "B"));
+            assert_eq!((ReconcileState::Cleared, None), bank_state(&ledger, "E"));
+
+            let reloaded = Ledger::load_with_data_source(ledger.entry.0.clone(), ledger.entry.1.clone(), ledger.data_source.clone())?;
+            assert_eq!((ReconcileState::Reconciled, Some(statement_date)), bank_state(&reloaded, "A"));
+            assert_eq!((ReconcileState::Pending, None), bank_state(&reloaded, "C"));
+            assert_eq!((ReconcileState::Reconciled, NaiveDate::from_ymd_opt(2023, 12, 31)), bank_state(&reloaded, "D"));
+            assert_eq!((ReconcileState::Cleared, None), bank_state(&reloaded, "E"));
+
+            let statement_date = NaiveDate::from_ymd_opt(2024, 2, 29).unwrap();
+            let reconciled = reloaded.reconcile("Assets:Bank", statement_date, &Amount::new(BigDecimal::from(-56), "CNY"))?;
+            assert_eq!(2, reconciled.len());
+            let reloaded = Ledger::load_with_data_source(ledger.entry.0.clone(), ledger.entry.1.clone(), ledger.data_source.clone())?;
+            assert_eq!((ReconcileState::Reconciled, Some(statement_date)), bank_state(&reloaded, "E"));
+            assert_eq!((ReconcileState::Reconciled, Some(statement_date)), bank_state(&reloaded, "F"));
+            Ok(())
+        }
+
+        #[test]
+        fn should_assert_balance_of_reconciled_postings() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Expenses:Food
+                2024-01-02 "A" ""
+                  Assets:Bank -10 CNY
+                    reconcile: "reconciled"
+                  Expenses:Food
+                2024-01-03 "B" ""
+                  Assets:Bank -20 CNY
+                  Expenses:Food
+                2024-01-10 balance Assets:Bank -10 CNY
+                  reconciled: "true"
+                2024-01-10 balance Assets:Bank -30 CNY
+                2024-01-11 balance Assets:Bank -30 CNY
+                  reconciled: "true"
+            "#});
+
+            let mut operations = ledger.operations();
+            let errors = operations.errors()?;
+            assert_eq!(1, errors.len());
+            assert_eq!(ErrorKind::AccountReconciledBalanceCheckError, errors[0].error_type);
+            assert_eq!("-10", errors[0].metas.get("reconciled_amount").unwrap());
+            Ok(())
+        }
+    }
+
+    mod budget {
+        use std::str::FromStr;
+
+        use bigdecimal::BigDecimal;
+        use indoc::indoc;
+        use zhang_ast::error::ErrorKind;
+
+        use crate::domains::schemas::BudgetReportInterval;
+        use crate::ledger::Ledger;
+        use crate::test::load_from_text;
+
+        fn ledger_with_rollover(rollover: &str) -> Ledger {
+            load_from_text(&format!(
+                indoc! {r#"
+                    1970-01-01 commodity CNY
+                    1970-01-01 open Assets:BankCard
+                    1970-01-01 open Expenses:Food
+                      budget: food
+                    1970-01-01 open Expenses:Rent
+                      budget: rent
+
+                    2023-11-01 budget food CNY
+                      rollover: "{rollover}"
+                    2023-11-01 budget rent CNY
+                      rollover: "{rollover}"
+
+                    2023-11-01 budget-add food 100 CNY
+                    2023-11-01 budget-add rent 100 CNY
+
+                    2023-11-02 "KFC"
+                      Assets:BankCard -40 CNY
+                      Expenses:Food
+
+                    2023-11-03 "Landlord"
+                      Assets:BankCard -130 CNY
+                      Expenses:Rent
+
+                    2023-12-01 budget-add food 100 CNY
+                    2023-12-01 budget-add rent 100 CNY
+                "#},
+                rollover = rollover
+            ))
+        }
+
+        fn available(ledger: &Ledger, name: &str, interval: u32) -> BigDecimal {
+            ledger.operations().budget_available_amount(name, interval).unwrap().unwrap().number
+        }
+
+        #[test]
+        fn should_carry_unused_and_overspending_by_default() {
+            let ledger = ledger_with_rollover("carry");
+            assert_eq!(BigDecimal::from(60), available(&ledger, "food", 202311));
+            assert_eq!(BigDecimal::from(-30), available(&ledger, "rent", 202311));
+            assert_eq!(BigDecimal::from(160), available(&ledger, "food", 202312));
+            assert_eq!(BigDecimal::from(70), available(&ledger, "rent", 202312));
+        }
+
+        #[test]
+        fn should_carry_unused_only() {
+            let ledger = ledger_with_rollover("carry_positive");
+            assert_eq!(BigDecimal::from(160), available(&ledger, "food", 202312));
+            assert_eq!(BigDecimal::from(100), available(&ledger, "rent", 202312));
+        }
+
+        #[test]
+        fn should_carry_overspending_only() {
+            let ledger = ledger_with_rollover("carry_negative");
+            assert_eq!(BigDecimal::from(100), available(&ledger, "food", 202312));
+            assert_eq!(BigDecimal::from(70), available(&ledger, "rent", 202312));
+        }
+
+        #[test]
+        fn should_report_budgeted_and_actual_by_month() {
+            let ledger = ledger_with_rollover("carry");
+            let report = ledger.operations().budget_report(BudgetReportInterval::Month).unwrap();
+            assert_eq!(4, report.len());
+
+            let rent = report.iter().find(|it| it.name == "rent" && it.period == "2023-11").unwrap();
+            assert_eq!(BigDecimal::from(100), rent.budgeted_amount.number);
+            assert_eq!(BigDecimal::from(130), rent.actual_amount.number);
+            assert_eq!(BigDecimal::from(-30), rent.variance.number);
+            assert_eq!(Some(BigDecimal::from(130)), rent.percent_consumed);
+
+            let food = report.iter().find(|it| it.name == "food" && it.period == "2023-12").unwrap();
+            assert_eq!(BigDecimal::from(100), food.budgeted_amount.number);
+            assert_eq!(BigDecimal::from(0), food.actual_amount.number);
+            assert_eq!(Some(BigDecimal::from(0)), food.percent_consumed);
+        }
+
+        #[test]
+        fn should_report_budgeted_and_actual_by_quarter() {
+            let ledger = ledger_with_rollover("carry");
+            let report = ledger.operations().budget_report(BudgetReportInterval::Quarter).unwrap();
+            assert_eq!(2, report.len());
+
+            let food = &report[0];
+            assert_eq!("food", food.name);
+            assert_eq!("2023-Q4", food.period);
+            assert_eq!(BigDecimal::from(200), food.budgeted_amount.number);
+            assert_eq!(BigDecimal::from(40), food.actual_amount.number);
+            assert_eq!(BigDecimal::from(160), food.variance.number);
+            assert_eq!(Some(BigDecimal::from(20)), food.percent_consumed);
+        }
+
+        #[test]
+        fn should_reset_every_month() {
+            let ledger = ledger_with_rollover("reset");
+            assert_eq!(BigDecimal::from(100), available(&ledger, "food", 202312));
+            assert_eq!(BigDecimal::from(100), available(&ledger, "rent", 202312));
+            assert_eq!(BigDecimal::from(0), available(&ledger, "rent", 202401));
+        }
+
+        #[test]
+        fn should_report_invalid_rollover_and_carry_by_default() {
+            let ledger = ledger_with_rollover("keep");
+            let errors = ledger.operations().errors().unwrap();
+            assert_eq!(2, errors.len());
+            assert_eq!(ErrorKind::InvalidBudgetRollover, errors[0].error_type);
+            assert_eq!(Some("food"), errors[0].metas.get("budget_name").map(String::as_str));
+            assert_eq!(Some("keep"), errors[0].metas.get("rollover").map(String::as_str));
+            assert_eq!(BigDecimal::from(160), available(&ledger, "food", 202312));
+            assert_eq!(BigDecimal::from(70), available(&ledger, "rent", 202312));
+        }
+
+        fn multi_currency_activity(convert: &str) -> BigDecimal {
+            let ledger = load_from_text(&format!(
+                indoc! {r#"
+                    option "operating_currency" "CNY"
+                    option "budget_convert_to_operating_currency" "{convert}"
+                    1970-01-01 commodity CNY
+                    1970-01-01 commodity USD
+                    1970-01-01 commodity HKD
+                    1970-01-01 open Assets:BankCard
+                    1970-01-01 open Expenses:Travel
+                      budget: travel
+
+                    2023-11-01 price USD 6 CNY
+                    2023-11-10 price USD 7 CNY
+                    2023-11-01 price HKD 0.9 CNY
+
+                    2023-11-01 budget travel CNY
+                    2023-11-01 budget-add travel 1000 CNY
+
+                    2023-11-15 "Hotel"
+                      Assets:BankCard -10 USD
+                      Expenses:Travel
+
+                    2023-11-16 "Taxi"
+                      Assets:BankCard -100 HKD
+                      Expenses:Travel
+
+                    2023-11-17 "Lunch"
+                      Assets:BankCard -30 CNY
+                      Expenses:Travel
+                "#},
+                convert = convert
+            ));
+            let operations = ledger.operations();
+            operations.budget_month_detail("travel", 202311).unwrap().unwrap().activity_amount.number
+        }
+
+        #[test]
+        fn should_convert_activity_into_budget_commodity_at_posting_date() {
+            assert_eq!(BigDecimal::from(190), multi_currency_activity("true"));
+        }
+
+        #[test]
+        fn should_not_convert_activity_by_default() {
+            assert_eq!(BigDecimal::from(140), multi_currency_activity("false"));
+        }
+
+        #[test]
+        fn should_convert_activity_via_operating_currency() {
+            let ledger = load_from_text(indoc! {r#"
+                option "operating_currency" "CNY"
+                option "budget_convert_to_operating_currency" "true"
+                1970-01-01 commodity CNY
+                1970-01-01 commodity USD
+                1970-01-01 commodity HKD
+                1970-01-01 open Assets:BankCard
+                1970-01-01 open Expenses:Travel
+                  budget: travel
+
+                2023-11-01 price HKD 0.9 CNY
+                2023-11-01 price CNY 0.125 USD
+
+                2023-11-01 budget travel USD
+                2023-11-16 "Taxi"
+                  Assets:BankCard -100 HKD
+                  Expenses:Travel
+            "#});
+            let operations = ledger.operations();
+            let detail = operations.budget_month_detail("travel", 202311).unwrap().unwrap();
+            assert_eq!(BigDecimal::from_str("11.25").unwrap(), detail.activity_amount.number);
+        }
+    }
+    mod commodity {
+        use indoc::indoc;
+
+        use crate::test::load_from_text;
+
+        #[test]
+        fn should_get_commodity() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity CNY
+            "#});
+
+            let operations = ledger.operations();
+            let commodity = operations.commodity("CNY")?.unwrap();
+            assert_eq!("CNY", commodity.name);
+            assert_eq!(2, commodity.precision);
+            assert_eq!(None, commodity.prefix);
+            assert_eq!(None, commodity.suffix);
+            Ok(())
+        }
+
+        #[test]
+        fn should_not_get_non_exist_commodity() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity CNY
+            "#});
+
+            let operations = ledger.operations();
+            let commodity = operations.commodity("USD")?;
+            assert!(commodity.is_none());
+            Ok(())
+        }
+
+        #[test]
+        fn should_get_correct_precision_given_override_default_precision() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                option "default_commodity_precision" "3"
+                1970-01-01 commodity CNY
+            "#});
+
+            let operations = ledger.operations();
+            let commodity = operations.commodity("CNY")?.unwrap();
+            assert_eq!("CNY", commodity.name);
+            assert_eq!(3, commodity.precision);
+            assert_eq!(None, commodity.prefix);
+            assert_eq!(None, commodity.suffix);
+            Ok(())
+        }
+
+        #[test]
+        fn should_get_info_from_meta() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity CNY
+                  precision: "3"
+                  prefix: "¥"
+                  suffix: "CNY"
+            "#});
+
+            let operations = ledger.operations();
+            let commodity = operations.commodity("CNY")?.unwrap();
+            assert_eq!("CNY", commodity.name);
+            assert_eq!(3, commodity.precision);
+            assert_eq!("¥", commodity.prefix.unwrap());
+            assert_eq!("CNY", commodity.suffix.unwrap());
+            Ok(())
+        }
+        #[test]
+        fn should_meta_precision_have_higher_priority() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                option "default_commodity_precision" "3"
+                1970-01-01 commodity CNY
+                  precision: "4"
+            "#});
+
+            let operations = ledger.operations();
+            let commodity = operations.commodity("CNY")?.unwrap();
+            assert_eq!("CNY", commodity.name);
+            assert_eq!(4, commodity.precision);
+            assert_eq!(None, commodity.prefix);
+            assert_eq!(None, commodity.suffix);
+            Ok(())
+        }
+
+        #[test]
+        fn should_work_with_same_default_operating_currency_and_commodity() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                option "operating_currency" "CNY"
+                1970-01-01 commodity CNY
+                  precision: "4"
+            "#});
+
+            let operations = ledger.operations();
+            let commodity = operations.commodity("CNY")?.unwrap();
+            assert_eq!("CNY", commodity.name);
+            assert_eq!(4, commodity.precision);
+            assert_eq!(None, commodity.prefix);
+            assert_eq!(None, commodity.suffix);
+            Ok(())
+        }
+    }
+    mod document {
+        use std::sync::Arc;
+
+        use indoc::indoc;
+        use sha256::digest;
+        use tempfile::tempdir;
+
+        use crate::data_source::LocalFileSystemDataSource;
+        use crate::data_type::text::ZhangDataType;
+        use crate::ledger::Ledger;
+
+        fn ledger_with_documents() -> Ledger {
+            let temp_dir = tempdir().unwrap().into_path();
+            std::fs::write(temp_dir.join("statement.pdf"), "statement").unwrap();
+            std::fs::write(temp_dir.join("statement-copy.pdf"), "statement").unwrap();
+            std::fs::write(temp_dir.join("receipt.pdf"), "receipt").unwrap();
+            std::fs::write(
+                temp_dir.join("example.zhang"),
+                indoc! {r#"
+                    1970-01-01 open Assets:BankCard
+                    1970-01-01 open Liabilities:CreditCard
+                    2023-01-01 document Assets:BankCard "statement.pdf"
+                    2023-01-02 document Liabilities:CreditCard "statement-copy.pdf"
+                    2023-01-03 document Assets:BankCard "receipt.pdf"
+                    2023-01-04 document Assets:BankCard "missing.pdf"
+                "#},
+            )
+            .unwrap();
+            let source = LocalFileSystemDataSource::new(ZhangDataType {});
+            Ledger::load_with_data_source(temp_dir, "example.zhang".to_string(), Arc::new(source)).unwrap()
+        }
+
+        #[test]
+        fn should_compute_document_content_hash() {
+            let ledger = ledger_with_documents();
+            let operations = ledger.operations();
+
+            let documents = operations.documents_by_hash(digest("receipt")).unwrap();
+            assert_eq!(1, documents.len());
+            assert_eq!("receipt.pdf", documents[0].path);
+
+            let store = operations.read();
+            let missing = store.documents.iter().find(|it| it.path.eq("missing.pdf")).unwrap();
+            assert_eq!(None, missing.hash);
+        }
+
+        #[test]
+        fn should_detect_duplicated_documents_across_accounts() {
+            let ledger = ledger_with_documents();
+            let operations = ledger.operations();
+
+            let duplicated = operations.duplicated_documents().unwrap();
+            assert_eq!(1, duplicated.len());
+            let (hash, documents) = &duplicated[0];
+            assert_eq!(&digest("statement"), hash);
+            assert_eq!(
+                vec!["statement.pdf", "statement-copy.pdf"],
+                documents.iter().map(|it| it.path.as_str()).collect::<Vec<_>>()
+            );
+        }
+
+        #[test]
+        fn should_search_documents_by_content() {
+            let temp_dir = tempdir().unwrap().into_path();
+            std::fs::write(temp_dir.join("receipt.txt"), "Coffee Shop\nLatte 32 CNY").unwrap();
+            std::fs::write(temp_dir.join("invoice.txt"), "Book Store\nRust Programming 99 CNY").unwrap();
+            std::fs::write(
+                temp_dir.join("example.zhang"),
+                indoc! {r#"
+                    option "features.document_index" "true"
+                    1970-01-01 open Assets:BankCard
+                    2023-01-01 document Assets:BankCard "receipt.txt"
+                    2023-01-02 document Assets:BankCard "invoice.txt"
+                "#},
+            )
+            .unwrap();
+            let source = LocalFileSystemDataSource::new(ZhangDataType {});
+            let mut ledger = Ledger::load_with_data_source(temp_dir.clone(), "example.zhang".to_string(), Arc::new(source)).unwrap();
+
+            let search = |ledger: &Ledger, query: &str| {
+                let operations = ledger.operations();
+                operations.search_documents(query).unwrap().into_iter().map(|it| it.path).collect::<Vec<_>>()
+            };
+            assert_eq!(vec!["receipt.txt"], search(&ledger, "latte coffee"));
+            assert_eq!(vec!["invoice.txt"], search(&ledger, "RUST"));
+            assert_eq!(vec!["invoice.txt"], search(&ledger, "invoice"));
+            assert!(search(&ledger, "latte rust").is_empty());
+
+            std::fs::write(temp_dir.join("receipt.txt"), "Coffee Shop\nMocha 35 CNY").unwrap();
+            ledger.reload().unwrap();
+            assert!(search(&ledger, "latte").is_empty());
+            assert_eq!(vec!["receipt.txt"], search(&ledger, "mocha"));
+        }
+
+        #[test]
+        fn should_not_index_document_content_by_default() {
+            let ledger = ledger_with_documents();
+            let operations = ledger.operations();
+            assert_eq!(2, operations.search_documents("statement").unwrap().len());
+            assert_eq!(1, operations.search_documents("receipt").unwrap().len());
+            assert!(operations.read().document_texts.is_empty());
+        }
+    }
+    mod error {
+        use indoc::indoc;
+        use zhang_ast::error::ErrorKind;
+
+        use crate::test::load_from_text;
+
+        mod close_non_zero_account {
+            use indoc::indoc;
+            use zhang_ast::error::ErrorKind;
+
+            use crate::test::load_from_text;
+
+            #[test]
+            fn should_not_raise_error() -> Result<(), Box<dyn std::error::Error>> {
+                let ledger = load_from_text(indoc! {r#"
+                    1970-01-01 open Assets:MyCard
+                    1970-01-03 close Assets:MyCard
+                "#});
+
+                let mut operations = ledger.operations();
+                let errors = operations.errors()?;
+                assert_eq!(errors.len(), 0);
+                Ok(())
+            }
+            #[test]
+            fn should_raise_error() -> Result<(), Box<dyn std::error::Error>> {
+                let ledger = load_from_text(indoc! {r#"
+                    1970-01-01 open Assets:MyCard
+                    1970-01-01 open Expenses:Lunch
+                    1970-01-02 "KFC" "Crazy Thursday"
+                      Assets:MyCard -50 CNY
+                      Expenses:Lunch 50 CNY
+
+                    1970-01-03 close Assets:MyCard
+                "#});
+
+                let mut operations = ledger.operations();
+                let mut errors = operations.errors()?;
+                assert_eq!(errors.len(), 1);
+                let error = errors.pop().unwrap();
+                assert_eq!(error.error_type, ErrorKind::CloseNonZeroAccount);
+                Ok(())
+            }
+        }
+
+        mod meta_schema {
+            use indoc::indoc;
+            use zhang_ast::error::ErrorKind;
+
+            use crate::test::load_from_text;
+
+            const LEDGER: &str = indoc! {r#"
+                option "meta_schema" "posting:Expenses:Business:* receipt=path"
+                option "meta_schema" "transaction invoice-no=number?"
+                1970-01-01 open Assets:MyCard
+                1970-01-01 open Expenses:Business
+                1970-01-01 open Expenses:Lunch
+            "#};
+
+            #[test]
+            fn should_raise_error_given_posting_missing_required_meta() -> Result<(), Box<dyn std::error::Error>> {
+                let ledger = load_from_text(&format!(
+                    "{}{}",
+                    LEDGER,
+                    indoc! {r#"
+                        1970-01-02 "KFC" "lunch"
+                          Assets:MyCard -50 CNY
+                          Expenses:Lunch
+                        1970-01-03 "Hotel" "business trip" #trip
+                          Assets:MyCard -50 CNY
+                          Expenses:Business
+                    "#}
+                ));
+
+                let mut operations = ledger.operations();
+                let errors = operations.errors()?;
+                assert_eq!(1, errors.len(), "the posting out of account subtree is not checked");
+                assert_eq!(ErrorKind::MissingRequiredMeta, errors[0].error_type);
+                assert_eq!("posting of Expenses:Business", errors[0].metas.get("target").unwrap());
+                assert_eq!("receipt", errors[0].metas.get("key").unwrap());
+                assert_eq!("path", errors[0].metas.get("meta_type").unwrap());
+                Ok(())
+            }
+
+            #[test]
+            fn should_raise_error_given_meta_of_invalid_type() -> Result<(), Box<dyn std::error::Error>> {
+                let ledger = load_from_text(&format!(
+                    "{}{}",
+                    LEDGER,
+                    indoc! {r#"
+                        1970-01-03 "Hotel" "business trip"
+                          invoice-no: "INV-1"
+                          Assets:MyCard -50 CNY
+                          Expenses:Business 50 CNY
+                            receipt: "receipts/hotel.pdf"
+                    "#}
+                ));
+
+                let mut operations = ledger.operations();
+                let mut errors = operations.errors()?;
+                errors.sort_by(|a, b| a.metas.get("key").cmp(&b.metas.get("key")));
+                assert_eq!(2, errors.len());
+                assert_eq!(ErrorKind::InvalidMetaType, errors[0].error_type);
+                assert_eq!("invoice-no", errors[0].metas.get("key").unwrap());
+                assert_eq!("INV-1", errors[0].metas.get("value").unwrap());
+                assert_eq!(ErrorKind::InvalidMetaType, errors[1].error_type);
+                assert_eq!("receipt", errors[1].metas.get("key").unwrap());
+                Ok(())
+            }
+
+            #[test]
+            fn should_not_raise_error_given_valid_metas() -> Result<(), Box<dyn std::error::Error>> {
+                let ledger = load_from_text(&format!(
+                    "{}{}",
+                    LEDGER,
+                    indoc! {r#"
+                        1970-01-03 "Hotel" "business trip"
+                          invoice-no: "1024"
+                          Assets:MyCard -50 CNY
+                          Expenses:Business 50 CNY
+                            receipt: "example.zhang"
+                    "#}
+                ));
+
+                let mut operations = ledger.operations();
+                assert_eq!(0, operations.errors()?.len());
+                assert_eq!(2, ledger.options.meta_schemas.len());
+                Ok(())
+            }
+        }
+
+        mod lot_becomes_negative {
+            use indoc::indoc;
+            use zhang_ast::error::ErrorKind;
+
+            use crate::test::load_from_text;
+
+            #[test]
+            fn should_raise_error_given_reduction_more_than_holding() -> Result<(), Box<dyn std::error::Error>> {
+                let ledger = load_from_text(indoc! {r#"
+                    1970-01-01 commodity USD
+                    1970-01-01 commodity AAPL
+                    1970-01-01 open Assets:Cash
+                    1970-01-01 open Assets:Broker
+
+                    2023-01-01 "Buy"
+                      Assets:Broker 10 AAPL {100 USD}
+                      Assets:Cash -1000 USD
+
+                    2023-01-03 "Sell"
+                      Assets:Broker -15 AAPL @ 130 USD
+                      Assets:Cash 1950 USD
+                "#});
+
+                let mut operations = ledger.operations();
+                let mut errors = operations.errors()?;
+                assert_eq!(errors.len(), 1);
+                let error = errors.pop().unwrap();
+                assert_eq!(error.error_type, ErrorKind::LotBecomesNegative);
+                assert_eq!(error.metas.get("account_name").unwrap(), "Assets:Broker");
+                assert_eq!(error.metas.get("commodity_name").unwrap(), "AAPL");
+                assert_eq!(error.metas.get("amount").unwrap(), "-5");
+                Ok(())
+            }
+
+            #[test]
+            fn should_raise_error_given_target_lot_becomes_negative() -> Result<(), Box<dyn std::error::Error>> {
+                let ledger = load_from_text(indoc! {r#"
+                    1970-01-01 commodity USD
+                    1970-01-01 commodity AAPL
+                    1970-01-01 open Assets:Cash
+                    1970-01-01 open Assets:Broker
+
+                    2023-01-01 "Buy"
+                      Assets:Broker 10 AAPL {100 USD}
+                      Assets:Cash -1000 USD
+
+                    2023-01-03 "Sell"
+                      Assets:Broker -12 AAPL {100 USD}
+                      Assets:Cash 1200 USD
+                "#});
+
+                let mut operations = ledger.operations();
+                let mut errors = operations.errors()?;
+                assert_eq!(errors.len(), 1);
+                let error = errors.pop().unwrap();
+                assert_eq!(error.error_type, ErrorKind::LotBecomesNegative);
+                assert_eq!(error.metas.get("amount").unwrap(), "-2");
+                Ok(())
+            }
+
+            #[test]
+            fn should_not_raise_error_given_commodity_not_held_at_cost() -> Result<(), Box<dyn std::error::Error>> {
+                let ledger = load_from_text(indoc! {r#"
+                    1970-01-01 commodity CNY
+                    1970-01-01 open Assets:MyCard
+                    1970-01-01 open Expenses:Lunch
+                    1970-01-02 "KFC" "Crazy Thursday"
+                      Assets:MyCard -50 CNY
+                      Expenses:Lunch 50 CNY
+                "#});
+
+                let mut operations = ledger.operations();
+                assert_eq!(operations.errors()?.len(), 0);
+                Ok(())
+            }
+        }
+
+        #[test]
+        fn should_raise_non_balance_error_only() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                    1970-01-01 open Assets:MyCard CNY
+                    1970-01-03 balance Assets:MyCard 10 CNY
+                "#});
+
+            let mut operations = ledger.operations();
+            let mut errors = operations.errors()?;
+            assert_eq!(errors.len(), 1);
+            let domain = errors.pop().unwrap();
+            assert_eq!(domain.error_type, ErrorKind::AccountBalanceCheckError);
+            assert_eq!(domain.metas.get("account_name").unwrap(), "Assets:MyCard");
+            Ok(())
+        }
+
+        mod balance_tolerance {
+            use indoc::indoc;
+            use zhang_ast::error::ErrorKind;
+
+            use crate::test::load_from_text;
+
+            #[test]
+            fn should_warn_given_distance_within_global_tolerance() -> Result<(), Box<dyn std::error::Error>> {
+                let ledger = load_from_text(indoc! {r#"
+                    option "balance_tolerance" "0.005"
+                    1970-01-01 open Assets:MyCard CNY
+                    1970-01-01 open Expenses:Lunch CNY
+                    1970-01-02 "KFC" "Crazy Thursday"
+                      Assets:MyCard -10.004 CNY
+                      Expenses:Lunch
+                    1970-01-03 balance Assets:MyCard -10 CNY
+                "#});
+
+                let mut operations = ledger.operations();
+                let mut errors = operations.errors()?;
+                assert_eq!(errors.len(), 1);
+                let domain = errors.pop().unwrap();
+                assert_eq!(domain.error_type, ErrorKind::AccountBalanceCheckWithinTolerance);
+                assert_eq!(domain.metas.get("distance").unwrap(), "0.004");
+                Ok(())
+            }
+
+            #[test]
+            fn should_raise_error_given_distance_over_tolerance() -> Result<(), Box<dyn std::error::Error>> {
+                let ledger = load_from_text(indoc! {r#"
+                    option "balance_tolerance" "0.005"
+                    1970-01-01 open Assets:MyCard CNY
+                    1970-01-03 balance Assets:MyCard 0.01 CNY
+                "#});
+
+                let mut operations = ledger.operations();
+                let errors = operations.errors()?;
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0].error_type, ErrorKind::AccountBalanceCheckError);
+                Ok(())
+            }
+
+            #[test]
+            fn should_prefer_commodity_tolerance() -> Result<(), Box<dyn std::error::Error>> {
+                let ledger = load_from_text(indoc! {r#"
+                    option "balance_tolerance" "0.005"
+                    1970-01-01 commodity JPY
+                      balance_tolerance: "1"
+                    1970-01-01 commodity CNY
+                      balance_tolerance: "0"
+                    1970-01-01 open Assets:MyCard
+                    1970-01-03 balance Assets:MyCard 0.5 JPY
+                    1970-01-03 balance Assets:MyCard 0.001 CNY
+                "#});
+
+                let mut operations = ledger.operations();
+                let errors = operations.errors()?;
+                assert_eq!(errors.len(), 2);
+                assert_eq!(
+                    1,
+                    errors
+                        .iter()
+                        .filter(|it| it.error_type == ErrorKind::AccountBalanceCheckWithinTolerance)
+                        .count()
+                );
+                assert_eq!(1, errors.iter().filter(|it| it.error_type == ErrorKind::AccountBalanceCheckError).count());
+                Ok(())
+            }
+        }
+
+        mod duplicated_transaction {
+            use indoc::indoc;
+            use zhang_ast::error::ErrorKind;
+
+            use crate::test::load_from_text;
+
+            #[test]
+            fn should_not_detect_duplicates_without_window_option() -> Result<(), Box<dyn std::error::Error>> {
+                let ledger = load_from_text(indoc! {r#"
+                    1970-01-01 open Assets:MyCard
+                    1970-01-01 open Expenses:Lunch
+                    1970-01-02 "KFC" "Crazy Thursday"
+                      Assets:MyCard -50 CNY
+                      Expenses:Lunch
+                    1970-01-02 "KFC" "Crazy Thursday"
+                      Assets:MyCard -50 CNY
+                      Expenses:Lunch
+                "#});
+
+                let mut operations = ledger.operations();
+                assert_eq!(0, operations.errors()?.len());
+                Ok(())
+            }
+
+            #[test]
+            fn should_raise_warning_for_probable_duplicates() -> Result<(), Box<dyn std::error::Error>> {
+                let ledger = load_from_text(indoc! {r#"
+                    option "duplicate_transaction_window" "3"
+                    1970-01-01 open Assets:MyCard
+                    1970-01-01 open Expenses:Lunch
+                    1970-01-02 "KFC" "Crazy Thursday"
+                      Assets:MyCard -50 CNY
+                      Expenses:Lunch
+                    1970-01-04 "KFC #0231" ""
+                      Assets:MyCard -50 CNY
+                      Expenses:Lunch
+                    1970-01-04 "McDonald's" ""
+                      Assets:MyCard -50 CNY
+                      Expenses:Lunch
+                    1970-01-05 "KFC" "Crazy Thursday"
+                      dedup-ignore: "true"
+                      Assets:MyCard -50 CNY
+                      Expenses:Lunch
+                    1970-01-10 "KFC" "Crazy Thursday"
+                      Assets:MyCard -50 CNY
+                      Expenses:Lunch
+                "#});
+
+                let mut operations = ledger.operations();
+                let errors = operations.errors()?;
+                assert_eq!(1, errors.len());
+                assert_eq!(ErrorKind::DuplicatedTransaction, errors[0].error_type);
+                assert_eq!("1970-01-02", errors[0].metas.get("date").unwrap());
+                assert_eq!("KFC", errors[0].metas.get("payee").unwrap());
+                assert!(errors[0].span.as_ref().unwrap().content.contains("KFC #0231"));
+                Ok(())
+            }
+
+            #[test]
+            fn should_not_detect_duplicates_of_balance_directives() -> Result<(), Box<dyn std::error::Error>> {
+                let ledger = load_from_text(indoc! {r#"
+                    option "duplicate_transaction_window" "3"
+                    1970-01-01 open Assets:MyCard
+                    1970-01-01 open Equity:Opening-Balances
+                    1970-01-02 balance Assets:MyCard 100 CNY with pad Equity:Opening-Balances
+                    1970-01-03 balance Assets:MyCard 200 CNY with pad Equity:Opening-Balances
+                    1970-01-04 balance Assets:MyCard 200 CNY
+                    1970-01-05 balance Assets:MyCard 200 CNY
+                "#});
+
+                let mut operations = ledger.operations();
+                assert_eq!(0, operations.errors()?.len());
+                Ok(())
+            }
+        }
+        mod parse_error {
+            use indoc::indoc;
+            use zhang_ast::error::ErrorKind;
+
+            use crate::test::load_from_text;
+
+            #[test]
+            fn should_load_rest_of_file_given_malformed_directive() -> Result<(), Box<dyn std::error::Error>> {
+                let ledger = load_from_text(indoc! {r#"
+                    1970-01-01 open Assets:MyCard
+                    1970-01-01 opne Expenses:Lunch
+                    1970-01-01 open Expenses:Food
+                "#});
+
+                let mut operations = ledger.operations();
+                assert!(operations.exist_account("Assets:MyCard")?);
+                assert!(operations.exist_account("Expenses:Food")?);
+                let errors = operations.errors()?;
+                assert_eq!(1, errors.len());
+                assert_eq!(ErrorKind::ParseError, errors[0].error_type);
+                assert_eq!("1970-01-01 opne Expenses:Lunch", errors[0].span.as_ref().unwrap().content);
+                assert!(errors[0].metas.contains_key("message"));
+                Ok(())
+            }
+        }
+        mod severity {
+            use indoc::indoc;
+            use zhang_ast::error::{ErrorKind, ErrorSeverity};
+
+            use crate::test::load_from_text;
+
+            #[test]
+            fn should_have_default_severity() -> Result<(), Box<dyn std::error::Error>> {
+                let ledger = load_from_text(indoc! {r#"
+                    option "duplicate_transaction_window" "3"
+                    1970-01-01 open Assets:MyCard
+                    1970-01-01 open Expenses:Lunch
+                    1970-01-02 "KFC"
+                      Assets:MyCard -50 CNY
+                      Expenses:Lunch
+                    1970-01-03 "KFC"
+                      Assets:MyCard -50 CNY
+                      Expenses:Lunch
+                    1970-01-10 close Expenses:Food
+                "#});
+
+                let mut operations = ledger.operations();
+                let errors = operations.errors()?;
+                assert_eq!(2, errors.len());
+                let errors = operations.errors_by_severity(ErrorSeverity::Error)?;
+                assert_eq!(1, errors.len());
+                assert_eq!(ErrorKind::AccountDoesNotExist, errors[0].error_type);
+                let warnings = operations.errors_by_severity(ErrorSeverity::Warning)?;
+                assert_eq!(2, warnings.len());
+                Ok(())
+            }
+
+            #[test]
+            fn should_downgrade_and_suppress_errors_by_option() -> Result<(), Box<dyn std::error::Error>> {
+                let ledger = load_from_text(indoc! {r#"
+                    option "duplicate_transaction_window" "3"
+                    option "error_severity" "AccountDoesNotExist=warning"
+                    option "error_severity" "DuplicatedTransaction=ignore"
+                    1970-01-01 open Assets:MyCard
+                    1970-01-01 open Expenses:Lunch
+                    1970-01-02 "KFC"
+                      Assets:MyCard -50 CNY
+                      Expenses:Lunch
+                    1970-01-03 "KFC"
+                      Assets:MyCard -50 CNY
+                      Expenses:Lunch
+                    1970-01-10 close Expenses:Food
+                "#});
+
+                let mut operations = ledger.operations();
+                let errors = operations.errors()?;
+                assert_eq!(1, errors.len());
+                assert_eq!(ErrorKind::AccountDoesNotExist, errors[0].error_type);
+                assert_eq!(ErrorSeverity::Warning, errors[0].severity);
+                assert_eq!(0, operations.errors_by_severity(ErrorSeverity::Error)?.len());
+                Ok(())
+            }
+
+            #[test]
+            fn should_prefer_rule_of_file() -> Result<(), Box<dyn std::error::Error>> {
+                let ledger = load_from_text(indoc! {r#"
+                    option "error_severity" "example.zhang:AccountDoesNotExist=info"
+                    option "error_severity" "AccountDoesNotExist=warning"
+                    option "error_severity" "other.zhang:AccountDoesNotExist=ignore"
+                    option "error_severity" "NotAnErrorKind=ignore"
+                    1970-01-10 close Expenses:Food
+                "#});
+
+                let mut operations = ledger.operations();
+                let errors = operations.errors()?;
+                assert_eq!(1, errors.len());
+                assert_eq!(ErrorSeverity::Info, errors[0].severity);
+                Ok(())
+            }
+        }
+    }
+    mod timezone {
+        use indoc::indoc;
+
+        use crate::test::load_from_text;
+
+        #[test]
+        fn should_get_system_timezone() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                    1970-01-01 open Assets:MyCard CNY
+                "#});
+
+            let operations = ledger.operations();
+            let timezone: String = operations.option("timezone")?.unwrap();
+            assert_eq!(iana_time_zone::get_timezone().unwrap(), timezone);
+            Ok(())
+        }
+
+        #[test]
+        fn should_fallback_to_use_system_timezone_given_invalid_timezone() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                    option "timezone" "MYZone"
+                "#});
+
+            let operations = ledger.operations();
+            let timezone: String = operations.option("timezone")?.unwrap();
+            assert_eq!(iana_time_zone::get_timezone().unwrap(), timezone);
+            Ok(())
+        }
+        #[test]
+        fn should_parse_user_timezone() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                    option "timezone" "Antarctica/South_Pole"
+                "#});
+
+            let operations = ledger.operations();
+            let timezone: String = operations.option("timezone")?.unwrap();
+            assert_eq!("Antarctica/South_Pole", timezone);
+            assert_eq!(ledger.options.timezone, "Antarctica/South_Pole".parse().unwrap());
+            Ok(())
+        }
+
+        #[test]
+        fn should_convert_datetime_with_offset_into_ledger_timezone() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                    option "timezone" "Asia/Shanghai"
+                    1970-01-01 commodity CNY
+                    1970-01-01 open Assets:Bank
+                    1970-01-01 open Expenses:A
+
+                    2024-05-02 00:30:00+09:00 "Tokyo"
+                      Assets:Bank -50 CNY
+                      Expenses:A
+
+                    2024-05-01 20:00:00-07:00 "San Francisco"
+                      Assets:Bank -20 CNY
+                      Expenses:A
+
+                    2024-05-02 balance Assets:Bank -50 CNY
+                    2024-05-03 balance Assets:Bank -70 CNY
+                "#});
+
+            let mut operations = ledger.operations();
+            assert_eq!(0, operations.errors()?.len());
+            let journals = operations.account_journals("Assets:Bank")?;
+            let tokyo = journals.iter().find(|it| it.payee.as_deref() == Some("Tokyo")).unwrap();
+            assert_eq!("2024-05-01 23:30:00", tokyo.datetime.to_string());
+            let san_francisco = journals.iter().find(|it| it.payee.as_deref() == Some("San Francisco")).unwrap();
+            assert_eq!("2024-05-02 11:00:00", san_francisco.datetime.to_string());
+            Ok(())
+        }
+    }
+
+    mod transaction {
+        use std::str::FromStr;
+
+        use bigdecimal::BigDecimal;
+        use chrono::NaiveDate;
+        use indoc::indoc;
+        use zhang_ast::error::ErrorKind;
+
+        use zhang_ast::Directive;
+
+        use crate::data_type::text::ZhangDataType;
+        use crate::data_type::DataType;
+        use crate::domains::JournalFilter;
+        use crate::test::{load_from_text, load_store};
+
+        #[test]
+        fn should_validate_transaction_against_ledger() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Expenses:Food
+                1970-01-01 open Expenses:Old
+                2000-01-01 close Expenses:Old
+            "#});
+            let transaction = |content: &str| {
+                let data_type = ZhangDataType {};
+                match data_type.transform(content.to_owned(), None).unwrap().remove(0).data {
+                    Directive::Transaction(trx) => trx,
+                    _ => unreachable!(),
+                }
+            };
+            let operations = ledger.operations();
+
+            let valid = transaction(indoc! {r#"
+                2024-01-01 "KFC" "Lunch"
+                  Assets:Bank -10 CNY
+                  Expenses:Food
+            "#});
+            assert!(operations.validate_transaction(&valid)?.is_empty());
+
+            let invalid = transaction(indoc! {r#"
+                2024-01-01 "KFC" "Lunch"
+                  Assets:Bank -10 CNY
+                  Expenses:Old 5 CNY
+                  Expenses:Unknown 1 CNY
+            "#});
+            assert_eq!(
+                vec![ErrorKind::AccountClosed, ErrorKind::AccountDoesNotExist, ErrorKind::UnbalancedTransaction],
+                operations.validate_transaction(&invalid)?
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn should_filter_and_paginate_journals() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Expenses:Food:Lunch
+                1970-01-01 open Expenses:Travel
+
+                2024-01-01 "KFC" "Lunch" #food
+                  Assets:Bank -10 CNY
+                  Expenses:Food:Lunch
+
+                2024-01-02 "Airline" "Flight"
+                  Assets:Bank -100 CNY
+                  Expenses:Travel
+
+                2024-01-03 "kfc" "Dinner" #food
+                  Assets:Bank -20 CNY
+                  Expenses:Food:Lunch
+
+                2024-01-04 "McDonald" "Breakfast"
+                  Assets:Bank -5 CNY
+                  Expenses:Food:Lunch
+            "#});
+            let operations = ledger.operations();
+            let narrations = |filter: JournalFilter, offset: usize, limit: usize| {
+                let (total_count, journals) = operations.journals(&filter, offset, limit).unwrap();
+                (total_count, journals.into_iter().filter_map(|it| it.narration).collect::<Vec<_>>())
+            };
+
+            let food = JournalFilter {
+                account: Some("Expenses:Food".to_owned()),
+                ..Default::default()
+            };
+            assert_eq!((3, vec!["Breakfast".to_owned(), "Dinner".to_owned()]), narrations(food.clone(), 0, 2));
+            assert_eq!((3, vec!["Lunch".to_owned()]), narrations(food, 2, 2));
+
+            let payee = JournalFilter {
+                payee: Some("KFC".to_owned()),
+                to: Some(NaiveDate::from_ymd_opt(2024, 1, 2).unwrap().and_hms_opt(0, 0, 0).unwrap().and_utc()),
+                ..Default::default()
+            };
+            assert_eq!((1, vec!["Lunch".to_owned()]), narrations(payee, 0, 10));
+
+            let tag = JournalFilter {
+                tag: Some("food".to_owned()),
+                keyword: Some("dinner".to_owned()),
+                ..Default::default()
+            };
+            assert_eq!((1, vec!["Dinner".to_owned()]), narrations(tag, 0, 10));
+            Ok(())
+        }
+
+        #[test]
+        fn should_keep_voided_transaction_in_journal_only() -> Result<(), Box<dyn std::error::Error>> {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Expenses:Food
+
+                2024-01-01 "KFC" "Lunch"
+                  Assets:Bank -10 CNY
+                  Expenses:Food
+
+                2024-01-02 "KFC" "Refunded dinner"
+                  void: "true"
+                  Assets:Bank -20 CNY
+                  Expenses:Food 15 CNY
+
+                2024-01-03 V "KFC" "Custom flag"
+                  Assets:Bank -5 CNY
+                  Expenses:Food
+            "#});
+            let mut operations = ledger.operations();
+            assert!(operations.errors()?.is_empty());
+
+            let balance = operations.single_account_latest_balances("Assets:Bank")?.pop().unwrap();
+            assert_eq!(BigDecimal::from(-15), balance.balance_number);
+            assert_eq!(2, operations.transaction_counts()?);
+
+            let (total_count, journals) = operations.journals(&JournalFilter::default(), 0, 10)?;
+            assert_eq!(3, total_count);
+            assert_eq!(zhang_ast::Flag::Custom("V".to_owned()), journals[0].flag);
+            let voided = &journals[1];
+            assert_eq!(zhang_ast::Flag::Void, voided.flag);
+            assert_eq!(2, voided.postings.len());
+            assert_eq!(BigDecimal::from(-10), voided.postings[0].after_amount.number);
             Ok(())
         }
+
+        #[test]
+        fn should_get_all_payees() {
+            let ledger = load_store(indoc! {r#"
+                1970-01-01 commodity USD
+                1970-01-01 open Assets:A
+                1970-01-01 open Expenses:A
+
+                1970-01-02 "Apple Inc" "iPhone 15"
+                  Assets:A -1000 USD
+                  Expenses:A
+
+                1970-01-02 "Origan Inc" "iPhone 15"
+                  Assets:A -1000 USD
+                  Expenses:A
+            "#})
+            .ledger;
+            let mut operations = ledger.operations();
+            let result = operations.all_payees().unwrap();
+            assert!(result.contains(&"Origan Inc".to_owned()));
+            assert!(result.contains(&"Apple Inc".to_owned()));
+        }
+
+        #[test]
+        fn should_split_residual_equally_given_multiple_implicit_postings() {
+            let ledger = load_store(indoc! {r#"
+                1970-01-01 commodity CNY
+                1970-01-01 open Assets:A
+                1970-01-01 open Expenses:A
+                1970-01-01 open Expenses:B
+                1970-01-01 open Expenses:C
+
+                1970-01-02 "Dinner"
+                  Assets:A -100 CNY
+                  Expenses:A
+                  Expenses:B
+                  Expenses:C
+            "#})
+            .ledger;
+            let mut operations = ledger.operations();
+            assert_eq!(0, operations.errors().unwrap().len());
+            let balance = |account: &str| operations.single_account_latest_balances(account).unwrap().pop().unwrap().balance_number;
+            assert_eq!(BigDecimal::from(33), balance("Expenses:A"));
+            assert_eq!(BigDecimal::from(33), balance("Expenses:B"));
+            assert_eq!(BigDecimal::from(34), balance("Expenses:C"));
+        }
+
+        #[test]
+        fn should_split_residual_by_weight() {
+            let ledger = load_store(indoc! {r#"
+                1970-01-01 commodity CNY
+                1970-01-01 open Assets:A
+                1970-01-01 open Expenses:A
+                1970-01-01 open Expenses:B
+
+                1970-01-02 "Dinner"
+                  Assets:A -90.00 CNY
+                  Expenses:A
+                    split-weight: "2"
+                  Expenses:B
+                    split-weight: "1"
+            "#})
+            .ledger;
+            let mut operations = ledger.operations();
+            assert_eq!(0, operations.errors().unwrap().len());
+            let balance = |account: &str| operations.single_account_latest_balances(account).unwrap().pop().unwrap().balance_number;
+            assert_eq!(BigDecimal::from(60), balance("Expenses:A"));
+            assert_eq!(BigDecimal::from(30), balance("Expenses:B"));
+        }
+
+        #[test]
+        fn should_raise_error_given_ambiguous_split() {
+            let ledger = load_store(indoc! {r#"
+                1970-01-01 commodity CNY
+                1970-01-01 open Assets:A
+                1970-01-01 open Expenses:A
+                1970-01-01 open Expenses:B
+
+                1970-01-02 "Dinner"
+                  Assets:A -90 CNY
+                  Expenses:A
+                    split-weight: "2"
+                  Expenses:B
+            "#})
+            .ledger;
+            let mut operations = ledger.operations();
+            let mut errors = operations.errors().unwrap();
+            assert_eq!(1, errors.len());
+            assert_eq!(ErrorKind::TransactionAmbiguousSplit, errors.pop().unwrap().error_type);
+        }
+
+        #[test]
+        fn should_remove_duplicated_payees() {
+            let ledger = load_store(indoc! {r#"
+                1970-01-01 commodity USD
+                1970-01-01 open Assets:A
+                1970-01-01 open Expenses:A
+
+                1970-01-02 "Apple Inc" "iPhone 15"
+                  Assets:A -1000 USD
+                  Expenses:A
+
+                1970-01-02 "Apple Inc" "iPhone 15"
+                  Assets:A -1000 USD
+                  Expenses:A
+            "#})
+            .ledger;
+            let mut operations = ledger.operations();
+            let result = operations.all_payees().unwrap();
+            assert!(result.contains(&"Apple Inc".to_owned()));
+            assert_eq!(1, result.len());
+        }
+
+        #[test]
+        fn should_normalize_payee_by_alias() {
+            let ledger = load_store(indoc! {r#"
+                option "payee-alias" "AMZN Mktp=Amazon"
+                option "payee-alias" "amazon.com=Amazon"
+                1970-01-01 open Assets:A
+                1970-01-01 open Expenses:Book
+                1970-01-01 open Expenses:Food
+
+                1970-01-02 "AMZN Mktp US*2K4" "Book"
+                  Assets:A -100 CNY
+                  Expenses:Book
+
+                1970-01-05 "Amazon.com" "Book"
+                  Assets:A -100 CNY
+                  Expenses:Book
+
+                1970-01-03 "KFC" "Lunch"
+                  Assets:A -50 CNY
+                  Expenses:Food
+            "#})
+            .ledger;
+            let operations = ledger.operations();
+            let payees = operations.payees().unwrap();
+            assert_eq!(2, payees.len());
+            let amazon = &payees[0];
+            assert_eq!("Amazon", amazon.name);
+            assert_eq!(2, amazon.transaction_count);
+            assert_eq!(NaiveDate::from_ymd_opt(1970, 1, 2).unwrap(), amazon.first_seen);
+            assert_eq!(NaiveDate::from_ymd_opt(1970, 1, 5).unwrap(), amazon.last_seen);
+            assert_eq!(vec!["Assets:A".to_owned(), "Expenses:Book".to_owned()], amazon.typical_accounts);
+            assert_eq!("KFC", payees[1].name);
+        }
+
+        #[test]
+        fn should_query_transactions_by_tag_and_link() {
+            let ledger = load_store(indoc! {r#"
+                1970-01-01 open Assets:A
+                1970-01-01 open Expenses:A
+
+                1970-01-02 "Hotel" #trip ^tokyo-2024
+                  Assets:A -100 CNY
+                  Expenses:A
+
+                1970-01-03 "Flight" #trip #flight ^tokyo-2024
+                  Assets:A -200 CNY
+                  Expenses:A
+
+                1970-01-04 "Dinner"
+                  Assets:A -50 CNY
+                  Expenses:A
+            "#})
+            .ledger;
+            let operations = ledger.operations();
+            let trip = operations.transactions_by_tag("trip").unwrap();
+            assert_eq!(
+                vec![Some("Hotel".to_owned()), Some("Flight".to_owned())],
+                trip.into_iter().map(|it| it.payee).collect::<Vec<_>>()
+            );
+            assert_eq!(1, operations.transactions_by_tag("flight").unwrap().len());
+            assert_eq!(2, operations.transactions_by_link("tokyo-2024").unwrap().len());
+            assert!(operations.transactions_by_tag("missing").unwrap().is_empty());
+        }
+
+        #[test]
+        fn should_tolerate_residual_within_half_unit_of_commodity_precision() {
+            let ledger = load_store(indoc! {r#"
+                1970-01-01 commodity CNY
+                  precision: 2
+                1970-01-01 open Assets:A
+                1970-01-01 open Expenses:A
+
+                1970-01-02 "Dinner"
+                  Assets:A -10.004 CNY
+                  Expenses:A 10 CNY
+
+                1970-01-02 "Dinner"
+                  Assets:A -10.005 CNY
+                  Expenses:A 10 CNY
+            "#})
+            .ledger;
+            let mut operations = ledger.operations();
+            let errors = operations.errors().unwrap();
+            assert_eq!(0, errors.len());
+        }
+
+        #[test]
+        fn should_raise_unbalanced_error_given_residual_exceeding_half_unit_of_commodity_precision() {
+            let ledger = load_store(indoc! {r#"
+                1970-01-01 commodity CNY
+                  precision: 2
+                1970-01-01 commodity USD
+                  precision: 4
+                1970-01-01 open Assets:A
+                1970-01-01 open Expenses:A
+
+                1970-01-02 "Dinner"
+                  Assets:A -10.006 CNY
+                  Expenses:A 10 CNY
+
+                1970-01-02 "Dinner"
+                  Assets:A -10.004 USD
+                  Expenses:A 10 USD
+            "#})
+            .ledger;
+            let mut operations = ledger.operations();
+            let errors = operations.errors().unwrap();
+            assert_eq!(2, errors.len());
+            assert!(errors.iter().all(|error| error.error_type == ErrorKind::UnbalancedTransaction));
+        }
+
+        #[test]
+        fn should_post_tiny_residual_into_rounding_account() {
+            let ledger = load_store(indoc! {r#"
+                option "rounding_account" "Expenses:Rounding"
+                1970-01-01 commodity CNY
+                  precision: 2
+                1970-01-01 open Assets:A
+                1970-01-01 open Expenses:A
+                1970-01-01 open Expenses:Rounding
+
+                1970-01-02 "Dinner"
+                  Assets:A -10.007 CNY
+                  Expenses:A 10 CNY
+
+                1970-01-02 "Dinner"
+                  Assets:A -10.1 CNY
+                  Expenses:A 10 CNY
+            "#})
+            .ledger;
+            let mut operations = ledger.operations();
+            let errors = operations.errors().unwrap();
+            assert_eq!(1, errors.len(), "residual exceeding one unit of precision should not be rounded");
+            assert_eq!(ErrorKind::UnbalancedTransaction, errors[0].error_type);
+            let balance = operations
+                .single_account_latest_balances("Expenses:Rounding")
+                .unwrap()
+                .pop()
+                .unwrap()
+                .balance_number;
+            assert_eq!(BigDecimal::from_str("0.007").unwrap(), balance);
+        }
+
+        #[test]
+        fn should_take_posting_date_into_balances_given_date_meta() {
+            let ledger = load_store(indoc! {r#"
+                1970-01-01 commodity CNY
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Assets:Cash
+                1970-01-01 open Expenses:A
+
+                2024-05-01 "Card payment"
+                  Assets:Bank -50 CNY
+                    date: "2024-05-05"
+                  Expenses:A
+
+                2024-05-02 "Withdraw"
+                  Assets:Bank -20 CNY
+                  Assets:Cash
+
+                2024-05-04 balance Assets:Bank -20 CNY
+                2024-05-06 balance Assets:Bank -70 CNY
+            "#})
+            .ledger;
+            let mut operations = ledger.operations();
+            assert_eq!(0, operations.errors().unwrap().len());
+
+            let journals = operations.account_journals("Assets:Bank").unwrap();
+            let payment = journals.iter().find(|it| it.payee.as_deref() == Some("Card payment")).unwrap();
+            assert_eq!("2024-05-05", payment.datetime.date().to_string());
+            assert_eq!(BigDecimal::from(-70), payment.account_after_number);
+            let expense = operations.account_journals("Expenses:A").unwrap().pop().unwrap();
+            assert_eq!("2024-05-01", expense.datetime.date().to_string());
+        }
+
+        #[test]
+        fn should_report_by_auxiliary_date_given_transaction_date_option() {
+            let content = indoc! {r#"
+                1970-01-01 commodity CNY
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Expenses:A
+
+                2024-05-01=2024-05-03 "Card payment"
+                  Assets:Bank -50 CNY
+                  Expenses:A
+
+                2024-05-02 balance Assets:Bank {} CNY
+            "#};
+            let ledger = load_store(&content.replace("{}", "-50")).ledger;
+            let mut operations = ledger.operations();
+            assert_eq!(0, operations.errors().unwrap().len());
+
+            let ledger = load_store(&format!("option \"transaction_date\" \"auxiliary\"\n{}", content.replace("{}", "0"))).ledger;
+            let mut operations = ledger.operations();
+            assert_eq!(0, operations.errors().unwrap().len());
+            let journal = operations.account_journals("Expenses:A").unwrap().pop().unwrap();
+            assert_eq!("2024-05-03", journal.datetime.date().to_string());
+        }
+
+        #[test]
+        fn should_exclude_virtual_postings_from_balancing() {
+            let ledger = load_store(indoc! {r#"
+                1970-01-01 commodity CNY
+                1970-01-01 open Assets:A
+                1970-01-01 open Assets:Budget:Food
+                1970-01-01 open Equity:Budget:Food
+                1970-01-01 open Equity:Budget:Available
+                1970-01-01 open Expenses:A
+
+                1970-01-02 "Dinner"
+                  Assets:A -50 CNY
+                  Expenses:A
+                  (Assets:Budget:Food) -50 CNY
+                  [Equity:Budget:Food] 30 CNY
+                  [Equity:Budget:Available]
+            "#})
+            .ledger;
+            let mut operations = ledger.operations();
+            assert_eq!(0, operations.errors().unwrap().len());
+            let balance = |operations: &mut crate::domains::Operations, account: &str| {
+                operations.single_account_latest_balances(account).unwrap().pop().unwrap().balance_number
+            };
+            assert_eq!(BigDecimal::from(50), balance(&mut operations, "Expenses:A"));
+            assert_eq!(BigDecimal::from(-50), balance(&mut operations, "Assets:Budget:Food"));
+            assert_eq!(BigDecimal::from(-30), balance(&mut operations, "Equity:Budget:Available"));
+        }
+
+        #[test]
+        fn should_raise_error_given_unbalanced_or_implicit_virtual_postings() {
+            let ledger = load_store(indoc! {r#"
+                1970-01-01 commodity CNY
+                1970-01-01 open Assets:A
+                1970-01-01 open Assets:Budget:Food
+                1970-01-01 open Equity:Budget:Food
+                1970-01-01 open Expenses:A
+
+                1970-01-02 "Dinner"
+                  Assets:A -50 CNY
+                  Expenses:A 50 CNY
+                  [Equity:Budget:Food] 30 CNY
+
+                1970-01-03 "Dinner"
+                  Assets:A -50 CNY
+                  Expenses:A 50 CNY
+                  (Assets:Budget:Food)
+            "#})
+            .ledger;
+            let mut operations = ledger.operations();
+            let errors = operations.errors().unwrap();
+            assert_eq!(2, errors.len());
+            assert_eq!(ErrorKind::UnbalancedTransaction, errors[0].error_type);
+            assert_eq!(ErrorKind::TransactionCannotInferTradeAmount, errors[1].error_type);
+        }
+    }
+
+    mod lot {
+        use bigdecimal::BigDecimal;
+        use chrono::{NaiveDate, NaiveTime};
+        use indoc::indoc;
+
+        use crate::test::load_from_text;
+
+        fn ledger_with_booking_method(booking_method: &str) -> crate::ledger::Ledger {
+            load_from_text(&format!(
+                indoc! {r#"
+                    1970-01-01 commodity USD
+                    1970-01-01 commodity AAPL
+                    1970-01-01 open Assets:Cash
+                    1970-01-01 open Assets:Broker
+                      booking_method: "{}"
+
+                    2023-01-01 "Buy"
+                      Assets:Broker 10 AAPL {{100 USD}}
+                      Assets:Cash -1000 USD
+
+                    2023-01-02 "Buy"
+                      Assets:Broker 10 AAPL {{120 USD}}
+                      Assets:Cash -1200 USD
+
+                    2023-01-03 "Sell"
+                      Assets:Broker -15 AAPL @ 130 USD
+                      Assets:Cash 1950 USD
+                "#},
+                booking_method
+            ))
+        }
+
+        fn lot_amounts(ledger: &crate::ledger::Ledger) -> Vec<(Option<BigDecimal>, BigDecimal)> {
+            let operations = ledger.operations();
+            operations
+                .account_lots("Assets:Broker", "AAPL")
+                .unwrap()
+                .into_iter()
+                .map(|lot| (lot.price.map(|it| it.number), lot.amount))
+                .collect()
+        }
+
+        #[test]
+        fn should_consume_latest_lot_first_given_filo() {
+            let ledger = ledger_with_booking_method("LIFO");
+
+            let lots = lot_amounts(&ledger);
+            assert_eq!(
+                vec![
+                    (Some(BigDecimal::from(100)), BigDecimal::from(5)),
+                    (Some(BigDecimal::from(120)), BigDecimal::from(0))
+                ],
+                lots
+            );
+            let cost_basis: BigDecimal = lots.iter().map(|(price, amount)| price.clone().unwrap_or_default() * amount).sum();
+            assert_eq!(BigDecimal::from(500), cost_basis);
+        }
+
+        #[test]
+        fn should_consume_earliest_lot_first_given_fifo() {
+            let ledger = ledger_with_booking_method("FIFO");
+
+            let lots = lot_amounts(&ledger);
+            assert_eq!(
+                vec![
+                    (Some(BigDecimal::from(100)), BigDecimal::from(0)),
+                    (Some(BigDecimal::from(120)), BigDecimal::from(5))
+                ],
+                lots
+            );
+            let cost_basis: BigDecimal = lots.iter().map(|(price, amount)| price.clone().unwrap_or_default() * amount).sum();
+            assert_eq!(BigDecimal::from(600), cost_basis);
+        }
+
+        #[test]
+        fn should_merge_lots_at_weighted_average_price_given_average() {
+            let ledger = ledger_with_booking_method("AVERAGE");
+
+            let lots = lot_amounts(&ledger);
+            assert_eq!(vec![(Some(BigDecimal::from(110)), BigDecimal::from(5))], lots);
+        }
+
+        #[test]
+        fn should_keep_lots_before_reduction_given_average() {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity USD
+                1970-01-01 commodity AAPL
+                1970-01-01 open Assets:Cash
+                1970-01-01 open Assets:Broker
+                  booking_method: "AVERAGE"
+
+                2023-01-01 "Buy"
+                  Assets:Broker 10 AAPL {100 USD}
+                  Assets:Cash -1000 USD
+
+                2023-01-02 "Buy"
+                  Assets:Broker 30 AAPL {120 USD}
+                  Assets:Cash -3600 USD
+            "#});
+
+            let lots = lot_amounts(&ledger);
+            assert_eq!(
+                vec![
+                    (Some(BigDecimal::from(100)), BigDecimal::from(10)),
+                    (Some(BigDecimal::from(120)), BigDecimal::from(30))
+                ],
+                lots
+            );
+        }
+
+        #[test]
+        fn should_adjust_lots_and_balances_given_split() {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity USD
+                1970-01-01 commodity AAPL
+                1970-01-01 open Assets:Cash
+                1970-01-01 open Assets:Broker
+                1970-01-01 open Assets:Wallet
+                1970-01-01 open Equity:Open
+
+                2023-01-01 "Buy"
+                  Assets:Broker 10 AAPL {100 USD}
+                  Assets:Cash -1000 USD
+
+                2023-01-02 "Buy"
+                  Assets:Broker 10 AAPL {120 USD}
+                  Assets:Cash -1200 USD
+
+                2023-01-03 "Transfer in"
+                  Assets:Wallet 5 AAPL
+                  Equity:Open
+
+                2023-02-01 split AAPL 4:1
+                2023-02-02 balance Assets:Wallet 20 AAPL
+
+                2023-02-03 "Sell"
+                  Assets:Broker -40 AAPL @ 30 USD
+                  Assets:Cash 1200 USD
+            "#});
+            let mut operations = ledger.operations();
+            assert_eq!(0, operations.errors().unwrap().len());
+
+            let lots = lot_amounts(&ledger);
+            assert_eq!(
+                vec![
+                    (Some(BigDecimal::from(25)), BigDecimal::from(0)),
+                    (Some(BigDecimal::from(30)), BigDecimal::from(40))
+                ],
+                lots
+            );
+
+            let timezone = ledger.options.timezone;
+            let from = NaiveDate::from_ymd_opt(2023, 1, 1)
+                .unwrap()
+                .and_time(NaiveTime::MIN)
+                .and_local_timezone(timezone)
+                .unwrap();
+            let to = NaiveDate::from_ymd_opt(2023, 12, 31)
+                .unwrap()
+                .and_time(NaiveTime::MIN)
+                .and_local_timezone(timezone)
+                .unwrap();
+            let gains = operations.realized_gains("Assets:Broker", from, to).unwrap();
+            assert_eq!(1, gains.len());
+            assert_eq!(BigDecimal::from(200), gains[0].gain.number);
+        }
+
+        #[test]
+        fn should_raise_error_given_zero_split_ratio() {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity AAPL
+                1970-01-01 open Assets:Broker
+
+                2023-02-01 split AAPL 0:1
+            "#});
+            let errors = ledger.operations().errors().unwrap();
+            assert_eq!(1, errors.len());
+            assert_eq!(zhang_ast::error::ErrorKind::InvalidSplitRatio, errors[0].error_type);
+        }
+
+        #[test]
+        fn should_reduce_target_lot_given_lot_label() {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity USD
+                1970-01-01 commodity AAPL
+                1970-01-01 open Assets:Cash
+                1970-01-01 open Assets:Broker
+
+                2023-01-01 "Buy"
+                  Assets:Broker 10 AAPL {100 USD, "lot-A"}
+                  Assets:Cash -1000 USD
+
+                2023-01-02 "Buy"
+                  Assets:Broker 10 AAPL {2023-01-02, "lot-B", 120 USD}
+                  Assets:Cash -1200 USD
+
+                2023-01-03 "Sell"
+                  Assets:Broker -5 AAPL {"lot-B"} @ 130 USD
+                  Assets:Cash 650 USD
+            "#});
+            let mut operations = ledger.operations();
+            assert_eq!(0, operations.errors().unwrap().len());
+
+            let lots = operations
+                .account_lots("Assets:Broker", "AAPL")
+                .unwrap()
+                .into_iter()
+                .map(|lot| (lot.label, lot.amount))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                vec![
+                    (Some("lot-A".to_owned()), BigDecimal::from(10)),
+                    (Some("lot-B".to_owned()), BigDecimal::from(5))
+                ],
+                lots
+            );
+
+            let timezone = ledger.options.timezone;
+            let from = NaiveDate::from_ymd_opt(2023, 1, 1)
+                .unwrap()
+                .and_time(NaiveTime::MIN)
+                .and_local_timezone(timezone)
+                .unwrap();
+            let to = NaiveDate::from_ymd_opt(2023, 12, 31)
+                .unwrap()
+                .and_time(NaiveTime::MIN)
+                .and_local_timezone(timezone)
+                .unwrap();
+            let gains = operations.realized_gains("Assets:Broker", from, to).unwrap();
+            assert_eq!(1, gains.len());
+            assert_eq!(BigDecimal::from(120), gains[0].cost.number);
+            assert_eq!(BigDecimal::from(50), gains[0].gain.number);
+        }
+
+        #[test]
+        fn should_raise_error_given_lot_label_not_held() {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity USD
+                1970-01-01 commodity AAPL
+                1970-01-01 open Assets:Cash
+                1970-01-01 open Assets:Broker
+
+                2023-01-01 "Buy"
+                  Assets:Broker 10 AAPL {100 USD, "lot-A"}
+                  Assets:Cash -1000 USD
+
+                2023-01-03 "Sell"
+                  Assets:Broker -5 AAPL {"lot-C"} @ 130 USD
+                  Assets:Cash 650 USD
+            "#});
+            let errors = ledger.operations().errors().unwrap();
+            assert_eq!(1, errors.len());
+            assert_eq!(zhang_ast::error::ErrorKind::LotNotFound, errors[0].error_type);
+            assert_eq!("lot-C", errors[0].metas.get("label").unwrap());
+        }
+
+        #[test]
+        fn should_value_holdings_by_latest_price() {
+            let ledger = load_from_text(indoc! {r#"
+                option "operating_currency" "USD"
+                1970-01-01 commodity USD
+                1970-01-01 commodity AAPL
+                1970-01-01 open Assets:Cash
+                1970-01-01 open Assets:Broker
+
+                2023-01-01 "Buy"
+                  Assets:Broker 10 AAPL {100 USD}
+                  Assets:Cash -1000 USD
+
+                2023-01-02 "Buy"
+                  Assets:Broker 10 AAPL {120 USD}
+                  Assets:Cash -1200 USD
+
+                2023-01-03 "Sell"
+                  Assets:Broker -5 AAPL @ 130 USD
+                  Assets:Cash 650 USD
+
+                2023-02-01 price AAPL 150 USD
+            "#});
+            let operations = ledger.operations();
+
+            let date = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap().and_time(NaiveTime::MIN);
+            let holdings = operations.holdings(date).unwrap();
+            let broker = holdings.iter().find(|it| it.account.eq("Assets:Broker")).unwrap();
+            assert_eq!("AAPL", broker.commodity);
+            assert_eq!(BigDecimal::from(15), broker.amount);
+            assert_eq!(2, broker.lots.len());
+            assert_eq!(BigDecimal::from(5), broker.lots[0].amount);
+            assert_eq!(BigDecimal::from(500), broker.lots[0].cost_basis.as_ref().unwrap().number);
+            assert_eq!(BigDecimal::from(750), broker.lots[0].market_value.as_ref().unwrap().number);
+            assert_eq!(BigDecimal::from(250), broker.lots[0].unrealized_gain.as_ref().unwrap().number);
+            assert_eq!(BigDecimal::from(1700), broker.cost_basis.as_ref().unwrap().number);
+            assert_eq!(BigDecimal::from(2250), broker.market_value.as_ref().unwrap().number);
+            assert_eq!(BigDecimal::from(550), broker.unrealized_gain.as_ref().unwrap().number);
+            assert_eq!("USD", broker.unrealized_gain.as_ref().unwrap().currency);
+
+            let cash = holdings.iter().find(|it| it.account.eq("Assets:Cash")).unwrap();
+            assert_eq!(BigDecimal::from(-1550), cash.market_value.as_ref().unwrap().number);
+            assert!(cash.cost_basis.is_none());
+            assert!(cash.unrealized_gain.is_none());
+
+            let date = NaiveDate::from_ymd_opt(2023, 1, 15).unwrap().and_time(NaiveTime::MIN);
+            let holdings = operations.holdings(date).unwrap();
+            let broker = holdings.iter().find(|it| it.account.eq("Assets:Broker")).unwrap();
+            assert!(broker.market_value.is_none());
+            assert_eq!(BigDecimal::from(1700), broker.cost_basis.as_ref().unwrap().number);
+        }
+
+        #[test]
+        fn should_rebuild_holdings_at_past_date() {
+            let ledger = load_from_text(indoc! {r#"
+                option "operating_currency" "USD"
+                1970-01-01 commodity USD
+                1970-01-01 commodity AAPL
+                1970-01-01 open Assets:Cash
+                1970-01-01 open Assets:Broker
+
+                2023-01-01 "Buy"
+                  Assets:Broker 10 AAPL {100 USD}
+                  Assets:Cash -1000 USD
+
+                2023-01-02 "Buy"
+                  Assets:Broker 10 AAPL {120 USD}
+                  Assets:Cash -1200 USD
+
+                2023-01-03 "Sell"
+                  Assets:Broker -15 AAPL @ 130 USD
+                  Assets:Cash 1950 USD
+            "#});
+            let operations = ledger.operations();
+
+            let holdings = operations
+                .holdings(NaiveDate::from_ymd_opt(2022, 12, 31).unwrap().and_time(NaiveTime::MIN))
+                .unwrap();
+            assert!(holdings.is_empty());
+
+            let holdings = operations
+                .holdings(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap().and_hms_opt(12, 0, 0).unwrap())
+                .unwrap();
+            let broker = holdings.iter().find(|it| it.account.eq("Assets:Broker")).unwrap();
+            assert_eq!(BigDecimal::from(20), broker.amount);
+            assert_eq!(2, broker.lots.len());
+            assert_eq!(BigDecimal::from(2200), broker.cost_basis.as_ref().unwrap().number);
+
+            let holdings = operations
+                .holdings(NaiveDate::from_ymd_opt(2023, 1, 3).unwrap().and_hms_opt(12, 0, 0).unwrap())
+                .unwrap();
+            let broker = holdings.iter().find(|it| it.account.eq("Assets:Broker")).unwrap();
+            assert_eq!(BigDecimal::from(5), broker.amount);
+            assert_eq!(BigDecimal::from(600), broker.cost_basis.as_ref().unwrap().number);
+        }
+
+        #[test]
+        fn should_partially_consume_single_lot_given_filo() {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity USD
+                1970-01-01 commodity AAPL
+                1970-01-01 open Assets:Cash
+                1970-01-01 open Assets:Broker
+                  booking_method: "FILO"
+
+                2023-01-01 "Buy"
+                  Assets:Broker 10 AAPL {100 USD}
+                  Assets:Cash -1000 USD
+
+                2023-01-02 "Buy"
+                  Assets:Broker 10 AAPL {120 USD}
+                  Assets:Cash -1200 USD
+
+                2023-01-03 "Sell"
+                  Assets:Broker -3 AAPL @ 130 USD
+                  Assets:Cash 390 USD
+            "#});
+
+            let lots = lot_amounts(&ledger);
+            assert_eq!(
+                vec![
+                    (Some(BigDecimal::from(100)), BigDecimal::from(10)),
+                    (Some(BigDecimal::from(120)), BigDecimal::from(7))
+                ],
+                lots
+            );
+        }
+
+        mod realized_gain {
+            use bigdecimal::BigDecimal;
+            use chrono::{NaiveDate, NaiveTime};
+            use indoc::indoc;
+
+            use crate::domains::schemas::RealizedGainDomain;
+            use crate::test::load_from_text;
+
+            const LEDGER: &str = indoc! {r#"
+                1970-01-01 commodity USD
+                1970-01-01 commodity AAPL
+                1970-01-01 open Assets:Cash
+                1970-01-01 open Assets:Broker
+                  booking_method: "FIFO"
+
+                2023-01-01 "Buy"
+                  Assets:Broker 10 AAPL {100 USD}
+                  Assets:Cash -1000 USD
+
+                2023-01-02 "Buy"
+                  Assets:Broker 10 AAPL {120 USD}
+                  Assets:Cash -1200 USD
+            "#};
+
+            /// the realized gains of broker account from 2023-01-01 to the given date
+            fn realized_gains(transactions: &str, to: NaiveDate) -> Vec<RealizedGainDomain> {
+                let ledger = load_from_text(&format!("{}{}", LEDGER, transactions));
+                let timezone = ledger.options.timezone;
+                let datetime = |date: NaiveDate| date.and_time(NaiveTime::MIN).and_local_timezone(timezone).unwrap();
+                let from = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+                ledger.operations().realized_gains("Assets:Broker", datetime(from), datetime(to)).unwrap()
+            }
+
+            fn end_of_2023() -> NaiveDate {
+                NaiveDate::from_ymd_opt(2023, 12, 31).unwrap()
+            }
+
+            #[test]
+            fn should_calculate_realized_gains_of_consumed_lots() {
+                let sell = indoc! {r#"
+                    2023-01-03 "Sell"
+                      Assets:Broker -15 AAPL @ 130 USD
+                      Assets:Cash 1950 USD
+                "#};
+
+                let gains = realized_gains(sell, end_of_2023());
+                assert_eq!(2, gains.len());
+                assert_eq!(BigDecimal::from(10), gains[0].amount);
+                assert_eq!(BigDecimal::from(100), gains[0].cost.number);
+                assert_eq!(BigDecimal::from(300), gains[0].gain.number);
+                assert_eq!(BigDecimal::from(5), gains[1].amount);
+                assert_eq!(BigDecimal::from(120), gains[1].cost.number);
+                assert_eq!(BigDecimal::from(50), gains[1].gain.number);
+                assert_eq!("USD", gains[1].gain.currency);
+
+                let gains = realized_gains(sell, NaiveDate::from_ymd_opt(2023, 1, 1).unwrap());
+                assert_eq!(0, gains.len());
+            }
+
+            #[test]
+            fn should_calculate_realized_loss_given_disposal_price_lower_than_cost() {
+                let gains = realized_gains(
+                    indoc! {r#"
+                        2023-01-03 "Sell"
+                          Assets:Broker -4 AAPL {100 USD} @@ 360 USD
+                          Assets:Cash 400 USD
+                    "#},
+                    end_of_2023(),
+                );
+                assert_eq!(1, gains.len());
+                assert_eq!(BigDecimal::from(90), gains[0].price.number);
+                assert_eq!(BigDecimal::from(-40), gains[0].gain.number);
+            }
+
+            #[test]
+            fn should_not_calculate_realized_gain_given_zero_units_or_buying_with_total_price() {
+                let gains = realized_gains(
+                    indoc! {r#"
+                        2023-01-03 "Buy"
+                          Assets:Broker 10 AAPL {100 USD} @@ 1000 USD
+                          Assets:Cash -1000 USD
+
+                        2023-01-03 "Nothing"
+                          Assets:Broker 0 AAPL @@ 0 USD
+                          Assets:Cash 0 USD
+                    "#},
+                    end_of_2023(),
+                );
+                assert_eq!(0, gains.len());
+            }
+        }
     }
 
-    mod transaction {
+    mod depreciation {
         use indoc::indoc;
+        use zhang_ast::error::ErrorKind;
 
-        use crate::test::load_store;
+        use crate::test::load_from_text;
 
         #[test]
-        fn should_get_all_payees() {
-            let ledger = load_store(indoc! {r#"
-                1970-01-01 commodity USD
-                1970-01-01 open Assets:A
-                1970-01-01 open Expenses:A
+        fn should_generate_monthly_depreciation_transactions() {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity CNY
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Assets:Equipment
+                1970-01-01 open Expenses:Depreciation
+
+                2024-01-15 "Laptop"
+                  Assets:Equipment 3600 CNY
+                  Assets:Bank -3600 CNY
+                2024-01-15 depreciation Assets:Equipment Expenses:Depreciation 3600 CNY straight-line 36
+
+                2024-02-20 "Memory upgrade"
+                  Assets:Equipment 100 CNY
+                  Assets:Bank -100 CNY
+
+                2024-03-16 balance Assets:Equipment 3500 CNY
+                2024-03-16 balance Expenses:Depreciation 200 CNY
+                2027-01-16 balance Assets:Equipment 100 CNY
+            "#});
+            let mut operations = ledger.operations();
+            assert_eq!(0, operations.errors().unwrap().len());
+            let store = operations.read();
+            let depreciations = store
+                .transactions
+                .values()
+                .filter(|trx| trx.narration.as_deref() == Some("depreciation of Assets:Equipment"))
+                .count();
+            assert_eq!(36, depreciations);
+        }
 
-                1970-01-02 "Apple Inc" "iPhone 15"
-                  Assets:A -1000 USD
-                  Expenses:A
+        #[test]
+        fn should_raise_error_given_zero_lifetime() {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity CNY
+                1970-01-01 open Assets:Equipment
+                1970-01-01 open Expenses:Depreciation
 
-                1970-01-02 "Origan Inc" "iPhone 15"
-                  Assets:A -1000 USD
-                  Expenses:A
-            "#})
-            .ledger;
+                2024-01-15 depreciation Assets:Equipment Expenses:Depreciation 3600 CNY straight-line 0
+            "#});
             let mut operations = ledger.operations();
-            let result = operations.all_payees().unwrap();
-            assert!(result.contains(&"Origan Inc".to_owned()));
-            assert!(result.contains(&"Apple Inc".to_owned()));
+            let errors = operations.errors().unwrap();
+            assert_eq!(1, errors.len());
+            assert_eq!(ErrorKind::InvalidDepreciationLifetime, errors[0].error_type);
+            assert_eq!(0, operations.transaction_counts().unwrap());
         }
+    }
+
+    mod loan {
+        use bigdecimal::BigDecimal;
+        use indoc::indoc;
+        use zhang_ast::error::ErrorKind;
+
+        use crate::test::load_from_text;
 
         #[test]
-        fn should_remove_duplicated_payees() {
-            let ledger = load_store(indoc! {r#"
+        fn should_generate_repayments_and_remaining_principal() {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity CNY
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Liabilities:Loan
+                1970-01-01 open Expenses:Interest
+
+                2024-01-10 "Borrow"
+                  Assets:Bank 10000 CNY
+                  Liabilities:Loan -10000 CNY
+                2024-01-10 loan Liabilities:Loan 10000 CNY 12% 12 Assets:Bank Expenses:Interest
+
+                2024-02-11 balance Liabilities:Loan -9211.51 CNY
+                2024-02-11 balance Expenses:Interest 100 CNY
+                2025-01-11 balance Liabilities:Loan 0 CNY
+            "#});
+            let mut operations = ledger.operations();
+            assert_eq!(0, operations.errors().unwrap().len());
+
+            let payments = operations.loan_payments("Liabilities:Loan").unwrap();
+            assert_eq!(12, payments.len());
+            assert_eq!(BigDecimal::from(100), payments[0].interest.number);
+            assert_eq!("9211.51".parse::<BigDecimal>().unwrap(), payments[0].remaining_principal.number);
+            assert_eq!(BigDecimal::from(0), payments[11].remaining_principal.number);
+        }
+
+        #[test]
+        fn should_raise_error_given_zero_term() {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity CNY
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Liabilities:Loan
+                1970-01-01 open Expenses:Interest
+
+                2024-01-10 loan Liabilities:Loan 10000 CNY 12% 0 Assets:Bank Expenses:Interest
+            "#});
+            let mut operations = ledger.operations();
+            let errors = operations.errors().unwrap();
+            assert_eq!(1, errors.len());
+            assert_eq!(ErrorKind::InvalidLoanTerm, errors[0].error_type);
+            assert!(operations.loan_payments("Liabilities:Loan").unwrap().is_empty());
+        }
+    }
+
+    mod accrue {
+        use indoc::indoc;
+        use zhang_ast::error::ErrorKind;
+
+        use crate::test::load_from_text;
+
+        #[test]
+        fn should_recognize_deferred_amount_over_periods() {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity CNY
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Assets:Prepaid:Insurance
+                1970-01-01 open Expenses:Insurance
+                1970-01-01 open Liabilities:Unearned
+                1970-01-01 open Income:Consulting
+
+                2024-01-01 "Annual insurance"
+                  Assets:Prepaid:Insurance 1000 CNY
+                  Assets:Bank -1000 CNY
+                2024-01-01 accrue Assets:Prepaid:Insurance Expenses:Insurance 1000 CNY 12 monthly
+
+                2024-01-01 "Consulting paid in advance"
+                  Assets:Bank 4000 CNY
+                  Liabilities:Unearned -4000 CNY
+                2024-01-01 accrue Liabilities:Unearned Income:Consulting -4000 CNY 4 quarterly
+
+                2024-03-02 balance Expenses:Insurance 249.99 CNY
+                2024-03-02 balance Assets:Prepaid:Insurance 750.01 CNY
+                2024-12-02 balance Expenses:Insurance 1000 CNY
+                2024-12-02 balance Assets:Prepaid:Insurance 0 CNY
+                2024-07-02 balance Income:Consulting -3000 CNY
+                2024-07-02 balance Liabilities:Unearned -1000 CNY
+            "#});
+            let mut operations = ledger.operations();
+            assert_eq!(0, operations.errors().unwrap().len());
+        }
+
+        #[test]
+        fn should_raise_error_given_zero_periods() {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity CNY
+                1970-01-01 open Assets:Prepaid
+                1970-01-01 open Expenses:Insurance
+
+                2024-01-01 accrue Assets:Prepaid Expenses:Insurance 1000 CNY 0 monthly
+            "#});
+            let mut operations = ledger.operations();
+            let errors = operations.errors().unwrap();
+            assert_eq!(1, errors.len());
+            assert_eq!(ErrorKind::InvalidAccrualPeriods, errors[0].error_type);
+        }
+
+        #[test]
+        fn should_raise_error_given_periods_overflowing_months() {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity CNY
+                1970-01-01 open Assets:Prepaid
+                1970-01-01 open Expenses:Insurance
+
+                2024-01-01 accrue Assets:Prepaid Expenses:Insurance 1000 CNY 400000000 yearly
+            "#});
+            let mut operations = ledger.operations();
+            let errors = operations.errors().unwrap();
+            assert_eq!(1, errors.len());
+            assert_eq!(ErrorKind::InvalidAccrualPeriods, errors[0].error_type);
+            assert!(operations.read().transactions.is_empty());
+        }
+    }
+
+    mod tax {
+        use bigdecimal::BigDecimal;
+        use chrono::{NaiveDate, NaiveTime};
+        use indoc::indoc;
+        use zhang_ast::amount::Amount;
+        use zhang_ast::error::ErrorKind;
+        use zhang_ast::Directive;
+
+        use crate::domains::schemas::BalanceInterval;
+        use crate::test::load_from_text;
+
+        #[test]
+        fn should_split_gross_posting_by_tax_rate() {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity CNY
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Liabilities:Tax:VAT
+                1970-01-01 open Income:Sales
+                1970-01-01 open Expenses:Office
+                  tax-rate: "13%"
+                  tax-account: "Liabilities:Tax:VAT"
+
+                2024-01-05 "Paper"
+                  Expenses:Office 113 CNY
+                  Assets:Bank
+
+                2024-01-20 "Consulting"
+                  tax-rate: "0.06"
+                  tax-account: "Liabilities:Tax:VAT"
+                  Income:Sales -106 CNY
+                  Assets:Bank
+
+                2024-02-10 "Printer"
+                  Expenses:Office 226 CNY
+                  Assets:Bank
+
+                2024-03-01 balance Expenses:Office 300 CNY
+                2024-03-01 balance Income:Sales -100 CNY
+                2024-03-01 balance Assets:Bank -233 CNY
+                2024-03-01 balance Liabilities:Tax:VAT 33 CNY
+            "#});
+            let mut operations = ledger.operations();
+            assert_eq!(0, operations.errors().unwrap().len());
+
+            let timezone = ledger.options.timezone;
+            let datetime = |month: u32| {
+                NaiveDate::from_ymd_opt(2024, month, 1)
+                    .unwrap()
+                    .and_time(NaiveTime::MIN)
+                    .and_local_timezone(timezone)
+                    .unwrap()
+            };
+            let report = operations.tax_report(BalanceInterval::Month, datetime(1), datetime(12)).unwrap();
+            assert_eq!(2, report.len());
+            assert_eq!(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(), report[0].period);
+            assert_eq!("Liabilities:Tax:VAT", report[0].tax_account);
+            assert_eq!(BigDecimal::from(6), report[0].collected);
+            assert_eq!(BigDecimal::from(13), report[0].paid);
+            assert_eq!(BigDecimal::from(-7), report[0].payable);
+            assert_eq!(BigDecimal::from(26), report[1].paid);
+
+            let report = operations.tax_report(BalanceInterval::Quarter, datetime(1), datetime(12)).unwrap();
+            assert_eq!(1, report.len());
+            assert_eq!(BigDecimal::from(39), report[0].paid);
+        }
+
+        #[test]
+        fn should_keep_directives_as_written_after_splitting_tax() {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Liabilities:Tax:VAT
+                1970-01-01 open Expenses:Office
+                  tax-rate: "13%"
+                  tax-account: "Liabilities:Tax:VAT"
+
+                2024-01-05 "Paper"
+                  Expenses:Office 113 CNY
+                  Assets:Bank
+            "#});
+            let Directive::Transaction(trx) = &ledger.directives.last().unwrap().data else {
+                unreachable!()
+            };
+            assert_eq!(2, trx.postings.len());
+            assert_eq!(Some(Amount::new(BigDecimal::from(113), "CNY")), trx.postings[0].units);
+            assert_eq!(3, ledger.store.read().unwrap().postings.len());
+        }
+
+        #[test]
+        fn should_prefer_posting_tax_rate_and_round_net_amount() {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity CNY
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Liabilities:Tax:VAT
+                1970-01-01 open Expenses:Office
+                  tax-rate: "13%"
+                  tax-account: "Liabilities:Tax:VAT"
+
+                2024-01-05 "Paper"
+                  Expenses:Office 10 CNY
+                    tax-rate: "9%"
+                  Assets:Bank
+
+                2024-01-06 "Exempted"
+                  Expenses:Office 10 CNY
+                    tax-rate: "0%"
+                  Assets:Bank
+
+                2024-02-01 balance Expenses:Office 19.17 CNY
+                2024-02-01 balance Liabilities:Tax:VAT 0.83 CNY
+            "#});
+            let mut operations = ledger.operations();
+            assert_eq!(0, operations.errors().unwrap().len());
+        }
+
+        #[test]
+        fn should_raise_error_given_tax_rate_without_tax_account() {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity CNY
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Expenses:Office
+                  tax-rate: "13%"
+
+                2024-01-05 "Paper"
+                  Expenses:Office 113 CNY
+                  Assets:Bank
+
+                2024-02-01 balance Expenses:Office 113 CNY
+            "#});
+            let mut operations = ledger.operations();
+            let errors = operations.errors().unwrap();
+            assert_eq!(1, errors.len());
+            assert_eq!(ErrorKind::TaxAccountMissing, errors[0].error_type);
+        }
+    }
+
+    mod invoice {
+        use bigdecimal::BigDecimal;
+        use chrono::NaiveDate;
+        use indoc::indoc;
+        use zhang_ast::error::ErrorKind;
+
+        use crate::domains::schemas::{AgingBucket, InvoiceKind};
+        use crate::test::load_from_text;
+
+        #[test]
+        fn should_track_outstanding_invoices_with_aging() {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity CNY
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Assets:Receivable
+                1970-01-01 open Liabilities:Payable
+                1970-01-01 open Income:Consulting
+                1970-01-01 open Expenses:Rent
+
+                2024-01-01 custom "invoice" "INV-001" "ACME" Assets:Receivable
+                  due: "2024-01-31"
+                  item: "Consulting 1000 CNY"
+                  item: "Travel 200 CNY"
+                2024-01-01 "ACME" "Consulting" ^INV-001
+                  Assets:Receivable 1200 CNY
+                  Income:Consulting
+
+                2024-02-10 "ACME" "Partial payment" ^INV-001
+                  Assets:Bank 500 CNY
+                  Assets:Receivable
+
+                2024-02-01 custom "invoice" "RENT-02" "Landlord" Liabilities:Payable
+                  due: "2024-02-05"
+                  item: "Rent 3000 CNY"
+                2024-02-01 "Landlord" "Rent"
+                  Expenses:Rent 3000 CNY
+                  Liabilities:Payable
+
+                2024-02-05 "Landlord" "Rent" ^RENT-02
+                  Liabilities:Payable 3000 CNY
+                  Assets:Bank
+            "#});
+            let mut operations = ledger.operations();
+            assert_eq!(0, operations.errors().unwrap().len());
+
+            let invoices = operations.open_invoices(NaiveDate::from_ymd_opt(2024, 2, 1).unwrap()).unwrap();
+            assert_eq!(2, invoices.len());
+            assert_eq!("INV-001", invoices[0].invoice.id);
+            assert_eq!(InvoiceKind::Receivable, invoices[0].invoice.kind);
+            assert_eq!(BigDecimal::from(1200), invoices[0].invoice.total.number);
+            assert_eq!(BigDecimal::from(0), invoices[0].paid.number);
+            assert_eq!(1, invoices[0].days_overdue);
+            assert_eq!(AgingBucket::Days1To30, invoices[0].aging);
+            assert_eq!("RENT-02", invoices[1].invoice.id);
+            assert_eq!(AgingBucket::Current, invoices[1].aging);
+
+            let invoices = operations.open_invoices(NaiveDate::from_ymd_opt(2024, 4, 1).unwrap()).unwrap();
+            assert_eq!(1, invoices.len());
+            assert_eq!(BigDecimal::from(500), invoices[0].paid.number);
+            assert_eq!(BigDecimal::from(700), invoices[0].outstanding.number);
+            assert_eq!(AgingBucket::Days61To90, invoices[0].aging);
+        }
+
+        #[test]
+        fn should_raise_error_given_invalid_or_duplicated_invoice() {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity CNY
                 1970-01-01 commodity USD
-                1970-01-01 open Assets:A
-                1970-01-01 open Expenses:A
+                1970-01-01 open Assets:Receivable
+
+                2024-01-01 custom "invoice" "INV-001" "ACME" Assets:Receivable
+                  item: "Consulting 1000 CNY"
+                2024-01-02 custom "invoice" "INV-001" "ACME" Assets:Receivable
+                  item: "Consulting 1000 CNY"
+                2024-01-03 custom "invoice" "INV-002" "ACME" Assets:Receivable
+                  item: "Consulting 1000 CNY"
+                  item: "Travel 200 USD"
+            "#});
+            let mut operations = ledger.operations();
+            let errors = operations.errors().unwrap();
+            assert_eq!(2, errors.len());
+            assert_eq!(ErrorKind::DuplicatedInvoice, errors[0].error_type);
+            assert_eq!(ErrorKind::InvalidInvoice, errors[1].error_type);
+        }
+    }
 
-                1970-01-02 "Apple Inc" "iPhone 15"
-                  Assets:A -1000 USD
-                  Expenses:A
+    mod open_balance {
+        use indoc::indoc;
+        use zhang_ast::error::ErrorKind;
 
-                1970-01-02 "Apple Inc" "iPhone 15"
-                  Assets:A -1000 USD
-                  Expenses:A
-            "#})
-            .ledger;
+        use crate::domains::schemas::MetaType;
+        use crate::test::load_from_text;
+
+        #[test]
+        fn should_open_account_with_initial_balance() {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity CNY
+                1970-01-01 open Equity:Opening-Balances
+                1970-01-01 open Equity:Migration
+                1970-01-01 open Expenses:Food
+
+                2024-01-01 open-balance Assets:Bank 1000 CNY
+                  alias: "bank"
+                2024-01-01 open-balance Liabilities:Card -200 CNY Equity:Migration
+
+                2024-01-02 "KFC"
+                  Liabilities:Card -50 CNY
+                  Expenses:Food
+
+                2024-01-03 balance Assets:Bank 1000 CNY
+                2024-01-03 balance Liabilities:Card -250 CNY
+                2024-01-03 balance Equity:Opening-Balances -1000 CNY
+                2024-01-03 balance Equity:Migration 200 CNY
+            "#});
             let mut operations = ledger.operations();
-            let result = operations.all_payees().unwrap();
-            assert!(result.contains(&"Apple Inc".to_owned()));
-            assert_eq!(1, result.len());
+            assert_eq!(0, operations.errors().unwrap().len());
+            assert!(operations.account("Assets:Bank").unwrap().is_some());
+            let alias = operations.meta(MetaType::AccountMeta, "Assets:Bank", "alias").unwrap().unwrap();
+            assert_eq!("bank", alias.value);
+        }
+
+        #[test]
+        fn should_raise_error_given_equity_account_not_opened() {
+            let ledger = load_from_text(indoc! {r#"
+                1970-01-01 commodity CNY
+
+                2024-01-01 open-balance Assets:Bank 1000 CNY
+            "#});
+            let mut operations = ledger.operations();
+            let errors = operations.errors().unwrap();
+            assert_eq!(1, errors.len());
+            assert_eq!(ErrorKind::AccountDoesNotExist, errors[0].error_type);
         }
     }
 }
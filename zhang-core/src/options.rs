@@ -1,16 +1,20 @@
 use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::str::FromStr;
 
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{Datelike, NaiveDateTime};
 use chrono_tz::Tz;
 use itertools::Itertools;
 use log::error;
 use once_cell::sync::OnceCell;
 use strum::{AsRefStr, EnumIter, EnumString, IntoEnumIterator};
-use zhang_ast::error::ErrorKind;
-use zhang_ast::{Directive, Options, Rounding, SpanInfo, Spanned, ZhangString};
+use zhang_ast::error::{ErrorKind, ErrorSeverity};
+use zhang_ast::{Date, Directive, Options, Rounding, SpanInfo, Spanned, Transaction, ZhangString};
 
 use crate::constants::*;
+use crate::domains::schemas::CommodityDomain;
 use crate::domains::Operations;
 use crate::features::Features;
 use crate::{ZhangError, ZhangResult};
@@ -21,9 +25,274 @@ pub struct InMemoryOptions {
     pub default_rounding: Rounding,
     pub default_balance_tolerance_precision: i32,
     pub timezone: Tz,
+    pub budget_convert_to_operating_currency: bool,
+    /// the max distance of balance assertion to be treated as passed with warning
+    pub balance_tolerance: BigDecimal,
+    /// payee variants and their normalized payee, given by options `payee-alias` in the format of `{VARIANT}={PAYEE}`
+    pub payee_aliases: Vec<(String, String)>,
+    pub insert_strategy: InsertStrategy,
+    pub transaction_date: TransactionDate,
+    /// the folder relative to the entry where the uploaded documents are stored
+    pub document_directory: String,
+    pub document_naming: DocumentNaming,
+    /// the overrides of error severity, given by options `error_severity`
+    pub error_severities: Vec<ErrorSeverityRule>,
+    /// the required or typed metas, given by options `meta_schema`
+    pub meta_schemas: Vec<MetaSchemaRule>,
     pub features: Features,
 }
 
+/// the override of error severity given by option `error_severity` in the format of `[{FILE}:]{KIND}={SEVERITY}`,
+/// where the severity is one of `error`, `warning`, `info` and `ignore`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorSeverityRule {
+    /// the rule only applies to the errors of file whose path ends with it
+    pub file: Option<PathBuf>,
+    pub kind: ErrorKind,
+    /// `None` means the error is suppressed
+    pub severity: Option<ErrorSeverity>,
+}
+
+impl FromStr for ErrorSeverityRule {
+    type Err = ZhangError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (target, severity) = s.split_once('=').ok_or(ZhangError::InvalidOptionValue)?;
+        let (file, kind) = match target.rsplit_once(':') {
+            Some((file, kind)) => (Some(PathBuf::from(file.trim())), kind),
+            None => (None, target),
+        };
+        let kind = ErrorKind::from_str(kind.trim()).map_err(|_| ZhangError::InvalidOptionValue)?;
+        let severity = match severity.trim().to_lowercase().as_str() {
+            "ignore" => None,
+            other => Some(ErrorSeverity::from_str(other).map_err(|_| ZhangError::InvalidOptionValue)?),
+        };
+        Ok(ErrorSeverityRule { file, kind, severity })
+    }
+}
+
+impl ErrorSeverityRule {
+    fn is_applied_to(&self, kind: &ErrorKind, span: Option<&SpanInfo>) -> bool {
+        let in_file = match &self.file {
+            Some(file) => span.and_then(|it| it.filename.as_ref()).map(|it| it.ends_with(file)).unwrap_or(false),
+            None => true,
+        };
+        self.kind.eq(kind) && in_file
+    }
+}
+
+/// the directives which meta schema can be declared for by their keywords, and `posting` for the postings of transactions
+pub const META_SCHEMA_TARGETS: [&str; 22] = [
+    "posting",
+    "transaction",
+    "open",
+    "close",
+    "commodity",
+    "balance",
+    "pad",
+    "note",
+    "document",
+    "price",
+    "event",
+    "custom",
+    "split",
+    "depreciation",
+    "loan",
+    "accrue",
+    "open-balance",
+    "template",
+    "budget",
+    "budget-add",
+    "budget-transfer",
+    "budget-close",
+];
+
+/// the type of meta value declared by option `meta_schema`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum MetaValueType {
+    /// any value
+    String,
+    Number,
+    /// the date in the format of `2024-01-01`
+    Date,
+    /// `true` or `false`
+    Bool,
+    /// the file relative to the ledger entry, which must exist
+    Path,
+    /// the account which is opened
+    Account,
+}
+
+/// the schema of meta given by option `meta_schema` in the format of `{TARGET}[:{ACCOUNT}] {KEY}={TYPE}[?]`, e.g.
+/// `posting:Expenses:Business receipt=path`. the meta is required unless the type is suffixed by `?`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetaSchemaRule {
+    /// one of [META_SCHEMA_TARGETS]
+    pub target: String,
+    /// the rule only applies to the account and its sub accounts, e.g. `Expenses:Business` or `Expenses:Business:*`
+    pub account: Option<String>,
+    pub key: String,
+    pub value_type: MetaValueType,
+    pub required: bool,
+}
+
+impl FromStr for MetaSchemaRule {
+    type Err = ZhangError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (target, definition) = s.trim().split_once(char::is_whitespace).ok_or(ZhangError::InvalidOptionValue)?;
+        let (target, account) = match target.split_once(':') {
+            Some((target, account)) => (target, Some(account.trim_end_matches('*').trim_end_matches(':').to_owned())),
+            None => (target, None),
+        };
+        if !META_SCHEMA_TARGETS.contains(&target) || account.as_ref().is_some_and(|it| it.is_empty()) {
+            return Err(ZhangError::InvalidOptionValue);
+        }
+        let (key, value_type) = definition.split_once('=').ok_or(ZhangError::InvalidOptionValue)?;
+        let value_type = value_type.trim();
+        let (value_type, required) = match value_type.strip_suffix('?') {
+            Some(value_type) => (value_type, false),
+            None => (value_type, true),
+        };
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(ZhangError::InvalidOptionValue);
+        }
+        Ok(MetaSchemaRule {
+            target: target.to_owned(),
+            account,
+            key: key.to_owned(),
+            value_type: MetaValueType::from_str(value_type.trim()).map_err(|_| ZhangError::InvalidOptionValue)?,
+            required,
+        })
+    }
+}
+
+impl MetaSchemaRule {
+    pub fn is_applied_to_account(&self, account_name: &str) -> bool {
+        match &self.account {
+            Some(account) => account_name == account || account_name.starts_with(&format!("{}:", account)),
+            None => true,
+        }
+    }
+}
+
+/// the file where the appended directives are written to, given by option `insert_strategy`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, AsRefStr, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum InsertStrategy {
+    /// the main file of ledger
+    Single,
+    /// the file of year, e.g. `data/2024.zhang`
+    Yearly,
+    /// the file of month, e.g. `data/2024/1.zhang`
+    #[default]
+    Monthly,
+}
+
+impl InsertStrategy {
+    /// the file relative to the entry that the directive is written to, `None` means the main file.
+    /// the directives without date are always written to the main file
+    pub fn endpoint(&self, directive: &Directive) -> Option<PathBuf> {
+        let datetime = directive.datetime()?;
+        match self {
+            InsertStrategy::Single => None,
+            InsertStrategy::Yearly => Some(PathBuf::from(datetime.format("data/%Y.zhang").to_string())),
+            // the month is not padded, which is the layout of the ledgers written before the option is introduced
+            InsertStrategy::Monthly => Some(PathBuf::from(format!("data/{}/{}.zhang", datetime.year(), datetime.month()))),
+        }
+    }
+}
+
+/// the date of transaction which drives the balances and reports, given by option `transaction_date`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, AsRefStr, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum TransactionDate {
+    /// the date before `=` of `2024-05-01=2024-05-03`
+    #[default]
+    Primary,
+    /// the date after `=` of `2024-05-01=2024-05-03`, the primary date is used if the transaction has no auxiliary date
+    Auxiliary,
+}
+
+impl TransactionDate {
+    pub fn of<'a>(&self, trx: &'a Transaction) -> &'a Date {
+        match self {
+            TransactionDate::Primary => &trx.date,
+            TransactionDate::Auxiliary => trx.auxiliary_date.as_ref().unwrap_or(&trx.date),
+        }
+    }
+
+    /// replace the date of transaction which [TransactionDate::of] refers to, the other date is kept
+    pub fn set(&self, trx: &mut Transaction, date: Date) {
+        match (self, trx.auxiliary_date.as_mut()) {
+            (TransactionDate::Auxiliary, Some(auxiliary_date)) => *auxiliary_date = date,
+            _ => trx.date = date,
+        }
+    }
+}
+
+/// how the uploaded document is named under the document directory, given by option `document_naming`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, AsRefStr, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum DocumentNaming {
+    /// the folder of random uuid, e.g. `attachments/67e55044-10b1-426f-9247-bb680e5fe0c8/receipt.pdf`
+    #[default]
+    Uuid,
+    /// the folder of content hash, so that the same file uploaded twice is stored once, e.g. `attachments/9f86d081884c7d65/receipt.pdf`
+    Hashed,
+    /// the folder of month with the day prefixed, e.g. `attachments/2024/01/15-receipt.pdf`,
+    /// the document of the same name uploaded in the same day is replaced
+    Dated,
+}
+
+/// the options deciding the order of directives. the directives are sorted before the options are handled,
+/// so that they are resolved from the option directives
+#[derive(Debug, Clone, Copy)]
+pub struct DirectiveOrder {
+    pub transaction_date: TransactionDate,
+    pub timezone: Tz,
+}
+
+impl Default for DirectiveOrder {
+    fn default() -> Self {
+        DirectiveOrder::declared_by(&[])
+    }
+}
+
+impl DirectiveOrder {
+    pub fn declared_by(directives: &[Spanned<Directive>]) -> Self {
+        let declared = |option: BuiltinOption| {
+            directives.iter().rev().find_map(|it| match &it.data {
+                Directive::Option(directive) if directive.key.as_str().eq(option.key()) => Some(directive.value.as_str().trim().to_owned()),
+                _ => None,
+            })
+        };
+        DirectiveOrder {
+            transaction_date: declared(BuiltinOption::TransactionDate)
+                .and_then(|it| TransactionDate::from_str(&it).ok())
+                .unwrap_or_default(),
+            timezone: declared(BuiltinOption::Timezone)
+                .and_then(|it| it.parse::<Tz>().ok())
+                .or_else(|| BuiltinOption::Timezone.default_value().parse::<Tz>().ok())
+                .unwrap_or(Tz::UTC),
+        }
+    }
+
+    /// the datetime of directive in the timezone of ledger, in which the transaction is dated by the chosen date
+    pub fn datetime_of(&self, directive: &Directive) -> Option<NaiveDateTime> {
+        let date = match directive {
+            Directive::Transaction(trx) => self.transaction_date.of(trx),
+            other => other.date()?,
+        };
+        Some(match date {
+            Date::ZonedDatetime(datetime) => datetime.with_timezone(&self.timezone).naive_local(),
+            other => other.naive_datetime(),
+        })
+    }
+}
+
 #[derive(Debug, AsRefStr, EnumIter, EnumString)]
 #[strum(serialize_all = "snake_case")]
 #[non_exhaustive]
@@ -33,6 +302,12 @@ pub enum BuiltinOption {
     DefaultBalanceTolerancePrecision,
     DefaultCommodityPrecision,
     Timezone,
+    BudgetConvertToOperatingCurrency,
+    BalanceTolerance,
+    InsertStrategy,
+    TransactionDate,
+    DocumentDirectory,
+    DocumentNaming,
 }
 
 fn detect_timezone() -> String {
@@ -64,6 +339,12 @@ impl BuiltinOption {
             BuiltinOption::DefaultBalanceTolerancePrecision => DEFAULT_BALANCE_TOLERANCE_PRECISION_PLAIN.to_owned(),
             BuiltinOption::DefaultCommodityPrecision => DEFAULT_COMMODITY_PRECISION_PLAIN.to_owned(),
             BuiltinOption::Timezone => detect_timezone(),
+            BuiltinOption::BudgetConvertToOperatingCurrency => DEFAULT_BUDGET_CONVERT_TO_OPERATING_CURRENCY_PLAIN.to_owned(),
+            BuiltinOption::BalanceTolerance => DEFAULT_BALANCE_TOLERANCE_PLAIN.to_owned(),
+            BuiltinOption::InsertStrategy => InsertStrategy::default().as_ref().to_owned(),
+            BuiltinOption::TransactionDate => TransactionDate::default().as_ref().to_owned(),
+            BuiltinOption::DocumentDirectory => DEFAULT_DOCUMENT_DIRECTORY.to_owned(),
+            BuiltinOption::DocumentNaming => DocumentNaming::default().as_ref().to_owned(),
         }
     }
     pub fn key(&self) -> &str {
@@ -92,16 +373,19 @@ impl InMemoryOptions {
         if let Ok(option) = BuiltinOption::from_str(&key) {
             match option {
                 BuiltinOption::OperatingCurrency => {
-                    let precision = self.default_balance_tolerance_precision;
-                    let prefix: Option<String> = None;
-                    let suffix: Option<String> = None;
-                    let rounding = self.default_rounding;
-
                     let has_operating_currency = operation.option::<String>(&key)?.is_some();
                     if has_operating_currency {
                         operation.new_error(ErrorKind::MultipleOperatingCurrencyDetect, span, HashMap::default())?;
                     }
-                    operation.insert_commodity(&value, precision, prefix, suffix, rounding)?;
+                    operation.insert_commodity(CommodityDomain {
+                        name: value.clone(),
+                        precision: self.default_balance_tolerance_precision,
+                        prefix: None,
+                        suffix: None,
+                        rounding: self.default_rounding,
+                        grouping_separator: None,
+                        decimal_symbol: None,
+                    })?;
 
                     value.clone_into(&mut self.operating_currency);
                 }
@@ -123,12 +407,100 @@ impl InMemoryOptions {
                         return Ok(BuiltinOption::Timezone.default_value());
                     }
                 },
+                BuiltinOption::BudgetConvertToOperatingCurrency => {
+                    self.budget_convert_to_operating_currency = value.to_lowercase().eq(TRUE);
+                }
+                BuiltinOption::BalanceTolerance => match BigDecimal::from_str(value.trim()) {
+                    Ok(tolerance) => self.balance_tolerance = tolerance.abs(),
+                    Err(e) => {
+                        error!("balance tolerance '{value}' is not a valid number, fallback to use zero tolerance: {e}");
+                        return Ok(BuiltinOption::BalanceTolerance.default_value());
+                    }
+                },
+                BuiltinOption::InsertStrategy => match InsertStrategy::from_str(value.trim()) {
+                    Ok(strategy) => self.insert_strategy = strategy,
+                    Err(_) => {
+                        error!("insert strategy '{value}' is not one of single, yearly and monthly, fallback to use monthly");
+                        return Ok(BuiltinOption::InsertStrategy.default_value());
+                    }
+                },
+                BuiltinOption::TransactionDate => match TransactionDate::from_str(value.trim()) {
+                    Ok(transaction_date) => self.transaction_date = transaction_date,
+                    Err(_) => {
+                        error!("transaction date '{value}' is not one of primary and auxiliary, fallback to use primary");
+                        return Ok(BuiltinOption::TransactionDate.default_value());
+                    }
+                },
+                BuiltinOption::DocumentDirectory => {
+                    let directory = value.trim().trim_matches('/');
+                    if directory.is_empty() {
+                        error!("document directory '{value}' is empty, fallback to use {DEFAULT_DOCUMENT_DIRECTORY}");
+                        return Ok(BuiltinOption::DocumentDirectory.default_value());
+                    }
+                    directory.clone_into(&mut self.document_directory);
+                }
+                BuiltinOption::DocumentNaming => match DocumentNaming::from_str(value.trim()) {
+                    Ok(naming) => self.document_naming = naming,
+                    Err(_) => {
+                        error!("document naming '{value}' is not one of uuid, hashed and dated, fallback to use uuid");
+                        return Ok(BuiltinOption::DocumentNaming.default_value());
+                    }
+                },
+            }
+        }
+        if key.eq(KEY_ERROR_SEVERITY) {
+            match ErrorSeverityRule::from_str(&value) {
+                Ok(rule) => self.error_severities.push(rule),
+                Err(_) => error!("error severity '{value}' is invalid, it should be in the format of '[FILE:]KIND=error|warning|info|ignore'"),
+            }
+        }
+        if key.eq(KEY_META_SCHEMA) {
+            match MetaSchemaRule::from_str(&value) {
+                Ok(rule) => self.meta_schemas.push(rule),
+                Err(_) => error!("meta schema '{value}' is invalid, it should be in the format of 'TARGET[:ACCOUNT] KEY=TYPE[?]'"),
+            }
+        }
+        if key.eq(KEY_PAYEE_ALIAS) {
+            match value.split_once('=') {
+                Some((variant, payee)) if !variant.trim().is_empty() && !payee.trim().is_empty() => {
+                    self.payee_aliases.push((variant.trim().to_lowercase(), payee.trim().to_owned()));
+                }
+                _ => error!("payee alias '{value}' is invalid, it should be in the format of 'VARIANT=PAYEE'"),
             }
         }
         self.features.handle_options(&key, &value);
 
         Ok(value)
     }
+
+    /// the severity of error, the rule of file takes precedence over the global one, and the later rule takes precedence
+    /// over the earlier one. `None` means the error is suppressed
+    pub fn error_severity(&self, kind: &ErrorKind, span: Option<&SpanInfo>) -> Option<ErrorSeverity> {
+        self.error_severities
+            .iter()
+            .filter(|rule| rule.is_applied_to(kind, span))
+            .max_by_key(|rule| rule.file.is_some())
+            .map(|rule| rule.severity)
+            .unwrap_or(Some(kind.severity()))
+    }
+
+    pub fn directive_order(&self) -> DirectiveOrder {
+        DirectiveOrder {
+            transaction_date: self.transaction_date,
+            timezone: self.timezone,
+        }
+    }
+
+    /// normalize the payee by the longest payee alias whose variant is the case-insensitive prefix of payee
+    pub fn normalize_payee<'a>(&'a self, payee: &'a str) -> &'a str {
+        let lowercase_payee = payee.to_lowercase();
+        self.payee_aliases
+            .iter()
+            .filter(|(variant, _)| lowercase_payee.starts_with(variant.as_str()))
+            .max_by_key(|(variant, _)| variant.len())
+            .map(|(_, normalized)| normalized.as_str())
+            .unwrap_or(payee)
+    }
 }
 
 impl Default for InMemoryOptions {
@@ -138,6 +510,15 @@ impl Default for InMemoryOptions {
             default_rounding: Rounding::RoundDown,
             default_balance_tolerance_precision: 2,
             timezone: BuiltinOption::Timezone.default_value().parse().expect("invalid timezone"),
+            budget_convert_to_operating_currency: false,
+            balance_tolerance: BigDecimal::zero(),
+            payee_aliases: vec![],
+            insert_strategy: InsertStrategy::default(),
+            transaction_date: TransactionDate::default(),
+            document_directory: DEFAULT_DOCUMENT_DIRECTORY.to_owned(),
+            document_naming: DocumentNaming::default(),
+            error_severities: vec![],
+            meta_schemas: vec![],
             features: Features::default(),
         }
     }
@@ -1,6 +1,6 @@
 pub use semver::Version;
 use serde::{Deserialize, Serialize};
-use zhang_ast::{Directive, Spanned};
+use zhang_ast::{Custom, Directive, Spanned, Transaction};
 
 pub mod http;
 pub mod store;
@@ -8,6 +8,7 @@ pub mod store;
 /// indicate which type the plugin belongs to
 /// the plugin can be multiple types
 #[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum PluginType {
     /// the plugin can handle batches of directive, usually used to filter or combine directives, signature would be like [Plugin::processor]
     Processor,
@@ -24,6 +25,48 @@ pub enum PluginType {
     /// the plugin can handle the customized routes, usually used for new page's API
     /// like the request of URL `/api/plugins/{PLUGIN_NAME}/my-resources` will be forwarded to plugin's router by zhang-core
     Router,
+
+    /// the plugin is invoked before every transaction is processed, usually used to add postings, tags or metas into transaction,
+    /// the returned transaction replaces the original one in processing. the hook signature would be like [Plugin::on_transaction]
+    /// ```rust,ignore
+    /// fn on_transaction(transaction: Spanned<Transaction>) -> Transaction {
+    ///     // your logic here
+    /// }
+    /// ```
+    TransactionHook,
+
+    /// the plugin can validate every directive and report errors with plugin-defined code and message, e.g. to enforce organization policies.
+    /// the validator signature would be like [Plugin::validator]
+    /// ```rust,ignore
+    /// fn validator(directive: Spanned<Directive>) -> Vec<PluginValidationError> {
+    ///     // your logic here
+    /// }
+    /// ```
+    Validator,
+
+    /// the plugin can handle `custom` directives of the types declared by `custom_types`, and write key/value rows into
+    /// the plugin-scoped area of store, which is exposed by the HTTP API. the handler signature would be like [Plugin::custom_directive]
+    /// ```rust,ignore
+    /// fn custom_directive(custom: Spanned<Custom>) -> Vec<PluginDataRow> {
+    ///     // your logic here
+    /// }
+    /// ```
+    CustomDirective,
+}
+
+/// key/value row written by plugin of custom directive type into its plugin-scoped area of store
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct PluginDataRow {
+    pub key: String,
+    pub value: serde_json::Value,
+}
+
+/// error reported by plugin of validator type, it is stored as [zhang_ast::error::ErrorKind::PluginValidationError]
+/// with the plugin name, code and message in error metas
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct PluginValidationError {
+    pub code: String,
+    pub message: String,
 }
 
 pub trait Plugin {
@@ -40,4 +83,21 @@ pub trait Plugin {
     fn mapper(_: Spanned<Directive>) -> Vec<Spanned<Directive>> {
         unimplemented!("plugin does not support mapper type")
     }
+
+    fn on_transaction(_: Spanned<Transaction>) -> Transaction {
+        unimplemented!("plugin does not support transaction hook type")
+    }
+
+    fn validator(_: Spanned<Directive>) -> Vec<PluginValidationError> {
+        unimplemented!("plugin does not support validator type")
+    }
+
+    /// the types of `custom` directive handled by the plugin, e.g. `fava-option` for `custom "fava-option" ...`
+    fn custom_types() -> Vec<String> {
+        vec![]
+    }
+
+    fn custom_directive(_: Spanned<Custom>) -> Vec<PluginDataRow> {
+        unimplemented!("plugin does not support custom directive type")
+    }
 }
@@ -1,18 +1,19 @@
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 
 #[cfg(feature = "plugin_runtime")]
 use extism::convert::Json as WasmJson;
 #[cfg(feature = "plugin_runtime")]
 use extism::{Manifest, Plugin as WasmPlugin, Wasm};
-use itertools::Itertools;
 use log::info;
 use sha256::digest;
-use zhang_ast::{Directive, Plugin, Spanned};
+use zhang_ast::{Custom, Directive, Plugin, SpanInfo, Spanned, Transaction};
 
+use crate::constants::{DEFAULT_PLUGIN_MEMORY_LIMIT, DEFAULT_PLUGIN_TIMEOUT, KEY_PLUGIN_MEMORY_LIMIT, KEY_PLUGIN_TIMEOUT};
 use crate::domains::schemas::OptionDomain;
 use crate::error::IoErrorIntoZhangError;
-use crate::plugin::PluginType;
+use crate::plugin::{PluginDataRow, PluginType, PluginValidationError};
 use crate::{ZhangError, ZhangResult};
 
 #[derive(Default)]
@@ -20,10 +21,25 @@ pub struct PluginStore {
     pub processors: Vec<RegisteredPlugin>,
     pub mappers: Vec<RegisteredPlugin>,
     pub routers: Vec<RegisteredPlugin>,
+    pub transaction_hooks: Vec<RegisteredPlugin>,
+    pub validators: Vec<RegisteredPlugin>,
+    pub custom_directives: Vec<RegisteredPlugin>,
 }
 
 impl PluginStore {
-    pub fn insert_plugin(&mut self, _plugin: &Plugin) -> ZhangResult<()> {
+    /// registered plugins of given type
+    pub fn plugins_of(&self, plugin_type: &PluginType) -> &[RegisteredPlugin] {
+        match plugin_type {
+            PluginType::Processor => &self.processors,
+            PluginType::Mapper => &self.mappers,
+            PluginType::Router => &self.routers,
+            PluginType::TransactionHook => &self.transaction_hooks,
+            PluginType::Validator => &self.validators,
+            PluginType::CustomDirective => &self.custom_directives,
+        }
+    }
+
+    pub fn insert_plugin(&mut self, _plugin: &Plugin, span: &SpanInfo, options: &[OptionDomain]) -> ZhangResult<()> {
         let plugin_name = _plugin.module.as_str().to_string();
         let plugin_hash = digest(plugin_name);
         let plugin_cache_file = PathBuf::from_str(".cache/plugins")
@@ -31,8 +47,7 @@ impl PluginStore {
             .join(format!("{}.wasm", plugin_hash));
         let content = std::fs::read(&plugin_cache_file)?;
 
-        let wasm = Wasm::data(content);
-        let manifest = Manifest::new([wasm]);
+        let manifest = sandboxed_manifest(Wasm::data(content), options);
 
         let mut plugin = WasmPlugin::new(manifest, [], true).map_err(|e| ZhangError::CustomError(format!("Failed to create WasmPlugin: {}", e)))?;
         let name = plugin
@@ -48,9 +63,20 @@ impl PluginStore {
             .map_err(|e| ZhangError::CustomError(format!("Failed to call 'supported_type': {}", e)))?
             .0;
 
+        let custom_types = if plugin_types.contains(&PluginType::CustomDirective) {
+            plugin
+                .call::<(), WasmJson<Vec<String>>>("custom_types", ())
+                .map_err(|e| ZhangError::CustomError(format!("Failed to call 'custom_types': {}", e)))?
+                .0
+        } else {
+            vec![]
+        };
+
         let registered_plugin = RegisteredPlugin {
             name,
             version,
+            custom_types,
+            span: span.clone(),
             path: plugin_cache_file,
         };
         if plugin_types.contains(&PluginType::Processor) {
@@ -59,8 +85,17 @@ impl PluginStore {
         if plugin_types.contains(&PluginType::Mapper) {
             self.mappers.push(registered_plugin.clone())
         }
-        if plugin_types.contains(&PluginType::Mapper) {
-            self.routers.push(registered_plugin)
+        if plugin_types.contains(&PluginType::Router) {
+            self.routers.push(registered_plugin.clone())
+        }
+        if plugin_types.contains(&PluginType::TransactionHook) {
+            self.transaction_hooks.push(registered_plugin.clone())
+        }
+        if plugin_types.contains(&PluginType::Validator) {
+            self.validators.push(registered_plugin.clone())
+        }
+        if plugin_types.contains(&PluginType::CustomDirective) {
+            self.custom_directives.push(registered_plugin)
         }
 
         Ok(())
@@ -71,17 +106,40 @@ impl PluginStore {
 pub struct RegisteredPlugin {
     pub name: String,
     pub version: String,
+    /// the types of custom directive handled by plugin
+    pub custom_types: Vec<String>,
+    /// the span of plugin directive, execution errors of plugin are reported here
+    pub span: SpanInfo,
     path: PathBuf,
 }
 
+/// build the manifest with the execution limits of plugin, which are configured by options:
+/// - `plugin_timeout`: max execution time of each call in milliseconds
+/// - `plugin_memory_limit`: max memory of plugin instance in MiB
+///
+/// all options are passed into plugin as config
+fn sandboxed_manifest(wasm: Wasm, options: &[OptionDomain]) -> Manifest {
+    let option = |key: &str| options.iter().find(|it| it.key.eq(key)).map(|it| it.value.trim());
+    let timeout = option(KEY_PLUGIN_TIMEOUT)
+        .and_then(|it| it.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_PLUGIN_TIMEOUT);
+    let memory_limit = option(KEY_PLUGIN_MEMORY_LIMIT)
+        .and_then(|it| it.parse::<u32>().ok())
+        .unwrap_or(DEFAULT_PLUGIN_MEMORY_LIMIT);
+
+    Manifest::new([wasm])
+        .with_config(options.iter().map(|it| (it.key.as_str(), it.value.as_str())))
+        .with_timeout(Duration::from_millis(timeout))
+        // wasm memory is counted in 64KiB pages
+        .with_memory_max(memory_limit.saturating_mul(16))
+}
+
 impl RegisteredPlugin {
     pub fn load_as_plugin(&self, options: &[OptionDomain]) -> ZhangResult<WasmPlugin> {
         info!("executing the processor plugin {} {}", &self.name, &self.version);
-        let options = options.iter().map(|it| (it.key.as_str(), it.value.as_str())).collect_vec();
         let module_bytes = std::fs::read(&self.path).with_path(self.path.as_path())?;
-        let wasm = Wasm::data(module_bytes);
-        let manifest = Manifest::new([wasm]).with_config(options.into_iter());
-        let plugin = WasmPlugin::new(manifest, [], true).unwrap();
+        let manifest = sandboxed_manifest(Wasm::data(module_bytes), options);
+        let plugin = WasmPlugin::new(manifest, [], true).map_err(|e| ZhangError::CustomError(format!("Failed to create WasmPlugin: {}", e)))?;
 
         Ok(plugin)
     }
@@ -90,7 +148,7 @@ impl RegisteredPlugin {
         let mut plugin = self.load_as_plugin(options)?;
         let ret = plugin
             .call::<WasmJson<Vec<Spanned<Directive>>>, WasmJson<Vec<Spanned<Directive>>>>("processor", WasmJson(directive))
-            .unwrap()
+            .map_err(|e| ZhangError::CustomError(format!("Failed to call 'processor': {}", e)))?
             .0;
         Ok(ret)
     }
@@ -99,8 +157,277 @@ impl RegisteredPlugin {
         let mut plugin = self.load_as_plugin(options)?;
         let ret = plugin
             .call::<WasmJson<Spanned<Directive>>, WasmJson<Vec<Spanned<Directive>>>>("mapper", WasmJson(directive))
-            .unwrap()
+            .map_err(|e| ZhangError::CustomError(format!("Failed to call 'mapper': {}", e)))?
+            .0;
+        Ok(ret)
+    }
+
+    pub fn execute_on_transaction(plugin: &mut WasmPlugin, transaction: Spanned<Transaction>) -> ZhangResult<Transaction> {
+        let ret = plugin
+            .call::<WasmJson<Spanned<Transaction>>, WasmJson<Transaction>>("on_transaction", WasmJson(transaction))
+            .map_err(|e| ZhangError::CustomError(format!("Failed to call 'on_transaction': {}", e)))?
+            .0;
+        Ok(ret)
+    }
+
+    pub fn execute_as_validator(plugin: &mut WasmPlugin, directive: Spanned<Directive>) -> ZhangResult<Vec<PluginValidationError>> {
+        let ret = plugin
+            .call::<WasmJson<Spanned<Directive>>, WasmJson<Vec<PluginValidationError>>>("validator", WasmJson(directive))
+            .map_err(|e| ZhangError::CustomError(format!("Failed to call 'validator': {}", e)))?
             .0;
         Ok(ret)
     }
+
+    pub fn execute_as_custom_directive(plugin: &mut WasmPlugin, custom: Spanned<Custom>) -> ZhangResult<Vec<PluginDataRow>> {
+        let ret = plugin
+            .call::<WasmJson<Spanned<Custom>>, WasmJson<Vec<PluginDataRow>>>("custom_directive", WasmJson(custom))
+            .map_err(|e| ZhangError::CustomError(format!("Failed to call 'custom_directive': {}", e)))?
+            .0;
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::formatdoc;
+    use zhang_ast::error::ErrorKind;
+    use zhang_ast::{Date, Flag, Transaction, ZhangString};
+
+    use crate::test::load_from_text;
+
+    /// the wasm of plugin exporting the functions of extism which output constant json values
+    fn constant_plugin(functions: &[(&str, String)]) -> Vec<u8> {
+        plugin_module(functions, 1, "")
+    }
+
+    /// the wasm of plugin with the constant functions, the initial memory of given pages and the extra functions in wat
+    fn plugin_module(functions: &[(&str, String)], memory_pages: u32, extra_functions: &str) -> Vec<u8> {
+        let bytes = |value: &str| value.bytes().map(|byte| format!("\\{:02x}", byte)).collect::<String>();
+        let mut offset = 0;
+        let mut data = String::new();
+        let mut exports = String::new();
+        for (name, output) in functions {
+            data.push_str(&format!("(data (i32.const {}) \"{}\")\n", offset, bytes(output)));
+            exports.push_str(&format!(
+                "(func (export \"{}\") (result i32) (call $output (i32.const {}) (i32.const {})) (i32.const 0))\n",
+                name,
+                offset,
+                output.len()
+            ));
+            offset += output.len();
+        }
+        let module = formatdoc! {r#"
+            (module
+              (import "extism:host/env" "alloc" (func $alloc (param i64) (result i64)))
+              (import "extism:host/env" "store_u8" (func $store_u8 (param i64 i32)))
+              (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+              (memory {memory_pages})
+              {data}
+              (func $output (param $ptr i32) (param $len i32)
+                (local $offset i64) (local $idx i32)
+                (local.set $offset (call $alloc (i64.extend_i32_u (local.get $len))))
+                (block $done
+                  (loop $copy
+                    (br_if $done (i32.ge_u (local.get $idx) (local.get $len)))
+                    (call $store_u8
+                      (i64.add (local.get $offset) (i64.extend_i32_u (local.get $idx)))
+                      (i32.load8_u (i32.add (local.get $ptr) (local.get $idx))))
+                    (local.set $idx (i32.add (local.get $idx) (i32.const 1)))
+                    (br $copy)))
+                (call $output_set (local.get $offset) (i64.extend_i32_u (local.get $len))))
+              {exports}
+              {extra_functions}
+            )
+        "#};
+        wat::parse_str(module).unwrap()
+    }
+
+    #[test]
+    fn should_register_and_invoke_transaction_hook_plugin() {
+        let hooked = Transaction {
+            date: Date::Date(chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()),
+            auxiliary_date: None,
+            flag: Some(Flag::Okay),
+            payee: None,
+            narration: Some(ZhangString::QuoteString("hooked".to_owned())),
+            tags: ["hooked".to_owned()].into_iter().collect(),
+            links: Default::default(),
+            postings: vec![],
+            meta: Default::default(),
+        };
+        let plugin = constant_plugin(&[
+            ("name", serde_json::to_string("hook").unwrap()),
+            ("version", serde_json::to_string("0.1.0").unwrap()),
+            ("supported_type", serde_json::to_string(&["TransactionHook"]).unwrap()),
+            ("on_transaction", serde_json::to_string(&hooked).unwrap()),
+        ]);
+        let folder = tempfile::tempdir().unwrap();
+        let plugin_path = folder.path().join("hook.wasm");
+        std::fs::write(&plugin_path, plugin).unwrap();
+
+        let ledger = load_from_text(&formatdoc! {r#"
+            option "features.plugin" "true"
+            plugin "{}"
+            1970-01-01 open Assets:MyCard
+            2024-01-01 "original"
+              Assets:MyCard 0 CNY
+        "#, plugin_path.display()});
+
+        assert_eq!(
+            vec!["hook"],
+            ledger.plugins.transaction_hooks.iter().map(|it| it.name.as_str()).collect::<Vec<_>>()
+        );
+        assert!(ledger.plugins.routers.is_empty() && ledger.plugins.mappers.is_empty());
+        assert!(ledger.operations().errors().unwrap().is_empty());
+        let operations = ledger.operations();
+        let store = operations.read();
+        assert!(
+            store.tag_index.contains_key("hooked"),
+            "the transaction returned by hook replaces the original one"
+        );
+        assert!(store.transactions.values().all(|trx| trx.narration.as_deref() == Some("hooked")));
+    }
+
+    #[test]
+    fn should_report_errors_of_validator_plugin() {
+        let plugin = constant_plugin(&[
+            ("name", serde_json::to_string("policy").unwrap()),
+            ("version", serde_json::to_string("0.1.0").unwrap()),
+            ("supported_type", serde_json::to_string(&["Validator"]).unwrap()),
+            (
+                "validator",
+                r#"[{"code":"trip-tag","message":"travel posting must carry a trip tag"}]"#.to_owned(),
+            ),
+        ]);
+        let folder = tempfile::tempdir().unwrap();
+        let plugin_path = folder.path().join("policy.wasm");
+        std::fs::write(&plugin_path, plugin).unwrap();
+
+        let ledger = load_from_text(&formatdoc! {r#"
+            option "features.plugin" "true"
+            plugin "{}"
+            1970-01-01 open Expenses:Travel
+        "#, plugin_path.display()});
+
+        assert_eq!(vec!["policy"], ledger.plugins.validators.iter().map(|it| it.name.as_str()).collect::<Vec<_>>());
+        let errors = ledger.operations().errors().unwrap();
+        let error = errors
+            .iter()
+            .find(|error| error.span.as_ref().map(|span| span.content.contains("open Expenses:Travel")).unwrap_or(false))
+            .expect("the error is reported at the validated directive");
+        assert_eq!(ErrorKind::PluginValidationError, error.error_type);
+        assert_eq!(Some("policy"), error.metas.get("plugin_name").map(String::as_str));
+        assert_eq!(Some("trip-tag"), error.metas.get("code").map(String::as_str));
+        assert_eq!(Some("travel posting must carry a trip tag"), error.metas.get("message").map(String::as_str));
+        assert!(errors.iter().all(|error| error.error_type == ErrorKind::PluginValidationError));
+    }
+
+    #[test]
+    fn should_write_rows_of_custom_directive_plugin_into_plugin_data() {
+        let plugin = constant_plugin(&[
+            ("name", serde_json::to_string("trips").unwrap()),
+            ("version", serde_json::to_string("0.1.0").unwrap()),
+            ("supported_type", serde_json::to_string(&["CustomDirective"]).unwrap()),
+            ("custom_types", serde_json::to_string(&["trip"]).unwrap()),
+            ("custom_directive", r#"[{"key":"tokyo","value":{"budget":1000}}]"#.to_owned()),
+        ]);
+        let folder = tempfile::tempdir().unwrap();
+        let plugin_path = folder.path().join("trips.wasm");
+        std::fs::write(&plugin_path, plugin).unwrap();
+
+        let ledger = load_from_text(&formatdoc! {r#"
+            option "features.plugin" "true"
+            plugin "{}"
+            2024-01-01 custom "trip" "Tokyo"
+        "#, plugin_path.display()});
+
+        assert_eq!(vec!["trip".to_owned()], ledger.plugins.custom_directives[0].custom_types);
+        assert!(ledger.operations().errors().unwrap().is_empty());
+        let data = ledger.operations().plugin_data("trips").unwrap();
+        assert_eq!(1, data.len());
+        assert_eq!(Some(&serde_json::json!({"budget": 1000})), data.get("tokyo"));
+    }
+
+    #[test]
+    fn should_ignore_custom_directive_of_undeclared_type() {
+        let plugin = constant_plugin(&[
+            ("name", serde_json::to_string("trips").unwrap()),
+            ("version", serde_json::to_string("0.1.0").unwrap()),
+            ("supported_type", serde_json::to_string(&["CustomDirective"]).unwrap()),
+            ("custom_types", serde_json::to_string(&["trip"]).unwrap()),
+            ("custom_directive", r#"[{"key":"tokyo","value":true}]"#.to_owned()),
+        ]);
+        let folder = tempfile::tempdir().unwrap();
+        let plugin_path = folder.path().join("trips.wasm");
+        std::fs::write(&plugin_path, plugin).unwrap();
+
+        let ledger = load_from_text(&formatdoc! {r#"
+            option "features.plugin" "true"
+            plugin "{}"
+            2024-01-01 custom "invoice" "INV-001"
+        "#, plugin_path.display()});
+
+        assert!(ledger.operations().plugin_data("trips").unwrap().is_empty());
+    }
+
+    #[test]
+    fn should_report_plugin_exceeding_timeout_at_plugin_directive() {
+        let plugin = plugin_module(
+            &[
+                ("name", serde_json::to_string("forever").unwrap()),
+                ("version", serde_json::to_string("0.1.0").unwrap()),
+                ("supported_type", serde_json::to_string(&["Validator"]).unwrap()),
+            ],
+            1,
+            r#"(func (export "validator") (result i32) (loop $forever (br $forever)) (i32.const 0))"#,
+        );
+        let folder = tempfile::tempdir().unwrap();
+        let plugin_path = folder.path().join("forever.wasm");
+        std::fs::write(&plugin_path, plugin).unwrap();
+
+        let ledger = load_from_text(&formatdoc! {r#"
+            option "features.plugin" "true"
+            option "plugin_timeout" "100"
+            plugin "{}"
+            1970-01-01 open Assets:MyCard
+            1970-01-01 open Expenses:Food
+        "#, plugin_path.display()});
+
+        let errors = ledger.operations().errors().unwrap();
+        assert_eq!(1, errors.len(), "the plugin is unloaded once it fails, so it is reported only once");
+        assert_eq!(ErrorKind::PluginExecutionError, errors[0].error_type);
+        assert!(errors[0].span.as_ref().unwrap().content.starts_with("plugin"));
+        assert_eq!(Some("forever"), errors[0].metas.get("plugin_name").map(String::as_str));
+    }
+
+    #[test]
+    fn should_report_plugin_exceeding_memory_limit_at_plugin_directive() {
+        let plugin = plugin_module(
+            &[
+                ("name", serde_json::to_string("greedy").unwrap()),
+                ("version", serde_json::to_string("0.1.0").unwrap()),
+                ("supported_type", serde_json::to_string(&["Validator"]).unwrap()),
+                ("validator", "[]".to_owned()),
+            ],
+            // 2MiB of initial memory
+            32,
+            "",
+        );
+        let folder = tempfile::tempdir().unwrap();
+        let plugin_path = folder.path().join("greedy.wasm");
+        std::fs::write(&plugin_path, plugin).unwrap();
+
+        let ledger = load_from_text(&formatdoc! {r#"
+            option "features.plugin" "true"
+            option "plugin_memory_limit" "1"
+            plugin "{}"
+            1970-01-01 open Assets:MyCard
+        "#, plugin_path.display()});
+
+        assert!(ledger.plugins.validators.is_empty());
+        let errors = ledger.operations().errors().unwrap();
+        assert_eq!(1, errors.len());
+        assert_eq!(ErrorKind::PluginExecutionError, errors[0].error_type);
+        assert!(errors[0].span.as_ref().unwrap().content.starts_with("plugin"));
+    }
 }
@@ -0,0 +1,68 @@
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, NaiveDate, NaiveTime};
+use itertools::Itertools;
+use serde_json::Value;
+
+use crate::price_fetcher::{FetchedPrice, PriceSource};
+use crate::{ZhangError, ZhangResult};
+
+/// fetch daily prices of crypto currencies from coingecko, the symbol is the coin id, e.g. `bitcoin`
+pub struct CoinGeckoPriceSource {
+    endpoint: String,
+}
+
+impl Default for CoinGeckoPriceSource {
+    fn default() -> Self {
+        CoinGeckoPriceSource {
+            endpoint: "https://api.coingecko.com/api/v3".to_owned(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl PriceSource for CoinGeckoPriceSource {
+    fn name(&self) -> &str {
+        "coingecko"
+    }
+
+    async fn fetch(&self, symbol: &str, target_currency: &str, from: NaiveDate, to: NaiveDate) -> ZhangResult<Vec<FetchedPrice>> {
+        let from_timestamp = from.and_time(NaiveTime::MIN).and_utc().timestamp();
+        let to_timestamp = to.succ_opt().unwrap_or(to).and_time(NaiveTime::MIN).and_utc().timestamp();
+        let url = format!(
+            "{}/coins/{}/market_chart/range?vs_currency={}&from={}&to={}",
+            self.endpoint,
+            symbol,
+            target_currency.to_lowercase(),
+            from_timestamp,
+            to_timestamp
+        );
+        let response: Value = reqwest::get(url)
+            .await
+            .map_err(|_| ZhangError::FetchError)?
+            .json()
+            .await
+            .map_err(|_| ZhangError::FetchError)?;
+
+        let prices = response["prices"].as_array().ok_or(ZhangError::FetchError)?;
+
+        // coingecko returns multiple points a day for short range, the last point of a day is used as the daily price
+        Ok(prices
+            .iter()
+            .filter_map(|point| {
+                let date = DateTime::from_timestamp_millis(point[0].as_i64()?)?.date_naive();
+                let amount = BigDecimal::from_str(&point[1].as_f64()?.to_string()).ok()?;
+                Some(FetchedPrice {
+                    date,
+                    amount,
+                    currency: target_currency.to_owned(),
+                })
+            })
+            .filter(|price| price.date.ge(&from) && price.date.le(&to))
+            .rev()
+            .unique_by(|price| price.date)
+            .sorted_by_key(|price| price.date)
+            .collect_vec())
+    }
+}
@@ -0,0 +1,231 @@
+//! price fetcher is used to retrieve current or historical prices of commodities from remote providers.
+//! the provider of commodity is configured by the `price-source` meta of commodity directive, in the format of `{SOURCE}:{SYMBOL}`
+//! ```zhang
+//! 1970-01-01 commodity AAPL
+//!   price-source: "yahoo:AAPL"
+//! ```
+//! fetched prices are appended into the prices file as `price` directives.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use itertools::Itertools;
+use log::{info, warn};
+use zhang_ast::amount::Amount;
+use zhang_ast::{Date, Directive, Price};
+
+use crate::constants::COMMODITY_PRICE_SOURCE;
+use crate::domains::schemas::MetaType;
+use crate::ledger::Ledger;
+use crate::utils::append_directives;
+use crate::ZhangResult;
+
+mod coingecko;
+mod yahoo;
+
+pub use coingecko::CoinGeckoPriceSource;
+pub use yahoo::YahooPriceSource;
+
+/// price of symbol at the given date, returned by [PriceSource]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchedPrice {
+    pub date: NaiveDate,
+    pub amount: BigDecimal,
+    pub currency: String,
+}
+
+/// `PriceSource` is the protocol of price provider, implement it to add custom provider into [PriceFetcher]
+#[async_trait::async_trait]
+pub trait PriceSource
+where
+    Self: Send + Sync,
+{
+    /// the name of source, which is the prefix of `price-source` meta, e.g. `yahoo` in `yahoo:AAPL`
+    fn name(&self) -> &str;
+
+    /// fetch daily prices of symbol between `from` and `to`(both inclusive),
+    /// `target_currency` is the preferred currency of prices, source may ignore it if the price currency is decided by the symbol
+    async fn fetch(&self, symbol: &str, target_currency: &str, from: NaiveDate, to: NaiveDate) -> ZhangResult<Vec<FetchedPrice>>;
+}
+
+/// commodity with its configured price source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommodityPriceSource {
+    pub commodity: String,
+    pub source: String,
+    pub symbol: String,
+}
+
+pub struct PriceFetcher {
+    sources: HashMap<String, Box<dyn PriceSource>>,
+    prices_file: PathBuf,
+}
+
+impl PriceFetcher {
+    /// create price fetcher with builtin sources, fetched prices are appended into `prices_file`,
+    /// relative path is resolved from the ledger entry.
+    pub fn new(prices_file: impl Into<PathBuf>) -> Self {
+        let mut fetcher = PriceFetcher {
+            sources: HashMap::new(),
+            prices_file: prices_file.into(),
+        };
+        fetcher.register(YahooPriceSource::default());
+        fetcher.register(CoinGeckoPriceSource::default());
+        fetcher
+    }
+
+    /// register a price source, the source with the same name is replaced
+    pub fn register(&mut self, source: impl PriceSource + 'static) {
+        self.sources.insert(source.name().to_owned(), Box::new(source));
+    }
+
+    /// list the commodities which have valid `price-source` meta
+    pub fn commodity_price_sources(ledger: &Ledger) -> ZhangResult<Vec<CommodityPriceSource>> {
+        let operations = ledger.operations();
+        let commodities = operations.read().commodities.keys().cloned().sorted().collect_vec();
+        let mut ret = vec![];
+        for commodity in commodities {
+            let price_source = operations.meta(MetaType::CommodityMeta, &commodity, COMMODITY_PRICE_SOURCE)?;
+            let Some(price_source) = price_source else {
+                continue;
+            };
+            match price_source.value.split_once(':') {
+                Some((source, symbol)) if !source.trim().is_empty() && !symbol.trim().is_empty() => ret.push(CommodityPriceSource {
+                    commodity,
+                    source: source.trim().to_owned(),
+                    symbol: symbol.trim().to_owned(),
+                }),
+                _ => warn!("price source '{}' of commodity {} is invalid", price_source.value, commodity),
+            }
+        }
+        Ok(ret)
+    }
+
+    /// fetch prices between `from` and `to` of all commodities with price source, prices which already exist in ledger are skipped
+    pub async fn fetch(&self, ledger: &Ledger, from: NaiveDate, to: NaiveDate) -> ZhangResult<Vec<Price>> {
+        let operating_currency = &ledger.options.operating_currency;
+        let mut prices = vec![];
+        for commodity_source in PriceFetcher::commodity_price_sources(ledger)? {
+            let Some(source) = self.sources.get(&commodity_source.source) else {
+                warn!(
+                    "price source {} of commodity {} is not registered",
+                    commodity_source.source, commodity_source.commodity
+                );
+                continue;
+            };
+            let fetched_prices = source.fetch(&commodity_source.symbol, operating_currency, from, to).await?;
+            info!("fetched {} prices of commodity {}", fetched_prices.len(), commodity_source.commodity);
+
+            let operations = ledger.operations();
+            let existing_prices = operations.commodity_prices(&commodity_source.commodity)?;
+            for fetched_price in fetched_prices {
+                let existed = existing_prices
+                    .iter()
+                    .any(|price| price.datetime.date() == fetched_price.date && price.target_commodity.eq(&fetched_price.currency));
+                if existed {
+                    continue;
+                }
+                prices.push(Price {
+                    date: Date::Date(fetched_price.date),
+                    currency: commodity_source.commodity.clone(),
+                    amount: Amount::new(fetched_price.amount, fetched_price.currency),
+                    meta: Default::default(),
+                });
+            }
+        }
+        Ok(prices)
+    }
+
+    /// fetch prices and append them into prices file, the prices file is included in main file if it is not loaded by ledger.
+    /// return the appended prices.
+    pub async fn fetch_and_append(&self, ledger: &Ledger, from: NaiveDate, to: NaiveDate) -> ZhangResult<Vec<Price>> {
+        let prices = self.fetch(ledger, from, to).await?;
+        if prices.is_empty() {
+            return Ok(prices);
+        }
+        append_directives(ledger, &self.prices_file, prices.iter().cloned().map(Directive::Price).collect_vec()).await?;
+        Ok(prices)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+    use chrono::NaiveDate;
+    use indoc::indoc;
+
+    use crate::price_fetcher::{CommodityPriceSource, FetchedPrice, PriceFetcher, PriceSource};
+    use crate::test::load_from_text;
+    use crate::ZhangResult;
+
+    struct StaticPriceSource;
+
+    #[async_trait::async_trait]
+    impl PriceSource for StaticPriceSource {
+        fn name(&self) -> &str {
+            "static"
+        }
+
+        async fn fetch(&self, _symbol: &str, target_currency: &str, from: NaiveDate, to: NaiveDate) -> ZhangResult<Vec<FetchedPrice>> {
+            Ok(from
+                .iter_days()
+                .take_while(|date| date.le(&to))
+                .map(|date| FetchedPrice {
+                    date,
+                    amount: BigDecimal::from_str("7.1").unwrap(),
+                    currency: target_currency.to_owned(),
+                })
+                .collect())
+        }
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, day).unwrap()
+    }
+
+    #[test]
+    fn should_parse_price_source_meta() {
+        let ledger = load_from_text(indoc! {r#"
+            1970-01-01 commodity CNY
+            1970-01-01 commodity USD
+              price-source: "static:USDCNY"
+            1970-01-01 commodity BTC
+              price-source: "invalid"
+        "#});
+        let sources = PriceFetcher::commodity_price_sources(&ledger).unwrap();
+        assert_eq!(
+            vec![CommodityPriceSource {
+                commodity: "USD".to_owned(),
+                source: "static".to_owned(),
+                symbol: "USDCNY".to_owned()
+            }],
+            sources
+        );
+    }
+
+    #[tokio::test]
+    async fn should_append_fetched_prices_into_prices_file() {
+        let mut ledger = load_from_text(indoc! {r#"
+            option "operating_currency" "CNY"
+            1970-01-01 commodity CNY
+            1970-01-01 commodity USD
+              price-source: "static:USDCNY"
+            2024-01-01 price USD 7 CNY
+        "#});
+        let mut fetcher = PriceFetcher::new("prices.zhang");
+        fetcher.register(StaticPriceSource);
+
+        let prices = fetcher.fetch_and_append(&ledger, date(1), date(3)).await.unwrap();
+        assert_eq!(2, prices.len(), "existing price should be skipped");
+
+        ledger.reload().unwrap();
+        let operations = ledger.operations();
+        let prices = operations.commodity_prices("USD").unwrap();
+        assert_eq!(3, prices.len());
+        assert_eq!(BigDecimal::from_str("7.1").unwrap(), prices[2].amount);
+    }
+}
@@ -0,0 +1,65 @@
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, NaiveDate, NaiveTime};
+use itertools::Itertools;
+use serde_json::Value;
+
+use crate::price_fetcher::{FetchedPrice, PriceSource};
+use crate::{ZhangError, ZhangResult};
+
+/// fetch daily close prices from yahoo finance, the currency of prices is decided by the symbol
+pub struct YahooPriceSource {
+    endpoint: String,
+}
+
+impl Default for YahooPriceSource {
+    fn default() -> Self {
+        YahooPriceSource {
+            endpoint: "https://query1.finance.yahoo.com".to_owned(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl PriceSource for YahooPriceSource {
+    fn name(&self) -> &str {
+        "yahoo"
+    }
+
+    async fn fetch(&self, symbol: &str, _target_currency: &str, from: NaiveDate, to: NaiveDate) -> ZhangResult<Vec<FetchedPrice>> {
+        let period1 = from.and_time(NaiveTime::MIN).and_utc().timestamp();
+        let period2 = to.succ_opt().unwrap_or(to).and_time(NaiveTime::MIN).and_utc().timestamp();
+        let url = format!(
+            "{}/v8/finance/chart/{}?period1={}&period2={}&interval=1d",
+            self.endpoint, symbol, period1, period2
+        );
+        let response: Value = reqwest::get(url)
+            .await
+            .map_err(|_| ZhangError::FetchError)?
+            .json()
+            .await
+            .map_err(|_| ZhangError::FetchError)?;
+
+        let result = &response["chart"]["result"][0];
+        let currency = result["meta"]["currency"].as_str().ok_or(ZhangError::FetchError)?;
+        let timestamps = result["timestamp"].as_array().cloned().unwrap_or_default();
+        let closes = result["indicators"]["quote"][0]["close"].as_array().cloned().unwrap_or_default();
+
+        Ok(timestamps
+            .iter()
+            .zip(closes.iter())
+            .filter_map(|(timestamp, close)| {
+                let date = DateTime::from_timestamp(timestamp.as_i64()?, 0)?.date_naive();
+                let amount = BigDecimal::from_str(&close.as_f64()?.to_string()).ok()?;
+                Some(FetchedPrice {
+                    date,
+                    amount,
+                    currency: currency.to_owned(),
+                })
+            })
+            .filter(|price| price.date.ge(&from) && price.date.le(&to))
+            .unique_by(|price| price.date)
+            .collect_vec())
+    }
+}
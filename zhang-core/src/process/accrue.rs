@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::ops::{Div, Neg, Sub};
+
+use bigdecimal::{BigDecimal, RoundingMode};
+use chrono::Months;
+use uuid::Uuid;
+use zhang_ast::amount::Amount;
+use zhang_ast::error::ErrorKind;
+use zhang_ast::{Accrue, Date, SpanInfo};
+
+use crate::constants::DEFAULT_COMMODITY_PRECISION;
+use crate::ledger::Ledger;
+use crate::process::DirectiveProcess;
+use crate::utils::hashmap::HashMapOfExt;
+use crate::utils::id::FromSpan;
+use crate::{process, ZhangResult};
+
+impl DirectiveProcess for Accrue {
+    fn validate(&mut self, ledger: &mut Ledger, span: &SpanInfo) -> ZhangResult<bool> {
+        process::check_account_existed(self.deferral_account.name(), ledger, span)?;
+        process::check_account_existed(self.account.name(), ledger, span)?;
+        process::check_commodity_define(&self.amount.currency, ledger, span)?;
+        // the months from the first period to the last one should fit in u32
+        if self.periods == 0 || (self.periods - 1).checked_mul(self.interval.months()).is_none() {
+            let mut operations = ledger.operations();
+            operations.new_error(
+                ErrorKind::InvalidAccrualPeriods,
+                span,
+                HashMap::of("account_name", self.account.name().to_owned()),
+            )?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// one transaction is generated at the start of each period, moving the recognized amount from the deferral account
+    /// into the target account. the amounts are rounded into the precision of commodity, and the last period takes the rest
+    fn process(&mut self, ledger: &mut Ledger, span: &SpanInfo) -> ZhangResult<()> {
+        let mut operations = ledger.operations();
+        let precision = operations
+            .commodity(&self.amount.currency)?
+            .map(|it| it.precision)
+            .unwrap_or(DEFAULT_COMMODITY_PRECISION);
+        let recognized = (&self.amount.number)
+            .div(BigDecimal::from(self.periods))
+            .with_scale_round(precision as i64, RoundingMode::HalfUp);
+
+        let id = Uuid::from_span(span);
+        let narration = format!("accrual of {}", self.account.name());
+        let currency = &self.amount.currency;
+        let mut remaining = self.amount.number.clone();
+        for period in 0..self.periods {
+            let Some(date) = period
+                .checked_mul(self.interval.months())
+                .and_then(|months| self.date.naive_date().checked_add_months(Months::new(months)))
+            else {
+                break;
+            };
+            let number = if period + 1 == self.periods { remaining.clone() } else { recognized.clone() };
+            remaining = remaining.sub(&number);
+
+            let datetime = Date::Date(date).to_timezone_datetime(&ledger.options.timezone);
+            let postings = vec![
+                (self.account.name(), Amount::new(number.clone(), currency)),
+                (self.deferral_account.name(), Amount::new(number.neg(), currency)),
+            ];
+            process::insert_generated_transaction(
+                ledger,
+                &Uuid::from_txn_posting(&id, period as usize),
+                datetime,
+                &narration,
+                postings,
+                span,
+                &mut operations,
+            )?;
+        }
+        Ok(())
+    }
+}
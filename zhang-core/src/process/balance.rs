@@ -1,11 +1,14 @@
 use std::collections::HashMap;
 use std::ops::Sub;
+use std::str::FromStr;
 
 use bigdecimal::{BigDecimal, Zero};
 use zhang_ast::amount::Amount;
 use zhang_ast::error::ErrorKind;
-use zhang_ast::{BalanceCheck, BalancePad, Flag, Posting, SpanInfo, Transaction, ZhangString};
+use zhang_ast::{BalanceCheck, BalancePad, Flag, Posting, PostingType, SpanInfo, Transaction, ZhangString};
 
+use crate::constants::{BALANCE_RECONCILED, BALANCE_RECURSIVE, COMMODITY_BALANCE_TOLERANCE, TRUE};
+use crate::domains::schemas::MetaType;
 use crate::ledger::Ledger;
 use crate::process::DirectiveProcess;
 use crate::utils::hashmap::HashMapOfExt;
@@ -34,6 +37,7 @@ impl DirectiveProcess for BalancePad {
         let distance = Amount::new((&self.amount.number).sub(&current_balance_amount), self.amount.currency.clone());
         let mut transformed_trx = Transaction {
             date: self.date.clone(),
+            auxiliary_date: None,
             flag: Some(Flag::BalancePad),
             payee: Some(ZhangString::quote("Balance Pad")),
             narration: Some(ZhangString::quote(format!("pad {} to {}", self.account.name(), self.pad.name()))),
@@ -42,20 +46,24 @@ impl DirectiveProcess for BalancePad {
             postings: vec![
                 Posting {
                     flag: None,
+                    posting_type: PostingType::Real,
                     account: self.account.clone(),
                     units: Some(distance.clone()),
                     cost: None,
                     cost_date: None,
+                    cost_label: None,
                     price: None,
                     comment: None,
                     meta: Default::default(),
                 },
                 Posting {
                     flag: None,
+                    posting_type: PostingType::Real,
                     account: self.pad.clone(),
                     units: None,
                     cost: None,
                     cost_date: None,
+                    cost_label: None,
                     price: None,
                     comment: None,
                     meta: Default::default(),
@@ -71,34 +79,101 @@ impl DirectiveProcess for BalancePad {
     }
 }
 
+/// the balance check with `recursive: true` meta asserts the combined balance of account and all its sub-accounts
+fn is_recursive(balance_check: &BalanceCheck) -> bool {
+    balance_check
+        .meta
+        .get_one(BALANCE_RECURSIVE)
+        .map(|it| it.as_str().to_lowercase().eq(TRUE))
+        .unwrap_or(false)
+}
+
+/// the balance check with `reconciled: true` meta asserts the balance of reconciled postings only.
+/// it is a pure assertion, the distance is neither padded nor carried into the account
+fn is_reconciled_only(balance_check: &BalanceCheck) -> bool {
+    balance_check
+        .meta
+        .get_one(BALANCE_RECONCILED)
+        .map(|it| it.as_str().to_lowercase().eq(TRUE))
+        .unwrap_or(false)
+}
+
 impl DirectiveProcess for BalanceCheck {
     fn validate(&mut self, ledger: &mut Ledger, span: &SpanInfo) -> ZhangResult<bool> {
-        process::check_account_existed(self.account.name(), ledger, span)?;
+        if is_recursive(self) {
+            let mut operations = ledger.operations();
+            if !operations.exist_account_tree(self.account.name())? {
+                operations.new_error(
+                    ErrorKind::AccountDoesNotExist,
+                    span,
+                    HashMap::of("account_name", self.account.name().to_string()),
+                )?;
+            }
+        } else {
+            process::check_account_existed(self.account.name(), ledger, span)?;
+        }
         process::check_account_closed(self.account.name(), ledger, span)?;
         Ok(true)
     }
 
     fn process(&mut self, ledger: &mut Ledger, span: &SpanInfo) -> ZhangResult<()> {
         let mut operations = ledger.operations();
-        let option = operations.account_target_day_balance(
-            self.account.name(),
-            self.date.to_timezone_datetime(&ledger.options.timezone),
-            &self.amount.currency,
-        )?;
-
-        let current_balance_amount = option.map(|it| it.number).unwrap_or_else(BigDecimal::zero);
+        let datetime = self.date.to_timezone_datetime(&ledger.options.timezone);
+        if is_reconciled_only(self) {
+            let reconciled_balance = operations.account_reconciled_balance(self.account.name(), is_recursive(self), datetime, &self.amount.currency)?;
+            if reconciled_balance.ne(&self.amount.number) {
+                let metas = HashMap::of3(
+                    "account_name",
+                    self.account.name().to_string(),
+                    "commodity_name",
+                    self.amount.currency.clone(),
+                    "reconciled_amount",
+                    reconciled_balance.to_string(),
+                );
+                operations.new_error(ErrorKind::AccountReconciledBalanceCheckError, span, metas)?;
+            }
+            return Ok(());
+        }
+        let current_balance_amount = if is_recursive(self) {
+            operations.account_tree_target_day_balance(self.account.name(), datetime, &self.amount.currency)?
+        } else {
+            let option = operations.account_target_day_balance(self.account.name(), datetime, &self.amount.currency)?;
+            option.map(|it| it.number).unwrap_or_else(BigDecimal::zero)
+        };
 
-        let distance = Amount::new((&self.amount.number).sub(&current_balance_amount), self.amount.currency.clone());
+        let mut distance = Amount::new((&self.amount.number).sub(&current_balance_amount), self.amount.currency.clone());
+        if process::pad::pad_account(ledger, &self.account, &distance)? {
+            distance = Amount::new(BigDecimal::zero(), self.amount.currency.clone());
+        }
         if !distance.is_zero() {
-            operations.new_error(
-                ErrorKind::AccountBalanceCheckError,
-                span,
-                HashMap::of("account_name", self.account.name().to_string()),
-            )?;
+            let tolerance = balance_tolerance(ledger, &self.amount.currency)?;
+            if distance.number.abs().le(&tolerance) {
+                let metas = HashMap::of3(
+                    "account_name",
+                    self.account.name().to_string(),
+                    "commodity_name",
+                    self.amount.currency.clone(),
+                    "distance",
+                    distance.number.to_string(),
+                );
+                operations.new_error(ErrorKind::AccountBalanceCheckWithinTolerance, span, metas)?;
+            } else {
+                operations.new_error(
+                    ErrorKind::AccountBalanceCheckError,
+                    span,
+                    HashMap::of("account_name", self.account.name().to_string()),
+                )?;
+            }
+        }
+
+        // the parent account of recursive balance check may not be opened, there is no account to carry the distance
+        if !operations.exist_account(self.account.name())? {
+            return Ok(());
         }
 
         let mut transformed_trx = Transaction {
             date: self.date.clone(),
+            auxiliary_date: None,
             flag: Some(Flag::BalanceCheck),
             payee: Some(ZhangString::quote("Balance Check")),
             narration: Some(ZhangString::quote(self.account.name())),
@@ -106,10 +181,12 @@ impl DirectiveProcess for BalanceCheck {
             links: Default::default(),
             postings: vec![Posting {
                 flag: None,
+                posting_type: PostingType::Real,
                 account: self.account.clone(),
                 units: Some(distance),
                 cost: None,
                 cost_date: None,
+                cost_label: None,
                 price: None,
                 comment: None,
                 meta: Default::default(),
@@ -121,3 +198,14 @@ impl DirectiveProcess for BalanceCheck {
         Ok(())
     }
 }
+
+/// the tolerance of balance assertion in the commodity, which is the `balance_tolerance` meta of commodity or the `balance_tolerance` option
+fn balance_tolerance(ledger: &Ledger, commodity: &str) -> ZhangResult<BigDecimal> {
+    let operations = ledger.operations();
+    let commodity_tolerance = operations
+        .meta(MetaType::CommodityMeta, commodity, COMMODITY_BALANCE_TOLERANCE)?
+        .and_then(|meta| BigDecimal::from_str(meta.value.trim()).ok());
+    Ok(commodity_tolerance
+        .map(|it| it.abs())
+        .unwrap_or_else(|| ledger.options.balance_tolerance.clone()))
+}
@@ -1,11 +1,18 @@
 use std::collections::HashMap;
+use std::ops::Mul;
+use std::str::FromStr;
 
+use chrono::DateTime;
+use chrono_tz::Tz;
+use zhang_ast::amount::Amount;
 use zhang_ast::error::ErrorKind;
 use zhang_ast::{Budget, BudgetAdd, BudgetClose, BudgetTransfer, SpanInfo};
 
+use crate::domains::Operations;
 use crate::ledger::Ledger;
 use crate::process::DirectiveProcess;
-use crate::store::BudgetEventType;
+use crate::store::{BudgetEventType, BudgetRollover};
+use crate::utils::hashmap::HashMapOfExt;
 use crate::ZhangResult;
 
 impl DirectiveProcess for Budget {
@@ -19,14 +26,27 @@ impl DirectiveProcess for Budget {
         }
     }
 
-    fn process(&mut self, ledger: &mut Ledger, _span: &SpanInfo) -> ZhangResult<()> {
+    fn process(&mut self, ledger: &mut Ledger, span: &SpanInfo) -> ZhangResult<()> {
         let mut operations = ledger.operations();
+        // the invalid rollover is reported, and the budget carries as the default one
+        let rollover = match self.meta.get_one("rollover").map(|it| it.as_str().trim().to_owned()) {
+            Some(rollover) => match BudgetRollover::from_str(&rollover) {
+                Ok(rollover) => rollover,
+                Err(_) => {
+                    let metas = HashMap::of2("budget_name", self.name.as_str(), "rollover", rollover);
+                    operations.new_error(ErrorKind::InvalidBudgetRollover, span, metas)?;
+                    BudgetRollover::default()
+                }
+            },
+            None => BudgetRollover::default(),
+        };
         operations.init_budget(
             &self.name,
             &self.commodity,
             self.date.to_timezone_datetime(&ledger.options.timezone),
             self.meta.get_one("alias").map(|it| it.as_str().to_owned()),
             self.meta.get_one("category").map(|it| it.as_str().to_owned()),
+            rollover,
         )?;
         Ok(())
     }
@@ -102,3 +122,20 @@ impl DirectiveProcess for BudgetClose {
         Ok(())
     }
 }
+
+/// convert the activity amount of posting into the commodity of budget when `budget_convert_to_operating_currency` is enabled,
+/// the exchange rate at the posting date is resolved from the price graph, e.g. via the operating currency if no direct price is defined.
+/// return `None` if the amount cannot be converted
+pub(crate) fn budget_activity_amount(
+    ledger: &Ledger, budget: &str, amount: Amount, datetime: DateTime<Tz>, operations: &mut Operations,
+) -> ZhangResult<Option<Amount>> {
+    let Some(budget_commodity) = operations.budget_commodity(budget)? else {
+        return Ok(None);
+    };
+    if !ledger.options.budget_convert_to_operating_currency || amount.currency.eq(&budget_commodity) {
+        return Ok(Some(amount));
+    }
+
+    let rate = operations.exchange_rate(&amount.currency, &budget_commodity, datetime.naive_local())?;
+    Ok(rate.map(|rate| Amount::new(amount.number.mul(rate), budget_commodity)))
+}
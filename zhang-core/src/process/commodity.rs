@@ -3,7 +3,7 @@ use std::str::FromStr;
 use zhang_ast::{Commodity, Rounding, SpanInfo};
 
 use crate::constants::{DEFAULT_COMMODITY_PRECISION, DEFAULT_ROUNDING, KEY_DEFAULT_COMMODITY_PRECISION, KEY_DEFAULT_ROUNDING};
-use crate::domains::schemas::MetaType;
+use crate::domains::schemas::{CommodityDomain, MetaType};
 use crate::ledger::Ledger;
 use crate::process::DirectiveProcess;
 use crate::{ZhangError, ZhangResult};
@@ -26,6 +26,8 @@ impl DirectiveProcess for Commodity {
             .unwrap_or(DEFAULT_COMMODITY_PRECISION);
         let prefix = self.meta.get_one("prefix").map(|it| it.clone().to_plain_string());
         let suffix = self.meta.get_one("suffix").map(|it| it.clone().to_plain_string());
+        let grouping_separator = self.meta.get_one("grouping-separator").map(|it| it.clone().to_plain_string());
+        let decimal_symbol = self.meta.get_one("decimal-symbol").map(|it| it.clone().to_plain_string());
         let rounding = self
             .meta
             .get_one("rounding")
@@ -36,7 +38,15 @@ impl DirectiveProcess for Commodity {
             .or(ledger_default_rounding)
             .unwrap_or(DEFAULT_ROUNDING);
 
-        operations.insert_commodity(&self.currency, precision, prefix, suffix, rounding)?;
+        operations.insert_commodity(CommodityDomain {
+            name: self.currency.clone(),
+            precision,
+            prefix,
+            suffix,
+            rounding,
+            grouping_separator,
+            decimal_symbol,
+        })?;
         operations.insert_meta(MetaType::CommodityMeta, &self.currency, self.meta.clone())?;
 
         Ok(())
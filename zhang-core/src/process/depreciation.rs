@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::ops::{Div, Mul, Neg, Sub};
+
+use bigdecimal::{BigDecimal, RoundingMode, Zero};
+use chrono::Months;
+use uuid::Uuid;
+use zhang_ast::amount::Amount;
+use zhang_ast::error::ErrorKind;
+use zhang_ast::{Date, Depreciation, DepreciationMethod, SpanInfo};
+
+use crate::constants::DEFAULT_COMMODITY_PRECISION;
+use crate::ledger::Ledger;
+use crate::process::DirectiveProcess;
+use crate::utils::hashmap::HashMapOfExt;
+use crate::utils::id::FromSpan;
+use crate::{process, ZhangResult};
+
+impl DirectiveProcess for Depreciation {
+    fn validate(&mut self, ledger: &mut Ledger, span: &SpanInfo) -> ZhangResult<bool> {
+        process::check_account_existed(self.account.name(), ledger, span)?;
+        process::check_account_existed(self.expense_account.name(), ledger, span)?;
+        process::check_commodity_define(&self.amount.currency, ledger, span)?;
+        if self.lifetime == 0 {
+            let mut operations = ledger.operations();
+            operations.new_error(
+                ErrorKind::InvalidDepreciationLifetime,
+                span,
+                HashMap::of("account_name", self.account.name().to_owned()),
+            )?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// one transaction is generated at the same day of each following month within the lifetime, or at the last day of
+    /// the month which has no such day, moving the written off amount from the asset account into the expense account
+    fn process(&mut self, ledger: &mut Ledger, span: &SpanInfo) -> ZhangResult<()> {
+        let mut operations = ledger.operations();
+        let precision = operations
+            .commodity(&self.amount.currency)?
+            .map(|it| it.precision)
+            .unwrap_or(DEFAULT_COMMODITY_PRECISION);
+        let schedule = depreciation_schedule(&self.amount.number, self.method, self.lifetime, precision as i64);
+
+        let id = Uuid::from_span(span);
+        let narration = format!("depreciation of {}", self.account.name());
+        let purchase_date = self.date.naive_date();
+        for (period, number) in schedule.into_iter().enumerate().filter(|(_, number)| !number.is_zero()) {
+            let Some(date) = purchase_date.checked_add_months(Months::new(period as u32 + 1)) else {
+                break;
+            };
+            let datetime = Date::Date(date).to_timezone_datetime(&ledger.options.timezone);
+            let postings = vec![
+                (self.expense_account.name(), Amount::new(number.clone(), self.amount.currency.clone())),
+                (self.account.name(), Amount::new(number.neg(), self.amount.currency.clone())),
+            ];
+            process::insert_generated_transaction(
+                ledger,
+                &Uuid::from_txn_posting(&id, period),
+                datetime,
+                &narration,
+                postings,
+                span,
+                &mut operations,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// the amounts written off month by month, each amount is rounded into the precision of commodity,
+/// and the last month takes the remaining book value so that the total is the purchase amount
+fn depreciation_schedule(amount: &BigDecimal, method: DepreciationMethod, lifetime: u32, precision: i64) -> Vec<BigDecimal> {
+    let lifetime_number = BigDecimal::from(lifetime);
+    let mut remaining = amount.clone();
+    let mut schedule = Vec::with_capacity(lifetime as usize);
+    for period in 1..=lifetime {
+        let number = if period == lifetime {
+            remaining.clone()
+        } else {
+            let number = match method {
+                DepreciationMethod::StraightLine => amount.div(&lifetime_number),
+                DepreciationMethod::DecliningBalance => (&remaining).mul(BigDecimal::from(2)).div(&lifetime_number),
+            };
+            number.with_scale_round(precision, RoundingMode::HalfUp).min(remaining.clone())
+        };
+        remaining = remaining.sub(&number);
+        schedule.push(number);
+    }
+    schedule
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+    use zhang_ast::DepreciationMethod;
+
+    use crate::process::depreciation::depreciation_schedule;
+
+    #[test]
+    fn should_write_off_the_remaining_in_last_month() {
+        let schedule = depreciation_schedule(&BigDecimal::from(1000), DepreciationMethod::StraightLine, 3, 2);
+        assert_eq!(
+            vec![
+                BigDecimal::from_str("333.33").unwrap(),
+                BigDecimal::from_str("333.33").unwrap(),
+                BigDecimal::from_str("333.34").unwrap()
+            ],
+            schedule
+        );
+
+        let schedule = depreciation_schedule(&BigDecimal::from(1000), DepreciationMethod::DecliningBalance, 4, 2);
+        assert_eq!(
+            vec![BigDecimal::from(500), BigDecimal::from(250), BigDecimal::from(125), BigDecimal::from(125)],
+            schedule
+        );
+    }
+}
@@ -1,10 +1,14 @@
 use std::path::PathBuf;
 
-use zhang_ast::{Document, SpanInfo};
+use sha256::digest;
+use zhang_ast::{Directive, Document, Meta, SpanInfo, Spanned};
 
+use crate::domains::Operations;
 use crate::ledger::Ledger;
+use crate::options::MetaValueType;
 use crate::process::DirectiveProcess;
 use crate::store::DocumentType;
+use crate::utils::document_text;
 use crate::{process, ZhangResult};
 
 impl DirectiveProcess for Document {
@@ -20,12 +24,103 @@ impl DirectiveProcess for Document {
         let path = self.filename.clone().to_plain_string();
 
         let document_pathbuf = PathBuf::from(&path);
+        let hash = index_document(ledger, &path, &mut operations)?;
         operations.insert_document(
             self.date.to_timezone_datetime(&ledger.options.timezone),
             document_pathbuf.file_name().and_then(|it| it.to_str()),
-            path,
+            path.clone(),
             DocumentType::Account(self.account.clone()),
+            hash,
         )?;
         Ok(())
     }
 }
+
+/// the content hash and searchable text of document, the text is extracted only if `features.document_index` is enabled
+#[derive(Clone)]
+pub(crate) struct ReadDocument {
+    pub hash: String,
+    pub text: Option<String>,
+}
+
+impl ReadDocument {
+    fn of(ledger: &Ledger, path: &str, content: &[u8]) -> Self {
+        let hash = digest(content);
+        let text = if ledger.options.features.document_index {
+            document_text::extract_text(path, &hash, content)
+        } else {
+            None
+        };
+        ReadDocument { hash, text }
+    }
+}
+
+/// read the document through the data source of ledger, the relative path is resolved from the ledger entry.
+/// return `None` if the document cannot be read
+pub(crate) fn read_document(ledger: &Ledger, path: &str) -> Option<ReadDocument> {
+    if let Some(document) = ledger.prefetched_documents.get(path) {
+        return document.clone();
+    }
+    let content = ledger.data_source.get(path.to_owned()).ok()?;
+    Some(ReadDocument::of(ledger, path, &content))
+}
+
+/// the directives are processed synchronously, so the documents of async data sources, e.g. the remote ones, are read ahead.
+/// the missing file read as empty by data source is treated as absent
+pub(crate) async fn prefetch_documents(ledger: &mut Ledger, paths: Vec<String>) {
+    let data_source = ledger.data_source.clone();
+    for path in paths {
+        let document = match data_source.async_get(path.clone()).await {
+            Ok(content) if !content.is_empty() => Some(ReadDocument::of(ledger, &path, &content)),
+            _ => None,
+        };
+        ledger.prefetched_documents.insert(path, document);
+    }
+}
+
+/// compute the sha256 of document content and index its text for searching.
+/// return `None` if the document cannot be read
+pub(crate) fn index_document(ledger: &Ledger, path: &str, operations: &mut Operations) -> ZhangResult<Option<String>> {
+    let Some(document) = read_document(ledger, path) else {
+        return Ok(None);
+    };
+    if let Some(text) = document.text {
+        operations.insert_document_text(&document.hash, text)?;
+    }
+    Ok(Some(document.hash))
+}
+
+/// the files whose content or existence affects the processing of directives, relative to the ledger entry: the documents
+/// of document directives, the `document` metas of transactions, and the metas declared as `path` by option `meta_schema`
+pub(crate) fn referenced_files<'a>(ledger: &Ledger, directives: impl IntoIterator<Item = &'a Spanned<Directive>>) -> Vec<String> {
+    let mut path_keys = vec!["document"];
+    path_keys.extend(
+        ledger
+            .options
+            .meta_schemas
+            .iter()
+            .filter(|rule| rule.value_type == MetaValueType::Path)
+            .map(|rule| rule.key.as_str()),
+    );
+    let paths_of = |meta: &Meta| {
+        path_keys
+            .iter()
+            .flat_map(|key| meta.get_all(*key))
+            .map(|it| it.as_str().to_owned())
+            .collect::<Vec<_>>()
+    };
+    let mut files = vec![];
+    for directive in directives {
+        match &directive.data {
+            Directive::Document(document) => files.push(document.filename.as_str().to_owned()),
+            Directive::Transaction(trx) => files.extend(trx.postings.iter().flat_map(|posting| paths_of(&posting.meta))),
+            _ => {}
+        }
+        if let Some(meta) = directive.data.get_meta() {
+            files.extend(paths_of(meta));
+        }
+    }
+    files.sort();
+    files.dedup();
+    files
+}
@@ -0,0 +1,149 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::NaiveDate;
+use itertools::Itertools;
+use zhang_ast::amount::Amount;
+use zhang_ast::error::ErrorKind;
+use zhang_ast::{Flag, SpanInfo};
+
+use crate::constants::{KEY_DUPLICATE_TRANSACTION_WINDOW, TXN_DEDUP_IGNORE, TXN_IMPORT_ID};
+use crate::domains::schemas::MetaType;
+use crate::ledger::Ledger;
+use crate::utils::hashmap::HashMapOfExt;
+use crate::ZhangResult;
+
+/// what is compared between transactions to find probable duplicates
+pub(crate) struct TransactionFingerprint {
+    pub(crate) date: NaiveDate,
+    pub(crate) payee: Option<String>,
+    payee_tokens: HashSet<String>,
+    postings: Vec<(String, Amount)>,
+    import_id: Option<String>,
+    pub(crate) span: Option<SpanInfo>,
+}
+
+/// the lowercase words of payee, numbers like store number and card number are ignored
+fn payee_tokens(payee: &str) -> HashSet<String> {
+    payee
+        .split(|it: char| !it.is_alphanumeric())
+        .filter(|it| !it.is_empty() && !it.chars().all(|c| c.is_ascii_digit()))
+        .map(|it| it.to_lowercase())
+        .collect()
+}
+
+impl TransactionFingerprint {
+    fn new(date: NaiveDate, payee: Option<String>, postings: Vec<(String, Amount)>, import_id: Option<String>, span: Option<SpanInfo>) -> Self {
+        let payee_tokens = payee.as_deref().map(payee_tokens).unwrap_or_default();
+        Self {
+            date,
+            payee,
+            payee_tokens,
+            postings,
+            import_id,
+            span,
+        }
+    }
+
+    /// the fingerprint of transaction which is not processed by ledger, e.g. the imported one. the elided postings are not compared
+    #[cfg(feature = "importer")]
+    pub(crate) fn of_transaction(ledger: &Ledger, trx: &zhang_ast::Transaction) -> Self {
+        let payee = trx
+            .payee
+            .as_ref()
+            .or(trx.narration.as_ref())
+            .map(|it| ledger.options.normalize_payee(it.as_str()).to_owned())
+            .filter(|it| !it.is_empty());
+        let postings = trx
+            .postings
+            .iter()
+            .filter_map(|posting| posting.units.clone().map(|units| (posting.account.name().to_owned(), units)))
+            .collect_vec();
+        let import_id = trx.meta.get_one(TXN_IMPORT_ID).map(|it| it.as_str().to_owned());
+        Self::new(trx.date.naive_date(), payee, postings, import_id, None)
+    }
+
+    /// two transactions are probable duplicates if they are within `window` days, share a posting of the same account and amount,
+    /// and have similar payees. transactions imported from different statement entries are never duplicates
+    pub(crate) fn is_duplicate_of(&self, other: &TransactionFingerprint, window: i64) -> bool {
+        if let (Some(id), Some(other_id)) = (&self.import_id, &other.import_id) {
+            if id != other_id {
+                return false;
+            }
+        }
+        (self.date - other.date).num_days().abs() <= window
+            && self.postings.iter().any(|posting| other.postings.contains(posting))
+            && self.is_payee_similar(other)
+    }
+
+    /// payees are similar if at least half words of the shorter payee appear in the other one,
+    /// the transaction without payee is similar to any payee
+    fn is_payee_similar(&self, other: &TransactionFingerprint) -> bool {
+        let (shorter, longer) = if self.payee_tokens.len() <= other.payee_tokens.len() {
+            (&self.payee_tokens, &other.payee_tokens)
+        } else {
+            (&other.payee_tokens, &self.payee_tokens)
+        };
+        shorter.is_empty() || shorter.intersection(longer).count() * 2 >= shorter.len()
+    }
+}
+
+/// the fingerprints of processed transactions in the order of date, the transactions with `dedup-ignore` meta
+/// and the ones generated by balance and pad directives are excluded
+pub(crate) fn ledger_fingerprints(ledger: &Ledger) -> Vec<TransactionFingerprint> {
+    let operations = ledger.operations();
+    let store = operations.read();
+    let trx_metas = store
+        .metas
+        .iter()
+        .filter(|it| it.meta_type.eq(MetaType::TransactionMeta.as_ref()))
+        .map(|it| ((it.type_identifier.as_str(), it.key.as_str()), it.value.as_str()))
+        .collect::<HashMap<_, _>>();
+    let postings = store.postings.iter().map(|it| (it.trx_id, it)).into_group_map();
+    store
+        .transactions
+        .values()
+        .filter(|trx| !matches!(trx.flag, Flag::Void | Flag::BalanceCheck | Flag::BalancePad))
+        .filter(|trx| !trx_metas.contains_key(&(trx.id.to_string().as_str(), TXN_DEDUP_IGNORE)))
+        .map(|trx| {
+            let id = trx.id.to_string();
+            let postings = postings
+                .get(&trx.id)
+                .map(|postings| {
+                    postings
+                        .iter()
+                        .map(|posting| (posting.account.name().to_owned(), posting.inferred_amount.clone()))
+                        .collect_vec()
+                })
+                .unwrap_or_default();
+            let import_id = trx_metas.get(&(id.as_str(), TXN_IMPORT_ID)).map(|it| it.to_string());
+            let payee = trx.payee.clone().or(trx.narration.clone()).filter(|it| !it.is_empty());
+            (
+                trx.sequence,
+                TransactionFingerprint::new(trx.datetime.date_naive(), payee, postings, import_id, Some(trx.span.clone())),
+            )
+        })
+        .sorted_by(|(a_sequence, a), (b_sequence, b)| a.date.cmp(&b.date).then(a_sequence.cmp(b_sequence)))
+        .map(|(_, fingerprint)| fingerprint)
+        .collect_vec()
+}
+
+/// report the transaction which is the probable duplicate of an earlier one within the days given by option `duplicate_transaction_window`
+pub(crate) fn detect_duplicate_transactions(ledger: &Ledger) -> ZhangResult<()> {
+    let mut operations = ledger.operations();
+    let Some(window) = operations.option::<i64>(KEY_DUPLICATE_TRANSACTION_WINDOW).ok().flatten() else {
+        return Ok(());
+    };
+    let fingerprints = ledger_fingerprints(ledger);
+    for (idx, fingerprint) in fingerprints.iter().enumerate() {
+        let original = fingerprints[..idx]
+            .iter()
+            .rev()
+            .take_while(|it| (fingerprint.date - it.date).num_days() <= window)
+            .find(|it| fingerprint.is_duplicate_of(it, window));
+        if let (Some(original), Some(span)) = (original, &fingerprint.span) {
+            let metas = HashMap::of2("date", original.date.to_string(), "payee", original.payee.clone().unwrap_or_default());
+            operations.new_error(ErrorKind::DuplicatedTransaction, span, metas)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use std::collections::HashMap;
+
+use zhang_ast::error::ErrorKind;
+use zhang_ast::{Custom, SpanInfo, StringOrAccount};
+
+use crate::domains::schemas::EliminationDomain;
+use crate::ledger::Ledger;
+use crate::utils::hashmap::HashMapOfExt;
+use crate::ZhangResult;
+
+/// the type of custom directive declaring an elimination rule
+pub(crate) const ELIMINATION_CUSTOM_TYPE: &str = "elimination";
+
+/// the elimination rule is declared as `custom "elimination" {ACCOUNT} {ACCOUNT}...`, the postings into the accounts and their
+/// sub accounts since the date are excluded from consolidated reports, e.g. the inter-entity receivables and payables
+pub(crate) fn handle_elimination(ledger: &mut Ledger, custom: &Custom, span: &SpanInfo) -> ZhangResult<()> {
+    let accounts = custom
+        .values
+        .iter()
+        .filter_map(|value| match value {
+            StringOrAccount::Account(account) => Some(account.name().to_owned()),
+            StringOrAccount::String(_) => None,
+        })
+        .collect::<Vec<_>>();
+    let mut operations = ledger.operations();
+    // the eliminated account can be a parent account which is not defined itself
+    for account in accounts.iter() {
+        if !operations.exist_account_tree(account)? {
+            operations.new_error(ErrorKind::AccountDoesNotExist, span, HashMap::of("account_name", account.clone()))?;
+        }
+    }
+    operations.insert_elimination(EliminationDomain {
+        date: custom.date.naive_date(),
+        accounts,
+    })
+}
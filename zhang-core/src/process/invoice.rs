@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::ops::AddAssign;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, Signed, Zero};
+use chrono::NaiveDate;
+use itertools::Itertools;
+use zhang_ast::amount::Amount;
+use zhang_ast::error::ErrorKind;
+use zhang_ast::{AccountType, Custom, SpanInfo, StringOrAccount};
+
+use crate::domains::schemas::{InvoiceDomain, InvoiceItemDomain, InvoiceKind};
+use crate::ledger::Ledger;
+use crate::utils::hashmap::HashMapOfExt;
+use crate::{process, ZhangResult};
+
+/// the type of custom directive declaring an invoice
+pub(crate) const INVOICE_CUSTOM_TYPE: &str = "invoice";
+const INVOICE_DUE: &str = "due";
+const INVOICE_ITEM: &str = "item";
+
+/// the invoice is declared as `custom "invoice" "{ID}" "{COUNTERPARTY}" {ACCOUNT}` with `due` and `item` metas,
+/// the account of assets is receivable and the account of liabilities is payable.
+/// the invoice is paid by the transactions linked to its id
+pub(crate) fn handle_invoice(ledger: &mut Ledger, custom: &Custom, span: &SpanInfo) -> ZhangResult<()> {
+    let id = match custom.values.first() {
+        Some(StringOrAccount::String(id)) => id.as_str().to_owned(),
+        _ => String::new(),
+    };
+    let Some(invoice) = parse_invoice(id.clone(), custom) else {
+        let mut operations = ledger.operations();
+        operations.new_error(ErrorKind::InvalidInvoice, span, HashMap::of("invoice_id", id))?;
+        return Ok(());
+    };
+    process::check_account_existed(&invoice.account, ledger, span)?;
+    process::check_commodity_define(&invoice.total.currency, ledger, span)?;
+
+    let mut operations = ledger.operations();
+    if operations.invoice(&invoice.id)?.is_some() {
+        operations.new_error(ErrorKind::DuplicatedInvoice, span, HashMap::of("invoice_id", invoice.id))?;
+        return Ok(());
+    }
+    operations.insert_invoice(invoice)
+}
+
+fn parse_invoice(id: String, custom: &Custom) -> Option<InvoiceDomain> {
+    let (Some(StringOrAccount::String(counterparty)), Some(StringOrAccount::Account(account))) = (custom.values.get(1), custom.values.get(2)) else {
+        return None;
+    };
+    let kind = match account.account_type {
+        AccountType::Assets => InvoiceKind::Receivable,
+        AccountType::Liabilities => InvoiceKind::Payable,
+        _ => return None,
+    };
+    let date = custom.date.naive_date();
+    let due = match custom.meta.get_one(INVOICE_DUE) {
+        Some(due) => NaiveDate::parse_from_str(due.as_str().trim(), "%Y-%m-%d").ok()?,
+        None => date,
+    };
+    let items = custom
+        .meta
+        .clone()
+        .get_flatten()
+        .into_iter()
+        .filter(|(key, _)| key.eq(INVOICE_ITEM))
+        .map(|(_, item)| parse_item(item.as_str()))
+        .collect::<Option<Vec<_>>>()?;
+    let currency = items.iter().map(|item| &item.amount.currency).dedup().exactly_one().ok()?.clone();
+    let mut total = BigDecimal::zero();
+    for item in items.iter() {
+        total.add_assign(&item.amount.number);
+    }
+    if id.is_empty() || !total.is_positive() {
+        return None;
+    }
+    Some(InvoiceDomain {
+        id,
+        date,
+        counterparty: counterparty.as_str().to_owned(),
+        account: account.name().to_owned(),
+        kind,
+        due,
+        items,
+        total: Amount::new(total, currency),
+    })
+}
+
+/// the line item like `Consulting 1000 CNY`, whose amount is the last two words
+fn parse_item(item: &str) -> Option<InvoiceItemDomain> {
+    let mut words = item.trim().rsplitn(3, char::is_whitespace);
+    let currency = words.next()?;
+    let number = BigDecimal::from_str(words.next()?).ok()?;
+    let description = words.next().unwrap_or_default().trim();
+    Some(InvoiceItemDomain {
+        description: description.to_owned(),
+        amount: Amount::new(number, currency),
+    })
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use bigdecimal::{BigDecimal, One, RoundingMode, Zero};
+use chrono::Months;
+use uuid::Uuid;
+use zhang_ast::amount::Amount;
+use zhang_ast::error::ErrorKind;
+use zhang_ast::{Date, Loan, SpanInfo};
+
+use crate::constants::DEFAULT_COMMODITY_PRECISION;
+use crate::domains::schemas::LoanPaymentDomain;
+use crate::ledger::Ledger;
+use crate::process::DirectiveProcess;
+use crate::utils::hashmap::HashMapOfExt;
+use crate::utils::id::FromSpan;
+use crate::{process, ZhangResult};
+
+/// the scale kept when compounding the monthly rate, so that the long terms don't blow up the digits
+const COMPOUNDING_SCALE: i64 = 20;
+
+impl DirectiveProcess for Loan {
+    fn validate(&mut self, ledger: &mut Ledger, span: &SpanInfo) -> ZhangResult<bool> {
+        process::check_account_existed(self.account.name(), ledger, span)?;
+        process::check_account_existed(self.payment_account.name(), ledger, span)?;
+        process::check_account_existed(self.interest_account.name(), ledger, span)?;
+        process::check_commodity_define(&self.principal.currency, ledger, span)?;
+        if self.term == 0 {
+            let mut operations = ledger.operations();
+            operations.new_error(ErrorKind::InvalidLoanTerm, span, HashMap::of("account_name", self.account.name().to_owned()))?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// one repayment transaction is generated at the same day of each following month within the term
+    fn process(&mut self, ledger: &mut Ledger, span: &SpanInfo) -> ZhangResult<()> {
+        let mut operations = ledger.operations();
+        let precision = operations
+            .commodity(&self.principal.currency)?
+            .map(|it| it.precision)
+            .unwrap_or(DEFAULT_COMMODITY_PRECISION);
+        let schedule = amortization_schedule(&self.principal.number, &self.rate, self.term, precision as i64);
+
+        let id = Uuid::from_span(span);
+        let narration = format!("repayment of {}", self.account.name());
+        let currency = &self.principal.currency;
+        let mut remaining = self.principal.number.clone();
+        for (period, (interest, principal)) in schedule.into_iter().enumerate() {
+            let Some(date) = self.date.naive_date().checked_add_months(Months::new(period as u32 + 1)) else {
+                break;
+            };
+            let datetime = Date::Date(date).to_timezone_datetime(&ledger.options.timezone);
+            let trx_id = Uuid::from_txn_posting(&id, period);
+            let postings = vec![
+                (self.account.name(), Amount::new(principal.clone(), currency)),
+                (self.interest_account.name(), Amount::new(interest.clone(), currency)),
+                (self.payment_account.name(), Amount::new((&principal).add(&interest).neg(), currency)),
+            ];
+            process::insert_generated_transaction(ledger, &trx_id, datetime, &narration, postings, span, &mut operations)?;
+
+            remaining = remaining.sub(&principal);
+            operations.insert_loan_payment(
+                self.account.name(),
+                LoanPaymentDomain {
+                    datetime,
+                    trx_id,
+                    interest: Amount::new(interest, currency),
+                    principal: Amount::new(principal, currency),
+                    remaining_principal: Amount::new(remaining.clone(), currency),
+                },
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// the interest and principal repaid month by month with the equal monthly payment, each amount is rounded into the
+/// precision of commodity, and the last month repays the remaining principal so that the whole principal is repaid
+fn amortization_schedule(principal: &BigDecimal, annual_rate: &BigDecimal, term: u32, precision: i64) -> Vec<(BigDecimal, BigDecimal)> {
+    let monthly_rate = annual_rate.div(BigDecimal::from(1200));
+    let payment = if monthly_rate.is_zero() {
+        principal.div(BigDecimal::from(term))
+    } else {
+        // payment = principal * rate * growth / (growth - 1), where growth = (1 + rate) ^ term
+        let growth = (0..term).fold(BigDecimal::one(), |growth, _| {
+            growth
+                .mul(BigDecimal::one().add(&monthly_rate))
+                .with_scale_round(COMPOUNDING_SCALE, RoundingMode::HalfUp)
+        });
+        principal.mul(&monthly_rate).mul(&growth).div(growth.sub(BigDecimal::one()))
+    }
+    .with_scale_round(precision, RoundingMode::HalfUp);
+
+    let mut remaining = principal.clone();
+    let mut schedule = Vec::with_capacity(term as usize);
+    for period in 1..=term {
+        let interest = (&remaining).mul(&monthly_rate).with_scale_round(precision, RoundingMode::HalfUp);
+        let repaid = if period == term {
+            remaining.clone()
+        } else {
+            (&payment).sub(&interest).min(remaining.clone())
+        };
+        remaining = remaining.sub(&repaid);
+        schedule.push((interest, repaid));
+    }
+    schedule
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+
+    use crate::process::loan::amortization_schedule;
+
+    #[test]
+    fn should_repay_equal_payments_given_rate() {
+        let schedule = amortization_schedule(&BigDecimal::from(10000), &BigDecimal::from(12), 12, 2);
+        assert_eq!(12, schedule.len());
+        // the monthly payment of 10000 at 1% monthly rate within 12 months is 888.49
+        assert_eq!((BigDecimal::from(100), BigDecimal::from_str("788.49").unwrap()), schedule[0]);
+        let total_principal: BigDecimal = schedule.iter().map(|(_, principal)| principal.clone()).sum();
+        assert_eq!(BigDecimal::from(10000), total_principal);
+    }
+
+    #[test]
+    fn should_repay_principal_evenly_given_zero_rate() {
+        let schedule = amortization_schedule(&BigDecimal::from(1000), &BigDecimal::from(0), 3, 2);
+        let principals = schedule.into_iter().map(|(_, principal)| principal).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                BigDecimal::from_str("333.33").unwrap(),
+                BigDecimal::from_str("333.33").unwrap(),
+                BigDecimal::from_str("333.34").unwrap()
+            ],
+            principals
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use uuid::Uuid;
+use zhang_ast::error::ErrorKind;
+use zhang_ast::{Directive, Meta, SpanInfo};
+
+use crate::constants::TXN_ID;
+use crate::ledger::Ledger;
+use crate::options::{MetaSchemaRule, MetaValueType};
+use crate::utils::id::FromSpan;
+use crate::{process, ZhangResult};
+
+/// the keyword of directive which meta schema is declared for, see [crate::options::META_SCHEMA_TARGETS]
+fn target_of(directive: &Directive) -> Option<&'static str> {
+    let target = match directive {
+        Directive::Transaction(_) => "transaction",
+        Directive::Open(_) => "open",
+        Directive::Close(_) => "close",
+        Directive::Commodity(_) => "commodity",
+        Directive::BalanceCheck(_) | Directive::BalancePad(_) => "balance",
+        Directive::Pad(_) => "pad",
+        Directive::Note(_) => "note",
+        Directive::Document(_) => "document",
+        Directive::Price(_) => "price",
+        Directive::Event(_) => "event",
+        Directive::Custom(_) => "custom",
+        Directive::Split(_) => "split",
+        Directive::Depreciation(_) => "depreciation",
+        Directive::Loan(_) => "loan",
+        Directive::Accrue(_) => "accrue",
+        Directive::OpenBalance(_) => "open-balance",
+        Directive::Template(_) => "template",
+        Directive::Budget(_) => "budget",
+        Directive::BudgetAdd(_) => "budget-add",
+        Directive::BudgetTransfer(_) => "budget-transfer",
+        Directive::BudgetClose(_) => "budget-close",
+        _ => return None,
+    };
+    Some(target)
+}
+
+/// check the metas of directive, and the metas of its postings if it is a transaction, against the rules of option `meta_schema`
+pub(crate) fn check_meta_schema(ledger: &mut Ledger, directive: &mut Directive, span: &SpanInfo) -> ZhangResult<()> {
+    if ledger.options.meta_schemas.is_empty() {
+        return Ok(());
+    }
+    let Some(target) = target_of(directive) else {
+        return Ok(());
+    };
+    let rules = ledger.options.meta_schemas.clone();
+    let accounts = directive.accounts_mut().into_iter().map(|it| it.name().to_owned()).collect::<Vec<_>>();
+    let txn_id = matches!(directive, Directive::Transaction(_)).then(|| Uuid::from_span(span).to_string());
+
+    for rule in rules.iter().filter(|rule| rule.target == target) {
+        let applied_account = match &rule.account {
+            Some(_) => match accounts.iter().find(|account| rule.is_applied_to_account(account)) {
+                Some(account) => Some(account.as_str()),
+                None => continue,
+            },
+            None => None,
+        };
+        let described = match applied_account {
+            Some(account) => format!("{} of {}", target, account),
+            None => target.to_owned(),
+        };
+        if let Some(meta) = directive.get_meta() {
+            check_meta(ledger, rule, meta, described, txn_id.as_deref(), span)?;
+        }
+    }
+
+    if let Directive::Transaction(trx) = directive {
+        for rule in rules.iter().filter(|rule| rule.target == "posting") {
+            for posting in trx.postings.iter().filter(|posting| rule.is_applied_to_account(posting.account.name())) {
+                let described = format!("posting of {}", posting.account.name());
+                check_meta(ledger, rule, &posting.meta, described, txn_id.as_deref(), span)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_meta(ledger: &mut Ledger, rule: &MetaSchemaRule, meta: &Meta, target: String, txn_id: Option<&str>, span: &SpanInfo) -> ZhangResult<()> {
+    let mut metas = HashMap::from([
+        ("target".to_owned(), target),
+        ("key".to_owned(), rule.key.clone()),
+        ("meta_type".to_owned(), rule.value_type.as_ref().to_owned()),
+    ]);
+    if let Some(txn_id) = txn_id {
+        metas.insert(TXN_ID.to_owned(), txn_id.to_owned());
+    }
+    let Some(value) = meta.get_one(&rule.key).map(|it| it.as_str().to_owned()) else {
+        if rule.required {
+            ledger.operations().new_error(ErrorKind::MissingRequiredMeta, span, metas)?;
+        }
+        return Ok(());
+    };
+    if !is_type_of(ledger, rule.value_type, &value)? {
+        metas.insert("value".to_owned(), value);
+        ledger.operations().new_error(ErrorKind::InvalidMetaType, span, metas)?;
+    }
+    Ok(())
+}
+
+fn is_type_of(ledger: &mut Ledger, value_type: MetaValueType, value: &str) -> ZhangResult<bool> {
+    let value = value.trim();
+    Ok(match value_type {
+        MetaValueType::String => true,
+        MetaValueType::Number => BigDecimal::from_str(value).is_ok(),
+        MetaValueType::Date => NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok(),
+        MetaValueType::Bool => value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("false"),
+        MetaValueType::Path => !value.is_empty() && process::document::read_document(ledger, value).is_some(),
+        MetaValueType::Account => ledger.operations().exist_account(value)?,
+    })
+}
@@ -1,26 +1,46 @@
 use std::collections::HashMap;
-use std::ops::Add;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::sync::atomic::Ordering;
 
+use bigdecimal::{BigDecimal, Signed, Zero};
+use chrono::DateTime;
+use chrono_tz::Tz;
+use uuid::Uuid;
 use zhang_ast::amount::Amount;
 use zhang_ast::error::ErrorKind;
 use zhang_ast::utils::inventory::LotInfo;
 use zhang_ast::*;
 
-use crate::domains::schemas::AccountStatus;
-use crate::domains::Operations;
+use crate::constants::BOOKING_METHOD;
+use crate::domains::schemas::{AccountStatus, MetaType};
+use crate::domains::{AccountAmount, Operations};
 use crate::ledger::Ledger;
+use crate::store::{CommodityLotRecord, ReconcileState};
 use crate::utils::hashmap::HashMapOfExt;
 use crate::ZhangResult;
 
+pub(crate) mod accrue;
 pub(crate) mod balance;
 pub(crate) mod budget;
 pub(crate) mod close;
 pub(crate) mod commodity;
+pub(crate) mod depreciation;
 pub(crate) mod document;
+pub(crate) mod duplicate;
+pub(crate) mod elimination;
+pub(crate) mod invoice;
+pub(crate) mod loan;
+pub(crate) mod meta_schema;
 pub(crate) mod open;
+pub(crate) mod open_balance;
 pub(crate) mod options;
+pub(crate) mod pad;
 pub(crate) mod plugin;
 pub(crate) mod price;
+pub(crate) mod rename;
+pub(crate) mod severity;
+pub(crate) mod split;
+pub(crate) mod template;
 pub(crate) mod transaction;
 /// Directive Process is used to handle how a directive be validated, how we process directives and store the result into [Store]
 pub(crate) trait DirectiveProcess {
@@ -91,7 +111,20 @@ fn check_commodity_define(commodity_name: &str, ledger: &mut Ledger, span: &Span
     Ok(())
 }
 
-fn lot_add(account_name: AccountName, amount: Amount, lot_info: LotInfo, operations: &mut Operations) -> ZhangResult<()> {
+/// resolve the lot booking method of account from its `booking_method` meta, FIFO is used by default
+fn account_booking_method(account_name: &str, operations: &Operations) -> ZhangResult<LotInfo> {
+    let booking_method = operations.meta(MetaType::AccountMeta, account_name, BOOKING_METHOD)?;
+    let lot_info = match booking_method.as_ref().map(|it| it.value.to_uppercase()).as_deref() {
+        Some("LIFO") | Some("FILO") => LotInfo::Filo,
+        Some("AVERAGE") => LotInfo::Average,
+        _ => LotInfo::Fifo,
+    };
+    Ok(lot_info)
+}
+
+/// add amount into account's lots, return the lots consumed by the reduction
+fn lot_add(account_name: AccountName, amount: Amount, lot_info: LotInfo, span: &SpanInfo, operations: &mut Operations) -> ZhangResult<Vec<CommodityLotRecord>> {
+    let mut consumed_lots = vec![];
     match lot_info {
         LotInfo::Lot(target_currency, lot_number) => {
             let price = Amount::new(lot_number, target_currency);
@@ -99,31 +132,205 @@ fn lot_add(account_name: AccountName, amount: Amount, lot_info: LotInfo, operati
             let lot = operations.account_lot(&account_name, &amount.currency, Some(price.clone()))?;
 
             if let Some(lot_row) = lot {
-                operations.update_account_lot(&account_name, &amount.currency, Some(price), &lot_row.amount.add(&amount.number))?;
+                let lot_amount = (&lot_row.amount).add(&amount.number);
+                if amount.number.is_negative() {
+                    let consumed = (&lot_row.amount).max(&BigDecimal::zero()).min(&amount.number.abs()).clone();
+                    if !consumed.is_zero() {
+                        consumed_lots.push(CommodityLotRecord {
+                            amount: consumed,
+                            ..lot_row.clone()
+                        });
+                    }
+                    if lot_amount.is_negative() {
+                        lot_becomes_negative(&account_name, &amount.currency, &lot_amount, span, operations)?;
+                    }
+                }
+                operations.update_account_lot(&account_name, &amount.currency, Some(price), None, &lot_amount)?;
+            } else if amount.number.is_negative() {
+                lot_becomes_negative(&account_name, &amount.currency, &amount.number, span, operations)?;
+                operations.insert_account_lot(&account_name, &amount.currency, Some(price.clone()), None, &amount.number)?;
             } else {
-                operations.insert_account_lot(&account_name, &amount.currency, Some(price.clone()), &amount.number)?;
+                operations.insert_account_lot(&account_name, &amount.currency, Some(price.clone()), None, &amount.number)?;
+            }
+        }
+        LotInfo::Fifo | LotInfo::Filo if amount.number.is_negative() => {
+            let mut lots = operations.account_lots(&account_name, &amount.currency)?;
+            let held_at_cost = lots.iter().any(|lot| lot.price.is_some());
+            if lot_info == LotInfo::Filo {
+                // the most recently acquired lot is consumed first
+                lots.reverse();
+            }
+
+            let mut remaining = amount.number.abs();
+            for lot in lots.into_iter().filter(|lot| lot.amount.is_positive()) {
+                if remaining.is_zero() {
+                    break;
+                }
+                let consumed = (&lot.amount).min(&remaining).clone();
+                operations.update_account_lot(
+                    &account_name,
+                    &amount.currency,
+                    lot.price.clone(),
+                    lot.label.clone(),
+                    &(&lot.amount).sub(&consumed),
+                )?;
+                remaining = remaining.sub(&consumed);
+                consumed_lots.push(CommodityLotRecord { amount: consumed, ..lot });
+            }
+
+            if !remaining.is_zero() {
+                if held_at_cost {
+                    lot_becomes_negative(&account_name, &amount.currency, &remaining.clone().neg(), span, operations)?;
+                }
+                reduce_default_lot(&account_name, &amount.currency, &remaining, operations)?;
+            }
+        }
+        LotInfo::Average if amount.number.is_negative() => {
+            let lots = operations.account_lots(&account_name, &amount.currency)?;
+            let held_at_cost = lots.iter().any(|lot| lot.price.is_some());
+            let price_commodity = lots
+                .iter()
+                .filter(|lot| lot.amount.is_positive())
+                .find_map(|lot| lot.price.as_ref().map(|it| it.currency.clone()));
+
+            let mut remaining = amount.number.abs();
+            if let Some(price_commodity) = price_commodity {
+                // merge all open lots priced in the same commodity into one lot at the weighted average price
+                let (holding, cost) = lots
+                    .iter()
+                    .filter(|lot| lot.amount.is_positive())
+                    .filter_map(|lot| {
+                        lot.price
+                            .as_ref()
+                            .filter(|price| price.currency.eq(&price_commodity))
+                            .map(|price| (&lot.amount, price))
+                    })
+                    .fold((BigDecimal::zero(), BigDecimal::zero()), |(holding, cost), (amount, price)| {
+                        (holding.add(amount), cost.add(amount.mul(&price.number)))
+                    });
+                let average_price = Amount::new(cost.div(&holding), price_commodity.clone());
+                let consumed = (&holding).min(&remaining).clone();
+                remaining = remaining.sub(&consumed);
+
+                operations.remove_account_lots(&account_name, &amount.currency, &price_commodity)?;
+                operations.insert_account_lot(&account_name, &amount.currency, Some(average_price.clone()), None, &holding.sub(&consumed))?;
+                if !consumed.is_zero() {
+                    consumed_lots.push(CommodityLotRecord {
+                        commodity: amount.currency.clone(),
+                        datetime: None,
+                        amount: consumed,
+                        price: Some(average_price),
+                        label: None,
+                    });
+                }
+            }
+
+            if !remaining.is_zero() {
+                if held_at_cost {
+                    lot_becomes_negative(&account_name, &amount.currency, &remaining.clone().neg(), span, operations)?;
+                }
+                reduce_default_lot(&account_name, &amount.currency, &remaining, operations)?;
             }
         }
-        LotInfo::Fifo => {
+        LotInfo::Fifo | LotInfo::Filo | LotInfo::Average => {
             let lot = operations.account_lot(&account_name, &amount.currency, None)?;
             if let Some(lot) = lot {
-                if lot.price.is_some() {
-                    // target lot
-                    operations.update_account_lot(&account_name, &amount.currency, lot.price, &lot.amount.add(&amount.number))?;
-
-                    // todo check negative
-                } else {
-                    // default lot
-                    operations.update_account_lot(&account_name, &amount.currency, None, &lot.amount.add(&amount.number))?;
-                }
+                operations.update_account_lot(&account_name, &amount.currency, None, None, &lot.amount.add(&amount.number))?;
             } else {
-                operations.insert_account_lot(&account_name, &amount.currency, None, &amount.number)?;
+                operations.insert_account_lot(&account_name, &amount.currency, None, None, &amount.number)?;
             }
         }
-        LotInfo::Filo => {
-            unimplemented!()
+    }
+
+    Ok(consumed_lots)
+}
+
+/// add amount into the lot with given label, return the lot consumed by the reduction.
+/// the lot is created at the given cost when it is acquired, and the reduction must target an existing label
+fn labeled_lot_add(
+    account_name: AccountName, amount: Amount, label: &str, cost: Option<Amount>, span: &SpanInfo, operations: &mut Operations,
+) -> ZhangResult<Vec<CommodityLotRecord>> {
+    let lot = operations.account_labeled_lot(&account_name, &amount.currency, label)?;
+    let Some(lot) = lot else {
+        if amount.number.is_negative() {
+            let metas = HashMap::of3("account_name", account_name, "commodity_name", amount.currency, "label", label.to_owned());
+            operations.new_error(ErrorKind::LotNotFound, span, metas)?;
+        } else {
+            operations.insert_account_lot(&account_name, &amount.currency, cost, Some(label.to_owned()), &amount.number)?;
+        }
+        return Ok(vec![]);
+    };
+
+    let lot_amount = (&lot.amount).add(&amount.number);
+    let mut consumed_lots = vec![];
+    if amount.number.is_negative() {
+        let consumed = (&lot.amount).max(&BigDecimal::zero()).min(&amount.number.abs()).clone();
+        if !consumed.is_zero() {
+            consumed_lots.push(CommodityLotRecord {
+                amount: consumed,
+                ..lot.clone()
+            });
+        }
+        if lot_amount.is_negative() {
+            lot_becomes_negative(&account_name, &amount.currency, &lot_amount, span, operations)?;
         }
     }
+    operations.update_account_lot(&account_name, &amount.currency, lot.price, lot.label, &lot_amount)?;
+    Ok(consumed_lots)
+}
 
+/// insert the transaction generated by the ledger itself, e.g. the schedules of depreciation and loan,
+/// the postings are booked into the lots of accounts like the postings written in transactions
+fn insert_generated_transaction(
+    ledger: &Ledger, trx_id: &Uuid, datetime: DateTime<Tz>, narration: &str, postings: Vec<(&str, Amount)>, span: &SpanInfo, operations: &mut Operations,
+) -> ZhangResult<()> {
+    let sequence = ledger.trx_counter.fetch_add(1, Ordering::Relaxed);
+    operations.insert_transaction(trx_id, sequence, datetime, Flag::Okay, None, Some(narration), vec![], vec![], span)?;
+    for (posting_idx, (account_name, amount)) in postings.into_iter().enumerate() {
+        insert_generated_posting(trx_id, posting_idx, datetime, account_name, amount.clone(), operations)?;
+        let lot_info = account_booking_method(account_name, operations)?;
+        lot_add(account_name.to_owned(), amount, lot_info, span, operations)?;
+        operations.record_account_lots(account_name, datetime)?;
+    }
     Ok(())
 }
+
+/// insert the posting generated by the ledger itself rather than written in the transaction, e.g. the postings of split and depreciation
+fn insert_generated_posting(
+    trx_id: &Uuid, posting_idx: usize, datetime: DateTime<Tz>, account_name: &str, amount: Amount, operations: &mut Operations,
+) -> ZhangResult<()> {
+    let previous = operations
+        .account_target_day_balance(account_name, datetime, &amount.currency)?
+        .unwrap_or(AccountAmount {
+            number: BigDecimal::zero(),
+            commodity: amount.currency.clone(),
+        });
+    let after_number = (&previous.number).add(&amount.number);
+    operations.shift_account_balances(account_name, datetime, &amount)?;
+    operations.insert_transaction_posting(
+        trx_id,
+        posting_idx,
+        datetime,
+        account_name,
+        Some(amount.clone()),
+        None,
+        amount,
+        Amount::new(previous.number, previous.commodity.clone()),
+        Amount::new(after_number, previous.commodity),
+        ReconcileState::Cleared,
+        None,
+    )
+}
+
+/// reduce the lot without price, used when the priced lots are not enough to be consumed
+fn reduce_default_lot(account_name: &str, currency: &str, number: &BigDecimal, operations: &mut Operations) -> ZhangResult<()> {
+    let default_lot = operations.account_lot(account_name, currency, None)?;
+    let default_amount = default_lot.map(|it| it.amount).unwrap_or_else(BigDecimal::zero);
+    operations.update_account_lot(account_name, currency, None, None, &default_amount.sub(number))
+}
+
+/// emit error for the reduction which is more than the holding lots, `number` is the amount that the lot goes below zero
+fn lot_becomes_negative(account_name: &str, currency: &str, number: &BigDecimal, span: &SpanInfo, operations: &mut Operations) -> ZhangResult<()> {
+    let metas = HashMap::of3("account_name", account_name, "commodity_name", currency, "amount", number.to_string());
+    operations.new_error(ErrorKind::LotBecomesNegative, span, metas)
+}
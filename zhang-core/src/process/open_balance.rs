@@ -0,0 +1,50 @@
+use std::ops::Neg;
+use std::str::FromStr;
+
+use uuid::Uuid;
+use zhang_ast::{Account, Open, OpenBalance, SpanInfo};
+
+use crate::constants::DEFAULT_OPENING_BALANCE_ACCOUNT;
+use crate::ledger::Ledger;
+use crate::process::DirectiveProcess;
+use crate::utils::id::FromSpan;
+use crate::{process, ZhangResult};
+
+fn equity_account(open_balance: &OpenBalance) -> Account {
+    open_balance
+        .equity_account
+        .clone()
+        .unwrap_or_else(|| Account::from_str(DEFAULT_OPENING_BALANCE_ACCOUNT).expect("default opening balance account should be valid"))
+}
+
+impl DirectiveProcess for OpenBalance {
+    fn validate(&mut self, ledger: &mut Ledger, span: &SpanInfo) -> ZhangResult<bool> {
+        process::check_commodity_define(&self.amount.currency, ledger, span)?;
+        process::check_account_existed(equity_account(self).name(), ledger, span)?;
+        Ok(true)
+    }
+
+    /// the directive is expanded into the open of account and the transaction of initial balance at the date
+    fn process(&mut self, ledger: &mut Ledger, span: &SpanInfo) -> ZhangResult<()> {
+        let mut open = Open {
+            date: self.date.clone(),
+            account: self.account.clone(),
+            commodities: vec![],
+            meta: self.meta.clone(),
+        };
+        open.handler(ledger, span)?;
+
+        let mut operations = ledger.operations();
+        let equity_account = equity_account(self);
+        let datetime = self.date.to_timezone_datetime(&ledger.options.timezone);
+        process::insert_generated_transaction(
+            ledger,
+            &Uuid::from_span(span),
+            datetime,
+            &format!("opening balance of {}", self.account.name()),
+            vec![(self.account.name(), self.amount.clone()), (equity_account.name(), self.amount.clone().neg())],
+            span,
+            &mut operations,
+        )
+    }
+}
@@ -0,0 +1,84 @@
+use zhang_ast::amount::Amount;
+use zhang_ast::{Account, Flag, Pad, Posting, PostingType, SpanInfo, Transaction, ZhangString};
+
+use crate::ledger::Ledger;
+use crate::process::DirectiveProcess;
+use crate::store::PadDomain;
+use crate::{process, ZhangResult};
+
+impl DirectiveProcess for Pad {
+    fn validate(&mut self, ledger: &mut Ledger, span: &SpanInfo) -> ZhangResult<bool> {
+        process::check_account_existed(self.account.name(), ledger, span)?;
+        process::check_account_existed(self.pad.name(), ledger, span)?;
+        process::check_account_closed(self.account.name(), ledger, span)?;
+        process::check_account_closed(self.pad.name(), ledger, span)?;
+        Ok(true)
+    }
+
+    fn process(&mut self, ledger: &mut Ledger, span: &SpanInfo) -> ZhangResult<()> {
+        let mut operations = ledger.operations();
+        operations.insert_pad(
+            self.account.name(),
+            PadDomain {
+                date: self.date.clone(),
+                pad: self.pad.clone(),
+                span: span.clone(),
+            },
+        )?;
+        Ok(())
+    }
+}
+
+/// consume the most recent unused pad directive of account when a balance assertion is processed,
+/// a padding transaction with `distance` is inserted at the pad date if the balance does not match.
+/// return `true` if the account is padded
+pub(crate) fn pad_account(ledger: &mut Ledger, account: &Account, distance: &Amount) -> ZhangResult<bool> {
+    let mut operations = ledger.operations();
+    let Some(pad) = operations.take_pad(account.name())? else {
+        return Ok(false);
+    };
+    if distance.is_zero() {
+        return Ok(false);
+    }
+
+    let mut padding_trx = Transaction {
+        date: pad.date.clone(),
+        auxiliary_date: None,
+        flag: Some(Flag::BalancePad),
+        payee: Some(ZhangString::quote("Balance Pad")),
+        narration: Some(ZhangString::quote(format!("pad {} to {}", account.name(), pad.pad.name()))),
+        tags: Default::default(),
+        links: Default::default(),
+        postings: vec![
+            Posting {
+                flag: None,
+                posting_type: PostingType::Real,
+                account: account.clone(),
+                units: Some(distance.clone()),
+                cost: None,
+                cost_date: None,
+                cost_label: None,
+                price: None,
+                comment: None,
+                meta: Default::default(),
+            },
+            Posting {
+                flag: None,
+                posting_type: PostingType::Real,
+                account: pad.pad.clone(),
+                units: None,
+                cost: None,
+                cost_date: None,
+                cost_label: None,
+                price: None,
+                comment: None,
+                meta: Default::default(),
+            },
+        ],
+        meta: Default::default(),
+    };
+    // the padding transaction is processed at the pad date, so its after amount is the balance at the pad date plus the distance,
+    // and the balances of postings processed after the pad directive are shifted by it
+    padding_trx.process(ledger, &pad.span)?;
+    Ok(true)
+}
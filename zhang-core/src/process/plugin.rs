@@ -26,6 +26,107 @@ pub(crate) fn save_plugin_content_into_cache_folder(plugin_hash: String, module_
     Ok(())
 }
 
+/// report the failure of plugin execution, e.g. exceeding its time or memory limit, as [ErrorKind::PluginExecutionError] at the plugin directive
+#[cfg(feature = "plugin_runtime")]
+pub(crate) fn plugin_execution_failed(ledger: &mut Ledger, plugin_name: &str, span: &SpanInfo, error: crate::ZhangError) -> ZhangResult<()> {
+    use std::collections::HashMap;
+
+    use zhang_ast::error::ErrorKind;
+
+    use crate::utils::hashmap::HashMapOfExt;
+
+    log::error!("plugin {} failed to execute: {}", plugin_name, error);
+    let mut operations = ledger.operations();
+    let metas = HashMap::of2("plugin_name", plugin_name, "reason", error.to_string());
+    operations.new_error(ErrorKind::PluginExecutionError, span, metas)
+}
+
+/// call the loaded plugins one by one and collect the results along with the plugin name,
+/// the plugin failing to execute is reported and unloaded so that it is not called again for the remaining directives
+#[cfg(feature = "plugin_runtime")]
+pub(crate) fn execute_loaded_plugins<T>(
+    ledger: &mut Ledger, plugins: &mut Vec<(crate::plugin::store::RegisteredPlugin, extism::Plugin)>,
+    mut execute: impl FnMut(&crate::plugin::store::RegisteredPlugin, &mut extism::Plugin) -> ZhangResult<T>,
+) -> ZhangResult<Vec<(String, T)>> {
+    let mut ret = vec![];
+    let mut idx = 0;
+    while idx < plugins.len() {
+        let (plugin, wasm_plugin) = &mut plugins[idx];
+        match execute(plugin, wasm_plugin) {
+            Ok(value) => {
+                ret.push((plugin.name.clone(), value));
+                idx += 1;
+            }
+            Err(e) => {
+                let (plugin, _) = plugins.remove(idx);
+                plugin_execution_failed(ledger, &plugin.name, &plugin.span, e)?;
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// apply the plugins of transaction hook type to the transaction in order, each hook receives the transaction returned by the previous one
+#[cfg(feature = "plugin_runtime")]
+pub(crate) fn transaction_by_plugins(
+    ledger: &mut Ledger, hooks: &mut Vec<(crate::plugin::store::RegisteredPlugin, extism::Plugin)>, trx: &mut zhang_ast::Transaction, span: &SpanInfo,
+) -> ZhangResult<()> {
+    use crate::plugin::store::RegisteredPlugin;
+
+    execute_loaded_plugins(ledger, hooks, |_, hook| {
+        *trx = RegisteredPlugin::execute_on_transaction(hook, zhang_ast::Spanned::new(trx.clone(), span.clone()))?;
+        Ok(())
+    })?;
+    Ok(())
+}
+
+/// validate the directive by plugins of validator type, reported errors are stored as [ErrorKind::PluginValidationError]
+#[cfg(feature = "plugin_runtime")]
+pub(crate) fn validate_by_plugins(
+    ledger: &mut Ledger, validators: &mut Vec<(crate::plugin::store::RegisteredPlugin, extism::Plugin)>, directive: &zhang_ast::Spanned<zhang_ast::Directive>,
+) -> ZhangResult<()> {
+    use std::collections::HashMap;
+
+    use zhang_ast::error::ErrorKind;
+
+    use crate::plugin::store::RegisteredPlugin;
+    use crate::utils::hashmap::HashMapOfExt;
+
+    let results = execute_loaded_plugins(ledger, validators, |_, validator| {
+        RegisteredPlugin::execute_as_validator(validator, directive.clone())
+    })?;
+    let mut operations = ledger.operations();
+    for (plugin_name, errors) in results {
+        for error in errors {
+            let metas = HashMap::of3("plugin_name", plugin_name.as_str(), "code", error.code, "message", error.message);
+            operations.new_error(ErrorKind::PluginValidationError, &directive.span, metas)?;
+        }
+    }
+    Ok(())
+}
+
+/// handle the custom directive by plugins which declare its type, returned rows are written into the plugin's data area
+#[cfg(feature = "plugin_runtime")]
+pub(crate) fn handle_custom_by_plugins(
+    ledger: &mut Ledger, custom_plugins: &mut Vec<(crate::plugin::store::RegisteredPlugin, extism::Plugin)>, custom: &zhang_ast::Custom, span: &SpanInfo,
+) -> ZhangResult<()> {
+    use crate::plugin::store::RegisteredPlugin;
+
+    let results = execute_loaded_plugins(ledger, custom_plugins, |plugin, custom_plugin| {
+        if !plugin.custom_types.iter().any(|it| it.eq(custom.custom_type.as_str())) {
+            return Ok(vec![]);
+        }
+        RegisteredPlugin::execute_as_custom_directive(custom_plugin, zhang_ast::Spanned::new(custom.clone(), span.clone()))
+    })?;
+    let mut operations = ledger.operations();
+    for (plugin_name, rows) in results {
+        for row in rows {
+            operations.insert_plugin_data(&plugin_name, row.key, row.value)?;
+        }
+    }
+    Ok(())
+}
+
 /// mainly for fetch the plugin data from remote and save it into local cache folder
 #[async_trait::async_trait]
 impl DirectivePreProcess for Plugin {
@@ -73,7 +174,10 @@ impl DirectiveProcess for Plugin {
         feature_enable!(ledger.options.features.plugins, {
             #[cfg(feature = "plugin_runtime")]
             {
-                ledger.plugins.insert_plugin(self)?;
+                let options = ledger.operations().options()?;
+                if let Err(e) = ledger.plugins.insert_plugin(self, _span, &options) {
+                    plugin_execution_failed(ledger, self.module.as_str(), _span, e)?;
+                }
             }
         });
 
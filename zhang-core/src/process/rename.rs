@@ -0,0 +1,81 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use zhang_ast::error::ErrorKind;
+use zhang_ast::{Account, Directive, Rename, SpanInfo, Spanned};
+
+use crate::ledger::Ledger;
+use crate::utils::hashmap::HashMapOfExt;
+use crate::ZhangResult;
+
+/// the account renames declared by `rename` directives, the rename of account also applies to its sub-accounts
+#[derive(Debug, Default)]
+pub(crate) struct AccountRenames {
+    renames: HashMap<String, String>,
+}
+
+impl AccountRenames {
+    /// rename the account once by the rename of itself or its nearest parent account
+    fn rename_once(&self, account_name: &str) -> Option<String> {
+        let mut prefix = account_name;
+        loop {
+            if let Some(to) = self.renames.get(prefix) {
+                return Some(format!("{}{}", to, &account_name[prefix.len()..]));
+            }
+            prefix = &prefix[..prefix.rfind(':')?];
+        }
+    }
+
+    /// rename the account until no rename applies, return `None` if the renames form a cycle
+    fn resolve(&self, account_name: &str) -> Option<String> {
+        let mut visited = HashSet::from([account_name.to_owned()]);
+        let mut current = account_name.to_owned();
+        while let Some(renamed) = self.rename_once(&current) {
+            if !visited.insert(renamed.clone()) {
+                return None;
+            }
+            current = renamed;
+        }
+        Some(current)
+    }
+}
+
+/// collect the `rename` directives, which are applied to the accounts of all directives before they are processed,
+/// so that postings, balances, budgets and documents of the historical account all belong to the current one
+pub(crate) fn account_renames(ledger: &mut Ledger, rename_directives: &[(Rename, SpanInfo)]) -> ZhangResult<AccountRenames> {
+    let mut operations = ledger.operations();
+    let mut account_renames = AccountRenames::default();
+    for (rename, span) in rename_directives {
+        let from = rename.from.name();
+        if account_renames.renames.contains_key(from) {
+            operations.new_error(ErrorKind::DuplicatedAccountRename, span, HashMap::of("account_name", from.to_owned()))?;
+            continue;
+        }
+        account_renames.renames.insert(from.to_owned(), rename.to.name().to_owned());
+        if account_renames.resolve(from).is_none() {
+            account_renames.renames.remove(from);
+            operations.new_error(ErrorKind::AccountRenameCycle, span, HashMap::of("account_name", from.to_owned()))?;
+        }
+    }
+
+    Ok(account_renames)
+}
+
+impl AccountRenames {
+    /// rename the accounts of directive to their current names
+    pub(crate) fn apply(&self, directive: &mut Spanned<Directive>) {
+        if self.renames.is_empty() {
+            return;
+        }
+        for account in directive.data.accounts_mut() {
+            let Some(renamed) = self.resolve(account.name()) else {
+                continue;
+            };
+            if renamed.ne(account.name()) {
+                if let Ok(renamed_account) = Account::from_str(&renamed) {
+                    *account = renamed_account;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use crate::ledger::Ledger;
+use crate::ZhangResult;
+
+/// apply the severity overrides given by options `error_severity` to the errors of ledger, the suppressed errors are removed
+pub(crate) fn apply_error_severities(ledger: &Ledger) -> ZhangResult<()> {
+    if ledger.options.error_severities.is_empty() {
+        return Ok(());
+    }
+    let operations = ledger.operations();
+    let mut store = operations.write();
+    store
+        .errors
+        .retain_mut(|error| match ledger.options.error_severity(&error.error_type, error.span.as_ref()) {
+            Some(severity) => {
+                error.severity = severity;
+                true
+            }
+            None => false,
+        });
+    Ok(())
+}
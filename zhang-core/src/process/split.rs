@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use std::ops::Div;
+use std::sync::atomic::Ordering;
+
+use bigdecimal::Zero;
+use uuid::Uuid;
+use zhang_ast::amount::Amount;
+use zhang_ast::error::ErrorKind;
+use zhang_ast::{Flag, SpanInfo, Split};
+
+use crate::ledger::Ledger;
+use crate::process::DirectiveProcess;
+use crate::utils::hashmap::HashMapOfExt;
+use crate::utils::id::FromSpan;
+use crate::{process, ZhangResult};
+
+impl DirectiveProcess for Split {
+    fn validate(&mut self, ledger: &mut Ledger, span: &SpanInfo) -> ZhangResult<bool> {
+        process::check_commodity_define(&self.currency, ledger, span)?;
+        if self.numerator.is_zero() || self.denominator.is_zero() {
+            let mut operations = ledger.operations();
+            operations.new_error(
+                ErrorKind::InvalidSplitRatio,
+                span,
+                HashMap::of2(
+                    "commodity_name",
+                    self.currency.clone(),
+                    "ratio",
+                    format!("{}:{}", self.numerator, self.denominator),
+                ),
+            )?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// the lots of commodity are adjusted in place so that their acquiring dates and order are kept,
+    /// and the changes of the holdings without cost are recorded as a transaction at the split date
+    fn process(&mut self, ledger: &mut Ledger, span: &SpanInfo) -> ZhangResult<()> {
+        let mut operations = ledger.operations();
+        let ratio = (&self.numerator).div(&self.denominator);
+        let datetime = self.date.to_timezone_datetime(&ledger.options.timezone);
+        let changes = operations.split_commodity_lots(&self.currency, &ratio, datetime)?;
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        let id = Uuid::from_span(span);
+        let sequence = ledger.trx_counter.fetch_add(1, Ordering::Relaxed);
+        operations.insert_transaction(
+            &id,
+            sequence,
+            datetime,
+            Flag::Okay,
+            None,
+            Some(&format!("split {} {}:{}", self.currency, self.numerator, self.denominator)),
+            vec![],
+            vec![],
+            span,
+        )?;
+        for (posting_idx, (account_name, change)) in changes.into_iter().enumerate() {
+            process::insert_generated_posting(
+                &id,
+                posting_idx,
+                datetime,
+                &account_name,
+                Amount::new(change, self.currency.clone()),
+                &mut operations,
+            )?;
+        }
+        Ok(())
+    }
+}
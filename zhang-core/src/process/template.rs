@@ -0,0 +1,20 @@
+use zhang_ast::{SpanInfo, Template};
+
+use crate::ledger::Ledger;
+use crate::process::DirectiveProcess;
+use crate::{process, ZhangResult};
+
+impl DirectiveProcess for Template {
+    fn validate(&mut self, ledger: &mut Ledger, span: &SpanInfo) -> ZhangResult<bool> {
+        for posting in self.postings.iter() {
+            process::check_account_existed(posting.account.name(), ledger, span)?;
+            process::check_account_closed(posting.account.name(), ledger, span)?;
+        }
+        Ok(true)
+    }
+
+    /// the template is not posted by itself, it is instantiated into transaction on demand
+    fn process(&mut self, _ledger: &mut Ledger, _span: &SpanInfo) -> ZhangResult<()> {
+        Ok(())
+    }
+}
@@ -1,37 +1,49 @@
 use std::collections::HashMap;
-use std::ops::{Add, Mul};
+use std::ops::{Add, Div, Mul, Sub};
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::atomic::Ordering;
 
-use bigdecimal::{BigDecimal, Zero};
+use bigdecimal::{BigDecimal, One, RoundingMode, Signed, Zero};
+use chrono::NaiveDate;
 use itertools::Itertools;
 use uuid::Uuid;
 use zhang_ast::amount::Amount;
 use zhang_ast::error::ErrorKind;
 use zhang_ast::utils::inventory::LotInfo;
-use zhang_ast::{Flag, SpanInfo, Transaction};
+use zhang_ast::{Account, AccountType, Date, Flag, Meta, Posting, PostingType, SingleTotalPrice, SpanInfo, Transaction, ZhangString};
 
-use crate::constants::TXN_ID;
-use crate::domains::schemas::MetaType;
-use crate::domains::AccountAmount;
+use crate::constants::{
+    ACCOUNT_ALLOWED_COMMODITIES, ACCOUNT_MAX_POSTING_AMOUNT, ACCOUNT_REQUIRE_TAG, DEFAULT_COMMODITY_PRECISION, KEY_ROUNDING_ACCOUNT, POSTING_AUTO_ROUNDING,
+    POSTING_AUTO_TAX, POSTING_DATE, POSTING_RECONCILE, POSTING_STATEMENT_DATE, TAX_ACCOUNT, TAX_RATE, TRUE, TXN_ID,
+};
+use crate::domains::schemas::{MetaType, RealizedGainDomain, TaxDomain};
+use crate::domains::{AccountAmount, Operations};
 use crate::ledger::Ledger;
 use crate::process::DirectiveProcess;
-use crate::store::DocumentType;
+use crate::store::{DocumentType, ReconcileState};
 use crate::utils::hashmap::HashMapOfExt;
 use crate::utils::id::FromSpan;
 use crate::{process, ZhangError, ZhangResult};
 
 impl DirectiveProcess for Transaction {
     fn validate(&mut self, ledger: &mut Ledger, span: &SpanInfo) -> ZhangResult<bool> {
+        // the voided transaction is never posted, so that it does not need to be balanced
+        if self.is_voided() {
+            return Ok(true);
+        }
         let mut operations = ledger.operations();
         let id = Uuid::from_span(span);
+        split_tax_postings(self, ledger, span)?;
+        add_rounding_postings(self, ledger)?;
         let txn_error = operations.check_transaction(self)?;
         if let Some(txn_error) = txn_error {
             let meta = HashMap::of(TXN_ID, id.to_string());
             match txn_error {
                 e @ (ErrorKind::TransactionHasMultipleImplicitPosting
                 | ErrorKind::TransactionCannotInferTradeAmount
-                | ErrorKind::TransactionExplicitPostingHaveMultipleCommodity) => {
+                | ErrorKind::TransactionExplicitPostingHaveMultipleCommodity
+                | ErrorKind::TransactionAmbiguousSplit) => {
                     operations.new_error(e, span, meta)?;
                     return Ok(false);
                 }
@@ -40,6 +52,7 @@ impl DirectiveProcess for Transaction {
                 }
             }
         }
+        check_posting_constraints(self, ledger, span)?;
 
         Ok(true)
     }
@@ -49,67 +62,395 @@ impl DirectiveProcess for Transaction {
 
         let id = Uuid::from_span(span);
         let sequence = ledger.trx_counter.fetch_add(1, Ordering::Relaxed);
+        let date = ledger.options.transaction_date.of(self).clone();
         operations.insert_transaction(
             &id,
             sequence,
-            self.date.to_timezone_datetime(&ledger.options.timezone),
-            self.flag.clone().unwrap_or(Flag::Okay),
-            self.payee.as_ref().map(|it| it.as_str()),
+            date.to_timezone_datetime(&ledger.options.timezone),
+            if self.is_voided() {
+                Flag::Void
+            } else {
+                self.flag.clone().unwrap_or(Flag::Okay)
+            },
+            self.payee.as_ref().map(|it| ledger.options.normalize_payee(it.as_str())),
             self.narration.as_ref().map(|it| it.as_str()),
             self.tags.iter().cloned().collect_vec(),
             self.links.iter().cloned().collect_vec(),
             span,
         )?;
 
+        if self.is_voided() {
+            return insert_voided_postings(self, ledger, &id, &date);
+        }
+
         for (posting_idx, txn_posting) in self.txn_postings().into_iter().enumerate() {
             let inferred_amount = txn_posting.infer_trade_amount().map_err(ZhangError::ProcessError)?;
+            let datetime = posting_date(&date, txn_posting.posting).to_timezone_datetime(&ledger.options.timezone);
 
-            let option = operations.account_target_day_balance(
-                txn_posting.posting.account.name(),
-                self.date.to_timezone_datetime(&ledger.options.timezone),
-                &inferred_amount.currency,
-            )?;
+            let option = operations.account_target_day_balance(txn_posting.posting.account.name(), datetime, &inferred_amount.currency)?;
 
             let previous = option.unwrap_or(AccountAmount {
                 number: BigDecimal::zero(),
                 commodity: inferred_amount.currency.clone(),
             });
             let after_number = (&previous.number).add(&inferred_amount.number);
+            let (reconcile_state, statement_date) = posting_reconcile_state(self, txn_posting.posting);
+            // the postings processed before may be dated after this posting
+            operations.shift_account_balances(txn_posting.posting.account.name(), datetime, &inferred_amount)?;
 
             operations.insert_transaction_posting(
                 &id,
                 posting_idx,
+                datetime,
                 txn_posting.posting.account.name(),
                 txn_posting.posting.units.clone(),
                 txn_posting.posting.cost.clone(),
                 inferred_amount.clone(),
                 Amount::new(previous.number, previous.commodity.clone()),
                 Amount::new(after_number, previous.commodity),
+                reconcile_state,
+                statement_date,
             )?;
 
+            if let Some(taxed_account) = txn_posting.posting.meta.get_one(POSTING_AUTO_TAX) {
+                operations.insert_tax(TaxDomain {
+                    datetime,
+                    trx_id: id,
+                    account: taxed_account.as_str().to_owned(),
+                    tax_account: txn_posting.account_name(),
+                    tax: inferred_amount.clone(),
+                })?;
+            }
+
             // budget related
             let budgets_name = operations.get_account_budget(txn_posting.posting.account.name())?;
             for budget in budgets_name {
                 let budget_activity_amount = inferred_amount.mul(BigDecimal::from(txn_posting.posting.account.get_account_sign()));
-                operations.budget_add_activity(budget, self.date.to_timezone_datetime(&ledger.options.timezone), budget_activity_amount)?;
+                if let Some(budget_activity_amount) =
+                    process::budget::budget_activity_amount(ledger, &budget, budget_activity_amount, datetime, &mut operations)?
+                {
+                    operations.budget_add_activity(budget, datetime, budget_activity_amount)?;
+                }
             }
 
             let amount = txn_posting.units().unwrap_or(inferred_amount);
-            let lot_info = txn_posting.lots().unwrap_or(LotInfo::Fifo);
-            process::lot_add(txn_posting.account_name(), amount, lot_info, &mut operations)?;
+            let lot_info = match txn_posting.lots() {
+                // reduction with price only is disposing holding lots, instead of holding a lot at the given price
+                Some(LotInfo::Lot(..)) if amount.number.is_negative() && txn_posting.posting.cost.is_none() => {
+                    process::account_booking_method(txn_posting.posting.account.name(), &operations)?
+                }
+                Some(lot_info) => lot_info,
+                None => process::account_booking_method(txn_posting.posting.account.name(), &operations)?,
+            };
+            // only the reducing posting disposes lots, whose units are negative and never zero
+            let disposal_price = txn_posting
+                .posting
+                .price
+                .as_ref()
+                .filter(|_| amount.number.is_negative())
+                .map(|price| match price {
+                    SingleTotalPrice::Single(single_price) => single_price.clone(),
+                    SingleTotalPrice::Total(total_price) => Amount::new((&total_price.number).div(amount.number.abs()), total_price.currency.clone()),
+                });
+            let consumed_lots = match &txn_posting.posting.cost_label {
+                Some(label) => {
+                    let cost = match lot_info {
+                        LotInfo::Lot(currency, number) => Some(Amount::new(number, currency)),
+                        _ => None,
+                    };
+                    process::labeled_lot_add(txn_posting.account_name(), amount.clone(), label, cost, span, &mut operations)?
+                }
+                None => process::lot_add(txn_posting.account_name(), amount.clone(), lot_info, span, &mut operations)?,
+            };
+            operations.record_account_lots(txn_posting.posting.account.name(), datetime)?;
+
+            // realized gain is only calculated when the lot cost and disposal price are in the same commodity
+            if let Some(disposal_price) = disposal_price {
+                for consumed_lot in consumed_lots {
+                    let Some(cost) = consumed_lot.price.filter(|cost| cost.currency.eq(&disposal_price.currency)) else {
+                        continue;
+                    };
+                    let gain = (&disposal_price.number).sub(&cost.number).mul(&consumed_lot.amount);
+                    operations.insert_realized_gain(RealizedGainDomain {
+                        datetime,
+                        trx_id: id,
+                        account: txn_posting.account_name(),
+                        commodity: amount.currency.clone(),
+                        amount: consumed_lot.amount,
+                        gain: Amount::new(gain, cost.currency.clone()),
+                        cost,
+                        price: disposal_price.clone(),
+                    })?;
+                }
+            }
         }
         for document in self.meta.clone().get_flatten().into_iter().filter(|(key, _)| key.eq("document")) {
             let (_, document_file_name) = document;
             let document_path = document_file_name.to_plain_string();
             let document_pathbuf = PathBuf::from(&document_path);
+            let hash = process::document::index_document(ledger, &document_path, &mut operations)?;
             operations.insert_document(
-                self.date.to_timezone_datetime(&ledger.options.timezone),
+                date.to_timezone_datetime(&ledger.options.timezone),
                 document_pathbuf.file_name().and_then(|it| it.to_str()),
-                document_path,
+                document_path.clone(),
                 DocumentType::Trx(id),
+                hash,
             )?;
         }
         operations.insert_meta(MetaType::TransactionMeta, id.to_string(), self.meta.clone())?;
         Ok(())
     }
 }
+
+/// the postings of voided transaction are kept for journal only, the balances of accounts are not changed by them.
+/// the posting whose amount cannot be inferred is skipped, since the voided transaction is not validated
+fn insert_voided_postings(trx: &Transaction, ledger: &mut Ledger, id: &Uuid, date: &Date) -> ZhangResult<()> {
+    let mut operations = ledger.operations();
+    for (posting_idx, txn_posting) in trx.txn_postings().into_iter().enumerate() {
+        let Some(inferred_amount) = txn_posting.infer_trade_amount().ok().or_else(|| txn_posting.units()) else {
+            continue;
+        };
+        let datetime = posting_date(date, txn_posting.posting).to_timezone_datetime(&ledger.options.timezone);
+        let balance = operations
+            .account_target_day_balance(txn_posting.posting.account.name(), datetime, &inferred_amount.currency)?
+            .map(|it| it.number)
+            .unwrap_or_else(BigDecimal::zero);
+        let (reconcile_state, statement_date) = posting_reconcile_state(trx, txn_posting.posting);
+        operations.insert_transaction_posting(
+            id,
+            posting_idx,
+            datetime,
+            txn_posting.posting.account.name(),
+            txn_posting.posting.units.clone(),
+            txn_posting.posting.cost.clone(),
+            inferred_amount.clone(),
+            Amount::new(balance.clone(), inferred_amount.currency.clone()),
+            Amount::new(balance, inferred_amount.currency),
+            reconcile_state,
+            statement_date,
+        )?;
+    }
+    operations.insert_meta(MetaType::TransactionMeta, id.to_string(), trx.meta.clone())?;
+    Ok(())
+}
+
+/// the date of posting given by its `date` meta, e.g. the value date of bank, or the date of transaction if the meta is absent or invalid
+fn posting_date(trx_date: &Date, posting: &Posting) -> Date {
+    posting
+        .meta
+        .get_one(POSTING_DATE)
+        .and_then(|it| NaiveDate::parse_from_str(it.as_str().trim(), "%Y-%m-%d").ok())
+        .map(Date::Date)
+        .unwrap_or_else(|| trx_date.clone())
+}
+
+/// the reconciliation state of posting and the date of statement which it is reconciled against.
+/// the posting with `statement-date` meta is reconciled if the `reconcile` meta is absent
+fn posting_reconcile_state(trx: &Transaction, posting: &Posting) -> (ReconcileState, Option<NaiveDate>) {
+    let statement_date = posting
+        .meta
+        .get_one(POSTING_STATEMENT_DATE)
+        .and_then(|it| NaiveDate::parse_from_str(it.as_str().trim(), "%Y-%m-%d").ok());
+    let state = posting
+        .meta
+        .get_one(POSTING_RECONCILE)
+        .and_then(|it| ReconcileState::from_str(&it.as_str().trim().to_lowercase()).ok());
+    let state = match (state, &statement_date) {
+        (Some(state), _) => state,
+        (None, Some(_)) => ReconcileState::Reconciled,
+        (None, None) => match posting.flag.as_ref().or(trx.flag.as_ref()) {
+            Some(Flag::Warning) => ReconcileState::Pending,
+            _ => ReconcileState::Cleared,
+        },
+    };
+    (state, statement_date)
+}
+
+/// split the gross amount of posting into net amount and tax by the `tax-rate` meta, e.g. `13%` or `0.13`, and post the tax into `tax-account`.
+/// the metas are resolved from the posting, then the transaction whose metas only apply to income and expenses postings,
+/// and then the open directive of account. the generated tax posting is flagged by meta `auto-tax`
+fn split_tax_postings(trx: &mut Transaction, ledger: &mut Ledger, span: &SpanInfo) -> ZhangResult<()> {
+    if trx.flag == Some(Flag::BalanceCheck) {
+        return Ok(());
+    }
+    let mut operations = ledger.operations();
+    let mut tax_postings = vec![];
+    for posting in trx.postings.iter_mut() {
+        // only the explicit amount without cost or price is regarded as gross amount
+        let Some(gross) = posting.units.clone() else {
+            continue;
+        };
+        if posting.cost.is_some() || posting.price.is_some() {
+            continue;
+        }
+        let trx_meta = matches!(posting.account.account_type, AccountType::Income | AccountType::Expenses).then_some(&trx.meta);
+        let Some(rate) = tax_meta(posting, trx_meta, &operations, TAX_RATE)?.and_then(|it| parse_tax_rate(&it)) else {
+            continue;
+        };
+        if rate.is_zero() {
+            continue;
+        }
+        let Some(tax_account) = tax_meta(posting, trx_meta, &operations, TAX_ACCOUNT)?.and_then(|it| Account::from_str(&it).ok()) else {
+            let mut metas = HashMap::of2("account_name", posting.account.name(), "tax_rate", rate.to_string());
+            metas.insert(TXN_ID.to_owned(), Uuid::from_span(span).to_string());
+            operations.new_error(ErrorKind::TaxAccountMissing, span, metas)?;
+            continue;
+        };
+
+        let precision = operations
+            .commodity(&gross.currency)?
+            .map(|it| it.precision)
+            .unwrap_or(DEFAULT_COMMODITY_PRECISION);
+        let net = (&gross.number)
+            .div(BigDecimal::one().add(&rate))
+            .with_scale_round(precision as i64, RoundingMode::HalfUp);
+        let tax = (&gross.number).sub(&net);
+        posting.units = Some(Amount::new(net, gross.currency.clone()));
+
+        let mut meta = Meta::default();
+        meta.insert(POSTING_AUTO_TAX.to_owned(), ZhangString::quote(posting.account.name()));
+        if let Some(date) = posting.meta.get_one(POSTING_DATE) {
+            meta.insert(POSTING_DATE.to_owned(), date.clone());
+        }
+        tax_postings.push(Posting {
+            flag: None,
+            posting_type: PostingType::Real,
+            account: tax_account,
+            units: Some(Amount::new(tax, gross.currency)),
+            cost: None,
+            cost_date: None,
+            cost_label: None,
+            price: None,
+            comment: None,
+            meta,
+        });
+    }
+    trx.postings.extend(tax_postings);
+    Ok(())
+}
+
+/// the tax meta of posting, falling back to the given transaction meta and the meta of account
+fn tax_meta(posting: &Posting, trx_meta: Option<&Meta>, operations: &Operations, key: &str) -> ZhangResult<Option<String>> {
+    if let Some(value) = posting.meta.get_one(key).or_else(|| trx_meta.and_then(|meta| meta.get_one(key))) {
+        return Ok(Some(value.as_str().trim().to_owned()));
+    }
+    Ok(operations
+        .meta(MetaType::AccountMeta, posting.account.name(), key)?
+        .map(|it| it.value.trim().to_owned()))
+}
+
+/// the tax rate in percentage like `13%`, or in fraction like `0.13`
+fn parse_tax_rate(rate: &str) -> Option<BigDecimal> {
+    match rate.strip_suffix('%') {
+        Some(percentage) => BigDecimal::from_str(percentage.trim()).ok().map(|it| it.div(BigDecimal::from(100))),
+        None => BigDecimal::from_str(rate).ok(),
+    }
+}
+
+/// post the tiny residual of each commodity into the account of option `rounding_account`.
+/// the residual is tiny if it is less than one unit of the smallest decimal place of commodity's precision,
+/// the generated posting is flagged by meta `auto-rounding: true`
+fn add_rounding_postings(trx: &mut Transaction, ledger: &mut Ledger) -> ZhangResult<()> {
+    if trx.flag == Some(Flag::BalanceCheck) {
+        return Ok(());
+    }
+    let operations = ledger.operations();
+    let Some(rounding_account) = operations.option::<Account>(KEY_ROUNDING_ACCOUNT)? else {
+        return Ok(());
+    };
+    // the transaction which cannot be inferred is reported by transaction check
+    let Ok(inventory) = trx.get_postings_inventory() else {
+        return Ok(());
+    };
+    for (currency, amount) in inventory.currencies.iter().sorted_by_key(|(currency, _)| *currency) {
+        if amount.total.is_zero() {
+            continue;
+        }
+        let Some(commodity) = operations.commodity(currency)? else {
+            continue;
+        };
+        let smallest_unit = BigDecimal::new(1.into(), commodity.precision as i64);
+        if amount.total.abs() >= smallest_unit {
+            continue;
+        }
+        let mut meta = Meta::default();
+        meta.insert(POSTING_AUTO_ROUNDING.to_owned(), ZhangString::quote(TRUE));
+        trx.postings.push(Posting {
+            flag: None,
+            posting_type: PostingType::Real,
+            account: rounding_account.clone(),
+            units: Some(Amount::new(-&amount.total, currency.clone())),
+            cost: None,
+            cost_date: None,
+            cost_label: None,
+            price: None,
+            comment: None,
+            meta,
+        });
+    }
+    Ok(())
+}
+
+/// validate postings against the constraints declared by the metas of account's open directive:
+/// - `allowed-commodities`: comma separated commodities which are allowed to be posted into the account
+/// - `max-posting-amount`: the max absolute amount of single posting, e.g. `1000` or `1000 USD` which only limits the postings of `USD`
+/// - `require-tag`: comma separated tags which the transaction must have to post into the account
+fn check_posting_constraints(trx: &Transaction, ledger: &mut Ledger, span: &SpanInfo) -> ZhangResult<()> {
+    let mut operations = ledger.operations();
+    let txn_id = Uuid::from_span(span).to_string();
+    for txn_posting in trx.txn_postings() {
+        let account_name = txn_posting.posting.account.name();
+        // the posting which cannot be inferred is already reported by transaction check
+        let Ok(amount) = txn_posting.infer_trade_amount() else {
+            continue;
+        };
+
+        if let Some(allowed_commodities) = operations.meta(MetaType::AccountMeta, account_name, ACCOUNT_ALLOWED_COMMODITIES)? {
+            let allowed = allowed_commodities.value.split(',').map(|it| it.trim()).any(|it| it.eq(&amount.currency));
+            if !allowed {
+                let mut metas = HashMap::of3(
+                    "account_name",
+                    account_name,
+                    "commodity_name",
+                    amount.currency.as_str(),
+                    "allowed_commodities",
+                    allowed_commodities.value.as_str(),
+                );
+                metas.insert(TXN_ID.to_owned(), txn_id.clone());
+                operations.new_error(ErrorKind::CommodityNotAllowedInAccount, span, metas)?;
+            }
+        }
+
+        if let Some(max_posting_amount) = operations.meta(MetaType::AccountMeta, account_name, ACCOUNT_MAX_POSTING_AMOUNT)? {
+            let mut limit = max_posting_amount.value.split_whitespace();
+            let limit_number = limit.next().and_then(|it| BigDecimal::from_str(it).ok());
+            let limit_currency = limit.next();
+            if let Some(limit_number) = limit_number {
+                let applicable = limit_currency.map(|it| it.eq(&amount.currency)).unwrap_or(true);
+                if applicable && amount.number.abs().gt(&limit_number) {
+                    let mut metas = HashMap::of3(
+                        "account_name",
+                        account_name,
+                        "amount",
+                        format!("{} {}", amount.number, amount.currency),
+                        "max_posting_amount",
+                        max_posting_amount.value.as_str(),
+                    );
+                    metas.insert(TXN_ID.to_owned(), txn_id.clone());
+                    operations.new_error(ErrorKind::PostingAmountExceedsLimit, span, metas)?;
+                }
+            }
+        }
+
+        if let Some(require_tag) = operations.meta(MetaType::AccountMeta, account_name, ACCOUNT_REQUIRE_TAG)? {
+            for tag in require_tag.value.split(',').map(|it| it.trim()).filter(|it| !it.is_empty()) {
+                if !trx.tags.contains(tag) {
+                    let mut metas = HashMap::of2("account_name", account_name, "tag", tag);
+                    metas.insert(TXN_ID.to_owned(), txn_id.clone());
+                    operations.new_error(ErrorKind::TransactionMissingRequiredTag, span, metas)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,201 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::ops::AddAssign;
+
+use bigdecimal::{BigDecimal, Zero};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use zhang_ast::amount::Amount;
+use zhang_ast::Currency;
+
+use crate::domains::Operations;
+use crate::query::{Aggregation, Column, Condition, Expr, Operator, Query, QueryResult, Value};
+use crate::store::{PostingDomain, TransactionDomain};
+use crate::{ZhangError, ZhangResult};
+
+/// the posting joined with its transaction
+struct Row<'a> {
+    trx: &'a TransactionDomain,
+    posting: &'a PostingDomain,
+}
+
+impl<'a> Row<'a> {
+    fn value(&self, column: Column) -> Value {
+        let amount = self.posting.unit.as_ref().unwrap_or(&self.posting.inferred_amount);
+        match column {
+            Column::Date => Value::Date(self.trx.datetime.naive_local().date()),
+            Column::Flag => Value::String(self.trx.flag.to_string()),
+            Column::Payee => self.trx.payee.clone().map(Value::String).unwrap_or(Value::Null),
+            Column::Narration => self.trx.narration.clone().map(Value::String).unwrap_or(Value::Null),
+            Column::Account => Value::String(self.posting.account.name().to_owned()),
+            Column::Number => Value::Number(amount.number.clone()),
+            Column::Commodity => Value::String(amount.currency.clone()),
+            Column::Amount => Value::Amounts(vec![amount.clone()]),
+            Column::Tags => Value::String(self.trx.tags.join(",")),
+            Column::Links => Value::String(self.trx.links.join(",")),
+        }
+    }
+
+    fn matches(&self, condition: &Condition) -> bool {
+        match condition {
+            Condition::Compare(column, operator, value) => {
+                let ordering = self.value(*column).compare(value);
+                match operator {
+                    Operator::Eq => ordering == Some(Ordering::Equal),
+                    Operator::NotEq => ordering != Some(Ordering::Equal),
+                    Operator::Gt => ordering == Some(Ordering::Greater),
+                    Operator::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+                    Operator::Lt => ordering == Some(Ordering::Less),
+                    Operator::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                }
+            }
+            Condition::Match(column, regex) => regex.is_match(&self.value(*column).to_string()),
+            Condition::Not(condition) => !self.matches(condition),
+            Condition::And(conditions) => conditions.iter().all(|condition| self.matches(condition)),
+            Condition::Or(conditions) => conditions.iter().any(|condition| self.matches(condition)),
+        }
+    }
+}
+
+fn aggregate(aggregation: Aggregation, column: Option<Column>, rows: &[Row]) -> ZhangResult<Value> {
+    let values = || {
+        rows.iter()
+            .filter_map(|row| column.map(|column| row.value(column)))
+            .filter(|value| value.ne(&Value::Null))
+    };
+    Ok(match aggregation {
+        Aggregation::Count => match column {
+            Some(_) => Value::Number(BigDecimal::from(values().count() as i64)),
+            None => Value::Number(BigDecimal::from(rows.len() as i64)),
+        },
+        Aggregation::Sum => match column {
+            Some(Column::Number) => Value::Number(
+                values()
+                    .filter_map(|value| if let Value::Number(number) = value { Some(number) } else { None })
+                    .fold(BigDecimal::zero(), |total, number| total + number),
+            ),
+            Some(Column::Amount) => {
+                let mut total: BTreeMap<Currency, BigDecimal> = BTreeMap::new();
+                for value in values() {
+                    if let Value::Amounts(amounts) = value {
+                        for amount in amounts {
+                            total.entry(amount.currency).or_insert_with(BigDecimal::zero).add_assign(amount.number);
+                        }
+                    }
+                }
+                Value::Amounts(total.into_iter().map(|(currency, number)| Amount::new(number, currency)).collect_vec())
+            }
+            _ => {
+                let column = column.map(|it| it.as_ref().to_owned()).unwrap_or_default();
+                return Err(ZhangError::QueryError(format!("cannot sum column {}", column)));
+            }
+        },
+        Aggregation::Min => values()
+            .reduce(|min, value| if value.compare(&min) == Some(Ordering::Less) { value } else { min })
+            .unwrap_or(Value::Null),
+        Aggregation::Max => values()
+            .reduce(|max, value| if value.compare(&max) == Some(Ordering::Greater) { value } else { max })
+            .unwrap_or(Value::Null),
+        Aggregation::First => values().next().unwrap_or(Value::Null),
+        Aggregation::Last => values().next_back().unwrap_or(Value::Null),
+    })
+}
+
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Less,
+        (_, Value::Null) => Ordering::Greater,
+        _ => a.compare(b).unwrap_or_else(|| a.to_string().cmp(&b.to_string())),
+    }
+}
+
+pub(crate) fn execute(operations: &Operations, query: &Query) -> ZhangResult<QueryResult> {
+    let store = operations.read();
+    let rows = store
+        .postings
+        .iter()
+        .filter_map(|posting| store.transactions.get(&posting.trx_id).map(|trx| Row { trx, posting }))
+        .filter(|row| query.condition.as_ref().map(|condition| row.matches(condition)).unwrap_or(true))
+        .sorted_by_key(|row| (row.posting.datetime, row.posting.trx_sequence))
+        .collect_vec();
+
+    let grouping = !query.group_by.is_empty() || query.targets.iter().any(|target| matches!(target.expr, Expr::Aggregate(..)));
+    let mut result_rows = if grouping {
+        for target in &query.targets {
+            if let Expr::Column(column) = target.expr {
+                if !query.group_by.contains(&column) {
+                    return Err(ZhangError::QueryError(format!(
+                        "column {} must appear in GROUP BY or be used in an aggregate function",
+                        column.as_ref()
+                    )));
+                }
+            }
+        }
+        let mut groups: IndexMap<Vec<String>, Vec<Row>> = IndexMap::new();
+        for row in rows {
+            let key = query.group_by.iter().map(|column| row.value(*column).to_string()).collect_vec();
+            groups.entry(key).or_default().push(row);
+        }
+        if groups.is_empty() && query.group_by.is_empty() {
+            groups.insert(vec![], vec![]);
+        }
+        groups
+            .values()
+            .map(|rows| {
+                query
+                    .targets
+                    .iter()
+                    .map(|target| match target.expr {
+                        Expr::Column(column) => Ok(rows[0].value(column)),
+                        Expr::Aggregate(aggregation, column) => aggregate(aggregation, column, rows),
+                    })
+                    .collect::<ZhangResult<Vec<_>>>()
+            })
+            .collect::<ZhangResult<Vec<_>>>()?
+    } else {
+        rows.iter()
+            .map(|row| {
+                query
+                    .targets
+                    .iter()
+                    .map(|target| match target.expr {
+                        Expr::Column(column) => row.value(column),
+                        Expr::Aggregate(..) => unreachable!("aggregation is handled in grouping"),
+                    })
+                    .collect_vec()
+            })
+            .collect_vec()
+    };
+
+    let columns = query.targets.iter().map(|target| target.name()).collect_vec();
+    let orders = query
+        .order_by
+        .iter()
+        .map(|order| {
+            columns
+                .iter()
+                .position(|column| column.eq_ignore_ascii_case(&order.name))
+                .map(|index| (index, order.descending))
+                .ok_or_else(|| ZhangError::QueryError(format!("cannot order by {} which is not selected", order.name)))
+        })
+        .collect::<ZhangResult<Vec<_>>>()?;
+    result_rows.sort_by(|a, b| {
+        orders
+            .iter()
+            .map(|(index, descending)| {
+                let ordering = compare_values(&a[*index], &b[*index]);
+                if *descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .find(|ordering| ordering.ne(&Ordering::Equal))
+            .unwrap_or(Ordering::Equal)
+    });
+    if let Some(limit) = query.limit {
+        result_rows.truncate(limit);
+    }
+    Ok(QueryResult { columns, rows: result_rows })
+}
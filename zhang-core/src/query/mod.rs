@@ -0,0 +1,261 @@
+//! query is a small BQL-like query language over the postings of processed ledger, e.g.
+//! ```sql
+//! SELECT account, sum(amount) WHERE account ~ "Expenses:" AND date >= 2024-01-01 GROUP BY account ORDER BY account
+//! ```
+//! each row is a posting joined with its transaction, the available columns are
+//! `date`, `flag`, `payee`, `narration`, `account`, `number`, `commodity`, `amount`, `tags` and `links`.
+//! the aggregations `sum`, `count`, `min`, `max`, `first` and `last` group the rows by the columns of `GROUP BY`,
+//! or into a single group if `GROUP BY` is absent.
+//!
+//! `~` matches the column by regex, other comparison operators are `=`, `!=`, `>`, `>=`, `<` and `<=`.
+
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use itertools::Itertools;
+use regex::Regex;
+use serde::Serialize;
+use strum::{AsRefStr, EnumIter, EnumString};
+use zhang_ast::amount::Amount;
+
+use crate::domains::Operations;
+use crate::ZhangResult;
+
+mod executor;
+mod parser;
+
+pub use parser::parse_query;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr, EnumString, EnumIter)]
+#[strum(serialize_all = "snake_case", ascii_case_insensitive)]
+pub enum Column {
+    Date,
+    Flag,
+    Payee,
+    Narration,
+    Account,
+    Number,
+    Commodity,
+    Amount,
+    Tags,
+    Links,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr, EnumString, EnumIter)]
+#[strum(serialize_all = "snake_case", ascii_case_insensitive)]
+pub enum Aggregation {
+    Sum,
+    Count,
+    Min,
+    Max,
+    First,
+    Last,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Column(Column),
+    /// the aggregation of column, `None` means the wildcard `*` which is only valid for `count`
+    Aggregate(Aggregation, Option<Column>),
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::Column(column) => write!(f, "{}", column.as_ref()),
+            Expr::Aggregate(aggregation, Some(column)) => write!(f, "{}({})", aggregation.as_ref(), column.as_ref()),
+            Expr::Aggregate(aggregation, None) => write!(f, "{}(*)", aggregation.as_ref()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub expr: Expr,
+    pub alias: Option<String>,
+}
+
+impl Target {
+    /// the name of result column, which is the alias or the expression itself
+    pub fn name(&self) -> String {
+        self.alias.clone().unwrap_or_else(|| self.expr.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Eq,
+    NotEq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+#[derive(Debug, Clone)]
+pub enum Condition {
+    Compare(Column, Operator, Value),
+    Match(Column, Regex),
+    Not(Box<Condition>),
+    And(Vec<Condition>),
+    Or(Vec<Condition>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Order {
+    /// the name of selected column, which is the alias or the expression of target and matched case-insensitively,
+    /// the columns of posting not selected cannot be ordered by
+    pub name: String,
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Query {
+    pub targets: Vec<Target>,
+    pub condition: Option<Condition>,
+    pub group_by: Vec<Column>,
+    pub order_by: Vec<Order>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum Value {
+    Null,
+    String(String),
+    Number(BigDecimal),
+    Date(NaiveDate),
+    /// amounts in different commodities, e.g. the sum of amounts
+    Amounts(Vec<Amount>),
+}
+
+impl Value {
+    /// compare values in the same type, the amounts are compared by the number if they have single amount
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (Value::Number(a), Value::Number(b)) => Some(a.cmp(b)),
+            (Value::Date(a), Value::Date(b)) => Some(a.cmp(b)),
+            (Value::Amounts(a), Value::Number(b)) if a.len() == 1 => Some(a[0].number.cmp(b)),
+            (Value::Amounts(a), Value::Amounts(b)) if a.len() == 1 && b.len() == 1 && a[0].currency.eq(&b[0].currency) => Some(a[0].number.cmp(&b[0].number)),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Null => Ok(()),
+            Value::String(value) => write!(f, "{}", value),
+            Value::Number(value) => write!(f, "{}", value),
+            Value::Date(value) => write!(f, "{}", value),
+            Value::Amounts(amounts) => write!(f, "{}", amounts.iter().map(|it| format!("{} {}", it.number, it.currency)).join(", ")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    /// the cells of each row, which are null, string, number, date or the list of amounts
+    pub rows: Vec<Vec<Value>>,
+}
+
+/// parse and execute the query against the store
+pub fn execute(operations: &Operations, query: &str) -> ZhangResult<QueryResult> {
+    let query = parse_query(query)?;
+    executor::execute(operations, &query)
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use indoc::indoc;
+    use zhang_ast::amount::Amount;
+
+    use crate::query::{execute, Value};
+    use crate::test::load_from_text;
+
+    const LEDGER: &str = indoc! {r#"
+        1970-01-01 commodity CNY
+        1970-01-01 commodity USD
+        1970-01-01 open Assets:Bank
+        1970-01-01 open Expenses:Food
+        1970-01-01 open Expenses:Travel
+
+        2023-12-10 "KFC" "Lunch"
+          Assets:Bank -20 CNY
+          Expenses:Food
+
+        2024-01-10 "KFC" "Dinner" #eat
+          Assets:Bank -50 CNY
+          Expenses:Food
+
+        2024-01-11 "Airline" "Ticket"
+          Assets:Bank -300 USD
+          Expenses:Travel
+
+        2024-01-12 "Subway" "Lunch" #eat
+          Assets:Bank -30 CNY
+          Expenses:Food
+    "#};
+
+    #[test]
+    fn should_sum_amount_group_by_account() {
+        let ledger = load_from_text(LEDGER);
+        let operations = ledger.operations();
+        let result = execute(
+            &operations,
+            r#"SELECT account, sum(amount) AS total, count(*) AS count WHERE account ~ "^Expenses:" AND date >= 2024-01-05 GROUP BY account ORDER BY account"#,
+        )
+        .unwrap();
+
+        assert_eq!(vec!["account", "total", "count"], result.columns);
+        assert_eq!(
+            vec![
+                vec![
+                    Value::String("Expenses:Food".to_owned()),
+                    Value::Amounts(vec![Amount::new(BigDecimal::from(80), "CNY")]),
+                    Value::Number(BigDecimal::from(2)),
+                ],
+                vec![
+                    Value::String("Expenses:Travel".to_owned()),
+                    Value::Amounts(vec![Amount::new(BigDecimal::from(300), "USD")]),
+                    Value::Number(BigDecimal::from(1)),
+                ],
+            ],
+            result.rows
+        );
+    }
+
+    #[test]
+    fn should_filter_order_and_limit_postings() {
+        let ledger = load_from_text(LEDGER);
+        let operations = ledger.operations();
+        let result = execute(
+            &operations,
+            r#"SELECT payee, number WHERE account = "Assets:Bank" AND (tags ~ "eat" OR NOT commodity = "CNY") ORDER BY number LIMIT 2"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                vec![Value::String("Airline".to_owned()), Value::Number(BigDecimal::from(-300))],
+                vec![Value::String("KFC".to_owned()), Value::Number(BigDecimal::from(-50))],
+            ],
+            result.rows
+        );
+    }
+
+    #[test]
+    fn should_reject_column_not_in_group_by() {
+        let ledger = load_from_text(LEDGER);
+        let operations = ledger.operations();
+        assert!(execute(&operations, "SELECT payee, sum(number) GROUP BY account").is_err());
+        assert!(execute(&operations, "SELECT payee ORDER BY account").is_err());
+    }
+}
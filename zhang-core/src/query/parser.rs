@@ -0,0 +1,278 @@
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use pest_consume::{match_nodes, Error, Parser};
+use regex::Regex;
+
+use crate::query::{Aggregation, Column, Condition, Expr, Operator, Order, Query, Target, Value};
+use crate::{ZhangError, ZhangResult};
+
+type Result<T> = std::result::Result<T, Error<Rule>>;
+type Node<'i> = pest_consume::Node<'i, Rule, ()>;
+
+#[derive(Parser)]
+#[grammar = "query/query.pest"]
+struct QueryParser;
+
+enum Comparator {
+    Match,
+    Operator(Operator),
+}
+
+#[pest_consume::parser]
+impl QueryParser {
+    #[allow(dead_code)]
+    fn EOI(_input: Node) -> Result<()> {
+        Ok(())
+    }
+
+    fn identifier(input: Node) -> Result<String> {
+        Ok(input.as_str().to_owned())
+    }
+
+    fn date(input: Node) -> Result<Value> {
+        let date = NaiveDate::parse_from_str(input.as_str(), "%Y-%m-%d").map_err(|e| input.error(format!("invalid date: {}", e)))?;
+        Ok(Value::Date(date))
+    }
+
+    fn number(input: Node) -> Result<Value> {
+        let number = BigDecimal::from_str(input.as_str()).map_err(|e| input.error(format!("invalid number: {}", e)))?;
+        Ok(Value::Number(number))
+    }
+
+    fn string_inner(input: Node) -> Result<String> {
+        Ok(input.as_str().to_owned())
+    }
+
+    fn string(input: Node) -> Result<Value> {
+        Ok(match_nodes!(input.into_children();
+            [string_inner(value)] => Value::String(value),
+        ))
+    }
+
+    fn literal(input: Node) -> Result<Value> {
+        Ok(match_nodes!(input.into_children();
+            [date(value)] => value,
+            [number(value)] => value,
+            [string(value)] => value,
+        ))
+    }
+
+    fn wildcard(_input: Node) -> Result<()> {
+        Ok(())
+    }
+
+    fn function(input: Node) -> Result<Expr> {
+        let span = input.clone();
+        let (name, column) = match_nodes!(input.into_children();
+            [identifier(name), wildcard(_)] => (name, None),
+            [identifier(name), identifier(column)] => (name, Some(column)),
+        );
+        let aggregation = Aggregation::from_str(&name).map_err(|_| span.error(format!("unknown function: {}", name)))?;
+        let column = column.map(|column| parse_column(&span, &column)).transpose()?;
+        if column.is_none() && aggregation != Aggregation::Count {
+            return Err(span.error(format!("function {} requires a column", name)));
+        }
+        Ok(Expr::Aggregate(aggregation, column))
+    }
+
+    fn expr(input: Node) -> Result<Expr> {
+        let span = input.clone();
+        Ok(match_nodes!(input.into_children();
+            [function(function)] => function,
+            [identifier(column)] => Expr::Column(parse_column(&span, &column)?),
+        ))
+    }
+
+    fn target(input: Node) -> Result<Target> {
+        Ok(match_nodes!(input.into_children();
+            [expr(expr)] => Target { expr, alias: None },
+            [expr(expr), identifier(alias)] => Target { expr, alias: Some(alias) },
+        ))
+    }
+
+    fn operator(input: Node) -> Result<Comparator> {
+        Ok(match input.as_str() {
+            "~" => Comparator::Match,
+            "=" => Comparator::Operator(Operator::Eq),
+            "!=" => Comparator::Operator(Operator::NotEq),
+            ">" => Comparator::Operator(Operator::Gt),
+            ">=" => Comparator::Operator(Operator::Ge),
+            "<" => Comparator::Operator(Operator::Lt),
+            "<=" => Comparator::Operator(Operator::Le),
+            operator => unreachable!("unexpected operator {}", operator),
+        })
+    }
+
+    fn comparison(input: Node) -> Result<Condition> {
+        let span = input.clone();
+        let (column, comparator, value) = match_nodes!(input.into_children();
+            [identifier(column), operator(comparator), literal(value)] => (column, comparator, value),
+        );
+        let column = parse_column(&span, &column)?;
+        match comparator {
+            Comparator::Match => {
+                let Value::String(pattern) = value else {
+                    return Err(span.error("operator ~ requires a string pattern"));
+                };
+                let regex = Regex::new(&pattern).map_err(|e| span.error(format!("invalid regex: {}", e)))?;
+                Ok(Condition::Match(column, regex))
+            }
+            Comparator::Operator(operator) => Ok(Condition::Compare(column, operator, value)),
+        }
+    }
+
+    fn not_condition(input: Node) -> Result<Condition> {
+        Ok(match_nodes!(input.into_children();
+            [unary_condition(condition)] => Condition::Not(Box::new(condition)),
+        ))
+    }
+
+    fn unary_condition(input: Node) -> Result<Condition> {
+        Ok(match_nodes!(input.into_children();
+            [not_condition(condition)] => condition,
+            [condition(condition)] => condition,
+            [comparison(condition)] => condition,
+        ))
+    }
+
+    fn and_condition(input: Node) -> Result<Condition> {
+        let mut conditions: Vec<Condition> = match_nodes!(input.into_children();
+            [unary_condition(conditions)..] => conditions.collect(),
+        );
+        Ok(if conditions.len() == 1 {
+            conditions.remove(0)
+        } else {
+            Condition::And(conditions)
+        })
+    }
+
+    fn condition(input: Node) -> Result<Condition> {
+        let mut conditions: Vec<Condition> = match_nodes!(input.into_children();
+            [and_condition(conditions)..] => conditions.collect(),
+        );
+        Ok(if conditions.len() == 1 {
+            conditions.remove(0)
+        } else {
+            Condition::Or(conditions)
+        })
+    }
+
+    fn asc(_input: Node) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn desc(_input: Node) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn order(input: Node) -> Result<Order> {
+        Ok(match_nodes!(input.into_children();
+            [identifier(name)] => Order { name, descending: false },
+            [identifier(name), asc(descending)] => Order { name, descending },
+            [identifier(name), desc(descending)] => Order { name, descending },
+        ))
+    }
+
+    fn limit(input: Node) -> Result<usize> {
+        input.as_str().parse::<usize>().map_err(|e| input.error(format!("invalid limit: {}", e)))
+    }
+
+    fn select_clause(input: Node) -> Result<Vec<Target>> {
+        Ok(match_nodes!(input.into_children();
+            [target(targets)..] => targets.collect(),
+        ))
+    }
+
+    fn where_clause(input: Node) -> Result<Condition> {
+        Ok(match_nodes!(input.into_children();
+            [condition(condition)] => condition,
+        ))
+    }
+
+    fn group_by_clause(input: Node) -> Result<Vec<Column>> {
+        let span = input.clone();
+        let columns: Vec<String> = match_nodes!(input.into_children();
+            [identifier(columns)..] => columns.collect(),
+        );
+        columns.iter().map(|column| parse_column(&span, column)).collect()
+    }
+
+    fn order_by_clause(input: Node) -> Result<Vec<Order>> {
+        Ok(match_nodes!(input.into_children();
+            [order(orders)..] => orders.collect(),
+        ))
+    }
+
+    fn limit_clause(input: Node) -> Result<usize> {
+        Ok(match_nodes!(input.into_children();
+            [limit(limit)] => limit,
+        ))
+    }
+
+    fn query(input: Node) -> Result<Query> {
+        let mut query = Query {
+            targets: vec![],
+            condition: None,
+            group_by: vec![],
+            order_by: vec![],
+            limit: None,
+        };
+        for child in input.into_children() {
+            match child.as_rule() {
+                Rule::select_clause => query.targets = QueryParser::select_clause(child)?,
+                Rule::where_clause => query.condition = Some(QueryParser::where_clause(child)?),
+                Rule::group_by_clause => query.group_by = QueryParser::group_by_clause(child)?,
+                Rule::order_by_clause => query.order_by = QueryParser::order_by_clause(child)?,
+                Rule::limit_clause => query.limit = Some(QueryParser::limit_clause(child)?),
+                _ => {}
+            }
+        }
+        Ok(query)
+    }
+}
+
+fn parse_column(node: &Node, column: &str) -> Result<Column> {
+    Column::from_str(column).map_err(|_| node.error(format!("unknown column: {}", column)))
+}
+
+/// parse the query text into [Query]
+pub fn parse_query(query: &str) -> ZhangResult<Query> {
+    let parse = || -> Result<Query> {
+        let inputs = QueryParser::parse(Rule::query, query)?;
+        QueryParser::query(inputs.single()?)
+    };
+    parse().map_err(|e| ZhangError::QueryError(e.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::query::{parse_query, Aggregation, Column, Condition, Expr, Operator, Value};
+
+    #[test]
+    fn should_parse_query() {
+        let query = parse_query(
+            r#"select account, sum(amount) as total where account ~ "Expenses:" and date >= 2024-01-01 group by account order by total desc limit 10"#,
+        )
+        .unwrap();
+        assert_eq!(Expr::Column(Column::Account), query.targets[0].expr);
+        assert_eq!(Expr::Aggregate(Aggregation::Sum, Some(Column::Amount)), query.targets[1].expr);
+        assert_eq!("total", query.targets[1].name());
+        let Some(Condition::And(conditions)) = query.condition else {
+            panic!("condition should be and");
+        };
+        assert!(matches!(&conditions[0], Condition::Match(Column::Account, _)));
+        assert!(matches!(&conditions[1], Condition::Compare(Column::Date, Operator::Ge, Value::Date(_))));
+        assert_eq!(vec![Column::Account], query.group_by);
+        assert!(query.order_by[0].descending);
+        assert_eq!(Some(10), query.limit);
+    }
+
+    #[test]
+    fn should_reject_unknown_column() {
+        assert!(parse_query("SELECT balance").is_err());
+        assert!(parse_query("SELECT sum(*)").is_err());
+        assert!(parse_query("SELECT count(*) WHERE account ~ \"(\"").is_err());
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::AddAssign;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use log::warn;
+use serde::Serialize;
+use strum::{AsRefStr, EnumString};
+use zhang_ast::amount::Amount;
+use zhang_ast::Currency;
+
+use crate::constants::ACCOUNT_CASHFLOW_CATEGORY;
+use crate::domains::schemas::MetaType;
+use crate::domains::Operations;
+use crate::ZhangResult;
+
+/// the value of `cashflow-category` meta of account, `cash` marks the account whose changes are the cash flow,
+/// the others classify the cash flow against the account. accounts without the meta are treated as `operating`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, AsRefStr, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum CashFlowCategory {
+    Cash,
+    Operating,
+    Investing,
+    Financing,
+}
+
+/// cash flow of one activity, positive amount means cash inflow
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CashFlowSection {
+    pub amounts: Vec<Amount>,
+    /// the cash flow against each account of the activity
+    pub accounts: Vec<CashFlowAccount>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CashFlowAccount {
+    pub account: String,
+    pub amounts: Vec<Amount>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CashFlowStatement {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub operating: CashFlowSection,
+    pub investing: CashFlowSection,
+    pub financing: CashFlowSection,
+    /// the net change of cash accounts, which is the sum of all activities
+    pub net_change: Vec<Amount>,
+}
+
+fn account_category(operations: &Operations, account_name: &str) -> ZhangResult<CashFlowCategory> {
+    let Some(category) = operations.meta(MetaType::AccountMeta, account_name, ACCOUNT_CASHFLOW_CATEGORY)? else {
+        return Ok(CashFlowCategory::Operating);
+    };
+    Ok(CashFlowCategory::from_str(category.value.trim()).unwrap_or_else(|_| {
+        warn!(
+            "cashflow category '{}' of account {} is invalid, fallback to operating",
+            category.value, account_name
+        );
+        CashFlowCategory::Operating
+    }))
+}
+
+fn into_amounts(amounts: BTreeMap<Currency, BigDecimal>) -> Vec<Amount> {
+    amounts
+        .into_iter()
+        .filter(|(_, number)| !number.is_zero())
+        .map(|(currency, number)| Amount::new(number, currency))
+        .collect_vec()
+}
+
+/// cash flow statement of the transactions between `from` and `to`(both inclusive).
+/// for each transaction touching cash accounts, the cash flow is attributed to the activity of its other postings' accounts,
+/// the flow against a posting is the negated weight of the posting. transactions between cash accounts have no cash flow
+pub fn cash_flow(operations: &Operations, from: DateTime<Utc>, to: DateTime<Utc>) -> ZhangResult<CashFlowStatement> {
+    let postings = operations
+        .read()
+        .postings
+        .iter()
+        .filter(|posting| posting.datetime.ge(&from))
+        .filter(|posting| posting.datetime.le(&to))
+        .cloned()
+        .collect_vec();
+
+    let mut categories: HashMap<String, CashFlowCategory> = HashMap::new();
+    for posting in &postings {
+        let account_name = posting.account.name();
+        if !categories.contains_key(account_name) {
+            categories.insert(account_name.to_owned(), account_category(operations, account_name)?);
+        }
+    }
+
+    let mut activities: HashMap<CashFlowCategory, BTreeMap<String, BTreeMap<Currency, BigDecimal>>> = HashMap::new();
+    let mut net_change: BTreeMap<Currency, BigDecimal> = BTreeMap::new();
+    for trx_postings in postings.iter().into_group_map_by(|posting| posting.trx_id).into_values() {
+        let (cash_postings, other_postings): (Vec<_>, Vec<_>) = trx_postings
+            .into_iter()
+            .partition(|posting| categories.get(posting.account.name()) == Some(&CashFlowCategory::Cash));
+        if cash_postings.is_empty() {
+            continue;
+        }
+        for posting in cash_postings {
+            let amount = &posting.inferred_amount;
+            net_change
+                .entry(amount.currency.clone())
+                .or_insert_with(BigDecimal::zero)
+                .add_assign(&amount.number);
+        }
+        for posting in other_postings {
+            let category = categories.get(posting.account.name()).copied().unwrap_or(CashFlowCategory::Operating);
+            let amount = &posting.inferred_amount;
+            activities
+                .entry(category)
+                .or_default()
+                .entry(posting.account.name().to_owned())
+                .or_default()
+                .entry(amount.currency.clone())
+                .or_insert_with(BigDecimal::zero)
+                .add_assign(-&amount.number);
+        }
+    }
+
+    let mut section = |category: CashFlowCategory| {
+        let mut total: BTreeMap<Currency, BigDecimal> = BTreeMap::new();
+        let mut accounts = vec![];
+        for (account, amounts) in activities.remove(&category).unwrap_or_default() {
+            for (currency, number) in &amounts {
+                total.entry(currency.clone()).or_insert_with(BigDecimal::zero).add_assign(number);
+            }
+            accounts.push(CashFlowAccount {
+                account,
+                amounts: into_amounts(amounts),
+            });
+        }
+        CashFlowSection {
+            amounts: into_amounts(total),
+            accounts,
+        }
+    };
+    Ok(CashFlowStatement {
+        from,
+        to,
+        operating: section(CashFlowCategory::Operating),
+        investing: section(CashFlowCategory::Investing),
+        financing: section(CashFlowCategory::Financing),
+        net_change: into_amounts(net_change),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use chrono::{DateTime, NaiveDate, Utc};
+    use indoc::indoc;
+    use zhang_ast::amount::Amount;
+
+    use crate::reports::cash_flow;
+    use crate::test::load_from_text;
+
+    fn date(month: u32, day: u32) -> DateTime<Utc> {
+        NaiveDate::from_ymd_opt(2024, month, day).unwrap().and_hms_opt(23, 59, 59).unwrap().and_utc()
+    }
+
+    #[test]
+    fn should_classify_cash_flow_by_account_category() {
+        let ledger = load_from_text(indoc! {r#"
+            1970-01-01 commodity CNY
+            1970-01-01 open Assets:Bank
+              cashflow-category: "cash"
+            1970-01-01 open Assets:Cash
+              cashflow-category: "cash"
+            1970-01-01 open Assets:Stock
+              cashflow-category: "investing"
+            1970-01-01 open Liabilities:Loan
+              cashflow-category: "financing"
+            1970-01-01 open Income:Salary
+            1970-01-01 open Expenses:Food
+
+            2024-01-06 "Salary"
+              Assets:Bank 1000 CNY
+              Income:Salary
+
+            2024-01-02 "Dinner"
+              Assets:Cash -100 CNY
+              Expenses:Food
+
+            2024-01-03 "Buy stock"
+              Assets:Bank -500 CNY
+              Assets:Stock
+
+            2024-01-04 "Loan"
+              Assets:Bank 2000 CNY
+              Liabilities:Loan
+
+            2024-01-05 "Withdraw"
+              Assets:Bank -200 CNY
+              Assets:Cash
+
+            2024-02-01 "Salary"
+              Assets:Bank 1000 CNY
+              Income:Salary
+        "#});
+        let operations = ledger.operations();
+        let statement = cash_flow(&operations, date(1, 1), date(1, 31)).unwrap();
+
+        assert_eq!(vec![Amount::new(BigDecimal::from(900), "CNY")], statement.operating.amounts);
+        assert_eq!(2, statement.operating.accounts.len());
+        assert_eq!(vec![Amount::new(BigDecimal::from(-500), "CNY")], statement.investing.amounts);
+        assert_eq!(vec![Amount::new(BigDecimal::from(2000), "CNY")], statement.financing.amounts);
+        assert_eq!(vec![Amount::new(BigDecimal::from(2400), "CNY")], statement.net_change);
+    }
+}
@@ -0,0 +1,325 @@
+//! reports are the financial statements built from the postings of ledger, e.g. income statement, balance sheet and cash flow statement.
+//! accounts are aggregated into the tree of their account type, e.g. `Expenses:Food:Dinner` is counted into `Expenses:Food` and `Expenses`.
+//!
+//! the amounts of report are measured by posting weights and keep the sign of ledger, which means income and liabilities are negative.
+
+use std::collections::BTreeMap;
+use std::ops::AddAssign;
+use std::sync::Arc;
+
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use serde::Serialize;
+use zhang_ast::amount::Amount;
+use zhang_ast::{AccountType, Currency};
+
+use crate::domains::price_graph::PriceGraph;
+use crate::domains::{Conversion, Operations};
+use crate::store::PostingDomain;
+use crate::ZhangResult;
+
+mod cash_flow;
+
+pub use cash_flow::{cash_flow, CashFlowAccount, CashFlowCategory, CashFlowSection, CashFlowStatement};
+
+#[derive(Debug, Clone, Default)]
+pub struct ReportOptions {
+    /// max depth of account tree, the account type is depth `1`. deeper accounts are aggregated into their ancestor at the depth
+    pub depth: Option<usize>,
+    /// value the amounts in the currency, e.g. the operating currency.
+    /// commodities without exchange rate are skipped in the converted amount
+    pub convert: Option<Currency>,
+    /// only the accounts belonging to the entity by their `entity` meta are reported if it is given
+    pub entity: Option<String>,
+    /// report all entities as a whole, the postings into the accounts of elimination rules in effect are excluded.
+    /// it is ignored if entity is given
+    pub consolidated: bool,
+}
+
+/// the node of account tree, its amounts include the amounts of all sub-accounts
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportNode {
+    pub name: String,
+    pub amounts: Vec<Amount>,
+    pub converted: Option<Amount>,
+    pub children: Vec<ReportNode>,
+}
+
+impl ReportNode {
+    /// find the node of account in the tree
+    pub fn find(&self, account_name: &str) -> Option<&ReportNode> {
+        if self.name.eq(account_name) {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.find(account_name))
+    }
+
+    /// the amount of commodity, zero is returned if the commodity is absent
+    pub fn amount(&self, commodity: &str) -> BigDecimal {
+        self.amounts
+            .iter()
+            .find(|amount| amount.currency.eq(commodity))
+            .map(|amount| amount.number.clone())
+            .unwrap_or_else(BigDecimal::zero)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IncomeStatement {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub income: ReportNode,
+    pub expenses: ReportNode,
+    /// income minus expenses, positive means profit
+    pub net_income: ReportNode,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BalanceSheet {
+    pub date: DateTime<Utc>,
+    pub assets: ReportNode,
+    pub liabilities: ReportNode,
+    pub equity: ReportNode,
+    /// the accumulated income minus expenses which is not closed into equity yet, positive means profit
+    pub net_income: ReportNode,
+}
+
+#[derive(Debug, Default)]
+struct ReportTree {
+    amounts: BTreeMap<Currency, BigDecimal>,
+    children: BTreeMap<String, ReportTree>,
+}
+
+impl ReportTree {
+    fn add(&mut self, segments: &[&str], amount: &Amount) {
+        self.amounts
+            .entry(amount.currency.clone())
+            .or_insert_with(BigDecimal::zero)
+            .add_assign(&amount.number);
+        if let Some((first, rest)) = segments.split_first() {
+            self.children.entry((*first).to_owned()).or_default().add(rest, amount);
+        }
+    }
+
+    fn into_node(self, name: String, conversion: Option<&(Conversion, Arc<PriceGraph>)>) -> ReportNode {
+        let amounts = self.amounts.into_iter().map(|(currency, number)| Amount::new(number, currency)).collect_vec();
+        let converted = conversion.map(|(conversion, price_graph)| {
+            let total = amounts
+                .iter()
+                .filter_map(|amount| conversion.convert(price_graph, &amount.number, &amount.currency))
+                .fold(BigDecimal::zero(), |total, amount| total + amount.number);
+            Amount::new(total, conversion.currency.clone())
+        });
+        let children = self
+            .children
+            .into_iter()
+            .map(|(segment, child)| {
+                let child_name = format!("{}:{}", name, segment);
+                child.into_node(child_name, conversion)
+            })
+            .collect_vec();
+        ReportNode {
+            name,
+            amounts,
+            converted,
+            children,
+        }
+    }
+}
+
+/// build the account tree of each account type from postings
+fn build_trees<'a>(postings: impl Iterator<Item = &'a PostingDomain>, account_types: &[AccountType], options: &ReportOptions) -> BTreeMap<String, ReportTree> {
+    let mut trees: BTreeMap<String, ReportTree> = account_types.iter().map(|it| (it.to_string(), ReportTree::default())).collect();
+    for posting in postings {
+        let Some(tree) = trees.get_mut(&posting.account.account_type.to_string()) else {
+            continue;
+        };
+        let segments = posting
+            .account
+            .name()
+            .split(':')
+            .skip(1)
+            .take(options.depth.map(|depth| depth.saturating_sub(1)).unwrap_or(usize::MAX))
+            .collect_vec();
+        tree.add(&segments, &posting.inferred_amount);
+    }
+    trees
+}
+
+fn conversion(operations: &Operations, options: &ReportOptions, date: DateTime<Utc>) -> ZhangResult<Option<(Conversion, Arc<PriceGraph>)>> {
+    let Some(currency) = &options.convert else {
+        return Ok(None);
+    };
+    let date = date.naive_utc();
+    Ok(Some((Conversion::new(currency.clone(), date), operations.price_graph()?)))
+}
+
+/// the negated sum of income and expenses amounts
+fn net_income(amounts: impl IntoIterator<Item = (Currency, BigDecimal)>, conversion: Option<&(Conversion, Arc<PriceGraph>)>) -> ReportNode {
+    let mut net_income = ReportTree::default();
+    for (currency, number) in amounts {
+        net_income.add(&[], &Amount::new(-number, currency));
+    }
+    net_income.into_node("NetIncome".to_owned(), conversion)
+}
+
+/// income and expenses of the postings between `from` and `to`(both inclusive), converted amounts are valued by the prices at `to`
+pub fn income_statement(operations: &Operations, from: DateTime<Utc>, to: DateTime<Utc>, options: &ReportOptions) -> ZhangResult<IncomeStatement> {
+    let conversion = conversion(operations, options, to)?;
+    let scope = operations.posting_scope(options.entity.as_deref(), options.consolidated);
+    let store = operations.read();
+    let postings = store
+        .postings
+        .iter()
+        .filter(|posting| posting.datetime.ge(&from))
+        .filter(|posting| posting.datetime.le(&to))
+        .filter(|posting| scope(posting));
+    let mut trees = build_trees(postings, &[AccountType::Income, AccountType::Expenses], options);
+
+    let income = trees.remove(&AccountType::Income.to_string()).unwrap_or_default();
+    let expenses = trees.remove(&AccountType::Expenses.to_string()).unwrap_or_default();
+    let net_income_node = net_income(income.amounts.clone().into_iter().chain(expenses.amounts.clone()), conversion.as_ref());
+    Ok(IncomeStatement {
+        from,
+        to,
+        income: income.into_node(AccountType::Income.to_string(), conversion.as_ref()),
+        expenses: expenses.into_node(AccountType::Expenses.to_string(), conversion.as_ref()),
+        net_income: net_income_node,
+    })
+}
+
+/// assets, liabilities and equity at the given date, converted amounts are valued by the prices at the date
+pub fn balance_sheet(operations: &Operations, date: DateTime<Utc>, options: &ReportOptions) -> ZhangResult<BalanceSheet> {
+    let conversion = conversion(operations, options, date)?;
+    let scope = operations.posting_scope(options.entity.as_deref(), options.consolidated);
+    let store = operations.read();
+    let postings = store
+        .postings
+        .iter()
+        .filter(|posting| posting.datetime.le(&date))
+        .filter(|posting| scope(posting));
+    let mut trees = build_trees(
+        postings,
+        &[
+            AccountType::Assets,
+            AccountType::Liabilities,
+            AccountType::Equity,
+            AccountType::Income,
+            AccountType::Expenses,
+        ],
+        options,
+    );
+
+    let mut node = |account_type: AccountType| {
+        trees
+            .remove(&account_type.to_string())
+            .unwrap_or_default()
+            .into_node(account_type.to_string(), conversion.as_ref())
+    };
+    let assets = node(AccountType::Assets);
+    let liabilities = node(AccountType::Liabilities);
+    let equity = node(AccountType::Equity);
+    let net_income_node = net_income(trees.into_values().flat_map(|tree| tree.amounts), conversion.as_ref());
+    Ok(BalanceSheet {
+        date,
+        assets,
+        liabilities,
+        equity,
+        net_income: net_income_node,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::BigDecimal;
+    use chrono::{DateTime, NaiveDate, Utc};
+    use indoc::indoc;
+    use zhang_ast::amount::Amount;
+
+    use crate::reports::{balance_sheet, income_statement, ReportOptions};
+    use crate::test::load_from_text;
+
+    fn date(month: u32, day: u32) -> DateTime<Utc> {
+        NaiveDate::from_ymd_opt(2024, month, day).unwrap().and_hms_opt(23, 59, 59).unwrap().and_utc()
+    }
+
+    const LEDGER: &str = indoc! {r#"
+        1970-01-01 commodity CNY
+        1970-01-01 commodity USD
+        1970-01-01 open Assets:Bank:CNY
+        1970-01-01 open Assets:Bank:USD
+        1970-01-01 open Liabilities:CreditCard
+        1970-01-01 open Equity:Open
+        1970-01-01 open Income:Salary
+        1970-01-01 open Expenses:Food:Dinner
+        1970-01-01 open Expenses:Food:Lunch
+
+        2024-01-01 "Open"
+          Assets:Bank:USD 100 USD
+          Equity:Open
+
+        2024-01-01 price USD 7 CNY
+
+        2024-01-10 "Salary"
+          Assets:Bank:CNY 1000 CNY
+          Income:Salary
+
+        2024-01-11 "Dinner"
+          Liabilities:CreditCard -100 CNY
+          Expenses:Food:Dinner
+
+        2024-02-11 "Lunch"
+          Assets:Bank:CNY -50 CNY
+          Expenses:Food:Lunch
+    "#};
+
+    #[test]
+    fn should_aggregate_income_statement_by_account_tree() {
+        let ledger = load_from_text(LEDGER);
+        let operations = ledger.operations();
+        let statement = income_statement(&operations, date(1, 1), date(1, 31), &ReportOptions::default()).unwrap();
+
+        assert_eq!(BigDecimal::from(-1000), statement.income.amount("CNY"));
+        assert_eq!(BigDecimal::from(100), statement.expenses.amount("CNY"));
+        assert_eq!(BigDecimal::from(100), statement.expenses.find("Expenses:Food").unwrap().amount("CNY"));
+        assert!(
+            statement.expenses.find("Expenses:Food:Lunch").is_none(),
+            "posting out of range should be excluded"
+        );
+        assert_eq!(BigDecimal::from(900), statement.net_income.amount("CNY"));
+    }
+
+    #[test]
+    fn should_limit_depth_of_account_tree() {
+        let ledger = load_from_text(LEDGER);
+        let operations = ledger.operations();
+        let options = ReportOptions {
+            depth: Some(2),
+            ..Default::default()
+        };
+        let statement = income_statement(&operations, date(1, 1), date(2, 29), &options).unwrap();
+
+        let food = statement.expenses.find("Expenses:Food").unwrap();
+        assert_eq!(BigDecimal::from(150), food.amount("CNY"));
+        assert!(food.children.is_empty());
+    }
+
+    #[test]
+    fn should_convert_balance_sheet_into_given_currency() {
+        let ledger = load_from_text(LEDGER);
+        let operations = ledger.operations();
+        let options = ReportOptions {
+            convert: Some("CNY".to_owned()),
+            ..Default::default()
+        };
+        let sheet = balance_sheet(&operations, date(2, 29), &options).unwrap();
+
+        assert_eq!(BigDecimal::from(950), sheet.assets.amount("CNY"));
+        assert_eq!(BigDecimal::from(100), sheet.assets.amount("USD"));
+        assert_eq!(Some(Amount::new(BigDecimal::from(1650), "CNY")), sheet.assets.converted);
+        assert_eq!(BigDecimal::from(-100), sheet.liabilities.amount("CNY"));
+        assert_eq!(BigDecimal::from(-100), sheet.equity.amount("USD"));
+        assert_eq!(BigDecimal::from(850), sheet.net_income.amount("CNY"));
+    }
+}
@@ -0,0 +1,119 @@
+//! the directives of ledger indexed by their spans, used by editor integrations to find the directive under cursor
+//! and where accounts and commodities are defined.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use zhang_ast::{Directive, SpanInfo, Spanned};
+
+use crate::ledger::Ledger;
+
+#[derive(Debug, Default)]
+pub struct SpanIndex {
+    /// the directives of file sorted by their start offsets, by canonical path of file
+    files: HashMap<PathBuf, Vec<Spanned<Directive>>>,
+    /// the span of `open` directive, by account name
+    accounts: HashMap<String, SpanInfo>,
+    /// the span of `commodity` directive, by commodity name
+    commodities: HashMap<String, SpanInfo>,
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl SpanIndex {
+    pub fn new(ledger: &Ledger) -> Self {
+        let mut index = SpanIndex::default();
+        let directives = ledger.metas.iter().chain(ledger.directives.iter());
+        let mut canonical_paths: HashMap<&Path, PathBuf> = HashMap::new();
+        for directive in directives {
+            let Some(filename) = directive.span.filename.as_deref() else {
+                continue;
+            };
+            let path = canonical_paths.entry(filename).or_insert_with(|| canonical(filename));
+            match &directive.data {
+                Directive::Open(open) => {
+                    index.accounts.entry(open.account.name().to_owned()).or_insert_with(|| directive.span.clone());
+                }
+                Directive::Commodity(commodity) => {
+                    index.commodities.entry(commodity.currency.clone()).or_insert_with(|| directive.span.clone());
+                }
+                _ => {}
+            }
+            index.files.entry(path.clone()).or_default().push(directive.clone());
+        }
+        for directives in index.files.values_mut() {
+            directives.sort_by_key(|it| it.span.start);
+        }
+        index
+    }
+
+    /// the directives of file in the order of their positions
+    pub fn directives_of(&self, file: impl AsRef<Path>) -> &[Spanned<Directive>] {
+        self.files.get(&canonical(file.as_ref())).map(|it| it.as_slice()).unwrap_or_default()
+    }
+
+    /// the directive whose span contains the byte offset of file
+    pub fn directive_at(&self, file: impl AsRef<Path>, offset: usize) -> Option<&Spanned<Directive>> {
+        let directives = self.directives_of(file);
+        let idx = directives.partition_point(|it| it.span.start <= offset);
+        idx.checked_sub(1).map(|idx| &directives[idx]).filter(|it| offset <= it.span.end)
+    }
+
+    /// the span of `open` directive of account
+    pub fn account_definition(&self, account: &str) -> Option<&SpanInfo> {
+        self.accounts.get(account)
+    }
+
+    /// the span of `commodity` directive of commodity
+    pub fn commodity_definition(&self, commodity: &str) -> Option<&SpanInfo> {
+        self.commodities.get(commodity)
+    }
+
+    /// the files which have directives
+    pub fn files(&self) -> Vec<&PathBuf> {
+        self.files.keys().sorted().collect_vec()
+    }
+}
+
+impl Ledger {
+    /// index the directives of ledger by their spans
+    pub fn span_index(&self) -> SpanIndex {
+        SpanIndex::new(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+    use zhang_ast::Directive;
+
+    use crate::test::load_from_text;
+
+    #[test]
+    fn should_find_directive_at_offset() {
+        let content = indoc! {r#"
+            1970-01-01 commodity CNY
+            1970-01-01 open Assets:MyCard
+            1970-01-02 "KFC"
+              Assets:MyCard -50 CNY
+              Expenses:Lunch
+        "#};
+        let ledger = load_from_text(content);
+        let index = ledger.span_index();
+        let file = index.files()[0].clone();
+
+        let offset = content.find("Expenses:Lunch").unwrap();
+        assert!(matches!(index.directive_at(&file, offset).unwrap().data, Directive::Transaction(_)));
+        let offset = content.find("open").unwrap();
+        assert!(matches!(index.directive_at(&file, offset).unwrap().data, Directive::Open(_)));
+        assert!(index.directive_at(&file, content.len() + 10).is_none());
+
+        let open = index.account_definition("Assets:MyCard").unwrap();
+        assert_eq!(content.find("1970-01-01 open").unwrap(), open.start);
+        assert!(index.account_definition("Expenses:Lunch").is_none());
+        assert_eq!(0, index.commodity_definition("CNY").unwrap().start);
+    }
+}
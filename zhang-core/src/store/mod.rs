@@ -1,41 +1,77 @@
 use std::collections::{BTreeMap, HashMap};
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
 
-use bigdecimal::BigDecimal;
-use chrono::DateTime;
+use bigdecimal::{BigDecimal, Signed};
+use chrono::{DateTime, NaiveDate, Utc};
 use chrono_tz::Tz;
 use indexmap::IndexMap;
+use once_cell::sync::OnceCell;
+use strum::{AsRefStr, EnumString};
 use uuid::Uuid;
 use zhang_ast::amount::Amount;
-use zhang_ast::{Account, Flag, SpanInfo};
+use zhang_ast::{Account, Currency, Date, Flag, SpanInfo};
 
-use crate::domains::schemas::{AccountDomain, CommodityDomain, ErrorDomain, MetaDomain, PriceDomain};
+use crate::domains::price_graph::PriceGraph;
+use crate::domains::schemas::{
+    AccountDomain, CommodityDomain, EliminationDomain, ErrorDomain, InvoiceDomain, LoanPaymentDomain, MetaDomain, PriceDomain, RealizedGainDomain, TaxDomain,
+};
 
-#[derive(Default, serde::Serialize)]
+#[derive(Default, serde::Serialize, serde::Deserialize)]
 pub struct Store {
     pub options: HashMap<String, String>,
     pub accounts: HashMap<String, AccountDomain>,
     pub commodities: IndexMap<String, CommodityDomain>,
     pub transactions: HashMap<Uuid, TransactionDomain>,
     pub postings: Vec<PostingDomain>,
+    // rebuilt from postings when the store is restored
+    #[serde(skip)]
+    pub posting_index: PostingIndex,
+
+    // transaction ids by tag and link, in processing order
+    pub tag_index: HashMap<String, Vec<Uuid>>,
+    pub link_index: HashMap<String, Vec<Uuid>>,
 
     pub prices: Vec<PriceDomain>,
+    // built from prices on demand, and dropped once a price is inserted
+    #[serde(skip)]
+    pub price_graph: OnceCell<Arc<PriceGraph>>,
 
     pub budgets: HashMap<String, BudgetDomain>,
 
     // by account
     pub commodity_lots: HashMap<String, Vec<CommodityLotRecord>>,
+    // the lots of account after each change, in processing order, used by the holdings in the past
+    pub commodity_lot_history: Vec<CommodityLotSnapshot>,
+    pub realized_gains: Vec<RealizedGainDomain>,
+    pub taxes: Vec<TaxDomain>,
+    pub eliminations: Vec<EliminationDomain>,
+    // invoices by id, in declaring order
+    pub invoices: IndexMap<String, InvoiceDomain>,
+    // amortization schedules by loan account
+    pub loan_payments: HashMap<String, Vec<LoanPaymentDomain>>,
+
+    // unused pad directive by account
+    pub pads: HashMap<String, PadDomain>,
 
     pub documents: Vec<DocumentDomain>,
+    // lowercase text of documents by content hash, used for document searching
+    #[serde(skip)]
+    pub document_texts: HashMap<String, String>,
 
     pub metas: Vec<MetaDomain>,
 
     pub errors: Vec<ErrorDomain>,
+
+    // key/value area written by plugins handling custom directives, by plugin name
+    pub plugin_data: HashMap<String, IndexMap<String, serde_json::Value>>,
 }
 
-#[derive(Clone, serde::Serialize)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct TransactionDomain {
     pub id: Uuid,
     pub sequence: i32,
+    #[serde(with = "crate::utils::zoned_datetime")]
     pub datetime: DateTime<Tz>,
     pub flag: Flag,
     pub payee: Option<String>,
@@ -68,21 +104,120 @@ impl TransactionDomain {
     }
 }
 
-#[derive(Clone, serde::Serialize)]
+type PostingKey = (DateTime<Utc>, i32);
+
+/// positions of postings in `Store::postings` by account, ordered by the datetime of posting and the sequence of transaction,
+/// so that the journal of account in a date range and the balance of account at a date are found without scanning all postings
+#[derive(Default)]
+pub struct PostingIndex {
+    journals: HashMap<String, BTreeMap<PostingKey, Vec<usize>>>,
+    // by account and the commodity of balance, in the order of commodities first seen
+    balances: HashMap<String, IndexMap<Currency, BTreeMap<PostingKey, usize>>>,
+}
+
+impl PostingIndex {
+    pub fn build(postings: &[PostingDomain]) -> PostingIndex {
+        let mut index = PostingIndex::default();
+        for (position, posting) in postings.iter().enumerate() {
+            index.insert(position, posting);
+        }
+        index
+    }
+
+    pub fn insert(&mut self, position: usize, posting: &PostingDomain) {
+        let key = (posting.datetime.with_timezone(&Utc), posting.trx_sequence);
+        let account = posting.account.name();
+        self.journals.entry(account.to_owned()).or_default().entry(key).or_default().push(position);
+        self.balances
+            .entry(account.to_owned())
+            .or_default()
+            .entry(posting.after_amount.currency.clone())
+            .or_default()
+            .insert(key, position);
+    }
+
+    /// positions of account's postings within the datetime range in the order of datetime and sequence,
+    /// grouped by transaction, in which the postings are in their original order
+    pub fn journal(&self, account: &str, range: impl RangeBounds<DateTime<Utc>>) -> impl DoubleEndedIterator<Item = &[usize]> + '_ {
+        PostingIndex::key_bounds(range)
+            .zip(self.journals.get(account))
+            .into_iter()
+            .flat_map(|(bounds, journal)| journal.range(bounds))
+            .map(|(_, positions)| positions.as_slice())
+    }
+
+    /// position of the latest posting of account in the commodity at the datetime, whose after amount is the balance
+    pub fn balance(&self, account: &str, currency: &str, datetime: DateTime<Utc>) -> Option<usize> {
+        let postings = self.balances.get(account)?.get(currency)?;
+        postings.range(..=(datetime, i32::MAX)).next_back().map(|(_, position)| *position)
+    }
+
+    /// positions of the latest posting of each commodity of account at the datetime, whose after amount is the balance
+    pub fn balances(&self, account: &str, datetime: DateTime<Utc>) -> impl Iterator<Item = usize> + '_ {
+        self.balances
+            .get(account)
+            .into_iter()
+            .flat_map(|balances| balances.values())
+            .filter_map(move |postings| postings.range(..=(datetime, i32::MAX)).next_back().map(|(_, position)| *position))
+    }
+
+    /// the bounds of keys covering the datetime range, `None` if the range is empty
+    fn key_bounds(range: impl RangeBounds<DateTime<Utc>>) -> Option<(Bound<PostingKey>, Bound<PostingKey>)> {
+        let start = match range.start_bound() {
+            Bound::Included(start) => Bound::Included((*start, i32::MIN)),
+            Bound::Excluded(start) => Bound::Excluded((*start, i32::MAX)),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => Bound::Included((*end, i32::MAX)),
+            Bound::Excluded(end) => Bound::Excluded((*end, i32::MIN)),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let is_empty = match (&start, &end) {
+            (Bound::Included(start), Bound::Included(end)) => start > end,
+            (Bound::Included(start) | Bound::Excluded(start), Bound::Included(end) | Bound::Excluded(end)) => start >= end,
+            _ => false,
+        };
+        (!is_empty).then_some((start, end))
+    }
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct PostingDomain {
     pub id: Uuid,
     pub trx_id: Uuid,
     pub trx_sequence: i32,
+    #[serde(with = "crate::utils::zoned_datetime")]
     pub trx_datetime: DateTime<Tz>,
+    /// the datetime of posting used by balances and reports, which is the `date` meta of posting if present, or the datetime of transaction
+    #[serde(with = "crate::utils::zoned_datetime")]
+    pub datetime: DateTime<Tz>,
     pub account: Account,
     pub unit: Option<Amount>,
     pub cost: Option<Amount>,
     pub inferred_amount: Amount,
     pub previous_amount: Amount,
     pub after_amount: Amount,
+    pub reconcile_state: ReconcileState,
+    /// the date of statement which the posting is reconciled against
+    pub statement_date: Option<NaiveDate>,
+}
+
+/// the reconciliation state of posting, given by the `reconcile` meta of posting,
+/// or decided by the flag of posting and transaction if the meta is absent: `!` is pending, others are cleared
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, AsRefStr, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum ReconcileState {
+    /// the posting is not shown in the statement yet
+    Pending,
+    /// the posting is shown in the statement
+    #[default]
+    Cleared,
+    /// the posting is confirmed by the balance of statement
+    Reconciled,
 }
 
-#[derive(Clone, serde::Serialize)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub enum DocumentType {
     Trx(Uuid),
     Account(Account),
@@ -109,23 +244,45 @@ impl DocumentType {
     }
 }
 
-#[derive(Clone, serde::Serialize)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct DocumentDomain {
+    #[serde(with = "crate::utils::zoned_datetime")]
     pub datetime: DateTime<Tz>,
     pub document_type: DocumentType,
     pub filename: Option<String>,
     pub path: String,
+    /// sha256 of the document content, `None` if the document cannot be read
+    pub hash: Option<String>,
 }
 
-#[derive(Default, Clone, Debug, serde::Serialize)]
+/// the lots of account after they are changed by the posting at the datetime
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct CommodityLotSnapshot {
+    #[serde(with = "crate::utils::zoned_datetime")]
+    pub datetime: DateTime<Tz>,
+    pub account: String,
+    pub lots: Vec<CommodityLotRecord>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PadDomain {
+    pub date: Date,
+    pub pad: Account,
+    pub span: SpanInfo,
+}
+
+#[derive(Default, Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct CommodityLotRecord {
     pub commodity: String,
+    #[serde(with = "crate::utils::zoned_datetime::option")]
     pub datetime: Option<DateTime<Tz>>,
     pub amount: BigDecimal,
     pub price: Option<Amount>,
+    /// the label given by the posting acquiring the lot, lots are identified by price and label
+    pub label: Option<String>,
 }
 
-#[derive(Clone, Debug, serde::Serialize)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct BudgetDomain {
     pub name: String,
     pub alias: Option<String>,
@@ -133,9 +290,42 @@ pub struct BudgetDomain {
     pub closed: bool,
     pub detail: BTreeMap<u32, BudgetIntervalDetail>,
     pub commodity: String,
+    pub rollover: BudgetRollover,
+}
+
+/// how the available amount of a month is carried into the next month, configured by the `rollover` meta of budget
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, AsRefStr, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum BudgetRollover {
+    /// both unused amount and overspending are carried forward
+    #[default]
+    Carry,
+    /// only unused amount is carried forward
+    CarryPositive,
+    /// only overspending is carried forward
+    CarryNegative,
+    /// every month starts from zero
+    Reset,
+}
+
+impl BudgetRollover {
+    /// the amount carried into next month given the available amount of current month
+    pub fn carry(&self, available: Amount) -> Amount {
+        let carried = match self {
+            BudgetRollover::Carry => true,
+            BudgetRollover::CarryPositive => available.number.is_positive(),
+            BudgetRollover::CarryNegative => available.number.is_negative(),
+            BudgetRollover::Reset => false,
+        };
+        if carried {
+            available
+        } else {
+            Amount::zero(&available.currency)
+        }
+    }
 }
 
-#[derive(Clone, Debug, serde::Serialize)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct BudgetIntervalDetail {
     /// year and month pair, calculated as `year*100+month`, E.G. `202312`
     pub date: u32,
@@ -145,15 +335,18 @@ pub struct BudgetIntervalDetail {
     pub activity_amount: Amount,
 }
 
-#[derive(Clone, Debug, serde::Serialize)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct BudgetEvent {
+    #[serde(with = "crate::utils::zoned_datetime")]
     pub datetime: DateTime<Tz>,
     pub timestamp: i64,
     pub amount: Amount,
     pub event_type: BudgetEventType,
 }
 
-#[derive(Clone, Debug, serde::Serialize)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum BudgetEventType {
     AddAssignedAmount,
     Transfer,
@@ -0,0 +1,201 @@
+//! store snapshot is the processed store of ledger written into a compact binary file, so that the ledger is restored
+//! from the snapshot on next startup instead of processing all directives again if no source file changed.
+//!
+//! the snapshot is keyed by the fingerprint of loaded directives, the size and modified time of files referenced by directives,
+//! e.g. documents, timezone and zhang version, and is ignored if any of them changes.
+//! only the latest snapshot is kept, which is overwritten once the ledger is processed.
+
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use chrono_tz::Tz;
+use log::{debug, warn};
+use sha2::{Digest, Sha256};
+use zhang_ast::{Directive, Spanned};
+
+use crate::error::IoErrorIntoZhangError;
+use crate::store::{PostingIndex, Store};
+use crate::{ZhangError, ZhangResult};
+
+/// the file of store snapshot, relative to the ledger folder
+pub const DEFAULT_STORE_SNAPSHOT_FILE: &str = ".zhang-cache/store.snapshot";
+
+const ZHANG_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Clone)]
+pub struct StoreSnapshot {
+    file: PathBuf,
+}
+
+impl StoreSnapshot {
+    pub fn new(file: impl AsRef<Path>) -> Self {
+        StoreSnapshot {
+            file: file.as_ref().to_path_buf(),
+        }
+    }
+
+    /// the fingerprint of directives of ledger, the span of directive is a part of it so that any change of source file is detected.
+    /// the directives are serialized into the hasher one by one, so that the serialized ledger is never held as a whole.
+    /// the referenced files, relative to `root`, are fingerprinted by their size and modified time, so that the change of document
+    /// content, e.g. its hash and indexed text, is detected without reading it
+    pub fn fingerprint(
+        meta_directives: &[Spanned<Directive>], dated_runs: &[Vec<Spanned<Directive>>], timezone: &Tz, root: &Path, referenced_files: &[String],
+    ) -> ZhangResult<String> {
+        let mut hasher = Sha256::new();
+        hasher.update(format!("{}\n{}\n", ZHANG_VERSION, timezone.name()));
+        for directive in meta_directives.iter().chain(dated_runs.iter().flatten()) {
+            serde_json::to_writer(&mut hasher, directive).map_err(|e| ZhangError::CustomError(format!("cannot serialize directives: {}", e)))?;
+            hasher.update(b"\n");
+        }
+        for file in referenced_files {
+            hasher.update(format!("{}:{}\n", file, StoreSnapshot::file_state(&root.join(file))));
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    fn file_state(path: &Path) -> String {
+        let Ok(metadata) = std::fs::metadata(path) else {
+            return "missing".to_owned();
+        };
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|it| it.duration_since(UNIX_EPOCH).ok())
+            .map(|it| it.as_nanos())
+            .unwrap_or_default();
+        format!("{}:{}", metadata.len(), modified)
+    }
+
+    /// retrieve the store of fingerprint, `None` is returned if the snapshot is missing, stale or broken
+    pub fn restore(&self, fingerprint: &str) -> Option<Store> {
+        let content = std::fs::read(&self.file).ok()?;
+        // the snapshot starts with the line of fingerprint, so that stale snapshot is skipped without decoding the store
+        let (snapshot_fingerprint, encoded) = content.split_at(content.iter().position(|byte| *byte == b'\n')?);
+        if snapshot_fingerprint.ne(fingerprint.as_bytes()) {
+            debug!("store snapshot {} is stale", self.file.display());
+            return None;
+        }
+        match rmp_serde::from_slice::<(Store, _)>(&encoded[1..]) {
+            Ok((mut store, document_texts)) => {
+                store.document_texts = document_texts;
+                store.posting_index = PostingIndex::build(&store.postings);
+                Some(store)
+            }
+            Err(e) => {
+                warn!("store snapshot {} is broken: {}", self.file.display(), e);
+                None
+            }
+        }
+    }
+
+    /// write the store of fingerprint into the snapshot file
+    pub fn save(&self, fingerprint: &str, store: &Store) -> ZhangResult<()> {
+        let encoded = rmp_serde::to_vec(&(store, &store.document_texts)).map_err(|e| ZhangError::CustomError(format!("cannot serialize store: {}", e)))?;
+        if let Some(folder) = self.file.parent() {
+            std::fs::create_dir_all(folder).with_path(folder)?;
+        }
+        let mut content = Vec::with_capacity(fingerprint.len() + 1 + encoded.len());
+        content.extend_from_slice(fingerprint.as_bytes());
+        content.push(b'\n');
+        content.extend(encoded);
+        std::fs::write(&self.file, content).with_path(&self.file)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    use indoc::indoc;
+    use tempfile::tempdir;
+
+    use crate::data_source::LocalFileSystemDataSource;
+    use crate::data_type::text::ZhangDataType;
+    use crate::ledger::Ledger;
+    use crate::store::Store;
+    use crate::store_snapshot::StoreSnapshot;
+
+    fn load(root: &Path) -> Ledger {
+        let data_source = LocalFileSystemDataSource::new(ZhangDataType {}).with_store_snapshot(StoreSnapshot::new(root.join("store.snapshot")));
+        Ledger::load_with_data_source(root.to_path_buf(), "main.zhang".to_owned(), Arc::new(data_source)).unwrap()
+    }
+
+    fn store_json(ledger: &Ledger) -> serde_json::Value {
+        serde_json::to_value(&*ledger.store.read().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn should_restore_the_same_store_as_processing() {
+        let root = tempdir().unwrap().into_path();
+        std::fs::write(
+            root.join("main.zhang"),
+            indoc! {r#"
+                option "timezone" "Asia/Shanghai"
+                1970-01-01 open Assets:Bank
+                1970-01-01 open Expenses:Food
+                1970-01-01 commodity CNY
+                2023-12-01 price USD 7 CNY
+                2023-12-02 "KFC" "Crazy Thursday" #food ^kfc
+                  Assets:Bank -50 CNY
+                    date: "2023-12-03"
+                  Expenses:Food
+                2023-12-05 balance Assets:Bank 100 CNY
+            "#},
+        )
+        .unwrap();
+
+        let processed = load(&root);
+        assert!(root.join("store.snapshot").exists());
+
+        let restored = load(&root);
+        assert_eq!(store_json(&processed), store_json(&restored));
+        assert_eq!(processed.trx_counter.load(Ordering::Relaxed), restored.trx_counter.load(Ordering::Relaxed));
+        assert_eq!(processed.directives, restored.directives);
+    }
+
+    #[test]
+    fn should_process_again_once_source_file_changed() {
+        let root = tempdir().unwrap().into_path();
+        std::fs::write(root.join("main.zhang"), "1970-01-01 open Assets:Bank\n").unwrap();
+        load(&root);
+
+        // the snapshot of the same fingerprint is restored instead of processing the directives
+        let content = std::fs::read(root.join("store.snapshot")).unwrap();
+        let fingerprint = String::from_utf8(content.split(|byte| *byte == b'\n').next().unwrap().to_vec()).unwrap();
+        let mut marked = Store::default();
+        marked.options.insert("marker".to_owned(), "snapshot".to_owned());
+        StoreSnapshot::new(root.join("store.snapshot")).save(&fingerprint, &marked).unwrap();
+        assert!(load(&root).store.read().unwrap().options.contains_key("marker"));
+
+        std::fs::write(root.join("main.zhang"), "1970-01-01 open Assets:Cash\n").unwrap();
+        let reloaded = load(&root);
+        let store = reloaded.store.read().unwrap();
+        assert!(!store.options.contains_key("marker"));
+        assert!(store.accounts.contains_key("Assets:Cash"));
+    }
+
+    #[test]
+    fn should_process_again_once_document_changed() {
+        let root = tempdir().unwrap().into_path();
+        std::fs::write(
+            root.join("main.zhang"),
+            indoc! {r#"
+                1970-01-01 open Assets:Bank
+                1970-01-02 document Assets:Bank "statement.txt"
+            "#},
+        )
+        .unwrap();
+        std::fs::write(root.join("statement.txt"), "January").unwrap();
+        let document_hash = |ledger: &Ledger| ledger.store.read().unwrap().documents[0].hash.clone();
+
+        let processed = load(&root);
+        assert_eq!(document_hash(&processed), document_hash(&load(&root)));
+
+        std::fs::write(root.join("statement.txt"), "February").unwrap();
+        let reloaded = load(&root);
+        assert_ne!(document_hash(&processed), document_hash(&reloaded));
+        assert_eq!(Some(sha256::digest("February")), document_hash(&reloaded));
+    }
+}
@@ -0,0 +1,158 @@
+//! the transactions which recur with variable amounts, e.g. the rent shared with roommates, are declared once as `template`
+//! directive, and instantiated into transactions by filling the placeholders of amounts with concrete values.
+
+use std::collections::HashMap;
+
+use bigdecimal::BigDecimal;
+use itertools::Itertools;
+use zhang_ast::amount::Amount;
+use zhang_ast::{Date, Directive, Flag, Posting, Template, Transaction};
+
+use crate::data_type::text::parser::evaluate_template_expr;
+use crate::ledger::Ledger;
+use crate::{ZhangError, ZhangResult};
+
+/// all templates sorted by name, the template declared later replaces the earlier one in the same name
+pub fn templates(ledger: &Ledger) -> Vec<&Template> {
+    ledger
+        .directives
+        .iter()
+        .filter_map(|it| match &it.data {
+            Directive::Template(template) => Some(template),
+            _ => None,
+        })
+        .rev()
+        .unique_by(|template| template.name.as_str())
+        .sorted_by(|a, b| a.name.cmp(&b.name))
+        .collect_vec()
+}
+
+pub fn find_template<'a>(ledger: &'a Ledger, name: &str) -> Option<&'a Template> {
+    templates(ledger).into_iter().find(|template| template.name.eq(name))
+}
+
+/// the transaction of template at the given date. every placeholder of template must be given a value, and the values
+/// given to unknown placeholders are rejected since they are likely typos
+pub fn instantiate(template: &Template, date: Date, values: &HashMap<String, BigDecimal>) -> ZhangResult<Transaction> {
+    let placeholders = template.placeholders();
+    if let Some(unknown) = values.keys().filter(|it| !placeholders.contains(it)).sorted().next() {
+        return Err(ZhangError::CustomError(format!(
+            "template {} has no placeholder {{{}}}",
+            template.name, unknown
+        )));
+    }
+    let mut postings = vec![];
+    for posting in template.postings.iter() {
+        let units = match &posting.amount {
+            Some(amount) => {
+                let number = evaluate_template_expr(&amount.expression, values).map_err(ZhangError::CustomError)?;
+                Some(Amount::new(number, amount.currency.clone()))
+            }
+            None => None,
+        };
+        postings.push(Posting {
+            flag: None,
+            posting_type: posting.posting_type,
+            account: posting.account.clone(),
+            units,
+            cost: None,
+            cost_date: None,
+            cost_label: None,
+            price: None,
+            comment: None,
+            meta: Default::default(),
+        });
+    }
+    Ok(Transaction {
+        date,
+        auxiliary_date: None,
+        flag: Some(Flag::Okay),
+        payee: template.payee.clone(),
+        narration: template.narration.clone(),
+        tags: template.tags.clone(),
+        links: template.links.clone(),
+        postings,
+        meta: template.meta.clone(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use bigdecimal::BigDecimal;
+    use chrono::NaiveDate;
+    use indoc::indoc;
+    use zhang_ast::{Date, Directive, SpanInfo, Spanned};
+
+    use crate::data_type::text::ZhangDataType;
+    use crate::data_type::DataType;
+    use crate::template::{find_template, instantiate, templates};
+    use crate::test::load_from_text;
+
+    const LEDGER: &str = indoc! {r#"
+        1970-01-01 open Assets:Bank
+        1970-01-01 open Assets:Receivable:Roommate
+        1970-01-01 open Expenses:Rent
+        2024-01-01 template rent "Landlord" "Monthly rent" #home
+          Assets:Bank -{total} CNY
+          Assets:Receivable:Roommate ({total} - {deposit}) / 2 CNY
+          Expenses:Rent
+        2024-01-01 template utility "Utility"
+          Assets:Bank -{amount} CNY
+          Expenses:Rent
+    "#};
+
+    #[test]
+    fn should_instantiate_template_with_values() {
+        let ledger = load_from_text(LEDGER);
+        assert!(ledger.operations().errors().unwrap().is_empty());
+        assert_eq!(
+            vec!["rent", "utility"],
+            templates(&ledger).into_iter().map(|it| it.name.as_str()).collect::<Vec<_>>()
+        );
+
+        let template = find_template(&ledger, "rent").unwrap();
+        assert_eq!(vec!["total".to_owned(), "deposit".to_owned()], template.placeholders());
+        let values = HashMap::from([("total".to_owned(), BigDecimal::from(3000)), ("deposit".to_owned(), BigDecimal::from(200))]);
+        let trx = instantiate(template, Date::Date(NaiveDate::from_ymd_opt(2024, 2, 1).unwrap()), &values).unwrap();
+        let exported = ZhangDataType {}.export(Spanned::new(Directive::Transaction(trx), SpanInfo::default()));
+        assert_eq!(
+            indoc! {r#"
+                2024-02-01 * "Landlord" "Monthly rent" #home
+                  Assets:Bank -3000 CNY
+                  Assets:Receivable:Roommate 1400 CNY
+                  Expenses:Rent"#},
+            exported
+        );
+
+        let instantiated = load_from_text(&format!("{}{}\n", LEDGER, exported));
+        assert!(instantiated.operations().errors().unwrap().is_empty());
+    }
+
+    #[test]
+    fn should_reject_missing_or_unknown_placeholders() {
+        let ledger = load_from_text(LEDGER);
+        let template = find_template(&ledger, "utility").unwrap();
+        let date = Date::Date(NaiveDate::from_ymd_opt(2024, 2, 1).unwrap());
+
+        let missing = instantiate(template, date.clone(), &HashMap::new()).unwrap_err();
+        assert!(missing.to_string().contains("the value of placeholder {amount} is missing"));
+        let unknown = HashMap::from([("amount".to_owned(), BigDecimal::from(1)), ("amout".to_owned(), BigDecimal::from(1))]);
+        let unknown = instantiate(template, date, &unknown).unwrap_err();
+        assert!(unknown.to_string().contains("template utility has no placeholder {amout}"));
+    }
+
+    #[test]
+    fn should_reject_division_by_zero() {
+        let ledger = load_from_text(LEDGER);
+        let template = find_template(&ledger, "rent").unwrap();
+        let values = HashMap::from([("total".to_owned(), BigDecimal::from(3000)), ("deposit".to_owned(), BigDecimal::from(200))]);
+        let date = Date::Date(NaiveDate::from_ymd_opt(2024, 2, 1).unwrap());
+        assert!(instantiate(template, date, &values).is_ok());
+
+        let values = HashMap::from([("total".to_owned(), BigDecimal::from(3000)), ("n".to_owned(), BigDecimal::from(0))]);
+        let error = crate::data_type::text::parser::evaluate_template_expr("{total} / {n}", &values).unwrap_err();
+        assert_eq!("division by zero", error);
+    }
+}
@@ -0,0 +1,86 @@
+//! the display of amounts following the conventions of their commodities, like `¥1,234.00` or `1 234,00 €`.
+
+use bigdecimal::{BigDecimal, Signed};
+use itertools::Itertools;
+
+use crate::domains::schemas::CommodityDomain;
+use crate::utils::bigdecimal_ext::BigDecimalExt;
+
+pub const DEFAULT_DECIMAL_SYMBOL: &str = ".";
+
+/// the number rounded into the precision of commodity, whose integer part is grouped by the grouping separator.
+/// the prefix is put right before the number, the suffix(or the commodity name if neither is given) is put after a space.
+pub fn format_amount(commodity: &CommodityDomain, number: &BigDecimal) -> String {
+    let precision = commodity.precision.max(0) as i64;
+    let rounded = number.round_with(precision, commodity.rounding.is_up()).with_scale(precision);
+    let plain = rounded.abs().to_string();
+    let (integer, fraction) = match plain.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (plain.as_str(), None),
+    };
+
+    let mut formatted = String::new();
+    if rounded.is_negative() {
+        formatted.push('-');
+    }
+    if let Some(prefix) = &commodity.prefix {
+        formatted.push_str(prefix);
+    }
+    match &commodity.grouping_separator {
+        Some(separator) => formatted.push_str(&group_digits(integer, separator)),
+        None => formatted.push_str(integer),
+    }
+    if let Some(fraction) = fraction {
+        formatted.push_str(commodity.decimal_symbol.as_deref().unwrap_or(DEFAULT_DECIMAL_SYMBOL));
+        formatted.push_str(fraction);
+    }
+    match (&commodity.prefix, &commodity.suffix) {
+        (_, Some(suffix)) => formatted.push_str(&format!(" {}", suffix)),
+        (None, None) => formatted.push_str(&format!(" {}", commodity.name)),
+        (Some(_), None) => {}
+    }
+    formatted
+}
+
+/// group the digits by thousands from the right
+fn group_digits(integer: &str, separator: &str) -> String {
+    let digits = integer.chars().collect_vec();
+    digits.rchunks(3).rev().map(|chunk| chunk.iter().collect::<String>()).join(separator)
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+    use zhang_ast::Rounding;
+
+    use crate::domains::schemas::CommodityDomain;
+    use crate::utils::amount_format::format_amount;
+
+    fn commodity(name: &str, prefix: Option<&str>, suffix: Option<&str>, grouping_separator: Option<&str>, decimal_symbol: Option<&str>) -> CommodityDomain {
+        CommodityDomain {
+            name: name.to_owned(),
+            precision: 2,
+            prefix: prefix.map(|it| it.to_owned()),
+            suffix: suffix.map(|it| it.to_owned()),
+            rounding: Rounding::RoundDown,
+            grouping_separator: grouping_separator.map(|it| it.to_owned()),
+            decimal_symbol: decimal_symbol.map(|it| it.to_owned()),
+        }
+    }
+
+    #[test]
+    fn should_format_amount_by_commodity_conventions() {
+        let cny = commodity("CNY", Some("¥"), None, Some(","), None);
+        assert_eq!("¥1,234.00", format_amount(&cny, &BigDecimal::from(1234)));
+        assert_eq!("-¥1,234,567.89", format_amount(&cny, &BigDecimal::from_str("-1234567.891").unwrap()));
+        assert_eq!("¥123.00", format_amount(&cny, &BigDecimal::from(123)));
+
+        let eur = commodity("EUR", None, Some("€"), Some(" "), Some(","));
+        assert_eq!("1 234,00 €", format_amount(&eur, &BigDecimal::from(1234)));
+
+        let btc = commodity("BTC", None, None, None, None);
+        assert_eq!("1234.50 BTC", format_amount(&btc, &BigDecimal::from_str("1234.5").unwrap()));
+    }
+}
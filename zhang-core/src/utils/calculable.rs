@@ -27,8 +27,8 @@ impl Calculable for Vec<Amount> {
 
             if currency.eq(&operating_currency) {
                 total.add_assign(&number);
-            } else if let Some(price) = operations.get_price(date.naive_local(), &currency, &operating_currency)? {
-                total.add_assign((&number).mul(price.amount));
+            } else if let Some(rate) = operations.exchange_rate(&currency, &operating_currency, date.naive_local())? {
+                total.add_assign((&number).mul(rate));
             }
 
             let currency_amount = detail.entry(currency).or_insert_with(BigDecimal::zero);
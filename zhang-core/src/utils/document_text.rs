@@ -0,0 +1,42 @@
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::sync::Mutex;
+
+use lru::LruCache;
+use once_cell::sync::OnceCell;
+
+/// the max count of documents whose extracted texts are cached
+const MAX_EXTRACTED_TEXTS: usize = 1024;
+
+/// extracted texts by document content hash, shared across ledger reloads so that only new or changed documents are extracted.
+/// the least recently used ones are evicted once the cache is full
+static EXTRACTED_TEXTS: OnceCell<Mutex<LruCache<String, Option<String>>>> = OnceCell::new();
+
+/// extract the searchable text of document, the result is cached by the content hash.
+/// pdf documents are supported with `document_pdf` feature, other documents are indexed if their content is valid utf-8 text.
+pub fn extract_text(path: &str, hash: &str, content: &[u8]) -> Option<String> {
+    let cache = EXTRACTED_TEXTS.get_or_init(|| Mutex::new(LruCache::new(NonZeroUsize::new(MAX_EXTRACTED_TEXTS).expect("the capacity is not zero"))));
+    if let Some(text) = cache.lock().expect("poison lock detect").get(hash) {
+        return text.clone();
+    }
+    let text = extract(path, content).map(|it| it.to_lowercase());
+    cache.lock().expect("poison lock detect").put(hash.to_owned(), text.clone());
+    text
+}
+
+fn extract(path: &str, content: &[u8]) -> Option<String> {
+    let is_pdf = Path::new(path)
+        .extension()
+        .and_then(|it| it.to_str())
+        .map(|it| it.eq_ignore_ascii_case("pdf"))
+        .unwrap_or(false);
+    if is_pdf {
+        #[cfg(feature = "document_pdf")]
+        return pdf_extract::extract_text_from_mem(content)
+            .map_err(|e| log::warn!("cannot extract text from pdf document {}: {}", path, e))
+            .ok();
+        #[cfg(not(feature = "document_pdf"))]
+        return None;
+    }
+    String::from_utf8(content.to_vec()).ok()
+}
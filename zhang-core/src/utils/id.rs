@@ -40,6 +40,7 @@ mod test {
             end: 0,
             content: "".to_string(),
             filename: None,
+            trivia: Default::default(),
         };
         assert_eq!(Uuid::from_span(&empty_span), Uuid::from_span(&empty_span))
     }
@@ -51,6 +52,7 @@ mod test {
             end: 0,
             content: "".to_string(),
             filename: Some(PathBuf::from("a.abc")),
+            trivia: Default::default(),
         };
         assert_eq!(Uuid::from_span(&span), Uuid::from_span(&span));
 
@@ -59,13 +61,15 @@ mod test {
                 start: 10,
                 end: 0,
                 content: "".to_string(),
-                filename: Some(PathBuf::from("a.abc"))
+                filename: Some(PathBuf::from("a.abc")),
+                trivia: Default::default(),
             }),
             Uuid::from_span(&SpanInfo {
                 start: 10,
                 end: 0,
                 content: "".to_string(),
-                filename: Some(PathBuf::from("a.abc"))
+                filename: Some(PathBuf::from("a.abc")),
+                trivia: Default::default(),
             })
         );
     }
@@ -77,13 +81,15 @@ mod test {
                 start: 10,
                 end: 0,
                 content: "".to_string(),
-                filename: Some(PathBuf::from("a.abc"))
+                filename: Some(PathBuf::from("a.abc")),
+                trivia: Default::default(),
             }),
             Uuid::from_span(&SpanInfo {
                 start: 10,
                 end: 0,
                 content: "".to_string(),
-                filename: None
+                filename: None,
+                trivia: Default::default(),
             })
         );
         assert_ne!(
@@ -91,13 +97,15 @@ mod test {
                 start: 10,
                 end: 0,
                 content: "".to_string(),
-                filename: Some(PathBuf::from("a.abc"))
+                filename: Some(PathBuf::from("a.abc")),
+                trivia: Default::default(),
             }),
             Uuid::from_span(&SpanInfo {
                 start: 10,
                 end: 0,
                 content: "".to_string(),
-                filename: Some(PathBuf::from("a.ab"))
+                filename: Some(PathBuf::from("a.ab")),
+                trivia: Default::default(),
             })
         );
 
@@ -106,13 +114,15 @@ mod test {
                 start: 9,
                 end: 0,
                 content: "".to_string(),
-                filename: Some(PathBuf::from("a.abc"))
+                filename: Some(PathBuf::from("a.abc")),
+                trivia: Default::default(),
             }),
             Uuid::from_span(&SpanInfo {
                 start: 10,
                 end: 0,
                 content: "".to_string(),
-                filename: Some(PathBuf::from("a.abc"))
+                filename: Some(PathBuf::from("a.abc")),
+                trivia: Default::default(),
             })
         );
 
@@ -121,13 +131,15 @@ mod test {
                 start: 9,
                 end: 0,
                 content: "".to_string(),
-                filename: None
+                filename: None,
+                trivia: Default::default(),
             }),
             Uuid::from_span(&SpanInfo {
                 start: 10,
                 end: 0,
                 content: "".to_string(),
-                filename: None
+                filename: None,
+                trivia: Default::default(),
             })
         );
     }
@@ -0,0 +1,80 @@
+//! k-way merge of sorted runs, e.g. the directives of each file sorted by datetime,
+//! which yields the items one by one instead of sorting all of them at once.
+//! the items are moved out of their runs, whose buffers are shrunk as they are consumed,
+//! so that the merge holds no more than twice of the items not yielded yet.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+pub struct MergeSortedRuns<T, K: Ord, F: Fn(&T) -> K> {
+    // each run is reversed, so that its next item is popped from the end
+    runs: Vec<Vec<T>>,
+    heads: Vec<Option<T>>,
+    // the key of head item and its run, the items of equal keys are yielded in the order of their runs
+    heap: BinaryHeap<Reverse<(K, usize)>>,
+    key: F,
+}
+
+/// merge the runs each of which is sorted by `key`, the result is the same as the stable sort of the concatenated runs
+pub fn merge_sorted_runs<T, K: Ord, F: Fn(&T) -> K>(runs: Vec<Vec<T>>, key: F) -> MergeSortedRuns<T, K, F> {
+    let mut runs = runs
+        .into_iter()
+        .map(|mut run| {
+            run.reverse();
+            run
+        })
+        .collect::<Vec<_>>();
+    let heads = runs.iter_mut().map(Vec::pop).collect::<Vec<_>>();
+    let heap = heads
+        .iter()
+        .enumerate()
+        .filter_map(|(run, head)| head.as_ref().map(|head| Reverse((key(head), run))))
+        .collect();
+    MergeSortedRuns { runs, heads, heap, key }
+}
+
+impl<T, K: Ord, F: Fn(&T) -> K> Iterator for MergeSortedRuns<T, K, F> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((_, run)) = self.heap.pop()?;
+        let item = self.heads[run].take();
+        let remaining = &mut self.runs[run];
+        self.heads[run] = remaining.pop();
+        // release the consumed part of buffer, the items left are moved once the buffer is half empty
+        if remaining.len() < remaining.capacity() / 2 {
+            remaining.shrink_to_fit();
+        }
+        if let Some(head) = &self.heads[run] {
+            self.heap.push(Reverse(((self.key)(head), run)));
+        }
+        item
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use itertools::Itertools;
+
+    use crate::utils::merge::merge_sorted_runs;
+
+    #[test]
+    fn should_merge_runs_as_stable_sort() {
+        let runs = vec![vec![(1, 'a'), (3, 'a'), (3, 'b')], vec![], vec![(0, 'c'), (3, 'c'), (4, 'c')], vec![(1, 'd')]];
+        let expected = runs.iter().flatten().copied().sorted_by_key(|(key, _)| *key).collect_vec();
+        assert_eq!(expected, merge_sorted_runs(runs, |(key, _)| *key).collect_vec());
+    }
+
+    #[test]
+    fn should_release_buffer_of_consumed_items() {
+        let runs = (0..4).map(|run| (0..1000).map(|key| (key, run)).collect_vec()).collect_vec();
+        let mut merged = merge_sorted_runs(runs, |(key, _)| *key);
+        for consumed in 1..=4000 {
+            merged.next().unwrap();
+            let left = 4000 - consumed;
+            let buffered: usize = merged.runs.iter().map(Vec::capacity).sum::<usize>() + merged.heads.len();
+            assert!(buffered <= 2 * left + 8, "{} items are buffered for {} items left", buffered, left);
+        }
+        assert_eq!(None, merged.next());
+    }
+}
@@ -1,18 +1,113 @@
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 
+use glob::{MatchOptions, Pattern};
+use itertools::Itertools;
+use zhang_ast::{Directive, Include, ZhangString};
+
+use crate::ledger::Ledger;
+use crate::{ZhangError, ZhangResult};
+
+pub mod amount_format;
 pub mod bigdecimal_ext;
 pub mod calculable;
 pub mod date_range;
+pub mod document_text;
 pub mod hashmap;
 pub mod id;
 pub mod logging;
+pub mod merge;
 pub mod price_grip;
 pub mod string_;
+pub mod zoned_datetime;
 
 pub fn has_path_visited<'a>(visited: impl IntoIterator<Item = &'a PathBuf>, path: &PathBuf) -> bool {
     visited.into_iter().any(|pathbuf| pathbuf.eq(path))
 }
 
+/// `*` and `?` of include pattern never match the path separator, `**` is used to match files in nested folders
+const INCLUDE_MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: true,
+};
+
+/// whether the file of include directive is a glob pattern like `accounts/*.zhang`
+pub fn is_glob_pattern(file: &str) -> bool {
+    file.contains(['*', '?', '['])
+}
+
+/// the full pattern of include directive, the folder of including file is escaped so that it is always matched literally
+fn include_pattern(folder: &Path, pattern: &str) -> ZhangResult<Pattern> {
+    let full_pattern = if Path::new(pattern).is_absolute() {
+        pattern.to_owned()
+    } else {
+        format!("{}/{}", Pattern::escape(&folder.to_string_lossy()), pattern)
+    };
+    Pattern::new(&full_pattern).map_err(|e| ZhangError::CustomError(format!("invalid include pattern [{}]: {}", pattern, e)))
+}
+
+/// the deepest folder of include pattern without glob characters, which contains all the matching files
+pub fn include_pattern_base(folder: &Path, pattern: &str) -> PathBuf {
+    let mut base = if Path::new(pattern).is_absolute() {
+        PathBuf::new()
+    } else {
+        folder.to_path_buf()
+    };
+    let components = Path::new(pattern).components().collect_vec();
+    for component in &components[..components.len().saturating_sub(1)] {
+        match component {
+            Component::Normal(name) if is_glob_pattern(&name.to_string_lossy()) => break,
+            other => base.push(other),
+        }
+    }
+    base
+}
+
+/// the files matching include pattern relative to `folder`, sorted by path so that the loading order is deterministic
+pub fn match_include_pattern(folder: &Path, pattern: &str, files: impl IntoIterator<Item = PathBuf>) -> ZhangResult<Vec<PathBuf>> {
+    let pattern = include_pattern(folder, pattern)?;
+    Ok(files
+        .into_iter()
+        .filter(|file| pattern.matches_path_with(file, INCLUDE_MATCH_OPTIONS))
+        .sorted()
+        .dedup()
+        .collect_vec())
+}
+
+/// the files on local file system matching include pattern relative to `folder`, sorted by path
+pub fn expand_include_pattern(folder: &Path, pattern: &str) -> ZhangResult<Vec<PathBuf>> {
+    let full_pattern = include_pattern(folder, pattern)?;
+    let paths = glob::glob_with(full_pattern.as_str(), INCLUDE_MATCH_OPTIONS)
+        .map_err(|e| ZhangError::CustomError(format!("invalid include pattern [{}]: {}", pattern, e)))?;
+    Ok(paths.filter_map(Result::ok).filter(|it| it.is_file()).sorted().collect_vec())
+}
+
+/// append directives into `file`, relative path is resolved from the ledger entry.
+/// the file is included in main file if it is not loaded by ledger.
+pub async fn append_directives(ledger: &Ledger, file: &Path, directives: Vec<Directive>) -> ZhangResult<()> {
+    let (entry, main_file_endpoint) = &ledger.entry;
+    let target = entry.join(file);
+    if !has_path_visited(&ledger.visited_files, &target) {
+        let include = Directive::Include(Include {
+            file: ZhangString::QuoteString(file.to_string_lossy().to_string()),
+            profile: None,
+        });
+        append_to_file(ledger, entry.join(main_file_endpoint), vec![include]).await?;
+    }
+    append_to_file(ledger, target, directives).await
+}
+
+async fn append_to_file(ledger: &Ledger, path: PathBuf, directives: Vec<Directive>) -> ZhangResult<()> {
+    let path = path.to_string_lossy().to_string();
+    let mut content = ledger.data_source.async_get(path.clone()).await.unwrap_or_default();
+    for directive in directives {
+        content.push(b'\n');
+        content.extend(ledger.data_source.export(directive)?);
+    }
+    content.push(b'\n');
+    ledger.data_source.async_save(ledger, path, &content).await
+}
+
 macro_rules! feature_enable {
     ($feature_name: expr, $feature_process:expr) => {
         if $feature_name {
@@ -100,6 +100,7 @@ mod test {
             end: 4,
             content: "".to_string(),
             filename: None,
+            trivia: Default::default(),
         };
 
         let mut origin = "helloworld".to_string();
@@ -0,0 +1,51 @@
+//! serde of `DateTime<Tz>` used by `#[serde(with = "crate::utils::zoned_datetime")]`.
+//!
+//! human-readable formats like json keep the RFC 3339 string of chrono, which is what the api responses are.
+//! compact formats like store snapshot keep the timestamp along with the name of timezone, so that the datetime is restored in its timezone.
+
+use std::str::FromStr;
+
+use chrono::{DateTime, FixedOffset};
+use chrono_tz::Tz;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub fn serialize<S: Serializer>(datetime: &DateTime<Tz>, serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        datetime.serialize(serializer)
+    } else {
+        (datetime.timestamp(), datetime.timestamp_subsec_nanos(), datetime.timezone().name()).serialize(serializer)
+    }
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Tz>, D::Error> {
+    if deserializer.is_human_readable() {
+        // RFC 3339 string carries the offset only, so the datetime is restored in UTC
+        let datetime = DateTime::<FixedOffset>::deserialize(deserializer)?;
+        Ok(datetime.with_timezone(&Tz::UTC))
+    } else {
+        let (secs, nanos, timezone): (i64, u32, String) = Deserialize::deserialize(deserializer)?;
+        let timezone = Tz::from_str(&timezone).map_err(D::Error::custom)?;
+        DateTime::from_timestamp(secs, nanos)
+            .map(|datetime| datetime.with_timezone(&timezone))
+            .ok_or_else(|| D::Error::custom(format!("timestamp {} is out of range", secs)))
+    }
+}
+
+/// serde of `Option<DateTime<Tz>>`
+pub mod option {
+    use chrono::DateTime;
+    use chrono_tz::Tz;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct Zoned(#[serde(with = "super")] DateTime<Tz>);
+
+    pub fn serialize<S: Serializer>(datetime: &Option<DateTime<Tz>>, serializer: S) -> Result<S::Ok, S::Error> {
+        datetime.map(Zoned).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Tz>>, D::Error> {
+        Ok(Option::<Zoned>::deserialize(deserializer)?.map(|Zoned(datetime)| datetime))
+    }
+}
@@ -0,0 +1,128 @@
+//! ledger watcher monitors the files loaded by ledgers, including the ones included transitively,
+//! and emits the changed files once the changes settle down, so that rapid saves of editor trigger only one reload.
+//!
+//! the folders of files are watched instead of the files themselves, since many editors save file by replacing it.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use itertools::Itertools;
+use log::{debug, trace};
+use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{ZhangError, ZhangResult};
+
+/// how long the changes should settle down before they are emitted
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(300);
+
+pub struct LedgerWatcher {
+    watcher: RecommendedWatcher,
+    files: Arc<RwLock<HashSet<PathBuf>>>,
+    folders: HashSet<PathBuf>,
+}
+
+impl LedgerWatcher {
+    /// create the watcher, the changed files are sent into the receiver in batches,
+    /// each of them is emitted once no more change of watched files is seen within `debounce`
+    pub fn new(debounce: Duration) -> ZhangResult<(LedgerWatcher, Receiver<Vec<PathBuf>>)> {
+        let files: Arc<RwLock<HashSet<PathBuf>>> = Default::default();
+        let (event_sender, event_receiver) = channel::<Event>();
+        let (batch_sender, batch_receiver) = channel();
+
+        let watcher = RecommendedWatcher::new(
+            move |event: notify::Result<Event>| {
+                if let Ok(event) = event {
+                    event_sender.send(event).ok();
+                }
+            },
+            Config::default(),
+        )
+        .map_err(|e| ZhangError::CustomError(format!("cannot create file watcher: {}", e)))?;
+
+        let watched_files = files.clone();
+        std::thread::spawn(move || {
+            let changed_files = |event: Event| -> Vec<PathBuf> {
+                if matches!(event.kind, EventKind::Access(_)) {
+                    return vec![];
+                }
+                let files = watched_files.read().unwrap();
+                event.paths.into_iter().filter(|path| files.contains(path)).collect_vec()
+            };
+            while let Ok(event) = event_receiver.recv() {
+                let mut batch = changed_files(event);
+                loop {
+                    match event_receiver.recv_timeout(debounce) {
+                        Ok(event) => batch.extend(changed_files(event)),
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+                trace!("receive file changes: {:?}", batch);
+                let batch = batch.into_iter().unique().collect_vec();
+                if !batch.is_empty() && batch_sender.send(batch).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok((
+            LedgerWatcher {
+                watcher,
+                files,
+                folders: HashSet::new(),
+            },
+            batch_receiver,
+        ))
+    }
+
+    /// watch the files in place of the ones given before, e.g. the visited files of ledgers after reloading
+    pub fn watch(&mut self, files: impl IntoIterator<Item = PathBuf>) -> ZhangResult<()> {
+        let files: HashSet<PathBuf> = files.into_iter().collect();
+        let folders: HashSet<PathBuf> = files.iter().filter_map(|file| file.parent()).map(Path::to_path_buf).collect();
+
+        for folder in self.folders.difference(&folders) {
+            debug!("unwatching {}", folder.display());
+            self.watcher.unwatch(folder).ok();
+        }
+        for folder in folders.difference(&self.folders) {
+            debug!("watching {}", folder.display());
+            self.watcher
+                .watch(folder, RecursiveMode::NonRecursive)
+                .map_err(|e| ZhangError::CustomError(format!("cannot watch {}: {}", folder.display(), e)))?;
+        }
+        self.folders = folders;
+        *self.files.write().unwrap() = files;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use tempfile::tempdir;
+
+    use crate::watcher::LedgerWatcher;
+
+    #[test]
+    fn should_emit_debounced_changes_of_watched_files() {
+        let root = tempdir().unwrap().into_path().canonicalize().unwrap();
+        let main = root.join("main.zhang");
+        let unwatched = root.join("notes.txt");
+        std::fs::write(&main, "").unwrap();
+
+        let (mut watcher, changes) = LedgerWatcher::new(Duration::from_millis(200)).unwrap();
+        watcher.watch(vec![main.clone()]).unwrap();
+
+        for line in 0..5 {
+            std::fs::write(&main, format!("; line {}\n", line)).unwrap();
+            std::fs::write(&unwatched, "").unwrap();
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(vec![main], changes.recv_timeout(Duration::from_secs(5)).unwrap());
+        assert!(changes.recv_timeout(Duration::from_millis(500)).is_err(), "rapid saves should be emitted once");
+    }
+}
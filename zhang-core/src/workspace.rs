@@ -0,0 +1,80 @@
+//! several independent ledgers loaded in one process, e.g. the personal one and the business one.
+//!
+//! each ledger has its own store, files and errors, only the queries here combine them together.
+
+use bigdecimal::{BigDecimal, Zero};
+use chrono::NaiveDateTime;
+use indexmap::IndexMap;
+use zhang_ast::amount::Amount;
+
+use crate::domains::schemas::ConsolidatedNetWorthDomain;
+use crate::ledger::Ledger;
+use crate::ZhangResult;
+
+/// the net worth of ledgers in the currency at the date, each ledger is valued by its own prices at that time
+pub fn consolidated_net_worth<'a>(
+    ledgers: impl IntoIterator<Item = (&'a str, &'a Ledger)>, date: NaiveDateTime, currency: impl AsRef<str>,
+) -> ZhangResult<ConsolidatedNetWorthDomain> {
+    let currency = currency.as_ref();
+    let mut total = BigDecimal::zero();
+    let mut net_worths = IndexMap::new();
+    for (name, ledger) in ledgers {
+        let net_worth = ledger.operations().net_worth(date, currency)?;
+        total += &net_worth.number;
+        net_worths.insert(name.to_owned(), net_worth);
+    }
+    Ok(ConsolidatedNetWorthDomain {
+        date,
+        total: Amount::new(total, currency),
+        ledgers: net_worths,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+    use chrono::NaiveDate;
+    use indoc::indoc;
+
+    use crate::test::load_from_text;
+    use crate::workspace::consolidated_net_worth;
+
+    #[test]
+    fn should_keep_ledgers_separated_and_consolidate_net_worth() {
+        let personal = load_from_text(indoc! {r#"
+            1970-01-01 commodity CNY
+            1970-01-01 open Assets:Bank
+            1970-01-01 open Income:Salary
+            1970-01-02 "salary"
+              Assets:Bank 100 CNY
+              Income:Salary
+            1970-01-03 close Expenses:Food
+        "#});
+        let business = load_from_text(indoc! {r#"
+            1970-01-01 commodity USD
+            1970-01-01 commodity CNY
+            1970-01-01 open Assets:Bank
+            1970-01-01 open Equity:Capital
+            1970-01-01 price USD 7 CNY
+            1970-01-02 "capital"
+              Assets:Bank 10 USD
+              Equity:Capital
+        "#});
+
+        // each ledger has its own store and errors
+        assert!(business.operations().exist_account("Equity:Capital").unwrap());
+        assert!(!personal.operations().exist_account("Equity:Capital").unwrap());
+        assert_eq!(1, personal.operations().errors().unwrap().len());
+        assert!(business.operations().errors().unwrap().is_empty());
+
+        let date = NaiveDate::from_ymd_opt(1970, 1, 10).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let net_worth = consolidated_net_worth([("personal", &personal), ("business", &business)], date, "CNY").unwrap();
+        assert_eq!(vec!["personal", "business"], net_worth.ledgers.keys().collect::<Vec<_>>());
+        assert_eq!(BigDecimal::from(100), net_worth.ledgers["personal"].number);
+        assert_eq!(BigDecimal::from(70), net_worth.ledgers["business"].number);
+        assert_eq!(BigDecimal::from_str("170").unwrap(), net_worth.total.number);
+        assert_eq!("CNY", net_worth.total.currency);
+    }
+}
@@ -0,0 +1,184 @@
+//! the language server of zhang files, providing diagnostics, completion, go-to-definition and hover.
+//!
+//! the root of ledger is the root of workspace, and the endpoint is given by initialization option `endpoint`,
+//! which is `main.zhang` by default.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use log::info;
+use serde::Deserialize;
+use tokio::sync::RwLock;
+use tower_lsp::jsonrpc::Result;
+use tower_lsp::lsp_types::*;
+use tower_lsp::{Client, LanguageServer, LspService, Server};
+
+use crate::workspace::Workspace;
+
+pub mod line_index;
+pub mod workspace;
+
+const DEFAULT_ENDPOINT: &str = "main.zhang";
+
+#[derive(Deserialize, Default)]
+struct InitializationOptions {
+    endpoint: Option<String>,
+}
+
+pub struct Backend {
+    client: Client,
+    workspace: RwLock<Option<Workspace>>,
+    /// the documents which have diagnostics published, they are cleared once the diagnostics are gone
+    published: RwLock<HashSet<Url>>,
+}
+
+impl Backend {
+    pub fn new(client: Client) -> Self {
+        Backend {
+            client,
+            workspace: RwLock::new(None),
+            published: RwLock::new(HashSet::new()),
+        }
+    }
+
+    async fn publish_diagnostics(&self) {
+        let diagnostics = match self.workspace.read().await.as_ref() {
+            Some(workspace) => workspace.diagnostics(),
+            None => return,
+        };
+        let mut published = self.published.write().await;
+        let mut current = HashSet::new();
+        for (file, diagnostics) in diagnostics {
+            let Ok(uri) = Url::from_file_path(&file) else {
+                continue;
+            };
+            if diagnostics.is_empty() && !published.contains(&uri) {
+                continue;
+            }
+            if !diagnostics.is_empty() {
+                current.insert(uri.clone());
+            }
+            self.client.publish_diagnostics(uri, diagnostics, None).await;
+        }
+        for uri in published.difference(&current) {
+            self.client.publish_diagnostics(uri.clone(), vec![], None).await;
+        }
+        *published = current;
+    }
+
+    async fn update_document(&self, uri: &Url, content: Option<String>) {
+        let Ok(path) = uri.to_file_path() else {
+            return;
+        };
+        if let Some(workspace) = self.workspace.write().await.as_mut() {
+            match content {
+                Some(content) => workspace.update(path, content),
+                None => workspace.close(&path),
+            }
+            workspace.reload();
+        }
+        self.publish_diagnostics().await;
+    }
+}
+
+#[tower_lsp::async_trait]
+impl LanguageServer for Backend {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        let options: InitializationOptions = params.initialization_options.and_then(|it| serde_json::from_value(it).ok()).unwrap_or_default();
+        #[allow(deprecated)]
+        let root = params
+            .workspace_folders
+            .and_then(|folders| folders.into_iter().next())
+            .map(|folder| folder.uri)
+            .or(params.root_uri)
+            .and_then(|uri| uri.to_file_path().ok())
+            .unwrap_or_else(|| PathBuf::from("."));
+        let endpoint = options.endpoint.unwrap_or_else(|| DEFAULT_ENDPOINT.to_owned());
+        info!("open ledger {} with endpoint {}", root.display(), endpoint);
+        *self.workspace.write().await = Some(Workspace::new(root, endpoint));
+
+        Ok(InitializeResult {
+            capabilities: ServerCapabilities {
+                text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+                completion_provider: Some(CompletionOptions {
+                    trigger_characters: Some(vec![":".to_owned(), "\"".to_owned()]),
+                    ..CompletionOptions::default()
+                }),
+                definition_provider: Some(OneOf::Left(true)),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                ..ServerCapabilities::default()
+            },
+            server_info: Some(ServerInfo {
+                name: env!("CARGO_PKG_NAME").to_owned(),
+                version: Some(env!("CARGO_PKG_VERSION").to_owned()),
+            }),
+        })
+    }
+
+    async fn initialized(&self, _: InitializedParams) {
+        if let Some(workspace) = self.workspace.write().await.as_mut() {
+            workspace.reload();
+        }
+        self.publish_diagnostics().await;
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        self.update_document(&params.text_document.uri, Some(params.text_document.text)).await;
+    }
+
+    async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        // the documents are synchronized in full, the last change is the whole content
+        if let Some(change) = params.content_changes.into_iter().last() {
+            self.update_document(&params.text_document.uri, Some(change.text)).await;
+        }
+    }
+
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        if let Some(text) = params.text {
+            self.update_document(&params.text_document.uri, Some(text)).await;
+        }
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        self.update_document(&params.text_document.uri, None).await;
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let position = params.text_document_position;
+        let Ok(path) = position.text_document.uri.to_file_path() else {
+            return Ok(None);
+        };
+        let workspace = self.workspace.read().await;
+        let items = workspace.as_ref().map(|it| it.completions(&path, position.position)).unwrap_or_default();
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    async fn goto_definition(&self, params: GotoDefinitionParams) -> Result<Option<GotoDefinitionResponse>> {
+        let position = params.text_document_position_params;
+        let Ok(path) = position.text_document.uri.to_file_path() else {
+            return Ok(None);
+        };
+        let workspace = self.workspace.read().await;
+        let location = workspace.as_ref().and_then(|it| it.definition(&path, position.position));
+        Ok(location.map(GotoDefinitionResponse::Scalar))
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let position = params.text_document_position_params;
+        let Ok(path) = position.text_document.uri.to_file_path() else {
+            return Ok(None);
+        };
+        let workspace = self.workspace.read().await;
+        Ok(workspace.as_ref().and_then(|it| it.hover(&path, position.position)))
+    }
+}
+
+/// serve the language server over stdin and stdout
+pub async fn serve_stdio() {
+    let (service, socket) = LspService::new(Backend::new);
+    Server::new(tokio::io::stdin(), tokio::io::stdout(), socket).serve(service).await;
+}
@@ -0,0 +1,71 @@
+use tower_lsp::lsp_types::{Position, Range};
+
+/// the conversion between byte offsets and the positions of LSP, whose characters are counted in UTF-16 code units
+pub struct LineIndex<'a> {
+    content: &'a str,
+    /// the byte offsets of line starts
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(content: &'a str) -> Self {
+        let line_starts = std::iter::once(0).chain(content.match_indices('\n').map(|(idx, _)| idx + 1)).collect();
+        LineIndex { content, line_starts }
+    }
+
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.content.len());
+        let line = self.line_starts.partition_point(|start| *start <= offset) - 1;
+        let line_start = self.line_starts[line];
+        let character = self.content[line_start..]
+            .char_indices()
+            .take_while(|(idx, _)| line_start + idx < offset)
+            .map(|(_, c)| c.len_utf16())
+            .sum::<usize>();
+        Position::new(line as u32, character as u32)
+    }
+
+    pub fn range(&self, start: usize, end: usize) -> Range {
+        Range::new(self.position(start), self.position(end))
+    }
+
+    /// the byte offset of position, the position beyond the line or content is clamped to its end
+    pub fn offset(&self, position: Position) -> usize {
+        let Some(line_start) = self.line_starts.get(position.line as usize).copied() else {
+            return self.content.len();
+        };
+        let line = self.content[line_start..].split('\n').next().unwrap_or_default();
+        let mut units = 0;
+        for (idx, c) in line.char_indices() {
+            if units >= position.character as usize {
+                return line_start + idx;
+            }
+            units += c.len_utf16();
+        }
+        line_start + line.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tower_lsp::lsp_types::Position;
+
+    use crate::line_index::LineIndex;
+
+    #[test]
+    fn should_convert_between_offset_and_position() {
+        let content = "first\n价格 😀 line\nlast";
+        let index = LineIndex::new(content);
+        assert_eq!(Position::new(0, 0), index.position(0));
+        assert_eq!(Position::new(1, 0), index.position(6));
+        assert_eq!(Position::new(1, 2), index.position(12));
+        // the emoji takes two UTF-16 code units
+        assert_eq!(Position::new(1, 5), index.position(17));
+        assert_eq!(Position::new(2, 4), index.position(content.len()));
+
+        assert_eq!(12, index.offset(Position::new(1, 2)));
+        assert_eq!(17, index.offset(Position::new(1, 5)));
+        assert_eq!(22, index.offset(Position::new(1, 100)));
+        assert_eq!(content.len(), index.offset(Position::new(10, 0)));
+    }
+}
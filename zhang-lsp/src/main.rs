@@ -0,0 +1,8 @@
+use env_logger::Env;
+
+#[tokio::main]
+async fn main() {
+    // the stdout is used by the protocol, logs are written to stderr
+    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    zhang_lsp::serve_stdio().await;
+}
@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use itertools::Itertools;
+use log::{error, info};
+use pest::error::LineColLocation;
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, Diagnostic, DiagnosticSeverity, Hover, HoverContents, Location, MarkupContent, MarkupKind, Position, Range, Url,
+};
+use zhang_ast::error::ErrorSeverity;
+use zhang_core::data_source::{DataSource, FileOverlay, LocalFileSystemDataSource};
+use zhang_core::data_type::text::parser::parse;
+use zhang_core::data_type::text::ZhangDataType;
+use zhang_core::directive_cache::DirectiveCache;
+use zhang_core::ledger::Ledger;
+use zhang_core::span_index::SpanIndex;
+use zhang_core::ZhangError;
+
+use crate::line_index::LineIndex;
+
+/// the ledger opened by editor, the unsaved documents take precedence over the files on disk
+pub struct Workspace {
+    root: PathBuf,
+    endpoint: String,
+    overlay: FileOverlay,
+    data_source: Arc<dyn DataSource>,
+    /// the latest ledger loaded successfully, it is kept when the documents cannot be parsed
+    ledger: Option<Ledger>,
+    index: SpanIndex,
+    /// the file and error of the latest failed loading
+    load_error: Option<(PathBuf, ZhangError)>,
+}
+
+/// the characters of account names and commodities
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, ':' | '-' | '_' | '\'' | '.')
+}
+
+/// the word around the offset, e.g. the account name or commodity under cursor
+fn word_at(content: &str, offset: usize) -> Option<&str> {
+    let offset = offset.min(content.len());
+    let start = content[..offset].rfind(|c: char| !is_word_char(c)).map(|it| it + 1).unwrap_or(0);
+    let end = content[offset..]
+        .find(|c: char| !is_word_char(c))
+        .map(|it| offset + it)
+        .unwrap_or(content.len());
+    Some(&content[start..end]).filter(|it| !it.is_empty())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum CompletionContext {
+    Account,
+    Commodity,
+    Payee,
+}
+
+/// guess what is being typed from the text before cursor in the same line
+fn completion_context(line: &str) -> CompletionContext {
+    let is_dated = line.starts_with(|c: char| c.is_ascii_digit());
+    if is_dated && line.matches('"').count() % 2 == 1 {
+        return CompletionContext::Payee;
+    }
+    let mut tokens = line.split_whitespace().rev();
+    if !line.ends_with(char::is_whitespace) {
+        tokens.next();
+    }
+    let is_after_number = tokens
+        .next()
+        .map(|it| it.trim_start_matches(['-', '+']).starts_with(|c: char| c.is_ascii_digit()))
+        .unwrap_or(false);
+    if is_after_number || (is_dated && line.contains(" commodity ")) {
+        CompletionContext::Commodity
+    } else {
+        CompletionContext::Account
+    }
+}
+
+impl Workspace {
+    pub fn new(root: PathBuf, endpoint: String) -> Self {
+        let overlay = FileOverlay::default();
+        let data_source = LocalFileSystemDataSource::new(ZhangDataType {})
+            .with_directive_cache(DirectiveCache::in_memory())
+            .with_overlay(overlay.clone());
+        Workspace {
+            root,
+            endpoint,
+            overlay,
+            data_source: Arc::new(data_source),
+            ledger: None,
+            index: SpanIndex::default(),
+            load_error: None,
+        }
+    }
+
+    /// set the unsaved content of document
+    pub fn update(&mut self, path: PathBuf, content: String) {
+        if let Ok(mut overlay) = self.overlay.write() {
+            overlay.insert(canonical(&path), content);
+        }
+    }
+
+    /// the document is closed, the file on disk is used again
+    pub fn close(&mut self, path: &Path) {
+        if let Ok(mut overlay) = self.overlay.write() {
+            overlay.remove(&canonical(path));
+        }
+    }
+
+    /// reload the ledger, only the changed documents are parsed again
+    pub fn reload(&mut self) {
+        match Ledger::load_with_data_source(self.root.clone(), self.endpoint.clone(), self.data_source.clone()) {
+            Ok(ledger) => {
+                info!("ledger is reloaded");
+                self.index = ledger.span_index();
+                self.ledger = Some(ledger);
+                self.load_error = None;
+            }
+            Err(e) => {
+                error!("cannot load ledger: {}", e);
+                let file = match &e {
+                    ZhangError::PestError { path, .. } => PathBuf::from(path),
+                    _ => self.root.join(&self.endpoint),
+                };
+                self.load_error = Some((canonical(&file), e));
+            }
+        }
+    }
+
+    /// the content of file from overlay or disk
+    pub fn content(&self, path: &Path) -> Option<String> {
+        let content = self.data_source.get(canonical(path).to_string_lossy().to_string()).ok()?;
+        String::from_utf8(content).ok()
+    }
+
+    /// the diagnostics of all files of ledger, the files without diagnostics are included to clear the stale ones
+    pub fn diagnostics(&self) -> HashMap<PathBuf, Vec<Diagnostic>> {
+        let mut ret: HashMap<PathBuf, Vec<Diagnostic>> = HashMap::new();
+        if let Some(ledger) = &self.ledger {
+            for file in &ledger.visited_files {
+                ret.entry(canonical(file)).or_default();
+            }
+            let diagnostics = ledger.diagnostics().unwrap_or_default();
+            let mut contents: HashMap<PathBuf, Option<String>> = HashMap::new();
+            for diagnostic in diagnostics {
+                let Some(file) = diagnostic.file.as_deref().map(canonical) else {
+                    continue;
+                };
+                let content = contents.entry(file.clone()).or_insert_with(|| self.content(&file));
+                let range = match (content, diagnostic.start, diagnostic.end) {
+                    (Some(content), Some(start), Some(end)) => LineIndex::new(content).range(start, end),
+                    _ => Range::default(),
+                };
+                let metas = diagnostic.metas.iter().sorted().map(|(key, value)| format!("{key}: {value}")).join(", ");
+                let message = if metas.is_empty() {
+                    diagnostic.kind.to_string()
+                } else {
+                    format!("{} ({})", diagnostic.kind, metas)
+                };
+                let severity = match diagnostic.severity {
+                    ErrorSeverity::Error => DiagnosticSeverity::ERROR,
+                    ErrorSeverity::Warning => DiagnosticSeverity::WARNING,
+                    ErrorSeverity::Info => DiagnosticSeverity::INFORMATION,
+                };
+                ret.entry(file).or_default().push(Diagnostic {
+                    range,
+                    severity: Some(severity),
+                    code: Some(tower_lsp::lsp_types::NumberOrString::String(diagnostic.kind.to_string())),
+                    source: Some("zhang".to_owned()),
+                    message,
+                    ..Diagnostic::default()
+                });
+            }
+        }
+        if let Some((file, e)) = &self.load_error {
+            let range = self
+                .content(file)
+                .and_then(|content| parse(&content, None).err())
+                .map(|e| match e.line_col {
+                    LineColLocation::Pos((line, column)) => Range::new(lsp_position(line, column), lsp_position(line, column)),
+                    LineColLocation::Span(start, end) => Range::new(lsp_position(start.0, start.1), lsp_position(end.0, end.1)),
+                })
+                .unwrap_or_default();
+            ret.insert(
+                file.clone(),
+                vec![Diagnostic {
+                    range,
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    source: Some("zhang".to_owned()),
+                    message: e.to_string(),
+                    ..Diagnostic::default()
+                }],
+            );
+        }
+        ret
+    }
+
+    pub fn completions(&self, path: &Path, position: Position) -> Vec<CompletionItem> {
+        let Some(ledger) = &self.ledger else {
+            return vec![];
+        };
+        let Some(content) = self.content(path) else {
+            return vec![];
+        };
+        let offset = LineIndex::new(&content).offset(position);
+        let line_start = content[..offset].rfind('\n').map(|it| it + 1).unwrap_or(0);
+        let mut operations = ledger.operations();
+        let (labels, kind) = match completion_context(&content[line_start..offset]) {
+            CompletionContext::Account => (operations.all_accounts().unwrap_or_default(), CompletionItemKind::FIELD),
+            CompletionContext::Commodity => (operations.read().commodities.keys().cloned().collect_vec(), CompletionItemKind::UNIT),
+            CompletionContext::Payee => (operations.all_payees().unwrap_or_default(), CompletionItemKind::TEXT),
+        };
+        labels
+            .into_iter()
+            .sorted()
+            .map(|label| CompletionItem {
+                label,
+                kind: Some(kind),
+                ..CompletionItem::default()
+            })
+            .collect_vec()
+    }
+
+    /// the location of `open` directive of account or `commodity` directive of commodity under cursor
+    pub fn definition(&self, path: &Path, position: Position) -> Option<Location> {
+        let content = self.content(path)?;
+        let word = word_at(&content, LineIndex::new(&content).offset(position))?;
+        let span = self.index.account_definition(word).or_else(|| self.index.commodity_definition(word))?;
+        let file = span.filename.as_deref()?;
+        let file_content = self.content(file)?;
+        Some(Location::new(
+            Url::from_file_path(canonical(file)).ok()?,
+            LineIndex::new(&file_content).range(span.start, span.end),
+        ))
+    }
+
+    /// the latest balances of account under cursor
+    pub fn hover(&self, path: &Path, position: Position) -> Option<Hover> {
+        let ledger = self.ledger.as_ref()?;
+        let content = self.content(path)?;
+        let word = word_at(&content, LineIndex::new(&content).offset(position))?;
+        let mut operations = ledger.operations();
+        if !operations.exist_account(word).ok()? {
+            return None;
+        }
+        let balances = operations.single_account_latest_balances(word).ok()?;
+        let balances = if balances.is_empty() {
+            "no postings".to_owned()
+        } else {
+            balances.iter().map(|it| format!("- {} {}", it.balance_number, it.balance_commodity)).join("\n")
+        };
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("**{}**\n\n{}", word, balances),
+            }),
+            range: None,
+        })
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// the position of pest, whose line and column start from 1
+fn lsp_position(line: usize, column: usize) -> Position {
+    Position::new(line.saturating_sub(1) as u32, column.saturating_sub(1) as u32)
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+    use tempfile::tempdir;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, HoverContents, Position};
+
+    use crate::workspace::{completion_context, word_at, CompletionContext, Workspace};
+
+    const CONTENT: &str = indoc! {r#"
+        1970-01-01 commodity CNY
+        1970-01-01 open Assets:MyCard
+        1970-01-01 open Expenses:Lunch
+        1970-01-02 "KFC"
+          Assets:MyCard -50 CNY
+          Expenses:Lunch
+        1970-01-03 close Expenses:Food
+    "#};
+
+    fn workspace() -> Workspace {
+        let root = tempdir().unwrap().into_path();
+        std::fs::write(root.join("main.zhang"), CONTENT).unwrap();
+        let mut workspace = Workspace::new(root, "main.zhang".to_owned());
+        workspace.reload();
+        workspace
+    }
+
+    #[test]
+    fn should_get_word_and_completion_context() {
+        assert_eq!(Some("Assets:MyCard"), word_at("  Assets:MyCard -50 CNY", 5));
+        assert_eq!(Some("CNY"), word_at("  Assets:MyCard -50 CNY", 23));
+        assert_eq!(None, word_at("a  b", 2));
+
+        assert_eq!(CompletionContext::Account, completion_context("  Assets:My"));
+        assert_eq!(CompletionContext::Commodity, completion_context("  Assets:MyCard -50 C"));
+        assert_eq!(CompletionContext::Commodity, completion_context("  Assets:MyCard -50 "));
+        assert_eq!(CompletionContext::Payee, completion_context("1970-01-02 \"K"));
+        assert_eq!(CompletionContext::Account, completion_context("1970-01-02 open "));
+    }
+
+    #[test]
+    fn should_provide_diagnostics_definition_and_hover() {
+        let workspace = workspace();
+        let file = workspace.root.join("main.zhang").canonicalize().unwrap();
+
+        let diagnostics = workspace.diagnostics();
+        let diagnostics = diagnostics.get(&file).unwrap();
+        assert_eq!(1, diagnostics.len());
+        assert_eq!(Some(DiagnosticSeverity::ERROR), diagnostics[0].severity);
+        assert_eq!(Position::new(6, 0), diagnostics[0].range.start);
+
+        let definition = workspace.definition(&file, Position::new(4, 5)).unwrap();
+        assert_eq!(Position::new(1, 0), definition.range.start);
+
+        let hover = workspace.hover(&file, Position::new(4, 5)).unwrap();
+        let HoverContents::Markup(markup) = hover.contents else {
+            panic!("hover should be markup");
+        };
+        assert!(markup.value.contains("-50 CNY"));
+
+        let completions = workspace.completions(&file, Position::new(5, 4));
+        assert!(completions.iter().any(|it| it.label == "Expenses:Lunch"));
+    }
+
+    #[test]
+    fn should_reload_unsaved_document() {
+        let mut workspace = workspace();
+        let file = workspace.root.join("main.zhang");
+
+        workspace.update(file.clone(), format!("{}1970-01-04 open", CONTENT));
+        workspace.reload();
+        let diagnostics = workspace.diagnostics();
+        let diagnostics = diagnostics.get(&file.canonicalize().unwrap()).unwrap();
+        assert_eq!(2, diagnostics.len());
+        let parse_error = diagnostics.iter().find(|it| it.message.starts_with("ParseError")).unwrap();
+        assert_eq!(7, parse_error.range.start.line);
+
+        workspace.update(file.clone(), CONTENT.replace("1970-01-03 close Expenses:Food\n", ""));
+        workspace.reload();
+        assert!(workspace.diagnostics().values().all(|it| it.is_empty()));
+    }
+}
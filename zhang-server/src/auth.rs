@@ -0,0 +1,164 @@
+//! the authentication of requests by the basic credential or the static tokens, each of which grants a role to its user.
+//!
+//! the token is given by header `Authorization: Bearer {TOKEN}`, or by the basic credential `{NAME}:{TOKEN}` so that
+//! the browser can prompt for it. the authenticated user is put into the request extensions for the write-back endpoints.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, Method};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use base64::Engine as _;
+use serde::Serialize;
+use strum::{AsRefStr, EnumString};
+use utoipa::ToSchema;
+use zhang_ast::{Directive, ZhangString};
+
+use crate::error::ServerError;
+
+/// the meta recording the user who creates the directive via server
+pub const CREATED_BY: &str = "created-by";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, AsRefStr, EnumString, ToSchema)]
+#[strum(serialize_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum Role {
+    /// only the queries are allowed
+    ReadOnly,
+    /// the directives can be created, edited and deleted
+    Write,
+    /// the files of ledger can be edited as a whole
+    Admin,
+}
+
+impl Role {
+    /// the role required by request, the raw files editing requires admin, and the other requests except queries require write.
+    /// the GraphQL query is posted, but it is read-only
+    pub fn required_by(method: &Method, path: &str) -> Role {
+        if matches!(*method, Method::GET | Method::HEAD) || path == "/api/graphql" {
+            Role::ReadOnly
+        } else if path.starts_with("/api/files/") {
+            Role::Admin
+        } else {
+            Role::Write
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct AuthUser {
+    pub name: String,
+    pub role: Role,
+}
+
+impl AuthUser {
+    /// record the user into the `created-by` meta of new directives
+    pub fn sign(&self, directives: &mut [Directive]) {
+        for meta in directives.iter_mut().filter_map(Directive::get_meta_mut) {
+            meta.insert(CREATED_BY.to_owned(), ZhangString::quote(&self.name));
+        }
+    }
+}
+
+/// the user of request, `None` if the authentication is not enabled
+pub type CurrentUser = Option<Extension<AuthUser>>;
+
+/// record the user of request into the `created-by` meta of new directives, if the authentication is enabled
+pub fn sign_directives(user: &CurrentUser, directives: &mut [Directive]) {
+    if let Some(Extension(user)) = user {
+        user.sign(directives);
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Authenticator {
+    /// the name and password of basic credential, whose user is admin
+    basic_credential: Option<(String, String)>,
+    tokens: HashMap<String, AuthUser>,
+}
+
+impl Authenticator {
+    /// the basic credential in the format of `{NAME}:{PASSWORD}`
+    pub fn with_basic_credential(mut self, credential: &str) -> Result<Self, String> {
+        let (name, password) = credential
+            .split_once(':')
+            .ok_or_else(|| format!("auth credential '{}' should be in the format of 'NAME:PASSWORD'", credential))?;
+        self.basic_credential = Some((name.to_owned(), password.to_owned()));
+        Ok(self)
+    }
+
+    /// the static token in the format of `{NAME}:{ROLE}:{TOKEN}`, the role is one of `read-only`, `write` and `admin`
+    pub fn with_token(mut self, token: &str) -> Result<Self, String> {
+        let invalid_token = || format!("auth token '{}' should be in the format of 'NAME:read-only|write|admin:TOKEN'", token);
+        let mut parts = token.splitn(3, ':');
+        let (Some(name), Some(role), Some(secret)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(invalid_token());
+        };
+        let role = Role::from_str(role).map_err(|_| invalid_token())?;
+        if name.is_empty() || secret.is_empty() {
+            return Err(invalid_token());
+        }
+        self.tokens.insert(secret.to_owned(), AuthUser { name: name.to_owned(), role });
+        Ok(self)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.basic_credential.is_some() || !self.tokens.is_empty()
+    }
+
+    pub fn authenticate(&self, headers: &HeaderMap) -> Option<AuthUser> {
+        let authorization = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+        if let Some(token) = authorization.strip_prefix("Bearer ") {
+            return self.token_user(token.trim()).cloned();
+        }
+        let decoded = BASE64_STANDARD.decode(authorization.strip_prefix("Basic ")?.trim()).ok()?;
+        let (name, password) = std::str::from_utf8(&decoded).ok()?.split_once(':')?;
+        if let Some((basic_name, basic_password)) = &self.basic_credential {
+            if constant_time_eq(name.as_bytes(), basic_name.as_bytes()) & constant_time_eq(password.as_bytes(), basic_password.as_bytes()) {
+                return Some(AuthUser {
+                    name: name.to_owned(),
+                    role: Role::Admin,
+                });
+            }
+        }
+        self.token_user(password).filter(|user| user.name.eq(name)).cloned()
+    }
+
+    fn token_user(&self, token: &str) -> Option<&AuthUser> {
+        self.tokens
+            .iter()
+            .find(|(secret, _)| constant_time_eq(secret.as_bytes(), token.as_bytes()))
+            .map(|(_, user)| user)
+    }
+}
+
+/// the middleware rejects the request whose user is unknown or has no role required, the CORS preflight is always passed
+pub async fn authorize(State(authenticator): State<Arc<Authenticator>>, mut request: Request, next: Next) -> Response {
+    if request.method() == Method::OPTIONS {
+        return next.run(request).await;
+    }
+    let Some(user) = authenticator.authenticate(request.headers()) else {
+        return ServerError::Unauthorized.into_response();
+    };
+    let required_role = Role::required_by(request.method(), request.uri().path());
+    if user.role < required_role {
+        return ServerError::Forbidden(format!(
+            "user {} is {}, but {} is required",
+            user.name,
+            user.role.as_ref(),
+            required_role.as_ref()
+        ))
+        .into_response();
+    }
+    request.extensions_mut().insert(user);
+    next.run(request).await
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
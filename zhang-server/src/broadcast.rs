@@ -7,13 +7,28 @@ use serde::Serialize;
 use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::sync::Mutex;
 use tokio::time::interval;
+use zhang_core::ledger::LedgerChanges;
 
 #[derive(Debug, Serialize)]
 #[serde(tag = "type")]
 pub enum BroadcastEvent {
-    Reload,
+    /// the ledger is reloaded, along with the changed files which cause the reload, so that clients can refresh
+    /// the views affected by the changes only
+    Reload {
+        ledger: String,
+        changed_files: Vec<String>,
+        duration_ms: u128,
+        error_count: usize,
+        changes: LedgerChanges,
+    },
+    ReloadFailed {
+        ledger: String,
+        error: String,
+    },
     Connected,
-    NewVersionFound { version: String },
+    NewVersionFound {
+        version: String,
+    },
 }
 
 impl BroadcastEvent {
@@ -1,8 +1,9 @@
-use axum::http::StatusCode;
+use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::Json;
-use serde_json::json;
+use serde::Serialize;
 use thiserror::Error;
+use utoipa::ToSchema;
 use zhang_ast::account::InvalidAccountError;
 use zhang_core::ZhangError;
 
@@ -19,6 +20,18 @@ pub enum ServerError {
 
     #[error("io error: {0}")]
     StrumError(#[from] strum::ParseError),
+
+    #[error("ledger {0} is not found")]
+    LedgerNotFound(String),
+
+    #[error("bad request: {0}")]
+    BadRequest(String),
+
+    #[error("authentication is required")]
+    Unauthorized,
+
+    #[error("forbidden: {0}")]
+    Forbidden(String),
 }
 
 impl From<InvalidAccountError> for ServerError {
@@ -27,13 +40,31 @@ impl From<InvalidAccountError> for ServerError {
     }
 }
 
+/// the body of failed response
+#[derive(Serialize, ToSchema)]
+pub struct ErrorResponse {
+    pub message: String,
+    pub origin: String,
+}
+
 impl IntoResponse for ServerError {
     fn into_response(self) -> Response {
-        let payload = json!({
-            "message": format!("{}", self),
-            "origin": "with_rejection"
-        });
+        let payload = ErrorResponse {
+            message: format!("{}", self),
+            origin: "with_rejection".to_owned(),
+        };
 
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(payload)).into_response()
+        let status = match self {
+            ServerError::LedgerNotFound(_) => StatusCode::NOT_FOUND,
+            ServerError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ServerError::Unauthorized => {
+                // the browser prompts for the credential, which is either the basic credential or the name and token of user
+                let headers = [(header::WWW_AUTHENTICATE, "Basic realm=\"zhang\"")];
+                return (StatusCode::UNAUTHORIZED, headers, Json(payload)).into_response();
+            }
+            ServerError::Forbidden(_) => StatusCode::FORBIDDEN,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(payload)).into_response()
     }
 }
@@ -0,0 +1,47 @@
+//! the read-only GraphQL endpoint, whose fields are resolved from the same operations as the REST endpoints.
+//!
+//! only the query operation is supported, the schema can be introspected by the GraphQL clients.
+
+use std::sync::OnceLock;
+
+use async_graphql::{EmptyMutation, EmptySubscription, Request, Response, Schema};
+use axum::Json;
+
+use crate::graphql::schema::Query;
+use crate::openapi::LedgerHeader;
+use crate::state::SelectedLedger;
+
+pub mod schema;
+
+pub type LedgerSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// the max depth of query, which is deep enough for the introspection query of GraphQL clients
+const MAX_QUERY_DEPTH: usize = 16;
+/// the max complexity of query, each field counts one
+const MAX_QUERY_COMPLEXITY: usize = 500;
+
+/// the schema is built once, the ledger queried is given as the data of each request.
+/// the queries too deep or too complex are rejected before execution
+pub fn ledger_schema() -> &'static LedgerSchema {
+    static SCHEMA: OnceLock<LedgerSchema> = OnceLock::new();
+    SCHEMA.get_or_init(|| {
+        Schema::build(Query, EmptyMutation, EmptySubscription)
+            .limit_depth(MAX_QUERY_DEPTH)
+            .limit_complexity(MAX_QUERY_COMPLEXITY)
+            .finish()
+    })
+}
+
+/// the failed query is responded with the errors, and without data if it is rejected before execution, as the GraphQL over HTTP
+#[utoipa::path(
+    post,
+    path = "/api/graphql",
+    tag = "graphql",
+    params(LedgerHeader),
+    request_body = serde_json::Value,
+    responses((status = 200, description = "OK", content_type = "application/json", body = serde_json::Value))
+)]
+pub async fn graphql(ledger: SelectedLedger, Json(request): Json<Request>) -> Json<Response> {
+    let operations = ledger.read().await.operations();
+    Json(ledger_schema().execute(request.data(operations)).await)
+}
@@ -0,0 +1,425 @@
+//! the types of GraphQL schema, whose fields are named as the ones of REST responses.
+//!
+//! ```graphql
+//! type Query {
+//!   accounts(status: String, type: String, prefix: String): [Account!]!
+//!   account(name: String!): Account
+//!   journals(from: NaiveDate, to: NaiveDate, account: String, payee: String, tag: String, link: String, keyword: String, offset: Int, limit: Int): [Journal!]!
+//!   balances(account: String): [Balance!]!
+//!   budgets(category: String, closed: Boolean): [Budget!]!
+//!   prices(commodity: String, target: String, from: NaiveDate, to: NaiveDate): [Price!]!
+//!   documents(account: String, transaction: UUID): [Document!]!
+//! }
+//! ```
+//! the relations like `Account.journals` are resolved only if they are selected.
+
+use std::str::FromStr;
+
+use async_graphql::{Context, Object, Result, SimpleObject};
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use itertools::Itertools;
+use uuid::Uuid;
+use zhang_ast::amount::Amount as AstAmount;
+use zhang_core::domains::schemas::{AccountDomain, AccountJournalDomain, BudgetReportDomain, BudgetReportInterval, MetaType, PriceDomain};
+use zhang_core::domains::{JournalFilter, Operations};
+use zhang_core::store::{BudgetDomain, DocumentDomain, TransactionDomain};
+
+use crate::response::{DocumentResponse, JournalTransactionPostingResponse, MetaResponse};
+
+/// the default count of journals returned if `limit` is not given
+const DEFAULT_JOURNAL_LIMIT: usize = 100;
+
+/// the operations of ledger queried, which are given as the data of schema
+fn operations(ctx: &Context<'_>) -> Operations {
+    let operations = ctx.data_unchecked::<Operations>();
+    Operations {
+        timezone: operations.timezone,
+        store: operations.store.clone(),
+    }
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    async fn accounts(
+        &self, ctx: &Context<'_>, status: Option<String>, #[graphql(name = "type")] account_type: Option<String>, prefix: Option<String>,
+    ) -> Vec<Account> {
+        let operations = operations(ctx);
+        let store = operations.read();
+        store
+            .accounts
+            .values()
+            .filter(|account| status.as_ref().map(|status| account.status.as_ref().eq(status)).unwrap_or(true))
+            .filter(|account| account_type.as_ref().map(|account_type| account.r#type.eq(account_type)).unwrap_or(true))
+            .filter(|account| prefix.as_ref().map(|prefix| account.name.starts_with(prefix)).unwrap_or(true))
+            .sorted_by(|a, b| a.name.cmp(&b.name))
+            .cloned()
+            .map(Account)
+            .collect_vec()
+    }
+
+    async fn account(&self, ctx: &Context<'_>, name: String) -> Result<Option<Account>> {
+        Ok(operations(ctx).account(&name)?.map(Account))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn journals(
+        &self, ctx: &Context<'_>, from: Option<NaiveDate>, to: Option<NaiveDate>, account: Option<String>, payee: Option<String>, tag: Option<String>,
+        link: Option<String>, keyword: Option<String>, offset: Option<usize>, limit: Option<usize>,
+    ) -> Result<Vec<Journal>> {
+        let operations = operations(ctx);
+        let timezone = operations.timezone;
+        let filter = JournalFilter {
+            from: from.map(|from| start_of_date(from, timezone)).transpose()?,
+            to: to.map(|to| end_of_date(to, timezone)).transpose()?,
+            account,
+            payee,
+            tag,
+            link,
+            keyword,
+        };
+        let (_, journals) = operations.journals(&filter, offset.unwrap_or(0), limit.unwrap_or(DEFAULT_JOURNAL_LIMIT))?;
+        Ok(journals.into_iter().map(Journal).collect_vec())
+    }
+
+    /// the latest balances of account, or of all accounts if it is absent
+    async fn balances(&self, ctx: &Context<'_>, account: Option<String>) -> Result<Vec<Balance>> {
+        let mut operations = operations(ctx);
+        let accounts = match account {
+            Some(account) => vec![account],
+            None => operations.all_accounts()?.into_iter().sorted().collect_vec(),
+        };
+        let mut balances = vec![];
+        for account in accounts {
+            for balance in operations.single_account_latest_balances(&account)? {
+                balances.push(Balance {
+                    datetime: balance.datetime,
+                    account: balance.account,
+                    account_status: balance.account_status.as_ref().to_owned(),
+                    number: balance.balance_number,
+                    commodity: balance.balance_commodity,
+                });
+            }
+        }
+        Ok(balances)
+    }
+
+    async fn budgets(&self, ctx: &Context<'_>, category: Option<String>, closed: Option<bool>) -> Result<Vec<Budget>> {
+        Ok(operations(ctx)
+            .all_budgets()?
+            .into_iter()
+            .filter(|budget| category.is_none() || budget.category.eq(&category))
+            .filter(|budget| closed.map(|closed| budget.closed == closed).unwrap_or(true))
+            .sorted_by(|a, b| a.name.cmp(&b.name))
+            .map(Budget)
+            .collect_vec())
+    }
+
+    async fn prices(&self, ctx: &Context<'_>, commodity: Option<String>, target: Option<String>, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Vec<Price> {
+        let operations = operations(ctx);
+        let store = operations.read();
+        store
+            .prices
+            .iter()
+            .filter(|price| commodity.as_ref().map(|it| price.commodity.eq(it)).unwrap_or(true))
+            .filter(|price| target.as_ref().map(|it| price.target_commodity.eq(it)).unwrap_or(true))
+            .filter(|price| from.map(|from| price.datetime.date() >= from).unwrap_or(true))
+            .filter(|price| to.map(|to| price.datetime.date() <= to).unwrap_or(true))
+            .sorted_by_key(|price| price.datetime)
+            .cloned()
+            .map(Price::from)
+            .collect_vec()
+    }
+
+    async fn documents(&self, ctx: &Context<'_>, account: Option<String>, transaction: Option<Uuid>) -> Vec<Document> {
+        documents(&operations(ctx), |document| {
+            account.as_ref().map(|account| document.document_type.match_account(account)).unwrap_or(true)
+                && transaction.map(|id| document.document_type.as_trx() == Some(id.to_string())).unwrap_or(true)
+        })
+    }
+}
+
+pub struct Account(AccountDomain);
+
+#[Object]
+impl Account {
+    async fn date(&self) -> NaiveDateTime {
+        self.0.date
+    }
+
+    #[graphql(name = "type")]
+    async fn account_type(&self) -> &str {
+        &self.0.r#type
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn status(&self) -> &str {
+        self.0.status.as_ref()
+    }
+
+    async fn alias(&self) -> Option<&str> {
+        self.0.alias.as_deref()
+    }
+
+    async fn balances(&self, ctx: &Context<'_>) -> Result<Vec<Amount>> {
+        Ok(operations(ctx)
+            .single_account_latest_balances(&self.0.name)?
+            .into_iter()
+            .map(|balance| Amount {
+                number: balance.balance_number,
+                commodity: balance.balance_commodity,
+            })
+            .collect_vec())
+    }
+
+    async fn journals(&self, ctx: &Context<'_>, from: Option<NaiveDate>, to: Option<NaiveDate>, limit: Option<usize>) -> Result<Vec<AccountJournal>> {
+        Ok(operations(ctx)
+            .account_journals(&self.0.name)?
+            .into_iter()
+            .filter(|journal| from.map(|from| journal.datetime.date() >= from).unwrap_or(true))
+            .filter(|journal| to.map(|to| journal.datetime.date() <= to).unwrap_or(true))
+            .take(limit.unwrap_or(DEFAULT_JOURNAL_LIMIT))
+            .map(AccountJournal::from)
+            .collect_vec())
+    }
+
+    async fn documents(&self, ctx: &Context<'_>) -> Vec<Document> {
+        documents(&operations(ctx), |document| document.document_type.match_account(&self.0.name))
+    }
+}
+
+pub struct Journal(TransactionDomain);
+
+#[Object]
+impl Journal {
+    async fn id(&self) -> Uuid {
+        self.0.id
+    }
+
+    async fn sequence(&self) -> i32 {
+        self.0.sequence
+    }
+
+    async fn datetime(&self) -> NaiveDateTime {
+        self.0.datetime.naive_local()
+    }
+
+    async fn flag(&self) -> String {
+        self.0.flag.to_string()
+    }
+
+    async fn payee(&self) -> Option<&str> {
+        self.0.payee.as_deref()
+    }
+
+    async fn narration(&self) -> Option<&str> {
+        self.0.narration.as_deref()
+    }
+
+    async fn tags(&self) -> &[String] {
+        &self.0.tags
+    }
+
+    async fn links(&self) -> &[String] {
+        &self.0.links
+    }
+
+    async fn postings(&self) -> Vec<JournalTransactionPostingResponse> {
+        self.0.postings.iter().cloned().map(JournalTransactionPostingResponse::from).collect_vec()
+    }
+
+    async fn metas(&self, ctx: &Context<'_>) -> Result<Vec<MetaResponse>> {
+        Ok(operations(ctx)
+            .metas(MetaType::TransactionMeta, self.0.id.to_string())?
+            .into_iter()
+            .map(MetaResponse::from)
+            .collect_vec())
+    }
+
+    async fn documents(&self, ctx: &Context<'_>) -> Vec<Document> {
+        let id = self.0.id.to_string();
+        documents(&operations(ctx), |document| document.document_type.as_trx().as_ref() == Some(&id))
+    }
+}
+
+pub struct Budget(BudgetDomain);
+
+#[Object]
+impl Budget {
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn alias(&self) -> Option<&str> {
+        self.0.alias.as_deref()
+    }
+
+    async fn category(&self) -> Option<&str> {
+        self.0.category.as_deref()
+    }
+
+    async fn closed(&self) -> bool {
+        self.0.closed
+    }
+
+    async fn commodity(&self) -> &str {
+        &self.0.commodity
+    }
+
+    /// the value of meta `rollover`, e.g. `carry_positive`
+    async fn rollover(&self) -> &str {
+        self.0.rollover.as_ref()
+    }
+
+    /// the report of budget in each interval, which is `month` or `quarter`
+    async fn report(&self, ctx: &Context<'_>, interval: Option<String>) -> Result<Vec<BudgetReport>> {
+        let interval = match interval {
+            Some(interval) => BudgetReportInterval::from_str(&interval).map_err(|_| format!("invalid budget report interval: {}", interval))?,
+            None => BudgetReportInterval::Month,
+        };
+        Ok(operations(ctx)
+            .budget_report(interval)?
+            .into_iter()
+            .filter(|it| it.name == self.0.name)
+            .map(BudgetReport::from)
+            .collect_vec())
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Amount {
+    pub number: BigDecimal,
+    pub commodity: String,
+}
+
+impl From<AstAmount> for Amount {
+    fn from(amount: AstAmount) -> Self {
+        Amount {
+            number: amount.number,
+            commodity: amount.currency,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(rename_fields = "snake_case")]
+pub struct AccountJournal {
+    pub datetime: NaiveDateTime,
+    pub timestamp: i64,
+    pub account: String,
+    pub trx_id: String,
+    pub payee: Option<String>,
+    pub narration: Option<String>,
+    pub inferred_unit_number: BigDecimal,
+    pub inferred_unit_commodity: String,
+    pub account_after_number: BigDecimal,
+    pub account_after_commodity: String,
+}
+
+impl From<AccountJournalDomain> for AccountJournal {
+    fn from(journal: AccountJournalDomain) -> Self {
+        AccountJournal {
+            datetime: journal.datetime,
+            timestamp: journal.timestamp,
+            account: journal.account,
+            trx_id: journal.trx_id,
+            payee: journal.payee,
+            narration: journal.narration,
+            inferred_unit_number: journal.inferred_unit_number,
+            inferred_unit_commodity: journal.inferred_unit_commodity,
+            account_after_number: journal.account_after_number,
+            account_after_commodity: journal.account_after_commodity,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(rename_fields = "snake_case")]
+pub struct Balance {
+    pub datetime: NaiveDateTime,
+    pub account: String,
+    pub account_status: String,
+    pub number: BigDecimal,
+    pub commodity: String,
+}
+
+#[derive(SimpleObject)]
+#[graphql(rename_fields = "snake_case")]
+pub struct BudgetReport {
+    pub name: String,
+    pub alias: Option<String>,
+    pub category: Option<String>,
+    /// `2023-11` for month, `2023-Q4` for quarter
+    pub period: String,
+    pub budgeted_amount: Amount,
+    pub actual_amount: Amount,
+    /// budgeted minus actual, negative means overspending
+    pub variance: Amount,
+    /// percent of budgeted amount consumed by actual spend, absent if nothing is budgeted
+    pub percent_consumed: Option<BigDecimal>,
+}
+
+impl From<BudgetReportDomain> for BudgetReport {
+    fn from(report: BudgetReportDomain) -> Self {
+        BudgetReport {
+            name: report.name,
+            alias: report.alias,
+            category: report.category,
+            period: report.period,
+            budgeted_amount: report.budgeted_amount.into(),
+            actual_amount: report.actual_amount.into(),
+            variance: report.variance.into(),
+            percent_consumed: report.percent_consumed,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(rename_fields = "snake_case")]
+pub struct Price {
+    pub datetime: NaiveDateTime,
+    pub commodity: String,
+    pub amount: BigDecimal,
+    pub target_commodity: String,
+}
+
+impl From<PriceDomain> for Price {
+    fn from(price: PriceDomain) -> Self {
+        Price {
+            datetime: price.datetime,
+            commodity: price.commodity,
+            amount: price.amount,
+            target_commodity: price.target_commodity,
+        }
+    }
+}
+
+pub type Document = DocumentResponse;
+
+fn documents(operations: &Operations, predicate: impl Fn(&DocumentDomain) -> bool) -> Vec<Document> {
+    let store = operations.read();
+    store
+        .documents
+        .iter()
+        .filter(|document| predicate(document))
+        .cloned()
+        .map(DocumentResponse::from)
+        .collect_vec()
+}
+
+fn start_of_date(date: NaiveDate, timezone: Tz) -> Result<DateTime<Utc>> {
+    date.and_hms_opt(0, 0, 0)
+        .and_then(|datetime| datetime.and_local_timezone(timezone).earliest())
+        .map(|datetime| datetime.with_timezone(&Utc))
+        .ok_or_else(|| format!("invalid date {}", date).into())
+}
+
+fn end_of_date(date: NaiveDate, timezone: Tz) -> Result<DateTime<Utc>> {
+    start_of_date(date + Duration::days(1), timezone).map(|datetime| datetime - Duration::nanoseconds(1))
+}
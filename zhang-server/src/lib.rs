@@ -4,40 +4,47 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use axum::extract::DefaultBodyLimit;
-use axum::routing::{get, post, put};
+use axum::middleware;
+use axum::routing::{delete, get, post, put};
 use axum::Router;
-use itertools::Itertools;
-use log::{debug, error, info, trace};
-use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use indexmap::IndexMap;
+use log::{debug, error, info};
 use routes::account::*;
 use routes::budget::*;
 use routes::commodity::*;
 use routes::common::*;
 use routes::document::*;
+use routes::export::*;
 use routes::file::*;
+use routes::ledger::*;
+use routes::query::*;
 use routes::statistics::*;
 use routes::transaction::*;
 use self_update::version::bump_is_greater;
 use serde::Serialize;
-use tokio::sync::mpsc::error::TryRecvError;
-use tokio::sync::mpsc::{channel, Receiver, Sender};
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{Notify, RwLock};
 use tower_http::cors::CorsLayer;
 use tower_http::limit::RequestBodyLimitLayer;
-use tower_http::validate_request::ValidateRequestHeaderLayer;
 use uuid::{uuid, Uuid};
 use zhang_core::data_source::DataSource;
 use zhang_core::ledger::Ledger;
 use zhang_core::utils::has_path_visited;
-use zhang_core::ZhangResult;
+use zhang_core::watcher::{self, LedgerWatcher};
+use zhang_core::{ZhangError, ZhangResult};
 
+use crate::auth::Authenticator;
 use crate::broadcast::{BroadcastEvent, Broadcaster};
 use crate::error::ServerError;
 use crate::response::ResponseWrapper;
 use crate::state::AppState;
 
+pub mod auth;
 pub mod broadcast;
 pub mod error;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod metrics;
+pub mod openapi;
 pub mod request;
 pub mod response;
 pub mod routes;
@@ -49,22 +56,11 @@ pub type ServerResult<T> = Result<T, ServerError>;
 
 pub type LedgerState = Arc<RwLock<Ledger>>;
 
-fn async_watcher() -> notify::Result<(RecommendedWatcher, Receiver<notify::Result<Event>>)> {
-    let (tx, rx) = channel(1);
-
-    // Automatically select the best implementation for your platform.
-    // You can also access each implementation directly e.g. INotifyWatcher.
-    let watcher = RecommendedWatcher::new(
-        move |res| {
-            futures::executor::block_on(async {
-                tx.send(res).await.unwrap();
-            })
-        },
-        Config::default(),
-    )?;
-
-    Ok((watcher, rx))
-}
+/// the ledgers served by their names, the first one is the default ledger
+pub type LedgerStates = Arc<IndexMap<String, LedgerState>>;
+
+/// the name of main ledger of server
+pub const DEFAULT_LEDGER_NAME: &str = "default";
 
 pub struct ServeConfig {
     pub path: PathBuf,
@@ -73,33 +69,98 @@ pub struct ServeConfig {
     pub port: u16,
     pub no_report: bool,
     pub data_source: Arc<dyn DataSource>,
-    pub auth_credential: Option<String>,
+    pub authenticator: Authenticator,
     pub is_local_fs: bool,
+    /// the ledgers served along with the main one, which are selected by their names per request
+    pub ledgers: Vec<LedgerConfig>,
+}
+
+pub struct LedgerConfig {
+    pub name: String,
+    pub path: PathBuf,
+    pub endpoint: String,
+    pub data_source: Arc<dyn DataSource>,
 }
 
-pub struct ReloadSender(pub Sender<i32>);
+/// the sender of reload request with the changed files, which is empty if the reload is not caused by file changes.
+/// the requests arriving while reloading are merged into one, so that the changed files of any ledger are never dropped
+#[derive(Default)]
+pub struct ReloadSender {
+    /// the changed files of requests not handled yet, `None` if there is no request
+    pending: std::sync::Mutex<Option<Vec<PathBuf>>>,
+    notify: Notify,
+}
 
 impl ReloadSender {
-    fn reload(&self) {
-        self.0.try_send(1).ok();
+    /// reload all ledgers
+    pub fn reload(&self) {
+        self.send(vec![]);
+    }
+
+    /// reload the ledgers containing the changed files, all ledgers are reloaded if no file is given
+    pub fn send(&self, changed_files: Vec<PathBuf>) {
+        let mut pending = self.pending.lock().unwrap();
+        *pending = Some(match pending.take() {
+            None => changed_files,
+            Some(files) if files.is_empty() || changed_files.is_empty() => vec![],
+            Some(mut files) => {
+                files.extend(changed_files);
+                files.sort();
+                files.dedup();
+                files
+            }
+        });
+        drop(pending);
+        self.notify.notify_one();
+    }
+
+    /// wait for the next request, along with the changed files of all requests merged since the last one
+    pub async fn recv(&self) -> Vec<PathBuf> {
+        loop {
+            if let Some(changed_files) = self.pending.lock().unwrap().take() {
+                return changed_files;
+            }
+            self.notify.notified().await;
+        }
     }
 }
 
 pub async fn serve(opts: ServeConfig) -> ZhangResult<()> {
     info!("version: {}, build date: {}", env!("ZHANG_BUILD_VERSION"), env!("ZHANG_BUILD_DATE"));
+    let start_time = Instant::now();
     let ledger = Ledger::async_load(opts.path.clone(), opts.endpoint.clone(), opts.data_source.clone()).await?;
-    let ledger_data = Arc::new(RwLock::new(ledger));
+    metrics::registry().record_reload(DEFAULT_LEDGER_NAME, start_time.elapsed());
+    let mut ledgers = IndexMap::new();
+    ledgers.insert(DEFAULT_LEDGER_NAME.to_owned(), Arc::new(RwLock::new(ledger)));
+    for config in &opts.ledgers {
+        if ledgers.contains_key(&config.name) {
+            return Err(ZhangError::CustomError(format!("ledger {} is defined more than once", config.name)));
+        }
+        info!("load ledger {} from {}", config.name, config.path.display());
+        let start_time = Instant::now();
+        let ledger = Ledger::async_load(config.path.clone(), config.endpoint.clone(), config.data_source.clone()).await?;
+        metrics::registry().record_reload(&config.name, start_time.elapsed());
+        ledgers.insert(config.name.clone(), Arc::new(RwLock::new(ledger)));
+    }
+    let ledger_data: LedgerStates = Arc::new(ledgers);
     let broadcaster = Broadcaster::create();
-    let (tx, rx) = mpsc::channel::<i32>(1);
-    let reload_sender = Arc::new(ReloadSender(tx));
-
-    info!("start reload listener");
-    start_reload_listener(ledger_data.clone(), broadcaster.clone(), rx);
+    let reload_sender = Arc::new(ReloadSender::default());
 
-    if opts.is_local_fs {
+    let watcher = if opts.is_local_fs {
         info!("start fs event listener");
-        start_fs_event_lisenter(ledger_data.clone(), reload_sender.clone());
-    }
+        let fs_ledgers = ledger_data.clone();
+        let fs_reload_sender = reload_sender.clone();
+        tokio::task::spawn_blocking(move || start_fs_event_lisenter(fs_ledgers, fs_reload_sender))
+            .await
+            .map_err(|e| ZhangError::CustomError(format!("cannot start fs event listener: {}", e)))?
+            .map_err(|e| error!("cannot watch ledger files, ledgers will not be reloaded on changes: {}", e))
+            .ok()
+    } else {
+        None
+    };
+
+    info!("start reload listener");
+    start_reload_listener(ledger_data.clone(), broadcaster.clone(), reload_sender.clone(), watcher);
 
     info!("start version report tasker");
     start_version_check_tasker(broadcaster.clone());
@@ -139,147 +200,215 @@ fn start_version_check_tasker(update_checker_broadcaster: Arc<Broadcaster>) {
     });
 }
 
-fn start_fs_event_lisenter(cloned_ledger: Arc<RwLock<Ledger>>, reload_sender_for_fs: Arc<ReloadSender>) {
-    tokio::spawn(async move {
-        let (mut watcher, mut rx) = async_watcher().unwrap();
-
-        let entry_path = {
-            let guard1 = cloned_ledger.read().await;
-            guard1.entry.0.clone()
-        };
-        info!("watching {}", &entry_path.to_str().unwrap_or(""));
-        watcher.watch(entry_path.as_path(), RecursiveMode::Recursive).expect("cannot watch entry path");
-        'looper: loop {
-            let mut all = vec![];
-            match rx.recv().await {
-                Some(event) => all.push(event),
-                None => break 'looper,
-            }
-            tokio::time::sleep(Duration::from_millis(500)).await;
-            'each_time: loop {
-                let result = rx.try_recv();
-                match result {
-                    Ok(event) => {
-                        all.push(event);
-                        tokio::time::sleep(Duration::from_millis(100)).await;
-                    }
-                    Err(TryRecvError::Empty) => break 'each_time,
-                    Err(TryRecvError::Disconnected) => break 'looper,
-                }
-            }
-            trace!("receive all file changes: {:?}", all);
-            let guard = cloned_ledger.read().await;
-            let is_visited_file_updated = all
-                .into_iter()
-                .filter_map(|event| event.ok())
-                .filter(|event| {
-                    let include_visited_files = event.paths.iter().any(|path| has_path_visited(&guard.visited_files, path));
-                    include_visited_files && event.kind.is_modify()
-                })
-                .count()
-                > 0;
-
-            drop(guard);
-
-            if is_visited_file_updated {
-                debug!("gotcha event, sending reload event...");
-                reload_sender_for_fs.0.try_send(1).ok();
-            }
+/// the watcher is shared with reload listener, so that the files included by reloaded ledgers are watched as well
+type SharedWatcher = Arc<std::sync::Mutex<LedgerWatcher>>;
+
+fn start_fs_event_lisenter(cloned_ledgers: LedgerStates, reload_sender_for_fs: Arc<ReloadSender>) -> ZhangResult<SharedWatcher> {
+    let (mut watcher, changes) = LedgerWatcher::new(watcher::DEFAULT_DEBOUNCE)?;
+    let mut visited_files = vec![];
+    for cloned_ledger in cloned_ledgers.values() {
+        let guard = cloned_ledger.blocking_read();
+        info!("watching files of {}", guard.entry.0.to_str().unwrap_or(""));
+        visited_files.extend(guard.visited_files.clone());
+    }
+    watcher.watch(visited_files)?;
+
+    std::thread::spawn(move || {
+        while let Ok(changed_files) = changes.recv() {
+            debug!("gotcha changes of {:?}, sending reload event...", changed_files);
+            reload_sender_for_fs.send(changed_files);
         }
     });
+    Ok(Arc::new(std::sync::Mutex::new(watcher)))
 }
 
-fn start_reload_listener(ledger_for_reload: Arc<RwLock<Ledger>>, cloned_broadcaster: Arc<Broadcaster>, mut rx: Receiver<i32>) {
+/// the ledgers containing changed files are reloaded, all ledgers are reloaded if the reload is requested without changed files.
+/// the reloaded ledger is built aside and swapped in once it is done, so that queries keep being served by the old ledger meanwhile.
+///
+/// the affected ledger is processed as a whole again, only the parsing of unchanged files is skipped by the directive cache
+fn start_reload_listener(
+    ledgers_for_reload: LedgerStates, cloned_broadcaster: Arc<Broadcaster>, reload_receiver: Arc<ReloadSender>, watcher: Option<SharedWatcher>,
+) {
     tokio::spawn(async move {
-        while rx.recv().await.is_some() {
-            info!("start reloading...");
-            let start_time = Instant::now();
-            let mut guard = ledger_for_reload.write().await;
-            match guard.async_reload().await {
-                Ok(_) => {
-                    let duration = start_time.elapsed();
-                    info!("ledger is reloaded successfully in {:?}", duration);
-                    // todo: add reload duration to reload event
-                    cloned_broadcaster.broadcast(BroadcastEvent::Reload).await;
+        loop {
+            let changed_files = reload_receiver.recv().await;
+            for (name, ledger_for_reload) in ledgers_for_reload.iter() {
+                let ((entry, endpoint), data_source) = {
+                    let guard = ledger_for_reload.read().await;
+                    if !changed_files.is_empty() && !changed_files.iter().any(|file| has_path_visited(&guard.visited_files, file)) {
+                        continue;
+                    }
+                    (guard.entry.clone(), guard.data_source.clone())
+                };
+                info!("start reloading ledger {}...", name);
+                let start_time = Instant::now();
+                match Ledger::async_load(entry, endpoint, data_source).await {
+                    Ok(reloaded_ledger) => {
+                        let duration = start_time.elapsed();
+                        info!("ledger {} is reloaded successfully in {:?}", name, duration);
+                        metrics::registry().record_reload(name, duration);
+                        match swap_reloaded_ledger(name, ledger_for_reload, reloaded_ledger, &changed_files, duration).await {
+                            Ok(event) => cloned_broadcaster.broadcast(event).await,
+                            Err(err) => error!("cannot get the changes of reloaded ledger {}: {}", name, err),
+                        }
+                    }
+                    Err(err) => {
+                        error!("error on reloading ledger {}: {}", name, err);
+                        metrics::registry().record_reload_failure(name);
+                        cloned_broadcaster
+                            .broadcast(BroadcastEvent::ReloadFailed {
+                                ledger: name.clone(),
+                                error: err.to_string(),
+                            })
+                            .await;
+                    }
+                }
+            }
+            if let Some(watcher) = &watcher {
+                let mut visited_files = vec![];
+                for ledger in ledgers_for_reload.values() {
+                    visited_files.extend(ledger.read().await.visited_files.clone());
                 }
-                Err(err) => {
-                    error!("error on reload: {}", err);
-                    // todo: broadcast the error
+                if let Err(e) = watcher.lock().unwrap().watch(visited_files) {
+                    error!("cannot watch files of reloaded ledgers: {}", e);
                 }
             }
-            drop(guard);
         }
     });
 }
 
-pub async fn start_server(
-    opts: ServeConfig, ledger_data: Arc<RwLock<Ledger>>, broadcaster: Arc<Broadcaster>, reload_sender: Arc<ReloadSender>,
-) -> ZhangResult<()> {
+/// the event of ledger reloaded, with the changes since its previous version
+pub fn reload_event(name: &str, previous: &Ledger, reloaded: &Ledger, changed_files: &[PathBuf], duration: Duration) -> ZhangResult<BroadcastEvent> {
+    Ok(BroadcastEvent::Reload {
+        ledger: name.to_owned(),
+        changed_files: changed_files.iter().map(|file| file.to_string_lossy().to_string()).collect(),
+        duration_ms: duration.as_millis(),
+        error_count: reloaded.operations().errors()?.len(),
+        changes: reloaded.changes_since(previous),
+    })
+}
+
+/// replace the ledger with the reloaded one and get the event of reloading, the write lock is held across both
+/// so that the changes are never computed against a ledger which is replaced by another reloading meanwhile
+pub async fn swap_reloaded_ledger(
+    name: &str, ledger: &RwLock<Ledger>, reloaded: Ledger, changed_files: &[PathBuf], duration: Duration,
+) -> ZhangResult<BroadcastEvent> {
+    let mut guard = ledger.write().await;
+    let event = reload_event(name, &guard, &reloaded, changed_files, duration);
+    *guard = reloaded;
+    event
+}
+
+pub async fn start_server(opts: ServeConfig, ledger_data: LedgerStates, broadcaster: Arc<Broadcaster>, reload_sender: Arc<ReloadSender>) -> ZhangResult<()> {
     let addr = SocketAddrV4::new(opts.addr.parse()?, opts.port);
     info!("zhang is listening on http://{}:{}/", opts.addr, opts.port);
 
-    let app = create_server_app(ledger_data, broadcaster, reload_sender, opts.auth_credential);
+    let app = create_server_app(
+        ledger_data,
+        broadcaster,
+        reload_sender,
+        Some(opts.authenticator).filter(Authenticator::is_enabled),
+    );
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
     axum::serve(listener, app).await.unwrap();
     Ok(())
 }
+/// the router of API routes in the form of `METHOD PATH => HANDLER`, along with [api_routes] listing them,
+/// so that the routes described in [openapi::ApiDoc] are checked against the ones served
+macro_rules! api_routes {
+    ($($(#[$attr:meta])* $method:ident $path:literal => $handler:path;)*) => {
+        fn api_router() -> Router<AppState> {
+            let app = Router::new();
+            $(
+                $(#[$attr])*
+                let app = app.route($path, $method($handler));
+            )*
+            app
+        }
+
+        /// the method and path of API routes served, which are all described in the OpenAPI document
+        pub fn api_routes() -> Vec<(&'static str, &'static str)> {
+            [$($(#[$attr])* (stringify!($method), $path)),*].to_vec()
+        }
+    };
+}
+
+api_routes! {
+    get "/metrics" => metrics::get_metrics;
+    get "/api/sse" => sse;
+    post "/api/reload" => reload;
+    get "/api/info" => get_basic_info;
+    get "/api/auth/user" => get_current_user;
+    get "/api/ledgers" => get_ledgers;
+    get "/api/ledgers/net-worth" => get_consolidated_net_worth;
+    get "/api/store" => get_store_data;
+    get "/api/options" => get_all_options;
+    get "/api/errors" => get_errors;
+    get "/api/files" => get_files;
+    get "/api/files/:file_path" => get_file_content;
+    put "/api/files/:file_path" => update_file_content;
+    get "/api/for-new-transaction" => get_info_for_new_transactions;
+    get "/api/journals" => get_journals;
+    post "/api/transactions" => create_new_transaction;
+    put "/api/transactions/:transaction_id" => routes::transaction::update_single_transaction;
+    delete "/api/transactions/:transaction_id" => routes::transaction::delete_single_transaction;
+    post "/api/transactions/:transaction_id/documents" => upload_transaction_document;
+    get "/api/templates" => get_templates;
+    post "/api/templates/:template_name/transactions" => instantiate_template;
+    get "/api/directives/:directive_id" => routes::directive::get_directive;
+    put "/api/directives/:directive_id" => routes::directive::update_directive;
+    delete "/api/directives/:directive_id" => routes::directive::delete_directive;
+    get "/api/accounts" => get_account_list;
+    get "/api/accounts/:account_name" => get_account_info;
+    post "/api/accounts/:account_name/documents" => upload_account_document;
+    get "/api/accounts/:account_name/documents" => get_account_documents;
+    get "/api/accounts/:account_name/journals" => get_account_journals;
+    get "/api/accounts/:account_name/balances" => get_account_balance_data;
+    post "/api/accounts/:account_name/balances" => create_account_balance;
+    post "/api/accounts/batch-balances" => create_batch_account_balances;
+    get "/api/documents" => get_documents;
+    post "/api/documents" => upload_documents;
+    get "/api/documents/:file_path" => download_document;
+    get "/api/commodities" => get_all_commodities;
+    get "/api/commodities/:commodity_name" => get_single_commodity;
+    get "/api/statistic/summary" => get_statistic_summary;
+    get "/api/statistic/graph" => get_statistic_graph;
+    get "/api/statistic/trial-balance" => get_trial_balance;
+    get "/api/statistic/tax" => get_tax_report;
+    get "/api/statistic/:account_type" => get_statistic_rank_detail_by_account_type;
+    get "/api/query" => get_query_result;
+    get "/api/export/:report" => download_report;
+    get "/api/budgets" => get_budget_list;
+    post "/api/budgets" => create_budget;
+    get "/api/budget-report" => get_budget_report;
+    get "/api/budgets/:budget_name" => get_budget_info;
+    post "/api/budgets/:budget_name/amounts" => add_budget_amount;
+    get "/api/budgets/:budget_name/interval/:year/:month" => get_budget_interval_detail;
+    get "/api/plugins" => routes::plugin::plugin_list;
+    get "/api/plugins/:plugin_name/data" => routes::plugin::plugin_data;
+    #[cfg(feature = "graphql")]
+    post "/api/graphql" => graphql::graphql;
+}
+
 pub fn create_server_app(
-    ledger: Arc<RwLock<Ledger>>, broadcaster: Arc<Broadcaster>, reload_sender: Arc<ReloadSender>, auth_credential: Option<String>,
+    ledgers: LedgerStates, broadcaster: Arc<Broadcaster>, reload_sender: Arc<ReloadSender>, authenticator: Option<Authenticator>,
 ) -> Router {
-    let basic_credential = auth_credential.map(|credential| {
-        let token_part = credential.splitn(2, ':').map(|it| it.to_owned()).collect_vec();
-        (
-            token_part.first().cloned().expect("cannot retrieve credential user_id"),
-            token_part.get(1).cloned(),
-        )
-    });
+    let app = api_router().route("/openapi.json", get(openapi::get_openapi_document));
 
-    let app = Router::new()
-        .route("/api/sse", get(sse))
-        .route("/api/reload", post(reload))
-        .route("/api/info", get(get_basic_info))
-        .route("/api/store", get(get_store_data))
-        .route("/api/options", get(get_all_options))
-        .route("/api/errors", get(get_errors))
-        .route("/api/files", get(get_files))
-        .route("/api/files/:file_path", get(get_file_content))
-        .route("/api/files/:file_path", put(update_file_content))
-        .route("/api/for-new-transaction", get(get_info_for_new_transactions))
-        .route("/api/journals", get(get_journals))
-        .route("/api/transactions", post(create_new_transaction))
-        .route("/api/transactions/:transaction_id", put(routes::transaction::update_single_transaction))
-        .route("/api/transactions/:transaction_id/documents", post(upload_transaction_document))
-        .route("/api/accounts", get(get_account_list))
-        .route("/api/accounts/:account_name", get(get_account_info))
-        .route("/api/accounts/:account_name/documents", post(upload_account_document))
-        .route("/api/accounts/:account_name/documents", get(get_account_documents))
-        .route("/api/accounts/:account_name/journals", get(get_account_journals))
-        .route("/api/accounts/:account_name/balances", get(get_account_balance_data))
-        .route("/api/accounts/:account_name/balances", post(create_account_balance))
-        .route("/api/accounts/batch-balances", post(create_batch_account_balances))
-        .route("/api/documents", get(get_documents))
-        .route("/api/documents/:file_path", get(download_document))
-        .route("/api/commodities", get(get_all_commodities))
-        .route("/api/commodities/:commodity_name", get(get_single_commodity))
-        .route("/api/statistic/summary", get(get_statistic_summary))
-        .route("/api/statistic/graph", get(get_statistic_graph))
-        .route("/api/statistic/:account_type", get(get_statistic_rank_detail_by_account_type))
-        .route("/api/budgets", get(get_budget_list))
-        .route("/api/budgets/:budget_name", get(get_budget_info))
-        .route("/api/budgets/:budget_name/interval/:year/:month", get(get_budget_interval_detail))
-        .route("/api/plugins", get(routes::plugin::plugin_list))
+    let app = app
+        .layer(middleware::from_fn(metrics::track_requests))
         .layer(CorsLayer::permissive())
         .layer(DefaultBodyLimit::disable())
         .layer(RequestBodyLimitLayer::new(250 * 1024 * 1024 /* 250mb */))
         .with_state(AppState {
-            ledger,
+            ledgers,
             broadcaster,
             reload_sender,
         });
 
-    let app = if let Some((username, password)) = basic_credential {
-        info!("web basic auth is enabled with username {}", &username);
-        app.layer(ValidateRequestHeaderLayer::basic(&username, password.as_deref().unwrap_or_default()))
+    let app = if let Some(authenticator) = authenticator {
+        info!("web auth is enabled");
+        app.layer(middleware::from_fn_with_state(Arc::new(authenticator), auth::authorize))
     } else {
         app
     };
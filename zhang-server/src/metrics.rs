@@ -0,0 +1,201 @@
+//! the metrics of server in the Prometheus text format, for monitoring the server like any other service.
+//!
+//! the statistics of ledgers are gathered on scrape, while the reloads and the latencies of requests are recorded in the
+//! process-wide registry as they happen, since the ledgers are reloaded outside of requests as well.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::header;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use zhang_ast::error::ErrorSeverity;
+
+use crate::{LedgerStates, ServerResult};
+
+/// the upper bounds in seconds of the buckets of request latency histogram
+const LATENCY_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+const TEXT_FORMAT: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+#[derive(Default)]
+pub struct Registry {
+    reloads: Mutex<BTreeMap<String, ReloadRecord>>,
+    requests: Mutex<BTreeMap<(String, String), Histogram>>,
+}
+
+#[derive(Default)]
+struct ReloadRecord {
+    last_duration: Duration,
+    total: u64,
+    failures: u64,
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// the count of observations in each bucket, which is accumulated on rendering
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+/// the registry shared by the server and its reload listener
+pub fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Registry::default)
+}
+
+impl Registry {
+    /// record the load of ledger which succeeds, including the initial one
+    pub fn record_reload(&self, ledger: &str, duration: Duration) {
+        let mut reloads = self.reloads.lock().unwrap();
+        let record = reloads.entry(ledger.to_owned()).or_default();
+        record.last_duration = duration;
+        record.total += 1;
+    }
+
+    pub fn record_reload_failure(&self, ledger: &str) {
+        self.reloads.lock().unwrap().entry(ledger.to_owned()).or_default().failures += 1;
+    }
+
+    fn record_request(&self, method: &str, path: &str, duration: Duration) {
+        self.requests
+            .lock()
+            .unwrap()
+            .entry((method.to_owned(), path.to_owned()))
+            .or_default()
+            .observe(duration.as_secs_f64());
+    }
+}
+
+/// the middleware records the latency of request by its method and route, the requests matching no route are not recorded
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let Some(path) = request.extensions().get::<MatchedPath>().map(|path| path.as_str().to_owned()) else {
+        return next.run(request).await;
+    };
+    let method = request.method().to_string();
+    let start_time = Instant::now();
+    let response = next.run(request).await;
+    registry().record_request(&method, &path, start_time.elapsed());
+    response
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "common",
+    responses((status = 200, description = "OK", content_type = "text/plain", body = String))
+)]
+pub async fn get_metrics(State(ledgers): State<LedgerStates>) -> ServerResult<Response> {
+    let mut directives = vec![];
+    let mut errors = vec![];
+    for (name, ledger) in ledgers.iter() {
+        let ledger = ledger.read().await;
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for directive in &ledger.directives {
+            *counts.entry(format!("{:?}", directive.data.directive_type())).or_default() += 1;
+        }
+        directives.extend(counts.into_iter().map(|(directive_type, count)| (name.clone(), directive_type, count)));
+
+        let mut severities: HashMap<ErrorSeverity, usize> = HashMap::new();
+        for error in ledger.operations().errors()? {
+            *severities.entry(error.severity).or_default() += 1;
+        }
+        for severity in [ErrorSeverity::Error, ErrorSeverity::Warning, ErrorSeverity::Info] {
+            errors.push((name.clone(), severity, severities.get(&severity).copied().unwrap_or_default()));
+        }
+    }
+
+    let mut text = String::new();
+    header_of(&mut text, "zhang_build_info", "gauge", "the version of zhang server");
+    writeln!(text, "zhang_build_info{{version={}}} 1", label(env!("ZHANG_BUILD_VERSION"))).unwrap();
+
+    header_of(&mut text, "zhang_directives", "gauge", "the count of directives in ledger by directive type");
+    for (ledger, directive_type, count) in directives {
+        writeln!(text, "zhang_directives{{ledger={},type={}}} {}", label(&ledger), label(&directive_type), count).unwrap();
+    }
+    header_of(&mut text, "zhang_errors", "gauge", "the count of errors in ledger by severity");
+    for (ledger, severity, count) in errors {
+        writeln!(
+            text,
+            "zhang_errors{{ledger={},severity={}}} {}",
+            label(&ledger),
+            label(&severity.to_string()),
+            count
+        )
+        .unwrap();
+    }
+
+    {
+        let reloads = registry().reloads.lock().unwrap();
+        header_of(
+            &mut text,
+            "zhang_last_reload_duration_seconds",
+            "gauge",
+            "the duration of the last succeeded load of ledger",
+        );
+        for (ledger, record) in reloads.iter().filter(|(_, record)| record.total > 0) {
+            writeln!(
+                text,
+                "zhang_last_reload_duration_seconds{{ledger={}}} {}",
+                label(ledger),
+                record.last_duration.as_secs_f64()
+            )
+            .unwrap();
+        }
+        header_of(&mut text, "zhang_reloads_total", "counter", "the count of succeeded loads of ledger");
+        for (ledger, record) in reloads.iter() {
+            writeln!(text, "zhang_reloads_total{{ledger={}}} {}", label(ledger), record.total).unwrap();
+        }
+        header_of(&mut text, "zhang_reload_failures_total", "counter", "the count of failed reloads of ledger");
+        for (ledger, record) in reloads.iter() {
+            writeln!(text, "zhang_reload_failures_total{{ledger={}}} {}", label(ledger), record.failures).unwrap();
+        }
+    }
+
+    {
+        let requests = registry().requests.lock().unwrap();
+        header_of(
+            &mut text,
+            "zhang_http_request_duration_seconds",
+            "histogram",
+            "the latency of http requests by method and route",
+        );
+        for ((method, path), histogram) in requests.iter() {
+            let labels = format!("method={},path={}", label(method), label(path));
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += count;
+                writeln!(text, "zhang_http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}", labels, bound, cumulative).unwrap();
+            }
+            writeln!(text, "zhang_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}", labels, histogram.count).unwrap();
+            writeln!(text, "zhang_http_request_duration_seconds_sum{{{}}} {}", labels, histogram.sum).unwrap();
+            writeln!(text, "zhang_http_request_duration_seconds_count{{{}}} {}", labels, histogram.count).unwrap();
+        }
+    }
+
+    Ok(([(header::CONTENT_TYPE, TEXT_FORMAT)], text).into_response())
+}
+
+fn header_of(text: &mut String, name: &str, metric_type: &str, help: &str) {
+    writeln!(text, "# HELP {} {}", name, help).unwrap();
+    writeln!(text, "# TYPE {} {}", name, metric_type).unwrap();
+}
+
+/// the quoted label value, whose backslash, double-quote and line feed are escaped
+fn label(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
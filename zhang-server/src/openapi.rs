@@ -0,0 +1,183 @@
+//! the OpenAPI 3 document of the HTTP API served at `/openapi.json`, so that the clients can be generated from it.
+//!
+//! the schemas are derived from the request and response types by [`utoipa`], and the operations are declared by
+//! `#[utoipa::path]` on the handlers. the handlers should be listed in [`ApiDoc`] once the routes are added to
+//! [`crate::api_routes`], which is checked by test.
+
+use axum::Json;
+use serde_json::Value;
+use utoipa::openapi::path::Operation;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityRequirement, SecurityScheme};
+use utoipa::openapi::{ContentBuilder, RefOr, ResponseBuilder};
+use utoipa::{IntoParams, Modify, OpenApi, PartialSchema, ToSchema};
+
+use zhang_ast::AccountType;
+
+use crate::error::ErrorResponse;
+use crate::metrics::*;
+use crate::routes::account::*;
+use crate::routes::budget::*;
+use crate::routes::commodity::*;
+use crate::routes::common::*;
+use crate::routes::directive::*;
+use crate::routes::document::*;
+use crate::routes::export::*;
+use crate::routes::file::*;
+use crate::routes::ledger::*;
+use crate::routes::plugin::*;
+use crate::routes::query::*;
+use crate::routes::statistics::*;
+use crate::routes::transaction::*;
+
+/// the raw content of file downloaded
+#[derive(ToSchema)]
+#[schema(value_type = String, format = Binary)]
+pub struct FileContent(pub Vec<u8>);
+
+/// the header selecting the ledger of request
+#[derive(IntoParams)]
+#[into_params(parameter_in = Header)]
+pub struct LedgerHeader {
+    /// the name of ledger served, the default ledger is used if absent
+    #[param(rename = "x-zhang-ledger")]
+    pub ledger: Option<String>,
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "zhang"),
+    paths(
+        get_metrics,
+        sse,
+        reload,
+        get_basic_info,
+        get_current_user,
+        get_ledgers,
+        get_consolidated_net_worth,
+        get_store_data,
+        get_all_options,
+        get_errors,
+        get_files,
+        get_file_content,
+        update_file_content,
+        get_info_for_new_transactions,
+        get_journals,
+        create_new_transaction,
+        update_single_transaction,
+        delete_single_transaction,
+        upload_transaction_document,
+        get_templates,
+        instantiate_template,
+        get_directive,
+        update_directive,
+        delete_directive,
+        get_account_list,
+        get_account_info,
+        upload_account_document,
+        get_account_documents,
+        get_account_journals,
+        get_account_balance_data,
+        create_account_balance,
+        create_batch_account_balances,
+        get_documents,
+        upload_documents,
+        download_document,
+        get_all_commodities,
+        get_single_commodity,
+        get_statistic_summary,
+        get_statistic_graph,
+        get_trial_balance,
+        get_tax_report,
+        get_statistic_rank_detail_by_account_type,
+        get_query_result,
+        download_report,
+        get_budget_list,
+        create_budget,
+        get_budget_report,
+        get_budget_info,
+        add_budget_amount,
+        get_budget_interval_detail,
+        plugin_list,
+        plugin_data,
+    ),
+    components(schemas(AccountType)),
+    modifiers(&Security, &ErrorResponses)
+)]
+pub struct ApiDoc;
+
+#[cfg(feature = "graphql")]
+#[derive(OpenApi)]
+#[openapi(paths(crate::graphql::graphql))]
+struct GraphQLApiDoc;
+
+/// the authentication is required only if it is enabled
+struct Security;
+
+impl Modify for Security {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme("basic", SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Basic).build()));
+        components.add_security_scheme("bearer", SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()));
+        openapi.security = Some(vec![
+            SecurityRequirement::new("basic", Vec::<String>::new()),
+            SecurityRequirement::new("bearer", Vec::<String>::new()),
+            SecurityRequirement::default(),
+        ]);
+    }
+}
+
+/// every operation may be rejected or failed with [`ErrorResponse`]
+struct ErrorResponses;
+
+impl Modify for ErrorResponses {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.schemas.insert("ErrorResponse".to_owned(), ErrorResponse::schema());
+        let error = ResponseBuilder::new()
+            .description("the request is rejected or failed")
+            .content(
+                "application/json",
+                ContentBuilder::new()
+                    .schema(Some(RefOr::Ref(utoipa::openapi::Ref::from_schema_name("ErrorResponse"))))
+                    .build(),
+            )
+            .build();
+        components.responses.insert("Error".to_owned(), RefOr::T(error));
+
+        let operations = openapi.paths.paths.values_mut().flat_map(|item| {
+            [
+                &mut item.get,
+                &mut item.put,
+                &mut item.post,
+                &mut item.delete,
+                &mut item.options,
+                &mut item.head,
+                &mut item.patch,
+                &mut item.trace,
+            ]
+            .into_iter()
+            .flatten()
+        });
+        operations.for_each(|operation: &mut Operation| {
+            operation
+                .responses
+                .responses
+                .insert("default".to_owned(), RefOr::Ref(utoipa::openapi::Ref::new("#/components/responses/Error")));
+        });
+    }
+}
+
+/// the OpenAPI document of server
+pub fn document() -> Value {
+    let mut document = ApiDoc::openapi();
+    #[cfg(feature = "graphql")]
+    document.merge(GraphQLApiDoc::openapi());
+    document.info.version = env!("ZHANG_BUILD_VERSION").to_owned();
+    document.info.description = None;
+    document.info.license = None;
+    serde_json::to_value(document).expect("the OpenAPI document is always serializable")
+}
+
+pub async fn get_openapi_document() -> Json<Value> {
+    Json(document())
+}
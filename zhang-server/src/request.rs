@@ -1,38 +1,103 @@
 use std::cmp::max;
+use std::collections::HashMap;
 
 use bigdecimal::BigDecimal;
-use chrono::{DateTime, Datelike, Local, Utc};
+use chrono::{DateTime, Datelike, Local, NaiveDate, Utc};
 use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use zhang_ast::error::ErrorSeverity;
 use zhang_ast::Flag;
+use zhang_core::domains::JournalFilter;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 #[serde(tag = "type")]
 pub enum AccountBalanceRequest {
     Check { account_name: String, amount: AmountRequest },
     Pad { account_name: String, amount: AmountRequest, pad: String },
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct FileUpdateRequest {
     pub content: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub enum StatisticInterval {
     Day,
     Week,
     Month,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct StatisticRequest {
     pub from: DateTime<Utc>,
     pub to: DateTime<Utc>,
 }
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportRequest {
+    /// `csv`, `json` or `table`, default to `csv`
+    pub format: Option<String>,
+    /// the columns of report separated by comma
+    pub columns: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// display the numbers by the conventions of their commodities
+    #[serde(default)]
+    pub formatted: bool,
+    /// max depth of account tree of the financial statements
+    pub depth: Option<usize>,
+    /// only the accounts of the entity are included in the financial statements if it is given
+    pub entity: Option<String>,
+    /// whether to exclude the accounts of elimination rules from the financial statements, which is ignored if entity is given
+    #[serde(default)]
+    pub consolidated: bool,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct QueryRequest {
+    /// the query text, e.g. `SELECT account, sum(amount) GROUP BY account`
+    pub query: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TrialBalanceRequest {
+    /// the date of trial balance, default to now
+    pub date: Option<DateTime<Utc>>,
+    /// only the accounts of the entity are included if it is given
+    pub entity: Option<String>,
+    /// whether to exclude the accounts of elimination rules, which is ignored if entity is given
+    pub consolidated: Option<bool>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TaxReportRequest {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// `day`, `week`, `month`, `quarter` or `year`, default to `month`
+    pub interval: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct NetWorthRequest {
+    /// the date of net worth, default to now
+    pub date: Option<DateTime<Utc>>,
+    /// the currency which net worth is valued in, default to the operating currency of default ledger
+    pub currency: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct StatisticGraphRequest {
     pub from: DateTime<Utc>,
     pub to: DateTime<Utc>,
+    #[param(inline)]
     pub interval: StatisticInterval,
 }
 
@@ -42,11 +107,19 @@ pub struct ReportRequest {
     pub to: DateTime<Utc>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct JournalRequest {
     pub page: Option<u32>,
     pub size: Option<u32>,
     pub keyword: Option<String>,
+    pub tag: Option<String>,
+    pub link: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// the transactions having postings of the account or its sub-accounts
+    pub account: Option<String>,
+    pub payee: Option<String>,
 }
 impl JournalRequest {
     pub fn page(&self) -> u32 {
@@ -59,9 +132,42 @@ impl JournalRequest {
     pub fn limit(&self) -> u32 {
         self.size.unwrap_or(100)
     }
+    pub fn filter(&self) -> JournalFilter {
+        JournalFilter {
+            from: self.from,
+            to: self.to,
+            account: self.account.clone(),
+            payee: self.payee.clone(),
+            tag: self.tag.clone(),
+            link: self.link.clone(),
+            keyword: self.keyword.clone(),
+        }
+    }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ErrorRequest {
+    pub page: Option<u32>,
+    pub size: Option<u32>,
+    /// only the errors at least as severe as it are returned
+    #[param(inline)]
+    pub severity: Option<ErrorSeverity>,
+}
+impl ErrorRequest {
+    pub fn page(&self) -> u32 {
+        max(self.page.unwrap_or(1), 1)
+    }
+    pub fn offset(&self) -> u32 {
+        let page = self.page();
+        (page - 1) * self.limit()
+    }
+    pub fn limit(&self) -> u32 {
+        self.size.unwrap_or(100)
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
 pub struct CreateTransactionRequest {
     pub datetime: DateTime<Utc>,
     pub payee: String,
@@ -71,6 +177,9 @@ pub struct CreateTransactionRequest {
     pub metas: Vec<MetaRequest>,
     pub tags: Vec<String>,
     pub links: Vec<String>,
+    /// the paths of documents attached to the transaction, relative to the entry of ledger
+    #[serde(default)]
+    pub documents: Vec<String>,
 }
 
 #[derive(Deserialize)]
@@ -83,6 +192,21 @@ pub enum FlagRequest {
     Custom(char),
 }
 
+/// the flag is either one of the builtin flags or a custom flag of single character, which is not a tagged enum in schema
+impl utoipa::PartialSchema for FlagRequest {
+    fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        utoipa::openapi::ObjectBuilder::new()
+            .schema_type(utoipa::openapi::schema::Type::String)
+            .description(Some(
+                "one of `Okay`, `Warning`, `BalancePad` and `BalanceCheck`, or a custom flag of single character",
+            ))
+            .examples(["Okay"])
+            .into()
+    }
+}
+
+impl ToSchema for FlagRequest {}
+
 impl From<FlagRequest> for Flag {
     fn from(req: FlagRequest) -> Self {
         match req {
@@ -95,25 +219,27 @@ impl From<FlagRequest> for Flag {
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct CreateTransactionPostingRequest {
     pub account: String,
     pub unit: Option<AmountRequest>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct AmountRequest {
+    #[schema(value_type = String, format = "decimal")]
     pub number: BigDecimal,
     pub commodity: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct MetaRequest {
     pub key: String,
     pub value: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct BudgetListRequest {
     pub month: Option<u32>,
     pub year: Option<u32>,
@@ -124,3 +250,68 @@ impl BudgetListRequest {
         self.year.unwrap_or(time.year() as u32) * 100 + self.month.unwrap_or(time.month())
     }
 }
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateBudgetRequest {
+    pub name: String,
+    pub commodity: String,
+    pub alias: Option<String>,
+    pub category: Option<String>,
+    /// the date of budget directive, today if absent
+    pub date: Option<NaiveDate>,
+    /// the amount assigned to the budget once it is created
+    #[schema(value_type = Option<String>, format = "decimal")]
+    pub assigned_amount: Option<BigDecimal>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct InstantiateTemplateRequest {
+    /// the date of instantiated transaction, today if absent
+    pub date: Option<NaiveDate>,
+    /// the values of placeholders in template
+    #[serde(default)]
+    #[schema(value_type = HashMap<String, String>)]
+    pub values: HashMap<String, BigDecimal>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AddBudgetAmountRequest {
+    /// the date of budget-add directive, today if absent
+    pub date: Option<NaiveDate>,
+    /// the amount in the commodity of budget
+    #[schema(value_type = String, format = "decimal")]
+    pub amount: BigDecimal,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BudgetReportRequest {
+    /// `month` or `quarter`, month if absent
+    pub interval: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateDirectiveRequest {
+    /// the source of directive in the format of ledger, which replaces the original one
+    pub content: String,
+}
+
+/// the multipart form of files uploaded as documents
+#[derive(ToSchema)]
+pub struct FilesUploadForm {
+    #[schema(value_type = Vec<String>, format = Binary)]
+    pub file: Vec<Vec<u8>>,
+}
+
+/// the multipart form of documents uploaded and attached to either an account or a transaction
+#[derive(ToSchema)]
+pub struct DocumentsUploadForm {
+    /// the account which documents are attached to
+    pub account: Option<String>,
+    /// the id of transaction which documents are attached to
+    pub transaction: Option<Uuid>,
+    /// the date of documents, today or the date of transaction if absent
+    pub date: Option<NaiveDate>,
+    #[schema(value_type = Vec<String>, format = Binary)]
+    pub file: Vec<Vec<u8>>,
+}
@@ -5,12 +5,14 @@ use axum::Json;
 use bigdecimal::BigDecimal;
 use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use serde::Serialize;
+use utoipa::ToSchema;
 use uuid::Uuid;
 use zhang_ast::amount::{Amount, CalculatedAmount};
-use zhang_ast::AccountType;
+use zhang_ast::{AccountType, SpanInfo};
 use zhang_core::domains::schemas::{AccountJournalDomain, AccountStatus, MetaDomain};
 use zhang_core::plugin::PluginType;
-use zhang_core::store::{BudgetEvent, PostingDomain};
+use zhang_core::store::{BudgetEvent, DocumentDomain, PostingDomain};
+use zhang_core::utils::id::FromSpan;
 
 use crate::ServerResult;
 
@@ -36,12 +38,14 @@ impl<T: Serialize> ResponseWrapper<T> {
     }
 }
 
+/// the body of successful response, whose data is wrapped into the `data` field
+#[derive(Serialize, ToSchema)]
+pub struct SuccessWrapper<T: Serialize> {
+    data: T,
+}
+
 impl<T: Serialize> IntoResponse for ResponseWrapper<T> {
     fn into_response(self) -> Response {
-        #[derive(Serialize)]
-        pub struct SuccessWrapper<T: Serialize> {
-            data: T,
-        }
         match self {
             ResponseWrapper::Json(data) => {
                 let wrapper = SuccessWrapper { data };
@@ -54,7 +58,7 @@ impl<T: Serialize> IntoResponse for ResponseWrapper<T> {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct Pageable<T: Serialize> {
     pub total_count: u32,
     pub total_page: u32,
@@ -76,7 +80,7 @@ impl<T: Serialize> Pageable<T> {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct AccountResponse {
     pub name: String,
     pub status: AccountStatus,
@@ -84,7 +88,12 @@ pub struct AccountResponse {
     pub amount: CalculatedAmount,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(
+    feature = "graphql",
+    derive(async_graphql::SimpleObject),
+    graphql(name = "Document", rename_fields = "snake_case")
+)]
 pub struct DocumentResponse {
     pub datetime: NaiveDateTime,
     pub filename: String,
@@ -92,6 +101,21 @@ pub struct DocumentResponse {
     pub extension: Option<String>,
     pub account: Option<String>,
     pub trx_id: Option<String>,
+    pub hash: Option<String>,
+}
+
+impl From<DocumentDomain> for DocumentResponse {
+    fn from(document: DocumentDomain) -> Self {
+        DocumentResponse {
+            datetime: document.datetime.naive_local(),
+            extension: mime_guess::from_path(&document.path).first().map(|it| it.to_string()),
+            filename: document.filename.unwrap_or_default(),
+            path: document.path,
+            account: document.document_type.as_account(),
+            trx_id: document.document_type.as_trx(),
+            hash: document.hash,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -107,10 +131,11 @@ pub struct StatisticResponse {
     pub details: HashMap<NaiveDate, HashMap<String, AmountResponse>>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject), graphql(name = "Meta"))]
 pub struct MetaResponse {
-    key: String,
-    value: String,
+    pub(crate) key: String,
+    pub(crate) value: String,
 }
 impl From<MetaDomain> for MetaResponse {
     fn from(value: MetaDomain) -> Self {
@@ -121,7 +146,7 @@ impl From<MetaDomain> for MetaResponse {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 #[serde(tag = "type")]
 pub enum JournalItemResponse {
     Transaction(JournalTransactionItemResponse),
@@ -139,7 +164,36 @@ impl JournalItemResponse {
     }
 }
 
-#[derive(Serialize)]
+/// where the directive is written, the id is the one of directive once the ledger is reloaded
+#[derive(Serialize, ToSchema)]
+pub struct CreatedDirectiveResponse {
+    pub id: Uuid,
+    pub path: String,
+    pub span_start: usize,
+    pub span_end: usize,
+}
+
+impl From<SpanInfo> for CreatedDirectiveResponse {
+    fn from(span: SpanInfo) -> Self {
+        CreatedDirectiveResponse {
+            id: Uuid::from_span(&span),
+            path: span.filename.map(|it| it.to_string_lossy().to_string()).unwrap_or_default(),
+            span_start: span.start,
+            span_end: span.end,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DirectiveResponse {
+    pub id: Uuid,
+    pub path: String,
+    pub span_start: usize,
+    pub span_end: usize,
+    pub content: String,
+}
+
+#[derive(Serialize, ToSchema)]
 pub struct JournalTransactionItemResponse {
     pub id: Uuid,
     pub sequence: i32,
@@ -153,17 +207,27 @@ pub struct JournalTransactionItemResponse {
     pub postings: Vec<JournalTransactionPostingResponse>,
     pub metas: Vec<MetaResponse>,
 }
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(
+    feature = "graphql",
+    derive(async_graphql::SimpleObject),
+    graphql(name = "Posting", rename_fields = "snake_case")
+)]
 pub struct JournalTransactionPostingResponse {
     pub account: String,
+    #[schema(value_type = Option<String>, format = "decimal")]
     pub unit_number: Option<BigDecimal>,
     pub unit_commodity: Option<String>,
+    #[schema(value_type = Option<String>, format = "decimal")]
     pub cost_number: Option<BigDecimal>,
     pub cost_commodity: Option<String>,
+    #[schema(value_type = String, format = "decimal")]
     pub inferred_unit_number: BigDecimal,
     pub inferred_unit_commodity: String,
+    #[schema(value_type = String, format = "decimal")]
     pub account_before_number: BigDecimal,
     pub account_before_commodity: String,
+    #[schema(value_type = String, format = "decimal")]
     pub account_after_number: BigDecimal,
     pub account_after_commodity: String,
 }
@@ -186,7 +250,7 @@ impl From<PostingDomain> for JournalTransactionPostingResponse {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct JournalBalanceCheckItemResponse {
     pub id: Uuid,
     pub sequence: i32,
@@ -197,7 +261,7 @@ pub struct JournalBalanceCheckItemResponse {
     pub(crate) postings: Vec<JournalTransactionPostingResponse>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct JournalBalancePadItemResponse {
     pub id: Uuid,
     pub sequence: i32,
@@ -208,14 +272,15 @@ pub struct JournalBalancePadItemResponse {
     pub(crate) postings: Vec<JournalTransactionPostingResponse>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct InfoForNewTransaction {
     pub payee: Vec<String>,
     pub account_name: Vec<String>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema)]
 pub struct AmountResponse {
+    #[schema(value_type = String, format = "decimal")]
     pub number: BigDecimal,
     pub commodity: String,
 }
@@ -229,51 +294,61 @@ impl From<Amount> for AmountResponse {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct CommodityListItemResponse {
     pub name: String,
     pub precision: i32,
     pub prefix: Option<String>,
     pub suffix: Option<String>,
     pub rounding: String,
+    pub grouping_separator: Option<String>,
+    pub decimal_symbol: Option<String>,
     pub group: Option<String>,
 
+    #[schema(value_type = String, format = "decimal")]
     pub total_amount: BigDecimal,
+    /// the total amount displayed by the conventions of commodity
+    pub formatted_total_amount: String,
     pub latest_price_date: Option<NaiveDateTime>,
+    #[schema(value_type = Option<String>, format = "decimal")]
     pub latest_price_amount: Option<BigDecimal>,
     pub latest_price_commodity: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct CommodityLot {
     pub datetime: Option<NaiveDateTime>,
+    #[schema(value_type = String, format = "decimal")]
     pub amount: BigDecimal,
+    #[schema(value_type = Option<String>, format = "decimal")]
     pub price_amount: Option<BigDecimal>,
     pub price_commodity: Option<String>,
+    pub label: Option<String>,
     pub account: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct CommodityPrice {
     pub datetime: NaiveDateTime,
+    #[schema(value_type = String, format = "decimal")]
     pub amount: BigDecimal,
     pub target_commodity: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct CommodityDetailResponse {
     pub info: CommodityListItemResponse,
     pub lots: Vec<CommodityLot>,
     pub prices: Vec<CommodityPrice>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct FileDetailResponse {
     pub path: String,
     pub content: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct StatisticSummaryResponse {
     pub from: DateTime<Utc>,
     pub to: DateTime<Utc>,
@@ -311,7 +386,7 @@ pub struct ReportResponse {
     pub expense_top_transactions: Vec<AccountJournalDomain>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct StatisticRankResponse {
     pub from: NaiveDateTime,
     pub to: NaiveDateTime,
@@ -320,7 +395,7 @@ pub struct StatisticRankResponse {
     pub top_transactions: Vec<AccountJournalDomain>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct StatisticGraphResponse {
     pub from: NaiveDateTime,
     pub to: NaiveDateTime,
@@ -329,20 +404,28 @@ pub struct StatisticGraphResponse {
     pub changes: HashMap<NaiveDate, HashMap<AccountType, CalculatedAmount>>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ReportRankItemResponse {
     pub account: String,
     pub amount: CalculatedAmount,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
+pub struct LedgerResponse {
+    pub name: String,
+    pub title: Option<String>,
+    pub is_default: bool,
+    pub error_count: usize,
+}
+
+#[derive(Serialize, ToSchema)]
 pub struct BasicInfo {
     pub title: Option<String>,
     pub version: String,
     pub build_date: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct AccountInfoResponse {
     pub date: NaiveDateTime,
     pub r#type: String,
@@ -352,7 +435,15 @@ pub struct AccountInfoResponse {
     pub amount: CalculatedAmount,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
+pub struct TemplateResponse {
+    pub name: String,
+    pub payee: Option<String>,
+    pub narration: Option<String>,
+    pub placeholders: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
 pub struct BudgetListItemResponse {
     pub name: String,
     pub alias: Option<String>,
@@ -363,7 +454,7 @@ pub struct BudgetListItemResponse {
     pub available_amount: Amount,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct BudgetInfoResponse {
     pub name: String,
     pub alias: Option<String>,
@@ -377,7 +468,7 @@ pub struct BudgetInfoResponse {
     pub available_amount: Amount,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 #[serde(untagged)]
 pub enum BudgetIntervalEventResponse {
     BudgetEvent(BudgetEvent),
@@ -393,14 +484,14 @@ impl BudgetIntervalEventResponse {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct PluginResponse {
     pub name: String,
     pub version: String,
     pub plugin_type: Vec<PluginType>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct AccountBalanceItemResponse {
     pub date: NaiveDate,
     pub balance: AmountResponse,
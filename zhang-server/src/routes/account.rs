@@ -7,19 +7,27 @@ use axum::Json;
 use chrono::Utc;
 use itertools::Itertools;
 use log::info;
-use tokio::sync::RwLock;
-use uuid::Uuid;
 use zhang_ast::amount::Amount;
 use zhang_ast::{Account, BalanceCheck, BalancePad, Currency, Date, Directive, Document, ZhangString};
 use zhang_core::domains::schemas::AccountJournalDomain;
-use zhang_core::ledger::Ledger;
 use zhang_core::utils::calculable::Calculable;
 
-use crate::request::AccountBalanceRequest;
-use crate::response::{AccountBalanceItemResponse, AccountInfoResponse, AccountResponse, AmountResponse, DocumentResponse, ResponseWrapper};
-use crate::{ApiResult, LedgerState, ReloadSender};
+use crate::auth::{sign_directives, CurrentUser};
+use crate::openapi::LedgerHeader;
+use crate::request::{AccountBalanceRequest, FilesUploadForm};
+use crate::response::{AccountBalanceItemResponse, AccountInfoResponse, AccountResponse, AmountResponse, DocumentResponse, ResponseWrapper, SuccessWrapper};
+use crate::state::SelectedLedger;
+use crate::util::document_path;
+use crate::{ApiResult, ReloadSender};
 
-pub async fn get_account_list(ledger: State<Arc<RwLock<Ledger>>>) -> ApiResult<Vec<AccountResponse>> {
+#[utoipa::path(
+    get,
+    path = "/api/accounts",
+    tag = "account",
+    params(LedgerHeader),
+    responses((status = 200, description = "OK", body = SuccessWrapper<Vec<AccountResponse>>))
+)]
+pub async fn get_account_list(ledger: SelectedLedger) -> ApiResult<Vec<AccountResponse>> {
     let ledger = ledger.read().await;
     let timezone = &ledger.options.timezone;
     let mut operations = ledger.operations();
@@ -44,7 +52,14 @@ pub async fn get_account_list(ledger: State<Arc<RwLock<Ledger>>>) -> ApiResult<V
     ResponseWrapper::json(ret)
 }
 
-pub async fn get_account_info(ledger: State<Arc<RwLock<Ledger>>>, path: Path<(String,)>) -> ApiResult<AccountInfoResponse> {
+#[utoipa::path(
+    get,
+    path = "/api/accounts/{account_name}",
+    tag = "account",
+    params(("account_name" = String, Path), LedgerHeader),
+    responses((status = 200, description = "OK", body = SuccessWrapper<AccountInfoResponse>))
+)]
+pub async fn get_account_info(ledger: SelectedLedger, path: Path<(String,)>) -> ApiResult<AccountInfoResponse> {
     let account_name = path.0 .0;
     let ledger = ledger.read().await;
     let timezone = &ledger.options.timezone;
@@ -72,12 +87,19 @@ pub async fn get_account_info(ledger: State<Arc<RwLock<Ledger>>>, path: Path<(St
     })
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/accounts/{account_name}/documents",
+    tag = "account",
+    params(("account_name" = String, Path), LedgerHeader),
+    request_body(content = FilesUploadForm, content_type = "multipart/form-data"),
+    responses((status = 201, description = "Created"))
+)]
 pub async fn upload_account_document(
-    ledger: State<Arc<RwLock<Ledger>>>, reload_sender: State<Arc<ReloadSender>>, path: Path<(String,)>, mut multipart: Multipart,
+    ledger: SelectedLedger, reload_sender: State<Arc<ReloadSender>>, user: CurrentUser, path: Path<(String,)>, mut multipart: Multipart,
 ) -> ApiResult<()> {
     let account_name = path.0 .0;
     let ledger_stage = ledger.read().await;
-    let entry = &ledger_stage.entry.0;
     let mut documents = vec![];
 
     while let Some(field) = multipart.next_field().await.unwrap() {
@@ -85,21 +107,21 @@ pub async fn upload_account_document(
         let file_name = field.file_name().unwrap().to_string();
         let _content_type = field.content_type().unwrap().to_string();
 
-        let v4 = Uuid::new_v4();
-        let buf = entry.join("attachments").join(v4.to_string()).join(&file_name);
-        let striped_buf = buf.strip_prefix(entry).unwrap();
-        info!("uploading document `{}`(id={}) to account {}", file_name, &v4.to_string(), &account_name);
-
         let content_buf = field.bytes().await.unwrap();
+        let date = Date::now(&ledger_stage.options.timezone);
+        let Some(path) = document_path(&ledger_stage, &file_name, &content_buf, date.naive_date()).await? else {
+            return ResponseWrapper::bad_request();
+        };
+        let striped_path_string = path.to_string_lossy().to_string();
+        info!("uploading document `{}` to account {}", striped_path_string, &account_name);
 
-        let striped_path_string = striped_buf.to_string_lossy().to_string();
         ledger_stage
             .data_source
             .async_save(&ledger_stage, striped_path_string.to_owned(), &content_buf)
             .await?;
 
         documents.push(Directive::Document(Document {
-            date: Date::now(&ledger_stage.options.timezone),
+            date,
             account: Account::from_str(&account_name)?,
             filename: ZhangString::QuoteString(striped_path_string),
             tags: None,
@@ -108,12 +130,20 @@ pub async fn upload_account_document(
         }));
     }
 
+    sign_directives(&user, &mut documents);
     ledger_stage.data_source.async_append(&ledger_stage, documents).await?;
     reload_sender.reload();
     ResponseWrapper::<()>::created()
 }
 
-pub async fn get_account_balance_data(ledger: State<LedgerState>, params: Path<(String,)>) -> ApiResult<HashMap<Currency, Vec<AccountBalanceItemResponse>>> {
+#[utoipa::path(
+    get,
+    path = "/api/accounts/{account_name}/balances",
+    tag = "account",
+    params(("account_name" = String, Path), LedgerHeader),
+    responses((status = 200, description = "OK", body = SuccessWrapper<HashMap<String, Vec<AccountBalanceItemResponse>>>))
+)]
+pub async fn get_account_balance_data(ledger: SelectedLedger, params: Path<(String,)>) -> ApiResult<HashMap<Currency, Vec<AccountBalanceItemResponse>>> {
     let account_name = params.0 .0;
     let ledger = ledger.read().await;
     let operations = ledger.operations();
@@ -138,7 +168,14 @@ pub async fn get_account_balance_data(ledger: State<LedgerState>, params: Path<(
     )
 }
 
-pub async fn get_account_documents(ledger: State<Arc<RwLock<Ledger>>>, params: Path<(String,)>) -> ApiResult<Vec<DocumentResponse>> {
+#[utoipa::path(
+    get,
+    path = "/api/accounts/{account_name}/documents",
+    tag = "account",
+    params(("account_name" = String, Path), LedgerHeader),
+    responses((status = 200, description = "OK", body = SuccessWrapper<Vec<DocumentResponse>>))
+)]
+pub async fn get_account_documents(ledger: SelectedLedger, params: Path<(String,)>) -> ApiResult<Vec<DocumentResponse>> {
     let account_name = params.0 .0;
 
     let ledger = ledger.read().await;
@@ -157,13 +194,21 @@ pub async fn get_account_documents(ledger: State<Arc<RwLock<Ledger>>>, params: P
             extension: None,
             account: doc.document_type.as_account(),
             trx_id: doc.document_type.as_trx(),
+            hash: doc.hash.clone(),
         })
         .collect_vec();
 
     ResponseWrapper::json(rows)
 }
 
-pub async fn get_account_journals(ledger: State<Arc<RwLock<Ledger>>>, params: Path<(String,)>) -> ApiResult<Vec<AccountJournalDomain>> {
+#[utoipa::path(
+    get,
+    path = "/api/accounts/{account_name}/journals",
+    tag = "account",
+    params(("account_name" = String, Path), LedgerHeader),
+    responses((status = 200, description = "OK", body = SuccessWrapper<Vec<AccountJournalDomain>>))
+)]
+pub async fn get_account_journals(ledger: SelectedLedger, params: Path<(String,)>) -> ApiResult<Vec<AccountJournalDomain>> {
     let account_name = params.0 .0;
     let ledger = ledger.read().await;
     let mut operations = ledger.operations();
@@ -173,8 +218,16 @@ pub async fn get_account_journals(ledger: State<Arc<RwLock<Ledger>>>, params: Pa
     ResponseWrapper::json(journals)
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/accounts/{account_name}/balances",
+    tag = "account",
+    params(("account_name" = String, Path), LedgerHeader),
+    request_body = AccountBalanceRequest,
+    responses((status = 201, description = "Created"))
+)]
 pub async fn create_account_balance(
-    ledger: State<Arc<RwLock<Ledger>>>, reload_sender: State<Arc<ReloadSender>>, params: Path<(String,)>, Json(payload): Json<AccountBalanceRequest>,
+    ledger: SelectedLedger, reload_sender: State<Arc<ReloadSender>>, user: CurrentUser, params: Path<(String,)>, Json(payload): Json<AccountBalanceRequest>,
 ) -> ApiResult<()> {
     let target_account = params.0 .0;
     let ledger = ledger.read().await;
@@ -201,13 +254,23 @@ pub async fn create_account_balance(
         }),
     };
 
-    ledger.data_source.async_append(&ledger, vec![balance]).await.unwrap();
+    let mut directives = vec![balance];
+    sign_directives(&user, &mut directives);
+    ledger.data_source.async_append(&ledger, directives).await.unwrap();
     reload_sender.reload();
     ResponseWrapper::<()>::created()
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/accounts/batch-balances",
+    tag = "account",
+    params(LedgerHeader),
+    request_body = Vec<AccountBalanceRequest>,
+    responses((status = 201, description = "Created"))
+)]
 pub async fn create_batch_account_balances(
-    ledger: State<Arc<RwLock<Ledger>>>, reload_sender: State<Arc<ReloadSender>>, Json(payload): Json<Vec<AccountBalanceRequest>>,
+    ledger: SelectedLedger, reload_sender: State<Arc<ReloadSender>>, user: CurrentUser, Json(payload): Json<Vec<AccountBalanceRequest>>,
 ) -> ApiResult<()> {
     let ledger = ledger.read().await;
     let mut directives = vec![];
@@ -236,6 +299,7 @@ pub async fn create_batch_account_balances(
         directives.push(balance);
     }
 
+    sign_directives(&user, &mut directives);
     ledger.data_source.async_append(&ledger, directives).await?;
     reload_sender.reload();
     ResponseWrapper::<()>::created()
@@ -1,21 +1,35 @@
 use std::cmp::Reverse;
 use std::ops::Sub;
+use std::str::FromStr;
 use std::sync::Arc;
 
 use axum::extract::{Path, Query, State};
+use axum::Json;
 use chrono::NaiveDate;
 use itertools::Itertools;
 use now::DateTimeNow;
-use tokio::sync::RwLock;
 use zhang_ast::amount::Amount;
-use zhang_core::ledger::Ledger;
+use zhang_ast::{Budget, BudgetAdd, Date, Directive, Meta};
+use zhang_core::domains::schemas::{BudgetReportDomain, BudgetReportInterval};
 use zhang_core::store::BudgetIntervalDetail;
+use zhang_core::utils::string_::StringExt;
 
-use crate::request::BudgetListRequest;
-use crate::response::{BudgetInfoResponse, BudgetIntervalEventResponse, BudgetListItemResponse, ResponseWrapper};
-use crate::ApiResult;
+use crate::auth::{sign_directives, CurrentUser};
+use crate::error::ServerError;
+use crate::openapi::LedgerHeader;
+use crate::request::{AddBudgetAmountRequest, BudgetListRequest, BudgetReportRequest, CreateBudgetRequest};
+use crate::response::{BudgetInfoResponse, BudgetIntervalEventResponse, BudgetListItemResponse, CreatedDirectiveResponse, ResponseWrapper, SuccessWrapper};
+use crate::state::SelectedLedger;
+use crate::{ApiResult, ReloadSender};
 
-pub async fn get_budget_list(ledger: State<Arc<RwLock<Ledger>>>, params: Query<BudgetListRequest>) -> ApiResult<Vec<BudgetListItemResponse>> {
+#[utoipa::path(
+    get,
+    path = "/api/budgets",
+    tag = "budget",
+    params(LedgerHeader, BudgetListRequest),
+    responses((status = 200, description = "OK", body = SuccessWrapper<Vec<BudgetListItemResponse>>))
+)]
+pub async fn get_budget_list(ledger: SelectedLedger, params: Query<BudgetListRequest>) -> ApiResult<Vec<BudgetListItemResponse>> {
     let interval = params.as_interval();
 
     let ledger = ledger.read().await;
@@ -38,7 +52,14 @@ pub async fn get_budget_list(ledger: State<Arc<RwLock<Ledger>>>, params: Query<B
     ResponseWrapper::json(ret)
 }
 
-pub async fn get_budget_info(ledger: State<Arc<RwLock<Ledger>>>, paths: Path<(String,)>, params: Query<BudgetListRequest>) -> ApiResult<BudgetInfoResponse> {
+#[utoipa::path(
+    get,
+    path = "/api/budgets/{budget_name}",
+    tag = "budget",
+    params(("budget_name" = String, Path), LedgerHeader, BudgetListRequest),
+    responses((status = 200, description = "OK", body = SuccessWrapper<BudgetInfoResponse>))
+)]
+pub async fn get_budget_info(ledger: SelectedLedger, paths: Path<(String,)>, params: Query<BudgetListRequest>) -> ApiResult<BudgetInfoResponse> {
     let (budget_name,) = paths.0;
     let ledger = ledger.read().await;
     let operations = ledger.operations();
@@ -74,7 +95,14 @@ pub async fn get_budget_info(ledger: State<Arc<RwLock<Ledger>>>, paths: Path<(St
     })
 }
 
-pub async fn get_budget_interval_detail(ledger: State<Arc<RwLock<Ledger>>>, paths: Path<(String, u32, u32)>) -> ApiResult<Vec<BudgetIntervalEventResponse>> {
+#[utoipa::path(
+    get,
+    path = "/api/budgets/{budget_name}/interval/{year}/{month}",
+    tag = "budget",
+    params(("budget_name" = String, Path), ("year" = u32, Path), ("month" = u32, Path), LedgerHeader),
+    responses((status = 200, description = "OK", body = SuccessWrapper<Vec<BudgetIntervalEventResponse>>))
+)]
+pub async fn get_budget_interval_detail(ledger: SelectedLedger, paths: Path<(String, u32, u32)>) -> ApiResult<Vec<BudgetIntervalEventResponse>> {
     let (budget_name, year, month) = paths.0;
     let ledger = ledger.read().await;
     let operations = ledger.operations();
@@ -115,3 +143,115 @@ pub async fn get_budget_interval_detail(ledger: State<Arc<RwLock<Ledger>>>, path
     ret.sort_by_key(|a| Reverse(a.naive_datetime()));
     ResponseWrapper::json(ret)
 }
+
+/// write the budget directive, followed by the budget-add one if the initial assigned amount is given
+#[utoipa::path(
+    post,
+    path = "/api/budgets",
+    tag = "budget",
+    params(LedgerHeader),
+    request_body = CreateBudgetRequest,
+    responses((status = 200, description = "OK", body = SuccessWrapper<CreatedDirectiveResponse>))
+)]
+pub async fn create_budget(
+    ledger: SelectedLedger, reload_sender: State<Arc<ReloadSender>>, user: CurrentUser, Json(payload): Json<CreateBudgetRequest>,
+) -> ApiResult<CreatedDirectiveResponse> {
+    let ledger = ledger.read().await;
+    let mut operations = ledger.operations();
+
+    // the budget name is written unquoted, so that the one the grammar cannot parse back is rejected
+    if payload.name.is_empty() || payload.name.contains(|c: char| c.is_whitespace() || "\":(),".contains(c)) {
+        return Err(ServerError::BadRequest(format!("invalid budget name: {}", payload.name)));
+    }
+    if operations.contains_budget(&payload.name) {
+        return Err(ServerError::BadRequest(format!("budget {} is already defined", payload.name)));
+    }
+    if !operations.exist_commodity(&payload.commodity)? {
+        return Err(ServerError::BadRequest(format!("commodity {} is not defined", payload.commodity)));
+    }
+
+    let date = payload.date.map(Date::Date).unwrap_or_else(|| Date::now(&ledger.options.timezone));
+    let mut meta = Meta::default();
+    if let Some(alias) = payload.alias {
+        meta.insert("alias".to_owned(), alias.to_quote());
+    }
+    if let Some(category) = payload.category {
+        meta.insert("category".to_owned(), category.to_quote());
+    }
+    let mut directives = vec![Directive::Budget(Budget {
+        date: date.clone(),
+        name: payload.name.clone(),
+        commodity: payload.commodity.clone(),
+        meta,
+    })];
+    if let Some(assigned_amount) = payload.assigned_amount {
+        directives.push(Directive::BudgetAdd(BudgetAdd {
+            date,
+            name: payload.name,
+            amount: Amount::new(assigned_amount, payload.commodity),
+            meta: Default::default(),
+        }));
+    }
+
+    sign_directives(&user, &mut directives);
+    let spans = ledger.data_source.async_append(&ledger, directives).await?;
+    reload_sender.reload();
+    let span = spans.into_iter().next().expect("the span of appended budget");
+    ResponseWrapper::json(CreatedDirectiveResponse::from(span))
+}
+
+/// assign the amount to budget by writing the budget-add directive
+#[utoipa::path(
+    post,
+    path = "/api/budgets/{budget_name}/amounts",
+    tag = "budget",
+    params(("budget_name" = String, Path), LedgerHeader),
+    request_body = AddBudgetAmountRequest,
+    responses((status = 200, description = "OK", body = SuccessWrapper<CreatedDirectiveResponse>))
+)]
+pub async fn add_budget_amount(
+    ledger: SelectedLedger, reload_sender: State<Arc<ReloadSender>>, user: CurrentUser, paths: Path<(String,)>, Json(payload): Json<AddBudgetAmountRequest>,
+) -> ApiResult<CreatedDirectiveResponse> {
+    let (budget_name,) = paths.0;
+    let ledger = ledger.read().await;
+    let operations = ledger.operations();
+
+    let Some(budget) = operations.all_budgets()?.into_iter().find(|budget| budget.name.eq(&budget_name)) else {
+        return ResponseWrapper::not_found();
+    };
+    if budget.closed {
+        return Err(ServerError::BadRequest(format!("budget {} is closed", budget_name)));
+    }
+    let mut directives = vec![Directive::BudgetAdd(BudgetAdd {
+        date: payload.date.map(Date::Date).unwrap_or_else(|| Date::now(&ledger.options.timezone)),
+        name: budget.name,
+        amount: Amount::new(payload.amount, budget.commodity),
+        meta: Default::default(),
+    })];
+
+    sign_directives(&user, &mut directives);
+    let mut spans = ledger.data_source.async_append(&ledger, directives).await?;
+    reload_sender.reload();
+    let span = spans.pop().expect("the span of appended budget-add");
+    ResponseWrapper::json(CreatedDirectiveResponse::from(span))
+}
+
+/// the actual vs. budgeted amounts of all budgets, bucketed by month or quarter
+#[utoipa::path(
+    get,
+    path = "/api/budget-report",
+    tag = "budget",
+    params(LedgerHeader, BudgetReportRequest),
+    responses((status = 200, description = "OK", body = SuccessWrapper<Vec<BudgetReportDomain>>))
+)]
+pub async fn get_budget_report(ledger: SelectedLedger, params: Query<BudgetReportRequest>) -> ApiResult<Vec<BudgetReportDomain>> {
+    let interval = match params.interval.as_deref() {
+        Some(interval) => {
+            BudgetReportInterval::from_str(interval).map_err(|_| ServerError::BadRequest(format!("invalid budget report interval: {}", interval)))?
+        }
+        None => BudgetReportInterval::Month,
+    };
+    let ledger = ledger.read().await;
+    let operations = ledger.operations();
+    ResponseWrapper::json(operations.budget_report(interval)?)
+}
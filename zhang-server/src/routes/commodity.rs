@@ -1,16 +1,22 @@
-use std::sync::Arc;
-
-use axum::extract::{Path, State};
+use axum::extract::Path;
 use itertools::Itertools;
-use tokio::sync::RwLock;
 use zhang_core::constants::COMMODITY_GROUP;
 use zhang_core::domains::schemas::{CommodityDomain, MetaType};
-use zhang_core::ledger::Ledger;
+use zhang_core::utils::amount_format::format_amount;
 
-use crate::response::{CommodityDetailResponse, CommodityListItemResponse, CommodityLot, CommodityPrice, ResponseWrapper};
+use crate::openapi::LedgerHeader;
+use crate::response::{CommodityDetailResponse, CommodityListItemResponse, CommodityLot, CommodityPrice, ResponseWrapper, SuccessWrapper};
+use crate::state::SelectedLedger;
 use crate::ApiResult;
 
-pub async fn get_all_commodities(ledger: State<Arc<RwLock<Ledger>>>) -> ApiResult<Vec<CommodityListItemResponse>> {
+#[utoipa::path(
+    get,
+    path = "/api/commodities",
+    tag = "commodity",
+    params(LedgerHeader),
+    responses((status = 200, description = "OK", body = SuccessWrapper<Vec<CommodityListItemResponse>>))
+)]
+pub async fn get_all_commodities(ledger: SelectedLedger) -> ApiResult<Vec<CommodityListItemResponse>> {
     let ledger = ledger.read().await;
 
     let operations = ledger.operations();
@@ -25,13 +31,17 @@ pub async fn get_all_commodities(ledger: State<Arc<RwLock<Ledger>>>) -> ApiResul
         let group = operations
             .meta(MetaType::CommodityMeta, commodity.name.as_str(), COMMODITY_GROUP)?
             .map(|it| it.value);
+        let formatted_total_amount = format_amount(&commodity, &amount);
         ret.push(CommodityListItemResponse {
             name: commodity.name,
             precision: commodity.precision,
             prefix: commodity.prefix,
             suffix: commodity.suffix,
             rounding: commodity.rounding.to_string(),
+            grouping_separator: commodity.grouping_separator,
+            decimal_symbol: commodity.decimal_symbol,
             group,
+            formatted_total_amount,
             total_amount: amount,
             latest_price_date: latest_price.as_ref().map(|it| it.datetime),
             latest_price_amount: latest_price.as_ref().map(|it| it.amount.clone()),
@@ -42,7 +52,14 @@ pub async fn get_all_commodities(ledger: State<Arc<RwLock<Ledger>>>) -> ApiResul
     ResponseWrapper::json(ret)
 }
 
-pub async fn get_single_commodity(ledger: State<Arc<RwLock<Ledger>>>, params: Path<(String,)>) -> ApiResult<CommodityDetailResponse> {
+#[utoipa::path(
+    get,
+    path = "/api/commodities/{commodity_name}",
+    tag = "commodity",
+    params(("commodity_name" = String, Path), LedgerHeader),
+    responses((status = 200, description = "OK", body = SuccessWrapper<CommodityDetailResponse>))
+)]
+pub async fn get_single_commodity(ledger: SelectedLedger, params: Path<(String,)>) -> ApiResult<CommodityDetailResponse> {
     let commodity_name = params.0 .0;
     let ledger = ledger.read().await;
     let operating_currency = ledger.options.operating_currency.clone();
@@ -55,12 +72,16 @@ pub async fn get_single_commodity(ledger: State<Arc<RwLock<Ledger>>>, params: Pa
     let group = operations
         .meta(MetaType::CommodityMeta, commodity.name.as_str(), COMMODITY_GROUP)?
         .map(|it| it.value);
+    let formatted_total_amount = format_amount(&commodity, &amount);
     let commodity_item = CommodityListItemResponse {
         name: commodity.name,
         precision: commodity.precision,
         prefix: commodity.prefix,
         suffix: commodity.suffix,
         rounding: commodity.rounding.to_string(),
+        grouping_separator: commodity.grouping_separator,
+        decimal_symbol: commodity.decimal_symbol,
+        formatted_total_amount,
         total_amount: amount,
         group,
         latest_price_date: latest_price.as_ref().map(|it| it.datetime),
@@ -76,6 +97,7 @@ pub async fn get_single_commodity(ledger: State<Arc<RwLock<Ledger>>>, params: Pa
             amount: it.amount,
             price_amount: it.price.as_ref().map(|price| price.number.clone()),
             price_commodity: it.price.as_ref().map(|price| price.currency.clone()),
+            label: it.label,
             account: it.account.name().to_owned(),
         })
         .collect_vec();
@@ -5,15 +5,18 @@ use async_stream::try_stream;
 use axum::extract::{Query, State};
 use axum::response::sse::{Event, KeepAlive};
 use axum::response::Sse;
+use axum::Extension;
 use futures_util::Stream;
 use itertools::Itertools;
-use tokio::sync::RwLock;
+use serde_json::Value;
 use zhang_core::domains::schemas::{ErrorDomain, OptionDomain};
-use zhang_core::ledger::Ledger;
 
+use crate::auth::{AuthUser, CurrentUser};
 use crate::broadcast::Broadcaster;
-use crate::request::JournalRequest;
-use crate::response::{BasicInfo, Pageable, ResponseWrapper};
+use crate::openapi::LedgerHeader;
+use crate::request::ErrorRequest;
+use crate::response::{BasicInfo, Pageable, ResponseWrapper, SuccessWrapper};
+use crate::state::SelectedLedger;
 use crate::{ApiResult, ReloadSender};
 
 pub async fn backend_only_info() -> &'static str {
@@ -22,6 +25,12 @@ pub async fn backend_only_info() -> &'static str {
     try to enable the feature and compile again"
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/sse",
+    tag = "common",
+    responses((status = 200, description = "OK", content_type = "text/event-stream", body = String))
+)]
 pub async fn sse(broadcaster: State<Arc<Broadcaster>>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
     let mut receiver = broadcaster.new_client().await;
     Sse::new(try_stream! {
@@ -32,12 +41,25 @@ pub async fn sse(broadcaster: State<Arc<Broadcaster>>) -> Sse<impl Stream<Item =
     .keep_alive(KeepAlive::default())
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/reload",
+    tag = "common",
+    responses((status = 200, description = "OK", body = SuccessWrapper<String>))
+)]
 pub async fn reload(reload_sender: State<Arc<ReloadSender>>) -> ApiResult<String> {
-    reload_sender.0 .0.try_send(1).ok();
+    reload_sender.reload();
     ResponseWrapper::json("Ok".to_string())
 }
 
-pub async fn get_basic_info(ledger: State<Arc<RwLock<Ledger>>>) -> ApiResult<BasicInfo> {
+#[utoipa::path(
+    get,
+    path = "/api/info",
+    tag = "common",
+    params(LedgerHeader),
+    responses((status = 200, description = "OK", body = SuccessWrapper<BasicInfo>))
+)]
+pub async fn get_basic_info(ledger: SelectedLedger) -> ApiResult<BasicInfo> {
     let ledger = ledger.read().await;
     let operations = ledger.operations();
 
@@ -48,10 +70,31 @@ pub async fn get_basic_info(ledger: State<Arc<RwLock<Ledger>>>) -> ApiResult<Bas
     })
 }
 
-pub async fn get_errors(ledger: State<Arc<RwLock<Ledger>>>, params: Query<JournalRequest>) -> ApiResult<Pageable<ErrorDomain>> {
+/// the authenticated user of request, `null` if the authentication is not enabled
+#[utoipa::path(
+    get,
+    path = "/api/auth/user",
+    tag = "common",
+    responses((status = 200, description = "OK", body = SuccessWrapper<Option<AuthUser>>))
+)]
+pub async fn get_current_user(user: CurrentUser) -> ApiResult<Option<AuthUser>> {
+    ResponseWrapper::json(user.map(|Extension(user)| user))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/errors",
+    tag = "common",
+    params(LedgerHeader, ErrorRequest),
+    responses((status = 200, description = "OK", body = SuccessWrapper<Pageable<ErrorDomain>>))
+)]
+pub async fn get_errors(ledger: SelectedLedger, params: Query<ErrorRequest>) -> ApiResult<Pageable<ErrorDomain>> {
     let ledger = ledger.read().await;
     let mut operations = ledger.operations();
-    let errors = operations.errors()?;
+    let errors = match params.severity {
+        Some(severity) => operations.errors_by_severity(severity)?,
+        None => operations.errors()?,
+    };
     let total_count = errors.len();
     let ret = errors
         .iter()
@@ -62,14 +105,28 @@ pub async fn get_errors(ledger: State<Arc<RwLock<Ledger>>>, params: Query<Journa
     ResponseWrapper::json(Pageable::new(total_count as u32, params.page(), params.limit(), ret))
 }
 
-pub async fn get_all_options(ledger: State<Arc<RwLock<Ledger>>>) -> ApiResult<Vec<OptionDomain>> {
+#[utoipa::path(
+    get,
+    path = "/api/options",
+    tag = "common",
+    params(LedgerHeader),
+    responses((status = 200, description = "OK", body = SuccessWrapper<Vec<OptionDomain>>))
+)]
+pub async fn get_all_options(ledger: SelectedLedger) -> ApiResult<Vec<OptionDomain>> {
     let ledger = ledger.read().await;
     let mut operations = ledger.operations();
     let options = operations.options()?;
     ResponseWrapper::json(options)
 }
 
-pub async fn get_store_data(ledger: State<Arc<RwLock<Ledger>>>) -> ApiResult<serde_json::Value> {
+#[utoipa::path(
+    get,
+    path = "/api/store",
+    tag = "common",
+    params(LedgerHeader),
+    responses((status = 200, description = "OK", body = SuccessWrapper<Value>))
+)]
+pub async fn get_store_data(ledger: SelectedLedger) -> ApiResult<serde_json::Value> {
     let ledger = ledger.read().await;
     let store = ledger.store.read().unwrap();
     let value = serde_json::to_value(&*store).unwrap();
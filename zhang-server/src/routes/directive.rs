@@ -0,0 +1,142 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::extract::{Path, State};
+use axum::Json;
+use itertools::Itertools;
+use log::error;
+use uuid::Uuid;
+use zhang_ast::SpanInfo;
+use zhang_core::data_source::SpanEdit;
+use zhang_core::domains::schemas::ErrorDomain;
+use zhang_core::ledger::Ledger;
+
+use crate::broadcast::Broadcaster;
+use crate::error::ServerError;
+use crate::openapi::LedgerHeader;
+use crate::request::UpdateDirectiveRequest;
+use crate::response::{DirectiveResponse, ResponseWrapper, SuccessWrapper};
+use crate::state::SelectedLedger;
+use crate::{metrics, swap_reloaded_ledger, ApiResult};
+
+#[utoipa::path(
+    get,
+    path = "/api/directives/{directive_id}",
+    tag = "directive",
+    params(("directive_id" = Uuid, Path), LedgerHeader),
+    responses((status = 200, description = "OK", body = SuccessWrapper<DirectiveResponse>))
+)]
+pub async fn get_directive(ledger: SelectedLedger, path: Path<(String,)>) -> ApiResult<DirectiveResponse> {
+    let Ok(directive_id) = Uuid::from_str(&path.0 .0) else {
+        return ResponseWrapper::bad_request();
+    };
+    let ledger = ledger.read().await;
+    let Some(directive) = ledger.directive(&directive_id) else {
+        return ResponseWrapper::not_found();
+    };
+    ResponseWrapper::json(DirectiveResponse {
+        id: directive_id,
+        path: directive.span.filename.as_ref().map(|it| it.to_string_lossy().to_string()).unwrap_or_default(),
+        span_start: directive.span.start,
+        span_end: directive.span.end,
+        content: directive.span.content.trim_end().to_owned(),
+    })
+}
+
+/// replace the source of directive by the given content, and respond with the errors of ledger reloaded from the edited files
+#[utoipa::path(
+    put,
+    path = "/api/directives/{directive_id}",
+    tag = "directive",
+    params(("directive_id" = Uuid, Path), LedgerHeader),
+    request_body = UpdateDirectiveRequest,
+    responses((status = 200, description = "OK", body = SuccessWrapper<Vec<ErrorDomain>>))
+)]
+pub async fn update_directive(
+    ledger: SelectedLedger, broadcaster: State<Arc<Broadcaster>>, path: Path<(String,)>, Json(payload): Json<UpdateDirectiveRequest>,
+) -> ApiResult<Vec<ErrorDomain>> {
+    let Ok(directive_id) = Uuid::from_str(&path.0 .0) else {
+        return ResponseWrapper::bad_request();
+    };
+    let Some(span) = directive_span(&ledger, &directive_id).await else {
+        return ResponseWrapper::not_found();
+    };
+    {
+        let ledger = ledger.read().await;
+        ledger.data_source.async_edit_span(&ledger, &span, SpanEdit::Update(&payload.content)).await?;
+    }
+
+    // the malformed content is reported as the error of reloaded ledger, and the original directive is restored
+    // only if the edited files cannot be loaded at all
+    let trailing = &span.content[span.content.trim_end().len()..];
+    let updated_content = format!("{}{}", payload.content.trim_end(), trailing);
+    let updated_span = SpanInfo {
+        end: span.start + updated_content.len(),
+        content: updated_content,
+        ..span.clone()
+    };
+    reload_or_revert(&ledger, &broadcaster, &updated_span, SpanEdit::Update(&span.content)).await
+}
+
+/// remove the source of directive, and respond with the errors of ledger reloaded from the edited files
+#[utoipa::path(
+    delete,
+    path = "/api/directives/{directive_id}",
+    tag = "directive",
+    params(("directive_id" = Uuid, Path), LedgerHeader),
+    responses((status = 200, description = "OK", body = SuccessWrapper<Vec<ErrorDomain>>))
+)]
+pub async fn delete_directive(ledger: SelectedLedger, broadcaster: State<Arc<Broadcaster>>, path: Path<(String,)>) -> ApiResult<Vec<ErrorDomain>> {
+    let Ok(directive_id) = Uuid::from_str(&path.0 .0) else {
+        return ResponseWrapper::bad_request();
+    };
+    let Some(span) = directive_span(&ledger, &directive_id).await else {
+        return ResponseWrapper::not_found();
+    };
+    {
+        let ledger = ledger.read().await;
+        ledger.data_source.async_edit_span(&ledger, &span, SpanEdit::Delete).await?;
+    }
+
+    let removed_span = SpanInfo {
+        end: span.start,
+        content: String::new(),
+        ..span.clone()
+    };
+    reload_or_revert(&ledger, &broadcaster, &removed_span, SpanEdit::Insert(&span.content)).await
+}
+
+async fn directive_span(ledger: &SelectedLedger, directive_id: &Uuid) -> Option<SpanInfo> {
+    let ledger = ledger.read().await;
+    ledger.directive(directive_id).map(|it| it.span.clone())
+}
+
+/// the ledger is reloaded aside and swapped in, so that the queries are served during reloading.
+/// the edit is reverted at the edited span if the ledger cannot be reloaded from the edited files
+async fn reload_or_revert(ledger: &SelectedLedger, broadcaster: &Broadcaster, edited_span: &SpanInfo, revert: SpanEdit<'_>) -> ApiResult<Vec<ErrorDomain>> {
+    let ((entry, endpoint), data_source) = {
+        let guard = ledger.read().await;
+        (guard.entry.clone(), guard.data_source.clone())
+    };
+    let start_time = Instant::now();
+    match Ledger::async_load(entry, endpoint, data_source).await {
+        Ok(reloaded_ledger) => {
+            let errors = reloaded_ledger.operations().errors()?;
+            let changed_files = edited_span.filename.iter().cloned().collect_vec();
+            let duration = start_time.elapsed();
+            metrics::registry().record_reload(&ledger.1, duration);
+            let event = swap_reloaded_ledger(&ledger.1, &ledger.0, reloaded_ledger, &changed_files, duration).await?;
+            broadcaster.broadcast(event).await;
+            ResponseWrapper::json(errors)
+        }
+        Err(e) => {
+            metrics::registry().record_reload_failure(&ledger.1);
+            let guard = ledger.read().await;
+            if let Err(revert_error) = guard.data_source.async_edit_span(&guard, edited_span, revert).await {
+                error!("cannot revert the edit of directive: {}", revert_error);
+            }
+            Err(ServerError::BadRequest(format!("the edited ledger cannot be loaded: {}", e)))
+        }
+    }
+}
@@ -1,21 +1,37 @@
+use std::str::FromStr;
 use std::sync::Arc;
 
-use axum::extract::{Path, State};
+use axum::extract::{Multipart, Path, State};
 use axum::http::header;
 use axum::response::{AppendHeaders, IntoResponse};
 use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
 use base64::Engine as _;
 use bytes::Bytes;
+use chrono::NaiveDate;
 use itertools::Itertools;
 use log::info;
-use tokio::sync::RwLock;
-use zhang_core::ledger::Ledger;
+use uuid::Uuid;
+use zhang_ast::{Account, Date, Directive, Document, SpanInfo, ZhangString};
+use zhang_core::data_source::SpanEdit;
+use zhang_core::utils::string_::escape_with_quote;
 
-use crate::response::{DocumentResponse, ResponseWrapper};
-use crate::util::cacheable_data;
-use crate::ApiResult;
+use crate::auth::{sign_directives, CurrentUser};
+use crate::error::ServerError;
+use crate::openapi::{FileContent, LedgerHeader};
+use crate::request::DocumentsUploadForm;
+use crate::response::{CreatedDirectiveResponse, DocumentResponse, ResponseWrapper, SuccessWrapper};
+use crate::state::SelectedLedger;
+use crate::util::{cacheable_data, document_path};
+use crate::{ApiResult, ReloadSender};
 
-pub async fn download_document(ledger: State<Arc<RwLock<Ledger>>>, path: Path<(String,)>) -> impl IntoResponse {
+#[utoipa::path(
+    get,
+    path = "/api/documents/{file_path}",
+    tag = "document",
+    params(("file_path" = String, Path, description = "the path of document encoded by base64"), LedgerHeader),
+    responses((status = 200, description = "OK", content_type = "application/octet-stream", body = FileContent))
+)]
+pub async fn download_document(ledger: SelectedLedger, path: Path<(String,)>) -> impl IntoResponse {
     let encoded_file_path = path.0 .0;
     let filename = String::from_utf8(BASE64_STANDARD.decode(&encoded_file_path).unwrap()).unwrap();
     let ledger = ledger.read().await;
@@ -34,25 +50,132 @@ pub async fn download_document(ledger: State<Arc<RwLock<Ledger>>>, path: Path<(S
     (headers, bytes)
 }
 
-pub async fn get_documents(ledger: State<Arc<RwLock<Ledger>>>) -> ApiResult<Vec<DocumentResponse>> {
+#[utoipa::path(
+    get,
+    path = "/api/documents",
+    tag = "document",
+    params(LedgerHeader),
+    responses((status = 200, description = "OK", body = SuccessWrapper<Vec<DocumentResponse>>))
+)]
+pub async fn get_documents(ledger: SelectedLedger) -> ApiResult<Vec<DocumentResponse>> {
     let ledger = ledger.read().await;
     let operations = ledger.operations();
     let store = operations.read();
 
-    let rows = store
-        .documents
-        .iter()
-        .cloned()
-        .rev()
-        .map(|doc| DocumentResponse {
-            datetime: doc.datetime.naive_local(),
-            filename: doc.filename.unwrap_or_default(),
-            path: doc.path.clone(),
-            extension: mime_guess::from_path(doc.path).first().map(|it| it.to_string()),
-            account: doc.document_type.as_account(),
-            trx_id: doc.document_type.as_trx(),
-        })
-        .collect_vec();
+    let rows = store.documents.iter().cloned().rev().map(DocumentResponse::from).collect_vec();
 
     ResponseWrapper::json(rows)
 }
+
+enum DocumentTarget {
+    Account(Account),
+    Transaction(SpanInfo),
+}
+
+/// store the uploaded files under the document directory, and attach them to the account given by the `account` field by
+/// appending the `document` directives, or to the transaction given by the `transaction` field by its `document` metas.
+/// the `date` field is the date of documents, today or the date of transaction if absent
+#[utoipa::path(
+    post,
+    path = "/api/documents",
+    tag = "document",
+    params(LedgerHeader),
+    request_body(content = DocumentsUploadForm, content_type = "multipart/form-data"),
+    responses((status = 200, description = "OK", body = SuccessWrapper<Vec<CreatedDirectiveResponse>>))
+)]
+pub async fn upload_documents(
+    ledger: SelectedLedger, reload_sender: State<Arc<ReloadSender>>, user: CurrentUser, mut multipart: Multipart,
+) -> ApiResult<Vec<CreatedDirectiveResponse>> {
+    let ledger = ledger.read().await;
+    let mut account = None;
+    let mut transaction = None;
+    let mut date = None;
+    let mut files = vec![];
+    while let Some(field) = multipart.next_field().await.map_err(|e| ServerError::BadRequest(e.to_string()))? {
+        let name = field.name().unwrap_or_default().to_owned();
+        if let Some(file_name) = field.file_name().map(ToOwned::to_owned) {
+            files.push((file_name, field.bytes().await.map_err(|e| ServerError::BadRequest(e.to_string()))?));
+            continue;
+        }
+        let value = field.text().await.map_err(|e| ServerError::BadRequest(e.to_string()))?;
+        match name.as_str() {
+            "account" => account = Some(value),
+            "transaction" => transaction = Some(value),
+            "date" => {
+                date = Some(Date::Date(
+                    NaiveDate::from_str(&value).map_err(|_| ServerError::BadRequest(format!("invalid date: {}", value)))?,
+                ))
+            }
+            _ => {}
+        }
+    }
+    if files.is_empty() {
+        return Err(ServerError::BadRequest("no document is uploaded".to_owned()));
+    }
+
+    // the target is checked before any file is stored, so that the documents attached to nothing are never left behind
+    let (target, date) = match (account, transaction) {
+        (Some(account), None) => {
+            if ledger.operations().account(&account)?.is_none() {
+                return Err(ServerError::BadRequest(format!("account {} does not exist", account)));
+            }
+            let date = date.unwrap_or_else(|| Date::now(&ledger.options.timezone));
+            (DocumentTarget::Account(Account::from_str(&account)?), date)
+        }
+        (None, Some(transaction)) => {
+            let id = Uuid::from_str(&transaction).map_err(|_| ServerError::BadRequest(format!("invalid transaction id: {}", transaction)))?;
+            let transaction = ledger.operations().read().transactions.get(&id).map(|it| (it.span.clone(), it.datetime));
+            let Some((span, datetime)) = transaction else {
+                return ResponseWrapper::not_found();
+            };
+            (DocumentTarget::Transaction(span), date.unwrap_or(Date::Datetime(datetime.naive_local())))
+        }
+        _ => return Err(ServerError::BadRequest("either account or transaction of documents should be given".to_owned())),
+    };
+
+    let mut paths = vec![];
+    for (file_name, content) in files {
+        let Some(path) = document_path(&ledger, &file_name, &content, date.naive_date()).await? else {
+            return Err(ServerError::BadRequest(format!("invalid document name: {}", file_name)));
+        };
+        let path = path.to_string_lossy().to_string();
+        info!("uploading document `{}` to {}", file_name, path);
+        ledger.data_source.async_save(&ledger, path.clone(), &content).await?;
+        paths.push(path);
+    }
+
+    let spans = match target {
+        DocumentTarget::Account(account) => {
+            let mut documents = paths
+                .into_iter()
+                .map(|path| {
+                    Directive::Document(Document {
+                        date: date.clone(),
+                        account: account.clone(),
+                        filename: ZhangString::QuoteString(path),
+                        tags: None,
+                        links: None,
+                        meta: Default::default(),
+                    })
+                })
+                .collect_vec();
+            sign_directives(&user, &mut documents);
+            ledger.data_source.async_append(&ledger, documents).await?
+        }
+        DocumentTarget::Transaction(span) => {
+            let metas_content = paths.iter().map(|path| format!("  document: {}", escape_with_quote(path))).join("\n");
+            let trimmed_content = span.content.trim_end();
+            let trailing = &span.content[trimmed_content.len()..];
+            let updated_content = format!("{}\n{}", trimmed_content, metas_content);
+            ledger.data_source.async_edit_span(&ledger, &span, SpanEdit::Update(&updated_content)).await?;
+            let updated_content = format!("{}{}", updated_content, trailing);
+            vec![SpanInfo {
+                end: span.start + updated_content.len(),
+                content: updated_content,
+                ..span
+            }]
+        }
+    };
+    reload_sender.reload();
+    ResponseWrapper::json(spans.into_iter().map(CreatedDirectiveResponse::from).collect_vec())
+}
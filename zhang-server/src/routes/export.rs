@@ -0,0 +1,46 @@
+use std::str::FromStr;
+
+use axum::extract::{Path, Query};
+use axum::http::header;
+use axum::response::{AppendHeaders, IntoResponse};
+use zhang_core::export::{export_report, ExportFormat, ExportOptions, ExportReport};
+
+use crate::error::ServerError;
+use crate::openapi::LedgerHeader;
+use crate::request::ExportRequest;
+use crate::state::SelectedLedger;
+
+#[utoipa::path(
+    get,
+    path = "/api/export/{report}",
+    tag = "export",
+    params(("report" = String, Path, description = "the name of report, e.g. `balance-sheet` or `income-statement`"), LedgerHeader, ExportRequest),
+    responses((status = 200, description = "OK", content_type = "text/csv", body = String))
+)]
+pub async fn download_report(ledger: SelectedLedger, path: Path<(String,)>, params: Query<ExportRequest>) -> Result<impl IntoResponse, ServerError> {
+    let report = ExportReport::from_str(&path.0 .0)?;
+    let format = params.format.as_deref().map(ExportFormat::from_str).transpose()?.unwrap_or(ExportFormat::Csv);
+    let options = ExportOptions {
+        columns: params
+            .columns
+            .as_ref()
+            .map(|columns| columns.split(',').map(|it| it.trim().to_owned()).filter(|it| !it.is_empty()).collect()),
+        from: params.from,
+        to: params.to,
+        formatted: params.formatted,
+        depth: params.depth,
+        entity: params.entity.clone(),
+        consolidated: params.consolidated,
+    };
+
+    let ledger = ledger.read().await;
+    let content = export_report(&ledger.operations(), report, format, &options)?;
+    let headers = AppendHeaders([
+        (header::CONTENT_TYPE, format.content_type().to_owned()),
+        (
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}.{}\"", report.as_ref(), format.extension()),
+        ),
+    ]);
+    Ok((headers, content))
+}
@@ -3,14 +3,21 @@ use std::sync::Arc;
 use axum::extract::State;
 use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
 use base64::Engine as _;
-use tokio::sync::RwLock;
-use zhang_core::ledger::Ledger;
 
+use crate::openapi::LedgerHeader;
 use crate::request::FileUpdateRequest;
-use crate::response::{FileDetailResponse, ResponseWrapper};
+use crate::response::{FileDetailResponse, ResponseWrapper, SuccessWrapper};
+use crate::state::SelectedLedger;
 use crate::{ApiResult, ReloadSender};
 
-pub async fn get_files(ledger: State<Arc<RwLock<Ledger>>>) -> ApiResult<Vec<Option<String>>> {
+#[utoipa::path(
+    get,
+    path = "/api/files",
+    tag = "file",
+    params(LedgerHeader),
+    responses((status = 200, description = "OK", body = SuccessWrapper<Vec<Option<String>>>))
+)]
+pub async fn get_files(ledger: SelectedLedger) -> ApiResult<Vec<Option<String>>> {
     let ledger = ledger.read().await;
     let entry_path = &ledger.entry.0;
 
@@ -23,7 +30,14 @@ pub async fn get_files(ledger: State<Arc<RwLock<Ledger>>>) -> ApiResult<Vec<Opti
     ResponseWrapper::json(ret)
 }
 
-pub async fn get_file_content(ledger: State<Arc<RwLock<Ledger>>>, path: axum::extract::Path<(String,)>) -> ApiResult<FileDetailResponse> {
+#[utoipa::path(
+    get,
+    path = "/api/files/{file_path}",
+    tag = "file",
+    params(("file_path" = String, Path, description = "the path of file encoded by base64"), LedgerHeader),
+    responses((status = 200, description = "OK", body = SuccessWrapper<FileDetailResponse>))
+)]
+pub async fn get_file_content(ledger: SelectedLedger, path: axum::extract::Path<(String,)>) -> ApiResult<FileDetailResponse> {
     let encoded_file_path = path.0 .0;
     let filename = String::from_utf8(BASE64_STANDARD.decode(encoded_file_path).unwrap()).unwrap();
     let ledger = ledger.read().await;
@@ -34,8 +48,16 @@ pub async fn get_file_content(ledger: State<Arc<RwLock<Ledger>>>, path: axum::ex
     ResponseWrapper::json(FileDetailResponse { path: filename, content })
 }
 
+#[utoipa::path(
+    put,
+    path = "/api/files/{file_path}",
+    tag = "file",
+    params(("file_path" = String, Path, description = "the path of file encoded by base64"), LedgerHeader),
+    request_body = FileUpdateRequest,
+    responses((status = 201, description = "Created"))
+)]
 pub async fn update_file_content(
-    ledger: State<Arc<RwLock<Ledger>>>, reload_sender: State<Arc<ReloadSender>>, path: axum::extract::Path<(String,)>,
+    ledger: SelectedLedger, reload_sender: State<Arc<ReloadSender>>, path: axum::extract::Path<(String,)>,
     axum::extract::Json(payload): axum::extract::Json<FileUpdateRequest>,
 ) -> ApiResult<()> {
     let encoded_file_path = path.0 .0;
@@ -0,0 +1,53 @@
+use axum::extract::{Query, State};
+use chrono::Utc;
+use zhang_core::domains::schemas::ConsolidatedNetWorthDomain;
+use zhang_core::workspace::consolidated_net_worth;
+
+use crate::request::NetWorthRequest;
+use crate::response::{LedgerResponse, ResponseWrapper, SuccessWrapper};
+use crate::{ApiResult, LedgerStates};
+
+#[utoipa::path(
+    get,
+    path = "/api/ledgers",
+    tag = "ledger",
+    responses((status = 200, description = "OK", body = SuccessWrapper<Vec<LedgerResponse>>))
+)]
+pub async fn get_ledgers(ledgers: State<LedgerStates>) -> ApiResult<Vec<LedgerResponse>> {
+    let mut ret = vec![];
+    for (idx, (name, ledger)) in ledgers.iter().enumerate() {
+        let ledger = ledger.read().await;
+        let mut operations = ledger.operations();
+        ret.push(LedgerResponse {
+            name: name.clone(),
+            title: operations.option::<String>("title")?,
+            is_default: idx == 0,
+            error_count: operations.errors()?.len(),
+        });
+    }
+    ResponseWrapper::json(ret)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/ledgers/net-worth",
+    tag = "ledger",
+    params(NetWorthRequest),
+    responses((status = 200, description = "OK", body = SuccessWrapper<ConsolidatedNetWorthDomain>))
+)]
+pub async fn get_consolidated_net_worth(ledgers: State<LedgerStates>, params: Query<NetWorthRequest>) -> ApiResult<ConsolidatedNetWorthDomain> {
+    let mut guards = vec![];
+    for (name, ledger) in ledgers.iter() {
+        guards.push((name.as_str(), ledger.read().await));
+    }
+    let (_, default_ledger) = guards.first().expect("server should serve at least one ledger");
+    let date = params
+        .date
+        .unwrap_or_else(Utc::now)
+        .with_timezone(&default_ledger.options.timezone)
+        .naive_local();
+    let currency = params.currency.clone().unwrap_or_else(|| default_ledger.options.operating_currency.clone());
+
+    let net_worth = consolidated_net_worth(guards.iter().map(|(name, ledger)| (*name, &**ledger)), date, currency)?;
+    ResponseWrapper::json(net_worth)
+}
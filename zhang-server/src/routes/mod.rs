@@ -2,8 +2,12 @@ pub mod account;
 pub mod budget;
 pub mod commodity;
 pub mod common;
+pub mod directive;
 pub mod document;
+pub mod export;
 pub mod file;
+pub mod ledger;
+pub mod query;
 pub mod statistics;
 pub mod transaction;
 
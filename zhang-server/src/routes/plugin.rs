@@ -1,16 +1,24 @@
 use std::collections::HashMap;
-use std::sync::Arc;
 
-use axum::extract::State;
+use axum::extract::Path;
+use indexmap::IndexMap;
 use itertools::Itertools;
-use tokio::sync::RwLock;
-use zhang_core::ledger::Ledger;
+use serde_json::Value;
 use zhang_core::plugin::PluginType;
 
-use crate::response::{PluginResponse, ResponseWrapper};
+use crate::openapi::LedgerHeader;
+use crate::response::{PluginResponse, ResponseWrapper, SuccessWrapper};
+use crate::state::SelectedLedger;
 use crate::ApiResult;
 
-pub async fn plugin_list(ledger: State<Arc<RwLock<Ledger>>>) -> ApiResult<Vec<PluginResponse>> {
+#[utoipa::path(
+    get,
+    path = "/api/plugins",
+    tag = "plugin",
+    params(LedgerHeader),
+    responses((status = 200, description = "OK", body = SuccessWrapper<Vec<PluginResponse>>))
+)]
+pub async fn plugin_list(ledger: SelectedLedger) -> ApiResult<Vec<PluginResponse>> {
     let store = ledger.read().await;
 
     let mut grouped_plugins: HashMap<(String, String), Vec<PluginType>> = HashMap::default();
@@ -22,6 +30,9 @@ pub async fn plugin_list(ledger: State<Arc<RwLock<Ledger>>>) -> ApiResult<Vec<Pl
         .map(|it| (it, PluginType::Processor))
         .chain(store.plugins.mappers.iter().map(|it| (it, PluginType::Mapper)))
         .chain(store.plugins.routers.iter().map(|it| (it, PluginType::Router)))
+        .chain(store.plugins.transaction_hooks.iter().map(|it| (it, PluginType::TransactionHook)))
+        .chain(store.plugins.validators.iter().map(|it| (it, PluginType::Validator)))
+        .chain(store.plugins.custom_directives.iter().map(|it| (it, PluginType::CustomDirective)))
     {
         grouped_plugins
             .entry((plugin.name.to_owned(), plugin.version.to_owned()))
@@ -39,3 +50,18 @@ pub async fn plugin_list(ledger: State<Arc<RwLock<Ledger>>>) -> ApiResult<Vec<Pl
         .collect_vec();
     ResponseWrapper::json(ret)
 }
+
+#[utoipa::path(
+    get,
+    path = "/api/plugins/{plugin_name}/data",
+    tag = "plugin",
+    params(("plugin_name" = String, Path), LedgerHeader),
+    responses((status = 200, description = "OK", body = SuccessWrapper<Value>))
+)]
+pub async fn plugin_data(ledger: SelectedLedger, paths: Path<(String,)>) -> ApiResult<IndexMap<String, Value>> {
+    let plugin_name = paths.0 .0;
+    let ledger = ledger.read().await;
+    let operations = ledger.operations();
+
+    ResponseWrapper::json(operations.plugin_data(plugin_name)?)
+}
@@ -0,0 +1,21 @@
+use axum::extract::Query;
+use zhang_core::query::{execute, QueryResult};
+
+use crate::openapi::LedgerHeader;
+use crate::request::QueryRequest;
+use crate::response::{ResponseWrapper, SuccessWrapper};
+use crate::state::SelectedLedger;
+use crate::ApiResult;
+
+#[utoipa::path(
+    get,
+    path = "/api/query",
+    tag = "query",
+    params(LedgerHeader, QueryRequest),
+    responses((status = 200, description = "OK", body = SuccessWrapper<QueryResult>))
+)]
+pub async fn get_query_result(ledger: SelectedLedger, params: Query<QueryRequest>) -> ApiResult<QueryResult> {
+    let ledger = ledger.read().await;
+    let operations = ledger.operations();
+    ResponseWrapper::json(execute(&operations, &params.query)?)
+}
@@ -1,22 +1,30 @@
 use std::collections::HashMap;
 use std::str::FromStr;
-use std::sync::Arc;
 
-use axum::extract::{Path, Query, State};
+use axum::extract::{Path, Query};
 use chrono::Utc;
 use itertools::Itertools;
-use tokio::sync::RwLock;
 use zhang_ast::amount::Amount;
 use zhang_ast::{Account, AccountType, Flag};
-use zhang_core::ledger::Ledger;
+use zhang_core::domains::schemas::{BalanceInterval, TaxReportDomain, TrialBalanceDomain};
 use zhang_core::utils::calculable::Calculable;
 use zhang_core::utils::date_range::NaiveDateRange;
 
-use crate::request::{StatisticGraphRequest, StatisticRequest};
-use crate::response::{ReportRankItemResponse, ResponseWrapper, StatisticGraphResponse, StatisticRankResponse, StatisticSummaryResponse};
+use crate::error::ServerError;
+use crate::openapi::LedgerHeader;
+use crate::request::{StatisticGraphRequest, StatisticRequest, TaxReportRequest, TrialBalanceRequest};
+use crate::response::{ReportRankItemResponse, ResponseWrapper, StatisticGraphResponse, StatisticRankResponse, StatisticSummaryResponse, SuccessWrapper};
+use crate::state::SelectedLedger;
 use crate::ApiResult;
 
-pub async fn get_statistic_summary(ledger: State<Arc<RwLock<Ledger>>>, params: Query<StatisticRequest>) -> ApiResult<StatisticSummaryResponse> {
+#[utoipa::path(
+    get,
+    path = "/api/statistic/summary",
+    tag = "statistic",
+    params(LedgerHeader, StatisticRequest),
+    responses((status = 200, description = "OK", body = SuccessWrapper<StatisticSummaryResponse>))
+)]
+pub async fn get_statistic_summary(ledger: SelectedLedger, params: Query<StatisticRequest>) -> ApiResult<StatisticSummaryResponse> {
     let ledger = ledger.read().await;
     let timezone = &ledger.options.timezone;
     let mut operations = ledger.operations();
@@ -28,7 +36,7 @@ pub async fn get_statistic_summary(ledger: State<Arc<RwLock<Ledger>>>, params: Q
         let account = Account::from_str(account_name)?;
         if account.account_type == AccountType::Assets || account.account_type == AccountType::Liabilities {
             operations
-                .account_target_date_balance(account_name, params.to)?
+                .account_target_date_balance(account_name, params.to, None)?
                 .into_iter()
                 .for_each(|balance| {
                     balances.push(Amount::new(balance.balance_number, balance.balance_commodity));
@@ -42,7 +50,7 @@ pub async fn get_statistic_summary(ledger: State<Arc<RwLock<Ledger>>>, params: Q
         let account = Account::from_str(account_name)?;
         if account.account_type == AccountType::Liabilities {
             operations
-                .account_target_date_balance(account_name, params.to)?
+                .account_target_date_balance(account_name, params.to, None)?
                 .into_iter()
                 .for_each(|balance| {
                     liability_amounts.push(Amount::new(balance.balance_number, balance.balance_commodity));
@@ -55,8 +63,8 @@ pub async fn get_statistic_summary(ledger: State<Arc<RwLock<Ledger>>>, params: Q
         .read()
         .postings
         .iter()
-        .filter(|posting| posting.trx_datetime.ge(&params.from))
-        .filter(|posting| posting.trx_datetime.le(&params.to))
+        .filter(|posting| posting.datetime.ge(&params.from))
+        .filter(|posting| posting.datetime.le(&params.to))
         .filter(|posting| posting.account.account_type == AccountType::Income)
         .map(|posting| posting.inferred_amount.clone())
         .collect_vec();
@@ -67,8 +75,8 @@ pub async fn get_statistic_summary(ledger: State<Arc<RwLock<Ledger>>>, params: Q
         .read()
         .postings
         .iter()
-        .filter(|posting| posting.trx_datetime.ge(&params.from))
-        .filter(|posting| posting.trx_datetime.le(&params.to))
+        .filter(|posting| posting.datetime.ge(&params.from))
+        .filter(|posting| posting.datetime.le(&params.to))
         .filter(|posting| posting.account.account_type == AccountType::Expenses)
         .map(|posting| posting.inferred_amount.clone())
         .collect_vec();
@@ -79,6 +87,7 @@ pub async fn get_statistic_summary(ledger: State<Arc<RwLock<Ledger>>>, params: Q
         .transactions
         .values()
         .filter(|trx| trx.flag != Flag::BalanceCheck || trx.flag != Flag::BalancePad)
+        .filter(|trx| trx.flag != Flag::Void)
         .filter(|trx| trx.datetime.ge(&params.from))
         .filter(|trx| trx.datetime.le(&params.to))
         .count();
@@ -93,7 +102,14 @@ pub async fn get_statistic_summary(ledger: State<Arc<RwLock<Ledger>>>, params: Q
         transaction_number: trx_number as i64,
     })
 }
-pub async fn get_statistic_graph(ledger: State<Arc<RwLock<Ledger>>>, params: Query<StatisticGraphRequest>) -> ApiResult<StatisticGraphResponse> {
+#[utoipa::path(
+    get,
+    path = "/api/statistic/graph",
+    tag = "statistic",
+    params(LedgerHeader, StatisticGraphRequest),
+    responses((status = 200, description = "OK", body = SuccessWrapper<StatisticGraphResponse>))
+)]
+pub async fn get_statistic_graph(ledger: SelectedLedger, params: Query<StatisticGraphRequest>) -> ApiResult<StatisticGraphResponse> {
     let ledger = ledger.read().await;
     let timezone = &ledger.options.timezone;
     let mut operations = ledger.operations();
@@ -108,7 +124,7 @@ pub async fn get_statistic_graph(ledger: State<Arc<RwLock<Ledger>>>, params: Que
             let account = Account::from_str(account_name)?;
             if account.account_type == AccountType::Assets || account.account_type == AccountType::Liabilities {
                 operations
-                    .account_target_date_balance(account_name, date.and_hms_opt(23, 59, 59).unwrap().and_local_timezone(Utc).unwrap())?
+                    .account_target_date_balance(account_name, date.and_hms_opt(23, 59, 59).unwrap().and_local_timezone(Utc).unwrap(), None)?
                     .into_iter()
                     .for_each(|balance| {
                         balances.push(Amount::new(balance.balance_number, balance.balance_commodity));
@@ -123,7 +139,7 @@ pub async fn get_statistic_graph(ledger: State<Arc<RwLock<Ledger>>>, params: Que
     let postings = operations.dated_journals(params.from, params.to)?;
 
     for posting in postings {
-        let date = posting.trx_datetime.naive_local().date();
+        let date = posting.datetime.naive_local().date();
         let account_type_store = dated_change.entry(date).or_insert_with(HashMap::new);
         let currency_store = account_type_store.entry(posting.account.account_type).or_insert_with(Vec::new);
         currency_store.push(posting.inferred_amount);
@@ -148,8 +164,15 @@ pub async fn get_statistic_graph(ledger: State<Arc<RwLock<Ledger>>>, params: Que
     })
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/statistic/{account_type}",
+    tag = "statistic",
+    params(("account_type" = AccountType, Path), LedgerHeader, StatisticRequest),
+    responses((status = 200, description = "OK", body = SuccessWrapper<StatisticRankResponse>))
+)]
 pub async fn get_statistic_rank_detail_by_account_type(
-    ledger: State<Arc<RwLock<Ledger>>>, paths: Path<(String,)>, params: Query<StatisticRequest>,
+    ledger: SelectedLedger, paths: Path<(String,)>, params: Query<StatisticRequest>,
 ) -> ApiResult<StatisticRankResponse> {
     let account_type = AccountType::from_str(&paths.0 .0)?;
     let ledger = ledger.read().await;
@@ -192,3 +215,42 @@ pub async fn get_statistic_rank_detail_by_account_type(
         top_transactions,
     })
 }
+
+#[utoipa::path(
+    get,
+    path = "/api/statistic/trial-balance",
+    tag = "statistic",
+    params(LedgerHeader, TrialBalanceRequest),
+    responses((status = 200, description = "OK", body = SuccessWrapper<TrialBalanceDomain>))
+)]
+pub async fn get_trial_balance(ledger: SelectedLedger, params: Query<TrialBalanceRequest>) -> ApiResult<TrialBalanceDomain> {
+    let ledger = ledger.read().await;
+    let operations = ledger.operations();
+    let date = params.date.unwrap_or_else(Utc::now);
+    let trial_balance = match (&params.entity, params.consolidated.unwrap_or(false)) {
+        (Some(entity), _) => operations.entity_trial_balance(date, entity)?,
+        (None, true) => operations.consolidated_trial_balance(date)?,
+        (None, false) => operations.trial_balance(date)?,
+    };
+    ResponseWrapper::json(trial_balance)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/statistic/tax",
+    tag = "statistic",
+    params(LedgerHeader, TaxReportRequest),
+    responses((status = 200, description = "OK", body = SuccessWrapper<Vec<TaxReportDomain>>))
+)]
+pub async fn get_tax_report(ledger: SelectedLedger, params: Query<TaxReportRequest>) -> ApiResult<Vec<TaxReportDomain>> {
+    let interval = match params.interval.as_deref() {
+        Some(interval) => BalanceInterval::from_str(interval).map_err(|_| ServerError::BadRequest(format!("invalid interval: {}", interval)))?,
+        None => BalanceInterval::Month,
+    };
+    let ledger = ledger.read().await;
+    let timezone = &ledger.options.timezone;
+    let report = ledger
+        .operations()
+        .tax_report(interval, params.from.with_timezone(timezone), params.to.with_timezone(timezone))?;
+    ResponseWrapper::json(report)
+}
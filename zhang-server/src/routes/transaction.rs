@@ -6,26 +6,37 @@ use axum::Json;
 use indexmap::IndexSet;
 use itertools::Itertools;
 use log::info;
-use tokio::sync::RwLock;
+use serde_json::Value;
 use uuid::Uuid;
 use zhang_ast::amount::Amount;
 use zhang_ast::error::ErrorKind;
-use zhang_ast::{Account, Date, Directive, Flag, Meta, Posting, SpanInfo, Transaction, ZhangString};
+use zhang_ast::{Account, Date, Directive, Flag, Meta, Posting, PostingType, Transaction, ZhangString};
 use zhang_core::constants::TXN_ID;
 use zhang_core::domains::schemas::MetaType;
-use zhang_core::ledger::Ledger;
-use zhang_core::store::TransactionDomain;
+use zhang_core::template;
 use zhang_core::utils::string_::{escape_with_quote, StringExt};
 
-use crate::request::{CreateTransactionRequest, JournalRequest};
+use crate::auth::{sign_directives, CurrentUser};
+use crate::error::ServerError;
+use crate::openapi::LedgerHeader;
+use crate::request::{CreateTransactionRequest, FilesUploadForm, InstantiateTemplateRequest, JournalRequest};
 use crate::response::{
-    InfoForNewTransaction, JournalBalanceCheckItemResponse, JournalBalancePadItemResponse, JournalItemResponse, JournalTransactionItemResponse,
-    JournalTransactionPostingResponse, Pageable, ResponseWrapper,
+    CreatedDirectiveResponse, InfoForNewTransaction, JournalBalanceCheckItemResponse, JournalBalancePadItemResponse, JournalItemResponse,
+    JournalTransactionItemResponse, JournalTransactionPostingResponse, Pageable, ResponseWrapper, SuccessWrapper, TemplateResponse,
 };
+use crate::state::SelectedLedger;
+use crate::util::document_path;
 use crate::{ApiResult, ReloadSender};
 
 // todo rename api
-pub async fn get_info_for_new_transactions(ledger: State<Arc<RwLock<Ledger>>>) -> ApiResult<InfoForNewTransaction> {
+#[utoipa::path(
+    get,
+    path = "/api/for-new-transaction",
+    tag = "transaction",
+    params(LedgerHeader),
+    responses((status = 200, description = "OK", body = SuccessWrapper<InfoForNewTransaction>))
+)]
+pub async fn get_info_for_new_transactions(ledger: SelectedLedger) -> ApiResult<InfoForNewTransaction> {
     let guard = ledger.read().await;
     let mut operations = guard.operations();
 
@@ -38,26 +49,20 @@ pub async fn get_info_for_new_transactions(ledger: State<Arc<RwLock<Ledger>>>) -
     })
 }
 
-pub async fn get_journals(ledger: State<Arc<RwLock<Ledger>>>, params: Query<JournalRequest>) -> ApiResult<Pageable<JournalItemResponse>> {
+#[utoipa::path(
+    get,
+    path = "/api/journals",
+    tag = "transaction",
+    params(LedgerHeader, JournalRequest),
+    responses((status = 200, description = "OK", body = SuccessWrapper<Pageable<JournalItemResponse>>))
+)]
+pub async fn get_journals(ledger: SelectedLedger, params: Query<JournalRequest>) -> ApiResult<Pageable<JournalItemResponse>> {
     let ledger = ledger.read().await;
     let mut operations = ledger.operations();
     let params = params.0;
 
-    let total_count = operations.transaction_counts()?;
+    let (total_count, journals) = operations.journals(&params.filter(), params.offset() as usize, params.limit() as usize)?;
 
-    let store = operations.read();
-
-    let journals: Vec<TransactionDomain> = store
-        .transactions
-        .values()
-        .filter(|it| params.keyword.as_ref().map(|keyword| it.contains_keyword(keyword)).unwrap_or(true))
-        .sorted_by_key(|it| -it.sequence)
-        .skip(params.offset() as usize)
-        .take(params.limit() as usize)
-        .cloned()
-        .collect_vec();
-
-    drop(store);
     let mut ret = vec![];
     for journal_item in journals {
         let item = match journal_item.flag {
@@ -120,19 +125,29 @@ pub async fn get_journals(ledger: State<Arc<RwLock<Ledger>>>, params: Query<Jour
     ResponseWrapper::json(Pageable::new(total_count as u32, params.page(), params.limit(), ret))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/transactions",
+    tag = "transaction",
+    params(LedgerHeader),
+    request_body = CreateTransactionRequest,
+    responses((status = 200, description = "OK", body = SuccessWrapper<CreatedDirectiveResponse>))
+)]
 pub async fn create_new_transaction(
-    ledger: State<Arc<RwLock<Ledger>>>, reload_sender: State<Arc<ReloadSender>>, Json(payload): Json<CreateTransactionRequest>,
-) -> ApiResult<String> {
+    ledger: SelectedLedger, reload_sender: State<Arc<ReloadSender>>, user: CurrentUser, Json(payload): Json<CreateTransactionRequest>,
+) -> ApiResult<CreatedDirectiveResponse> {
     let ledger = ledger.read().await;
 
     let mut postings = vec![];
     for posting in payload.postings.into_iter() {
         postings.push(Posting {
             flag: None,
+            posting_type: PostingType::Real,
             account: Account::from_str(&posting.account)?,
             units: posting.unit.map(|unit| Amount::new(unit.number, unit.commodity)),
             cost: None,
             cost_date: None,
+            cost_label: None,
             price: None,
             comment: None,
             meta: Default::default(),
@@ -143,9 +158,13 @@ pub async fn create_new_transaction(
     for meta in payload.metas {
         metas.insert(meta.key, meta.value.to_quote());
     }
+    for document in payload.documents {
+        metas.insert("document".to_owned(), document.to_quote());
+    }
     let time = payload.datetime.with_timezone(&ledger.options.timezone).naive_local();
-    let trx = Directive::Transaction(Transaction {
+    let trx = Transaction {
         date: Date::Datetime(time),
+        auxiliary_date: None,
         flag: payload.flag.map(|it| it.into()).or(Some(Flag::Okay)),
         payee: Some(payload.payee.to_quote()),
         narration: payload.narration.map(|it| it.to_quote()),
@@ -153,21 +172,91 @@ pub async fn create_new_transaction(
         links: IndexSet::from_iter(payload.links.into_iter()),
         postings,
         meta: metas,
-    });
+    };
 
-    ledger.data_source.async_append(&ledger, vec![trx]).await?;
+    // the transaction is checked against the ledger before it is written, so that the invalid one never reaches the files
+    let issues = ledger.operations().validate_transaction(&trx)?;
+    if !issues.is_empty() {
+        return Err(ServerError::BadRequest(format!("invalid transaction: {}", issues.iter().join(", "))));
+    }
+
+    let mut directives = vec![Directive::Transaction(trx)];
+    sign_directives(&user, &mut directives);
+    let mut spans = ledger.data_source.async_append(&ledger, directives).await?;
     reload_sender.reload();
-    ResponseWrapper::json("Ok".to_string())
+    let span = spans.pop().expect("the span of appended transaction");
+    ResponseWrapper::json(CreatedDirectiveResponse::from(span))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/templates",
+    tag = "transaction",
+    params(LedgerHeader),
+    responses((status = 200, description = "OK", body = SuccessWrapper<Vec<TemplateResponse>>))
+)]
+pub async fn get_templates(ledger: SelectedLedger) -> ApiResult<Vec<TemplateResponse>> {
+    let ledger = ledger.read().await;
+    let ret = template::templates(&ledger)
+        .into_iter()
+        .map(|template| TemplateResponse {
+            name: template.name.clone(),
+            payee: template.payee.as_ref().map(|it| it.as_str().to_owned()),
+            narration: template.narration.as_ref().map(|it| it.as_str().to_owned()),
+            placeholders: template.placeholders(),
+        })
+        .collect_vec();
+    ResponseWrapper::json(ret)
+}
+
+/// append the transaction instantiated from template with the values of its placeholders
+#[utoipa::path(
+    post,
+    path = "/api/templates/{template_name}/transactions",
+    tag = "transaction",
+    params(("template_name" = String, Path), LedgerHeader),
+    request_body = InstantiateTemplateRequest,
+    responses((status = 200, description = "OK", body = SuccessWrapper<CreatedDirectiveResponse>))
+)]
+pub async fn instantiate_template(
+    ledger: SelectedLedger, reload_sender: State<Arc<ReloadSender>>, user: CurrentUser, paths: Path<(String,)>, Json(payload): Json<InstantiateTemplateRequest>,
+) -> ApiResult<CreatedDirectiveResponse> {
+    let (template_name,) = paths.0;
+    let ledger = ledger.read().await;
+    let Some(template) = template::find_template(&ledger, &template_name) else {
+        return ResponseWrapper::not_found();
+    };
+    let date = payload.date.map(Date::Date).unwrap_or_else(|| Date::now(&ledger.options.timezone));
+    let trx = template::instantiate(template, date, &payload.values).map_err(|e| ServerError::BadRequest(e.to_string()))?;
+
+    let issues = ledger.operations().validate_transaction(&trx)?;
+    if !issues.is_empty() {
+        return Err(ServerError::BadRequest(format!("invalid transaction: {}", issues.iter().join(", "))));
+    }
+
+    let mut directives = vec![Directive::Transaction(trx)];
+    sign_directives(&user, &mut directives);
+    let mut spans = ledger.data_source.async_append(&ledger, directives).await?;
+    reload_sender.reload();
+    let span = spans.pop().expect("the span of appended transaction");
+    ResponseWrapper::json(CreatedDirectiveResponse::from(span))
 }
 
 // todo(refact): use exporter to update transaction
+#[utoipa::path(
+    post,
+    path = "/api/transactions/{transaction_id}/documents",
+    tag = "transaction",
+    params(("transaction_id" = Uuid, Path), LedgerHeader),
+    request_body(content = FilesUploadForm, content_type = "multipart/form-data"),
+    responses((status = 200, description = "OK", body = SuccessWrapper<String>))
+)]
 pub async fn upload_transaction_document(
-    ledger: State<Arc<RwLock<Ledger>>>, reload_sender: State<Arc<ReloadSender>>, path: Path<(String,)>, mut multipart: Multipart,
+    ledger: SelectedLedger, reload_sender: State<Arc<ReloadSender>>, path: Path<(String,)>, mut multipart: Multipart,
 ) -> ApiResult<String> {
     let transaction_id = Uuid::from_str(&path.0 .0).expect("invalid txn id");
     let ledger = ledger.read().await;
     let mut operations = ledger.operations();
-    let entry = &ledger.entry.0;
     let mut documents = vec![];
 
     let span_info = operations.transaction_span(&transaction_id)?;
@@ -180,21 +269,17 @@ pub async fn upload_transaction_document(
         let file_name = field.file_name().unwrap().to_string();
         let _content_type = field.content_type().unwrap().to_string();
 
-        let v4 = Uuid::new_v4();
-        let buf = entry.join("attachments").join(v4.to_string()).join(&file_name);
-        let striped_buf = buf.strip_prefix(entry).unwrap();
-        let striped_path_string = striped_buf.to_string_lossy().to_string();
-        info!("uploading document `{}`(id={}) to transaction {}", file_name, &v4.to_string(), &transaction_id);
         let content_buf = field.bytes().await.unwrap();
-
-        ledger.data_source.async_save(&ledger, striped_path_string, &content_buf).await?;
-
-        let path = match buf.strip_prefix(entry) {
-            Ok(relative_path) => relative_path.to_str().unwrap(),
-            Err(_) => buf.to_str().unwrap(),
+        let date = Date::now(&ledger.options.timezone);
+        let Some(path) = document_path(&ledger, &file_name, &content_buf, date.naive_date()).await? else {
+            return ResponseWrapper::bad_request();
         };
+        let striped_path_string = path.to_string_lossy().to_string();
+        info!("uploading document `{}` to transaction {}", striped_path_string, &transaction_id);
+
+        ledger.data_source.async_save(&ledger, striped_path_string.clone(), &content_buf).await?;
 
-        documents.push(ZhangString::QuoteString(path.to_string()));
+        documents.push(ZhangString::QuoteString(striped_path_string));
     }
 
     let metas_content = documents
@@ -211,8 +296,16 @@ pub async fn upload_transaction_document(
     ResponseWrapper::json("Ok".to_string())
 }
 
+#[utoipa::path(
+    put,
+    path = "/api/transactions/{transaction_id}",
+    tag = "transaction",
+    params(("transaction_id" = Uuid, Path), LedgerHeader),
+    request_body = CreateTransactionRequest,
+    responses((status = 200, description = "OK", body = SuccessWrapper<Value>))
+)]
 pub async fn update_single_transaction(
-    ledger: State<Arc<RwLock<Ledger>>>, reload_sender: State<Arc<ReloadSender>>, path: Path<(String,)>, Json(payload): Json<CreateTransactionRequest>,
+    ledger: SelectedLedger, reload_sender: State<Arc<ReloadSender>>, path: Path<(String,)>, Json(payload): Json<CreateTransactionRequest>,
 ) -> ApiResult<()> {
     let Ok(transaction_id) = Uuid::from_str(&path.0 .0) else {
         return ResponseWrapper::bad_request();
@@ -220,8 +313,7 @@ pub async fn update_single_transaction(
     let ledger = ledger.read().await;
     let mut operations = ledger.operations();
 
-    let span_info = operations.transaction_span(&transaction_id)?;
-    let Some(span_info) = span_info else {
+    let Some(transaction) = operations.single_transaction(&transaction_id)? else {
         return ResponseWrapper::bad_request();
     };
 
@@ -229,10 +321,12 @@ pub async fn update_single_transaction(
     for posting in payload.postings.into_iter() {
         postings.push(Posting {
             flag: None,
+            posting_type: PostingType::Real,
             account: Account::from_str(&posting.account)?,
             units: posting.unit.map(|unit| Amount::new(unit.number, unit.commodity)),
             cost: None,
             cost_date: None,
+            cost_label: None,
             price: None,
             comment: None,
             meta: Default::default(),
@@ -243,8 +337,18 @@ pub async fn update_single_transaction(
         metas.insert(meta.key, meta.value.to_quote());
     }
     let time = payload.datetime.with_timezone(&ledger.options.timezone).naive_local();
-    let trx = Directive::Transaction(Transaction {
-        date: Date::Datetime(time),
+    // the dates of the edited transaction are kept, only the one displayed by option `transaction_date` is replaced
+    let (date, auxiliary_date) = ledger
+        .directives
+        .iter()
+        .find_map(|directive| match &directive.data {
+            Directive::Transaction(trx) if directive.span == transaction.span => Some((trx.date.clone(), trx.auxiliary_date.clone())),
+            _ => None,
+        })
+        .unwrap_or((Date::Datetime(time), None));
+    let mut trx = Transaction {
+        date,
+        auxiliary_date,
         flag: payload.flag.map(|it| it.into()).or(Some(Flag::Okay)),
         payee: Some(payload.payee.to_quote()),
         narration: payload.narration.map(|it| it.to_quote()),
@@ -252,15 +356,35 @@ pub async fn update_single_transaction(
         links: IndexSet::from_iter(payload.links.into_iter()),
         postings,
         meta: metas,
-    });
-    let txn_content = ledger.data_source.export(trx)?;
-    let trx_content = String::from_utf8_lossy(&txn_content);
-    let source_file_path = span_info.source_file.to_string_lossy().to_string();
+    };
+    ledger.options.transaction_date.set(&mut trx, Date::Datetime(time));
+    ledger
+        .data_source
+        .async_update_directive(&ledger, &transaction.span, Directive::Transaction(trx))
+        .await?;
+    reload_sender.reload();
+    ResponseWrapper::json(())
+}
 
-    let mut content = String::from_utf8(ledger.data_source.async_get(source_file_path.clone()).await?).unwrap();
-    content.replace_by_span(&SpanInfo::simple(span_info.span_start, span_info.span_end), &trx_content);
+#[utoipa::path(
+    delete,
+    path = "/api/transactions/{transaction_id}",
+    tag = "transaction",
+    params(("transaction_id" = Uuid, Path), LedgerHeader),
+    responses((status = 200, description = "OK", body = SuccessWrapper<Value>))
+)]
+pub async fn delete_single_transaction(ledger: SelectedLedger, reload_sender: State<Arc<ReloadSender>>, path: Path<(String,)>) -> ApiResult<()> {
+    let Ok(transaction_id) = Uuid::from_str(&path.0 .0) else {
+        return ResponseWrapper::bad_request();
+    };
+    let ledger = ledger.read().await;
+    let mut operations = ledger.operations();
 
-    ledger.data_source.async_save(&ledger, source_file_path, content.as_bytes()).await?;
+    let Some(transaction) = operations.single_transaction(&transaction_id)? else {
+        return ResponseWrapper::bad_request();
+    };
+
+    ledger.data_source.async_delete_directive(&ledger, &transaction.span).await?;
     reload_sender.reload();
     ResponseWrapper::json(())
 }
@@ -1,22 +1,28 @@
+use std::ops::Deref;
 use std::sync::Arc;
 
-use axum::extract::FromRef;
-use tokio::sync::RwLock;
-use zhang_core::ledger::Ledger;
+use axum::async_trait;
+use axum::extract::{FromRef, FromRequestParts, Query};
+use axum::http::request::Parts;
+use serde::Deserialize;
 
 use crate::broadcast::Broadcaster;
-use crate::ReloadSender;
+use crate::error::ServerError;
+use crate::{LedgerState, LedgerStates, ReloadSender};
+
+/// the header to select the ledger which request is made to
+pub const LEDGER_HEADER: &str = "x-zhang-ledger";
 
 #[derive(Clone)]
 pub struct AppState {
-    pub ledger: Arc<RwLock<Ledger>>,
+    pub ledgers: LedgerStates,
     pub broadcaster: Arc<Broadcaster>,
     pub reload_sender: Arc<ReloadSender>,
 }
 
-impl FromRef<AppState> for Arc<RwLock<Ledger>> {
+impl FromRef<AppState> for LedgerStates {
     fn from_ref(input: &AppState) -> Self {
-        input.ledger.clone()
+        input.ledgers.clone()
     }
 }
 
@@ -30,3 +36,39 @@ impl FromRef<AppState> for Arc<ReloadSender> {
         input.reload_sender.clone()
     }
 }
+
+#[derive(Deserialize)]
+struct LedgerSelection {
+    ledger: Option<String>,
+}
+
+/// the ledger which request is made to along with its name, it is selected by the header `X-Zhang-Ledger` or the query
+/// parameter `ledger`, the default ledger is used if both are absent
+pub struct SelectedLedger(pub LedgerState, pub String);
+
+impl Deref for SelectedLedger {
+    type Target = LedgerState;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for SelectedLedger {
+    type Rejection = ServerError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let name = parts
+            .headers
+            .get(LEDGER_HEADER)
+            .and_then(|it| it.to_str().ok())
+            .map(|it| it.to_owned())
+            .or_else(|| Query::<LedgerSelection>::try_from_uri(&parts.uri).ok().and_then(|it| it.0.ledger));
+        let (name, ledger) = match name {
+            Some(name) => state.ledgers.get_key_value(&name).ok_or(ServerError::LedgerNotFound(name))?,
+            None => state.ledgers.first().expect("server should serve at least one ledger"),
+        };
+        Ok(SelectedLedger(ledger.clone(), name.clone()))
+    }
+}
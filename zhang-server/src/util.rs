@@ -1,10 +1,14 @@
 use std::future::Future;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
 use log::info;
+use uuid::Uuid;
 use zhang_core::domains::schemas::{AccountBalanceDomain, AccountDailyBalanceDomain};
+use zhang_core::ledger::Ledger;
+use zhang_core::options::DocumentNaming;
 use zhang_core::ZhangResult;
 
 pub trait AmountLike {
@@ -56,3 +60,37 @@ where
     };
     Ok(vec)
 }
+
+/// the path relative to the entry where the uploaded document is stored, following the options `document_directory` and `document_naming`.
+/// only the last component of uploaded file name is kept, `None` if there is no such one.
+/// the dated document is suffixed by a sequence if the file of same name exists on that day, e.g. `20-receipt-1.pdf`
+pub async fn document_path(ledger: &Ledger, file_name: &str, content: &[u8], date: NaiveDate) -> ZhangResult<Option<PathBuf>> {
+    let Some(file_name) = Path::new(file_name).file_name().map(|it| it.to_string_lossy().to_string()) else {
+        return Ok(None);
+    };
+    let directory = PathBuf::from(&ledger.options.document_directory);
+    Ok(Some(match ledger.options.document_naming {
+        DocumentNaming::Uuid => directory.join(Uuid::new_v4().to_string()).join(file_name),
+        DocumentNaming::Hashed => directory.join(&sha256::digest(content)[..16]).join(file_name),
+        DocumentNaming::Dated => {
+            let folder = directory.join(date.format("%Y").to_string()).join(date.format("%m").to_string());
+            let day = date.format("%d");
+            let name = Path::new(&file_name);
+            let stem = name.file_stem().map(|it| it.to_string_lossy().to_string()).unwrap_or_default();
+            let extension = name.extension().map(|it| format!(".{}", it.to_string_lossy())).unwrap_or_default();
+            let mut path = folder.join(format!("{}-{}", day, file_name));
+            let mut sequence = 0;
+            // the missing file is read as empty by some data sources rather than an error
+            while ledger
+                .data_source
+                .async_get(path.to_string_lossy().to_string())
+                .await
+                .is_ok_and(|content| !content.is_empty())
+            {
+                sequence += 1;
+                path = folder.join(format!("{}-{}-{}{}", day, stem, sequence, extension));
+            }
+            path
+        }
+    }))
+}